@@ -0,0 +1,2998 @@
+//! Commit, reveal and transfer construction over plain inputs.
+//!
+//! Nothing in this module reads the index, the database or the node. Callers
+//! gather utxos and inscriptions however they like and get unsigned commits,
+//! signed reveals and unsigned transfers and cancels back. The `wallet
+//! mint`, `wallet mints`, `wallet transfer` and `wallet cancel` subcommands
+//! are thin wrappers around it.
+
+use {
+  super::*,
+  crate::subcommand::wallet::{
+    apply_lock_time, error::BuildError, funding::insufficient_funds, reveal_fee_check::Cpfp,
+    transaction_builder,
+  },
+  bitcoin::{
+    blockdata::{opcodes, script},
+    policy::MAX_STANDARD_TX_WEIGHT,
+    psbt::Psbt,
+    schnorr::{TapTweak, TweakedKeyPair, TweakedPublicKey, UntweakedKeyPair},
+    secp256k1::{
+      self, constants::SCHNORR_SIGNATURE_SIZE, rand, schnorr::Signature, Secp256k1, XOnlyPublicKey,
+    },
+    util::sighash::{Prevouts, SighashCache},
+    util::taproot::{
+      ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder, TAPROOT_CONTROL_BASE_SIZE,
+    },
+    AddressType, PackedLockTime, SchnorrSighashType, Witness,
+  },
+  std::collections::BTreeSet,
+};
+
+/// Everything a commit and its reveals are built from.
+#[derive(Debug, Clone)]
+pub struct CommitParams {
+  /// Address type of the funding utxos, used to estimate their witnesses.
+  pub input_type: AddressType,
+  pub network: Network,
+  /// Sats the commit must spend first. When empty, the first utxo that
+  /// holds no inscription is used.
+  pub satpoints: Vec<SatPoint>,
+  /// Inscriptions held by `utxos`, which are never spent as cardinals.
+  pub inscriptions: BTreeMap<SatPoint, InscriptionId>,
+  pub utxos: BTreeMap<OutPoint, Amount>,
+  pub change: [Address; 2],
+  pub destination: Address,
+  /// How many consecutive inscriptions each reveal packs into its script,
+  /// each pointed at a postage output of its own. One inscription per
+  /// reveal when empty.
+  pub groups: Vec<usize>,
+  pub commit_fee_rate: FeeRate,
+  pub reveal_fee_rate: FeeRate,
+  /// Lets reveals exceed MAX_STANDARD_TX_WEIGHT, up to this weight, for
+  /// builds allowed to be non-standard.
+  pub max_nonstandard_weight: Option<u64>,
+  pub service_address: Address,
+  /// Charged per inscription and paid by the first reveal. Dropped when the
+  /// total would be dust.
+  pub service_fee: Amount,
+  /// Charged once on top of the per inscription service fee.
+  pub additional_service_fee: Amount,
+  /// Value of each inscribed output.
+  pub target_postage: Amount,
+  /// Destination and postage of each inscription, in place of `destination`
+  /// and `target_postage` when not empty.
+  pub recipients: Vec<(Address, Amount)>,
+  /// Spent by the reveal so the inscription is its child. Only for a single
+  /// inscription, which must carry the parent tag.
+  pub parent: Option<Parent>,
+  /// Where the reveal key and signature randomness come from.
+  pub key_source: KeySource,
+}
+
+/// An inscription a reveal spends and sends on, making the new inscription
+/// its child. Indexers only look at the first input for new inscriptions,
+/// so the reveal spends the commit output, then the parent, then a second
+/// commit output paying the reveal fee. The fee comes off the last input,
+/// which keeps it from eating the parent's sats.
+#[derive(Debug, Clone)]
+pub struct Parent {
+  pub id: InscriptionId,
+  pub satpoint: SatPoint,
+  /// The output holding the parent, which the reveal spends whole.
+  pub output: TxOut,
+  /// Where the reveal sends the parent.
+  pub destination: Address,
+}
+
+/// The randomness a build consumes. Production builds draw it fresh,
+/// fixtures pin it so that built reveals are byte-exact.
+#[derive(Debug, Clone, Copy)]
+pub enum KeySource {
+  /// A fresh reveal key and fresh signature randomness.
+  Random,
+  /// Sign reveals with this key, with fresh signature randomness.
+  Key(UntweakedKeyPair),
+  /// Sign reveals with this key and no auxiliary randomness, so the same
+  /// inputs always produce the same signatures.
+  Fixed(UntweakedKeyPair),
+}
+
+impl KeySource {
+  fn key_pair(self, secp256k1: &Secp256k1<secp256k1::All>) -> UntweakedKeyPair {
+    match self {
+      KeySource::Random => UntweakedKeyPair::new(secp256k1, &mut rand::thread_rng()),
+      KeySource::Key(key_pair) | KeySource::Fixed(key_pair) => key_pair,
+    }
+  }
+
+  fn sign(
+    self,
+    secp256k1: &Secp256k1<secp256k1::All>,
+    message: &secp256k1::Message,
+    key_pair: &UntweakedKeyPair,
+  ) -> Signature {
+    match self {
+      KeySource::Random | KeySource::Key(_) => secp256k1.sign_schnorr(message, key_pair),
+      KeySource::Fixed(_) => secp256k1.sign_schnorr_no_aux_rand(message, key_pair),
+    }
+  }
+}
+
+/// An unsigned commit, the reveals spending it and the fees they carry.
+#[derive(Debug)]
+pub struct Inscribed {
+  pub commit: Transaction,
+  /// Signed reveals, the i-th spending output i of `commit` and holding the
+  /// i-th group of inscriptions.
+  pub reveals: Vec<Transaction>,
+  /// Keys that can spend each commit output back if its reveal is lost,
+  /// the one paying a parent's reveal fee last.
+  pub recovery_key_pairs: Vec<TweakedKeyPair>,
+  pub service_fee: u64,
+  pub satpoint_fee: u64,
+  /// Fees paid by the reveals, the commit fee is not included.
+  pub network_fee: u64,
+  /// What each of the first `reveals.len()` commit outputs pays for.
+  pub breakdown: Vec<CommitOutputBreakdown>,
+}
+
+/// The parts of a commit output, which sum to its value exactly. The
+/// service fee is only ever carried by output 0 and paid by its reveal.
+/// With a parent, the reveal fee is carried by an output of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CommitOutputBreakdown {
+  pub postage: u64,
+  pub reveal_fee: u64,
+  pub service_fee: u64,
+}
+
+/// Everything a transfer is built from.
+#[derive(Debug, Clone)]
+pub struct TransferParams {
+  pub input_type: AddressType,
+  /// Sats to send, all of them go to `destination`, each with the postage
+  /// it keeps there.
+  pub postages: Vec<(SatPoint, Amount)>,
+  pub inscriptions: BTreeMap<SatPoint, InscriptionId>,
+  pub utxos: BTreeMap<OutPoint, Amount>,
+  pub destination: Address,
+  /// Destination of each of `postages`, in place of `destination` when not
+  /// empty.
+  pub destinations: Vec<Address>,
+  /// Paid to `destination` on top of the postages.
+  pub additional_value: Amount,
+  pub change: [Address; 2],
+  pub fee_rate: FeeRate,
+  pub op_return: Option<String>,
+  pub locktime: Option<u32>,
+  /// Signal BIP-125 replaceability.
+  pub rbf: bool,
+  /// Witness bytes of inputs spent other than by the key path.
+  pub witness_sizes: BTreeMap<OutPoint, usize>,
+}
+
+/// Everything a cancel is built from.
+#[derive(Debug, Clone)]
+pub struct CancelParams {
+  pub input_type: AddressType,
+  /// Outputs to spend back to `source`, each of them must be in `utxos`.
+  pub inputs: Vec<OutPoint>,
+  pub utxos: BTreeMap<OutPoint, Amount>,
+  pub source: Address,
+  /// Paid after the network fee, reduced to whatever the inputs have left.
+  pub service: Option<(Address, Amount)>,
+  pub fee_rate: FeeRate,
+  pub locktime: Option<u32>,
+  /// Signal BIP-125 replaceability.
+  pub rbf: bool,
+  /// Witness bytes of inputs spent other than by the key path.
+  pub witness_sizes: BTreeMap<OutPoint, usize>,
+}
+
+/// Everything a child paying for a stuck parent is built from.
+#[derive(Debug, Clone)]
+pub struct AccelerateParams {
+  /// The stuck transaction, signed.
+  pub parent: Transaction,
+  /// Fee `parent` pays.
+  pub parent_fee: u64,
+  /// Output of `parent` the child spends back to its own script.
+  pub vout: u32,
+  /// Address type of that output, used to estimate its witness.
+  pub input_type: AddressType,
+  /// Effective fee rate of parent and child together.
+  pub fee_rate: FeeRate,
+  /// Signal BIP-125 replaceability.
+  pub rbf: bool,
+}
+
+/// Everything a replacement paying a higher fee is built from.
+#[derive(Debug, Clone)]
+pub struct BumpFeeParams {
+  /// The transaction to replace, signed or not.
+  pub original: Transaction,
+  /// Value of every output `original` spends.
+  pub utxos: BTreeMap<OutPoint, Amount>,
+  /// Address of the change output, the only one the bump is taken from.
+  pub change: Address,
+  pub input_type: AddressType,
+  pub fee_rate: FeeRate,
+}
+
+/// An unsigned replacement, the fee it pays and the fee it replaces.
+#[derive(Debug)]
+pub struct Bumped {
+  pub transaction: Transaction,
+  pub network_fee: u64,
+  pub original_fee: u64,
+}
+
+/// An unsigned child, the fee it pays and the fee rate of the package.
+#[derive(Debug)]
+pub struct Accelerated {
+  pub transaction: Transaction,
+  pub network_fee: u64,
+  pub package_fee_rate: f64,
+}
+
+/// An unsigned cancel, the network fee and the service fee it pays.
+#[derive(Debug)]
+pub struct Cancelled {
+  pub transaction: Transaction,
+  pub network_fee: u64,
+  pub service_fee: u64,
+}
+
+/// Largest OP_RETURN payload bitcoind relays by default, in bytes.
+pub const MAX_OP_RETURN_SIZE: usize = 80;
+
+/// Minimum fee rate bitcoind relays by default, in sat/vB.
+pub const MIN_RELAY_FEE_RATE: f64 = 1.0;
+
+/// Heaviest non-standard reveal built by default, in weight units, a
+/// quarter of a block.
+pub const MAX_NONSTANDARD_WEIGHT: u64 = 1_000_000;
+
+/// Returns the commit and reveal fee rates, each defaulting to `fee_rate`.
+/// Both must be relayable on their own, a reveal cannot lean on its commit.
+pub fn component_fee_rates(
+  fee_rate: FeeRate,
+  commit_fee_rate: Option<FeeRate>,
+  reveal_fee_rate: Option<FeeRate>,
+) -> Result<(FeeRate, FeeRate)> {
+  let commit_fee_rate = commit_fee_rate.unwrap_or(fee_rate);
+  let reveal_fee_rate = reveal_fee_rate.unwrap_or(fee_rate);
+
+  for (component, fee_rate) in [("commit", commit_fee_rate), ("reveal", reveal_fee_rate)] {
+    if fee_rate.0 < MIN_RELAY_FEE_RATE {
+      return Err(
+        BuildError::FeeRateBelowRelayFloor {
+          component,
+          fee_rate: fee_rate.0,
+        }
+        .into(),
+      );
+    }
+  }
+
+  Ok((commit_fee_rate, reveal_fee_rate))
+}
+
+/// Refuses a build whose fees add up to more than `max_total_fee` sats, a
+/// guard against a mistyped fee rate.
+pub fn check_total_fee(
+  service_fee: u64,
+  satpoint_fee: u64,
+  network_fee: u64,
+  max_total_fee: Option<u64>,
+) -> Result<(), BuildError> {
+  match max_total_fee {
+    Some(max) if service_fee + satpoint_fee + network_fee > max => {
+      Err(BuildError::TotalFeeAboveMax {
+        service_fee,
+        satpoint_fee,
+        network_fee,
+        max,
+      })
+    }
+    _ => Ok(()),
+  }
+}
+
+/// `fee_rate` raised by `buffer`, headroom for reveals whose commit may sit
+/// in the mempool while fees rise.
+pub fn buffered_fee_rate(fee_rate: FeeRate, buffer: Option<FeeRate>) -> Result<FeeRate> {
+  match buffer {
+    Some(buffer) => FeeRate::try_from(fee_rate.0 + buffer.0),
+    None => Ok(fee_rate),
+  }
+}
+
+/// The reveal scripts for a set of inscriptions and the commit outputs
+/// that fund their reveals.
+struct Plan {
+  reveal_script: Vec<Script>,
+  control_block: Vec<ControlBlock>,
+  recovery_key_pairs: Vec<TweakedKeyPair>,
+  /// The postage outputs of each reveal.
+  recipients: Vec<Vec<(Address, Amount)>>,
+  service_fee: Amount,
+  breakdown: Vec<CommitOutputBreakdown>,
+  outputs: Vec<(Address, Amount)>,
+  parent: Option<Parent>,
+  /// What the commit output paying the reveal fee holds beyond the fee,
+  /// to keep it above dust. The reveal adds it to the parent's output.
+  parent_padding: Amount,
+}
+
+impl Plan {
+  fn new(
+    params: &CommitParams,
+    secp256k1: &Secp256k1<secp256k1::All>,
+    key_pair: &UntweakedKeyPair,
+    inscriptions: &[Inscription],
+  ) -> Result<Self> {
+    let repeat = inscriptions.len();
+
+    let recipients = if params.recipients.is_empty() {
+      vec![(params.destination.clone(), params.target_postage); repeat]
+    } else if params.recipients.len() == repeat {
+      params.recipients.clone()
+    } else {
+      bail!(
+        "{} recipients given for {repeat} inscriptions",
+        params.recipients.len()
+      );
+    };
+
+    // the reveal's first output holds the postage, a pointer must land in it
+    for (item, (inscription, (_, postage))) in inscriptions.iter().zip(&recipients).enumerate() {
+      if let Some(pointer) = inscription.pointer() {
+        if pointer >= postage.to_sat() {
+          return Err(
+            BuildError::PointerOutOfRange {
+              item,
+              pointer,
+              postage: postage.to_sat(),
+            }
+            .into(),
+          );
+        }
+      }
+    }
+
+    if params.parent.is_some() && repeat != 1 {
+      bail!("a parent can only be given for a single inscription, not {repeat}");
+    }
+
+    let groups = pack(
+      inscriptions.iter().cloned().zip(recipients).collect(),
+      &params.groups,
+    )?;
+
+    let (public_key, _parity) = XOnlyPublicKey::from_keypair(key_pair);
+
+    let mut reveal_script = vec![];
+    let mut control_block = vec![];
+    let mut commit_tx_address = vec![];
+    let mut recovery_key_pairs = vec![];
+
+    for group in &groups {
+      let r = Inscription::append_batch_reveal_script(
+        &group
+          .iter()
+          .map(|(inscription, _)| inscription.clone())
+          .collect::<Vec<Inscription>>(),
+        script::Builder::new()
+          .push_slice(&public_key.serialize())
+          .push_opcode(opcodes::all::OP_CHECKSIG),
+      );
+      let t = TaprootBuilder::new()
+        .add_leaf(0, r.clone())
+        .expect("adding leaf should work")
+        .finalize(secp256k1, public_key)
+        .expect("finalizing taproot builder should work");
+      let c = t
+        .control_block(&(r.clone(), LeafVersion::TapScript))
+        .expect("should compute control block");
+      let ca = Address::p2tr_tweaked(t.output_key(), params.network);
+
+      let rk = key_pair.tap_tweak(secp256k1, t.merkle_root());
+      let (x_only_pub_key, _parity) = rk.to_inner().x_only_public_key();
+      assert_eq!(
+        Address::p2tr_tweaked(
+          TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
+          params.network,
+        ),
+        ca
+      );
+
+      reveal_script.push(r);
+      control_block.push(c);
+      commit_tx_address.push(ca);
+      recovery_key_pairs.push(rk);
+    }
+
+    let mut plan = Self {
+      reveal_script,
+      control_block,
+      recovery_key_pairs,
+      recipients: groups
+        .into_iter()
+        .map(|group| group.into_iter().map(|(_, recipient)| recipient).collect())
+        .collect(),
+      service_fee: service_fee(params, repeat),
+      breakdown: vec![],
+      outputs: vec![],
+      parent: params.parent.clone(),
+      parent_padding: Amount::ZERO,
+    };
+
+    for (i, reveal_output) in plan
+      .reveal_outputs(&params.service_address, false)
+      .into_iter()
+      .enumerate()
+    {
+      let (_, reveal_fee) = build_reveal_transaction(
+        &plan.control_block[i],
+        params.reveal_fee_rate,
+        OutPoint::null(),
+        &plan.extra_inputs(i, OutPoint::null(), params.input_type),
+        reveal_output,
+        &plan.reveal_script[i],
+      );
+      let parts = CommitOutputBreakdown {
+        postage: plan.recipients[i]
+          .iter()
+          .map(|(_, postage)| postage.to_sat())
+          .sum(),
+        reveal_fee: reveal_fee.to_sat(),
+        service_fee: if i == 0 { plan.service_fee.to_sat() } else { 0 },
+      };
+      let value = if plan.parent.is_some() {
+        parts.postage + parts.service_fee
+      } else {
+        parts.postage + parts.reveal_fee + parts.service_fee
+      };
+      plan
+        .outputs
+        .push((commit_tx_address[i].clone(), Amount::from_sat(value)));
+      plan.breakdown.push(parts);
+    }
+
+    // spent by the key path, untweaked by the reveal script so the commit
+    // does not pay one address twice
+    if plan.parent.is_some() {
+      let fee_key_pair = key_pair.tap_tweak(secp256k1, None);
+      let fee_address = Address::p2tr(secp256k1, public_key, None, params.network);
+      let reveal_fee = Amount::from_sat(plan.breakdown[0].reveal_fee);
+      let fee_output = reveal_fee.max(fee_address.script_pubkey().dust_value());
+      plan.parent_padding = fee_output - reveal_fee;
+      plan.outputs.push((fee_address, fee_output));
+      plan.recovery_key_pairs.push(fee_key_pair);
+    }
+
+    Ok(plan)
+  }
+
+  /// Inputs the i-th reveal spends after its commit output, with the size
+  /// of their witnesses: the parent and the commit output at `fee_outpoint`
+  /// paying the fee.
+  fn extra_inputs(
+    &self,
+    i: usize,
+    fee_outpoint: OutPoint,
+    input_type: AddressType,
+  ) -> Vec<(OutPoint, usize)> {
+    match &self.parent {
+      Some(parent) if i == 0 => vec![
+        (
+          parent.satpoint.outpoint,
+          TransactionBuilder::key_path_witness_size(input_type),
+        ),
+        (
+          fee_outpoint,
+          TransactionBuilder::key_path_witness_size(AddressType::P2tr),
+        ),
+      ],
+      _ => Vec::new(),
+    }
+  }
+
+  /// The postage of every reveal, which may differ between recipients.
+  fn satpoint_fee(&self) -> u64 {
+    self
+      .recipients
+      .iter()
+      .flatten()
+      .map(|(_, postage)| postage.to_sat())
+      .sum()
+  }
+
+  fn reveal_outputs(&self, service_address: &Address, values: bool) -> Vec<Vec<TxOut>> {
+    self
+      .recipients
+      .iter()
+      .enumerate()
+      .map(|(i, recipients)| {
+        let mut tx_out = recipients
+          .iter()
+          .map(|(destination, postage)| TxOut {
+            script_pubkey: destination.script_pubkey(),
+            value: if values { postage.to_sat() } else { 0 },
+          })
+          .collect::<Vec<TxOut>>();
+        if i == 0 && self.service_fee.to_sat() > 0 {
+          tx_out.push(TxOut {
+            script_pubkey: service_address.script_pubkey(),
+            value: if values { self.service_fee.to_sat() } else { 0 },
+          });
+        }
+        if let (Some(parent), 0) = (&self.parent, i) {
+          tx_out.push(TxOut {
+            script_pubkey: parent.destination.script_pubkey(),
+            value: if values {
+              parent.output.value + self.parent_padding.to_sat()
+            } else {
+              0
+            },
+          });
+        }
+        tx_out
+      })
+      .collect()
+  }
+}
+
+// the service fee for `repeat` inscriptions, dropped when it would be dust
+fn service_fee(params: &CommitParams, repeat: usize) -> Amount {
+  let service_fee = params.service_fee * (repeat as u64) + params.additional_service_fee;
+  if service_fee.to_sat() < 546 {
+    Amount::ZERO
+  } else {
+    service_fee
+  }
+}
+
+/// Builds a commit with one output per group of inscriptions and a signed
+/// reveal for each of them, all sent to `params.destination` unless
+/// `params.recipients` says otherwise.
+pub fn inscribe(params: CommitParams, inscriptions: Vec<Inscription>) -> Result<Inscribed> {
+  let secp256k1 = Secp256k1::new();
+  let key_pair = params.key_source.key_pair(&secp256k1);
+  let plan = Plan::new(&params, &secp256k1, &key_pair, &inscriptions)?;
+
+  let CommitParams {
+    input_type,
+    satpoints,
+    inscriptions: inscribed,
+    utxos,
+    change,
+    commit_fee_rate,
+    reveal_fee_rate,
+    max_nonstandard_weight,
+    service_address,
+    key_source,
+    ..
+  } = params;
+
+  let satpoints = if !satpoints.is_empty() {
+    satpoints
+  } else {
+    let inscribed_utxos = inscribed
+      .keys()
+      .map(|satpoint| satpoint.outpoint)
+      .collect::<BTreeSet<OutPoint>>();
+
+    vec![utxos
+      .keys()
+      .find(|outpoint| !inscribed_utxos.contains(outpoint))
+      .map(|outpoint| SatPoint {
+        outpoint: *outpoint,
+        offset: 0,
+      })
+      .ok_or(BuildError::NoCardinalUtxos { min_value: None })?]
+  };
+
+  for (inscribed_satpoint, inscription_id) in &inscribed {
+    for satpoint in &satpoints {
+      if inscribed_satpoint == satpoint {
+        return Err(
+          BuildError::AlreadyInscribed {
+            satpoint: *satpoint,
+          }
+          .into(),
+        );
+      }
+
+      if inscribed_satpoint.outpoint == satpoint.outpoint {
+        return Err(anyhow!(
+          "utxo {} already inscribed with inscription {inscription_id} on sat {inscribed_satpoint}",
+          satpoint.outpoint,
+        ));
+      }
+    }
+  }
+
+  let repeat = inscriptions.len();
+  let outputs = plan.outputs.clone();
+
+  let commit = TransactionBuilder::build_transaction_with_value_v1(
+    input_type,
+    satpoints,
+    inscribed,
+    utxos.clone(),
+    outputs.clone(),
+    change.clone(),
+    commit_fee_rate,
+  )
+  .map_err(|err| match err {
+    transaction_builder::Error::NotEnoughCardinalUtxos => {
+      insufficient_funds(input_type, &utxos, &outputs, &change[0], commit_fee_rate).into()
+    }
+    err => Error::from(err),
+  })?;
+
+  let mut reveals: Vec<Transaction> = vec![];
+  for (i, reveal_output) in plan
+    .reveal_outputs(&service_address, true)
+    .into_iter()
+    .enumerate()
+  {
+    let (txid, vout) = (commit.txid(), u32::try_from(i).unwrap());
+
+    // the output paying a parent's reveal fee follows the reveals' outputs
+    let fee_vout = u32::try_from(repeat).unwrap();
+    let extra_inputs = plan.extra_inputs(
+      i,
+      OutPoint {
+        txid,
+        vout: fee_vout,
+      },
+      input_type,
+    );
+
+    let (mut reveal_tx, _fee) = build_reveal_transaction(
+      &plan.control_block[i],
+      reveal_fee_rate,
+      OutPoint { txid, vout },
+      &extra_inputs,
+      reveal_output,
+      &plan.reveal_script[i],
+    );
+
+    let dust_value = reveal_tx.output[0].script_pubkey.dust_value().to_sat();
+    if reveal_tx.output[0].value < dust_value {
+      return Err(
+        BuildError::DustOutput {
+          value: reveal_tx.output[0].value,
+          min: dust_value,
+        }
+        .into(),
+      );
+    }
+
+    let mut sighash_cache = SighashCache::new(&mut reveal_tx);
+
+    let mut prevouts = vec![commit.output[i].clone()];
+    if let (Some(parent), false) = (&plan.parent, extra_inputs.is_empty()) {
+      prevouts.push(parent.output.clone());
+      prevouts.push(commit.output[usize::try_from(fee_vout).unwrap()].clone());
+    }
+
+    let signature_hash = sighash_cache
+      .taproot_script_spend_signature_hash(
+        0,
+        &Prevouts::All(&prevouts),
+        TapLeafHash::from_script(&plan.reveal_script[i], LeafVersion::TapScript),
+        SchnorrSighashType::Default,
+      )
+      .expect("signature hash should compute");
+
+    let signature = key_source.sign(
+      &secp256k1,
+      &secp256k1::Message::from_slice(signature_hash.as_inner())
+        .expect("should be cryptographically secure hash"),
+      &key_pair,
+    );
+
+    let witness = sighash_cache
+      .witness_mut(0)
+      .expect("getting mutable witness reference should work");
+    witness.push(signature.as_ref());
+    witness.push(plan.reveal_script[i].clone());
+    witness.push(&plan.control_block[i].serialize());
+
+    // the parent's input is left for its owner to sign, the fee input is
+    // spent by the key path of the recovery key
+    if prevouts.len() > 1 {
+      let signature_hash = sighash_cache
+        .taproot_key_spend_signature_hash(2, &Prevouts::All(&prevouts), SchnorrSighashType::Default)
+        .expect("signature hash should compute");
+
+      let signature = key_source.sign(
+        &secp256k1,
+        &secp256k1::Message::from_slice(signature_hash.as_inner())
+          .expect("should be cryptographically secure hash"),
+        &plan.recovery_key_pairs[usize::try_from(fee_vout).unwrap()].to_inner(),
+      );
+
+      sighash_cache
+        .witness_mut(2)
+        .expect("getting mutable witness reference should work")
+        .push(signature.as_ref());
+    }
+
+    let reveal_weight = u64::try_from(reveal_tx.weight())?;
+
+    match max_nonstandard_weight {
+      None if reveal_weight > MAX_STANDARD_TX_WEIGHT.into() => bail!(
+        "reveal transaction weight greater than {MAX_STANDARD_TX_WEIGHT} (MAX_STANDARD_TX_WEIGHT): {reveal_weight}"
+      ),
+      Some(max) if reveal_weight > max => bail!(
+        "reveal transaction weight greater than {max} (max non-standard weight): {reveal_weight}"
+      ),
+      _ => {}
+    }
+
+    reveals.push(reveal_tx);
+  }
+
+  Ok(Inscribed {
+    commit,
+    reveals,
+    satpoint_fee: plan.satpoint_fee(),
+    recovery_key_pairs: plan.recovery_key_pairs,
+    service_fee: plan.service_fee.to_sat(),
+    network_fee: plan.breakdown.iter().map(|parts| parts.reveal_fee).sum(),
+    breakdown: plan.breakdown,
+  })
+}
+
+/// What a mint would cost. `total` is what the commit spends from the
+/// source, which is `shortfall` more than the utxos hold when they hold too
+/// little.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Estimate {
+  pub service_fee: u64,
+  pub satpoint_fee: u64,
+  /// Commit and reveal fees.
+  pub network_fee: u64,
+  pub total: u64,
+  pub available: u64,
+  pub shortfall: u64,
+}
+
+/// Works out what `inscribe` would charge without signing anything. Short
+/// of funds, the commit fee is estimated for spending every cardinal utxo,
+/// or one utxo of `params.input_type` when there are none.
+pub fn estimate(params: &CommitParams, inscriptions: &[Inscription]) -> Result<Estimate> {
+  let secp256k1 = Secp256k1::new();
+  let key_pair = KeySource::Random.key_pair(&secp256k1);
+  let plan = Plan::new(params, &secp256k1, &key_pair, inscriptions)?;
+
+  let inscribed_utxos = params
+    .inscriptions
+    .keys()
+    .map(|satpoint| satpoint.outpoint)
+    .collect::<BTreeSet<OutPoint>>();
+  let cardinals = params
+    .utxos
+    .iter()
+    .filter(|(outpoint, _)| !inscribed_utxos.contains(outpoint))
+    .map(|(outpoint, amount)| (*outpoint, *amount))
+    .collect::<BTreeMap<OutPoint, Amount>>();
+  let available = cardinals
+    .values()
+    .map(|amount| amount.to_sat())
+    .sum::<u64>();
+
+  let satpoints = if !params.satpoints.is_empty() {
+    params.satpoints.clone()
+  } else {
+    cardinals
+      .keys()
+      .next()
+      .map(|outpoint| SatPoint {
+        outpoint: *outpoint,
+        offset: 0,
+      })
+      .into_iter()
+      .collect()
+  };
+
+  let built = if satpoints.is_empty() {
+    None
+  } else {
+    match TransactionBuilder::build_transaction_with_value_v1(
+      params.input_type,
+      satpoints,
+      params.inscriptions.clone(),
+      params.utxos.clone(),
+      plan.outputs.clone(),
+      params.change.clone(),
+      params.commit_fee_rate,
+    ) {
+      Ok(commit) => Some(commit),
+      Err(transaction_builder::Error::NotEnoughCardinalUtxos) => None,
+      Err(err) => return Err(err.into()),
+    }
+  };
+
+  let commit_fee = match built {
+    Some(commit) => fee(&commit, &params.utxos),
+    None => {
+      let inputs = cardinals.len().max(1);
+      let commit = Transaction {
+        version: 1,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![
+          TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+          };
+          inputs
+        ],
+        output: plan
+          .outputs
+          .iter()
+          .chain([(params.change[0].clone(), Amount::ZERO)].iter())
+          .map(|(address, amount)| TxOut {
+            script_pubkey: address.script_pubkey(),
+            value: amount.to_sat(),
+          })
+          .collect(),
+      };
+      params
+        .commit_fee_rate
+        .fee(estimate_vsize(&commit, params.input_type))
+        .to_sat()
+    }
+  };
+
+  let service_fee = plan.service_fee.to_sat();
+  let satpoint_fee = plan.satpoint_fee();
+  let network_fee = commit_fee
+    + plan
+      .breakdown
+      .iter()
+      .map(|parts| parts.reveal_fee)
+      .sum::<u64>();
+  let total = plan
+    .outputs
+    .iter()
+    .map(|(_, amount)| amount.to_sat())
+    .sum::<u64>()
+    + commit_fee;
+
+  Ok(Estimate {
+    service_fee,
+    satpoint_fee,
+    network_fee,
+    total,
+    available,
+    shortfall: total.saturating_sub(available),
+  })
+}
+
+// splits `items` into runs of `groups` sizes, each inscription pointed at
+// its recipient's output
+fn pack(
+  items: Vec<(Inscription, (Address, Amount))>,
+  groups: &[usize],
+) -> Result<Vec<Vec<(Inscription, (Address, Amount))>>> {
+  if groups.is_empty() {
+    return Ok(items.into_iter().map(|item| vec![item]).collect());
+  }
+
+  if groups.iter().sum::<usize>() != items.len() || groups.contains(&0) {
+    bail!(
+      "groups of {groups:?} inscriptions given for {} inscriptions",
+      items.len()
+    );
+  }
+
+  let mut items = items.into_iter();
+  Ok(
+    groups
+      .iter()
+      .map(|size| {
+        let (inscriptions, recipients): (Vec<Inscription>, Vec<(Address, Amount)>) =
+          items.by_ref().take(*size).unzip();
+        let postages = recipients
+          .iter()
+          .map(|(_, postage)| *postage)
+          .collect::<Vec<Amount>>();
+        point(inscriptions, &postages)
+          .into_iter()
+          .zip(recipients)
+          .collect()
+      })
+      .collect(),
+  )
+}
+
+/// Points every inscription of a packed `group` after the first at its own
+/// output, the one following those of `postages` before it, keeping any
+/// pointer it has to a sat within that output.
+pub fn point(group: Vec<Inscription>, postages: &[Amount]) -> Vec<Inscription> {
+  let mut offset = Amount::ZERO;
+  group
+    .into_iter()
+    .zip(postages)
+    .map(|(item, postage)| {
+      let item = match offset.to_sat() {
+        0 => item,
+        offset => {
+          let pointer = item.pointer().unwrap_or(0);
+          item.with_pointer(offset + pointer)
+        }
+      };
+      offset += *postage;
+      item
+    })
+    .collect()
+}
+
+/// Weight of a signed reveal packing `inscriptions` into its script and
+/// paying `output`, for sizing groups before anything is built.
+pub fn reveal_weight(inscriptions: &[Inscription], output: Vec<TxOut>) -> usize {
+  let script = Inscription::append_batch_reveal_script(
+    inscriptions,
+    script::Builder::new()
+      .push_slice(&[0; 32])
+      .push_opcode(opcodes::all::OP_CHECKSIG),
+  );
+
+  Transaction {
+    version: 1,
+    lock_time: PackedLockTime::ZERO,
+    input: vec![TxIn {
+      previous_output: OutPoint::null(),
+      script_sig: Script::new(),
+      sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      witness: Witness::from_vec(vec![
+        vec![0; SCHNORR_SIGNATURE_SIZE],
+        script.into_bytes(),
+        vec![0; TAPROOT_CONTROL_BASE_SIZE],
+      ]),
+    }],
+    output,
+  }
+  .weight()
+}
+
+/// Builds an unsigned transaction sending `params.postages` and
+/// `params.additional_value` to `params.destination`.
+pub fn transfer(params: TransferParams) -> Result<Transaction> {
+  if let Some(op_return) = &params.op_return {
+    if op_return.len() > MAX_OP_RETURN_SIZE {
+      return Err(
+        BuildError::OpReturnTooLarge {
+          size: op_return.len(),
+          limit: MAX_OP_RETURN_SIZE,
+        }
+        .into(),
+      );
+    }
+  }
+
+  let mut transaction = if params.destinations.is_empty() {
+    TransactionBuilder::build_transaction_with_postages_v1(
+      params.input_type,
+      params.witness_sizes,
+      params.postages,
+      params.inscriptions,
+      params.utxos,
+      params.destination,
+      params.additional_value,
+      params.change,
+      params.fee_rate,
+      params.op_return,
+    )?
+  } else {
+    if params.destinations.len() != params.postages.len() {
+      bail!(
+        "{} destinations given for {} outgoing sats",
+        params.destinations.len(),
+        params.postages.len()
+      );
+    }
+
+    let [change, _] = params.change;
+    TransactionBuilder::build_transaction_with_destinations(
+      params.input_type,
+      params.witness_sizes,
+      params
+        .postages
+        .into_iter()
+        .zip(params.destinations)
+        .map(|((satpoint, postage), destination)| (satpoint, postage, destination))
+        .collect(),
+      params.inscriptions,
+      params.utxos,
+      (params.destination, params.additional_value),
+      change,
+      params.fee_rate,
+      params.op_return,
+    )?
+  };
+
+  apply_lock_time(&mut transaction, params.locktime, params.rbf);
+
+  Ok(transaction)
+}
+
+/// Builds an unsigned transaction spending `params.inputs` back to
+/// `params.source`, less the network fee and any service fee.
+pub fn cancel(params: CancelParams) -> Result<Cancelled> {
+  let CancelParams {
+    input_type,
+    inputs,
+    utxos,
+    source,
+    service,
+    fee_rate,
+    locktime,
+    rbf,
+    witness_sizes,
+  } = params;
+
+  let mut output = vec![TxOut {
+    script_pubkey: source.script_pubkey(),
+    value: 0,
+  }];
+  let mut service_fee = 0;
+  if let Some((service_address, fee)) = service.filter(|(_, fee)| *fee > Amount::ZERO) {
+    service_fee = fee.to_sat();
+    output.push(TxOut {
+      script_pubkey: service_address.script_pubkey(),
+      value: service_fee,
+    });
+  }
+
+  let mut transaction = Transaction {
+    input: inputs
+      .iter()
+      .map(|outpoint| TxIn {
+        previous_output: *outpoint,
+        script_sig: Script::new(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      })
+      .collect(),
+    output,
+    lock_time: PackedLockTime::ZERO,
+    version: 1,
+  };
+  apply_lock_time(&mut transaction, locktime, rbf);
+
+  let network_fee = fee_rate
+    .fee(estimate_vsize_with_witnesses(
+      &transaction,
+      input_type,
+      &witness_sizes,
+    ))
+    .to_sat();
+
+  let mut input_amount = 0;
+  for outpoint in &inputs {
+    input_amount += utxos
+      .get(outpoint)
+      .ok_or(BuildError::NoCardinalUtxos { min_value: None })?
+      .to_sat();
+  }
+
+  if input_amount <= network_fee {
+    bail!("Input amount less than network fee");
+  }
+  if input_amount <= network_fee + service_fee {
+    service_fee = input_amount - network_fee;
+    transaction.output[1].value = service_fee;
+  }
+  transaction.output[0].value = input_amount - network_fee - service_fee;
+
+  Ok(Cancelled {
+    transaction,
+    network_fee,
+    service_fee,
+  })
+}
+
+/// Builds an unsigned child spending output `params.vout` of `params.parent`
+/// back to the same script, paying enough that parent and child together
+/// reach `params.fee_rate`. The spent output must keep at least dust.
+pub fn accelerate(params: AccelerateParams) -> Result<Accelerated> {
+  let AccelerateParams {
+    parent,
+    parent_fee,
+    vout,
+    input_type,
+    fee_rate,
+    rbf,
+  } = params;
+
+  let txid = parent.txid();
+  let spent = parent
+    .output
+    .get(usize::try_from(vout)?)
+    .cloned()
+    .ok_or_else(|| anyhow!("transaction {txid} has no output {vout}"))?;
+  let spend = OutPoint { txid, vout };
+
+  let mut transaction = Transaction {
+    input: vec![TxIn {
+      previous_output: spend,
+      script_sig: Script::new(),
+      witness: Witness::new(),
+      sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    }],
+    output: vec![TxOut {
+      script_pubkey: spent.script_pubkey.clone(),
+      value: 0,
+    }],
+    lock_time: PackedLockTime::ZERO,
+    version: 1,
+  };
+  apply_lock_time(&mut transaction, None, rbf);
+
+  let vsize = u64::try_from(parent.vsize())?;
+  let child_vsize = u64::try_from(estimate_vsize(&transaction, input_type))?;
+  let cpfp =
+    Cpfp::recommend(spend, parent_fee, vsize, child_vsize, fee_rate.0).ok_or_else(|| {
+      anyhow!(
+        "transaction {txid} already pays {:.2} sat/vB, at or above {} sat/vB",
+        parent_fee as f64 / vsize as f64,
+        fee_rate.0
+      )
+    })?;
+
+  let required = cpfp.child_fee + spent.script_pubkey.dust_value().to_sat();
+  if spent.value < required {
+    return Err(
+      BuildError::InsufficientFunds {
+        available: spent.value,
+        required,
+      }
+      .into(),
+    );
+  }
+  transaction.output[0].value = spent.value - cpfp.child_fee;
+
+  Ok(Accelerated {
+    transaction,
+    network_fee: cpfp.child_fee,
+    package_fee_rate: cpfp.package_fee_rate,
+  })
+}
+
+/// Rebuilds `params.original` with the same inputs and outputs, paying
+/// `params.fee_rate` out of its change output alone. Per BIP-125 the
+/// replacement pays at least the original fee plus the minimum relay fee
+/// for its own size, and the change output must keep at least dust.
+pub fn bump_fee(params: BumpFeeParams) -> Result<Bumped> {
+  let BumpFeeParams {
+    original,
+    utxos,
+    change,
+    input_type,
+    fee_rate,
+  } = params;
+
+  let txid = original.txid();
+  if !original.is_explicitly_rbf() {
+    bail!("transaction {txid} does not signal replaceability");
+  }
+  if let Some(input) = original
+    .input
+    .iter()
+    .find(|input| !utxos.contains_key(&input.previous_output))
+  {
+    bail!(
+      "output {} spent by transaction {txid} is not a known utxo",
+      input.previous_output
+    );
+  }
+  if original
+    .input
+    .iter()
+    .map(|input| utxos[&input.previous_output].to_sat())
+    .sum::<u64>()
+    < original
+      .output
+      .iter()
+      .map(|output| output.value)
+      .sum::<u64>()
+  {
+    bail!("transaction {txid} spends less than it creates");
+  }
+
+  let mut transaction = original;
+  for input in &mut transaction.input {
+    input.witness = Witness::new();
+  }
+
+  let change_script = change.script_pubkey();
+  let vout = transaction
+    .output
+    .iter()
+    .rposition(|output| output.script_pubkey == change_script)
+    .ok_or_else(|| anyhow!("transaction {txid} has no change output to {change}"))?;
+
+  let original_fee = fee(&transaction, &utxos);
+  let vsize = estimate_vsize(&transaction, input_type);
+  let network_fee = fee_rate
+    .fee(vsize)
+    .to_sat()
+    .max(original_fee + FeeRate(MIN_RELAY_FEE_RATE).fee(vsize).to_sat());
+
+  let bump = network_fee - original_fee;
+  let available = transaction.output[vout].value;
+  let required = bump + change_script.dust_value().to_sat();
+  if available < required {
+    return Err(
+      BuildError::InsufficientFunds {
+        available,
+        required,
+      }
+      .into(),
+    );
+  }
+  transaction.output[vout].value -= bump;
+
+  Ok(Bumped {
+    transaction,
+    network_fee,
+    original_fee,
+  })
+}
+
+/// Locates, within the commit's funding utxos, the sat `pointer` sats into
+/// each of the first `count` commit outputs. Reveal i spends commit output i
+/// and puts its inscription on that sat.
+pub fn inscribed_sats(
+  commit: &Transaction,
+  utxos: &BTreeMap<OutPoint, Amount>,
+  count: usize,
+  pointer: u64,
+) -> Result<Vec<SatPoint>> {
+  let mut satpoints = Vec::new();
+  let mut start_of_output = 0;
+  for output in commit.output.iter().take(count) {
+    let position = start_of_output + pointer;
+    let mut start = 0;
+    let mut satpoint = None;
+    for input in &commit.input {
+      let value = utxos
+        .get(&input.previous_output)
+        .ok_or_else(|| anyhow!("commit input {} is not a known utxo", input.previous_output))?
+        .to_sat();
+      if position < start + value {
+        satpoint = Some(SatPoint {
+          outpoint: input.previous_output,
+          offset: position - start,
+        });
+        break;
+      }
+      start += value;
+    }
+    satpoints.push(
+      satpoint
+        .ok_or_else(|| anyhow!("commit output at sat {position} is not funded by its inputs"))?,
+    );
+    start_of_output += output.value;
+  }
+  Ok(satpoints)
+}
+
+/// Wraps `tx` in a PSBT whose witness utxos come from `utxos` and
+/// `script_pubkey`.
+pub fn psbt(
+  tx: &Transaction,
+  utxos: &BTreeMap<OutPoint, Amount>,
+  script_pubkey: impl Fn(OutPoint) -> Script,
+) -> Result<Psbt> {
+  let mut tx_psbt = Psbt::from_unsigned_tx(tx.clone())?;
+  for i in 0..tx_psbt.unsigned_tx.input.len() {
+    let previous_output = tx_psbt.unsigned_tx.input[i].previous_output;
+    tx_psbt.inputs[i].witness_utxo = Some(TxOut {
+      value: utxos
+        .get(&previous_output)
+        .ok_or(BuildError::NoCardinalUtxos { min_value: None })?
+        .to_sat(),
+      script_pubkey: script_pubkey(previous_output),
+    });
+  }
+  Ok(tx_psbt)
+}
+
+/// The reveal spending `parent` as a PSBT, with the commit's inputs signed
+/// and the parent's left for its owner.
+pub fn parent_reveal_psbt(
+  commit: &Transaction,
+  reveal: &Transaction,
+  parent: &Parent,
+) -> Result<Psbt> {
+  let mut unsigned = reveal.clone();
+  for input in &mut unsigned.input {
+    input.witness = Witness::new();
+  }
+
+  let mut psbt = Psbt::from_unsigned_tx(unsigned)?;
+  for (i, input) in reveal.input.iter().enumerate() {
+    psbt.inputs[i].witness_utxo = Some(if input.previous_output == parent.satpoint.outpoint {
+      parent.output.clone()
+    } else {
+      commit.output[usize::try_from(input.previous_output.vout)?].clone()
+    });
+    if !input.witness.is_empty() {
+      psbt.inputs[i].final_script_witness = Some(input.witness.clone());
+    }
+  }
+  Ok(psbt)
+}
+
+/// Fee paid by `tx`, every input must be in `utxos`.
+pub fn fee(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> u64 {
+  tx.input
+    .iter()
+    .map(|txin| utxos.get(&txin.previous_output).unwrap().to_sat())
+    .sum::<u64>()
+    .checked_sub(tx.output.iter().map(|txout| txout.value).sum::<u64>())
+    .unwrap()
+}
+
+/// Virtual size of `transaction` once its inputs are signed.
+pub fn estimate_vsize(transaction: &Transaction, input_type: AddressType) -> usize {
+  estimate_vsize_with_witnesses(transaction, input_type, &BTreeMap::new())
+}
+
+/// Like `estimate_vsize`, with the inputs in `witness_sizes` signed with
+/// witnesses of that many bytes instead of by the key path.
+pub fn estimate_vsize_with_witnesses(
+  transaction: &Transaction,
+  input_type: AddressType,
+  witness_sizes: &BTreeMap<OutPoint, usize>,
+) -> usize {
+  let mut modified_tx = transaction.clone();
+  for input in &mut modified_tx.input {
+    let witness_size = witness_sizes
+      .get(&input.previous_output)
+      .copied()
+      .unwrap_or_else(|| TransactionBuilder::key_path_witness_size(input_type));
+    input.witness = Witness::from_vec(vec![vec![0; witness_size]]);
+  }
+  modified_tx.vsize()
+}
+
+/// Everything a stuck reveal is bumped with.
+#[derive(Debug, Clone)]
+pub struct BumpParams {
+  pub reveal: Transaction,
+  /// The commit output the reveal spends.
+  pub commit_output: TxOut,
+  pub funding: OutPoint,
+  pub funding_output: TxOut,
+  /// Address type of the funding output, used to estimate its witness.
+  pub input_type: AddressType,
+  pub change: Address,
+  pub fee_rate: FeeRate,
+  /// The key the reveal was signed with.
+  pub key_pair: UntweakedKeyPair,
+}
+
+/// Rebuilds a signed reveal with `params.funding` added to pay a higher fee,
+/// returning what is left of it to `params.change`. The reveal input is
+/// signed again, the funding input is left for its owner to sign.
+pub fn bump_reveal(params: BumpParams) -> Result<Transaction> {
+  let BumpParams {
+    reveal,
+    commit_output,
+    funding,
+    funding_output,
+    input_type,
+    change,
+    fee_rate,
+    key_pair,
+  } = params;
+
+  if reveal.input.len() != 1 || reveal.input[0].witness.len() != 3 {
+    bail!("transaction {} is not a reveal", reveal.txid());
+  }
+  let witness = reveal.input[0].witness.to_vec();
+  let reveal_script = Script::from(witness[1].clone());
+  let control_block = ControlBlock::from_slice(&witness[2])
+    .map_err(|err| anyhow!("invalid reveal control block: {err}"))?;
+
+  let secp256k1 = Secp256k1::new();
+  let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+  match reveal_script.instructions().next() {
+    Some(Ok(script::Instruction::PushBytes(key))) if key == public_key.serialize() => {}
+    _ => bail!(
+      "reveal {} was not signed with the stored key",
+      reveal.txid()
+    ),
+  }
+
+  let paid = reveal.output.iter().map(|output| output.value).sum::<u64>();
+  let old_fee = commit_output
+    .value
+    .checked_sub(paid)
+    .ok_or_else(|| anyhow!("reveal spends more than its commit output"))?;
+
+  let mut bumped = Transaction {
+    version: reveal.version,
+    lock_time: reveal.lock_time,
+    input: vec![
+      TxIn {
+        witness: Witness::new(),
+        ..reveal.input[0].clone()
+      },
+      TxIn {
+        previous_output: funding,
+        script_sig: Script::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::new(),
+      },
+    ],
+    output: reveal.output.clone(),
+  };
+
+  let fee = |tx: &Transaction| {
+    let mut tx = tx.clone();
+    tx.input[0].witness = Witness::from_vec(vec![
+      vec![0; SCHNORR_SIGNATURE_SIZE],
+      reveal_script.to_bytes(),
+      control_block.serialize(),
+    ]);
+    tx.input[1].witness = Witness::from_vec(vec![vec![
+      0;
+      if input_type == AddressType::P2tr {
+        TransactionBuilder::SCHNORR_SIGNATURE_SIZE
+      } else {
+        TransactionBuilder::P2WPKH_WINETSS_SIZE
+      }
+    ]]);
+    fee_rate.fee(tx.vsize()).to_sat()
+  };
+
+  let available = commit_output.value + funding_output.value;
+
+  bumped.output.push(TxOut {
+    value: 0,
+    script_pubkey: change.script_pubkey(),
+  });
+  let required = paid + fee(&bumped);
+  let change_value = available.saturating_sub(required);
+  if change_value >= change.script_pubkey().dust_value().to_sat() {
+    bumped.output.last_mut().unwrap().value = change_value;
+  } else {
+    bumped.output.pop();
+    let required = paid + fee(&bumped);
+    if available < required {
+      return Err(
+        BuildError::InsufficientFunds {
+          available,
+          required,
+        }
+        .into(),
+      );
+    }
+  }
+
+  let new_fee = available - bumped.output.iter().map(|output| output.value).sum::<u64>();
+  if new_fee <= old_fee {
+    bail!(
+      "fee rate {} does not raise the reveal fee above {old_fee} sats",
+      fee_rate.0
+    );
+  }
+
+  let signature_hash = SighashCache::new(&bumped)
+    .taproot_script_spend_signature_hash(
+      0,
+      &Prevouts::All(&[commit_output, funding_output]),
+      TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
+      SchnorrSighashType::Default,
+    )
+    .expect("signature hash should compute");
+
+  let signature = secp256k1.sign_schnorr(
+    &secp256k1::Message::from_slice(signature_hash.as_inner())
+      .expect("should be cryptographically secure hash"),
+    &key_pair,
+  );
+
+  let witness = &mut bumped.input[0].witness;
+  witness.push(signature.as_ref());
+  witness.push(reveal_script);
+  witness.push(&control_block.serialize());
+
+  Ok(bumped)
+}
+
+/// Everything a commit output whose reveal was lost is swept back with.
+#[derive(Debug, Clone)]
+pub struct RecoverParams {
+  pub commit_output: OutPoint,
+  pub output: TxOut,
+  /// The recovery key of `commit_output`, see `Inscribed::recovery_key_pairs`.
+  pub key_pair: TweakedKeyPair,
+  pub destination: Address,
+  pub fee_rate: FeeRate,
+}
+
+/// Spends a commit output by the key path of its recovery key, sending all
+/// of it less the fee to `params.destination`.
+pub fn recover(params: RecoverParams) -> Result<Transaction> {
+  let RecoverParams {
+    commit_output,
+    output,
+    key_pair,
+    destination,
+    fee_rate,
+  } = params;
+
+  let secp256k1 = Secp256k1::new();
+  let (public_key, _parity) = key_pair.to_inner().x_only_public_key();
+  if output.script_pubkey
+    != Script::new_v1_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(public_key))
+  {
+    bail!("recovery key does not unlock commit output {commit_output}");
+  }
+
+  let mut recovered = Transaction {
+    version: 2,
+    lock_time: PackedLockTime::ZERO,
+    input: vec![TxIn {
+      previous_output: commit_output,
+      script_sig: Script::new(),
+      sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      witness: Witness::from_vec(vec![vec![0; SCHNORR_SIGNATURE_SIZE]]),
+    }],
+    output: vec![TxOut {
+      value: 0,
+      script_pubkey: destination.script_pubkey(),
+    }],
+  };
+
+  let fee = fee_rate.fee(recovered.vsize()).to_sat();
+  let required = fee + destination.script_pubkey().dust_value().to_sat();
+  if output.value < required {
+    return Err(
+      BuildError::InsufficientFunds {
+        available: output.value,
+        required,
+      }
+      .into(),
+    );
+  }
+  recovered.output[0].value = output.value - fee;
+  recovered.input[0].witness = Witness::new();
+
+  let signature_hash = SighashCache::new(&recovered)
+    .taproot_key_spend_signature_hash(0, &Prevouts::All(&[output]), SchnorrSighashType::Default)
+    .expect("signature hash should compute");
+
+  let signature = secp256k1.sign_schnorr(
+    &secp256k1::Message::from_slice(signature_hash.as_inner())
+      .expect("should be cryptographically secure hash"),
+    &key_pair.to_inner(),
+  );
+  recovered.input[0].witness.push(signature.as_ref());
+
+  Ok(recovered)
+}
+
+// `extra_inputs` are spent after `input`, priced with witnesses of the
+// given sizes
+fn build_reveal_transaction(
+  control_block: &ControlBlock,
+  fee_rate: FeeRate,
+  input: OutPoint,
+  extra_inputs: &[(OutPoint, usize)],
+  output: Vec<TxOut>,
+  script: &Script,
+) -> (Transaction, Amount) {
+  let reveal_tx = Transaction {
+    input: [input]
+      .into_iter()
+      .chain(extra_inputs.iter().map(|(outpoint, _)| *outpoint))
+      .map(|previous_output| TxIn {
+        previous_output,
+        script_sig: script::Builder::new().into_script(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      })
+      .collect(),
+    output,
+    lock_time: PackedLockTime::ZERO,
+    version: 1,
+  };
+
+  let fee = {
+    let mut reveal_tx = reveal_tx.clone();
+
+    for (i, (_, witness_size)) in extra_inputs.iter().enumerate() {
+      reveal_tx.input[i + 1].witness = Witness::from_vec(vec![vec![0; *witness_size]]);
+    }
+
+    reveal_tx.input[0].witness.push(
+      Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+        .unwrap()
+        .as_ref(),
+    );
+    reveal_tx.input[0].witness.push(script);
+    reveal_tx.input[0].witness.push(&control_block.serialize());
+
+    fee_rate.fee(reveal_tx.vsize())
+  };
+
+  (reveal_tx, fee)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(utxos: &[(OutPoint, u64)]) -> CommitParams {
+    CommitParams {
+      input_type: AddressType::P2wpkh,
+      network: Network::Testnet,
+      satpoints: Vec::new(),
+      inscriptions: BTreeMap::new(),
+      utxos: utxos
+        .iter()
+        .map(|(outpoint, value)| (*outpoint, Amount::from_sat(*value)))
+        .collect(),
+      change: [change(0), change(1)],
+      destination: recipient(),
+      groups: Vec::new(),
+      commit_fee_rate: FeeRate::try_from(1.0).unwrap(),
+      reveal_fee_rate: FeeRate::try_from(1.0).unwrap(),
+      max_nonstandard_weight: None,
+      service_address: change(2),
+      service_fee: Amount::from_sat(1000),
+      additional_service_fee: Amount::ZERO,
+      target_postage: Amount::from_sat(546),
+      recipients: Vec::new(),
+      parent: None,
+      key_source: KeySource::Random,
+    }
+  }
+
+  fn transfer_params() -> TransferParams {
+    TransferParams {
+      input_type: AddressType::P2wpkh,
+      postages: vec![(satpoint(1, 0), Amount::from_sat(5_000))],
+      inscriptions: BTreeMap::new(),
+      utxos: [
+        (outpoint(1), Amount::from_sat(10_000)),
+        (outpoint(2), Amount::from_sat(20_000)),
+      ]
+      .into(),
+      destination: recipient(),
+      destinations: Vec::new(),
+      additional_value: Amount::ZERO,
+      change: [change(0), change(1)],
+      fee_rate: FeeRate::try_from(1.0).unwrap(),
+      op_return: None,
+      locktime: None,
+      rbf: true,
+      witness_sizes: BTreeMap::new(),
+    }
+  }
+
+  #[test]
+  fn reveals_spend_commit_outputs_in_order() {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![
+        inscription("text/plain", "foo"),
+        inscription("text/plain", "bar"),
+        inscription("text/plain", "baz"),
+      ],
+    )
+    .unwrap();
+
+    assert_eq!(inscribed.reveals.len(), 3);
+    assert_eq!(inscribed.recovery_key_pairs.len(), 3);
+    for (i, reveal) in inscribed.reveals.iter().enumerate() {
+      assert_eq!(
+        reveal.input[0].previous_output,
+        OutPoint {
+          txid: inscribed.commit.txid(),
+          vout: u32::try_from(i).unwrap(),
+        }
+      );
+      assert_eq!(reveal.input[0].witness.len(), 3);
+      assert_eq!(reveal.output[0], tx_out(546, recipient()));
+    }
+  }
+
+  fn parent_params(parent_value: u64) -> CommitParams {
+    CommitParams {
+      inscriptions: [(satpoint(2, 0), inscription_id(2))].into(),
+      parent: Some(Parent {
+        id: inscription_id(2),
+        satpoint: satpoint(2, 0),
+        output: tx_out(parent_value, change(0)),
+        destination: change(0),
+      }),
+      reveal_fee_rate: FeeRate::try_from(10.0).unwrap(),
+      ..params(&[(outpoint(1), 100_000), (outpoint(2), parent_value)])
+    }
+  }
+
+  #[test]
+  fn parent_is_spent_between_commit_outputs() {
+    let inscribed = inscribe(parent_params(546), vec![inscription("text/plain", "foo")]).unwrap();
+    let commit = &inscribed.commit;
+    let reveal = &inscribed.reveals[0];
+
+    assert_eq!(
+      reveal
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<OutPoint>>(),
+      [
+        OutPoint {
+          txid: commit.txid(),
+          vout: 0
+        },
+        outpoint(2),
+        OutPoint {
+          txid: commit.txid(),
+          vout: 1
+        },
+      ]
+    );
+    assert_eq!(reveal.input[0].witness.len(), 3);
+    assert!(reveal.input[1].witness.is_empty());
+    assert_eq!(reveal.input[2].witness.len(), 1);
+    assert_eq!(inscribed.recovery_key_pairs.len(), 2);
+
+    // the reveal fee, far above the parent's 546 sats, comes off the last
+    // input, so every sat of the parent reaches its output
+    let reveal_fee = inscribed.breakdown[0].reveal_fee;
+    assert!(reveal_fee > 546);
+    assert_eq!(commit.output[0].value, 546 + 1000);
+    assert_eq!(commit.output[1].value, reveal_fee);
+    assert_eq!(
+      reveal.output,
+      [
+        tx_out(546, recipient()),
+        tx_out(1000, change(2)),
+        tx_out(546, change(0)),
+      ]
+    );
+
+    let utxos = [
+      (
+        OutPoint {
+          txid: commit.txid(),
+          vout: 0,
+        },
+        Amount::from_sat(commit.output[0].value),
+      ),
+      (outpoint(2), Amount::from_sat(546)),
+      (
+        OutPoint {
+          txid: commit.txid(),
+          vout: 1,
+        },
+        Amount::from_sat(commit.output[1].value),
+      ),
+    ]
+    .into();
+    assert_eq!(fee(reveal, &utxos), reveal_fee);
+  }
+
+  #[test]
+  fn parent_fee_output_is_kept_above_dust() {
+    let inscribed = inscribe(
+      CommitParams {
+        reveal_fee_rate: FeeRate::try_from(1.0).unwrap(),
+        ..parent_params(1000)
+      },
+      vec![inscription("text/plain", "foo")],
+    )
+    .unwrap();
+
+    let reveal_fee = inscribed.breakdown[0].reveal_fee;
+    assert!(reveal_fee < 330);
+    assert_eq!(inscribed.commit.output[1].value, 330);
+    assert_eq!(
+      inscribed.reveals[0].output[2].value,
+      1000 + 330 - reveal_fee
+    );
+  }
+
+  #[test]
+  fn parent_takes_a_single_inscription() {
+    assert_eq!(
+      inscribe(
+        parent_params(546),
+        vec![
+          inscription("text/plain", "foo"),
+          inscription("text/plain", "bar")
+        ],
+      )
+      .unwrap_err()
+      .to_string(),
+      "a parent can only be given for a single inscription, not 2"
+    );
+  }
+
+  #[test]
+  fn reveals_pay_their_own_recipients() {
+    let inscribed = inscribe(
+      CommitParams {
+        recipients: vec![
+          (recipient(), Amount::from_sat(546)),
+          (change(1), Amount::from_sat(10_000)),
+        ],
+        ..params(&[(outpoint(1), 100_000)])
+      },
+      vec![
+        inscription("text/plain", "foo"),
+        inscription("text/plain", "bar"),
+      ],
+    )
+    .unwrap();
+
+    assert_eq!(inscribed.reveals[0].output[0], tx_out(546, recipient()));
+    assert_eq!(inscribed.reveals[1].output[0], tx_out(10_000, change(1)));
+    assert!(inscribed.commit.output[1].value > 10_000);
+  }
+
+  #[test]
+  fn satpoint_fee_sums_recipient_postages() {
+    let params = CommitParams {
+      recipients: vec![
+        (recipient(), Amount::from_sat(546)),
+        (recipient(), Amount::from_sat(10_000)),
+      ],
+      ..params(&[(outpoint(1), 100_000)])
+    };
+    let inscriptions = vec![inscription("text/plain", "foo"); 2];
+
+    assert_eq!(
+      estimate(&params, &inscriptions).unwrap().satpoint_fee,
+      10_546
+    );
+    assert_eq!(inscribe(params, inscriptions).unwrap().satpoint_fee, 10_546);
+  }
+
+  #[test]
+  fn pointer_within_postage_is_inscribed() {
+    let utxos = [(outpoint(1), Amount::from_sat(100_000))];
+    for pointer in [0, 5_000, 9_999] {
+      let inscribed = inscribe(
+        CommitParams {
+          target_postage: Amount::from_sat(10_000),
+          ..params(&utxos.map(|(outpoint, amount)| (outpoint, amount.to_sat())))
+        },
+        vec![inscription("text/plain", "foo").with_pointer(pointer)],
+      )
+      .unwrap();
+
+      let reveal = &inscribed.reveals[0];
+      assert_eq!(reveal.output[0].value, 10_000);
+      assert_eq!(
+        Inscription::from_transaction(reveal).unwrap().pointer(),
+        Some(pointer)
+      );
+      assert_eq!(
+        inscribed_sats(&inscribed.commit, &utxos.into(), 1, pointer).unwrap(),
+        [satpoint(1, pointer)]
+      );
+    }
+  }
+
+  #[test]
+  fn pointer_past_postage_is_rejected() {
+    let error = inscribe(
+      CommitParams {
+        target_postage: Amount::from_sat(10_000),
+        ..params(&[(outpoint(1), 100_000)])
+      },
+      vec![inscription("text/plain", "foo").with_pointer(10_000)],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+      error.downcast::<BuildError>().unwrap(),
+      BuildError::PointerOutOfRange {
+        item: 0,
+        pointer: 10_000,
+        postage: 10_000,
+      }
+    );
+  }
+
+  #[test]
+  fn recipients_must_match_inscriptions() {
+    assert!(inscribe(
+      CommitParams {
+        recipients: vec![(recipient(), Amount::from_sat(546))],
+        ..params(&[(outpoint(1), 100_000)])
+      },
+      vec![
+        inscription("text/plain", "foo"),
+        inscription("text/plain", "bar"),
+      ],
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn distinct_inscriptions_commit_to_distinct_addresses() {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![
+        inscription("text/plain", "foo"),
+        inscription("text/plain", "bar"),
+      ],
+    )
+    .unwrap();
+
+    assert_ne!(
+      inscribed.commit.output[0].script_pubkey,
+      inscribed.commit.output[1].script_pubkey
+    );
+  }
+
+  #[test]
+  fn repeated_inscription_commits_to_one_address() {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![inscription("text/plain", "foo"); 2],
+    )
+    .unwrap();
+
+    assert_eq!(
+      inscribed.commit.output[0].script_pubkey,
+      inscribed.commit.output[1].script_pubkey
+    );
+  }
+
+  #[test]
+  fn first_reveal_pays_service_fee() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.additional_service_fee = Amount::from_sat(500);
+
+    let inscribed = inscribe(params, vec![inscription("text/plain", "foo"); 2]).unwrap();
+
+    assert_eq!(inscribed.service_fee, 2500);
+    assert_eq!(inscribed.satpoint_fee, 1092);
+    assert_eq!(inscribed.reveals[0].output[1], tx_out(2500, change(2)));
+    assert_eq!(inscribed.reveals[1].output.len(), 1);
+  }
+
+  #[test]
+  fn dust_service_fee_is_dropped() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.service_fee = Amount::from_sat(545);
+
+    let inscribed = inscribe(params, vec![inscription("text/plain", "foo")]).unwrap();
+
+    assert_eq!(inscribed.service_fee, 0);
+    assert_eq!(inscribed.reveals[0].output.len(), 1);
+  }
+
+  #[test]
+  fn packed_reveal_points_at_consecutive_outputs() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.groups = vec![3, 1];
+
+    let inscribed = inscribe(
+      params,
+      vec![
+        inscription("text/plain", "foo"),
+        inscription("text/plain", "bar"),
+        inscription("text/plain", "baz"),
+        inscription("text/plain", "qux"),
+      ],
+    )
+    .unwrap();
+
+    assert_eq!(inscribed.reveals.len(), 2);
+    assert_eq!(inscribed.recovery_key_pairs.len(), 2);
+    assert_eq!(inscribed.satpoint_fee, 546 * 4);
+    assert_eq!(
+      inscribed.reveals[0].output,
+      [
+        tx_out(546, recipient()),
+        tx_out(546, recipient()),
+        tx_out(546, recipient()),
+        tx_out(4000, change(2)),
+      ]
+    );
+    assert_eq!(inscribed.reveals[1].output, [tx_out(546, recipient())]);
+
+    assert_eq!(
+      Inscription::all_from_transaction(&inscribed.reveals[0])
+        .iter()
+        .map(Inscription::pointer)
+        .collect::<Vec<Option<u64>>>(),
+      [None, Some(546), Some(1092)],
+    );
+  }
+
+  #[test]
+  fn packed_pointers_stay_within_their_outputs() {
+    assert_eq!(
+      point(
+        vec![
+          inscription("text/plain", "foo").with_pointer(100),
+          inscription("text/plain", "bar").with_pointer(100),
+          inscription("text/plain", "baz"),
+        ],
+        &[
+          Amount::from_sat(546),
+          Amount::from_sat(1000),
+          Amount::from_sat(546)
+        ],
+      )
+      .iter()
+      .map(Inscription::pointer)
+      .collect::<Vec<Option<u64>>>(),
+      [Some(100), Some(646), Some(1546)],
+    );
+  }
+
+  #[test]
+  fn zero_service_fee_leaves_out_service_output() {
+    for repeat in [1, 2] {
+      let mut params = params(&[(outpoint(1), 100_000)]);
+      params.service_fee = Amount::ZERO;
+
+      let inscribed = inscribe(params, vec![inscription("text/plain", "foo"); repeat]).unwrap();
+
+      assert_eq!(inscribed.service_fee, 0);
+      assert!(inscribed
+        .reveals
+        .iter()
+        .all(|reveal| reveal.output.len() == 1));
+    }
+
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.service_fee = Amount::from_sat(3000);
+    let inscribed = inscribe(params, vec![inscription("text/plain", "foo")]).unwrap();
+    assert_eq!(inscribed.reveals[0].output[1], tx_out(3000, change(2)));
+  }
+
+  #[test]
+  fn component_fee_rates_default_to_fee_rate() {
+    let fee_rate = FeeRate::try_from(5.0).unwrap();
+    assert_eq!(
+      component_fee_rates(fee_rate, None, None).unwrap(),
+      (fee_rate, fee_rate)
+    );
+    assert_eq!(
+      component_fee_rates(fee_rate, Some(FeeRate::try_from(20.0).unwrap()), None).unwrap(),
+      (FeeRate::try_from(20.0).unwrap(), fee_rate)
+    );
+  }
+
+  #[test]
+  fn packing_costs_less_than_a_reveal_each() {
+    let inscriptions = (0..10)
+      .map(|i| inscription("text/plain", format!("item {i}")))
+      .collect::<Vec<Inscription>>();
+
+    let total_fee = |groups: Vec<usize>| {
+      let mut params = params(&[(outpoint(1), 100_000)]);
+      params.groups = groups;
+      let utxos = params.utxos.clone();
+      let inscribed = inscribe(params, inscriptions.clone()).unwrap();
+      fee(&inscribed.commit, &utxos) + inscribed.network_fee
+    };
+
+    let ungrouped = total_fee(Vec::new());
+    assert_eq!(total_fee(vec![1; 10]), ungrouped);
+
+    let grouped = total_fee(vec![10]);
+    assert!(
+      grouped < ungrouped / 2,
+      "grouped fee {grouped} not under half of ungrouped fee {ungrouped}"
+    );
+    assert!(total_fee(vec![5, 5]) < ungrouped);
+    assert!(total_fee(vec![5, 5]) > grouped);
+  }
+
+  #[test]
+  fn groups_must_cover_inscriptions() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.groups = vec![1, 2];
+
+    assert_eq!(
+      inscribe(params, vec![inscription("text/plain", "foo"); 2])
+        .unwrap_err()
+        .to_string(),
+      "groups of [1, 2] inscriptions given for 2 inscriptions",
+    );
+  }
+
+  #[test]
+  fn reveal_weight_matches_signed_reveal() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.groups = vec![2];
+    params.service_fee = Amount::ZERO;
+
+    let inscriptions = vec![
+      inscription("text/plain", "foo"),
+      inscription("text/plain", "bar"),
+    ];
+
+    let inscribed = inscribe(params, inscriptions.clone()).unwrap();
+
+    assert_eq!(
+      reveal_weight(
+        &point(inscriptions, &[Amount::from_sat(546); 2]),
+        inscribed.reveals[0].output.clone(),
+      ),
+      inscribed.reveals[0].weight(),
+    );
+  }
+
+  #[test]
+  fn component_fee_rates_respect_relay_floor() {
+    assert_eq!(
+      component_fee_rates(
+        FeeRate::try_from(5.0).unwrap(),
+        None,
+        Some(FeeRate::try_from(0.5).unwrap())
+      )
+      .unwrap_err()
+      .downcast::<BuildError>()
+      .unwrap(),
+      BuildError::FeeRateBelowRelayFloor {
+        component: "reveal",
+        fee_rate: 0.5,
+      }
+    );
+    assert!(component_fee_rates(FeeRate::try_from(0.5).unwrap(), None, None).is_err());
+  }
+
+  #[test]
+  fn total_fee_at_max_passes() {
+    assert_eq!(check_total_fee(3000, 546, 1454, Some(5000)), Ok(()));
+    assert_eq!(check_total_fee(3000, 546, 1_000_000, None), Ok(()));
+  }
+
+  #[test]
+  fn total_fee_one_sat_over_max_fails() {
+    assert_eq!(
+      check_total_fee(3000, 546, 1455, Some(5000)),
+      Err(BuildError::TotalFeeAboveMax {
+        service_fee: 3000,
+        satpoint_fee: 546,
+        network_fee: 1455,
+        max: 5000,
+      })
+    );
+  }
+
+  #[test]
+  fn buffered_fee_rate_adds_headroom() {
+    let fee_rate = FeeRate::try_from(5.0).unwrap();
+    assert_eq!(buffered_fee_rate(fee_rate, None).unwrap(), fee_rate);
+    assert_eq!(
+      buffered_fee_rate(fee_rate, Some(FeeRate::try_from(2.5).unwrap())).unwrap(),
+      FeeRate::try_from(7.5).unwrap()
+    );
+  }
+
+  #[test]
+  fn commit_and_reveal_pay_their_own_fee_rates() {
+    let utxos = [(outpoint(1), 100_000)];
+    let mut params = params(&utxos);
+    params.commit_fee_rate = FeeRate::try_from(10.0).unwrap();
+    params.reveal_fee_rate = FeeRate::try_from(2.0).unwrap();
+
+    let inscribed = inscribe(params.clone(), vec![inscription("text/plain", "foo")]).unwrap();
+
+    let commit_fee_rate = fee(&inscribed.commit, &params.utxos) as f64
+      / estimate_vsize(&inscribed.commit, AddressType::P2wpkh) as f64;
+    let reveal_fee_rate = inscribed.network_fee as f64 / inscribed.reveals[0].vsize() as f64;
+
+    assert!((9.5..10.5).contains(&commit_fee_rate), "{commit_fee_rate}");
+    assert!((1.5..2.5).contains(&reveal_fee_rate), "{reveal_fee_rate}");
+  }
+
+  #[test]
+  fn commit_outputs_cover_reveal_fees() {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![inscription("text/plain", "foo"); 2],
+    )
+    .unwrap();
+
+    let reveal_fees = inscribed
+      .reveals
+      .iter()
+      .enumerate()
+      .map(|(i, reveal)| {
+        inscribed.commit.output[i].value - reveal.output.iter().map(|out| out.value).sum::<u64>()
+      })
+      .sum::<u64>();
+
+    assert_eq!(reveal_fees, inscribed.network_fee);
+  }
+
+  fn assert_breakdown_sums_to_commit_outputs(inscribed: &Inscribed) {
+    assert_eq!(inscribed.breakdown.len(), inscribed.reveals.len());
+    for (i, parts) in inscribed.breakdown.iter().enumerate() {
+      assert_eq!(
+        parts.postage + parts.reveal_fee + parts.service_fee,
+        inscribed.commit.output[i].value
+      );
+    }
+  }
+
+  #[test]
+  fn single_mint_breakdown() {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![inscription("text/plain", "foo")],
+    )
+    .unwrap();
+
+    assert_breakdown_sums_to_commit_outputs(&inscribed);
+    assert_eq!(
+      inscribed.breakdown[0],
+      CommitOutputBreakdown {
+        postage: 546,
+        reveal_fee: inscribed.network_fee,
+        service_fee: 1000,
+      }
+    );
+  }
+
+  #[test]
+  fn repeat_mint_breakdown() {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![inscription("text/plain", "foo"); 3],
+    )
+    .unwrap();
+
+    assert_breakdown_sums_to_commit_outputs(&inscribed);
+    assert_eq!(inscribed.breakdown[0].service_fee, 3000);
+    assert!(inscribed.breakdown[1..]
+      .iter()
+      .all(|parts| parts.service_fee == 0 && parts.postage == 546));
+    assert_eq!(
+      inscribed
+        .breakdown
+        .iter()
+        .map(|parts| parts.reveal_fee)
+        .sum::<u64>(),
+      inscribed.network_fee
+    );
+  }
+
+  #[test]
+  fn inscribed_utxos_are_not_spent() {
+    let mut params = params(&[(outpoint(1), 100_000), (outpoint(2), 100_000)]);
+    params.inscriptions = [(satpoint(1, 0), inscription_id(1))].into();
+
+    let inscribed = inscribe(params, vec![inscription("text/plain", "foo")]).unwrap();
+
+    assert!(inscribed
+      .commit
+      .input
+      .iter()
+      .all(|input| input.previous_output != outpoint(1)));
+  }
+
+  #[test]
+  fn inscribed_satpoint_is_rejected() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.satpoints = vec![satpoint(1, 0)];
+    params.inscriptions = [(satpoint(1, 0), inscription_id(1))].into();
+
+    assert_eq!(
+      inscribe(params, vec![inscription("text/plain", "foo")])
+        .unwrap_err()
+        .to_string(),
+      format!("sat at {} already inscribed", satpoint(1, 0)),
+    );
+  }
+
+  #[test]
+  fn no_cardinal_utxos() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.inscriptions = [(satpoint(1, 0), inscription_id(1))].into();
+
+    assert_eq!(
+      inscribe(params, vec![inscription("text/plain", "foo")])
+        .unwrap_err()
+        .to_string(),
+      "wallet contains no cardinal utxos",
+    );
+  }
+
+  #[test]
+  fn insufficient_funds_reports_shortfall() {
+    let error = inscribe(
+      params(&[(outpoint(1), 1_000)]),
+      vec![inscription("text/plain", "foo")],
+    )
+    .unwrap_err();
+
+    assert_matches!(
+      error.downcast::<BuildError>().unwrap(),
+      BuildError::InsufficientFunds {
+        available: 1_000,
+        ..
+      }
+    );
+  }
+
+  #[test]
+  fn estimate_matches_inscribe() {
+    let params = params(&[(outpoint(1), 100_000)]);
+    let inscriptions = vec![inscription("text/plain", "foo"); 2];
+
+    let estimate = estimate(&params, &inscriptions).unwrap();
+    let inscribed = inscribe(params.clone(), inscriptions).unwrap();
+    let commit_fee = fee(&inscribed.commit, &params.utxos);
+
+    assert_eq!(estimate.service_fee, inscribed.service_fee);
+    assert_eq!(estimate.satpoint_fee, inscribed.satpoint_fee);
+    assert_eq!(estimate.network_fee, inscribed.network_fee + commit_fee);
+    assert_eq!(
+      estimate.total,
+      estimate.service_fee + estimate.satpoint_fee + estimate.network_fee
+    );
+    assert_eq!(estimate.available, 100_000);
+    assert_eq!(estimate.shortfall, 0);
+  }
+
+  #[test]
+  fn estimate_reports_shortfall_instead_of_failing() {
+    let estimate = estimate(
+      &params(&[(outpoint(1), 1_000)]),
+      &[inscription("text/plain", "foo")],
+    )
+    .unwrap();
+
+    assert_eq!(estimate.available, 1_000);
+    assert_eq!(estimate.shortfall, estimate.total - 1_000);
+    assert!(estimate.network_fee > 0);
+  }
+
+  #[test]
+  fn estimate_without_cardinal_utxos() {
+    let mut params = params(&[(outpoint(1), 100_000)]);
+    params.inscriptions = [(satpoint(1, 0), inscription_id(1))].into();
+
+    let estimate = estimate(&params, &[inscription("text/plain", "foo")]).unwrap();
+
+    assert_eq!(estimate.available, 0);
+    assert_eq!(estimate.shortfall, estimate.total);
+    assert_eq!(
+      estimate.total,
+      estimate.service_fee + estimate.satpoint_fee + estimate.network_fee
+    );
+  }
+
+  #[test]
+  fn reveal_weight_is_limited() {
+    let content = vec![0; 500_000];
+
+    assert!(inscribe(
+      params(&[(outpoint(1), 10_000_000)]),
+      vec![inscription("text/plain", &content)],
+    )
+    .unwrap_err()
+    .to_string()
+    .starts_with("reveal transaction weight greater than"));
+
+    let mut params = params(&[(outpoint(1), 10_000_000)]);
+    params.max_nonstandard_weight = Some(MAX_NONSTANDARD_WEIGHT);
+    assert!(inscribe(params.clone(), vec![inscription("text/plain", &content)]).is_ok());
+
+    params.max_nonstandard_weight = Some(450_000);
+    assert!(inscribe(params, vec![inscription("text/plain", &content)])
+      .unwrap_err()
+      .to_string()
+      .starts_with("reveal transaction weight greater than 450000 (max non-standard weight)"));
+  }
+
+  fn commit(inputs: &[u64], outputs: &[u64]) -> Transaction {
+    Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: inputs.iter().map(|n| tx_in(outpoint(*n))).collect(),
+      output: outputs
+        .iter()
+        .map(|value| tx_out(*value, recipient()))
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn inscribed_sats_within_first_input() {
+    let utxos = [(outpoint(1), Amount::from_sat(10_000))].into();
+
+    assert_eq!(
+      inscribed_sats(&commit(&[1], &[600, 700, 8_000]), &utxos, 2, 0).unwrap(),
+      [satpoint(1, 0), satpoint(1, 600)],
+    );
+  }
+
+  #[test]
+  fn inscribed_sats_across_inputs() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(1_000)),
+      (outpoint(2), Amount::from_sat(5_000)),
+    ]
+    .into();
+
+    assert_eq!(
+      inscribed_sats(&commit(&[1, 2], &[1_200, 700, 3_000]), &utxos, 3, 0).unwrap(),
+      [satpoint(1, 0), satpoint(2, 200), satpoint(2, 900)],
+    );
+  }
+
+  #[test]
+  fn inscribed_sats_after_padding_input() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(5_000)),
+      (outpoint(2), Amount::from_sat(300)),
+    ]
+    .into();
+
+    assert_eq!(
+      inscribed_sats(&commit(&[2, 1], &[800, 4_000]), &utxos, 1, 0).unwrap(),
+      [satpoint(2, 0)],
+    );
+    assert_eq!(
+      inscribed_sats(&commit(&[2, 1], &[200, 800, 4_000]), &utxos, 2, 0).unwrap(),
+      [satpoint(2, 0), satpoint(2, 200)],
+    );
+    assert_eq!(
+      inscribed_sats(&commit(&[2, 1], &[300, 800, 4_000]), &utxos, 2, 0).unwrap(),
+      [satpoint(2, 0), satpoint(1, 0)],
+    );
+  }
+
+  #[test]
+  fn inscribed_sats_requires_funded_outputs() {
+    let utxos = [(outpoint(1), Amount::from_sat(1_000))].into();
+
+    assert!(inscribed_sats(&commit(&[1], &[1_000, 500]), &utxos, 2, 0).is_err());
+    assert!(inscribed_sats(&commit(&[2], &[500]), &utxos, 1, 0).is_err());
+  }
+
+  #[test]
+  fn inscribed_sats_match_reveals() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(700)),
+      (outpoint(2), Amount::from_sat(100_000)),
+    ];
+    let mut params = params(&utxos.map(|(outpoint, amount)| (outpoint, amount.to_sat())));
+    params.satpoints = vec![satpoint(1, 0)];
+
+    let inscribed = inscribe(params, vec![inscription("text/plain", "foo"); 2]).unwrap();
+    let sats = inscribed_sats(&inscribed.commit, &utxos.into(), 2, 0).unwrap();
+
+    assert_eq!(sats[0], satpoint(1, 0));
+    assert_eq!(sats[1].outpoint, inscribed.commit.input[1].previous_output);
+  }
+
+  #[test]
+  fn transfer_pays_destination() {
+    let params = transfer_params();
+    let utxos = params.utxos.clone();
+
+    let transaction = transfer(params).unwrap();
+
+    assert_eq!(transaction.input[0].previous_output, outpoint(1));
+    assert!(transaction.output.contains(&tx_out(5_000, recipient())));
+    assert!(fee(&transaction, &utxos) > 0);
+  }
+
+  #[test]
+  fn change_goes_to_change_address_not_source() {
+    let source = change(0);
+    let custom = "tb1phsaern0qpcpqpv2h6cmu6fgae4y0lyx2tqhmqmgvv7c9whffm3rqjmlrqs"
+      .parse::<Address>()
+      .unwrap();
+    let params = CommitParams {
+      change: [custom.clone(), custom.clone()],
+      ..params(&[(outpoint(1), 100_000)])
+    };
+    let utxos = params.utxos.clone();
+
+    let inscribed = inscribe(params, vec![inscription("text/plain", "foo")]).unwrap();
+
+    let change_output = inscribed.commit.output.last().unwrap();
+    assert_eq!(change_output.script_pubkey, custom.script_pubkey());
+    assert!(inscribed
+      .commit
+      .output
+      .iter()
+      .all(|output| output.script_pubkey != source.script_pubkey()));
+
+    let psbt = psbt(&inscribed.commit, &utxos, |_| source.script_pubkey()).unwrap();
+    assert_eq!(
+      psbt.inputs[0].witness_utxo.as_ref().unwrap().script_pubkey,
+      source.script_pubkey()
+    );
+
+    let transaction = transfer(TransferParams {
+      change: [custom.clone(), custom.clone()],
+      ..transfer_params()
+    })
+    .unwrap();
+    assert!(transaction
+      .output
+      .iter()
+      .any(|output| output.script_pubkey == custom.script_pubkey()));
+    assert!(transaction
+      .output
+      .iter()
+      .all(|output| output.script_pubkey != source.script_pubkey()));
+  }
+
+  #[test]
+  fn transfer_keeps_every_sat_in_destination() {
+    let mut params = transfer_params();
+    params.postages = vec![
+      (satpoint(1, 0), TransactionBuilder::TARGET_POSTAGE),
+      (satpoint(2, 0), TransactionBuilder::TARGET_POSTAGE),
+    ];
+
+    let transaction = transfer(params).unwrap();
+
+    assert_eq!(transaction.input[0].previous_output, outpoint(1));
+    assert_eq!(transaction.input[1].previous_output, outpoint(2));
+    assert_eq!(transaction.output[0], tx_out(10_546, recipient()));
+    assert_eq!(
+      transaction.output[1].script_pubkey,
+      change(1).script_pubkey()
+    );
+  }
+
+  #[test]
+  fn transfer_preserves_inscription_offsets() {
+    let mut params = transfer_params();
+    params.postages = vec![
+      (satpoint(1, 2_000), TransactionBuilder::TARGET_POSTAGE),
+      (satpoint(2, 0), TransactionBuilder::TARGET_POSTAGE),
+    ];
+
+    let transaction = transfer(params).unwrap();
+
+    assert_eq!(transaction.output[0], tx_out(2_000, change(1)));
+    assert_eq!(transaction.output[1], tx_out(8_546, recipient()));
+  }
+
+  #[test]
+  fn transfer_refuses_dust_postage() {
+    let mut params = transfer_params();
+    params.postages = vec![(satpoint(1, 0), Amount::from_sat(100))];
+
+    assert!(transfer(params).is_err());
+  }
+
+  #[test]
+  fn transfer_applies_lock_time() {
+    let mut params = transfer_params();
+    params.locktime = Some(800_000);
+    params.rbf = false;
+
+    let transaction = transfer(params).unwrap();
+
+    assert_eq!(transaction.lock_time, PackedLockTime(800_000));
+    assert!(transaction
+      .input
+      .iter()
+      .all(|input| input.sequence == Sequence::ENABLE_LOCKTIME_NO_RBF));
+  }
+
+  #[test]
+  fn transfer_op_return_must_be_standard() {
+    let transaction = transfer(TransferParams {
+      op_return: Some("a".repeat(MAX_OP_RETURN_SIZE)),
+      ..transfer_params()
+    })
+    .unwrap();
+    assert!(transaction
+      .output
+      .iter()
+      .any(|output| output.script_pubkey.is_op_return()));
+
+    assert_eq!(
+      transfer(TransferParams {
+        op_return: Some("a".repeat(MAX_OP_RETURN_SIZE + 1)),
+        ..transfer_params()
+      })
+      .unwrap_err()
+      .downcast::<BuildError>()
+      .unwrap(),
+      BuildError::OpReturnTooLarge {
+        size: MAX_OP_RETURN_SIZE + 1,
+        limit: MAX_OP_RETURN_SIZE,
+      }
+    );
+  }
+
+  #[test]
+  fn transfer_destinations_must_match_postages() {
+    assert_eq!(
+      transfer(TransferParams {
+        destinations: vec![recipient(), change(2)],
+        ..transfer_params()
+      })
+      .unwrap_err()
+      .to_string(),
+      "2 destinations given for 1 outgoing sats",
+    );
+
+    let transaction = transfer(TransferParams {
+      destinations: vec![change(2)],
+      ..transfer_params()
+    })
+    .unwrap();
+    assert_eq!(transaction.output[0], tx_out(5_000, change(2)));
+  }
+
+  fn accelerate_params(value: u64, fee_rate: f64) -> AccelerateParams {
+    AccelerateParams {
+      parent: Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![tx_in(outpoint(1))],
+        output: vec![tx_out(value, change(0))],
+      },
+      parent_fee: 100,
+      vout: 0,
+      input_type: AddressType::P2wpkh,
+      fee_rate: FeeRate::try_from(fee_rate).unwrap(),
+      rbf: true,
+    }
+  }
+
+  #[test]
+  fn accelerate_lifts_the_package_to_the_fee_rate() {
+    let params = accelerate_params(10_000, 20.0);
+    let parent_txid = params.parent.txid();
+    let accelerated = accelerate(params).unwrap();
+
+    assert_eq!(
+      accelerated.transaction.input[0].previous_output,
+      OutPoint {
+        txid: parent_txid,
+        vout: 0,
+      }
+    );
+    assert_eq!(
+      accelerated.transaction.output,
+      vec![tx_out(10_000 - accelerated.network_fee, change(0))]
+    );
+    assert!(accelerated.package_fee_rate >= 20.0);
+  }
+
+  #[test]
+  fn accelerate_refuses_to_spend_below_dust() {
+    assert!(matches!(
+      accelerate(accelerate_params(1_000, 20.0))
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::InsufficientFunds {
+        available: 1_000,
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn accelerate_refuses_parents_paying_the_fee_rate() {
+    assert_regex_match!(
+      accelerate(accelerate_params(10_000, 0.5)).unwrap_err(),
+      "transaction .* already pays .* sat/vB, at or above 0.5 sat/vB",
+    );
+  }
+
+  fn bump_fee_params(change_value: u64, fee_rate: f64) -> BumpFeeParams {
+    BumpFeeParams {
+      original: Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+        output: vec![tx_out(546, recipient()), tx_out(change_value, change(0))],
+      },
+      utxos: [
+        (outpoint(1), Amount::from_sat(546)),
+        (outpoint(2), Amount::from_sat(change_value + 200)),
+      ]
+      .into(),
+      change: change(0),
+      input_type: AddressType::P2wpkh,
+      fee_rate: FeeRate::try_from(fee_rate).unwrap(),
+    }
+  }
+
+  #[test]
+  fn bump_fee_takes_the_bump_from_change() {
+    let params = bump_fee_params(10_000, 20.0);
+    let original = params.original.clone();
+    let bumped = bump_fee(params).unwrap();
+
+    assert_eq!(bumped.original_fee, 200);
+    assert!(bumped.network_fee > 200);
+    assert_eq!(bumped.transaction.input, original.input);
+    assert_eq!(
+      bumped.transaction.output,
+      vec![
+        tx_out(546, recipient()),
+        tx_out(10_000 - (bumped.network_fee - 200), change(0)),
+      ]
+    );
+  }
+
+  #[test]
+  fn bump_fee_pays_at_least_the_incremental_relay_fee() {
+    let params = bump_fee_params(10_000, 1.0);
+    let vsize = estimate_vsize(&params.original, AddressType::P2wpkh);
+    let bumped = bump_fee(params).unwrap();
+
+    assert_eq!(
+      bumped.network_fee,
+      200 + FeeRate(MIN_RELAY_FEE_RATE).fee(vsize).to_sat()
+    );
+  }
+
+  #[test]
+  fn bump_fee_refuses_change_below_dust() {
+    assert!(matches!(
+      bump_fee(bump_fee_params(1_000, 20.0))
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::InsufficientFunds {
+        available: 1_000,
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn bump_fee_requires_replaceability() {
+    let mut params = bump_fee_params(10_000, 20.0);
+    for input in &mut params.original.input {
+      input.sequence = Sequence::MAX;
+    }
+
+    assert_regex_match!(
+      bump_fee(params).unwrap_err(),
+      "transaction .* does not signal replaceability",
+    );
+  }
+
+  fn cancel_params(value: u64) -> CancelParams {
+    CancelParams {
+      input_type: AddressType::P2wpkh,
+      inputs: vec![outpoint(1)],
+      utxos: [(outpoint(1), Amount::from_sat(value))].into(),
+      source: change(0),
+      service: Some((change(2), Amount::from_sat(3000))),
+      fee_rate: FeeRate::try_from(1.0).unwrap(),
+      locktime: None,
+      rbf: true,
+      witness_sizes: BTreeMap::new(),
+    }
+  }
+
+  #[test]
+  fn cancel_returns_inputs_less_fees() {
+    let cancelled = cancel(cancel_params(10_000)).unwrap();
+
+    assert_eq!(cancelled.service_fee, 3000);
+    assert_eq!(
+      cancelled.transaction.output,
+      vec![
+        tx_out(10_000 - 3000 - cancelled.network_fee, change(0)),
+        tx_out(3000, change(2)),
+      ]
+    );
+  }
+
+  #[test]
+  fn cancel_service_fee_takes_what_is_left() {
+    let cancelled = cancel(cancel_params(2000)).unwrap();
+
+    assert_eq!(cancelled.service_fee, 2000 - cancelled.network_fee);
+    assert_eq!(cancelled.transaction.output[0].value, 0);
+  }
+
+  #[test]
+  fn psbt_uses_given_scripts() {
+    let transaction = transfer(transfer_params()).unwrap();
+
+    let psbt = psbt(&transaction, &transfer_params().utxos, |previous_output| {
+      if previous_output == outpoint(1) {
+        change(2).script_pubkey()
+      } else {
+        change(0).script_pubkey()
+      }
+    })
+    .unwrap();
+
+    assert_eq!(psbt.inputs[0].witness_utxo, Some(tx_out(10_000, change(2))));
+  }
+
+  #[test]
+  fn psbt_requires_known_inputs() {
+    let transaction = transfer(transfer_params()).unwrap();
+
+    assert!(psbt(&transaction, &BTreeMap::new(), |_| Script::new()).is_err());
+  }
+
+  #[test]
+  fn estimated_vsize_includes_witnesses() {
+    let transaction = transfer(transfer_params()).unwrap();
+
+    assert!(estimate_vsize(&transaction, AddressType::P2wpkh) > transaction.vsize());
+  }
+
+  fn bump_params(key_pair: UntweakedKeyPair, funding: u64) -> BumpParams {
+    let inscribed = inscribe(
+      CommitParams {
+        key_source: KeySource::Key(key_pair),
+        ..params(&[(outpoint(1), 100_000)])
+      },
+      vec![inscription("text/plain", "foo")],
+    )
+    .unwrap();
+
+    BumpParams {
+      reveal: inscribed.reveals[0].clone(),
+      commit_output: inscribed.commit.output[0].clone(),
+      funding: outpoint(2),
+      funding_output: tx_out(funding, change(0)),
+      input_type: AddressType::P2wpkh,
+      change: change(0),
+      fee_rate: FeeRate::try_from(10.0).unwrap(),
+      key_pair,
+    }
+  }
+
+  fn key_pair(n: u8) -> UntweakedKeyPair {
+    UntweakedKeyPair::from_seckey_slice(&Secp256k1::new(), &[n; 32]).unwrap()
+  }
+
+  #[test]
+  fn bumped_reveal_is_signed_again() {
+    let params = bump_params(key_pair(1), 20_000);
+    let reveal = params.reveal.clone();
+    let prevouts = [params.commit_output.clone(), params.funding_output.clone()];
+
+    let bumped = bump_reveal(params).unwrap();
+
+    assert_eq!(
+      bumped.input[0].previous_output,
+      reveal.input[0].previous_output
+    );
+    assert_eq!(bumped.input[1].previous_output, outpoint(2));
+    assert!(bumped.input[1].witness.is_empty());
+    assert_eq!(bumped.output[..reveal.output.len()], reveal.output[..]);
+    assert_eq!(
+      bumped.output.last().unwrap().script_pubkey,
+      change(0).script_pubkey()
+    );
+
+    let witness = bumped.input[0].witness.to_vec();
+    let script = Script::from(witness[1].clone());
+    let signature_hash = SighashCache::new(&bumped)
+      .taproot_script_spend_signature_hash(
+        0,
+        &Prevouts::All(&prevouts),
+        TapLeafHash::from_script(&script, LeafVersion::TapScript),
+        SchnorrSighashType::Default,
+      )
+      .unwrap();
+
+    Secp256k1::new()
+      .verify_schnorr(
+        &Signature::from_slice(&witness[0]).unwrap(),
+        &secp256k1::Message::from_slice(signature_hash.as_inner()).unwrap(),
+        &XOnlyPublicKey::from_keypair(&key_pair(1)).0,
+      )
+      .unwrap();
+  }
+
+  #[test]
+  fn bump_requires_reveal_key() {
+    let params = BumpParams {
+      key_pair: key_pair(2),
+      ..bump_params(key_pair(1), 20_000)
+    };
+
+    assert!(bump_reveal(params)
+      .unwrap_err()
+      .to_string()
+      .contains("was not signed with the stored key"));
+  }
+
+  #[test]
+  fn bump_requires_funding_for_fee() {
+    let err = bump_reveal(bump_params(key_pair(1), 600)).unwrap_err();
+
+    assert!(matches!(
+      err.downcast_ref::<BuildError>(),
+      Some(BuildError::InsufficientFunds { .. })
+    ));
+  }
+
+  fn recover_params() -> RecoverParams {
+    let inscribed = inscribe(
+      params(&[(outpoint(1), 100_000)]),
+      vec![inscription("text/plain", "foo")],
+    )
+    .unwrap();
+
+    RecoverParams {
+      commit_output: OutPoint {
+        txid: inscribed.commit.txid(),
+        vout: 0,
+      },
+      output: inscribed.commit.output[0].clone(),
+      key_pair: inscribed.recovery_key_pairs[0],
+      destination: change(0),
+      fee_rate: FeeRate::try_from(2.0).unwrap(),
+    }
+  }
+
+  #[test]
+  fn recovery_sweeps_commit_output_by_key_path() {
+    let params = recover_params();
+    let output = params.output.clone();
+    let key_pair = params.key_pair;
+
+    let recovered = recover(params).unwrap();
+
+    assert_eq!(recovered.input.len(), 1);
+    assert_eq!(recovered.output.len(), 1);
+    assert_eq!(recovered.output[0].script_pubkey, change(0).script_pubkey());
+    assert!(recovered.output[0].value < output.value);
+
+    let witness = recovered.input[0].witness.to_vec();
+    assert_eq!(witness.len(), 1);
+    let signature_hash = SighashCache::new(&recovered)
+      .taproot_key_spend_signature_hash(0, &Prevouts::All(&[output]), SchnorrSighashType::Default)
+      .unwrap();
+
+    Secp256k1::new()
+      .verify_schnorr(
+        &Signature::from_slice(&witness[0]).unwrap(),
+        &secp256k1::Message::from_slice(signature_hash.as_inner()).unwrap(),
+        &key_pair.to_inner().x_only_public_key().0,
+      )
+      .unwrap();
+  }
+
+  #[test]
+  fn recovery_requires_the_commit_output_key() {
+    let params = RecoverParams {
+      key_pair: key_pair(1).tap_tweak(&Secp256k1::new(), None),
+      ..recover_params()
+    };
+
+    assert!(recover(params)
+      .unwrap_err()
+      .to_string()
+      .starts_with("recovery key does not unlock commit output"));
+  }
+}
@@ -0,0 +1,104 @@
+use serde_json::Value;
+
+// major types, RFC 8949 section 3.1
+const UNSIGNED: u8 = 0;
+const NEGATIVE: u8 = 1;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+const FLOAT64: u8 = 0xfb;
+
+/// Encodes `value` as CBOR, the format inscription metadata is kept in.
+/// Integers take the shortest head that fits, other numbers are 64-bit
+/// floats and maps keep the key order of `value`.
+pub(crate) fn from_json(value: &Value) -> Vec<u8> {
+  let mut buffer = Vec::new();
+  encode(value, &mut buffer);
+  buffer
+}
+
+fn encode(value: &Value, buffer: &mut Vec<u8>) {
+  match value {
+    Value::Null => buffer.push(NULL),
+    Value::Bool(false) => buffer.push(FALSE),
+    Value::Bool(true) => buffer.push(TRUE),
+    Value::Number(number) => {
+      if let Some(n) = number.as_u64() {
+        head(UNSIGNED, n, buffer);
+      } else if let Some(n) = number.as_i64() {
+        // -1 - n
+        head(NEGATIVE, n.unsigned_abs() - 1, buffer);
+      } else {
+        buffer.push(FLOAT64);
+        buffer.extend_from_slice(&number.as_f64().unwrap_or_default().to_be_bytes());
+      }
+    }
+    Value::String(text) => {
+      head(TEXT, text.len() as u64, buffer);
+      buffer.extend_from_slice(text.as_bytes());
+    }
+    Value::Array(items) => {
+      head(ARRAY, items.len() as u64, buffer);
+      for item in items {
+        encode(item, buffer);
+      }
+    }
+    Value::Object(entries) => {
+      head(MAP, entries.len() as u64, buffer);
+      for (key, value) in entries {
+        head(TEXT, key.len() as u64, buffer);
+        buffer.extend_from_slice(key.as_bytes());
+        encode(value, buffer);
+      }
+    }
+  }
+}
+
+fn head(major: u8, n: u64, buffer: &mut Vec<u8>) {
+  let bytes = n.to_be_bytes();
+  let (info, len) = match n {
+    0..=23 => (bytes[7], 0),
+    24..=0xff => (24, 1),
+    0x100..=0xffff => (25, 2),
+    0x1_0000..=0xffff_ffff => (26, 4),
+    _ => (27, 8),
+  };
+  buffer.push(major << 5 | info);
+  buffer.extend_from_slice(&bytes[8 - len..]);
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, serde_json::json};
+
+  fn encoded(value: Value) -> String {
+    hex::encode(from_json(&value))
+  }
+
+  // vectors from RFC 8949 appendix A
+  #[test]
+  fn encodes_rfc_vectors() {
+    assert_eq!(encoded(json!(0)), "00");
+    assert_eq!(encoded(json!(23)), "17");
+    assert_eq!(encoded(json!(24)), "1818");
+    assert_eq!(encoded(json!(1000)), "1903e8");
+    assert_eq!(encoded(json!(1000000)), "1a000f4240");
+    assert_eq!(encoded(json!(1000000000000u64)), "1b000000e8d4a51000");
+    assert_eq!(encoded(json!(-1)), "20");
+    assert_eq!(encoded(json!(-1000)), "3903e7");
+    assert_eq!(encoded(json!(1.1)), "fb3ff199999999999a");
+    assert_eq!(encoded(json!(false)), "f4");
+    assert_eq!(encoded(json!(true)), "f5");
+    assert_eq!(encoded(json!(null)), "f6");
+    assert_eq!(encoded(json!("")), "60");
+    assert_eq!(encoded(json!("IETF")), "6449455446");
+    assert_eq!(encoded(json!([])), "80");
+    assert_eq!(encoded(json!([1, [2, 3], [4, 5]])), "8301820203820405");
+    assert_eq!(encoded(json!({})), "a0");
+    assert_eq!(encoded(json!({"a": 1, "b": [2, 3]})), "a26161016162820203");
+  }
+}
@@ -31,6 +31,17 @@ impl Chain {
     }
   }
 
+  // mainnet keeps the historical port, other chains are offset so that one
+  // server per chain can run on a host without flags
+  pub fn default_server_port(self) -> u16 {
+    match self {
+      Self::Mainnet => 3080,
+      Self::Testnet => 13080,
+      Self::Signet => 23080,
+      Self::Regtest => 33080,
+    }
+  }
+
   pub fn default_mempool_url(self) -> &'static str {
     match self {
       // https://mempool.coming.chat/
@@ -0,0 +1,176 @@
+use {
+  super::*,
+  base64::Engine,
+  flate2::{read::GzDecoder, write::GzEncoder},
+  std::io::{Read, Write},
+};
+
+/// Compression of an inscription body, named as in HTTP Content-Encoding.
+/// The name goes into the envelope so explorers can decompress the body.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+  Br,
+  Gzip,
+}
+
+impl FromStr for ContentEncoding {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "br" => Ok(Self::Br),
+      "gzip" => Ok(Self::Gzip),
+      _ => bail!("invalid content encoding `{s}`, expected one of br or gzip"),
+    }
+  }
+}
+
+impl Display for ContentEncoding {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      Self::Br => write!(f, "br"),
+      Self::Gzip => write!(f, "gzip"),
+    }
+  }
+}
+
+impl ContentEncoding {
+  const BUFFER_SIZE: usize = 4096;
+  // every byte of the body is paid for, so always the best compression
+  const BROTLI_QUALITY: u32 = 11;
+  const BROTLI_WINDOW: u32 = 22;
+
+  pub(crate) fn compress(self, body: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      Self::Br => {
+        let mut writer = brotli::CompressorWriter::new(
+          Vec::new(),
+          Self::BUFFER_SIZE,
+          Self::BROTLI_QUALITY,
+          Self::BROTLI_WINDOW,
+        );
+        writer.write_all(body)?;
+        Ok(writer.into_inner())
+      }
+      Self::Gzip => {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(body)?;
+        Ok(encoder.finish()?)
+      }
+    }
+  }
+
+  pub(crate) fn decompress(self, body: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    match self {
+      Self::Br => {
+        brotli::Decompressor::new(body, Self::BUFFER_SIZE).read_to_end(&mut decompressed)?;
+      }
+      Self::Gzip => {
+        GzDecoder::new(body).read_to_end(&mut decompressed)?;
+      }
+    }
+    Ok(decompressed)
+  }
+}
+
+/// How a request writes binary content in its text fields. The content is
+/// decoded before it is inscribed, unlike `ContentEncoding`, which goes on
+/// chain.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+  Base64,
+}
+
+impl FromStr for ContentFormat {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "base64" => Ok(Self::Base64),
+      _ => bail!("invalid content encoding `{s}`, expected base64"),
+    }
+  }
+}
+
+impl ContentFormat {
+  /// The bytes `content` stands for, itself when there is no `format`.
+  pub(crate) fn decode(format: Option<Self>, content: String) -> Result<Vec<u8>> {
+    match format {
+      Some(Self::Base64) => base64::engine::general_purpose::STANDARD
+        .decode(content.trim())
+        .map_err(|err| anyhow!("content is not valid base64: {err}")),
+      None => Ok(content.into_bytes()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn from_str() {
+    assert_eq!(
+      "br".parse::<ContentEncoding>().unwrap(),
+      ContentEncoding::Br
+    );
+    assert_eq!(
+      "gzip".parse::<ContentEncoding>().unwrap(),
+      ContentEncoding::Gzip
+    );
+    assert!("deflate".parse::<ContentEncoding>().is_err());
+    assert_eq!(ContentEncoding::Br.to_string(), "br");
+    assert_eq!(
+      serde_json::from_str::<ContentEncoding>(r#""gzip""#).unwrap(),
+      ContentEncoding::Gzip
+    );
+  }
+
+  #[test]
+  fn round_trip() {
+    let body = r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#.repeat(20);
+    for encoding in [ContentEncoding::Br, ContentEncoding::Gzip] {
+      let compressed = encoding.compress(body.as_bytes()).unwrap();
+      assert!(compressed.len() < body.len());
+      assert_eq!(encoding.decompress(&compressed).unwrap(), body.as_bytes());
+    }
+  }
+
+  #[test]
+  fn compression_is_deterministic() {
+    for encoding in [ContentEncoding::Br, ContentEncoding::Gzip] {
+      assert_eq!(
+        encoding.compress(b"hello hello hello").unwrap(),
+        encoding.compress(b"hello hello hello").unwrap()
+      );
+    }
+  }
+
+  #[test]
+  fn base64_content_is_decoded() {
+    assert_eq!(
+      ContentFormat::decode(Some(ContentFormat::Base64), "iVBORw0KGgo=".into()).unwrap(),
+      b"\x89PNG\r\n\x1a\n"
+    );
+    assert_eq!(
+      ContentFormat::decode(None, "iVBORw0KGgo=".into()).unwrap(),
+      b"iVBORw0KGgo="
+    );
+    assert_regex_match!(
+      ContentFormat::decode(Some(ContentFormat::Base64), "not base64!".into()).unwrap_err(),
+      "content is not valid base64: .*"
+    );
+    assert_eq!(
+      serde_json::from_str::<ContentFormat>(r#""base64""#).unwrap(),
+      ContentFormat::Base64
+    );
+  }
+
+  #[test]
+  fn garbage_does_not_decompress() {
+    assert!(ContentEncoding::Gzip.decompress(b"not gzip").is_err());
+  }
+}
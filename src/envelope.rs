@@ -0,0 +1,235 @@
+use {
+  super::*,
+  bitcoin::{hashes::sha256, policy::MAX_STANDARD_TX_WEIGHT},
+};
+
+const CONTENT_TYPE_TAG: u8 = 1;
+const POINTER_TAG: u8 = 2;
+const PARENT_TAG: u8 = 3;
+const METADATA_TAG: u8 = 5;
+const METAPROTOCOL_TAG: u8 = 7;
+const CONTENT_ENCODING_TAG: u8 = 9;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Envelope {
+  pub content_type: Option<String>,
+  pub content_length: Option<usize>,
+  pub content_hash: Option<String>,
+  pub content_encoding: Option<String>,
+  /// The body length once decompressed, when it is in an encoding this
+  /// indexer can decompress.
+  pub decoded_content_length: Option<usize>,
+  pub pointer: Option<u64>,
+  pub parent: Option<String>,
+  pub metadata: Option<String>,
+  pub metaprotocol: Option<String>,
+  pub unrecognized_even_field: bool,
+}
+
+impl Envelope {
+  pub fn from_transaction(tx: &Transaction) -> Result<Self> {
+    let mut fields = Inscription::envelope_fields(tx)?;
+
+    let text = |value: Vec<u8>| String::from_utf8_lossy(&value).into_owned();
+
+    let body = fields.remove(&Vec::new());
+    let content_type = fields.remove(&vec![CONTENT_TYPE_TAG]).map(text);
+    let pointer = fields
+      .remove(&vec![POINTER_TAG])
+      .map(|value| Self::pointer(&value))
+      .transpose()?;
+    let parent = fields
+      .remove(&vec![PARENT_TAG])
+      .map(|value| Self::parent(&value))
+      .transpose()?;
+    let metadata = fields.remove(&vec![METADATA_TAG]).map(hex::encode);
+    let metaprotocol = fields.remove(&vec![METAPROTOCOL_TAG]).map(text);
+    let content_encoding = fields.remove(&vec![CONTENT_ENCODING_TAG]).map(text);
+    let decoded_content_length = match (&body, &content_encoding) {
+      (Some(body), Some(content_encoding)) => content_encoding
+        .parse::<ContentEncoding>()
+        .ok()
+        .and_then(|encoding| encoding.decompress(body).ok())
+        .map(|decoded| decoded.len()),
+      _ => None,
+    };
+
+    // even fields are rules this indexer does not know, it still indexes
+    // the inscription by its known fields but other indexers may not
+    let unrecognized_even_field = fields
+      .keys()
+      .any(|tag| tag.first().map(|lsb| lsb % 2 == 0).unwrap_or(false));
+
+    Ok(Self {
+      content_length: body.as_ref().map(Vec::len),
+      content_hash: body
+        .as_ref()
+        .map(|body| sha256::Hash::hash(body).to_string()),
+      content_type,
+      content_encoding,
+      decoded_content_length,
+      pointer,
+      parent,
+      metadata,
+      metaprotocol,
+      unrecognized_even_field,
+    })
+  }
+
+  pub fn is_standard(tx: &Transaction) -> bool {
+    tx.weight() <= usize::try_from(MAX_STANDARD_TX_WEIGHT).unwrap()
+  }
+
+  fn pointer(value: &[u8]) -> Result<u64> {
+    if value.len() > 8 {
+      bail!("pointer field of {} bytes is too long", value.len());
+    }
+    let mut bytes = [0; 8];
+    bytes[..value.len()].copy_from_slice(value);
+    Ok(u64::from_le_bytes(bytes))
+  }
+
+  fn parent(value: &[u8]) -> Result<String> {
+    if value.len() < 32 || value.len() > 36 {
+      bail!("parent field of {} bytes is invalid", value.len());
+    }
+    let txid = Txid::from_slice(&value[..32])?;
+    let mut index = [0; 4];
+    index[..value.len() - 32].copy_from_slice(&value[32..]);
+    Ok(format!("{txid}i{}", u32::from_le_bytes(index)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    bitcoin::{
+      blockdata::{opcodes, script},
+      PackedLockTime,
+    },
+  };
+
+  fn reveal(payload: &[&[u8]]) -> Transaction {
+    let mut builder = script::Builder::new()
+      .push_opcode(opcodes::OP_FALSE)
+      .push_opcode(opcodes::all::OP_IF)
+      .push_slice(b"ord");
+
+    for data in payload {
+      builder = builder.push_slice(data);
+    }
+
+    let script = builder.push_opcode(opcodes::all::OP_ENDIF).into_script();
+
+    Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![TxIn {
+        previous_output: outpoint(1),
+        script_sig: Script::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::from_vec(vec![script.into_bytes(), Vec::new()]),
+      }],
+      output: vec![tx_out(546, recipient())],
+    }
+  }
+
+  #[test]
+  fn single_inscription() {
+    let tx = reveal(&[&[1], b"text/plain;charset=utf-8", &[], b"ord"]);
+
+    assert_eq!(
+      Envelope::from_transaction(&tx).unwrap(),
+      Envelope {
+        content_type: Some("text/plain;charset=utf-8".into()),
+        content_length: Some(3),
+        content_hash: Some(sha256::Hash::hash(b"ord").to_string()),
+        content_encoding: None,
+        decoded_content_length: None,
+        pointer: None,
+        parent: None,
+        metadata: None,
+        metaprotocol: None,
+        unrecognized_even_field: false,
+      }
+    );
+    assert!(Envelope::is_standard(&tx));
+  }
+
+  #[test]
+  fn multi_field_envelope() {
+    let mut parent = [0xab; 32].to_vec();
+    parent.push(1);
+
+    let tx = reveal(&[
+      &[1],
+      b"text/plain",
+      &[3],
+      &parent,
+      &[5],
+      &[0xa0],
+      &[7],
+      b"brc-20",
+      &[2],
+      &[0x10, 0x27],
+      &[],
+      b"ord",
+    ]);
+
+    let envelope = Envelope::from_transaction(&tx).unwrap();
+
+    assert_eq!(envelope.content_type, Some("text/plain".into()));
+    assert_eq!(
+      envelope.parent,
+      Some(format!("{}i1", Txid::from_slice(&[0xab; 32]).unwrap()))
+    );
+    assert_eq!(envelope.metadata, Some("a0".into()));
+    assert_eq!(envelope.metaprotocol, Some("brc-20".into()));
+    assert_eq!(envelope.pointer, Some(10000));
+    assert!(!envelope.unrecognized_even_field);
+  }
+
+  #[test]
+  fn compressed_body() {
+    let content = "<svg></svg>".repeat(50);
+    let inscription = Inscription::from_content(
+      Chain::Mainnet,
+      "image/svg+xml",
+      content.clone().into_bytes(),
+      Some(ContentEncoding::Br),
+    )
+    .unwrap();
+    let mut tx = reveal(&[]);
+    tx.input[0].witness = inscription.to_witness();
+
+    let envelope = Envelope::from_transaction(&tx).unwrap();
+
+    assert_eq!(envelope.content_type, Some("image/svg+xml".into()));
+    assert_eq!(envelope.content_encoding, Some("br".into()));
+    assert!(envelope.content_length.unwrap() < content.len());
+    assert_eq!(envelope.decoded_content_length, Some(content.len()));
+    assert!(!envelope.unrecognized_even_field);
+  }
+
+  #[test]
+  fn unknown_content_encoding() {
+    let tx = reveal(&[&[9], b"zstd", &[], b"ord"]);
+
+    let envelope = Envelope::from_transaction(&tx).unwrap();
+
+    assert_eq!(envelope.content_encoding, Some("zstd".into()));
+    assert_eq!(envelope.decoded_content_length, None);
+  }
+
+  #[test]
+  fn no_envelope() {
+    let mut tx = reveal(&[]);
+    tx.input[0].witness = Witness::from_vec(vec![vec![0; 64]]);
+
+    assert_regex_match!(
+      Envelope::from_transaction(&tx).unwrap_err().to_string(),
+      "transaction has no inscription envelope: .*"
+    );
+  }
+}
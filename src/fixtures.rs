@@ -0,0 +1,230 @@
+//! Golden transactions for every builder.
+//!
+//! Each case builds from frozen utxos, fee rates and contents, with reveals
+//! signed under `KeySource::Fixed`, and compares the raw transactions to
+//! `tests/fixtures/<case>.txt`. Any change to fee math or transaction
+//! structure fails here. When a change is intended, regenerate the fixtures
+//! with `just update-fixtures` and review the diff.
+//!
+//! The scenarios bundled in `tests/scenarios` are checked the same way, as
+//! `tests/fixtures/scenario_<name>.txt`.
+
+use {
+  super::*,
+  crate::builder::{self, CancelParams, CommitParams, KeySource, TransferParams},
+  bitcoin::{
+    consensus::encode::serialize_hex, schnorr::UntweakedKeyPair, secp256k1::Secp256k1, AddressType,
+  },
+  std::{iter, path::Path},
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn check(case: &str, transactions: &[Transaction]) {
+  let actual = transactions
+    .iter()
+    .map(|transaction| serialize_hex(transaction) + "\n")
+    .collect::<String>();
+
+  let path = Path::new(FIXTURES).join(format!("{case}.txt"));
+
+  if env::var_os("ORD_UPDATE_FIXTURES").is_some() {
+    fs::create_dir_all(FIXTURES).unwrap();
+    fs::write(&path, actual).unwrap();
+    return;
+  }
+
+  let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+    panic!(
+      "cannot read fixture {}: {err}, create it with `just update-fixtures`",
+      path.display()
+    )
+  });
+
+  assert!(
+    expected == actual,
+    "{case} no longer matches {}, run `just update-fixtures` if the change is intended\nexpected:\n{expected}\nactual:\n{actual}",
+    path.display(),
+  );
+}
+
+fn key_source() -> KeySource {
+  KeySource::Fixed(UntweakedKeyPair::from_seckey_slice(&Secp256k1::new(), &[1; 32]).unwrap())
+}
+
+fn commit_params() -> CommitParams {
+  CommitParams {
+    input_type: AddressType::P2wpkh,
+    network: Network::Testnet,
+    satpoints: Vec::new(),
+    inscriptions: BTreeMap::new(),
+    utxos: [
+      (outpoint(1), Amount::from_sat(50_000)),
+      (outpoint(2), Amount::from_sat(80_000)),
+    ]
+    .into(),
+    change: [change(0), change(1)],
+    destination: recipient(),
+    groups: Vec::new(),
+    commit_fee_rate: FeeRate::try_from(5.0).unwrap(),
+    reveal_fee_rate: FeeRate::try_from(5.02).unwrap(),
+    max_nonstandard_weight: None,
+    service_address: change(2),
+    service_fee: Amount::from_sat(3000),
+    additional_service_fee: Amount::ZERO,
+    target_postage: Amount::from_sat(546),
+    recipients: Vec::new(),
+    parent: None,
+    key_source: key_source(),
+  }
+}
+
+fn inscribed(params: CommitParams, inscriptions: Vec<Inscription>) -> Vec<Transaction> {
+  let inscribed = builder::inscribe(params, inscriptions).unwrap();
+  iter::once(inscribed.commit)
+    .chain(inscribed.reveals)
+    .collect()
+}
+
+fn transfer_params() -> TransferParams {
+  TransferParams {
+    input_type: AddressType::P2wpkh,
+    postages: Vec::new(),
+    inscriptions: [(satpoint(1, 0), inscription_id(1))].into(),
+    utxos: [
+      (outpoint(1), Amount::from_sat(10_000)),
+      (outpoint(2), Amount::from_sat(20_000)),
+      (outpoint(3), Amount::from_sat(30_000)),
+    ]
+    .into(),
+    destination: recipient(),
+    destinations: Vec::new(),
+    additional_value: Amount::ZERO,
+    change: [change(0), change(1)],
+    fee_rate: FeeRate::try_from(3.0).unwrap(),
+    op_return: None,
+    locktime: None,
+    rbf: true,
+    witness_sizes: BTreeMap::new(),
+  }
+}
+
+#[test]
+fn mint_single() {
+  check(
+    "mint_single",
+    &inscribed(
+      commit_params(),
+      vec![inscription("text/plain;charset=utf-8", "single")],
+    ),
+  );
+}
+
+#[test]
+fn mint_repeat() {
+  check(
+    "mint_repeat",
+    &inscribed(
+      commit_params(),
+      vec![inscription("text/plain;charset=utf-8", "repeat"); 3],
+    ),
+  );
+}
+
+#[test]
+fn mints() {
+  check(
+    "mints",
+    &inscribed(
+      commit_params(),
+      vec![
+        inscription("text/plain;charset=utf-8", "foo"),
+        inscription("text/plain;charset=utf-8", "bar"),
+        inscription("text/plain;charset=utf-8", "baz"),
+      ],
+    ),
+  );
+}
+
+#[test]
+fn transfer_satpoint() {
+  check(
+    "transfer_satpoint",
+    &[builder::transfer(TransferParams {
+      postages: vec![(satpoint(2, 5_000), Amount::from_sat(15_000))],
+      ..transfer_params()
+    })
+    .unwrap()],
+  );
+}
+
+#[test]
+fn transfer_id() {
+  check(
+    "transfer_id",
+    &[builder::transfer(TransferParams {
+      postages: vec![(satpoint(1, 0), Amount::from_sat(10_000))],
+      ..transfer_params()
+    })
+    .unwrap()],
+  );
+}
+
+#[test]
+fn transfer_amount() {
+  check(
+    "transfer_amount",
+    &[builder::transfer(TransferParams {
+      postages: vec![(satpoint(3, 0), Amount::from_sat(12_345))],
+      additional_value: Amount::from_sat(1_000),
+      ..transfer_params()
+    })
+    .unwrap()],
+  );
+}
+
+#[test]
+fn cancel() {
+  check(
+    "cancel",
+    &[builder::cancel(CancelParams {
+      input_type: AddressType::P2wpkh,
+      inputs: vec![outpoint(1), outpoint(2)],
+      utxos: [
+        (outpoint(1), Amount::from_sat(10_000)),
+        (outpoint(2), Amount::from_sat(20_000)),
+      ]
+      .into(),
+      source: change(0),
+      service: Some((change(2), Amount::from_sat(3000))),
+      fee_rate: FeeRate::try_from(4.0).unwrap(),
+      locktime: None,
+      rbf: true,
+      witness_sizes: BTreeMap::new(),
+    })
+    .unwrap()
+    .transaction],
+  );
+}
+
+#[test]
+fn fixed_keys_are_reproducible() {
+  assert_eq!(
+    inscribed(commit_params(), vec![inscription("text/plain", "foo")]),
+    inscribed(commit_params(), vec![inscription("text/plain", "foo")]),
+  );
+}
+
+#[test]
+fn scenarios() {
+  for (name, path) in scenario::bundled().unwrap() {
+    let report = scenario::Scenario::load(&path).unwrap().run().unwrap();
+    check(
+      &format!("scenario_{name}"),
+      &report
+        .transactions()
+        .map(|transaction| consensus::deserialize(&hex::decode(&transaction.hex).unwrap()).unwrap())
+        .collect::<Vec<Transaction>>(),
+    );
+  }
+}
@@ -1,6 +1,7 @@
-use bitcoin::hashes::hex::FromHex;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, HashEngine};
 use mysql::prelude::*;
-use mysql::{params, Opts, OptsBuilder, PooledConn};
+use mysql::{params, Opts, OptsBuilder, PooledConn, TxOpts};
 use {
   self::{
     entry::{
@@ -10,25 +11,67 @@ use {
     updater::Updater,
   },
   super::*,
-  crate::wallet::Wallet,
-  bitcoin::{blockdata::transaction::Transaction, BlockHeader},
+  crate::{
+    builder::CommitOutputBreakdown,
+    manifest::Manifest,
+    subcommand::wallet::{error::BuildError, unsafe_destination::UnsafeDestination},
+    wallet::Wallet,
+  },
+  bitcoin::{
+    blockdata::transaction::Transaction,
+    secp256k1::rand::{self, RngCore},
+    BlockHeader,
+  },
   bitcoincore_rpc::{json::GetBlockHeaderResult, Client},
-  chrono::SubsecRound,
+  chrono::{NaiveDate, SubsecRound},
+  clap::ValueEnum,
   indicatif::{ProgressBar, ProgressStyle},
   log::log_enabled,
   redb::{Database, ReadableTable, Table, TableDefinition, WriteStrategy, WriteTransaction},
-  reqwest,
   std::collections::HashMap,
   std::sync::atomic::{self, AtomicBool},
 };
 
+mod block_stats;
 mod entry;
 mod fetcher;
 mod rtx;
+mod snapshot;
+mod tip_cache;
 mod updater;
+mod upstream;
+
+use self::tip_cache::TipCache;
+pub use self::{
+  block_stats::{block_timing_stats, BlockTimingStats, BlockTimings},
+  tip_cache::cache_invalidation_counts,
+  upstream::{node_error_counts, NodeUnavailable},
+};
+
+/// Address utxo responses, by url.
+static UTXOS: TipCache<String, String> = TipCache::new("utxos", Duration::from_secs(2));
+
+/// Fee estimates, by confirmation target.
+static FEE_ESTIMATES: TipCache<u16, Option<f64>> =
+  TipCache::new("fee_estimates", Duration::from_secs(30));
+
+/// The node's getmempoolinfo, which changes between blocks too.
+static MEMPOOL_INFO: TipCache<(), serde_json::Value> =
+  TipCache::new("mempool_info", Duration::from_secs(5));
+
+/// Pages of inscriptions by address as served by the query endpoint, keyed by
+/// address, cursor, limit and content type filter.
+static INSCRIPTION_SUMMARIES: TipCache<
+  (String, Option<String>, u32, Option<String>),
+  AddressInscriptionsPage,
+> = TipCache::new("inscription_summaries", Duration::from_secs(10));
 
 const SCHEMA_VERSION: u64 = 3;
 
+/// Rows at or above `tip - MAX_REORG_DEPTH` may still be rolled back and are
+/// never pruned.
+pub const MAX_REORG_DEPTH: u64 = 6;
+
 macro_rules! define_table {
   ($name:ident, $key:ty, $value:ty) => {
     const $name: TableDefinition<$key, $value> = TableDefinition::new(stringify!($name));
@@ -36,6 +79,7 @@ macro_rules! define_table {
 }
 
 define_table! { HEIGHT_TO_BLOCK_HASH, u64, &BlockHashValue }
+define_table! { HEIGHT_TO_PARTIALLY_PARSED, u64, u64 }
 define_table! { INSCRIPTION_ID_TO_INSCRIPTION_ENTRY, &InscriptionIdValue, InscriptionEntryValue }
 define_table! { INSCRIPTION_ID_TO_SATPOINT, &InscriptionIdValue, &SatPointValue }
 define_table! { INSCRIPTION_NUMBER_TO_INSCRIPTION_ID, u64, &InscriptionIdValue }
@@ -60,8 +104,7 @@ pub struct ConstructTransaction {
 
 impl Encodable for ConstructTransaction {
   fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
-    let mut len = 0;
-    u8::try_from(self.pre_outputs.outputs.len())
+    let mut len = u8::try_from(self.pre_outputs.outputs.len())
       .expect("Len err")
       .consensus_encode(w)?;
     for i in &self.pre_outputs.outputs {
@@ -82,1830 +125,8448 @@ pub struct MysqlInscription {
   pub inscription_id: InscriptionId,
   pub new_satpoint: SatPoint,
   pub new_address: String,
+  pub created: bool,
 }
 
-impl MysqlDatabase {
-  pub fn new(
-    host: Option<String>,
-    username: Option<String>,
-    password: Option<String>,
-    network: Network,
-  ) -> Result<MysqlDatabase> {
-    let opts_builder = OptsBuilder::new()
-      .ip_or_hostname(host)
-      .user(username)
-      .pass(password)
-      .db_name(Some(Self::get_database(network)));
-    let pool =
-      mysql::Pool::new::<Opts>(opts_builder.into()).map_err(|_| anyhow!("Create pool fail"))?;
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct ContentTypeCount {
+  pub count: u64,
+  pub total_bytes: u64,
+}
 
-    Ok(MysqlDatabase { pool, network })
+impl ContentTypeCount {
+  const MAX_CONTENT_TYPE_LEN: usize = 255;
+
+  /// Adds a new inscription to `counts`. Inscriptions without a content type
+  /// are counted under the empty string, long ones are cut to fit the column.
+  pub(crate) fn record(counts: &mut BTreeMap<String, Self>, inscription: &Inscription) {
+    let content_type = inscription
+      .content_type()
+      .unwrap_or_default()
+      .chars()
+      .take(Self::MAX_CONTENT_TYPE_LEN)
+      .collect();
+    let count = counts.entry(content_type).or_default();
+    count.count += 1;
+    count.total_bytes += inscription.content_length().unwrap_or(0) as u64;
   }
+}
 
-  pub fn get_conn(&self) -> Result<PooledConn> {
-    self.pool.get_conn().map_err(|_| anyhow!("Connect fail"))
-  }
+/// Checksums of the inscriptions indexed per block, so that two
+/// deployments can compare their indexes without listing them. A block's
+/// checksum is the sha256 of its new inscription ids in number order, each
+/// followed by a newline. The rolling checksum at a height is the sha256 of
+/// the previous height's rolling checksum, the height as 8 little endian
+/// bytes and the block checksum, starting from 32 zero bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct InscriptionChecksum {
+  pub(crate) block: sha256::Hash,
+  pub(crate) rolling: sha256::Hash,
+}
 
-  pub fn get_database(network: Network) -> String {
-    match network {
-      Network::Bitcoin => "ord_mainnet".to_owned(),
-      Network::Testnet => "ord_testnet".to_owned(),
-      Network::Signet => todo!(),
-      Network::Regtest => "ord_regtest".to_owned(),
+impl InscriptionChecksum {
+  pub(crate) fn new(
+    previous: Option<sha256::Hash>,
+    height: u64,
+    inscription_ids: &[InscriptionId],
+  ) -> Self {
+    let mut engine = sha256::Hash::engine();
+    for inscription_id in inscription_ids {
+      engine.input(inscription_id.to_string().as_bytes());
+      engine.input(b"\n");
     }
-  }
+    let block = sha256::Hash::from_engine(engine);
 
-  pub fn get_whitelist_table(&self) -> String {
-    "INSCRIPTION_WHITELIST".to_owned()
+    let mut engine = sha256::Hash::engine();
+    engine.input(previous.unwrap_or_else(sha256::Hash::all_zeros).as_ref());
+    engine.input(&height.to_le_bytes());
+    engine.input(block.as_ref());
+
+    Self {
+      block,
+      rolling: sha256::Hash::from_engine(engine),
+    }
   }
+}
 
-  fn _is_whitelist(&self, new_address: &String) -> Result<bool> {
-    let tb = self.get_whitelist_table();
-    let mut conn = self.get_conn()?;
-    let query = format!("SELECT * FROM {} WHERE new_address = '{}'", tb, new_address);
-    let result: Vec<mysql::Row> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
-    if !result.is_empty() {
-      Ok(true)
-    } else {
-      Ok(false)
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DailyInscriptionCount {
+  pub date: String,
+  pub inscriptions: u64,
+}
+
+/// What an index holds up to a height, for comparison with other indexes.
+/// The checksum only covers blocks from `checksum_from`, the first block
+/// indexed after checksums were introduced.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct IndexStats {
+  pub inscriptions: u64,
+  pub latest_number: Option<u64>,
+  pub height: u64,
+  pub block_hash: String,
+  pub checksum: String,
+  pub checksum_from: u64,
+  pub daily: Vec<DailyInscriptionCount>,
+}
+
+impl IndexStats {
+  pub const DAYS: u32 = 7;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlocklistKind {
+  Inscription,
+  ContentHash,
+}
+
+impl BlocklistKind {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Inscription => "inscription",
+      Self::ContentHash => "content_hash",
     }
   }
 
-  pub fn is_whitelist(&self, new_address: &String) -> bool {
-    self._is_whitelist(new_address).unwrap_or(false)
+  /// Checks that `value` is an inscription id or a sha256 content hash and
+  /// returns it in the form stored in the blocklist.
+  pub fn normalize(self, value: &str) -> Result<String> {
+    Ok(match self {
+      Self::Inscription => InscriptionId::from_str(value)
+        .map_err(|err| anyhow!("invalid inscription id `{value}`: {err}"))?
+        .to_string(),
+      Self::ContentHash => bitcoin::hashes::sha256::Hash::from_str(value)
+        .map_err(|err| anyhow!("invalid content hash `{value}`: {err}"))?
+        .to_string(),
+    })
   }
+}
 
-  pub fn get_inscription_table(&self) -> String {
-    "INSCRIPTION_ID_AND_SATPOINT".to_owned()
+/// What a voucher takes off the service fee of the mint that redeems it.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoucherPolicy {
+  Free,
+  Discount(u8),
+}
+
+impl VoucherPolicy {
+  fn discount_percent(self) -> Result<u8> {
+    match self {
+      Self::Free => Ok(100),
+      Self::Discount(percent) if (1..100).contains(&percent) => Ok(percent),
+      Self::Discount(percent) => bail!("invalid voucher discount of {percent}%, expected 1 to 99"),
+    }
   }
 
-  pub fn get_inscription_by_address(
-    &self,
-    new_address: &String,
-  ) -> Result<BTreeMap<SatPoint, InscriptionId>> {
-    let tb = self.get_inscription_table();
-    let query = format!("SELECT * FROM {} WHERE new_address = '{}'", tb, new_address);
-    let mut conn = self.get_conn()?;
-    let result: Vec<mysql::Row> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
-    let mut map: BTreeMap<SatPoint, InscriptionId> = BTreeMap::new();
-    for row in result {
-      let inscription_id = SatPoint::from_str(
-        &row
-          .get::<String, _>("new_satpoint")
-          .ok_or(anyhow!("Row inscription_id not exist"))?,
-      )?;
-      let new_satpoint = InscriptionId::from_str(
-        &row
-          .get::<String, _>("inscription_id")
-          .ok_or(anyhow!("Row new_satpoint not exist"))?,
-      )?;
-      map.insert(inscription_id, new_satpoint);
+  fn from_discount_percent(percent: u8) -> Self {
+    if percent >= 100 {
+      Self::Free
+    } else {
+      Self::Discount(percent)
     }
-    Ok(map)
   }
 
-  pub fn insert_inscriptions(&self, data: Vec<MysqlInscription>) -> Result {
-    if data.is_empty() {
-      return Ok(());
-    };
+  pub fn apply(self, service_fee: Amount) -> Amount {
+    match self {
+      Self::Free => Amount::ZERO,
+      Self::Discount(percent) => {
+        Amount::from_sat(service_fee.to_sat() * u64::from(100 - percent.min(100)) / 100)
+      }
+    }
+  }
+}
 
-    let tb = self.get_inscription_table();
-    let query = format!(
-      "INSERT INTO {} (inscription_id, new_satpoint, new_address)
-       VALUES (:inscription_id, :new_satpoint, :new_address)
-       ON DUPLICATE KEY UPDATE inscription_id = :inscription_id , new_satpoint = :new_satpoint, new_address = :new_address",
-      tb
-    );
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct VoucherCounts {
+  pub remaining: u64,
+  pub redeemed: u64,
+  pub expired: u64,
+}
 
-    let mut conn = self.get_conn()?;
+/// How many inscriptions a source has minted through the service on one UTC
+/// day, against its daily limit.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MintQuota {
+  pub source: String,
+  pub day: NaiveDate,
+  pub used: u64,
+  /// `None` when the source is exempt or no quota is configured.
+  pub limit: Option<u64>,
+  pub exempt: bool,
+  pub reset_at: DateTime<Utc>,
+}
 
-    conn
-      .query_drop("START TRANSACTION")
-      .map_err(|_| anyhow!("Create transaction fail"))?;
-    for item in data.iter() {
-      conn
-        .exec_drop(
-          query.clone(),
-          params! {
-            "inscription_id" => format!("{}", item.inscription_id),
-            "new_satpoint" =>  format!("{}", item.new_satpoint),
-            "new_address" => item.new_address.clone(),
-          },
-        )
-        .map_err(|_| anyhow!("Execute transaction fail"))?;
-    }
-    conn
-      .query_drop("COMMIT")
-      .map_err(|_| anyhow!("Commit transaction fail"))?;
-    Ok(())
+impl MintQuota {
+  /// The UTC day a mint at `now` counts against.
+  pub fn day(now: DateTime<Utc>) -> NaiveDate {
+    now.date_naive()
+  }
+
+  /// When the counter of `day` stops applying, midnight UTC after it.
+  pub fn reset_at(day: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&day.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap())
   }
 }
 
-pub struct Index {
-  client: Client,
-  database: Database,
-  path: PathBuf,
-  first_inscription_height: u64,
-  genesis_block_coinbase_transaction: Transaction,
-  genesis_block_coinbase_txid: Txid,
-  height_limit: Option<u64>,
-  options: Options,
-  reorged: AtomicBool,
-  mysql_database: Option<Arc<MysqlDatabase>>,
+/// Inscriptions counted against a source before a build. Released again if
+/// the build fails, so only successful builds use up the quota.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MintQuotaReservation {
+  pub source: String,
+  pub day: NaiveDate,
+  pub count: u64,
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum List {
-  Spent,
-  Unspent(Vec<(u64, u64)>),
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ContentTypeStat {
+  pub date: String,
+  pub content_type: String,
+  pub count: u64,
+  pub total_bytes: u64,
 }
 
-#[derive(Copy, Clone)]
-#[repr(u64)]
-pub(crate) enum Statistic {
-  Schema = 0,
-  Commits = 1,
-  LostSats = 2,
-  OutputsTraversed = 3,
-  SatRanges = 4,
-  UnboundInscriptions = 5,
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JournalEntry {
+  pub(crate) method: String,
+  pub(crate) fee_rate: f64,
+  pub(crate) broadcast_height: u64,
+  pub(crate) confirmed_height: Option<u64>,
 }
 
-impl Statistic {
-  fn key(self) -> u64 {
-    self.into()
-  }
+/// How quickly transactions built by `method` at fee rates within
+/// `fee_rate_bucket` confirmed, in blocks after the tip they were broadcast at.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConfirmationStat {
+  pub method: String,
+  pub fee_rate_bucket: String,
+  pub broadcast: u64,
+  pub confirmed: u64,
+  pub p50_blocks: Option<u64>,
+  pub p90_blocks: Option<u64>,
+  pub unconfirmed_after_6_percent: Option<f64>,
 }
 
-impl From<Statistic> for u64 {
-  fn from(statistic: Statistic) -> Self {
-    statistic as u64
+impl ConfirmationStat {
+  const FEE_RATE_BUCKETS: [u64; 9] = [0, 1, 2, 5, 10, 20, 50, 100, 200];
+  const LATE_AFTER: u64 = 6;
+
+  fn bucket(fee_rate: f64) -> usize {
+    Self::FEE_RATE_BUCKETS
+      .iter()
+      .rposition(|lower| fee_rate >= *lower as f64)
+      .unwrap_or(0)
   }
-}
 
-#[derive(Serialize)]
-pub(crate) struct Info {
-  pub(crate) blocks_indexed: u64,
-  pub(crate) branch_pages: usize,
-  pub(crate) fragmented_bytes: usize,
-  pub(crate) index_file_size: u64,
-  pub(crate) index_path: PathBuf,
-  pub(crate) leaf_pages: usize,
-  pub(crate) metadata_bytes: usize,
-  pub(crate) outputs_traversed: u64,
-  pub(crate) page_size: usize,
-  pub(crate) sat_ranges: u64,
-  pub(crate) stored_bytes: usize,
-  pub(crate) transactions: Vec<TransactionInfo>,
-  pub(crate) tree_height: usize,
-  pub(crate) utxos_indexed: usize,
-}
+  fn bucket_label(bucket: usize) -> String {
+    match Self::FEE_RATE_BUCKETS.get(bucket + 1) {
+      Some(upper) => format!("{}-{upper}", Self::FEE_RATE_BUCKETS[bucket]),
+      None => format!("{}+", Self::FEE_RATE_BUCKETS[bucket]),
+    }
+  }
 
-#[derive(Serialize)]
-pub(crate) struct TransactionInfo {
-  pub(crate) starting_block_count: u64,
-  pub(crate) starting_timestamp: u128,
+  // nearest rank
+  fn percentile(sorted: &[u64], percent: usize) -> Option<u64> {
+    let rank = (sorted.len() * percent + 99) / 100;
+    sorted.get(rank.checked_sub(1)?).copied()
+  }
+
+  /// Percentiles are over confirmed transactions. The unconfirmed share only
+  /// counts transactions broadcast at least six blocks before `tip`, and
+  /// includes those that confirmed later than that.
+  pub(crate) fn aggregate(entries: Vec<JournalEntry>, tip: u64) -> Vec<Self> {
+    let mut groups: BTreeMap<(String, usize), Vec<JournalEntry>> = BTreeMap::new();
+    for entry in entries {
+      groups
+        .entry((entry.method.clone(), Self::bucket(entry.fee_rate)))
+        .or_default()
+        .push(entry);
+    }
+
+    groups
+      .into_iter()
+      .map(|((method, bucket), entries)| {
+        let mut delays = entries
+          .iter()
+          .filter_map(|entry| {
+            entry
+              .confirmed_height
+              .map(|height| height.saturating_sub(entry.broadcast_height))
+          })
+          .collect::<Vec<u64>>();
+        delays.sort_unstable();
+
+        let due = entries
+          .iter()
+          .filter(|entry| tip.saturating_sub(entry.broadcast_height) >= Self::LATE_AFTER)
+          .collect::<Vec<&JournalEntry>>();
+        let late = due
+          .iter()
+          .filter(|entry| {
+            entry.confirmed_height.map_or(true, |height| {
+              height.saturating_sub(entry.broadcast_height) > Self::LATE_AFTER
+            })
+          })
+          .count();
+
+        Self {
+          method,
+          fee_rate_bucket: Self::bucket_label(bucket),
+          broadcast: entries.len() as u64,
+          confirmed: delays.len() as u64,
+          p50_blocks: Self::percentile(&delays, 50),
+          p90_blocks: Self::percentile(&delays, 90),
+          unconfirmed_after_6_percent: if due.is_empty() {
+            None
+          } else {
+            Some(late as f64 * 100.0 / due.len() as f64)
+          },
+        }
+      })
+      .collect()
+  }
 }
 
-trait BitcoinCoreRpcResultExt<T> {
-  fn into_option(self) -> Result<Option<T>>;
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+  InscriptionCreated,
+  InscriptionReceived,
+  InscriptionSent,
 }
 
-impl<T> BitcoinCoreRpcResultExt<T> for Result<T, bitcoincore_rpc::Error> {
-  fn into_option(self) -> Result<Option<T>> {
+impl ActivityKind {
+  fn as_str(self) -> &'static str {
     match self {
-      Ok(ok) => Ok(Some(ok)),
-      Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::error::Error::Rpc(
-        bitcoincore_rpc::jsonrpc::error::RpcError { code: -8, .. },
-      ))) => Ok(None),
-      Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::error::Error::Rpc(
-        bitcoincore_rpc::jsonrpc::error::RpcError { message, .. },
-      )))
-        if message.ends_with("not found") =>
-      {
-        Ok(None)
-      }
-      Err(err) => Err(err.into()),
+      Self::InscriptionCreated => "inscription_created",
+      Self::InscriptionReceived => "inscription_received",
+      Self::InscriptionSent => "inscription_sent",
     }
   }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
-pub struct ListUnspentStatusEntry {
-  pub confirmed: bool,
-  pub block_height: Option<usize>,
-  pub block_hash: Option<bitcoin::BlockHash>,
-  pub block_time: Option<u32>,
+impl FromStr for ActivityKind {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "inscription_created" => Ok(Self::InscriptionCreated),
+      "inscription_received" => Ok(Self::InscriptionReceived),
+      "inscription_sent" => Ok(Self::InscriptionSent),
+      _ => bail!("invalid activity kind `{s}`"),
+    }
+  }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
-pub struct ListUnspentResultEntry {
-  pub txid: bitcoin::Txid,
-  pub vout: u32,
-  pub status: ListUnspentStatusEntry,
-  #[serde(with = "bitcoin::util::amount::serde::as_sat")]
-  pub value: Amount,
+/// Position of an event in the chain. `seq` orders events within a
+/// transaction, so feeds are stable however they are paged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ActivityCursor {
+  pub height: u64,
+  pub tx_index: u32,
+  pub seq: u32,
 }
 
-impl Index {
-  pub fn open(options: &Options) -> Result<Self> {
-    let client = options.bitcoin_rpc_client()?;
+impl Display for ActivityCursor {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "{}:{}:{}", self.height, self.tx_index, self.seq)
+  }
+}
 
-    let data_dir = options.data_dir()?;
+impl FromStr for ActivityCursor {
+  type Err = Error;
 
-    if let Err(err) = fs::create_dir_all(&data_dir) {
-      bail!("failed to create data dir `{}`: {err}", data_dir.display());
-    }
+  // `<HEIGHT>:<TX_INDEX>` pages before the whole transaction
+  fn from_str(s: &str) -> Result<Self> {
+    let parts = s.split(':').collect::<Vec<&str>>();
+    let (height, tx_index, seq) = match parts.as_slice() {
+      [height, tx_index] => (height, tx_index, "0"),
+      [height, tx_index, seq] => (height, tx_index, *seq),
+      _ => bail!("invalid cursor `{s}`, expected <HEIGHT>:<TX_INDEX>[:<SEQ>]"),
+    };
+    Ok(Self {
+      height: height.parse()?,
+      tx_index: tx_index.parse()?,
+      seq: seq.parse()?,
+    })
+  }
+}
 
-    let path = if let Some(path) = &options.index {
-      path.clone()
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ActivityPage {
+  pub events: Vec<Activity>,
+  pub next: Option<String>,
+}
+
+impl ActivityPage {
+  pub const DEFAULT_LIMIT: u32 = 20;
+  pub const MAX_LIMIT: u32 = 100;
+
+  // a short page is the last one
+  pub(crate) fn new(events: Vec<Activity>, limit: u32) -> Self {
+    let next = if events.len() == usize::try_from(limit).unwrap() {
+      events.last().map(|event| event.cursor.clone())
     } else {
-      data_dir.join("index.redb")
+      None
     };
+    Self { events, next }
+  }
+}
 
-    let database = match unsafe { Database::builder().open_mmapped(&path) } {
-      Ok(database) => {
-        let schema_version = database
-          .begin_read()?
-          .open_table(STATISTIC_TO_COUNT)?
-          .get(&Statistic::Schema.key())?
-          .map(|x| x.value())
-          .unwrap_or(0);
-
-        match schema_version.cmp(&SCHEMA_VERSION) {
-          cmp::Ordering::Less =>
-            bail!(
-              "index at `{}` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
-              path.display()
-            ),
-          cmp::Ordering::Greater =>
-            bail!(
-              "index at `{}` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
-              path.display()
-            ),
-          cmp::Ordering::Equal => {}
-        }
-
-        database
-      }
-      Err(redb::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
-        let database = unsafe {
-          Database::builder()
-            .set_write_strategy(if cfg!(test) {
-              WriteStrategy::Checksum
-            } else {
-              WriteStrategy::TwoPhase
-            })
-            .create_mmapped(&path)?
-        };
-        let tx = database.begin_write()?;
-
-        #[cfg(test)]
-        let tx = {
-          let mut tx = tx;
-          tx.set_durability(redb::Durability::None);
-          tx
-        };
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ActivityEvent {
+  pub(crate) address: String,
+  pub(crate) cursor: ActivityCursor,
+  pub(crate) kind: ActivityKind,
+  pub(crate) inscription_id: InscriptionId,
+  pub(crate) txid: Txid,
+  pub(crate) counterparty: Option<String>,
+}
 
-        tx.open_table(HEIGHT_TO_BLOCK_HASH)?;
-        tx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
-        tx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
-        tx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
-        tx.open_table(OUTPOINT_TO_VALUE)?;
-        tx.open_table(SATPOINT_TO_INSCRIPTION_ID)?;
-        tx.open_table(SAT_TO_INSCRIPTION_ID)?;
-        tx.open_table(SAT_TO_SATPOINT)?;
-        tx.open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?;
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Activity {
+  pub kind: ActivityKind,
+  pub inscription_id: InscriptionId,
+  pub txid: Txid,
+  pub height: u64,
+  pub tx_index: u32,
+  pub counterparty: Option<String>,
+  pub cursor: String,
+}
 
-        tx.open_table(STATISTIC_TO_COUNT)?
-          .insert(&Statistic::Schema.key(), &SCHEMA_VERSION)?;
+/// Where an inscription was created, in chain order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GenesisCursor {
+  pub height: u64,
+  pub tx_index: u32,
+  pub input_index: u32,
+}
 
-        if options.index_sats {
-          tx.open_table(OUTPOINT_TO_SAT_RANGES)?
-            .insert(&OutPoint::null().store(), [].as_slice())?;
-        }
+impl Display for GenesisCursor {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "{}:{}:{}", self.height, self.tx_index, self.input_index)
+  }
+}
 
-        tx.commit()?;
+impl FromStr for GenesisCursor {
+  type Err = Error;
 
-        database
-      }
-      Err(error) => return Err(error.into()),
+  fn from_str(s: &str) -> Result<Self> {
+    let [height, tx_index, input_index] = s.split(':').collect::<Vec<&str>>()[..] else {
+      bail!("invalid cursor `{s}`, expected <HEIGHT>:<TX_INDEX>:<INPUT_INDEX>");
     };
-
-    let genesis_block_coinbase_transaction =
-      options.chain().genesis_block().coinbase().unwrap().clone();
-
     Ok(Self {
-      genesis_block_coinbase_txid: genesis_block_coinbase_transaction.txid(),
-      client,
-      database,
-      path,
-      first_inscription_height: options.first_inscription_height(),
-      genesis_block_coinbase_transaction,
-      height_limit: options.height_limit,
-      reorged: AtomicBool::new(false),
-      options: options.clone(),
-      mysql_database: None,
+      height: height.parse()?,
+      tx_index: tx_index.parse()?,
+      input_index: input_index.parse()?,
     })
   }
+}
 
-  pub fn read_open(options: &Options) -> Result<Self> {
-    let client = options.bitcoin_rpc_client()?;
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InscriptionGenesis {
+  pub inscription_id: InscriptionId,
+  pub height: u64,
+  pub tx_index: u32,
+  pub input_index: u32,
+  pub content_type: Option<String>,
+  pub address: String,
+}
 
-    let data_dir = options.data_dir()?;
+impl InscriptionGenesis {
+  /// The inscriptions created in the block at `height`, from the block's
+  /// rows as they are written to the inscription table.
+  pub(crate) fn rows(
+    height: u64,
+    tx_indices: &HashMap<Txid, u32>,
+    data: &[MysqlInscription],
+    content_types: &HashMap<InscriptionId, Option<String>>,
+  ) -> Vec<Self> {
+    data
+      .iter()
+      .filter(|item| item.created)
+      .filter_map(|item| {
+        Some(Self {
+          inscription_id: item.inscription_id,
+          height,
+          tx_index: *tx_indices.get(&item.inscription_id.txid)?,
+          // envelopes are only read from the first input
+          input_index: 0,
+          content_type: content_types.get(&item.inscription_id).cloned().flatten(),
+          address: item.new_address.clone(),
+        })
+      })
+      .collect()
+  }
 
-    if let Err(err) = fs::create_dir_all(&data_dir) {
-      bail!("failed to create data dir `{}`: {err}", data_dir.display());
+  fn cursor(&self) -> GenesisCursor {
+    GenesisCursor {
+      height: self.height,
+      tx_index: self.tx_index,
+      input_index: self.input_index,
     }
+  }
+}
 
-    let path = if let Some(path) = &options.index {
-      path.clone()
-    } else {
-      data_dir.join("index.redb")
-    };
+/// The sha256 of an inscription's body under the address that built it or,
+/// once indexed, the address it was inscribed to. Built rows have no height.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentHash {
+  pub address: String,
+  pub content_hash: sha256::Hash,
+  pub inscription_id: InscriptionId,
+  pub height: Option<u64>,
+}
 
-    let database = match unsafe { Database::builder().open_mmapped(&path) } {
-      Ok(database) => {
-        let schema_version = database
-          .begin_read()?
-          .open_table(STATISTIC_TO_COUNT)?
-          .get(&Statistic::Schema.key())?
-          .map(|x| x.value())
-          .unwrap_or(0);
+impl ContentHash {
+  pub fn of(body: &[u8]) -> sha256::Hash {
+    sha256::Hash::hash(body)
+  }
 
-        match schema_version.cmp(&SCHEMA_VERSION) {
-          cmp::Ordering::Less =>
-            bail!(
-              "index at `{}` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
-              path.display()
-            ),
-          cmp::Ordering::Greater =>
-            bail!(
-              "index at `{}` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
-              path.display()
-            ),
-          cmp::Ordering::Equal => {}
-        }
-        database
-      }
-      Err(redb::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
-        let database = unsafe {
-          Database::builder()
-            .set_write_strategy(if cfg!(test) {
-              WriteStrategy::Checksum
-            } else {
-              WriteStrategy::TwoPhase
-            })
-            .create_mmapped(&path)?
-        };
-        let tx = database.begin_write()?;
+  /// Rows for the inscriptions created in a block, from its genesis rows and
+  /// the hashes of their bodies. Inscriptions without a body have no row.
+  pub(crate) fn rows(
+    genesis: &[InscriptionGenesis],
+    hashes: &HashMap<InscriptionId, sha256::Hash>,
+  ) -> Vec<Self> {
+    genesis
+      .iter()
+      .filter_map(|row| {
+        Some(Self {
+          address: row.address.clone(),
+          content_hash: *hashes.get(&row.inscription_id)?,
+          inscription_id: row.inscription_id,
+          height: Some(row.height),
+        })
+      })
+      .collect()
+  }
+}
 
-        #[cfg(test)]
-        let tx = {
-          let mut tx = tx;
-          tx.set_durability(redb::Durability::None);
-          tx
-        };
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GenesisFilter {
+  pub content_type_prefix: Option<String>,
+  pub address: Option<String>,
+}
 
-        tx.open_table(HEIGHT_TO_BLOCK_HASH)?;
-        tx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
-        tx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
-        tx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
-        tx.open_table(OUTPOINT_TO_VALUE)?;
-        tx.open_table(SATPOINT_TO_INSCRIPTION_ID)?;
-        tx.open_table(SAT_TO_INSCRIPTION_ID)?;
-        tx.open_table(SAT_TO_SATPOINT)?;
-        tx.open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?;
+/// Narrows admin reports to builds a client tagged with one purpose, or to
+/// builds that declared none.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum PurposeFilter {
+  #[default]
+  Any,
+  Untagged,
+  Tagged(String),
+}
 
-        tx.open_table(STATISTIC_TO_COUNT)?
-          .insert(&Statistic::Schema.key(), &SCHEMA_VERSION)?;
+impl PurposeFilter {
+  /// An empty `purpose` query parameter selects untagged builds.
+  pub fn from_query(purpose: Option<String>) -> Self {
+    match purpose {
+      None => Self::Any,
+      Some(purpose) if purpose.is_empty() => Self::Untagged,
+      Some(purpose) => Self::Tagged(purpose),
+    }
+  }
 
-        if options.index_sats {
-          tx.open_table(OUTPOINT_TO_SAT_RANGES)?
-            .insert(&OutPoint::null().store(), [].as_slice())?;
-        }
+  // always a condition, so it can be ANDed onto any WHERE clause
+  pub(crate) fn condition(&self) -> &'static str {
+    match self {
+      Self::Any => "TRUE",
+      Self::Untagged => "purpose IS NULL",
+      Self::Tagged(_) => "purpose = :purpose",
+    }
+  }
 
-        tx.commit()?;
+  /// `params` with the purpose added. Named parameters are refused for a
+  /// statement without any, so nothing at all becomes `Params::Empty`.
+  pub(crate) fn params(&self, mut params: Vec<(String, mysql::Value)>) -> mysql::Params {
+    if let Self::Tagged(purpose) = self {
+      params.push(("purpose".to_owned(), mysql::Value::from(purpose.clone())));
+    }
+    if params.is_empty() {
+      mysql::Params::Empty
+    } else {
+      mysql::Params::from(params)
+    }
+  }
+}
 
-        database
-      }
-      Err(error) => return Err(error.into()),
-    };
+/// Builds recorded in the audit trail per endpoint and declared purpose.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BuildRequestCount {
+  pub endpoint: String,
+  pub purpose: Option<String>,
+  pub requests: u64,
+}
 
-    let genesis_block_coinbase_transaction =
-      options.chain().genesis_block().coinbase().unwrap().clone();
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InscriptionsByHeightPage {
+  pub inscriptions: Vec<InscriptionGenesis>,
+  pub next: Option<String>,
+}
 
-    Ok(Self {
-      genesis_block_coinbase_txid: genesis_block_coinbase_transaction.txid(),
-      client,
-      database,
-      path,
-      first_inscription_height: options.first_inscription_height(),
-      genesis_block_coinbase_transaction,
-      height_limit: options.height_limit,
-      reorged: AtomicBool::new(false),
-      options: options.clone(),
-      mysql_database: None,
-    })
-  }
+impl InscriptionsByHeightPage {
+  pub const DEFAULT_LIMIT: u32 = 100;
+  pub const MAX_LIMIT: u32 = 1000;
 
-  pub fn open_with_mysql(options: &Options, mysql_database: Arc<MysqlDatabase>) -> Result<Self> {
-    let mut index = Self::open(options)?;
-    index.mysql_database = Some(mysql_database);
-    Ok(index)
+  // a short page is the last one
+  pub(crate) fn new(inscriptions: Vec<InscriptionGenesis>, limit: u32) -> Self {
+    let next = if inscriptions.len() == usize::try_from(limit).unwrap() {
+      inscriptions
+        .last()
+        .map(|inscription| inscription.cursor().to_string())
+    } else {
+      None
+    };
+    Self { inscriptions, next }
   }
+}
 
-  pub(crate) fn get_unspent_outputs_by_commit_id(
-    &self,
-    addr: &str,
-    remain_outpoint: BTreeMap<OutPoint, bool>,
-    txid: Txid,
-  ) -> Result<(BTreeMap<OutPoint, Amount>, Transaction)> {
-    let mut utxos = self._get_unspent_outputs_by_mempool_v1(
-      self.options.chain().default_mempool_url(),
-      addr,
-      remain_outpoint,
-    )?;
-
-    let url = format!(
-      "{}tx/{}/hex",
-      self.options.chain().default_mempool_url(),
-      txid,
-    );
+/// An inscription held by an address, as listed by `get_address_inscriptions`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddressInscription {
+  pub inscription_id: InscriptionId,
+  pub satpoint: SatPoint,
+  pub content_type: Option<String>,
+}
 
-    let rep = Vec::from_hex(&reqwest::blocking::get(url)?.text()?)?;
-    let tx: Transaction = Decodable::consensus_decode(&mut rep.as_slice()).unwrap();
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddressInscriptionsPage {
+  pub inscriptions: Vec<AddressInscription>,
+  pub total_count: u64,
+  pub next: Option<String>,
+}
 
-    for input in tx.input.clone() {
-      let txid = format!("{}", input.previous_output.txid);
-      let url = format!(
-        "{}tx/{}/hex",
-        self.options.chain().default_mempool_url(),
-        txid,
-      );
+impl AddressInscriptionsPage {
+  pub const DEFAULT_LIMIT: u32 = 100;
+  pub const MAX_LIMIT: u32 = 1000;
 
-      let rep = Vec::from_hex(&reqwest::blocking::get(url)?.text()?)?;
-      let tx: Transaction = Decodable::consensus_decode(&mut rep.as_slice()).unwrap();
-      utxos.insert(
-        input.previous_output,
-        Amount::from_sat(tx.output[input.previous_output.vout as usize].value),
-      );
+  // a short page is the last one
+  pub(crate) fn new(inscriptions: Vec<AddressInscription>, total_count: u64, limit: u32) -> Self {
+    let next = if inscriptions.len() == usize::try_from(limit).unwrap() {
+      inscriptions
+        .last()
+        .map(|inscription| inscription.inscription_id.to_string())
+    } else {
+      None
+    };
+    Self {
+      inscriptions,
+      total_count,
+      next,
     }
-    Ok((utxos, tx))
   }
+}
 
-  pub(crate) fn get_unspent_outputs_by_outpoints(
-    &self,
-    inputs: &Vec<OutPoint>,
-  ) -> Result<BTreeMap<OutPoint, Amount>> {
-    let mut utxos = BTreeMap::new();
-    for input in inputs {
-      let txid = format!("{}", input.txid);
-      let url = format!(
-        "{}tx/{}/hex",
-        self.options.chain().default_mempool_url(),
-        txid,
-      );
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct RevenueTotal {
+  pub service_address: Option<String>,
+  pub outputs: u64,
+  pub postage: u64,
+  pub reveal_fee: u64,
+  pub service_fee: u64,
+}
 
-      let rep = Vec::from_hex(&reqwest::blocking::get(url)?.text()?)?;
-      let tx: Transaction = Decodable::consensus_decode(&mut rep.as_slice()).unwrap();
-      utxos.insert(
-        *input,
-        Amount::from_sat(tx.output[input.vout as usize].value),
-      );
+impl RevenueTotal {
+  pub(crate) fn with_addresses(mut totals: Vec<Self>, addresses: &[String]) -> Vec<Self> {
+    for address in addresses {
+      if !totals
+        .iter()
+        .any(|total| total.service_address.as_ref() == Some(address))
+      {
+        totals.push(Self {
+          service_address: Some(address.clone()),
+          ..Default::default()
+        });
+      }
     }
-    Ok(utxos)
+    totals.sort_by(|a, b| a.service_address.cmp(&b.service_address));
+    totals
   }
+}
 
-  fn _get_unspent_outputs_by_mempool(
-    &self,
-    url: &str,
-    addr: &str,
-    remain_outpoint: BTreeMap<OutPoint, bool>,
-  ) -> Result<BTreeMap<OutPoint, Amount>> {
-    let mut utxos = BTreeMap::new();
-    let url = format!("{}address/{}/utxo", url, addr,);
-    let rep = reqwest::blocking::get(url)?.text()?;
-    utxos.extend(
-      serde_json::from_str::<Vec<ListUnspentResultEntry>>(&rep)
-        .map_err(|_| anyhow!(format!("Req utxo error:{}", rep)))?
-        .into_iter()
-        .map(|utxo| {
-          let outpoint = OutPoint::new(utxo.txid, utxo.vout);
-          let amount = utxo.value;
+/// Where an inscription was at the end of the block at `height`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SatpointChange {
+  pub inscription_id: InscriptionId,
+  pub height: u64,
+  pub satpoint: SatPoint,
+  pub address: String,
+}
 
-          (outpoint, amount)
-        }),
-    );
-    let rtx = self.database.begin_read()?;
-    let outpoint_to_value = rtx.open_table(OUTPOINT_TO_VALUE)?;
-    let mut filter_utxos = BTreeMap::new();
-    for (outpoint, amount) in utxos.into_iter() {
-      filter_utxos.insert(outpoint, amount);
-      // if remain_outpoint.contains_key(&outpoint)
-        // || outpoint_to_value.get(&outpoint.store())?.is_some()
-      // {
-      //   filter_utxos.insert(outpoint, amount);
-      // }
-    }
-    if filter_utxos.is_empty() {
-      Err(anyhow!("Not found utxo for addr"))
-    } else {
-      Ok(filter_utxos)
+/// An inscription an address holds, `last_transfer_height` is unknown for
+/// moves before satpoint history was recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+  pub inscription_id: InscriptionId,
+  pub satpoint: SatPoint,
+  pub last_transfer_height: Option<u64>,
+}
+
+impl Holding {
+  /// What `address` held at the end of the block at `height`, `history`
+  /// being every change of the inscriptions it ever held up to that block.
+  pub(crate) fn at_height(address: &str, height: u64, history: &[SatpointChange]) -> Vec<Self> {
+    let mut latest: BTreeMap<InscriptionId, &SatpointChange> = BTreeMap::new();
+
+    for change in history.iter().filter(|change| change.height <= height) {
+      match latest.get(&change.inscription_id) {
+        Some(previous) if previous.height > change.height => {}
+        _ => {
+          latest.insert(change.inscription_id, change);
+        }
+      }
     }
+
+    latest
+      .into_values()
+      .filter(|change| change.address == address)
+      .map(|change| Holding {
+        inscription_id: change.inscription_id,
+        satpoint: change.satpoint,
+        last_transfer_height: Some(change.height),
+      })
+      .collect()
   }
+}
 
-  fn _get_unspent_outputs_by_mempool_v1(
-    &self,
-    url: &str,
-    addr: &str,
-    remain_outpoint: BTreeMap<OutPoint, bool>,
-  ) -> Result<BTreeMap<OutPoint, Amount>> {
-    let mut utxos = BTreeMap::new();
-    let url = format!("{}address/{}/utxo", url, addr,);
-    let rep = reqwest::blocking::get(url)?.text()?;
-    utxos.extend(
-      serde_json::from_str::<Vec<ListUnspentResultEntry>>(&rep)
-        .map_err(|_| anyhow!(format!("Req utxo error:{}", rep)))?
-        .into_iter()
-        .filter(|utxo| utxo.status.confirmed)
-        .map(|utxo| {
-          let outpoint = OutPoint::new(utxo.txid, utxo.vout);
-          let amount = utxo.value;
+/// Where an inscription is now and who holds it, for checking ownership
+/// before building a transfer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InscriptionDetails {
+  pub id: InscriptionId,
+  pub number: u64,
+  pub satpoint: SatPoint,
+  pub owner: Option<String>,
+  pub genesis_height: u64,
+  pub content_type: Option<String>,
+}
 
-          (outpoint, amount)
-        }),
-    );
-    let rtx = self.database.begin_read()?;
-    let outpoint_to_value = rtx.open_table(OUTPOINT_TO_VALUE)?;
-    let mut filter_utxos = BTreeMap::new();
-    for (outpoint, amount) in utxos.into_iter() {
-      filter_utxos.insert(outpoint, amount);
-      // if remain_outpoint.contains_key(&outpoint)
-        // || outpoint_to_value.get(&outpoint.store())?.is_some()
-      // {
-      //   filter_utxos.insert(outpoint, amount);
-      // }
-    }
-    if filter_utxos.is_empty() {
-      Err(anyhow!("Not found utxo for addr"))
-    } else {
-      Ok(filter_utxos)
-    }
-  }
+/// One line of an address export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRow {
+  pub id: InscriptionId,
+  pub number: u64,
+  pub satpoint: SatPoint,
+  pub value: Option<u64>,
+  pub content_type: Option<String>,
+  pub genesis_height: u64,
+  pub last_transfer_height: Option<u64>,
+}
 
-  pub(crate) fn get_unspent_outputs_by_mempool(
-    &self,
-    addr: &str,
-    remain_outpoint: BTreeMap<OutPoint, bool>,
-  ) -> Result<BTreeMap<OutPoint, Amount>> {
-    self._get_unspent_outputs_by_mempool(
-      self.options.chain().default_mempool_url(),
-      addr,
-      remain_outpoint,
+impl ExportRow {
+  pub const CSV_HEADER: &'static str =
+    "id,number,satpoint,value,content_type,genesis_height,last_transfer_height";
+
+  pub fn to_csv(&self) -> String {
+    // content types may carry parameters, quote them whenever they could split
+    let content_type = match &self.content_type {
+      Some(content_type) if content_type.contains([',', '"', '\n', '\r']) => {
+        format!("\"{}\"", content_type.replace('"', "\"\""))
+      }
+      Some(content_type) => content_type.clone(),
+      None => String::new(),
+    };
+    format!(
+      "{},{},{},{},{},{},{}",
+      self.id,
+      self.number,
+      self.satpoint,
+      self
+        .value
+        .map(|value| value.to_string())
+        .unwrap_or_default(),
+      content_type,
+      self.genesis_height,
+      self
+        .last_transfer_height
+        .map(|height| height.to_string())
+        .unwrap_or_default(),
     )
   }
+}
 
-  pub(crate) fn get_unspent_outputs_by_mempool_v1(
-    &self,
-    addr: &str,
-    remain_outpoint: BTreeMap<OutPoint, bool>,
-  ) -> Result<BTreeMap<OutPoint, Amount>> {
-    if self.options.chain() == Chain::Mainnet {
-      let mempool_url = "https://mempool.space/api/";
-      let utxos = self._get_unspent_outputs_by_mempool(mempool_url, addr, remain_outpoint.clone());
-      if let Ok(utxos) = utxos {
-        if !utxos.is_empty() {
-          return Ok(utxos);
-        }
-      }
-    }
-    self.get_unspent_outputs_by_mempool(addr, remain_outpoint)
+pub struct RevealExpectation {
+  pub commit_txid: Txid,
+  pub reveal_txid: Txid,
+  pub reveal_hex: String,
+  pub source: String,
+}
+
+/// What one commit output paid for, and where its reveal sent the service
+/// fee if it carried one.
+pub struct Revenue {
+  pub commit_txid: Txid,
+  pub commit_vout: u32,
+  pub reveal_txid: Txid,
+  pub source: String,
+  pub purpose: Option<String>,
+  pub breakdown: CommitOutputBreakdown,
+  pub service_output: Option<(String, u32)>,
+}
+
+impl Revenue {
+  /// One row per commit output of `commit`, `reveals[i]` spending output i.
+  pub(crate) fn rows(
+    commit: &Transaction,
+    reveals: &[Transaction],
+    breakdown: &[CommitOutputBreakdown],
+    source: &Address,
+    purpose: Option<&str>,
+    service_address: &Address,
+  ) -> Vec<Self> {
+    reveals
+      .iter()
+      .zip(breakdown)
+      .enumerate()
+      .map(|(i, (reveal, parts))| Revenue {
+        commit_txid: commit.txid(),
+        commit_vout: u32::try_from(i).unwrap(),
+        reveal_txid: reveal.txid(),
+        source: source.to_string(),
+        purpose: purpose.map(str::to_owned),
+        breakdown: *parts,
+        service_output: reveal
+          .output
+          .iter()
+          .position(|output| {
+            parts.service_fee > 0
+              && output.value == parts.service_fee
+              && output.script_pubkey == service_address.script_pubkey()
+          })
+          .map(|vout| (service_address.to_string(), u32::try_from(vout).unwrap())),
+      })
+      .collect()
   }
+}
 
-  pub(crate) fn get_unspent_outputs(&self, _wallet: Wallet) -> Result<BTreeMap<OutPoint, Amount>> {
-    let mut utxos = BTreeMap::new();
-    utxos.extend(
-      self
-        .client
-        .list_unspent(None, None, None, None, None)?
-        .into_iter()
-        .map(|utxo| {
-          let outpoint = OutPoint::new(utxo.txid, utxo.vout);
-          let amount = utxo.amount;
+#[derive(Debug, Serialize)]
+pub struct AirdropJob {
+  pub id: String,
+  pub source: String,
+  pub fee_rate: f64,
+  pub extension: Option<String>,
+}
 
-          (outpoint, amount)
-        }),
-    );
+#[derive(Debug, Serialize)]
+pub struct AirdropBatch {
+  pub batch: u32,
+  /// pending, building, built or failed
+  pub state: String,
+  #[serde(skip_serializing)]
+  pub rows: String,
+  pub error: Option<String>,
+  pub output: Option<serde_json::Value>,
+}
 
-    #[derive(Deserialize)]
-    pub(crate) struct JsonOutPoint {
-      txid: bitcoin::Txid,
-      vout: u32,
-    }
+pub struct PruneOptions {
+  pub depth: u64,
+  pub batch_size: u64,
+  pub sleep: Duration,
+}
 
-    for JsonOutPoint { txid, vout } in self
-      .client
-      .call::<Vec<JsonOutPoint>>("listlockunspent", &[])?
-    {
-      utxos.insert(
-        OutPoint { txid, vout },
-        Amount::from_sat(self.client.get_raw_transaction(&txid, None)?.output[vout as usize].value),
-      );
-    }
-    let rtx = self.database.begin_read()?;
-    let outpoint_to_value = rtx.open_table(OUTPOINT_TO_VALUE)?;
-    for outpoint in utxos.keys() {
-      if outpoint_to_value.get(&outpoint.store())?.is_none() {
-        return Err(anyhow!(
-          "output in Bitcoin Core wallet but not in ord index: {outpoint}"
-        ));
-      }
-    }
+#[derive(Debug, Default, Serialize)]
+pub struct RevealExpectationCounts {
+  pub built: u64,
+  pub pending: u64,
+  pub fulfilled: u64,
+  pub superseded: u64,
+  pub expired: u64,
+}
 
-    Ok(utxos)
-  }
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevealExpectationState {
+  /// Built, but not broadcast through this server. Clients usually
+  /// broadcast their own reveals, so it is expected on chain all the same
+  /// until it expires.
+  Built,
+  Pending,
+  Fulfilled,
+  Superseded,
+  Expired,
+}
 
-  pub(crate) fn get_unspent_output_ranges(
-    &self,
-    wallet: Wallet,
-  ) -> Result<Vec<(OutPoint, Vec<(u64, u64)>)>> {
-    self
-      .get_unspent_outputs(wallet)?
-      .into_keys()
-      .map(|outpoint| match self.list(outpoint)? {
-        Some(List::Unspent(sat_ranges)) => Ok((outpoint, sat_ranges)),
-        Some(List::Spent) => bail!("output {outpoint} in wallet but is spent according to index"),
-        None => bail!("index has not seen {outpoint}"),
-      })
-      .collect()
+impl FromStr for RevealExpectationState {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "built" => Ok(Self::Built),
+      "pending" => Ok(Self::Pending),
+      "fulfilled" => Ok(Self::Fulfilled),
+      "superseded" => Ok(Self::Superseded),
+      "expired" => Ok(Self::Expired),
+      _ => bail!(
+        "invalid reveal expectation state `{s}`, expected one of built, pending, fulfilled, superseded or expired"
+      ),
+    }
   }
+}
 
-  pub(crate) fn has_sat_index(&self) -> Result<bool> {
-    match self.begin_read()?.0.open_table(OUTPOINT_TO_SAT_RANGES) {
-      Ok(_) => Ok(true),
-      Err(redb::Error::TableDoesNotExist(_)) => Ok(false),
-      Err(err) => Err(err.into()),
+impl RevealExpectationState {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Built => "built",
+      Self::Pending => "pending",
+      Self::Fulfilled => "fulfilled",
+      Self::Superseded => "superseded",
+      Self::Expired => "expired",
     }
   }
 
-  fn require_sat_index(&self, feature: &str) -> Result {
-    if !self.has_sat_index()? {
-      bail!("{feature} requires index created with `--index-sats` flag")
+  /// The state a pending expectation moves to, with the height it is
+  /// settled at, or `None` while the chain has not decided it yet.
+  pub(crate) fn reconcile(
+    reveal_height: Option<u64>,
+    commit_output: CommitOutput,
+    created_height: u64,
+    tip: u64,
+    expire_after: u64,
+  ) -> Option<(Self, u64)> {
+    if let Some(height) = reveal_height {
+      return Some((Self::Fulfilled, height));
     }
 
-    Ok(())
+    match commit_output {
+      // the reveal confirmed in a block not indexed yet, the updater
+      // fulfills it once it gets there
+      CommitOutput::Unspent | CommitOutput::SpentByReveal => None,
+      CommitOutput::SpentElsewhere => Some((Self::Superseded, tip)),
+      CommitOutput::Unconfirmed => {
+        (tip.saturating_sub(created_height) >= expire_after).then_some((Self::Expired, tip))
+      }
+    }
   }
+}
 
-  pub(crate) fn info(&self) -> Result<Info> {
-    let wtx = self.begin_write()?;
+/// The commit output a pending reveal spends, as the node sees it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CommitOutput {
+  Unconfirmed,
+  Unspent,
+  SpentByReveal,
+  SpentElsewhere,
+}
 
-    let stats = wtx.stats()?;
+pub(crate) struct PendingReveal {
+  commit: OutPoint,
+  reveal_txid: Txid,
+  created_height: Option<u64>,
+}
 
-    let info = {
-      let statistic_to_count = wtx.open_table(STATISTIC_TO_COUNT)?;
-      let sat_ranges = statistic_to_count
-        .get(&Statistic::SatRanges.key())?
-        .map(|x| x.value())
-        .unwrap_or(0);
-      let outputs_traversed = statistic_to_count
-        .get(&Statistic::OutputsTraversed.key())?
-        .map(|x| x.value())
-        .unwrap_or(0);
-      Info {
-        index_path: self.path.clone(),
-        blocks_indexed: wtx
-          .open_table(HEIGHT_TO_BLOCK_HASH)?
-          .range(0..)?
-          .rev()
-          .next()
-          .map(|(height, _hash)| height.value() + 1)
-          .unwrap_or(0),
-        branch_pages: stats.branch_pages(),
-        fragmented_bytes: stats.fragmented_bytes(),
-        index_file_size: fs::metadata(&self.path)?.len(),
-        leaf_pages: stats.leaf_pages(),
-        metadata_bytes: stats.metadata_bytes(),
-        sat_ranges,
-        outputs_traversed,
-        page_size: stats.page_size(),
-        stored_bytes: stats.stored_bytes(),
-        transactions: wtx
-          .open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?
-          .range(0..)?
-          .map(
-            |(starting_block_count, starting_timestamp)| TransactionInfo {
-              starting_block_count: starting_block_count.value(),
-              starting_timestamp: starting_timestamp.value(),
-            },
-          )
-          .collect(),
-        tree_height: stats.tree_height(),
-        utxos_indexed: wtx.open_table(OUTPOINT_TO_SAT_RANGES)?.len()?,
-      }
-    };
+pub struct ReconcileOptions {
+  pub expire_after: u64,
+  pub batch_size: u64,
+  pub sleep: Duration,
+}
 
-    Ok(info)
-  }
+/// What one reconciliation run settled.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct RevealReconciliation {
+  pub checked: u64,
+  pub fulfilled: u64,
+  pub superseded: u64,
+  pub expired: u64,
+}
 
-  pub fn reorg_height(&self, target_height: u64) -> Result {
-    Updater::reorg_height(self, target_height)
+impl RevealReconciliation {
+  fn record(&mut self, state: RevealExpectationState) {
+    match state {
+      RevealExpectationState::Built | RevealExpectationState::Pending => {}
+      RevealExpectationState::Fulfilled => self.fulfilled += 1,
+      RevealExpectationState::Superseded => self.superseded += 1,
+      RevealExpectationState::Expired => self.expired += 1,
+    }
   }
+}
 
-  pub fn update(&self) -> Result {
-    Updater::update(self)
-  }
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RevealExpectationRow {
+  pub reveal_txid: String,
+  pub commit_txid: String,
+  pub source: String,
+  pub state: String,
+  pub height: Option<u64>,
+  pub created_height: Option<u64>,
+}
 
-  pub(crate) fn is_reorged(&self) -> bool {
-    self.reorged.load(atomic::Ordering::Relaxed)
-  }
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RevealExpectationPage {
+  pub expectations: Vec<RevealExpectationRow>,
+  pub next: Option<String>,
+}
 
-  fn begin_read(&self) -> Result<rtx::Rtx> {
-    Ok(rtx::Rtx(self.database.begin_read()?))
-  }
+impl RevealExpectationPage {
+  pub const DEFAULT_LIMIT: u32 = 100;
+  pub const MAX_LIMIT: u32 = 1000;
 
-  fn begin_write(&self) -> Result<WriteTransaction> {
-    if cfg!(test) {
-      let mut tx = self.database.begin_write()?;
-      tx.set_durability(redb::Durability::None);
-      Ok(tx)
+  // a short page is the last one
+  pub(crate) fn new(expectations: Vec<RevealExpectationRow>, limit: u32) -> Self {
+    let next = if expectations.len() == usize::try_from(limit).unwrap() {
+      expectations
+        .last()
+        .map(|expectation| expectation.reveal_txid.clone())
     } else {
-      Ok(self.database.begin_write()?)
-    }
+      None
+    };
+    Self { expectations, next }
   }
+}
 
-  fn increment_statistic(wtx: &WriteTransaction, statistic: Statistic, n: u64) -> Result {
-    let mut statistic_to_count = wtx.open_table(STATISTIC_TO_COUNT)?;
-    let value = statistic_to_count
-      .get(&(statistic.key()))?
-      .map(|x| x.value())
-      .unwrap_or(0)
-      + n;
-    statistic_to_count.insert(&statistic.key(), &value)?;
-    Ok(())
-  }
+/// The requests an api key made on one UTC day, for how many distinct
+/// sources, and how many of them its policy refused.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiKeyUsage {
+  pub api_key: String,
+  pub day: NaiveDate,
+  pub distinct_sources: u64,
+  pub requests: u64,
+  pub violations: u64,
+}
 
-  #[cfg(test)]
-  pub(crate) fn statistic(&self, statistic: Statistic) -> u64 {
-    self
-      .database
-      .begin_read()
-      .unwrap()
-      .open_table(STATISTIC_TO_COUNT)
-      .unwrap()
-      .get(&statistic.key())
-      .unwrap()
-      .map(|x| x.value())
-      .unwrap_or(0)
-  }
+/// A whitelisted source, which mints without paying the service fee.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WhitelistEntry {
+  pub address: String,
+  pub note: Option<String>,
+  pub added_by: Option<String>,
+}
 
-  pub(crate) fn height(&self) -> Result<Option<Height>> {
-    self.begin_read()?.height()
-  }
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WhitelistPage {
+  pub entries: Vec<WhitelistEntry>,
+  pub next: Option<String>,
+}
 
-  pub(crate) fn block_count(&self) -> Result<u64> {
-    self.begin_read()?.block_count()
+impl WhitelistPage {
+  pub const DEFAULT_LIMIT: u32 = 100;
+  pub const MAX_LIMIT: u32 = 1000;
+
+  // a short page is the last one
+  pub(crate) fn new(entries: Vec<WhitelistEntry>, limit: u32) -> Self {
+    let next = if entries.len() == usize::try_from(limit).unwrap() {
+      entries.last().map(|entry| entry.address.clone())
+    } else {
+      None
+    };
+    Self { entries, next }
   }
+}
 
-  pub(crate) fn blocks(&self, take: usize) -> Result<Vec<(u64, BlockHash)>> {
-    let mut blocks = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackfillJobState {
+  Pending,
+  Running,
+  Done,
+  Failed,
+}
 
-    let rtx = self.begin_read()?;
+impl FromStr for BackfillJobState {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "pending" => Ok(Self::Pending),
+      "running" => Ok(Self::Running),
+      "done" => Ok(Self::Done),
+      "failed" => Ok(Self::Failed),
+      _ => {
+        bail!("invalid backfill job state `{s}`, expected one of pending, running, done or failed")
+      }
+    }
+  }
+}
 
-    let block_count = rtx.block_count()?;
+impl BackfillJobState {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Pending => "pending",
+      Self::Running => "running",
+      Self::Done => "done",
+      Self::Failed => "failed",
+    }
+  }
+}
 
-    let height_to_block_hash = rtx.0.open_table(HEIGHT_TO_BLOCK_HASH)?;
+/// A queued scan for one address's inscriptions from `from_height` on.
+/// `next_height` is the first block not yet scanned, so an interrupted job
+/// picks up where it stopped.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BackfillJob {
+  pub id: u64,
+  pub address: String,
+  pub from_height: u64,
+  pub next_height: u64,
+  pub state: String,
+  pub found: u64,
+  pub error: Option<String>,
+}
 
-    for next in height_to_block_hash.range(0..block_count)?.rev().take(take) {
-      blocks.push((next.0.value(), Entry::load(*next.1.value())));
-    }
+/// How many blocks backfill jobs may scan per run, and how long to sleep
+/// between blocks to spare the node.
+pub struct BackfillOptions {
+  pub blocks: u64,
+  pub sleep: Duration,
+}
 
-    Ok(blocks)
-  }
+impl MysqlDatabase {
+  pub fn new(
+    host: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    network: Network,
+  ) -> Result<MysqlDatabase> {
+    let opts_builder = OptsBuilder::new()
+      .ip_or_hostname(host)
+      .user(username)
+      .pass(password)
+      .db_name(Some(Self::get_database(network)));
+    let pool =
+      mysql::Pool::new::<Opts>(opts_builder.into()).map_err(|_| anyhow!("Create pool fail"))?;
 
-  pub(crate) fn rare_sat_satpoints(&self) -> Result<Option<Vec<(Sat, SatPoint)>>> {
-    if self.has_sat_index()? {
-      let mut result = Vec::new();
+    let database = MysqlDatabase { pool, network };
+    database.create_tables()?;
 
-      let rtx = self.database.begin_read()?;
+    Ok(database)
+  }
 
-      let sat_to_satpoint = rtx.open_table(SAT_TO_SATPOINT)?;
+  fn create_tables(&self) -> Result {
+    let statements = vec![
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         reveal_txid VARCHAR(64) NOT NULL PRIMARY KEY,
+         commit_txid VARCHAR(64) NOT NULL,
+         source VARCHAR(128) NOT NULL,
+         reveal_hex MEDIUMTEXT NOT NULL,
+         state VARCHAR(16) NOT NULL DEFAULT 'pending',
+         height BIGINT UNSIGNED NULL,
+         created_height BIGINT UNSIGNED NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (commit_txid),
+         INDEX (state)
+       )",
+        self.get_reveal_expectation_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id VARCHAR(32) NOT NULL PRIMARY KEY,
+         source VARCHAR(128) NOT NULL,
+         fee_rate DOUBLE NOT NULL,
+         extension VARCHAR(32) NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+       )",
+        self.get_airdrop_job_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         job_id VARCHAR(32) NOT NULL,
+         batch INT UNSIGNED NOT NULL,
+         state VARCHAR(16) NOT NULL DEFAULT 'pending',
+         rows_json MEDIUMTEXT NOT NULL,
+         error TEXT NULL,
+         output MEDIUMTEXT NULL,
+         updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+         PRIMARY KEY (job_id, batch),
+         INDEX (state)
+       )",
+        self.get_airdrop_batch_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         commit_txid VARCHAR(64) NOT NULL PRIMARY KEY,
+         salt CHAR(64) NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+       )",
+        self.get_reveal_key_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         commit_txid VARCHAR(64) NOT NULL,
+         commit_vout INT UNSIGNED NOT NULL,
+         reveal_txid VARCHAR(64) NOT NULL,
+         source VARCHAR(128) NOT NULL,
+         postage BIGINT UNSIGNED NOT NULL,
+         reveal_fee BIGINT UNSIGNED NOT NULL,
+         service_fee BIGINT UNSIGNED NOT NULL,
+         service_address VARCHAR(128) NULL,
+         service_vout INT UNSIGNED NULL,
+         purpose VARCHAR(64) NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         PRIMARY KEY (commit_txid, commit_vout),
+         INDEX (source),
+         INDEX (purpose)
+       )",
+        self.get_revenue_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         api_key VARCHAR(64) NOT NULL,
+         day DATE NOT NULL,
+         source VARCHAR(128) NOT NULL,
+         requests BIGINT UNSIGNED NOT NULL,
+         PRIMARY KEY (api_key, day, source),
+         INDEX (day)
+       )",
+        self.get_api_key_usage_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         api_key VARCHAR(64) NOT NULL,
+         day DATE NOT NULL,
+         sources BIGINT UNSIGNED NOT NULL DEFAULT 0,
+         PRIMARY KEY (api_key, day)
+       )",
+        self.get_api_key_day_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+         api_key VARCHAR(64) NOT NULL,
+         day DATE NOT NULL,
+         source VARCHAR(128) NOT NULL,
+         reason VARCHAR(32) NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (day, api_key)
+       )",
+        self.get_api_key_violation_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id TINYINT UNSIGNED NOT NULL PRIMARY KEY,
+         enabled BOOLEAN NOT NULL,
+         message TEXT NULL,
+         until_timestamp BIGINT UNSIGNED NULL
+       )",
+        self.get_maintenance_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         address VARCHAR(128) NOT NULL PRIMARY KEY,
+         backfilled BOOLEAN NOT NULL DEFAULT FALSE,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+       )",
+        self.get_watched_address_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         table_name VARCHAR(64) NOT NULL PRIMARY KEY,
+         cutoff_height BIGINT UNSIGNED NOT NULL,
+         pruned BIGINT UNSIGNED NOT NULL DEFAULT 0,
+         done BOOLEAN NOT NULL DEFAULT FALSE,
+         updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+       )",
+        self.get_prune_progress_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         address VARCHAR(128) NOT NULL,
+         height BIGINT UNSIGNED NOT NULL,
+         tx_index INT UNSIGNED NOT NULL,
+         seq INT UNSIGNED NOT NULL,
+         kind VARCHAR(32) NOT NULL,
+         inscription_id VARCHAR(80) NOT NULL,
+         txid VARCHAR(64) NOT NULL,
+         counterparty VARCHAR(128) NULL,
+         PRIMARY KEY (address, height, tx_index, seq),
+         INDEX (height)
+       )",
+        self.get_activity_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         inscription_id VARCHAR(80) NOT NULL,
+         height BIGINT UNSIGNED NOT NULL,
+         satpoint VARCHAR(80) NOT NULL,
+         address VARCHAR(128) NOT NULL,
+         PRIMARY KEY (inscription_id, height),
+         INDEX (address, height),
+         INDEX (height)
+       )",
+        self.get_satpoint_history_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         inscription_id VARCHAR(80) NOT NULL PRIMARY KEY,
+         height BIGINT UNSIGNED NOT NULL,
+         tx_index INT UNSIGNED NOT NULL,
+         input_index INT UNSIGNED NOT NULL,
+         content_type VARCHAR(255) NULL,
+         address VARCHAR(128) NOT NULL,
+         INDEX genesis_position (height, tx_index, input_index),
+         INDEX genesis_address (address, height, tx_index, input_index)
+       )",
+        self.get_genesis_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+         endpoint VARCHAR(64) NOT NULL,
+         source VARCHAR(128) NOT NULL,
+         is_unsafe BOOLEAN NOT NULL,
+         reveal_weight BIGINT UNSIGNED NOT NULL,
+         purpose VARCHAR(64) NULL,
+         api_version INT UNSIGNED NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (is_unsafe),
+         INDEX (purpose)
+       )",
+        self.get_build_audit_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+         method VARCHAR(64) NOT NULL,
+         request_sha256 CHAR(64) NOT NULL,
+         hmac CHAR(64) NOT NULL,
+         manifest MEDIUMTEXT NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (request_sha256)
+       )",
+        self.get_manifest_audit_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         name VARCHAR(32) NOT NULL PRIMARY KEY,
+         value BIGINT UNSIGNED NOT NULL
+       )",
+        self.get_audit_counter_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         seq BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+         audit VARCHAR(32) NOT NULL,
+         audit_id BIGINT UNSIGNED NOT NULL,
+         idempotency_key VARCHAR(255),
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         UNIQUE (audit, audit_id),
+         UNIQUE (audit, idempotency_key)
+       )",
+        self.get_audit_sequence_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         name VARCHAR(64) NOT NULL PRIMARY KEY,
+         value VARCHAR(255) NOT NULL
+       )",
+        self.get_settings_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         token VARCHAR(64) NOT NULL PRIMARY KEY,
+         campaign VARCHAR(64) NOT NULL,
+         discount_percent TINYINT UNSIGNED NOT NULL,
+         expires_at BIGINT UNSIGNED NOT NULL,
+         created_by VARCHAR(64) NOT NULL,
+         commit_txid VARCHAR(64) NULL,
+         redeemed_at TIMESTAMP NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (campaign)
+       )",
+        self.get_voucher_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         kind VARCHAR(16) NOT NULL,
+         value VARCHAR(80) NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         PRIMARY KEY (kind, value)
+       )",
+        self.get_blocklist_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         pattern VARCHAR(255) NOT NULL PRIMARY KEY,
+         label VARCHAR(255) NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+       )",
+        self.get_unsafe_destination_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+         action VARCHAR(16) NOT NULL,
+         kind VARCHAR(16) NOT NULL,
+         value VARCHAR(80) NOT NULL,
+         changed BOOLEAN NOT NULL,
+         admin_label VARCHAR(64) NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (kind, value)
+       )",
+        self.get_blocklist_audit_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         height BIGINT UNSIGNED NOT NULL,
+         content_type VARCHAR(255) NOT NULL,
+         date DATE NOT NULL,
+         count BIGINT UNSIGNED NOT NULL,
+         total_bytes BIGINT UNSIGNED NOT NULL,
+         PRIMARY KEY (height, content_type)
+       )",
+        self.get_content_type_block_stats_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         date DATE NOT NULL,
+         content_type VARCHAR(255) NOT NULL,
+         count BIGINT UNSIGNED NOT NULL,
+         total_bytes BIGINT UNSIGNED NOT NULL,
+         PRIMARY KEY (date, content_type)
+       )",
+        self.get_content_type_daily_stats_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         height BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+         block_hash CHAR(64) NOT NULL,
+         date DATE NOT NULL,
+         inscriptions INT UNSIGNED NOT NULL,
+         total BIGINT UNSIGNED NOT NULL,
+         block_checksum CHAR(64) NOT NULL,
+         checksum CHAR(64) NOT NULL,
+         INDEX (date)
+       )",
+        self.get_inscription_checksum_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         txid VARCHAR(64) NOT NULL PRIMARY KEY,
+         method VARCHAR(32) NOT NULL,
+         fee_rate DOUBLE NOT NULL,
+         broadcast_height BIGINT UNSIGNED NULL,
+         confirmed_height BIGINT UNSIGNED NULL,
+         purpose VARCHAR(64) NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (broadcast_height),
+         INDEX (confirmed_height),
+         INDEX (purpose)
+       )",
+        self.get_broadcast_journal_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         source VARCHAR(128) NOT NULL,
+         day DATE NOT NULL,
+         used BIGINT UNSIGNED NOT NULL,
+         PRIMARY KEY (source, day)
+       )",
+        self.get_mint_quota_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         source VARCHAR(128) NOT NULL PRIMARY KEY,
+         daily_limit BIGINT UNSIGNED NOT NULL
+       )",
+        self.get_mint_quota_override_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         source VARCHAR(128) NOT NULL PRIMARY KEY,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+       )",
+        self.get_mint_quota_exempt_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         height BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+         fetch_us BIGINT UNSIGNED NOT NULL,
+         inputs_us BIGINT UNSIGNED NOT NULL,
+         parse_us BIGINT UNSIGNED NOT NULL,
+         redb_us BIGINT UNSIGNED NOT NULL,
+         mysql_us BIGINT UNSIGNED NOT NULL,
+         commit_us BIGINT UNSIGNED NOT NULL,
+         total_us BIGINT UNSIGNED NOT NULL,
+         recorded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         INDEX (recorded_at)
+       )",
+        self.get_block_stats_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+         address VARCHAR(128) NOT NULL,
+         from_height BIGINT UNSIGNED NOT NULL,
+         next_height BIGINT UNSIGNED NOT NULL,
+         state VARCHAR(16) NOT NULL DEFAULT 'pending',
+         found BIGINT UNSIGNED NOT NULL DEFAULT 0,
+         error TEXT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+         INDEX (state)
+       )",
+        self.get_backfill_job_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         new_address VARCHAR(128) NOT NULL PRIMARY KEY
+       )",
+        self.get_whitelist_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         address VARCHAR(128) NOT NULL,
+         content_hash CHAR(64) NOT NULL,
+         inscription_id VARCHAR(80) NOT NULL,
+         height BIGINT UNSIGNED NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+         PRIMARY KEY (address, content_hash, inscription_id),
+         INDEX (height)
+       )",
+        self.get_content_hash_table()
+      ),
+      format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+         new_address VARCHAR(128) NOT NULL PRIMARY KEY,
+         note VARCHAR(255) NULL,
+         added_by VARCHAR(64) NOT NULL,
+         created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+       )",
+        self.get_whitelist_note_table()
+      ),
+    ];
 
-      for (sat, satpoint) in sat_to_satpoint.range(0..)? {
-        result.push((Sat(sat.value()), Entry::load(*satpoint.value())));
+    let mut conn = self.get_conn()?;
+    for statement in statements {
+      if let Err(err) = conn.query_drop(&statement) {
+        log::warn!("Create table fail:{err}");
       }
+    }
 
-      Ok(Some(result))
-    } else {
-      Ok(None)
+    // columns added after their table, audits from before api_version
+    // was recorded keep it NULL
+    let columns = [(
+      self.get_build_audit_table(),
+      "api_version",
+      "INT UNSIGNED NULL AFTER purpose",
+    )];
+    for (table, column, definition) in columns {
+      if let Err(err) = Self::add_column(&mut conn, &table, column, definition) {
+        log::warn!("Add column fail:{err}");
+      }
     }
+    Ok(())
   }
 
-  pub(crate) fn rare_sat_satpoint(&self, sat: Sat) -> Result<Option<SatPoint>> {
-    if self.has_sat_index()? {
-      Ok(
-        self
-          .database
-          .begin_read()?
-          .open_table(SAT_TO_SATPOINT)?
-          .get(&sat.n())?
-          .map(|satpoint| Entry::load(*satpoint.value())),
+  /// Adds `column` to `table` unless it is already there, so tables created
+  /// by an older version pick up columns added since.
+  fn add_column(conn: &mut PooledConn, table: &str, column: &str, definition: &str) -> Result {
+    let exists: Option<u8> = conn
+      .exec_first(
+        "SELECT 1 FROM information_schema.COLUMNS
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = :table AND COLUMN_NAME = :column",
+        params! { "table" => table, "column" => column },
       )
-    } else {
-      Ok(None)
+      .map_err(|_| anyhow!("Query fail"))?;
+    if exists.is_none() {
+      conn
+        .query_drop(format!(
+          "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))
+        .map_err(|_| anyhow!("Alter table fail"))?;
+      log::info!("Added column {column} to {table}");
     }
+    Ok(())
   }
 
-  pub(crate) fn block_header(&self, hash: BlockHash) -> Result<Option<BlockHeader>> {
-    self.client.get_block_header(&hash).into_option()
+  pub fn get_conn(&self) -> Result<PooledConn> {
+    self.pool.get_conn().map_err(|_| anyhow!("Connect fail"))
   }
 
-  pub(crate) fn block_header_info(&self, hash: BlockHash) -> Result<Option<GetBlockHeaderResult>> {
-    self.client.get_block_header_info(&hash).into_option()
+  pub fn get_database(network: Network) -> String {
+    match network {
+      Network::Bitcoin => "ord_mainnet".to_owned(),
+      Network::Testnet => "ord_testnet".to_owned(),
+      Network::Signet => "ord_signet".to_owned(),
+      Network::Regtest => "ord_regtest".to_owned(),
+    }
   }
 
-  pub(crate) fn get_block_by_height(&self, height: u64) -> Result<Option<Block>> {
-    Ok(
-      self
-        .client
-        .get_block_hash(height)
-        .into_option()?
-        .map(|hash| self.client.get_block(&hash))
-        .transpose()?,
-    )
+  pub fn get_whitelist_table(&self) -> String {
+    "INSCRIPTION_WHITELIST".to_owned()
   }
 
-  pub(crate) fn get_block_by_hash(&self, hash: BlockHash) -> Result<Option<Block>> {
-    self.client.get_block(&hash).into_option()
+  fn _is_whitelist(&self, new_address: &String) -> Result<bool> {
+    let tb = self.get_whitelist_table();
+    let mut conn = self.get_conn()?;
+    let query = format!("SELECT * FROM {} WHERE new_address = '{}'", tb, new_address);
+    let result: Vec<mysql::Row> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+    if !result.is_empty() {
+      Ok(true)
+    } else {
+      Ok(false)
+    }
   }
 
-  pub(crate) fn get_inscription_id_by_sat(&self, sat: Sat) -> Result<Option<InscriptionId>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(SAT_TO_INSCRIPTION_ID)?
-        .get(&sat.n())?
-        .map(|inscription_id| Entry::load(*inscription_id.value())),
-    )
+  pub fn is_whitelist(&self, new_address: &String) -> bool {
+    self._is_whitelist(new_address).unwrap_or(false)
   }
 
-  pub(crate) fn get_inscription_id_by_inscription_number(
+  // notes live beside the whitelist, whose rows predate them
+  pub fn get_whitelist_note_table(&self) -> String {
+    "INSCRIPTION_WHITELIST_NOTE".to_owned()
+  }
+
+  /// Whitelists `address`, returning whether it was not already. The note
+  /// and admin label are replaced either way.
+  pub fn insert_whitelist(
     &self,
-    n: u64,
-  ) -> Result<Option<InscriptionId>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
-        .get(&n)?
-        .map(|id| Entry::load(*id.value())),
+    address: &str,
+    note: Option<&str>,
+    added_by: &str,
+  ) -> Result<bool> {
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Start transaction fail"))?;
+    tx.exec_drop(
+      format!(
+        "INSERT INTO {tb} (new_address) SELECT :address FROM DUAL
+         WHERE NOT EXISTS (SELECT 1 FROM {tb} WHERE new_address = :address)",
+        tb = self.get_whitelist_table()
+      ),
+      params! { "address" => address },
     )
+    .map_err(|_| anyhow!("Insert fail"))?;
+    let inserted = tx.affected_rows() > 0;
+    tx.exec_drop(
+      format!(
+        "INSERT INTO {} (new_address, note, added_by) VALUES (:address, :note, :added_by)
+         ON DUPLICATE KEY UPDATE note = VALUES(note), added_by = VALUES(added_by)",
+        self.get_whitelist_note_table()
+      ),
+      params! { "address" => address, "note" => note, "added_by" => added_by },
+    )
+    .map_err(|_| anyhow!("Insert fail"))?;
+    tx.commit().map_err(|_| anyhow!("Commit fail"))?;
+    Ok(inserted)
   }
 
-  pub(crate) fn get_inscription_satpoint_by_id(
-    &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<SatPoint>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(INSCRIPTION_ID_TO_SATPOINT)?
-        .get(&inscription_id.store())?
-        .map(|satpoint| Entry::load(*satpoint.value())),
+  /// Removes `address` from the whitelist, returning whether it was on it.
+  pub fn delete_whitelist(&self, address: &str) -> Result<bool> {
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Start transaction fail"))?;
+    tx.exec_drop(
+      format!(
+        "DELETE FROM {} WHERE new_address = :address",
+        self.get_whitelist_table()
+      ),
+      params! { "address" => address },
+    )
+    .map_err(|_| anyhow!("Delete fail"))?;
+    let deleted = tx.affected_rows() > 0;
+    tx.exec_drop(
+      format!(
+        "DELETE FROM {} WHERE new_address = :address",
+        self.get_whitelist_note_table()
+      ),
+      params! { "address" => address },
     )
+    .map_err(|_| anyhow!("Delete fail"))?;
+    tx.commit().map_err(|_| anyhow!("Commit fail"))?;
+    Ok(deleted)
   }
 
-  pub(crate) fn get_inscription_by_id(
-    &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<Inscription>> {
-    if self
-      .database
-      .begin_read()?
-      .open_table(INSCRIPTION_ID_TO_SATPOINT)?
-      .get(&inscription_id.store())?
-      .is_none()
-    {
-      return Ok(None);
-    }
+  pub fn get_whitelist(&self, after: Option<&str>, limit: u32) -> Result<WhitelistPage> {
+    let query = format!(
+      "SELECT w.new_address, n.note, n.added_by FROM {} w
+       LEFT JOIN {} n ON n.new_address = w.new_address
+       WHERE w.new_address > :after ORDER BY w.new_address LIMIT :limit",
+      self.get_whitelist_table(),
+      self.get_whitelist_note_table()
+    );
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, Option<String>, Option<String>)> = conn
+      .exec(
+        query,
+        params! { "after" => after.unwrap_or_default(), "limit" => limit },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
 
-    Ok(
-      self
-        .get_transaction(inscription_id.txid)?
-        .and_then(|tx| Inscription::from_transaction(&tx)),
-    )
+    Ok(WhitelistPage::new(
+      rows
+        .into_iter()
+        .map(|(address, note, added_by)| WhitelistEntry {
+          address,
+          note,
+          added_by,
+        })
+        .collect(),
+      limit,
+    ))
   }
 
-  pub(crate) fn get_inscriptions_on_output(
-    &self,
-    outpoint: OutPoint,
-  ) -> Result<Vec<InscriptionId>> {
-    Ok(
-      Self::inscriptions_on_output(
-        &self
-          .database
-          .begin_read()?
-          .open_table(SATPOINT_TO_INSCRIPTION_ID)?,
-        outpoint,
-      )?
-      .map(|(_satpoint, inscription_id)| inscription_id)
-      .collect(),
-    )
+  pub fn get_inscription_table(&self) -> String {
+    "INSCRIPTION_ID_AND_SATPOINT".to_owned()
   }
 
-  pub(crate) fn get_transaction(&self, txid: Txid) -> Result<Option<Transaction>> {
-    if txid == self.genesis_block_coinbase_txid {
-      Ok(Some(self.genesis_block_coinbase_transaction.clone()))
-    } else {
-      self.client.get_raw_transaction(&txid, None).into_option()
+  /// Every inscription `new_address` holds, blocked ones included, since
+  /// builders rely on it to never spend inscribed sats as cardinals.
+  pub fn get_inscription_by_address(
+    &self,
+    new_address: &String,
+  ) -> Result<BTreeMap<SatPoint, InscriptionId>> {
+    let tb = self.get_inscription_table();
+    let query = format!("SELECT * FROM {} WHERE new_address = '{}'", tb, new_address);
+    let mut conn = self.get_conn()?;
+    let result: Vec<mysql::Row> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+    let mut map: BTreeMap<SatPoint, InscriptionId> = BTreeMap::new();
+    for row in result {
+      let inscription_id = SatPoint::from_str(
+        &row
+          .get::<String, _>("new_satpoint")
+          .ok_or(anyhow!("Row inscription_id not exist"))?,
+      )?;
+      let new_satpoint = InscriptionId::from_str(
+        &row
+          .get::<String, _>("inscription_id")
+          .ok_or(anyhow!("Row new_satpoint not exist"))?,
+      )?;
+      map.insert(inscription_id, new_satpoint);
     }
+    Ok(map)
   }
 
-  pub(crate) fn get_transaction_blockhash(&self, txid: Txid) -> Result<Option<BlockHash>> {
-    Ok(
-      self
-        .client
-        .get_raw_transaction_info(&txid, None)
-        .into_option()?
-        .and_then(|info| {
-          if info.in_active_chain.unwrap_or_default() {
-            info.blockhash
-          } else {
-            None
-          }
-        }),
-    )
+  /// A page of the inscriptions `address` holds in inscription id order,
+  /// after `after` when paging, optionally only those whose content type
+  /// starts with `content_type`. Inscriptions without a genesis row have no
+  /// content type and never match a content type filter.
+  pub fn get_address_inscriptions(
+    &self,
+    address: &str,
+    after: Option<InscriptionId>,
+    limit: u32,
+    content_type: Option<&str>,
+  ) -> Result<AddressInscriptionsPage> {
+    let mut conditions = vec![
+      "i.new_address = :address".to_owned(),
+      self.not_blocked("i.inscription_id"),
+    ];
+    let mut params = vec![("address".to_owned(), mysql::Value::from(address))];
+    if let Some(prefix) = content_type {
+      conditions.push("g.content_type LIKE :content_type".to_owned());
+      params.push((
+        "content_type".to_owned(),
+        mysql::Value::from(format!("{}%", Self::escape_like(prefix))),
+      ));
+    }
+    let from = format!(
+      "FROM {} i LEFT JOIN {} g ON g.inscription_id = i.inscription_id",
+      self.get_inscription_table(),
+      self.get_genesis_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    let total_count: u64 = conn
+      .exec_first(
+        format!("SELECT COUNT(*) {from} WHERE {}", conditions.join(" AND ")),
+        mysql::Params::from(params.clone()),
+      )
+      .map_err(|_| anyhow!("Query fail"))?
+      .unwrap_or_default();
+
+    if let Some(after) = after {
+      conditions.push("i.inscription_id > :after".to_owned());
+      params.push(("after".to_owned(), mysql::Value::from(after.to_string())));
+    }
+    params.push(("limit".to_owned(), mysql::Value::from(limit)));
+    let rows: Vec<(String, String, Option<String>)> = conn
+      .exec(
+        format!(
+          "SELECT i.inscription_id, i.new_satpoint, g.content_type {from}
+           WHERE {}
+           ORDER BY i.inscription_id LIMIT :limit",
+          conditions.join(" AND ")
+        ),
+        mysql::Params::from(params),
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    let mut inscriptions = vec![];
+    for (inscription_id, satpoint, content_type) in rows {
+      inscriptions.push(AddressInscription {
+        inscription_id: inscription_id.parse()?,
+        satpoint: satpoint.parse()?,
+        content_type,
+      });
+    }
+    Ok(AddressInscriptionsPage::new(
+      inscriptions,
+      total_count,
+      limit,
+    ))
   }
 
-  pub(crate) fn is_transaction_in_active_chain(&self, txid: Txid) -> Result<bool> {
-    Ok(
-      self
-        .client
-        .get_raw_transaction_info(&txid, None)
-        .into_option()?
-        .and_then(|info| info.in_active_chain)
-        .unwrap_or(false),
+  /// `get_address_inscriptions`, cached per tip of the node behind
+  /// `options`.
+  pub fn get_inscription_summary(
+    &self,
+    options: &Options,
+    address: &str,
+    after: Option<InscriptionId>,
+    limit: u32,
+    content_type: Option<&str>,
+  ) -> Result<AddressInscriptionsPage> {
+    INSCRIPTION_SUMMARIES.get_at_tip(
+      || Ok(options.bitcoin_rpc_client()?.get_best_block_hash()?),
+      (
+        address.to_owned(),
+        after.map(|after| after.to_string()),
+        limit,
+        content_type.map(str::to_owned),
+      ),
+      || self.get_address_inscriptions(address, after, limit, content_type),
     )
   }
 
-  pub(crate) fn find(&self, sat: u64) -> Result<Option<SatPoint>> {
-    self.require_sat_index("find")?;
-
-    let rtx = self.begin_read()?;
+  pub fn insert_inscriptions(&self, data: Vec<MysqlInscription>) -> Result {
+    if data.is_empty() {
+      return Ok(());
+    };
 
-    if rtx.block_count()? <= Sat(sat).height().n() {
-      return Ok(None);
-    }
+    let tb = self.get_inscription_table();
+    let query = format!(
+      "INSERT INTO {} (inscription_id, new_satpoint, new_address)
+       VALUES (:inscription_id, :new_satpoint, :new_address)
+       ON DUPLICATE KEY UPDATE inscription_id = :inscription_id , new_satpoint = :new_satpoint, new_address = :new_address",
+      tb
+    );
 
-    let outpoint_to_sat_ranges = rtx.0.open_table(OUTPOINT_TO_SAT_RANGES)?;
+    let mut conn = self.get_conn()?;
 
-    for (key, value) in outpoint_to_sat_ranges.range::<&[u8; 36]>(&[0; 36]..)? {
-      let mut offset = 0;
-      for chunk in value.value().chunks_exact(11) {
-        let (start, end) = SatRange::load(chunk.try_into().unwrap());
-        if start <= sat && sat < end {
-          return Ok(Some(SatPoint {
-            outpoint: Entry::load(*key.value()),
-            offset: offset + sat - start,
-          }));
-        }
-        offset += end - start;
-      }
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for item in data.iter() {
+      conn
+        .exec_drop(
+          query.clone(),
+          params! {
+            "inscription_id" => format!("{}", item.inscription_id),
+            "new_satpoint" =>  format!("{}", item.new_satpoint),
+            "new_address" => item.new_address.clone(),
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
     }
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))?;
+    Ok(())
+  }
+}
 
-    Ok(None)
+impl MysqlDatabase {
+  pub fn get_reveal_expectation_table(&self) -> String {
+    "INSCRIPTION_REVEAL_EXPECTATION".to_owned()
   }
 
-  fn list_inner(&self, outpoint: OutPointValue) -> Result<Option<Vec<u8>>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(OUTPOINT_TO_SAT_RANGES)?
-        .get(&outpoint)?
-        .map(|outpoint| outpoint.value().to_vec()),
-    )
+  pub fn insert_reveal_expectations(&self, data: Vec<RevealExpectation>) -> Result {
+    if data.is_empty() {
+      return Ok(());
+    };
+
+    let tb = self.get_reveal_expectation_table();
+    let query = format!(
+      "INSERT IGNORE INTO {} (reveal_txid, commit_txid, source, reveal_hex, state)
+       VALUES (:reveal_txid, :commit_txid, :source, :reveal_hex, 'built')",
+      tb
+    );
+
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for item in data.iter() {
+      tx.exec_drop(
+        query.clone(),
+        params! {
+          "reveal_txid" => format!("{}", item.reveal_txid),
+          "commit_txid" => format!("{}", item.commit_txid),
+          "source" => item.source.clone(),
+          "reveal_hex" => item.reveal_hex.clone(),
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
   }
 
-  pub(crate) fn list(&self, outpoint: OutPoint) -> Result<Option<List>> {
-    self.require_sat_index("list")?;
+  /// Marks the built reveals among `txids` pending, once a broadcast
+  /// through this server got them accepted. The creation height is cleared
+  /// so that expiry counts from the broadcast rather than the build.
+  pub fn expect_reveals(&self, txids: &[Txid]) -> Result {
+    if txids.is_empty() {
+      return Ok(());
+    };
 
-    let array = outpoint.store();
+    let query = format!(
+      "UPDATE {} SET state = 'pending', created_height = NULL
+       WHERE reveal_txid = :reveal_txid AND state = 'built'",
+      self.get_reveal_expectation_table()
+    );
 
-    let sat_ranges = self.list_inner(array)?;
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for txid in txids {
+      tx.exec_drop(query.clone(), params! { "reveal_txid" => txid.to_string() })
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
+  }
 
-    match sat_ranges {
-      Some(sat_ranges) => Ok(Some(List::Unspent(
-        sat_ranges
-          .chunks_exact(11)
-          .map(|chunk| SatRange::load(chunk.try_into().unwrap()))
-          .collect(),
-      ))),
-      None => {
-        if self.is_transaction_in_active_chain(outpoint.txid)? {
-          Ok(Some(List::Spent))
-        } else {
-          Ok(None)
-        }
-      }
+  /// Reveals built or broadcast by this service and not settled yet, which
+  /// the updater writes ahead of the rest of their block.
+  pub fn get_expected_reveal_txids(&self) -> Result<HashSet<Txid>> {
+    let tb = self.get_reveal_expectation_table();
+    let query = format!(
+      "SELECT reveal_txid FROM {} WHERE state IN ('built', 'pending')",
+      tb
+    );
+    let mut conn = self.get_conn()?;
+    let result: Vec<String> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+    let mut txids = HashSet::new();
+    for txid in result {
+      txids.insert(Txid::from_str(&txid)?);
     }
+    Ok(txids)
   }
 
-  pub(crate) fn blocktime(&self, height: Height) -> Result<Blocktime> {
-    let height = height.n();
+  pub fn fulfill_reveal_expectations(&self, txids: &[Txid], height: u64) -> Result {
+    if txids.is_empty() {
+      return Ok(());
+    };
 
-    match self.get_block_by_height(height)? {
-      Some(block) => Ok(Blocktime::confirmed(block.header.time)),
-      None => {
-        let tx = self.database.begin_read()?;
+    let tb = self.get_reveal_expectation_table();
+    let query = format!(
+      "UPDATE {} SET state = 'fulfilled', height = :height
+       WHERE reveal_txid = :reveal_txid AND state IN ('built', 'pending')",
+      tb
+    );
 
-        let current = tx
-          .open_table(HEIGHT_TO_BLOCK_HASH)?
-          .range(0..)?
-          .rev()
-          .next()
-          .map(|(height, _hash)| height)
-          .map(|x| x.value())
-          .unwrap_or(0);
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for txid in txids {
+      tx.exec_drop(
+        query.clone(),
+        params! {
+          "reveal_txid" => format!("{}", txid),
+          "height" => height,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
+  }
 
-        let expected_blocks = height.checked_sub(current).with_context(|| {
-          format!("current {current} height is greater than sat height {height}")
-        })?;
+  /// Raw reveals persisted when `commit_txid` was built.
+  pub fn get_reveal_hexes(&self, commit_txid: Txid) -> Result<Vec<String>> {
+    let tb = self.get_reveal_expectation_table();
+    let query = format!(
+      "SELECT reveal_hex FROM {} WHERE commit_txid = :commit_txid",
+      tb
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec(query, params! { "commit_txid" => commit_txid.to_string() })
+      .map_err(|_| anyhow!("Query fail"))
+  }
 
-        Ok(Blocktime::Expected(
-          Utc::now()
-            .round_subsecs(0)
-            .checked_add_signed(chrono::Duration::seconds(
-              10 * 60 * i64::try_from(expected_blocks)?,
-            ))
-            .ok_or_else(|| anyhow!("block timestamp out of range"))?,
-        ))
+  pub fn get_reveal_expectation_counts(&self) -> Result<RevealExpectationCounts> {
+    let tb = self.get_reveal_expectation_table();
+    let query = format!("SELECT state, COUNT(*) FROM {} GROUP BY state", tb);
+    let mut conn = self.get_conn()?;
+    let result: Vec<(String, u64)> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+    let mut counts = RevealExpectationCounts::default();
+    for (state, count) in result {
+      match state.parse() {
+        Ok(RevealExpectationState::Built) => counts.built = count,
+        Ok(RevealExpectationState::Pending) => counts.pending = count,
+        Ok(RevealExpectationState::Fulfilled) => counts.fulfilled = count,
+        Ok(RevealExpectationState::Superseded) => counts.superseded = count,
+        Ok(RevealExpectationState::Expired) => counts.expired = count,
+        Err(_) => {}
       }
     }
+    Ok(counts)
+  }
+
+  /// Marks built and pending expectations without a creation height as
+  /// created at `height`, the first time reconciliation sees them.
+  pub(crate) fn stamp_reveal_expectations(&self, height: u64) -> Result {
+    let query = format!(
+      "UPDATE {} SET created_height = :height
+       WHERE state IN ('built', 'pending') AND created_height IS NULL",
+      self.get_reveal_expectation_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "height" => height })
+      .map_err(|_| anyhow!("Execute fail"))
   }
 
-  pub(crate) fn get_inscriptions(
+  /// Up to `limit` built or pending expectations after `after`, ordered by
+  /// commit so the reveals of one commit are checked together.
+  pub(crate) fn get_unsettled_reveal_expectations(
     &self,
-    n: Option<usize>,
-  ) -> Result<BTreeMap<SatPoint, InscriptionId>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(SATPOINT_TO_INSCRIPTION_ID)?
-        .range::<&[u8; 44]>(&[0; 44]..)?
-        .map(|(satpoint, id)| (Entry::load(*satpoint.value()), Entry::load(*id.value())))
-        .take(n.unwrap_or(usize::MAX))
-        .collect(),
-    )
+    after: Option<(Txid, Txid)>,
+    limit: u64,
+  ) -> Result<Vec<PendingReveal>> {
+    let (commit_txid, reveal_txid) = after
+      .map(|(commit, reveal)| (commit.to_string(), reveal.to_string()))
+      .unwrap_or_default();
+    let query = format!(
+      "SELECT reveal_txid, reveal_hex, created_height FROM {}
+       WHERE state IN ('built', 'pending')
+       AND (commit_txid, reveal_txid) > (:commit_txid, :reveal_txid)
+       ORDER BY commit_txid, reveal_txid LIMIT :limit",
+      self.get_reveal_expectation_table()
+    );
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, String, Option<u64>)> = conn
+      .exec(
+        query,
+        params! {
+          "commit_txid" => commit_txid,
+          "reveal_txid" => reveal_txid,
+          "limit" => limit,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    rows
+      .into_iter()
+      .map(|(reveal_txid, reveal_hex, created_height)| {
+        let reveal: Transaction =
+          Decodable::consensus_decode(&mut Vec::from_hex(&reveal_hex)?.as_slice())
+            .map_err(|err| anyhow!("invalid reveal {reveal_txid}: {err}"))?;
+        Ok(PendingReveal {
+          commit: reveal
+            .input
+            .first()
+            .ok_or_else(|| anyhow!("reveal {reveal_txid} has no inputs"))?
+            .previous_output,
+          reveal_txid: Txid::from_str(&reveal_txid)?,
+          created_height,
+        })
+      })
+      .collect()
   }
 
-  pub(crate) fn get_homepage_inscriptions(&self) -> Result<Vec<InscriptionId>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
-        .iter()?
-        .rev()
-        .take(8)
-        .map(|(_number, id)| Entry::load(*id.value()))
-        .collect(),
-    )
-  }
-
-  pub(crate) fn get_latest_inscriptions_with_prev_and_next(
+  pub(crate) fn settle_reveal_expectations(
     &self,
-    n: usize,
-    from: Option<u64>,
-  ) -> Result<(Vec<InscriptionId>, Option<u64>, Option<u64>)> {
-    let rtx = self.database.begin_read()?;
-
-    let inscription_number_to_inscription_id =
-      rtx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
+    settled: &[(Txid, RevealExpectationState, u64)],
+  ) -> Result {
+    if settled.is_empty() {
+      return Ok(());
+    }
 
-    let latest = match inscription_number_to_inscription_id.iter()?.rev().next() {
-      Some((number, _id)) => number.value(),
-      None => return Ok(Default::default()),
-    };
+    let query = format!(
+      "UPDATE {} SET state = :state, height = :height
+       WHERE reveal_txid = :reveal_txid AND state IN ('built', 'pending')",
+      self.get_reveal_expectation_table()
+    );
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for (reveal_txid, state, height) in settled {
+      tx.exec_drop(
+        query.clone(),
+        params! {
+          "reveal_txid" => reveal_txid.to_string(),
+          "state" => state.as_str(),
+          "height" => height,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
+  }
 
-    let from = from.unwrap_or(latest);
+  pub fn get_reveal_expectations(
+    &self,
+    state: Option<RevealExpectationState>,
+    after: Option<Txid>,
+    limit: u32,
+  ) -> Result<RevealExpectationPage> {
+    let mut conditions = vec!["reveal_txid > :after"];
+    if state.is_some() {
+      conditions.push("state = :state");
+    }
+    let query = format!(
+      "SELECT reveal_txid, commit_txid, source, state, height, created_height FROM {}
+       WHERE {} ORDER BY reveal_txid LIMIT :limit",
+      self.get_reveal_expectation_table(),
+      conditions.join(" AND ")
+    );
 
-    let prev = if let Some(prev) = from.checked_sub(n.try_into()?) {
-      inscription_number_to_inscription_id
-        .get(&prev)?
-        .map(|_| prev)
-    } else {
-      None
-    };
+    let mut params = vec![
+      (
+        "after".to_owned(),
+        mysql::Value::from(after.map(|txid| txid.to_string()).unwrap_or_default()),
+      ),
+      ("limit".to_owned(), mysql::Value::from(limit)),
+    ];
+    if let Some(state) = state {
+      params.push(("state".to_owned(), mysql::Value::from(state.as_str())));
+    }
 
-    let next = if from < latest {
-      Some(
-        from
-          .checked_add(n.try_into()?)
-          .unwrap_or(latest)
-          .min(latest),
-      )
-    } else {
-      None
-    };
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, String, String, String, Option<u64>, Option<u64>)> = conn
+      .exec(query, mysql::Params::from(params))
+      .map_err(|_| anyhow!("Query fail"))?;
 
-    let inscriptions = inscription_number_to_inscription_id
-      .range(..=from)?
-      .rev()
-      .take(n)
-      .map(|(_number, id)| Entry::load(*id.value()))
-      .collect();
+    Ok(RevealExpectationPage::new(
+      rows
+        .into_iter()
+        .map(
+          |(reveal_txid, commit_txid, source, state, height, created_height)| {
+            RevealExpectationRow {
+              reveal_txid,
+              commit_txid,
+              source,
+              state,
+              height,
+              created_height,
+            }
+          },
+        )
+        .collect(),
+      limit,
+    ))
+  }
+}
 
-    Ok((inscriptions, prev, next))
+impl MysqlDatabase {
+  pub fn get_airdrop_job_table(&self) -> String {
+    "AIRDROP_JOB".to_owned()
   }
 
-  pub(crate) fn get_feed_inscriptions(&self, n: usize) -> Result<Vec<(u64, InscriptionId)>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
-        .iter()?
-        .rev()
-        .take(n)
-        .map(|(number, id)| (number.value(), Entry::load(*id.value())))
-        .collect(),
-    )
+  pub fn get_airdrop_batch_table(&self) -> String {
+    "AIRDROP_BATCH".to_owned()
   }
 
-  pub(crate) fn get_inscription_entry(
-    &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<InscriptionEntry>> {
-    Ok(
-      self
-        .database
-        .begin_read()?
-        .open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?
-        .get(&inscription_id.store())?
-        .map(|value| InscriptionEntry::load(value.value())),
+  /// Stores a job with one pending row per batch, `batches` holds the rows
+  /// of each batch as json.
+  pub fn insert_airdrop_job(&self, job: &AirdropJob, batches: &[String]) -> Result {
+    let job_query = format!(
+      "INSERT INTO {} (id, source, fee_rate, extension)
+       VALUES (:id, :source, :fee_rate, :extension)",
+      self.get_airdrop_job_table()
+    );
+    let batch_query = format!(
+      "INSERT INTO {} (job_id, batch, rows_json) VALUES (:job_id, :batch, :rows_json)",
+      self.get_airdrop_batch_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    tx.exec_drop(
+      job_query,
+      params! {
+        "id" => job.id.clone(),
+        "source" => job.source.clone(),
+        "fee_rate" => job.fee_rate,
+        "extension" => job.extension.clone(),
+      },
     )
+    .map_err(|_| anyhow!("Execute transaction fail"))?;
+    for (batch, rows) in batches.iter().enumerate() {
+      tx.exec_drop(
+        batch_query.clone(),
+        params! {
+          "job_id" => job.id.clone(),
+          "batch" => batch,
+          "rows_json" => rows.clone(),
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit()
+      .map_err(|_| anyhow!("Commit transaction fail"))?;
+    Ok(())
   }
 
-  #[cfg(test)]
-  fn assert_inscription_location(
-    &self,
-    inscription_id: InscriptionId,
-    satpoint: SatPoint,
-    sat: Option<u64>,
-  ) {
-    let rtx = self.database.begin_read().unwrap();
+  pub fn get_airdrop_job(&self, id: &str) -> Result<Option<AirdropJob>> {
+    let query = format!(
+      "SELECT id, source, fee_rate, extension FROM {} WHERE id = :id",
+      self.get_airdrop_job_table()
+    );
+    let mut conn = self.get_conn()?;
+    let row: Option<(String, String, f64, Option<String>)> = conn
+      .exec_first(query, params! { "id" => id })
+      .map_err(|_| anyhow!("Query fail"))?;
+    Ok(row.map(|(id, source, fee_rate, extension)| AirdropJob {
+      id,
+      source,
+      fee_rate,
+      extension,
+    }))
+  }
 
-    let satpoint_to_inscription_id = rtx.open_table(SATPOINT_TO_INSCRIPTION_ID).unwrap();
+  pub fn get_airdrop_batches(&self, job_id: &str) -> Result<Vec<AirdropBatch>> {
+    let query = format!(
+      "SELECT batch, state, rows_json, error, output FROM {} WHERE job_id = :job_id ORDER BY batch",
+      self.get_airdrop_batch_table()
+    );
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(u32, String, String, Option<String>, Option<String>)> = conn
+      .exec(query, params! { "job_id" => job_id })
+      .map_err(|_| anyhow!("Query fail"))?;
+    rows
+      .into_iter()
+      .map(|(batch, state, rows, error, output)| {
+        Ok(AirdropBatch {
+          batch,
+          state,
+          rows,
+          error,
+          output: output
+            .map(|output| serde_json::from_str(&output))
+            .transpose()?,
+        })
+      })
+      .collect()
+  }
 
-    let inscription_id_to_satpoint = rtx.open_table(INSCRIPTION_ID_TO_SATPOINT).unwrap();
+  /// Claims the oldest pending batch for building.
+  pub fn claim_airdrop_batch(&self) -> Result<Option<(String, u32)>> {
+    let tb = self.get_airdrop_batch_table();
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    let next: Option<(String, u32)> = tx
+      .query_first(format!(
+        "SELECT job_id, batch FROM {tb} WHERE state = 'pending'
+         ORDER BY updated_at, job_id, batch LIMIT 1 FOR UPDATE"
+      ))
+      .map_err(|_| anyhow!("Query fail"))?;
+    if let Some((job_id, batch)) = &next {
+      tx.exec_drop(
+        format!("UPDATE {tb} SET state = 'building' WHERE job_id = :job_id AND batch = :batch"),
+        params! { "job_id" => job_id.clone(), "batch" => *batch },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit()
+      .map_err(|_| anyhow!("Commit transaction fail"))?;
+    Ok(next)
+  }
 
-    assert_eq!(
-      satpoint_to_inscription_id.len().unwrap(),
-      inscription_id_to_satpoint.len().unwrap(),
+  /// Records the result of building a claimed batch, `output` is json.
+  pub fn finish_airdrop_batch(&self, job_id: &str, batch: u32, result: Result<String>) -> Result {
+    let (state, output, error) = match result {
+      Ok(output) => ("built", Some(output), None),
+      Err(err) => ("failed", None, Some(err.to_string())),
+    };
+    let query = format!(
+      "UPDATE {} SET state = :state, output = :output, error = :error
+       WHERE job_id = :job_id AND batch = :batch",
+      self.get_airdrop_batch_table()
     );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        query,
+        params! {
+          "state" => state,
+          "output" => output,
+          "error" => error,
+          "job_id" => job_id,
+          "batch" => batch,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
 
-    assert_eq!(
-      SatPoint::load(
-        *inscription_id_to_satpoint
-          .get(&inscription_id.store())
-          .unwrap()
-          .unwrap()
-          .value()
-      ),
-      satpoint,
+  /// Returns batches a previous process was building to pending.
+  pub fn reset_airdrop_batches(&self) -> Result<u64> {
+    let query = format!(
+      "UPDATE {} SET state = 'pending' WHERE state = 'building'",
+      self.get_airdrop_batch_table()
     );
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop(query)
+      .map_err(|_| anyhow!("Execute fail"))?;
+    Ok(conn.affected_rows())
+  }
+}
 
-    assert_eq!(
-      InscriptionId::load(
-        *satpoint_to_inscription_id
-          .get(&satpoint.store())
-          .unwrap()
-          .unwrap()
-          .value()
-      ),
-      inscription_id,
+impl MysqlDatabase {
+  pub fn get_reveal_key_table(&self) -> String {
+    "REVEAL_KEY".to_owned()
+  }
+
+  /// Stores the salt the reveals of `commit_txid` were signed under, never
+  /// the key itself.
+  pub fn insert_reveal_key_salt(&self, commit_txid: Txid, salt: &str) -> Result {
+    let tb = self.get_reveal_key_table();
+    let query = format!(
+      "INSERT IGNORE INTO {} (commit_txid, salt) VALUES (:commit_txid, :salt)",
+      tb
     );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        query,
+        params! {
+          "commit_txid" => commit_txid.to_string(),
+          "salt" => salt,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
 
-    if let Some(sat) = sat {
-      if self.has_sat_index().unwrap() {
-        assert_eq!(
-          InscriptionId::load(
-            *rtx
-              .open_table(SAT_TO_INSCRIPTION_ID)
-              .unwrap()
-              .get(&sat)
-              .unwrap()
-              .unwrap()
-              .value()
-          ),
-          inscription_id,
-        );
+  pub fn get_reveal_key_salt(&self, commit_txid: Txid) -> Result<Option<String>> {
+    let tb = self.get_reveal_key_table();
+    let query = format!("SELECT salt FROM {} WHERE commit_txid = :commit_txid", tb);
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_first(query, params! { "commit_txid" => commit_txid.to_string() })
+      .map_err(|_| anyhow!("Query fail"))
+  }
+}
 
-        assert_eq!(
-          SatPoint::load(
-            *rtx
-              .open_table(SAT_TO_SATPOINT)
-              .unwrap()
-              .get(&sat)
-              .unwrap()
-              .unwrap()
-              .value()
-          ),
-          satpoint,
-        );
-      }
-    }
+impl MysqlDatabase {
+  pub fn get_revenue_table(&self) -> String {
+    "REVENUE".to_owned()
   }
 
-  fn inscriptions_on_output<'a: 'tx, 'tx>(
-    satpoint_to_id: &'a impl ReadableTable<&'static SatPointValue, &'static InscriptionIdValue>,
-    outpoint: OutPoint,
-  ) -> Result<impl Iterator<Item = (SatPoint, InscriptionId)> + 'tx> {
-    let start = SatPoint {
-      outpoint,
-      offset: 0,
+  pub fn insert_revenue(&self, rows: &[Revenue]) -> Result {
+    if rows.is_empty() {
+      return Ok(());
     }
-    .store();
 
-    let end = SatPoint {
-      outpoint,
-      offset: u64::MAX,
+    let query = format!(
+      "INSERT IGNORE INTO {} (commit_txid, commit_vout, reveal_txid, source, postage, reveal_fee, service_fee, service_address, service_vout, purpose)
+       VALUES (:commit_txid, :commit_vout, :reveal_txid, :source, :postage, :reveal_fee, :service_fee, :service_address, :service_vout, :purpose)",
+      self.get_revenue_table()
+    );
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for row in rows {
+      tx.exec_drop(
+        query.clone(),
+        params! {
+          "commit_txid" => row.commit_txid.to_string(),
+          "commit_vout" => row.commit_vout,
+          "reveal_txid" => row.reveal_txid.to_string(),
+          "source" => row.source.clone(),
+          "postage" => row.breakdown.postage,
+          "reveal_fee" => row.breakdown.reveal_fee,
+          "service_fee" => row.breakdown.service_fee,
+          "service_address" => row.service_output.as_ref().map(|(address, _)| address.clone()),
+          "service_vout" => row.service_output.as_ref().map(|(_, vout)| *vout),
+          "purpose" => row.purpose.clone(),
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
     }
-    .store();
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
+  }
 
-    Ok(
-      satpoint_to_id
-        .range::<&[u8; 44]>(&start..=&end)?
-        .map(|(satpoint, id)| (Entry::load(*satpoint.value()), Entry::load(*id.value()))),
-    )
+  /// Revenue per service address, including configured `addresses` nothing
+  /// was paid to yet. Outputs that paid no service fee have no address.
+  pub fn get_revenue_totals(
+    &self,
+    addresses: &[String],
+    purpose: &PurposeFilter,
+  ) -> Result<Vec<RevenueTotal>> {
+    let query = format!(
+      "SELECT service_address, COUNT(*), CAST(SUM(postage) AS UNSIGNED),
+              CAST(SUM(reveal_fee) AS UNSIGNED), CAST(SUM(service_fee) AS UNSIGNED)
+       FROM {} WHERE {} GROUP BY service_address",
+      self.get_revenue_table(),
+      purpose.condition()
+    );
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(Option<String>, u64, u64, u64, u64)> = conn
+      .exec(query, purpose.params(Vec::new()))
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(RevenueTotal::with_addresses(
+      rows
+        .into_iter()
+        .map(
+          |(service_address, outputs, postage, reveal_fee, service_fee)| RevenueTotal {
+            service_address,
+            outputs,
+            postage,
+            reveal_fee,
+            service_fee,
+          },
+        )
+        .collect(),
+      addresses,
+    ))
   }
 }
 
-#[cfg(test)]
-mod tests {
-  use bip39::Language;
-  use {
-    super::*,
-    bitcoin::secp256k1::rand::{self, RngCore},
-  };
+impl MysqlDatabase {
+  pub fn get_api_key_usage_table(&self) -> String {
+    "API_KEY_USAGE".to_owned()
+  }
 
-  struct ContextBuilder {
-    args: Vec<OsString>,
-    tempdir: Option<TempDir>,
+  pub fn get_api_key_violation_table(&self) -> String {
+    "API_KEY_VIOLATION".to_owned()
   }
 
-  impl ContextBuilder {
-    fn build(self) -> Context {
-      self.try_build().unwrap()
+  pub fn get_api_key_day_table(&self) -> String {
+    "API_KEY_DAY".to_owned()
+  }
+
+  /// The sources `api_key` acted for on `day`.
+  pub fn get_api_key_sources(&self, api_key: &str, day: NaiveDate) -> Result<Vec<String>> {
+    let query = format!(
+      "SELECT source FROM {} WHERE api_key = :api_key AND day = :day",
+      self.get_api_key_usage_table()
+    );
+    self
+      .get_conn()?
+      .exec(
+        query,
+        params! {
+          "api_key" => api_key,
+          "day" => day.to_string(),
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))
+  }
+
+  /// Counts a request of `api_key` for `source` on `day`. A source the key
+  /// has not acted for that day is only taken while it has fewer than
+  /// `max_sources`, otherwise nothing is counted and `false` is returned.
+  /// The key's counter row of the day is locked until the count is
+  /// written, so concurrent requests cannot take it past the cap.
+  pub fn use_api_key_source(
+    &self,
+    api_key: &str,
+    source: &str,
+    day: NaiveDate,
+    max_sources: Option<u64>,
+  ) -> Result<bool> {
+    let days = self.get_api_key_day_table();
+    let usage = self.get_api_key_usage_table();
+    let day = day.to_string();
+    let mut conn = self.get_conn()?;
+
+    // created outside the transaction, whose shared lock on an existing row
+    // would deadlock concurrent requests upgrading it
+    conn
+      .exec_drop(
+        format!("INSERT IGNORE INTO {days} (api_key, day) VALUES (:api_key, :day)"),
+        params! {
+          "api_key" => api_key,
+          "day" => &day,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))?;
+
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    let sources: u64 = tx
+      .exec_first(
+        format!("SELECT sources FROM {days} WHERE api_key = :api_key AND day = :day FOR UPDATE"),
+        params! {
+          "api_key" => api_key,
+          "day" => &day,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?
+      .ok_or_else(|| anyhow!("Query fail"))?;
+    let known: Option<u64> = tx
+      .exec_first(
+        format!(
+          "SELECT requests FROM {usage} WHERE api_key = :api_key AND day = :day AND source = :source"
+        ),
+        params! {
+          "api_key" => api_key,
+          "day" => &day,
+          "source" => source,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    if known.is_none() && max_sources.map_or(false, |max| sources >= max) {
+      return Ok(false);
     }
 
-    fn try_build(self) -> Result<Context> {
-      let rpc_server = test_bitcoincore_rpc::builder()
-        .network(Network::Regtest)
-        .build();
+    tx.exec_drop(
+      format!(
+        "INSERT INTO {usage} (api_key, day, source, requests) VALUES (:api_key, :day, :source, 1)
+         ON DUPLICATE KEY UPDATE requests = requests + 1"
+      ),
+      params! {
+        "api_key" => api_key,
+        "day" => &day,
+        "source" => source,
+      },
+    )
+    .map_err(|_| anyhow!("Execute transaction fail"))?;
+    if known.is_none() {
+      tx.exec_drop(
+        format!("UPDATE {days} SET sources = sources + 1 WHERE api_key = :api_key AND day = :day"),
+        params! {
+          "api_key" => api_key,
+          "day" => &day,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    tx.commit()
+      .map_err(|_| anyhow!("Commit transaction fail"))?;
+    Ok(true)
+  }
 
-      let tempdir = self.tempdir.unwrap_or_else(|| TempDir::new().unwrap());
-      let cookie_file = tempdir.path().join("cookie");
-      fs::write(&cookie_file, "username:password").unwrap();
+  /// Records a request its api key's policy refused. `source` is empty for
+  /// requests that named none.
+  pub fn insert_api_key_violation(
+    &self,
+    api_key: &str,
+    source: &str,
+    day: NaiveDate,
+    reason: &str,
+  ) -> Result {
+    let query = format!(
+      "INSERT INTO {} (api_key, day, source, reason)
+       VALUES (:api_key, :day, :source, :reason)",
+      self.get_api_key_violation_table()
+    );
+    self
+      .get_conn()?
+      .exec_drop(
+        query,
+        params! {
+          "api_key" => api_key,
+          "day" => day.to_string(),
+          "source" => source,
+          "reason" => reason,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))
+  }
 
-      let command: Vec<OsString> = vec![
-        "ord".into(),
-        "--rpc-url".into(),
-        rpc_server.url().into(),
-        "--data-dir".into(),
-        tempdir.path().into(),
-        "--cookie-file".into(),
-        cookie_file.into(),
-        "--regtest".into(),
-      ];
+  /// The usage of every api key that made a request or was refused on
+  /// `day`, by key.
+  pub fn get_api_key_usage(&self, day: NaiveDate) -> Result<Vec<ApiKeyUsage>> {
+    let mut conn = self.get_conn()?;
+    let usage: Vec<(String, u64, u64)> = conn
+      .exec(
+        format!(
+          "SELECT api_key, COUNT(*), CAST(SUM(requests) AS UNSIGNED) FROM {}
+           WHERE day = :day GROUP BY api_key",
+          self.get_api_key_usage_table()
+        ),
+        params! { "day" => day.to_string() },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    let violations: Vec<(String, u64)> = conn
+      .exec(
+        format!(
+          "SELECT api_key, COUNT(*) FROM {} WHERE day = :day GROUP BY api_key",
+          self.get_api_key_violation_table()
+        ),
+        params! { "day" => day.to_string() },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    let empty = |api_key: String| ApiKeyUsage {
+      api_key,
+      day,
+      distinct_sources: 0,
+      requests: 0,
+      violations: 0,
+    };
+    let mut keys = BTreeMap::new();
+    for (api_key, distinct_sources, requests) in usage {
+      let entry = keys
+        .entry(api_key.clone())
+        .or_insert_with(|| empty(api_key));
+      entry.distinct_sources = distinct_sources;
+      entry.requests = requests;
+    }
+    for (api_key, count) in violations {
+      keys
+        .entry(api_key.clone())
+        .or_insert_with(|| empty(api_key))
+        .violations = count;
+    }
+    Ok(keys.into_values().collect())
+  }
+}
 
-      let options = Options::try_parse_from(command.into_iter().chain(self.args)).unwrap();
-      let index = Index::open(&options)?;
-      index.update().unwrap();
+impl MysqlDatabase {
+  pub fn get_maintenance_table(&self) -> String {
+    "SERVER_MAINTENANCE".to_owned()
+  }
 
-      Ok(Context {
-        options,
-        rpc_server,
-        tempdir,
-        index,
-      })
+  pub fn get_maintenance(&self) -> Result<Option<(bool, Option<String>, Option<u64>)>> {
+    let tb = self.get_maintenance_table();
+    let query = format!(
+      "SELECT enabled, message, until_timestamp FROM {} WHERE id = 1",
+      tb
+    );
+    let mut conn = self.get_conn()?;
+    conn.query_first(query).map_err(|_| anyhow!("Query fail"))
+  }
+
+  pub fn set_maintenance(
+    &self,
+    enabled: bool,
+    message: Option<String>,
+    until: Option<u64>,
+  ) -> Result {
+    let tb = self.get_maintenance_table();
+    let query = format!(
+      "INSERT INTO {} (id, enabled, message, until_timestamp)
+       VALUES (1, :enabled, :message, :until)
+       ON DUPLICATE KEY UPDATE enabled = :enabled, message = :message, until_timestamp = :until",
+      tb
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        query,
+        params! {
+          "enabled" => enabled,
+          "message" => message,
+          "until" => until,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_settings_table(&self) -> String {
+    "SETTINGS".to_owned()
+  }
+
+  /// Refuse a database stamped for another network. A database without a
+  /// stamp is stamped right away when it holds no inscriptions, and only
+  /// with `adopt` otherwise.
+  pub fn check_network(&self, adopt: bool) -> Result {
+    let tb = self.get_settings_table();
+    let network = self.network.to_string();
+    let database = Self::get_database(self.network);
+    let select = format!("SELECT value FROM {} WHERE name = 'network'", tb);
+    let mut conn = self.get_conn()?;
+    let recorded: Option<String> = conn
+      .query_first(&select)
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    if Self::recorded_network(&database, recorded, &network)? {
+      return Ok(());
     }
 
-    fn arg(mut self, arg: impl Into<OsString>) -> Self {
-      self.args.push(arg.into());
-      self
+    let populated = conn
+      .query_first::<u8, _>(format!(
+        "SELECT 1 FROM {} LIMIT 1",
+        self.get_inscription_table()
+      ))
+      .ok()
+      .flatten()
+      .is_some();
+
+    if populated && !adopt {
+      bail!(
+        "mysql database {database} does not record which network it was built for, rerun with --adopt-data-dir to mark it as {network}"
+      );
     }
 
-    fn args<T: Into<OsString>, I: IntoIterator<Item = T>>(mut self, args: I) -> Self {
-      self.args.extend(args.into_iter().map(|arg| arg.into()));
-      self
+    // sync and server starting on a fresh database may both stamp it, the
+    // first stamp wins and the other is checked against it
+    conn
+      .exec_drop(
+        format!(
+          "INSERT IGNORE INTO {} (name, value) VALUES ('network', :value)",
+          tb
+        ),
+        params! { "value" => &network },
+      )
+      .map_err(|_| anyhow!("Execute fail"))?;
+
+    let recorded: Option<String> = conn
+      .query_first(&select)
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    if !Self::recorded_network(&database, recorded, &network)? {
+      bail!("mysql database {database} could not be stamped for {network}");
     }
 
-    fn tempdir(mut self, tempdir: TempDir) -> Self {
-      self.tempdir = Some(tempdir);
-      self
+    Ok(())
+  }
+
+  /// Whether `recorded` is `network`, failing when it is another network.
+  fn recorded_network(database: &str, recorded: Option<String>, network: &str) -> Result<bool> {
+    match recorded {
+      Some(recorded) if recorded == network => Ok(true),
+      Some(recorded) => bail!(
+        "mysql database {database} was built for {recorded}, refusing to use it for {network}"
+      ),
+      None => Ok(false),
     }
   }
+}
 
-  struct Context {
-    options: Options,
-    rpc_server: test_bitcoincore_rpc::Handle,
-    #[allow(unused)]
-    tempdir: TempDir,
-    index: Index,
+impl MysqlDatabase {
+  pub fn get_watched_address_table(&self) -> String {
+    "WATCHED_ADDRESS".to_owned()
   }
 
-  impl Context {
-    fn builder() -> ContextBuilder {
-      ContextBuilder {
-        args: Vec::new(),
-        tempdir: None,
+  pub fn watch_address(&self, address: &str) -> Result {
+    let tb = self.get_watched_address_table();
+    let query = format!("INSERT IGNORE INTO {} (address) VALUES (:address)", tb);
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "address" => address })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn is_watched(&self, address: &str) -> Result<bool> {
+    let tb = self.get_watched_address_table();
+    let query = format!("SELECT address FROM {} WHERE address = :address", tb);
+    let mut conn = self.get_conn()?;
+    let result: Option<String> = conn
+      .exec_first(query, params! { "address" => address })
+      .map_err(|_| anyhow!("Query fail"))?;
+    Ok(result.is_some())
+  }
+
+  pub fn get_watched_addresses(&self) -> Result<HashSet<String>> {
+    let tb = self.get_watched_address_table();
+    let query = format!("SELECT address FROM {}", tb);
+    let mut conn = self.get_conn()?;
+    let result: Vec<String> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+    Ok(result.into_iter().collect())
+  }
+
+  pub fn get_unbackfilled_addresses(&self) -> Result<Vec<String>> {
+    let tb = self.get_watched_address_table();
+    let query = format!("SELECT address FROM {} WHERE backfilled = FALSE", tb);
+    let mut conn = self.get_conn()?;
+    conn.query(query).map_err(|_| anyhow!("Query fail"))
+  }
+
+  pub fn mark_backfilled(&self, address: &str) -> Result {
+    let tb = self.get_watched_address_table();
+    let query = format!(
+      "UPDATE {} SET backfilled = TRUE WHERE address = :address",
+      tb
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "address" => address })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  /// Those of `inscription_ids` that already have a row, looked up by id so
+  /// the whole table is never loaded.
+  pub fn get_tracked_inscription_ids(
+    &self,
+    inscription_ids: &[InscriptionId],
+  ) -> Result<HashSet<InscriptionId>> {
+    Ok(
+      self
+        .get_inscription_addresses(inscription_ids)?
+        .into_keys()
+        .collect(),
+    )
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_activity_table(&self) -> String {
+    "INSCRIPTION_ACTIVITY".to_owned()
+  }
+
+  /// Turns one block's inscription moves into per-address events. `previous`
+  /// holds the address each inscription was at before the block.
+  pub(crate) fn activity_events(
+    height: u64,
+    tx_indices: &HashMap<Txid, u32>,
+    data: &[MysqlInscription],
+    previous: &HashMap<InscriptionId, String>,
+  ) -> Vec<ActivityEvent> {
+    let mut latest = previous.clone();
+    let mut moves_in_tx: HashMap<Txid, u32> = HashMap::new();
+    let mut events = vec![];
+
+    let known = |address: &str| (!address.is_empty()).then(|| address.to_owned());
+
+    for item in data {
+      let txid = item.new_satpoint.outpoint.txid;
+      let Some(tx_index) = tx_indices.get(&txid).copied() else {
+        continue;
+      };
+      let moves = moves_in_tx.entry(txid).or_default();
+      let cursor = |seq| ActivityCursor {
+        height,
+        tx_index,
+        seq: *moves * 2 + seq,
+      };
+
+      let new_address = known(&item.new_address);
+      let old_address = latest.insert(item.inscription_id, item.new_address.clone());
+
+      let event = |address, seq, kind, counterparty| ActivityEvent {
+        address,
+        cursor: cursor(seq),
+        kind,
+        inscription_id: item.inscription_id,
+        txid,
+        counterparty,
+      };
+
+      match (old_address.as_deref().and_then(known), new_address) {
+        (_, Some(new_address)) if item.created => events.push(event(
+          new_address,
+          0,
+          ActivityKind::InscriptionCreated,
+          None,
+        )),
+        (Some(old_address), new_address) if Some(&old_address) != new_address.as_ref() => {
+          events.push(event(
+            old_address.clone(),
+            0,
+            ActivityKind::InscriptionSent,
+            new_address.clone(),
+          ));
+          if let Some(new_address) = new_address {
+            events.push(event(
+              new_address,
+              1,
+              ActivityKind::InscriptionReceived,
+              Some(old_address),
+            ));
+          }
+        }
+        (None, Some(new_address)) => events.push(event(
+          new_address,
+          1,
+          ActivityKind::InscriptionReceived,
+          None,
+        )),
+        _ => {}
       }
-    }
 
-    fn mine_blocks(&self, n: u64) -> Vec<Block> {
-      let blocks = self.rpc_server.mine_blocks(n);
-      self.index.update().unwrap();
-      blocks
+      *moves += 1;
     }
 
-    fn mine_blocks_with_subsidy(&self, n: u64, subsidy: u64) -> Vec<Block> {
-      let blocks = self.rpc_server.mine_blocks_with_subsidy(n, subsidy);
-      self.index.update().unwrap();
-      blocks
+    events
+  }
+
+  fn get_inscription_addresses(
+    &self,
+    inscription_ids: &[InscriptionId],
+  ) -> Result<HashMap<InscriptionId, String>> {
+    let mut conn = self.get_conn()?;
+    let mut addresses = HashMap::new();
+    for chunk in inscription_ids.chunks(1000) {
+      let query = format!(
+        "SELECT inscription_id, new_address FROM {} WHERE inscription_id IN ({})",
+        self.get_inscription_table(),
+        vec!["?"; chunk.len()].join(", ")
+      );
+      let rows: Vec<(String, String)> = conn
+        .exec(
+          query,
+          chunk
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>(),
+        )
+        .map_err(|_| anyhow!("Query fail"))?;
+      for (inscription_id, new_address) in rows {
+        addresses.insert(InscriptionId::from_str(&inscription_id)?, new_address);
+      }
     }
+    Ok(addresses)
+  }
 
-    fn configurations() -> Vec<Context> {
-      vec![
-        Context::builder().build(),
-        Context::builder().arg("--index-sats").build(),
-      ]
+  /// Records activity for one block, must run before the block's rows are
+  /// written to the inscription table. Rows are keyed by address and
+  /// position, so indexing a block twice writes nothing new.
+  pub fn insert_activity(
+    &self,
+    height: u64,
+    tx_indices: &HashMap<Txid, u32>,
+    data: &[MysqlInscription],
+  ) -> Result<usize> {
+    if data.is_empty() {
+      return Ok(0);
     }
+
+    let inscription_ids = data
+      .iter()
+      .map(|item| item.inscription_id)
+      .collect::<Vec<InscriptionId>>();
+    let previous = self.get_inscription_addresses(&inscription_ids)?;
+    let events = Self::activity_events(height, tx_indices, data, &previous);
+
+    let query = format!(
+      "INSERT IGNORE INTO {} (address, height, tx_index, seq, kind, inscription_id, txid, counterparty)
+       VALUES (:address, :height, :tx_index, :seq, :kind, :inscription_id, :txid, :counterparty)",
+      self.get_activity_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_batch(
+        query,
+        events.iter().map(|event| {
+          params! {
+            "address" => &event.address,
+            "height" => event.cursor.height,
+            "tx_index" => event.cursor.tx_index,
+            "seq" => event.cursor.seq,
+            "kind" => event.kind.as_str(),
+            "inscription_id" => event.inscription_id.to_string(),
+            "txid" => event.txid.to_string(),
+            "counterparty" => &event.counterparty,
+          }
+        }),
+      )
+      .map_err(|_| anyhow!("Execute fail"))?;
+
+    Ok(events.len())
   }
 
-  #[test]
-  fn height_limit() {
-    {
-      let context = Context::builder().args(["--height-limit", "0"]).build();
-      context.mine_blocks(1);
-      assert_eq!(context.index.height().unwrap(), None);
-      assert_eq!(context.index.block_count().unwrap(), 0);
+  pub fn get_activity(
+    &self,
+    address: &str,
+    before: Option<ActivityCursor>,
+    limit: u32,
+  ) -> Result<ActivityPage> {
+    let before = before.unwrap_or(ActivityCursor {
+      height: u64::MAX,
+      tx_index: u32::MAX,
+      seq: u32::MAX,
+    });
+    let query = format!(
+      "SELECT kind, inscription_id, txid, height, tx_index, seq, counterparty FROM {}
+       WHERE address = :address AND (height, tx_index, seq) < (:height, :tx_index, :seq) AND {}
+       ORDER BY height DESC, tx_index DESC, seq DESC LIMIT :limit",
+      self.get_activity_table(),
+      self.not_blocked("inscription_id")
+    );
+
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, String, String, u64, u32, u32, Option<String>)> = conn
+      .exec(
+        query,
+        params! {
+          "address" => address,
+          "height" => before.height,
+          "tx_index" => before.tx_index,
+          "seq" => before.seq,
+          "limit" => limit,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    let mut activity = vec![];
+    for (kind, inscription_id, txid, height, tx_index, seq, counterparty) in rows {
+      activity.push(Activity {
+        kind: kind.parse()?,
+        inscription_id: inscription_id.parse()?,
+        txid: txid.parse()?,
+        height,
+        tx_index,
+        counterparty,
+        cursor: ActivityCursor {
+          height,
+          tx_index,
+          seq,
+        }
+        .to_string(),
+      });
     }
+    Ok(ActivityPage::new(activity, limit))
+  }
 
-    {
-      let context = Context::builder().args(["--height-limit", "1"]).build();
-      context.mine_blocks(1);
-      assert_eq!(context.index.height().unwrap(), Some(Height(0)));
-      assert_eq!(context.index.block_count().unwrap(), 1);
+  pub fn rollback_activity(&self, height: u64) -> Result {
+    let query = format!(
+      "DELETE FROM {} WHERE height > :height",
+      self.get_activity_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "height" => height })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_content_hash_table(&self) -> String {
+    "INSCRIPTION_CONTENT_HASH".to_owned()
+  }
+
+  pub fn insert_content_hashes(&self, rows: &[ContentHash]) -> Result {
+    if rows.is_empty() {
+      return Ok(());
     }
 
-    {
-      let context = Context::builder().args(["--height-limit", "2"]).build();
-      context.mine_blocks(2);
-      assert_eq!(context.index.height().unwrap(), Some(Height(1)));
-      assert_eq!(context.index.block_count().unwrap(), 2);
+    let query = format!(
+      "INSERT IGNORE INTO {} (address, content_hash, inscription_id, height)
+       VALUES (:address, :content_hash, :inscription_id, :height)",
+      self.get_content_hash_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_batch(
+        query,
+        rows.iter().map(|row| {
+          params! {
+            "address" => &row.address,
+            "content_hash" => row.content_hash.to_string(),
+            "inscription_id" => row.inscription_id.to_string(),
+            "height" => row.height,
+          }
+        }),
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  /// The inscription `address` first built or received with a body hashing
+  /// to `content_hash`, those already indexed before those only built.
+  pub fn get_content_duplicate(
+    &self,
+    address: &str,
+    content_hash: sha256::Hash,
+  ) -> Result<Option<InscriptionId>> {
+    let query = format!(
+      "SELECT inscription_id FROM {}
+       WHERE address = :address AND content_hash = :content_hash
+       ORDER BY height IS NULL, height, created_at
+       LIMIT 1",
+      self.get_content_hash_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    let inscription_id: Option<String> = conn
+      .exec_first(
+        query,
+        params! {
+          "address" => address,
+          "content_hash" => content_hash.to_string(),
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(inscription_id.map(|id| id.parse()).transpose()?)
+  }
+
+  /// Drops indexed rows above `height`, rows of builds are kept.
+  pub fn rollback_content_hashes(&self, height: u64) -> Result {
+    let query = format!(
+      "DELETE FROM {} WHERE height > :height",
+      self.get_content_hash_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "height" => height })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn get_genesis_table(&self) -> String {
+    "INSCRIPTION_GENESIS".to_owned()
+  }
+
+  pub fn insert_genesis(&self, rows: &[InscriptionGenesis]) -> Result {
+    if rows.is_empty() {
+      return Ok(());
+    }
+
+    let query = format!(
+      "INSERT IGNORE INTO {} (inscription_id, height, tx_index, input_index, content_type, address)
+       VALUES (:inscription_id, :height, :tx_index, :input_index, :content_type, :address)",
+      self.get_genesis_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_batch(
+        query,
+        rows.iter().map(|row| {
+          params! {
+            "inscription_id" => row.inscription_id.to_string(),
+            "height" => row.height,
+            "tx_index" => row.tx_index,
+            "input_index" => row.input_index,
+            "content_type" => &row.content_type,
+            "address" => &row.address,
+          }
+        }),
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn rollback_genesis(&self, height: u64) -> Result {
+    let query = format!(
+      "DELETE FROM {} WHERE height > :height",
+      self.get_genesis_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "height" => height })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  // every filter is an equality or prefix match on top of the position
+  // range, so either composite index serves it without a table scan
+  fn genesis_range_query(&self, paged: bool, filter: &GenesisFilter) -> String {
+    let mut conditions = vec!["height >= :start AND height <= :end".to_owned()];
+    if paged {
+      conditions
+        .push("(height, tx_index, input_index) > (:height, :tx_index, :input_index)".to_owned());
+    }
+    if filter.content_type_prefix.is_some() {
+      conditions.push("content_type LIKE :content_type".to_owned());
+    }
+    if filter.address.is_some() {
+      conditions.push("address = :address".to_owned());
+    }
+    conditions.push(self.not_blocked("inscription_id"));
+
+    format!(
+      "SELECT inscription_id, height, tx_index, input_index, content_type, address FROM {}
+       WHERE {}
+       ORDER BY height, tx_index, input_index LIMIT :limit",
+      self.get_genesis_table(),
+      conditions.join(" AND ")
+    )
+  }
+
+  /// Inscriptions created from `start` to `end` inclusive in chain order,
+  /// after `after` when paging.
+  pub fn get_inscriptions_by_height_range(
+    &self,
+    start: u64,
+    end: u64,
+    limit: u32,
+    after: Option<GenesisCursor>,
+    filter: &GenesisFilter,
+  ) -> Result<InscriptionsByHeightPage> {
+    let mut params = vec![
+      ("start".to_owned(), mysql::Value::from(start)),
+      ("end".to_owned(), mysql::Value::from(end)),
+      ("limit".to_owned(), mysql::Value::from(limit)),
+    ];
+    if let Some(after) = after {
+      params.push(("height".to_owned(), mysql::Value::from(after.height)));
+      params.push(("tx_index".to_owned(), mysql::Value::from(after.tx_index)));
+      params.push((
+        "input_index".to_owned(),
+        mysql::Value::from(after.input_index),
+      ));
+    }
+    if let Some(prefix) = &filter.content_type_prefix {
+      params.push((
+        "content_type".to_owned(),
+        mysql::Value::from(format!("{}%", Self::escape_like(prefix))),
+      ));
+    }
+    if let Some(address) = &filter.address {
+      params.push(("address".to_owned(), mysql::Value::from(address.clone())));
+    }
+
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, u64, u32, u32, Option<String>, String)> = conn
+      .exec(
+        self.genesis_range_query(after.is_some(), filter),
+        mysql::Params::from(params),
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    let mut inscriptions = vec![];
+    for (inscription_id, height, tx_index, input_index, content_type, address) in rows {
+      inscriptions.push(InscriptionGenesis {
+        inscription_id: inscription_id.parse()?,
+        height,
+        tx_index,
+        input_index,
+        content_type,
+        address,
+      });
+    }
+    Ok(InscriptionsByHeightPage::new(inscriptions, limit))
+  }
+
+  /// The inscription numbered `number`, the genesis rows counted in chain
+  /// order. Only matches ord's numbering when every genesis was recorded,
+  /// which is not the case in watched-only mode.
+  pub fn get_inscription_by_number(&self, number: u64) -> Result<Option<InscriptionId>> {
+    let query = format!(
+      "SELECT inscription_id FROM {}
+       ORDER BY height, tx_index, input_index LIMIT 1 OFFSET :number",
+      self.get_genesis_table()
+    );
+    let mut conn = self.get_conn()?;
+    let inscription_id: Option<String> = conn
+      .exec_first(query, params! { "number" => number })
+      .map_err(|_| anyhow!("Query fail"))?;
+    Ok(
+      inscription_id
+        .map(|inscription_id| inscription_id.parse())
+        .transpose()?,
+    )
+  }
+
+  fn escape_like(prefix: &str) -> String {
+    prefix
+      .replace('\\', "\\\\")
+      .replace('%', "\\%")
+      .replace('_', "\\_")
+  }
+
+  pub fn get_satpoint_history_table(&self) -> String {
+    "INSCRIPTION_SATPOINT_HISTORY".to_owned()
+  }
+
+  /// Records where each moved inscription ended up in the block at
+  /// `height`, the last of several moves within the block wins.
+  pub fn insert_satpoint_history(&self, height: u64, data: &[MysqlInscription]) -> Result {
+    if data.is_empty() {
+      return Ok(());
+    }
+
+    let query = format!(
+      "INSERT INTO {} (inscription_id, height, satpoint, address)
+       VALUES (:inscription_id, :height, :satpoint, :address)
+       ON DUPLICATE KEY UPDATE satpoint = :satpoint, address = :address",
+      self.get_satpoint_history_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_batch(
+        query,
+        data.iter().map(|item| {
+          params! {
+            "inscription_id" => item.inscription_id.to_string(),
+            "height" => height,
+            "satpoint" => item.new_satpoint.to_string(),
+            "address" => &item.new_address,
+          }
+        }),
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn rollback_satpoint_history(&self, height: u64) -> Result {
+    let query = format!(
+      "DELETE FROM {} WHERE height > :height",
+      self.get_satpoint_history_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "height" => height })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  /// What `address` holds now, or held at the end of the block at
+  /// `at_height` as far as satpoint history reaches back.
+  pub fn get_holdings(&self, address: &str, at_height: Option<u64>) -> Result<Vec<Holding>> {
+    let height = at_height.unwrap_or(u64::MAX);
+    let query = format!(
+      "SELECT inscription_id, height, satpoint, address FROM {tb}
+       WHERE height <= :height AND inscription_id IN (
+         SELECT inscription_id FROM {tb} WHERE address = :address AND height <= :height
+       ) AND {}
+       ORDER BY height",
+      self.not_blocked("inscription_id"),
+      tb = self.get_satpoint_history_table(),
+    );
+
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, u64, String, String)> = conn
+      .exec(query, params! { "address" => address, "height" => height })
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    let mut history = vec![];
+    for (inscription_id, height, satpoint, address) in rows {
+      history.push(SatpointChange {
+        inscription_id: inscription_id.parse()?,
+        height,
+        satpoint: satpoint.parse()?,
+        address,
+      });
+    }
+
+    if at_height.is_some() {
+      return Ok(Holding::at_height(address, height, &history));
+    }
+
+    let last_transfers = Holding::at_height(address, height, &history)
+      .into_iter()
+      .map(|holding| (holding.inscription_id, holding.last_transfer_height))
+      .collect::<HashMap<InscriptionId, Option<u64>>>();
+
+    let blocked = self.get_blocked_inscriptions()?;
+    Ok(
+      self
+        .get_inscription_by_address(&address.to_owned())?
+        .into_iter()
+        .filter(|(_, inscription_id)| !blocked.contains(inscription_id))
+        .map(|(satpoint, inscription_id)| Holding {
+          inscription_id,
+          satpoint,
+          last_transfer_height: last_transfers.get(&inscription_id).copied().flatten(),
+        })
+        .collect(),
+    )
+  }
+}
+
+impl MysqlDatabase {
+  pub const MAX_VOUCHERS_PER_REQUEST: u64 = 10_000;
+
+  pub fn get_voucher_table(&self) -> String {
+    "VOUCHER".to_owned()
+  }
+
+  /// Generates `count` single-use vouchers for `campaign`, valid until the
+  /// unix time `expires_at`.
+  pub fn create_vouchers(
+    &self,
+    campaign: &str,
+    count: u64,
+    expires_at: u64,
+    policy: VoucherPolicy,
+    admin_label: &str,
+  ) -> Result<Vec<String>> {
+    if count == 0 || count > Self::MAX_VOUCHERS_PER_REQUEST {
+      bail!(
+        "invalid voucher count {count}, expected 1 to {}",
+        Self::MAX_VOUCHERS_PER_REQUEST
+      );
+    }
+    if campaign.is_empty() || campaign.len() > 64 {
+      bail!("invalid voucher campaign `{campaign}`, expected 1 to 64 characters");
+    }
+    let discount_percent = policy.discount_percent()?;
+
+    let tokens = (0..count)
+      .map(|_| {
+        let mut token = [0; 16];
+        rand::thread_rng().fill_bytes(&mut token);
+        token.to_hex()
+      })
+      .collect::<Vec<String>>();
+
+    let query = format!(
+      "INSERT INTO {} (token, campaign, discount_percent, expires_at, created_by)
+       VALUES (:token, :campaign, :discount_percent, :expires_at, :created_by)",
+      self.get_voucher_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_batch(
+        query,
+        tokens.iter().map(|token| {
+          params! {
+            "token" => token,
+            "campaign" => campaign,
+            "discount_percent" => discount_percent,
+            "expires_at" => expires_at,
+            "created_by" => admin_label,
+          }
+        }),
+      )
+      .map_err(|_| anyhow!("Execute fail"))?;
+
+    Ok(tokens)
+  }
+
+  /// The policy of a voucher that can still be redeemed. Nothing is
+  /// consumed until `redeem_voucher`.
+  pub fn get_voucher_policy(&self, token: &str) -> Result<VoucherPolicy> {
+    let mut conn = self.get_conn()?;
+    let (discount_percent, expired, commit_txid) =
+      Self::get_voucher(&mut conn, &self.get_voucher_table(), token, false)?;
+    Self::check_voucher(token, expired, commit_txid.as_deref(), None)?;
+    Ok(VoucherPolicy::from_discount_percent(discount_percent))
+  }
+
+  /// Marks a voucher as redeemed by `commit_txid`. The row is locked for the
+  /// duration of the transaction, so only one of two concurrent redemptions
+  /// succeeds. Redeeming again for the same commit is a no-op.
+  pub fn redeem_voucher(&self, token: &str, commit_txid: Txid) -> Result {
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+
+    let table = self.get_voucher_table();
+    let (_, expired, redeemed_by) = Self::get_voucher(&mut tx, &table, token, true)?;
+    let commit_txid = commit_txid.to_string();
+    if redeemed_by.as_deref() == Some(commit_txid.as_str()) {
+      return Ok(());
+    }
+    Self::check_voucher(token, expired, redeemed_by.as_deref(), Some(&commit_txid))?;
+
+    tx.exec_drop(
+      format!(
+        "UPDATE {table} SET commit_txid = :commit_txid, redeemed_at = CURRENT_TIMESTAMP
+         WHERE token = :token"
+      ),
+      params! {
+        "commit_txid" => &commit_txid,
+        "token" => token,
+      },
+    )
+    .map_err(|_| anyhow!("Execute transaction fail"))?;
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  fn get_voucher(
+    conn: &mut impl Queryable,
+    table: &str,
+    token: &str,
+    for_update: bool,
+  ) -> Result<(u8, bool, Option<String>)> {
+    conn
+      .exec_first(
+        format!(
+          "SELECT discount_percent, expires_at <= UNIX_TIMESTAMP(), commit_txid FROM {table}
+           WHERE token = :token{}",
+          if for_update { " FOR UPDATE" } else { "" }
+        ),
+        params! { "token" => token },
+      )
+      .map_err(|_| anyhow!("Query fail"))?
+      .ok_or_else(|| anyhow!("voucher `{token}` does not exist"))
+  }
+
+  fn check_voucher(
+    token: &str,
+    expired: bool,
+    redeemed_by: Option<&str>,
+    commit_txid: Option<&str>,
+  ) -> Result {
+    if let Some(redeemed_by) = redeemed_by {
+      if Some(redeemed_by) != commit_txid {
+        bail!("voucher `{token}` has already been redeemed");
+      }
+    }
+    if expired {
+      bail!("voucher `{token}` has expired");
+    }
+    Ok(())
+  }
+
+  pub fn get_voucher_counts(&self, campaign: &str) -> Result<VoucherCounts> {
+    let query = format!(
+      "SELECT CASE
+         WHEN commit_txid IS NOT NULL THEN 'redeemed'
+         WHEN expires_at <= UNIX_TIMESTAMP() THEN 'expired'
+         ELSE 'remaining'
+       END AS state, COUNT(*) FROM {} WHERE campaign = :campaign GROUP BY state",
+      self.get_voucher_table()
+    );
+    let mut conn = self.get_conn()?;
+    let result: Vec<(String, u64)> = conn
+      .exec(query, params! { "campaign" => campaign })
+      .map_err(|_| anyhow!("Query fail"))?;
+    let mut counts = VoucherCounts::default();
+    for (state, count) in result {
+      match state.as_str() {
+        "remaining" => counts.remaining = count,
+        "redeemed" => counts.redeemed = count,
+        "expired" => counts.expired = count,
+        _ => {}
+      }
+    }
+    Ok(counts)
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_blocklist_table(&self) -> String {
+    "BLOCKLIST".to_owned()
+  }
+
+  pub fn get_blocklist_audit_table(&self) -> String {
+    "BLOCKLIST_AUDIT".to_owned()
+  }
+
+  // filters blocked inscriptions in the query itself, so that limits and
+  // cursors count only rows that are served
+  fn not_blocked(&self, column: &str) -> String {
+    format!(
+      "{column} NOT IN (SELECT value FROM {} WHERE kind = '{}')",
+      self.get_blocklist_table(),
+      BlocklistKind::Inscription.as_str()
+    )
+  }
+
+  /// Adds or removes a blocklist entry and records the request in the audit
+  /// table under `admin_label`. Returns whether the blocklist changed.
+  pub fn set_blocked(
+    &self,
+    kind: BlocklistKind,
+    value: &str,
+    blocked: bool,
+    admin_label: &str,
+  ) -> Result<bool> {
+    let value = kind.normalize(value)?;
+    let (action, query) = if blocked {
+      (
+        "add",
+        format!(
+          "INSERT IGNORE INTO {} (kind, value) VALUES (:kind, :value)",
+          self.get_blocklist_table()
+        ),
+      )
+    } else {
+      (
+        "remove",
+        format!(
+          "DELETE FROM {} WHERE kind = :kind AND value = :value",
+          self.get_blocklist_table()
+        ),
+      )
+    };
+
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    conn
+      .exec_drop(
+        query,
+        params! {
+          "kind" => kind.as_str(),
+          "value" => &value,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    let changed = conn.affected_rows() > 0;
+    conn
+      .exec_drop(
+        format!(
+          "INSERT INTO {} (action, kind, value, changed, admin_label)
+           VALUES (:action, :kind, :value, :changed, :admin_label)",
+          self.get_blocklist_audit_table()
+        ),
+        params! {
+          "action" => action,
+          "kind" => kind.as_str(),
+          "value" => &value,
+          "changed" => changed,
+          "admin_label" => admin_label,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))?;
+
+    Ok(changed)
+  }
+
+  pub fn get_blocked_inscriptions(&self) -> Result<HashSet<InscriptionId>> {
+    let query = format!(
+      "SELECT value FROM {} WHERE kind = :kind",
+      self.get_blocklist_table()
+    );
+    let mut conn = self.get_conn()?;
+    let values: Vec<String> = conn
+      .exec(
+        query,
+        params! { "kind" => BlocklistKind::Inscription.as_str() },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    values.iter().map(|value| Ok(value.parse()?)).collect()
+  }
+
+  pub fn is_blocked(&self, kind: BlocklistKind, value: &str) -> Result<bool> {
+    let query = format!(
+      "SELECT COUNT(*) FROM {} WHERE kind = :kind AND value = :value",
+      self.get_blocklist_table()
+    );
+    let mut conn = self.get_conn()?;
+    let count: Option<u64> = conn
+      .exec_first(
+        query,
+        params! {
+          "kind" => kind.as_str(),
+          "value" => kind.normalize(value)?,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    Ok(count.unwrap_or(0) > 0)
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_unsafe_destination_table(&self) -> String {
+    "UNSAFE_DESTINATION".to_owned()
+  }
+
+  /// Destinations operators flagged as unsafe for inscriptions. Rows whose
+  /// pattern does not parse are skipped so one typo cannot stop transfers.
+  pub fn get_unsafe_destinations(&self) -> Result<Vec<UnsafeDestination>> {
+    let query = format!(
+      "SELECT label, pattern FROM {} ORDER BY pattern",
+      self.get_unsafe_destination_table()
+    );
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, String)> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(
+      rows
+        .into_iter()
+        .filter_map(|(label, pattern)| match pattern.parse() {
+          Ok(pattern) => Some(UnsafeDestination { label, pattern }),
+          Err(err) => {
+            log::warn!("Skip unsafe destination `{label}`: {err}");
+            None
+          }
+        })
+        .collect(),
+    )
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_content_type_block_stats_table(&self) -> String {
+    "CONTENT_TYPE_BLOCK_STATS".to_owned()
+  }
+
+  pub fn get_content_type_daily_stats_table(&self) -> String {
+    "CONTENT_TYPE_DAILY_STATS".to_owned()
+  }
+
+  /// Adds one block's new inscriptions to the daily aggregates. Blocks are
+  /// recorded by height first, so a block counted twice is only added once.
+  pub fn insert_content_type_stats(
+    &self,
+    height: u64,
+    date: NaiveDate,
+    counts: &BTreeMap<String, ContentTypeCount>,
+  ) -> Result {
+    if counts.is_empty() {
+      return Ok(());
+    }
+
+    let block_query = format!(
+      "INSERT IGNORE INTO {} (height, content_type, date, count, total_bytes)
+       VALUES (:height, :content_type, :date, :count, :total_bytes)",
+      self.get_content_type_block_stats_table()
+    );
+    let daily_query = format!(
+      "INSERT INTO {} (date, content_type, count, total_bytes)
+       VALUES (:date, :content_type, :count, :total_bytes)
+       ON DUPLICATE KEY UPDATE count = count + :count, total_bytes = total_bytes + :total_bytes",
+      self.get_content_type_daily_stats_table()
+    );
+    let date = date.format("%Y-%m-%d").to_string();
+
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for (content_type, count) in counts {
+      conn
+        .exec_drop(
+          &block_query,
+          params! {
+            "height" => height,
+            "content_type" => content_type,
+            "date" => &date,
+            "count" => count.count,
+            "total_bytes" => count.total_bytes,
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+      if conn.affected_rows() == 0 {
+        continue;
+      }
+      conn
+        .exec_drop(
+          &daily_query,
+          params! {
+            "date" => &date,
+            "content_type" => content_type,
+            "count" => count.count,
+            "total_bytes" => count.total_bytes,
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  /// Subtracts blocks above `height` from the daily aggregates.
+  pub fn rollback_content_type_stats(&self, height: u64) -> Result {
+    let block = self.get_content_type_block_stats_table();
+    let daily = self.get_content_type_daily_stats_table();
+
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    conn
+      .exec_drop(
+        format!(
+          "UPDATE {daily} d JOIN (
+             SELECT date, content_type, SUM(count) AS count, SUM(total_bytes) AS total_bytes
+             FROM {block} WHERE height > :height GROUP BY date, content_type
+           ) b ON d.date = b.date AND d.content_type = b.content_type
+           SET d.count = d.count - b.count, d.total_bytes = d.total_bytes - b.total_bytes"
+        ),
+        params! { "height" => height },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    conn
+      .exec_drop(
+        format!("DELETE FROM {block} WHERE height > :height"),
+        params! { "height" => height },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    conn
+      .query_drop(format!("DELETE FROM {daily} WHERE count = 0"))
+      .map_err(|_| anyhow!("Execute transaction fail"))?;
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  /// Daily aggregates between `from` and `to` inclusive, oldest first.
+  pub fn get_content_type_stats(
+    &self,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+  ) -> Result<Vec<ContentTypeStat>> {
+    let query = format!(
+      "SELECT DATE_FORMAT(date, '%Y-%m-%d'), content_type, count, total_bytes FROM {}
+       WHERE date >= :from AND date <= :to
+       ORDER BY date, content_type",
+      self.get_content_type_daily_stats_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, String, u64, u64)> = conn
+      .exec(
+        query,
+        params! {
+          "from" => from.map_or_else(|| "1000-01-01".into(), |date| date.to_string()),
+          "to" => to.map_or_else(|| "9999-12-31".into(), |date| date.to_string()),
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|(date, content_type, count, total_bytes)| ContentTypeStat {
+          date,
+          content_type,
+          count,
+          total_bytes,
+        })
+        .collect(),
+    )
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_inscription_checksum_table(&self) -> String {
+    "INSCRIPTION_CHECKSUM".to_owned()
+  }
+
+  /// Records a block's new inscriptions, rolling the checksum on from the
+  /// block before. `total` is the number of inscriptions indexed after it.
+  pub fn insert_inscription_checksum(
+    &self,
+    height: u64,
+    block_hash: BlockHash,
+    date: NaiveDate,
+    inscription_ids: &[InscriptionId],
+    total: u64,
+  ) -> Result {
+    let table = self.get_inscription_checksum_table();
+
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    let previous: Option<String> = tx
+      .exec_first(
+        format!("SELECT checksum FROM {table} WHERE height = :height"),
+        params! { "height" => height.checked_sub(1) },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    let previous = previous
+      .map(|checksum| sha256::Hash::from_str(&checksum))
+      .transpose()?;
+
+    let checksum = InscriptionChecksum::new(previous, height, inscription_ids);
+    tx.exec_drop(
+      format!(
+        "REPLACE INTO {table} (height, block_hash, date, inscriptions, total, block_checksum, checksum)
+         VALUES (:height, :block_hash, :date, :inscriptions, :total, :block_checksum, :checksum)"
+      ),
+      params! {
+        "height" => height,
+        "block_hash" => block_hash.to_string(),
+        "date" => date.format("%Y-%m-%d").to_string(),
+        "inscriptions" => inscription_ids.len(),
+        "total" => total,
+        "block_checksum" => checksum.block.to_string(),
+        "checksum" => checksum.rolling.to_string(),
+      },
+    )
+    .map_err(|_| anyhow!("Execute transaction fail"))?;
+    tx.commit().map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  /// Drops checksums of blocks above `height`.
+  pub fn rollback_inscription_checksum(&self, height: u64) -> Result {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "DELETE FROM {} WHERE height > :height",
+          self.get_inscription_checksum_table()
+        ),
+        params! { "height" => height },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  /// Stats at `height`, or at the latest checksummed block, with the
+  /// inscriptions per day over the trailing week up to it. `None` before
+  /// any block is checksummed.
+  pub fn get_index_stats(&self, height: Option<u64>) -> Result<Option<IndexStats>> {
+    let table = self.get_inscription_checksum_table();
+
+    let mut conn = self.get_conn()?;
+    let row: Option<(u64, String, String, u64, String)> = conn
+      .exec_first(
+        format!(
+          "SELECT height, block_hash, DATE_FORMAT(date, '%Y-%m-%d'), total, checksum FROM {table}
+           WHERE height <= :height ORDER BY height DESC LIMIT 1"
+        ),
+        params! { "height" => height.unwrap_or(u64::MAX) },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    let Some((height, block_hash, date, total, checksum)) = row else {
+      return Ok(None);
+    };
+
+    let checksum_from: Option<u64> = conn
+      .query_first(format!("SELECT MIN(height) FROM {table}"))
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    let daily: Vec<(String, u64)> = conn
+      .exec(
+        format!(
+          "SELECT DATE_FORMAT(date, '%Y-%m-%d'), CAST(SUM(inscriptions) AS UNSIGNED) FROM {table}
+           WHERE date > DATE_SUB(:date, INTERVAL :days DAY) AND height <= :height
+           GROUP BY date ORDER BY date"
+        ),
+        params! {
+          "date" => &date,
+          "days" => IndexStats::DAYS,
+          "height" => height,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(Some(IndexStats {
+      inscriptions: total,
+      latest_number: total.checked_sub(1),
+      height,
+      block_hash,
+      checksum,
+      checksum_from: checksum_from.unwrap_or(height),
+      daily: daily
+        .into_iter()
+        .map(|(date, inscriptions)| DailyInscriptionCount { date, inscriptions })
+        .collect(),
+    }))
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_block_stats_table(&self) -> String {
+    "BLOCK_STATS".to_owned()
+  }
+
+  /// Records a block's timings, replacing any from before a reorg.
+  pub fn insert_block_stats(&self, timings: &BlockTimings) -> Result {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "REPLACE INTO {} (height, fetch_us, inputs_us, parse_us, redb_us, mysql_us, commit_us, total_us)
+           VALUES (:height, :fetch, :inputs, :parse, :redb, :mysql, :commit, :total)",
+          self.get_block_stats_table()
+        ),
+        params! {
+          "height" => timings.height,
+          "fetch" => timings.fetch,
+          "inputs" => timings.inputs,
+          "parse" => timings.parse,
+          "redb" => timings.redb,
+          "mysql" => timings.mysql,
+          "commit" => timings.commit,
+          "total" => timings.total,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  /// Deletes timings recorded more than `retain_days` ago, returning how
+  /// many were deleted.
+  pub fn prune_block_stats(&self, retain_days: u64) -> Result<u64> {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "DELETE FROM {} WHERE recorded_at < NOW() - INTERVAL :days DAY",
+          self.get_block_stats_table()
+        ),
+        params! { "days" => retain_days },
+      )
+      .map_err(|_| anyhow!("Execute fail"))?;
+    Ok(conn.affected_rows())
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_backfill_job_table(&self) -> String {
+    "BACKFILL_JOB".to_owned()
+  }
+
+  /// Queues a backfill of `address` from `from_height` and watches it so
+  /// sync keeps it current afterwards. An unfinished job for the same
+  /// address is returned instead of queueing another.
+  pub fn enqueue_backfill_job(&self, address: &str, from_height: u64) -> Result<u64> {
+    let table = self.get_backfill_job_table();
+
+    self.watch_address(address)?;
+
+    let mut conn = self.get_conn()?;
+    let mut tx = conn
+      .start_transaction(TxOpts::default())
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    let existing: Option<u64> = tx
+      .exec_first(
+        format!(
+          "SELECT id FROM {table} WHERE address = :address AND state IN ('pending', 'running')
+           ORDER BY id LIMIT 1 FOR UPDATE"
+        ),
+        params! { "address" => address },
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+    let id = match existing {
+      Some(id) => id,
+      None => {
+        tx.exec_drop(
+          format!(
+            "INSERT INTO {table} (address, from_height, next_height)
+             VALUES (:address, :from_height, :from_height)"
+          ),
+          params! {
+            "address" => address,
+            "from_height" => from_height,
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+        tx.last_insert_id()
+          .ok_or_else(|| anyhow!("Execute transaction fail"))?
+      }
+    };
+    tx.commit()
+      .map_err(|_| anyhow!("Commit transaction fail"))?;
+    Ok(id)
+  }
+
+  /// Jobs, newest first, optionally only those in `state`.
+  pub fn get_backfill_jobs(&self, state: Option<BackfillJobState>) -> Result<Vec<BackfillJob>> {
+    self.query_backfill_jobs(
+      match state {
+        Some(_) => "state = :state ORDER BY id DESC",
+        None => "TRUE ORDER BY id DESC",
+      },
+      state,
+    )
+  }
+
+  /// Pending and running jobs, oldest first.
+  pub fn get_unfinished_backfill_jobs(&self) -> Result<Vec<BackfillJob>> {
+    self.query_backfill_jobs("state IN ('pending', 'running') ORDER BY id", None)
+  }
+
+  fn query_backfill_jobs(
+    &self,
+    condition: &str,
+    state: Option<BackfillJobState>,
+  ) -> Result<Vec<BackfillJob>> {
+    let query = format!(
+      "SELECT id, address, from_height, next_height, state, found, error FROM {} WHERE {condition}",
+      self.get_backfill_job_table()
+    );
+    let params = match state {
+      Some(state) => params! { "state" => state.as_str() },
+      None => mysql::Params::Empty,
+    };
+
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(u64, String, u64, u64, String, u64, Option<String>)> = conn
+      .exec(query, params)
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(
+          |(id, address, from_height, next_height, state, found, error)| BackfillJob {
+            id,
+            address,
+            from_height,
+            next_height,
+            state,
+            found,
+            error,
+          },
+        )
+        .collect(),
+    )
+  }
+
+  /// Records that blocks below `next_height` are scanned and `found` more
+  /// inscriptions upserted, marking the job done once `done`.
+  pub fn advance_backfill_job(&self, id: u64, next_height: u64, found: u64, done: bool) -> Result {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "UPDATE {} SET next_height = :next_height, found = found + :found, state = :state
+           WHERE id = :id",
+          self.get_backfill_job_table()
+        ),
+        params! {
+          "id" => id,
+          "next_height" => next_height,
+          "found" => found,
+          "state" => if done {
+            BackfillJobState::Done
+          } else {
+            BackfillJobState::Running
+          }
+          .as_str(),
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn fail_backfill_job(&self, id: u64, error: &str) -> Result {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "UPDATE {} SET state = :state, error = :error WHERE id = :id",
+          self.get_backfill_job_table()
+        ),
+        params! {
+          "id" => id,
+          "state" => BackfillJobState::Failed.as_str(),
+          "error" => error,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_mint_quota_table(&self) -> String {
+    "MINT_QUOTA".to_owned()
+  }
+
+  pub fn get_mint_quota_override_table(&self) -> String {
+    "MINT_QUOTA_OVERRIDE".to_owned()
+  }
+
+  pub fn get_mint_quota_exempt_table(&self) -> String {
+    "MINT_QUOTA_EXEMPT".to_owned()
+  }
+
+  /// Whether `source` is exempt, and its daily limit, its override if it
+  /// has one and `default_limit` otherwise.
+  fn get_mint_quota_limit(
+    &self,
+    conn: &mut PooledConn,
+    source: &str,
+    default_limit: Option<u64>,
+  ) -> Result<(bool, Option<u64>)> {
+    let (exempt, daily_limit): (bool, Option<u64>) = conn
+      .exec_first(
+        format!(
+          "SELECT EXISTS (SELECT 1 FROM {} WHERE source = :source),
+             (SELECT daily_limit FROM {} WHERE source = :source)",
+          self.get_mint_quota_exempt_table(),
+          self.get_mint_quota_override_table()
+        ),
+        params! { "source" => source },
+      )
+      .map_err(|_| anyhow!("Query fail"))?
+      .unwrap_or_default();
+    if exempt {
+      return Ok((true, None));
+    }
+    Ok((false, daily_limit.or(default_limit)))
+  }
+
+  fn get_mint_quota_used(
+    &self,
+    conn: &mut PooledConn,
+    source: &str,
+    day: NaiveDate,
+  ) -> Result<u64> {
+    Ok(
+      conn
+        .exec_first(
+          format!(
+            "SELECT used FROM {} WHERE source = :source AND day = :day",
+            self.get_mint_quota_table()
+          ),
+          params! {
+            "source" => source,
+            "day" => day.to_string(),
+          },
+        )
+        .map_err(|_| anyhow!("Query fail"))?
+        .unwrap_or_default(),
+    )
+  }
+
+  /// Counts `count` inscriptions against the quota of `source` for the day
+  /// of `now`. The check and the increment are a single upsert, so
+  /// concurrent requests can never take the counter past the limit. Returns
+  /// `None` when the source has no limit.
+  pub fn reserve_mint_quota(
+    &self,
+    source: &str,
+    count: u64,
+    default_limit: Option<u64>,
+    now: DateTime<Utc>,
+  ) -> Result<Option<MintQuotaReservation>> {
+    let mut conn = self.get_conn()?;
+    let (_, Some(limit)) = self.get_mint_quota_limit(&mut conn, source, default_limit)? else {
+      return Ok(None);
+    };
+
+    let day = MintQuota::day(now);
+    if count <= limit {
+      conn
+        .exec_drop(
+          format!(
+            "INSERT INTO {} (source, day, used) VALUES (:source, :day, :count)
+             ON DUPLICATE KEY UPDATE used = IF(used + :count <= :limit, used + :count, used)",
+            self.get_mint_quota_table()
+          ),
+          params! {
+            "source" => source,
+            "day" => day.to_string(),
+            "count" => count,
+            "limit" => limit,
+          },
+        )
+        .map_err(|_| anyhow!("Execute fail"))?;
+      if conn.affected_rows() > 0 {
+        return Ok(Some(MintQuotaReservation {
+          source: source.to_owned(),
+          day,
+          count,
+        }));
+      }
+    }
+
+    Err(
+      BuildError::QuotaExceeded {
+        source: source.to_owned(),
+        limit,
+        used: self.get_mint_quota_used(&mut conn, source, day)?,
+        requested: count,
+        reset_at: MintQuota::reset_at(day),
+      }
+      .into(),
+    )
+  }
+
+  /// Gives back a reservation whose build failed.
+  pub fn release_mint_quota(&self, reservation: &MintQuotaReservation) -> Result {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "UPDATE {} SET used = used - LEAST(used, :count) WHERE source = :source AND day = :day",
+          self.get_mint_quota_table()
+        ),
+        params! {
+          "source" => &reservation.source,
+          "day" => reservation.day.to_string(),
+          "count" => reservation.count,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn get_mint_quota(
+    &self,
+    source: &str,
+    day: NaiveDate,
+    default_limit: Option<u64>,
+  ) -> Result<MintQuota> {
+    let mut conn = self.get_conn()?;
+    let (exempt, limit) = self.get_mint_quota_limit(&mut conn, source, default_limit)?;
+    Ok(MintQuota {
+      source: source.to_owned(),
+      day,
+      used: self.get_mint_quota_used(&mut conn, source, day)?,
+      limit,
+      exempt,
+      reset_at: MintQuota::reset_at(day),
+    })
+  }
+
+  /// Clears the counter of `source` for `day`. Returns whether there was
+  /// one.
+  pub fn reset_mint_quota(&self, source: &str, day: NaiveDate) -> Result<bool> {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(
+        format!(
+          "DELETE FROM {} WHERE source = :source AND day = :day",
+          self.get_mint_quota_table()
+        ),
+        params! {
+          "source" => source,
+          "day" => day.to_string(),
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))?;
+    Ok(conn.affected_rows() > 0)
+  }
+
+  /// Sets the daily limit of `source`, or removes its override so the
+  /// default applies again.
+  pub fn set_mint_quota_override(&self, source: &str, daily_limit: Option<u64>) -> Result {
+    let mut conn = self.get_conn()?;
+    match daily_limit {
+      Some(daily_limit) => conn.exec_drop(
+        format!(
+          "INSERT INTO {} (source, daily_limit) VALUES (:source, :daily_limit)
+           ON DUPLICATE KEY UPDATE daily_limit = :daily_limit",
+          self.get_mint_quota_override_table()
+        ),
+        params! {
+          "source" => source,
+          "daily_limit" => daily_limit,
+        },
+      ),
+      None => conn.exec_drop(
+        format!(
+          "DELETE FROM {} WHERE source = :source",
+          self.get_mint_quota_override_table()
+        ),
+        params! { "source" => source },
+      ),
+    }
+    .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  pub fn set_mint_quota_exempt(&self, source: &str, exempt: bool) -> Result {
+    let query = if exempt {
+      format!(
+        "INSERT IGNORE INTO {} (source) VALUES (:source)",
+        self.get_mint_quota_exempt_table()
+      )
+    } else {
+      format!(
+        "DELETE FROM {} WHERE source = :source",
+        self.get_mint_quota_exempt_table()
+      )
+    };
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "source" => source })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_broadcast_journal_table(&self) -> String {
+    "BROADCAST_JOURNAL".to_owned()
+  }
+
+  pub fn insert_broadcast_journal(
+    &self,
+    method: &str,
+    purpose: Option<&str>,
+    fee_rate: f64,
+    txids: &[Txid],
+  ) -> Result {
+    if txids.is_empty() {
+      return Ok(());
+    };
+
+    let query = format!(
+      "INSERT IGNORE INTO {} (txid, method, fee_rate, purpose)
+       VALUES (:txid, :method, :fee_rate, :purpose)",
+      self.get_broadcast_journal_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for txid in txids {
+      conn
+        .exec_drop(
+          query.clone(),
+          params! {
+            "txid" => format!("{}", txid),
+            "method" => method,
+            "fee_rate" => fee_rate,
+            "purpose" => purpose,
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  /// Records the tip journaled transactions were first broadcast at, those
+  /// that were never built by the service are ignored.
+  pub fn mark_broadcast(&self, txids: &[Txid], height: u64) -> Result {
+    let query = format!(
+      "UPDATE {} SET broadcast_height = :height
+       WHERE txid = :txid AND broadcast_height IS NULL",
+      self.get_broadcast_journal_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for txid in txids {
+      conn
+        .exec_drop(
+          query.clone(),
+          params! {
+            "txid" => format!("{}", txid),
+            "height" => height,
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  pub fn get_pending_broadcast_txids(&self) -> Result<HashSet<Txid>> {
+    let query = format!(
+      "SELECT txid FROM {} WHERE broadcast_height IS NOT NULL AND confirmed_height IS NULL",
+      self.get_broadcast_journal_table()
+    );
+    let mut conn = self.get_conn()?;
+    let result: Vec<String> = conn.query(query).map_err(|_| anyhow!("Query fail"))?;
+    let mut txids = HashSet::new();
+    for txid in result {
+      txids.insert(Txid::from_str(&txid)?);
+    }
+    Ok(txids)
+  }
+
+  pub fn confirm_broadcasts(&self, txids: &[Txid], height: u64) -> Result {
+    if txids.is_empty() {
+      return Ok(());
+    };
+
+    let query = format!(
+      "UPDATE {} SET confirmed_height = :height
+       WHERE txid = :txid AND confirmed_height IS NULL",
+      self.get_broadcast_journal_table()
+    );
+
+    let mut conn = self.get_conn()?;
+    conn
+      .query_drop("START TRANSACTION")
+      .map_err(|_| anyhow!("Create transaction fail"))?;
+    for txid in txids {
+      conn
+        .exec_drop(
+          query.clone(),
+          params! {
+            "txid" => format!("{}", txid),
+            "height" => height,
+          },
+        )
+        .map_err(|_| anyhow!("Execute transaction fail"))?;
+    }
+    conn
+      .query_drop("COMMIT")
+      .map_err(|_| anyhow!("Commit transaction fail"))
+  }
+
+  pub fn rollback_broadcast_confirmations(&self, height: u64) -> Result {
+    let query = format!(
+      "UPDATE {} SET confirmed_height = NULL WHERE confirmed_height > :height",
+      self.get_broadcast_journal_table()
+    );
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_drop(query, params! { "height" => height })
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+
+  /// Confirmation stats for transactions broadcast at or after `from`.
+  pub fn get_confirmation_stats(
+    &self,
+    from: u64,
+    tip: u64,
+    purpose: &PurposeFilter,
+  ) -> Result<Vec<ConfirmationStat>> {
+    let query = format!(
+      "SELECT method, fee_rate, broadcast_height, confirmed_height FROM {}
+       WHERE broadcast_height >= :from AND {}",
+      self.get_broadcast_journal_table(),
+      purpose.condition()
+    );
+
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, f64, u64, Option<u64>)> = conn
+      .exec(
+        query,
+        purpose.params(vec![("from".to_owned(), mysql::Value::from(from))]),
+      )
+      .map_err(|_| anyhow!("Query fail"))?;
+
+    Ok(ConfirmationStat::aggregate(
+      rows
+        .into_iter()
+        .map(
+          |(method, fee_rate, broadcast_height, confirmed_height)| JournalEntry {
+            method,
+            fee_rate,
+            broadcast_height,
+            confirmed_height,
+          },
+        )
+        .collect(),
+      tip,
+    ))
+  }
+}
+
+/// The audit sequence number a request was recorded under, and whether it
+/// was a replay of a request already recorded with the same idempotency key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Audited {
+  New(u64),
+  Replay(u64),
+}
+
+impl MysqlDatabase {
+  pub fn get_build_audit_table(&self) -> String {
+    "BUILD_AUDIT".to_owned()
+  }
+
+  /// Audits a build, or returns the sequence number of the earlier build
+  /// with the same idempotency key without writing a row.
+  pub fn insert_build_audit(
+    &self,
+    endpoint: &str,
+    api_version: u32,
+    source: &str,
+    purpose: Option<&str>,
+    is_unsafe: bool,
+    reveal_weight: u64,
+    idempotency_key: Option<&str>,
+  ) -> Result<Audited> {
+    let query = format!(
+      "INSERT INTO {} (endpoint, api_version, source, purpose, is_unsafe, reveal_weight)
+       VALUES (:endpoint, :api_version, :source, :purpose, :is_unsafe, :reveal_weight)",
+      self.get_build_audit_table()
+    );
+    self.audit(&self.get_build_audit_table(), idempotency_key, |tx| {
+      tx.exec_drop(
+        query,
+        params! {
+          "endpoint" => endpoint,
+          "api_version" => api_version,
+          "source" => source,
+          "purpose" => purpose,
+          "is_unsafe" => is_unsafe,
+          "reveal_weight" => reveal_weight,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))
+    })
+  }
+
+  pub fn get_build_request_counts(
+    &self,
+    purpose: &PurposeFilter,
+  ) -> Result<Vec<BuildRequestCount>> {
+    let query = format!(
+      "SELECT endpoint, purpose, COUNT(*) FROM {} WHERE {}
+       GROUP BY endpoint, purpose ORDER BY endpoint, purpose",
+      self.get_build_audit_table(),
+      purpose.condition()
+    );
+    let mut conn = self.get_conn()?;
+    let rows: Vec<(String, Option<String>, u64)> = conn
+      .exec(query, purpose.params(Vec::new()))
+      .map_err(|_| anyhow!("Query fail"))?;
+    Ok(
+      rows
+        .into_iter()
+        .map(|(endpoint, purpose, requests)| BuildRequestCount {
+          endpoint,
+          purpose,
+          requests,
+        })
+        .collect(),
+    )
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_manifest_audit_table(&self) -> String {
+    "MANIFEST_AUDIT".to_owned()
+  }
+
+  /// Archives a signed build manifest as the JSON that was returned, so it
+  /// can be verified again later. A replayed request is not archived twice.
+  pub fn insert_manifest_audit(
+    &self,
+    manifest: &Manifest,
+    idempotency_key: Option<&str>,
+  ) -> Result<Audited> {
+    let query = format!(
+      "INSERT INTO {} (method, request_sha256, hmac, manifest)
+       VALUES (:method, :request_sha256, :hmac, :manifest)",
+      self.get_manifest_audit_table()
+    );
+    let manifest_json = serde_json::to_string(manifest)?;
+    self.audit(&self.get_manifest_audit_table(), idempotency_key, |tx| {
+      tx.exec_drop(
+        query,
+        params! {
+          "method" => &manifest.body.method,
+          "request_sha256" => &manifest.body.request_sha256,
+          "hmac" => &manifest.hmac,
+          "manifest" => manifest_json,
+        },
+      )
+      .map_err(|_| anyhow!("Execute transaction fail"))
+    })
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_audit_counter_table(&self) -> String {
+    "AUDIT_COUNTER".to_owned()
+  }
+
+  pub fn get_audit_sequence_table(&self) -> String {
+    "AUDIT_SEQUENCE".to_owned()
+  }
+
+  /// Writes an audit row with `insert` and numbers it, unless a row of the
+  /// same audit was already written for `idempotency_key`, in which case
+  /// its number is returned instead. Two replays racing each other both
+  /// insert, the loser fails on the unique key, rolls back and finds the
+  /// winner's number.
+  fn audit(
+    &self,
+    audit: &str,
+    idempotency_key: Option<&str>,
+    insert: impl FnOnce(&mut mysql::Transaction<'_>) -> Result,
+  ) -> Result<Audited> {
+    if let Some(key) = idempotency_key {
+      if let Some(seq) = self.get_audited_sequence(audit, key)? {
+        return Ok(Audited::Replay(seq));
+      }
+    }
+
+    let numbered = (|| {
+      let mut conn = self.get_conn()?;
+      let mut tx = conn
+        .start_transaction(TxOpts::default())
+        .map_err(|_| anyhow!("Create transaction fail"))?;
+      insert(&mut tx)?;
+      let seq = self.sequence_audit(&mut tx, audit, idempotency_key)?;
+      tx.commit()
+        .map_err(|_| anyhow!("Commit transaction fail"))?;
+      Ok(seq)
+    })();
+
+    match (numbered, idempotency_key) {
+      (Ok(seq), _) => Ok(Audited::New(seq)),
+      (Err(err), Some(key)) => self
+        .get_audited_sequence(audit, key)?
+        .map(Audited::Replay)
+        .ok_or(err),
+      (Err(err), None) => Err(err),
+    }
+  }
+
+  /// Numbers the audit row just inserted in `tx`. The number is taken in the
+  /// same transaction, so a rollback returns it and the sequence has no
+  /// gaps, unlike auto-increment ids.
+  ///
+  /// Bumping the counter locks its row until `tx` commits, which orders all
+  /// audit writes. Handing each connection a range of numbers up front would
+  /// avoid that, but a connection that dies with part of its range unused
+  /// leaves exactly the gaps the sequence exists to rule out. The lock is
+  /// instead held only for the last statements of the audit transaction,
+  /// after the build it records has finished, so builds still run
+  /// concurrently and only the few milliseconds of bookkeeping queue up.
+  fn sequence_audit(
+    &self,
+    tx: &mut impl Queryable,
+    audit: &str,
+    idempotency_key: Option<&str>,
+  ) -> Result<u64> {
+    let audit_id: u64 = tx
+      .query_first("SELECT LAST_INSERT_ID()")
+      .map_err(|_| anyhow!("Query fail"))?
+      .ok_or_else(|| anyhow!("Query fail"))?;
+    tx.query_drop(format!(
+      "INSERT INTO {} (name, value) VALUES ('audit', LAST_INSERT_ID(1))
+       ON DUPLICATE KEY UPDATE value = LAST_INSERT_ID(value + 1)",
+      self.get_audit_counter_table()
+    ))
+    .map_err(|_| anyhow!("Execute transaction fail"))?;
+    let seq: u64 = tx
+      .query_first("SELECT LAST_INSERT_ID()")
+      .map_err(|_| anyhow!("Query fail"))?
+      .ok_or_else(|| anyhow!("Query fail"))?;
+    tx.exec_drop(
+      format!(
+        "INSERT INTO {} (seq, audit, audit_id, idempotency_key)
+         VALUES (:seq, :audit, :audit_id, :idempotency_key)",
+        self.get_audit_sequence_table()
+      ),
+      params! {
+        "seq" => seq,
+        "audit" => audit,
+        "audit_id" => audit_id,
+        "idempotency_key" => idempotency_key,
+      },
+    )
+    .map_err(|_| anyhow!("Execute transaction fail"))?;
+    Ok(seq)
+  }
+
+  fn get_audited_sequence(&self, audit: &str, idempotency_key: &str) -> Result<Option<u64>> {
+    let mut conn = self.get_conn()?;
+    conn
+      .exec_first(
+        format!(
+          "SELECT seq FROM {} WHERE audit = :audit AND idempotency_key = :idempotency_key",
+          self.get_audit_sequence_table()
+        ),
+        params! {
+          "audit" => audit,
+          "idempotency_key" => idempotency_key,
+        },
+      )
+      .map_err(|_| anyhow!("Query fail"))
+  }
+
+  /// The sequence number of the build already audited for
+  /// `idempotency_key`, which survives restarts, so a replayed request is
+  /// recognised whenever it arrives.
+  pub fn get_replayed_sequence(&self, idempotency_key: &str) -> Result<Option<u64>> {
+    self.get_audited_sequence(&self.get_build_audit_table(), idempotency_key)
+  }
+
+  /// The latest audit sequence number, zero before anything was audited.
+  pub fn get_audit_sequence(&self) -> Result<u64> {
+    let query = format!(
+      "SELECT value FROM {} WHERE name = 'audit'",
+      self.get_audit_counter_table()
+    );
+    let mut conn = self.get_conn()?;
+    let seq: Option<u64> = conn.query_first(query).map_err(|_| anyhow!("Query fail"))?;
+    Ok(seq.unwrap_or(0))
+  }
+}
+
+impl MysqlDatabase {
+  pub fn get_prune_progress_table(&self) -> String {
+    "PRUNE_PROGRESS".to_owned()
+  }
+
+  // rows matching the condition are settled, or were never settled since
+  // reconciliation first saw them, and only admin listings read them
+  fn prune_targets(&self) -> Vec<(String, &'static str)> {
+    vec![(
+      self.get_reveal_expectation_table(),
+      "(state IN ('fulfilled', 'superseded', 'expired') AND height <= :cutoff)
+       OR (state IN ('built', 'pending') AND created_height <= :cutoff)",
+    )]
+  }
+
+  pub(crate) fn prune_cutoff(tip: u64, depth: u64) -> Option<u64> {
+    tip.checked_sub(depth.max(MAX_REORG_DEPTH))
+  }
+
+  /// Deletes rows settled, or left unsettled since they were created, at
+  /// least `depth` blocks below `tip`, in batches, returning the number of
+  /// rows pruned per table.
+  pub fn prune(&self, tip: u64, options: &PruneOptions) -> Result<BTreeMap<String, u64>> {
+    let mut pruned = BTreeMap::new();
+    let Some(cutoff) = Self::prune_cutoff(tip, options.depth) else {
+      return Ok(pruned);
+    };
+
+    let mut conn = self.get_conn()?;
+    for (table, condition) in self.prune_targets() {
+      // an interrupted run leaves its cutoff behind, rows below it are
+      // still matched so the next run picks up where it stopped
+      if let Some((previous, false)) = self.get_prune_progress(&mut conn, &table)? {
+        log::info!("Resume pruning {table} below height {previous}");
+      }
+
+      let query = format!(
+        "DELETE FROM {} WHERE {} LIMIT {}",
+        table, condition, options.batch_size
+      );
+      let mut total = 0;
+      loop {
+        conn
+          .exec_drop(&query, params! { "cutoff" => cutoff })
+          .map_err(|_| anyhow!("Execute fail"))?;
+        let affected = conn.affected_rows();
+        total += affected;
+
+        let done = affected < options.batch_size;
+        self.set_prune_progress(&mut conn, &table, cutoff, affected, done)?;
+        if done {
+          break;
+        }
+        thread::sleep(options.sleep);
+      }
+      pruned.insert(table, total);
+    }
+
+    Ok(pruned)
+  }
+
+  fn get_prune_progress(&self, conn: &mut PooledConn, table: &str) -> Result<Option<(u64, bool)>> {
+    let query = format!(
+      "SELECT cutoff_height, done FROM {} WHERE table_name = :table_name",
+      self.get_prune_progress_table()
+    );
+    conn
+      .exec_first(query, params! { "table_name" => table })
+      .map_err(|_| anyhow!("Query fail"))
+  }
+
+  fn set_prune_progress(
+    &self,
+    conn: &mut PooledConn,
+    table: &str,
+    cutoff: u64,
+    pruned: u64,
+    done: bool,
+  ) -> Result {
+    let query = format!(
+      "INSERT INTO {} (table_name, cutoff_height, pruned, done)
+       VALUES (:table_name, :cutoff_height, :pruned, :done)
+       ON DUPLICATE KEY UPDATE cutoff_height = :cutoff_height, pruned = pruned + :pruned, done = :done",
+      self.get_prune_progress_table()
+    );
+    conn
+      .exec_drop(
+        query,
+        params! {
+          "table_name" => table,
+          "cutoff_height" => cutoff,
+          "pruned" => pruned,
+          "done" => done,
+        },
+      )
+      .map_err(|_| anyhow!("Execute fail"))
+  }
+}
+
+pub struct Index {
+  client: Client,
+  database: Database,
+  path: PathBuf,
+  first_inscription_height: u64,
+  genesis_block_coinbase_transaction: Transaction,
+  genesis_block_coinbase_txid: Txid,
+  height_limit: Option<u64>,
+  options: Options,
+  reorged: AtomicBool,
+  mysql_database: Option<Arc<MysqlDatabase>>,
+  watched_only: Option<Vec<String>>,
+  block_stats_retention: Option<u64>,
+}
+
+/// Times `Index::read_open` opened an index file, rather than handing out a
+/// shared one.
+static READ_OPENS: AtomicU64 = AtomicU64::new(0);
+
+/// A read-only index opened once, handed out by `Index::read_open` to every
+/// caller whose options carry it.
+#[derive(Clone)]
+pub struct SharedIndex(Arc<Index>);
+
+impl SharedIndex {
+  pub fn open(options: &Options) -> Result<Self> {
+    Index::read_open(options).map(Self)
+  }
+
+  pub fn chain(&self) -> Chain {
+    self.0.options.chain()
+  }
+}
+
+impl fmt::Debug for SharedIndex {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    f.debug_tuple("SharedIndex").field(&self.0.path).finish()
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum List {
+  Spent,
+  Unspent(Vec<(u64, u64)>),
+}
+
+#[derive(Copy, Clone)]
+#[repr(u64)]
+pub(crate) enum Statistic {
+  Schema = 0,
+  Commits = 1,
+  LostSats = 2,
+  OutputsTraversed = 3,
+  SatRanges = 4,
+  UnboundInscriptions = 5,
+  Chain = 6,
+  SnapshotHeight = 7,
+  PartiallyParsedInscriptions = 8,
+}
+
+impl Statistic {
+  fn key(self) -> u64 {
+    self.into()
+  }
+}
+
+impl From<Statistic> for u64 {
+  fn from(statistic: Statistic) -> Self {
+    statistic as u64
+  }
+}
+
+fn chain_code(chain: Chain) -> u64 {
+  match chain {
+    Chain::Mainnet => 1,
+    Chain::Testnet => 2,
+    Chain::Signet => 3,
+    Chain::Regtest => 4,
+  }
+}
+
+fn code_chain(code: u64) -> Option<Chain> {
+  Chain::value_variants()
+    .iter()
+    .copied()
+    .find(|chain| chain_code(*chain) == code)
+}
+
+#[derive(Serialize)]
+pub(crate) struct Info {
+  pub(crate) blocks_indexed: u64,
+  pub(crate) branch_pages: usize,
+  pub(crate) fragmented_bytes: usize,
+  pub(crate) index_file_size: u64,
+  pub(crate) index_path: PathBuf,
+  pub(crate) leaf_pages: usize,
+  pub(crate) metadata_bytes: usize,
+  pub(crate) outputs_traversed: u64,
+  pub(crate) page_size: usize,
+  pub(crate) partially_parsed_inscriptions: u64,
+  pub(crate) sat_ranges: u64,
+  pub(crate) stored_bytes: usize,
+  pub(crate) transactions: Vec<TransactionInfo>,
+  pub(crate) tree_height: usize,
+  pub(crate) utxos_indexed: usize,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TransactionInfo {
+  pub(crate) starting_block_count: u64,
+  pub(crate) starting_timestamp: u128,
+}
+
+trait BitcoinCoreRpcResultExt<T> {
+  fn into_option(self) -> Result<Option<T>>;
+}
+
+impl<T> BitcoinCoreRpcResultExt<T> for Result<T, bitcoincore_rpc::Error> {
+  fn into_option(self) -> Result<Option<T>> {
+    match self {
+      Ok(ok) => Ok(Some(ok)),
+      Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::error::Error::Rpc(
+        bitcoincore_rpc::jsonrpc::error::RpcError { code: -8, .. },
+      ))) => Ok(None),
+      Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::error::Error::Rpc(
+        bitcoincore_rpc::jsonrpc::error::RpcError { message, .. },
+      )))
+        if message.ends_with("not found") =>
+      {
+        Ok(None)
+      }
+      Err(err) => Err(err.into()),
+    }
+  }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ListUnspentStatusEntry {
+  pub confirmed: bool,
+  pub block_height: Option<usize>,
+  pub block_hash: Option<bitcoin::BlockHash>,
+  pub block_time: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ListUnspentResultEntry {
+  pub txid: bitcoin::Txid,
+  pub vout: u32,
+  pub status: ListUnspentStatusEntry,
+  #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+  pub value: Amount,
+}
+
+impl Index {
+  pub fn open(options: &Options) -> Result<Self> {
+    let (options, client) = options.pinned_rpc_client()?;
+    let options = &options;
+
+    let data_dir = options.data_dir()?;
+
+    if let Err(err) = fs::create_dir_all(&data_dir) {
+      bail!("failed to create data dir `{}`: {err}", data_dir.display());
+    }
+
+    let path = if let Some(path) = &options.index {
+      path.clone()
+    } else {
+      data_dir.join("index.redb")
+    };
+
+    let database = match unsafe { Database::builder().open_mmapped(&path) } {
+      Ok(database) => {
+        let schema_version = database
+          .begin_read()?
+          .open_table(STATISTIC_TO_COUNT)?
+          .get(&Statistic::Schema.key())?
+          .map(|x| x.value())
+          .unwrap_or(0);
+
+        match schema_version.cmp(&SCHEMA_VERSION) {
+          cmp::Ordering::Less =>
+            bail!(
+              "index at `{}` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+              path.display()
+            ),
+          cmp::Ordering::Greater =>
+            bail!(
+              "index at `{}` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+              path.display()
+            ),
+          cmp::Ordering::Equal => {}
+        }
+
+        Self::check_chain(&database, &path, options)?;
+
+        database
+      }
+      Err(redb::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+        let database = unsafe {
+          Database::builder()
+            .set_write_strategy(if cfg!(test) {
+              WriteStrategy::Checksum
+            } else {
+              WriteStrategy::TwoPhase
+            })
+            .create_mmapped(&path)?
+        };
+        let tx = database.begin_write()?;
+
+        #[cfg(test)]
+        let tx = {
+          let mut tx = tx;
+          tx.set_durability(redb::Durability::None);
+          tx
+        };
+
+        tx.open_table(HEIGHT_TO_BLOCK_HASH)?;
+        tx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
+        tx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
+        tx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
+        tx.open_table(OUTPOINT_TO_VALUE)?;
+        tx.open_table(SATPOINT_TO_INSCRIPTION_ID)?;
+        tx.open_table(SAT_TO_INSCRIPTION_ID)?;
+        tx.open_table(SAT_TO_SATPOINT)?;
+        tx.open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?;
+
+        let mut statistics = tx.open_table(STATISTIC_TO_COUNT)?;
+        statistics.insert(&Statistic::Schema.key(), &SCHEMA_VERSION)?;
+        statistics.insert(&Statistic::Chain.key(), &chain_code(options.chain()))?;
+        drop(statistics);
+
+        if options.index_sats {
+          tx.open_table(OUTPOINT_TO_SAT_RANGES)?
+            .insert(&OutPoint::null().store(), [].as_slice())?;
+        }
+
+        tx.commit()?;
+
+        database
+      }
+      Err(error) => return Err(error.into()),
+    };
+
+    let genesis_block_coinbase_transaction =
+      options.chain().genesis_block().coinbase().unwrap().clone();
+
+    Ok(Self {
+      genesis_block_coinbase_txid: genesis_block_coinbase_transaction.txid(),
+      client,
+      database,
+      path,
+      first_inscription_height: options.first_inscription_height(),
+      genesis_block_coinbase_transaction,
+      height_limit: options.height_limit,
+      reorged: AtomicBool::new(false),
+      options: options.clone(),
+      mysql_database: None,
+      watched_only: None,
+      block_stats_retention: None,
+    })
+  }
+
+  pub fn read_open(options: &Options) -> Result<Arc<Self>> {
+    if let Some(SharedIndex(index)) = &options.shared_index {
+      return Ok(index.clone());
+    }
+
+    let (options, client) = options.pinned_rpc_client()?;
+    let options = &options;
+
+    let data_dir = options.data_dir()?;
+
+    if let Err(err) = fs::create_dir_all(&data_dir) {
+      bail!("failed to create data dir `{}`: {err}", data_dir.display());
+    }
+
+    let path = if let Some(path) = &options.index {
+      path.clone()
+    } else {
+      data_dir.join("index.redb")
+    };
+
+    READ_OPENS.fetch_add(1, atomic::Ordering::Relaxed);
+    let database = match unsafe { Database::builder().open_mmapped(&path) } {
+      Ok(database) => {
+        let schema_version = database
+          .begin_read()?
+          .open_table(STATISTIC_TO_COUNT)?
+          .get(&Statistic::Schema.key())?
+          .map(|x| x.value())
+          .unwrap_or(0);
+
+        match schema_version.cmp(&SCHEMA_VERSION) {
+          cmp::Ordering::Less =>
+            bail!(
+              "index at `{}` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+              path.display()
+            ),
+          cmp::Ordering::Greater =>
+            bail!(
+              "index at `{}` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+              path.display()
+            ),
+          cmp::Ordering::Equal => {}
+        }
+
+        Self::check_chain(&database, &path, options)?;
+        database
+      }
+      Err(redb::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+        let database = unsafe {
+          Database::builder()
+            .set_write_strategy(if cfg!(test) {
+              WriteStrategy::Checksum
+            } else {
+              WriteStrategy::TwoPhase
+            })
+            .create_mmapped(&path)?
+        };
+        let tx = database.begin_write()?;
+
+        #[cfg(test)]
+        let tx = {
+          let mut tx = tx;
+          tx.set_durability(redb::Durability::None);
+          tx
+        };
+
+        tx.open_table(HEIGHT_TO_BLOCK_HASH)?;
+        tx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
+        tx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
+        tx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
+        tx.open_table(OUTPOINT_TO_VALUE)?;
+        tx.open_table(SATPOINT_TO_INSCRIPTION_ID)?;
+        tx.open_table(SAT_TO_INSCRIPTION_ID)?;
+        tx.open_table(SAT_TO_SATPOINT)?;
+        tx.open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?;
+
+        let mut statistics = tx.open_table(STATISTIC_TO_COUNT)?;
+        statistics.insert(&Statistic::Schema.key(), &SCHEMA_VERSION)?;
+        statistics.insert(&Statistic::Chain.key(), &chain_code(options.chain()))?;
+        drop(statistics);
+
+        if options.index_sats {
+          tx.open_table(OUTPOINT_TO_SAT_RANGES)?
+            .insert(&OutPoint::null().store(), [].as_slice())?;
+        }
+
+        tx.commit()?;
+
+        database
+      }
+      Err(error) => return Err(error.into()),
+    };
+
+    let genesis_block_coinbase_transaction =
+      options.chain().genesis_block().coinbase().unwrap().clone();
+
+    Ok(Arc::new(Self {
+      genesis_block_coinbase_txid: genesis_block_coinbase_transaction.txid(),
+      client,
+      database,
+      path,
+      first_inscription_height: options.first_inscription_height(),
+      genesis_block_coinbase_transaction,
+      height_limit: options.height_limit,
+      reorged: AtomicBool::new(false),
+      options: options.clone(),
+      mysql_database: None,
+      watched_only: None,
+      block_stats_retention: None,
+    }))
+  }
+
+  /// Times an index file was read-opened since startup, shared indexes
+  /// handed out again not counted.
+  pub fn read_opens() -> u64 {
+    READ_OPENS.load(atomic::Ordering::Relaxed)
+  }
+
+  // indexes created before the chain was recorded are only stamped once the
+  // operator confirms with --adopt-data-dir
+  fn check_chain(database: &Database, path: &Path, options: &Options) -> Result {
+    let chain = options.chain();
+    let recorded = database
+      .begin_read()?
+      .open_table(STATISTIC_TO_COUNT)?
+      .get(&Statistic::Chain.key())?
+      .map(|x| x.value());
+
+    match recorded {
+      Some(code) if code == chain_code(chain) => Ok(()),
+      Some(code) => bail!(
+        "index at `{}` was built for {}, refusing to open it for {chain}",
+        path.display(),
+        code_chain(code).map_or_else(|| format!("unknown chain {code}"), |chain| chain.to_string()),
+      ),
+      None if options.adopt_data_dir => {
+        let tx = database.begin_write()?;
+        tx.open_table(STATISTIC_TO_COUNT)?
+          .insert(&Statistic::Chain.key(), &chain_code(chain))?;
+        tx.commit()?;
+        log::info!("Marked index at `{}` as {chain}", path.display());
+        Ok(())
+      }
+      None => bail!(
+        "index at `{}` does not record which chain it was built for, rerun with --adopt-data-dir to mark it as {chain}",
+        path.display()
+      ),
+    }
+  }
+
+  pub fn open_with_mysql(options: &Options, mysql_database: Arc<MysqlDatabase>) -> Result<Self> {
+    let mut index = Self::open(options)?;
+    index.mysql_database = Some(mysql_database);
+    Ok(index)
+  }
+
+  /// Record each block's timings in mysql, keeping `retain_days` of them.
+  pub fn with_block_stats(mut self, retain_days: u64) -> Self {
+    self.block_stats_retention = Some(retain_days);
+    self
+  }
+
+  /// Only keep mysql rows for registered watched addresses plus `extra`.
+  pub fn with_watched_only(mut self, extra: Vec<String>) -> Self {
+    self.watched_only = Some(extra);
+    self
+  }
+
+  pub(crate) fn watched_addresses(&self) -> Result<Option<HashSet<String>>> {
+    let Some(extra) = &self.watched_only else {
+      return Ok(None);
+    };
+    let mut addresses = extra.iter().cloned().collect::<HashSet<String>>();
+    if let Some(mysql) = &self.mysql_database {
+      addresses.extend(mysql.get_watched_addresses()?);
+    }
+    Ok(Some(addresses))
+  }
+
+  /// Settles the built and pending reveal expectations the chain has
+  /// decided, whoever broadcast them: those whose reveal is indexed are
+  /// fulfilled, those whose commit output went to another transaction are
+  /// superseded, and those whose commit is still unconfirmed `expire_after`
+  /// blocks after they were first seen expire.
+  /// The node is asked once per commit and once more per spent commit
+  /// output, `batch_size` expectations at a time.
+  pub fn reconcile_reveal_expectations(
+    &self,
+    options: &ReconcileOptions,
+  ) -> Result<RevealReconciliation> {
+    let mut reconciliation = RevealReconciliation::default();
+    let Some(mysql) = &self.mysql_database else {
+      return Ok(reconciliation);
+    };
+
+    let tip = self.block_count()?.saturating_sub(1);
+    mysql.stamp_reveal_expectations(tip)?;
+
+    let mut after = None;
+    loop {
+      let batch = mysql.get_unsettled_reveal_expectations(after, options.batch_size)?;
+      let Some(last) = batch.last() else {
+        break;
+      };
+      after = Some((last.commit.txid, last.reveal_txid));
+
+      let mut confirmed_commits = BTreeMap::new();
+      let mut settled = Vec::new();
+      for pending in &batch {
+        reconciliation.checked += 1;
+
+        let reveal_height = self
+          .get_inscription_entry(pending.reveal_txid.into())?
+          .map(|entry| entry.height);
+        let commit_output = match reveal_height {
+          Some(_) => CommitOutput::SpentByReveal,
+          None => self.commit_output(pending, &mut confirmed_commits)?,
+        };
+
+        if let Some((state, height)) = RevealExpectationState::reconcile(
+          reveal_height,
+          commit_output,
+          pending.created_height.unwrap_or(tip),
+          tip,
+          options.expire_after,
+        ) {
+          reconciliation.record(state);
+          settled.push((pending.reveal_txid, state, height));
+        }
+      }
+      mysql.settle_reveal_expectations(&settled)?;
+
+      if u64::try_from(batch.len())? < options.batch_size {
+        break;
+      }
+      thread::sleep(options.sleep);
+    }
+
+    Ok(reconciliation)
+  }
+
+  fn commit_output(
+    &self,
+    pending: &PendingReveal,
+    confirmed_commits: &mut BTreeMap<Txid, bool>,
+  ) -> Result<CommitOutput> {
+    let commit = pending.commit;
+    let confirmed = match confirmed_commits.get(&commit.txid) {
+      Some(confirmed) => *confirmed,
+      None => {
+        let confirmed = self.is_transaction_confirmed(commit.txid)?;
+        confirmed_commits.insert(commit.txid, confirmed);
+        confirmed
+      }
+    };
+    if !confirmed {
+      return Ok(CommitOutput::Unconfirmed);
+    }
+
+    if self
+      .client
+      .get_tx_out(&commit.txid, commit.vout, Some(false))?
+      .is_some()
+    {
+      return Ok(CommitOutput::Unspent);
+    }
+
+    // the output is spent in the chain, not the mempool, so by the reveal
+    // exactly when the reveal is confirmed
+    Ok(if self.is_transaction_confirmed(pending.reveal_txid)? {
+      CommitOutput::SpentByReveal
+    } else {
+      CommitOutput::SpentElsewhere
+    })
+  }
+
+  // getrawtransaction only reports in_active_chain when asked about a
+  // specific block, so a block hash is what tells confirmed transactions apart
+  fn is_transaction_confirmed(&self, txid: Txid) -> Result<bool> {
+    Ok(
+      self
+        .client
+        .get_raw_transaction_info(&txid, None)
+        .into_option()?
+        .and_then(|info| info.blockhash)
+        .is_some(),
+    )
+  }
+
+  /// Write mysql rows for inscriptions currently held by newly watched
+  /// addresses in outputs created within the last `depth` blocks.
+  pub fn backfill_watched_addresses(&self, depth: u64) -> Result<usize> {
+    let Some(mysql) = &self.mysql_database else {
+      return Ok(0);
+    };
+
+    let addresses = mysql.get_unbackfilled_addresses()?;
+    if addresses.is_empty() {
+      return Ok(0);
+    }
+
+    let mut scripts = BTreeMap::new();
+    for address in &addresses {
+      scripts.insert(Address::from_str(address)?.script_pubkey(), address.clone());
+    }
+
+    let tip = self.block_count()?.saturating_sub(1);
+    let mut data = vec![];
+    for height in tip.saturating_sub(depth)..=tip {
+      let Some(block) = self.get_block_by_height(height)? else {
+        continue;
+      };
+      data.extend(self.inscriptions_paid_to(&block, &scripts)?);
+    }
+
+    let count = data.len();
+    mysql.insert_inscriptions(data)?;
+    for address in &addresses {
+      mysql.mark_backfilled(address)?;
+    }
+    log::info!(
+      "Backfill {count} item for {} watched address",
+      addresses.len()
+    );
+    Ok(count)
+  }
+
+  // inscriptions still sitting on outputs of `block` that pay one of
+  // `scripts`, as the updater would have written them to mysql
+  pub(crate) fn inscriptions_paid_to(
+    &self,
+    block: &Block,
+    scripts: &BTreeMap<Script, String>,
+  ) -> Result<Vec<MysqlInscription>> {
+    let mut data = vec![];
+    for tx in &block.txdata {
+      let txid = tx.txid();
+      for (vout, output) in tx.output.iter().enumerate() {
+        let Some(address) = scripts.get(&output.script_pubkey) else {
+          continue;
+        };
+        let outpoint = OutPoint {
+          txid,
+          vout: u32::try_from(vout)?,
+        };
+        for inscription_id in self.get_inscriptions_on_output(outpoint)? {
+          if let Some(satpoint) = self.get_inscription_satpoint_by_id(inscription_id)? {
+            data.push(MysqlInscription {
+              inscription_id,
+              new_satpoint: satpoint,
+              new_address: address.clone(),
+              created: false,
+            });
+          }
+        }
+      }
+    }
+    Ok(data)
+  }
+
+  /// Advances queued address backfills by at most `options.blocks` blocks
+  /// in total, saving each job's progress after every block. Upserts are
+  /// idempotent, so a block scanned twice after a crash or alongside the
+  /// updater does no harm. Returns the number of blocks scanned.
+  pub fn run_backfill_jobs(&self, options: &BackfillOptions) -> Result<u64> {
+    let Some(mysql) = &self.mysql_database else {
+      return Ok(0);
+    };
+
+    let Some(tip) = self.block_count()?.checked_sub(1) else {
+      return Ok(0);
+    };
+
+    let mut scanned = 0;
+    for job in mysql.get_unfinished_backfill_jobs()? {
+      let script = match Address::from_str(&job.address) {
+        Ok(address) if address.is_valid_for_network(self.options.chain().network()) => {
+          address.script_pubkey()
+        }
+        Ok(_) => {
+          mysql.fail_backfill_job(job.id, "address is for another network")?;
+          continue;
+        }
+        Err(err) => {
+          mysql.fail_backfill_job(job.id, &err.to_string())?;
+          continue;
+        }
+      };
+      let scripts = BTreeMap::from([(script, job.address.clone())]);
+
+      let mut height = job.next_height;
+      while height <= tip {
+        if scanned == options.blocks {
+          return Ok(scanned);
+        }
+
+        let found = match self.get_block_by_height(height)? {
+          Some(block) => {
+            let data = self.inscriptions_paid_to(&block, &scripts)?;
+            let found = u64::try_from(data.len())?;
+            mysql.insert_inscriptions(data)?;
+            found
+          }
+          None => 0,
+        };
+
+        height += 1;
+        scanned += 1;
+        mysql.advance_backfill_job(job.id, height, found, height > tip)?;
+        thread::sleep(options.sleep);
+      }
+
+      if job.next_height > tip {
+        mysql.advance_backfill_job(job.id, job.next_height, 0, true)?;
+      }
+
+      log::info!(
+        "Backfilled address {} up to height {tip} for job {}",
+        job.address,
+        job.id
+      );
+    }
+
+    Ok(scanned)
+  }
+
+  /// Computes content type aggregates for inscriptions indexed before they
+  /// were tracked. Heights already recorded are left alone, so this may be
+  /// rerun or overlap with the updater.
+  pub fn backfill_content_type_stats(&self) -> Result<u64> {
+    let Some(mysql) = &self.mysql_database else {
+      bail!("content type stats require a mysql database");
+    };
+
+    let inscription_ids = self
+      .database
+      .begin_read()?
+      .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
+      .iter()?
+      .map(|(_number, id)| Entry::load(*id.value()))
+      .collect::<Vec<InscriptionId>>();
+
+    let mut total = 0;
+    let mut block = None;
+    let mut counts = BTreeMap::new();
+    for inscription_id in inscription_ids {
+      let entry = self
+        .get_inscription_entry(inscription_id)?
+        .ok_or_else(|| anyhow!("inscription {inscription_id} has no entry"))?;
+
+      if let Some((height, time)) = block {
+        if height != entry.height {
+          mysql.insert_content_type_stats(height, timestamp(time).naive_utc().date(), &counts)?;
+          counts.clear();
+        }
+      }
+      block = Some((entry.height, entry.timestamp));
+
+      if let Some(inscription) = self.get_inscription_by_id(inscription_id)? {
+        ContentTypeCount::record(&mut counts, &inscription);
+        total += 1;
+      }
+    }
+
+    if let Some((height, time)) = block {
+      mysql.insert_content_type_stats(height, timestamp(time).naive_utc().date(), &counts)?;
+    }
+
+    log::info!("Backfill content type stats for {total} inscriptions");
+    Ok(total)
+  }
+
+  pub(crate) fn get_unspent_outputs_by_commit_id(
+    &self,
+    addr: &str,
+    remain_outpoint: BTreeMap<OutPoint, bool>,
+    txid: Txid,
+  ) -> Result<(BTreeMap<OutPoint, Amount>, Transaction)> {
+    let mut utxos = self._get_unspent_outputs_by_mempool_v1(
+      self.options.chain().default_mempool_url(),
+      addr,
+      remain_outpoint,
+    )?;
+
+    let url = format!(
+      "{}tx/{}/hex",
+      self.options.chain().default_mempool_url(),
+      txid,
+    );
+
+    let rep = Vec::from_hex(&upstream::get("get_tx_hex", &url)?)?;
+    let tx: Transaction = Decodable::consensus_decode(&mut rep.as_slice()).unwrap();
+
+    for input in tx.input.clone() {
+      let txid = format!("{}", input.previous_output.txid);
+      let url = format!(
+        "{}tx/{}/hex",
+        self.options.chain().default_mempool_url(),
+        txid,
+      );
+
+      let rep = Vec::from_hex(&upstream::get("get_tx_hex", &url)?)?;
+      let tx: Transaction = Decodable::consensus_decode(&mut rep.as_slice()).unwrap();
+      utxos.insert(
+        input.previous_output,
+        Amount::from_sat(tx.output[input.previous_output.vout as usize].value),
+      );
+    }
+    Ok((utxos, tx))
+  }
+
+  pub(crate) fn get_transaction_by_mempool(&self, txid: Txid) -> Result<Transaction> {
+    let url = format!(
+      "{}tx/{}/hex",
+      self.options.chain().default_mempool_url(),
+      txid,
+    );
+
+    let rep = Vec::from_hex(&upstream::get("get_tx_hex", &url)?)?;
+    Decodable::consensus_decode(&mut rep.as_slice())
+      .map_err(|err| anyhow!("invalid transaction {txid}: {err}"))
+  }
+
+  fn get_address_utxos(&self, url: String) -> Result<String> {
+    UTXOS.get_at_tip(
+      || self.best_block_hash(),
+      url.clone(),
+      || upstream::get("get_address_utxos", &url),
+    )
+  }
+
+  pub(crate) fn get_unspent_outputs_by_outpoints(
+    &self,
+    inputs: &Vec<OutPoint>,
+  ) -> Result<BTreeMap<OutPoint, Amount>> {
+    let mut utxos = BTreeMap::new();
+    for input in inputs {
+      let txid = format!("{}", input.txid);
+      let url = format!(
+        "{}tx/{}/hex",
+        self.options.chain().default_mempool_url(),
+        txid,
+      );
+
+      let rep = Vec::from_hex(&upstream::get("get_tx_hex", &url)?)?;
+      let tx: Transaction = Decodable::consensus_decode(&mut rep.as_slice()).unwrap();
+      utxos.insert(
+        *input,
+        Amount::from_sat(tx.output[input.vout as usize].value),
+      );
+    }
+    Ok(utxos)
+  }
+
+  fn _get_unspent_outputs_by_mempool(
+    &self,
+    url: &str,
+    addr: &str,
+    remain_outpoint: BTreeMap<OutPoint, bool>,
+  ) -> Result<BTreeMap<OutPoint, Amount>> {
+    let mut utxos = BTreeMap::new();
+    let url = format!("{}address/{}/utxo", url, addr,);
+    let rep = self.get_address_utxos(url)?;
+    utxos.extend(
+      serde_json::from_str::<Vec<ListUnspentResultEntry>>(&rep)
+        .map_err(|_| anyhow!(format!("Req utxo error:{}", rep)))?
+        .into_iter()
+        .map(|utxo| {
+          let outpoint = OutPoint::new(utxo.txid, utxo.vout);
+          let amount = utxo.value;
+
+          (outpoint, amount)
+        }),
+    );
+    let rtx = self.database.begin_read()?;
+    let outpoint_to_value = rtx.open_table(OUTPOINT_TO_VALUE)?;
+    let mut filter_utxos = BTreeMap::new();
+    for (outpoint, amount) in utxos.into_iter() {
+      filter_utxos.insert(outpoint, amount);
+      // if remain_outpoint.contains_key(&outpoint)
+        // || outpoint_to_value.get(&outpoint.store())?.is_some()
+      // {
+      //   filter_utxos.insert(outpoint, amount);
+      // }
+    }
+    if filter_utxos.is_empty() {
+      Err(anyhow!("Not found utxo for addr"))
+    } else {
+      Ok(filter_utxos)
+    }
+  }
+
+  fn _get_unspent_outputs_by_mempool_v1(
+    &self,
+    url: &str,
+    addr: &str,
+    remain_outpoint: BTreeMap<OutPoint, bool>,
+  ) -> Result<BTreeMap<OutPoint, Amount>> {
+    let mut utxos = BTreeMap::new();
+    let url = format!("{}address/{}/utxo", url, addr,);
+    let rep = self.get_address_utxos(url)?;
+    utxos.extend(
+      serde_json::from_str::<Vec<ListUnspentResultEntry>>(&rep)
+        .map_err(|_| anyhow!(format!("Req utxo error:{}", rep)))?
+        .into_iter()
+        .filter(|utxo| utxo.status.confirmed)
+        .map(|utxo| {
+          let outpoint = OutPoint::new(utxo.txid, utxo.vout);
+          let amount = utxo.value;
+
+          (outpoint, amount)
+        }),
+    );
+    let rtx = self.database.begin_read()?;
+    let outpoint_to_value = rtx.open_table(OUTPOINT_TO_VALUE)?;
+    let mut filter_utxos = BTreeMap::new();
+    for (outpoint, amount) in utxos.into_iter() {
+      filter_utxos.insert(outpoint, amount);
+      // if remain_outpoint.contains_key(&outpoint)
+        // || outpoint_to_value.get(&outpoint.store())?.is_some()
+      // {
+      //   filter_utxos.insert(outpoint, amount);
+      // }
+    }
+    if filter_utxos.is_empty() {
+      Err(anyhow!("Not found utxo for addr"))
+    } else {
+      Ok(filter_utxos)
+    }
+  }
+
+  pub(crate) fn get_unspent_outputs_by_mempool(
+    &self,
+    addr: &str,
+    remain_outpoint: BTreeMap<OutPoint, bool>,
+  ) -> Result<BTreeMap<OutPoint, Amount>> {
+    self._get_unspent_outputs_by_mempool(
+      self.options.chain().default_mempool_url(),
+      addr,
+      remain_outpoint,
+    )
+  }
+
+  pub(crate) fn get_unspent_outputs_by_mempool_v1(
+    &self,
+    addr: &str,
+    remain_outpoint: BTreeMap<OutPoint, bool>,
+  ) -> Result<BTreeMap<OutPoint, Amount>> {
+    if self.options.chain() == Chain::Mainnet {
+      let mempool_url = "https://mempool.space/api/";
+      let utxos = self._get_unspent_outputs_by_mempool(mempool_url, addr, remain_outpoint.clone());
+      if let Ok(utxos) = utxos {
+        if !utxos.is_empty() {
+          return Ok(utxos);
+        }
+      }
+    }
+    self.get_unspent_outputs_by_mempool(addr, remain_outpoint)
+  }
+
+  fn _get_unspent_output_heights(
+    &self,
+    url: &str,
+    addr: &str,
+  ) -> Result<BTreeMap<OutPoint, Option<u64>>> {
+    let url = format!("{}address/{}/utxo", url, addr,);
+    let rep = self.get_address_utxos(url)?;
+    Ok(
+      serde_json::from_str::<Vec<ListUnspentResultEntry>>(&rep)
+        .map_err(|_| anyhow!(format!("Req utxo error:{}", rep)))?
+        .into_iter()
+        .map(|utxo| {
+          let height = utxo
+            .status
+            .block_height
+            .filter(|_| utxo.status.confirmed)
+            .map(|height| height as u64);
+
+          (OutPoint::new(utxo.txid, utxo.vout), height)
+        })
+        .collect(),
+    )
+  }
+
+  /// The height each utxo of `addr` confirmed at, `None` for those still in
+  /// the mempool. Read from the same listing, and cache, as
+  /// `get_unspent_outputs_by_mempool_v1`.
+  pub(crate) fn get_unspent_output_heights(
+    &self,
+    addr: &str,
+  ) -> Result<BTreeMap<OutPoint, Option<u64>>> {
+    let mut heights =
+      self._get_unspent_output_heights(self.options.chain().default_mempool_url(), addr)?;
+    if self.options.chain() == Chain::Mainnet {
+      if let Ok(mainnet) = self._get_unspent_output_heights("https://mempool.space/api/", addr) {
+        heights.extend(mainnet);
+      }
+    }
+    Ok(heights)
+  }
+
+  pub(crate) fn get_unspent_outputs(&self, _wallet: Wallet) -> Result<BTreeMap<OutPoint, Amount>> {
+    let mut utxos = BTreeMap::new();
+    utxos.extend(
+      self
+        .client
+        .list_unspent(None, None, None, None, None)?
+        .into_iter()
+        .map(|utxo| {
+          let outpoint = OutPoint::new(utxo.txid, utxo.vout);
+          let amount = utxo.amount;
+
+          (outpoint, amount)
+        }),
+    );
+
+    #[derive(Deserialize)]
+    pub(crate) struct JsonOutPoint {
+      txid: bitcoin::Txid,
+      vout: u32,
+    }
+
+    for JsonOutPoint { txid, vout } in self
+      .client
+      .call::<Vec<JsonOutPoint>>("listlockunspent", &[])?
+    {
+      utxos.insert(
+        OutPoint { txid, vout },
+        Amount::from_sat(self.client.get_raw_transaction(&txid, None)?.output[vout as usize].value),
+      );
+    }
+    let rtx = self.database.begin_read()?;
+    let outpoint_to_value = rtx.open_table(OUTPOINT_TO_VALUE)?;
+    for outpoint in utxos.keys() {
+      if outpoint_to_value.get(&outpoint.store())?.is_none() {
+        return Err(anyhow!(
+          "output in Bitcoin Core wallet but not in ord index: {outpoint}"
+        ));
+      }
+    }
+
+    Ok(utxos)
+  }
+
+  pub(crate) fn get_unspent_output_ranges(
+    &self,
+    wallet: Wallet,
+  ) -> Result<Vec<(OutPoint, Vec<(u64, u64)>)>> {
+    self
+      .get_unspent_outputs(wallet)?
+      .into_keys()
+      .map(|outpoint| match self.list(outpoint)? {
+        Some(List::Unspent(sat_ranges)) => Ok((outpoint, sat_ranges)),
+        Some(List::Spent) => bail!("output {outpoint} in wallet but is spent according to index"),
+        None => bail!("index has not seen {outpoint}"),
+      })
+      .collect()
+  }
+
+  pub(crate) fn has_sat_index(&self) -> Result<bool> {
+    match self.begin_read()?.0.open_table(OUTPOINT_TO_SAT_RANGES) {
+      Ok(_) => Ok(true),
+      Err(redb::Error::TableDoesNotExist(_)) => Ok(false),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  fn require_sat_index(&self, feature: &str) -> Result {
+    if !self.has_sat_index()? {
+      bail!("{feature} requires index created with `--index-sats` flag")
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn info(&self) -> Result<Info> {
+    let wtx = self.begin_write()?;
+
+    let stats = wtx.stats()?;
+
+    let info = {
+      let statistic_to_count = wtx.open_table(STATISTIC_TO_COUNT)?;
+      let sat_ranges = statistic_to_count
+        .get(&Statistic::SatRanges.key())?
+        .map(|x| x.value())
+        .unwrap_or(0);
+      let outputs_traversed = statistic_to_count
+        .get(&Statistic::OutputsTraversed.key())?
+        .map(|x| x.value())
+        .unwrap_or(0);
+      let partially_parsed_inscriptions = statistic_to_count
+        .get(&Statistic::PartiallyParsedInscriptions.key())?
+        .map(|x| x.value())
+        .unwrap_or(0);
+      Info {
+        index_path: self.path.clone(),
+        blocks_indexed: wtx
+          .open_table(HEIGHT_TO_BLOCK_HASH)?
+          .range(0..)?
+          .rev()
+          .next()
+          .map(|(height, _hash)| height.value() + 1)
+          .unwrap_or(0),
+        branch_pages: stats.branch_pages(),
+        fragmented_bytes: stats.fragmented_bytes(),
+        index_file_size: fs::metadata(&self.path)?.len(),
+        leaf_pages: stats.leaf_pages(),
+        metadata_bytes: stats.metadata_bytes(),
+        sat_ranges,
+        outputs_traversed,
+        page_size: stats.page_size(),
+        partially_parsed_inscriptions,
+        stored_bytes: stats.stored_bytes(),
+        transactions: wtx
+          .open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?
+          .range(0..)?
+          .map(
+            |(starting_block_count, starting_timestamp)| TransactionInfo {
+              starting_block_count: starting_block_count.value(),
+              starting_timestamp: starting_timestamp.value(),
+            },
+          )
+          .collect(),
+        tree_height: stats.tree_height(),
+        utxos_indexed: wtx.open_table(OUTPOINT_TO_SAT_RANGES)?.len()?,
+      }
+    };
+
+    Ok(info)
+  }
+
+  /// Inscriptions indexed despite envelope fields this indexer does not
+  /// know, as the total and per block for the last `blocks` blocks that had
+  /// any.
+  pub fn partially_parsed_inscriptions(&self, blocks: usize) -> Result<(u64, Vec<(u64, u64)>)> {
+    let rtx = self.database.begin_read()?;
+
+    let total = rtx
+      .open_table(STATISTIC_TO_COUNT)?
+      .get(&Statistic::PartiallyParsedInscriptions.key())?
+      .map(|x| x.value())
+      .unwrap_or(0);
+
+    let recent = match rtx.open_table(HEIGHT_TO_PARTIALLY_PARSED) {
+      Ok(table) => table
+        .range(0..)?
+        .rev()
+        .take(blocks)
+        .map(|(height, count)| (height.value(), count.value()))
+        .collect(),
+      Err(redb::Error::TableDoesNotExist(_)) => Vec::new(),
+      Err(err) => return Err(err.into()),
+    };
+
+    Ok((total, recent))
+  }
+
+  pub fn reorg_height(&self, target_height: u64) -> Result {
+    Updater::reorg_height(self, target_height)
+  }
+
+  pub fn update(&self) -> Result {
+    Updater::update(self)
+  }
+
+  pub(crate) fn is_reorged(&self) -> bool {
+    self.reorged.load(atomic::Ordering::Relaxed)
+  }
+
+  fn begin_read(&self) -> Result<rtx::Rtx> {
+    Ok(rtx::Rtx(self.database.begin_read()?))
+  }
+
+  fn begin_write(&self) -> Result<WriteTransaction> {
+    if cfg!(test) {
+      let mut tx = self.database.begin_write()?;
+      tx.set_durability(redb::Durability::None);
+      Ok(tx)
+    } else {
+      Ok(self.database.begin_write()?)
+    }
+  }
+
+  fn increment_statistic(wtx: &WriteTransaction, statistic: Statistic, n: u64) -> Result {
+    let mut statistic_to_count = wtx.open_table(STATISTIC_TO_COUNT)?;
+    let value = statistic_to_count
+      .get(&(statistic.key()))?
+      .map(|x| x.value())
+      .unwrap_or(0)
+      + n;
+    statistic_to_count.insert(&statistic.key(), &value)?;
+    Ok(())
+  }
+
+  #[cfg(test)]
+  pub(crate) fn statistic(&self, statistic: Statistic) -> u64 {
+    self
+      .database
+      .begin_read()
+      .unwrap()
+      .open_table(STATISTIC_TO_COUNT)
+      .unwrap()
+      .get(&statistic.key())
+      .unwrap()
+      .map(|x| x.value())
+      .unwrap_or(0)
+  }
+
+  pub(crate) fn height(&self) -> Result<Option<Height>> {
+    self.begin_read()?.height()
+  }
+
+  /// The options the index was opened with, pinned to its RPC endpoint.
+  pub(crate) fn options(&self) -> &Options {
+    &self.options
+  }
+
+  pub(crate) fn block_count(&self) -> Result<u64> {
+    self.begin_read()?.block_count()
+  }
+
+  pub fn node_height(&self) -> Result<u64> {
+    Ok(self.client.get_block_count()?)
+  }
+
+  pub fn index_height(&self) -> Result<Option<u64>> {
+    Ok(self.height()?.map(|height| height.n()))
+  }
+
+  /// The node's estimate for confirming in the next block, in sat/vB, if it
+  /// has enough data to make one.
+  pub(crate) fn fast_fee_rate(&self) -> Result<Option<f64>> {
+    self.fee_rate_estimate(1)
+  }
+
+  /// The node's estimate for confirming within `target` blocks, in sat/vB,
+  /// if it has enough data to make one.
+  pub(crate) fn fee_rate_estimate(&self, target: u16) -> Result<Option<f64>> {
+    FEE_ESTIMATES.get_at_tip(
+      || self.best_block_hash(),
+      target,
+      || {
+        Ok(
+          self
+            .client
+            .estimate_smart_fee(target, None)?
+            .fee_rate
+            .map(|per_kvb| per_kvb.to_sat() as f64 / 1000.0),
+        )
+      },
+    )
+  }
+
+  /// The node's mempoolminfee in sat/vB, the lowest rate it currently
+  /// accepts into its mempool.
+  pub(crate) fn mempool_min_fee_rate(&self) -> Result<f64> {
+    self.mempool_fee_rate("mempoolminfee")
+  }
+
+  /// The node's minrelaytxfee in sat/vB, below which it relays nothing
+  /// whatever its mempool holds.
+  pub(crate) fn min_relay_fee_rate(&self) -> Result<f64> {
+    self.mempool_fee_rate("minrelaytxfee")
+  }
+
+  fn mempool_fee_rate(&self, field: &str) -> Result<f64> {
+    let info = MEMPOOL_INFO.get_at_tip(
+      || self.best_block_hash(),
+      (),
+      || {
+        Ok(
+          self
+            .client
+            .call::<serde_json::Value>("getmempoolinfo", &[])?,
+        )
+      },
+    )?;
+    let per_kvb = info[field]
+      .as_f64()
+      .ok_or_else(|| anyhow!("getmempoolinfo returned no {field}"))?;
+    Ok(per_kvb * COIN_VALUE as f64 / 1000.0)
+  }
+
+  /// The node's best block hash, uncached. Tip caches fetch it through
+  /// the shared tip, which holds its lock while fetching.
+  fn best_block_hash(&self) -> Result<BlockHash> {
+    Ok(self.client.get_best_block_hash()?)
+  }
+
+  pub(crate) fn blocks(&self, take: usize) -> Result<Vec<(u64, BlockHash)>> {
+    let mut blocks = Vec::new();
+
+    let rtx = self.begin_read()?;
+
+    let block_count = rtx.block_count()?;
+
+    let height_to_block_hash = rtx.0.open_table(HEIGHT_TO_BLOCK_HASH)?;
+
+    for next in height_to_block_hash.range(0..block_count)?.rev().take(take) {
+      blocks.push((next.0.value(), Entry::load(*next.1.value())));
+    }
+
+    Ok(blocks)
+  }
+
+  pub(crate) fn rare_sat_satpoints(&self) -> Result<Option<Vec<(Sat, SatPoint)>>> {
+    if self.has_sat_index()? {
+      let mut result = Vec::new();
+
+      let rtx = self.database.begin_read()?;
+
+      let sat_to_satpoint = rtx.open_table(SAT_TO_SATPOINT)?;
+
+      for (sat, satpoint) in sat_to_satpoint.range(0..)? {
+        result.push((Sat(sat.value()), Entry::load(*satpoint.value())));
+      }
+
+      Ok(Some(result))
+    } else {
+      Ok(None)
+    }
+  }
+
+  pub(crate) fn rare_sat_satpoint(&self, sat: Sat) -> Result<Option<SatPoint>> {
+    if self.has_sat_index()? {
+      Ok(
+        self
+          .database
+          .begin_read()?
+          .open_table(SAT_TO_SATPOINT)?
+          .get(&sat.n())?
+          .map(|satpoint| Entry::load(*satpoint.value())),
+      )
+    } else {
+      Ok(None)
+    }
+  }
+
+  pub(crate) fn block_header(&self, hash: BlockHash) -> Result<Option<BlockHeader>> {
+    self.client.get_block_header(&hash).into_option()
+  }
+
+  pub(crate) fn block_header_info(&self, hash: BlockHash) -> Result<Option<GetBlockHeaderResult>> {
+    self.client.get_block_header_info(&hash).into_option()
+  }
+
+  pub(crate) fn get_block_by_height(&self, height: u64) -> Result<Option<Block>> {
+    Ok(
+      self
+        .client
+        .get_block_hash(height)
+        .into_option()?
+        .map(|hash| self.client.get_block(&hash))
+        .transpose()?,
+    )
+  }
+
+  pub(crate) fn get_block_by_hash(&self, hash: BlockHash) -> Result<Option<Block>> {
+    self.client.get_block(&hash).into_option()
+  }
+
+  pub(crate) fn get_inscription_id_by_sat(&self, sat: Sat) -> Result<Option<InscriptionId>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(SAT_TO_INSCRIPTION_ID)?
+        .get(&sat.n())?
+        .map(|inscription_id| Entry::load(*inscription_id.value())),
+    )
+  }
+
+  pub(crate) fn get_inscription_id_by_inscription_number(
+    &self,
+    n: u64,
+  ) -> Result<Option<InscriptionId>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
+        .get(&n)?
+        .map(|id| Entry::load(*id.value())),
+    )
+  }
+
+  pub(crate) fn get_inscription_satpoint_by_id(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<SatPoint>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(INSCRIPTION_ID_TO_SATPOINT)?
+        .get(&inscription_id.store())?
+        .map(|satpoint| Entry::load(*satpoint.value())),
+    )
+  }
+
+  /// Like `get_inscription_satpoint_by_id`, but falls back to reading the
+  /// reveal from the node while the inscription is not indexed yet, placing
+  /// it the way the updater will.
+  pub(crate) fn get_pending_inscription_satpoint_by_id(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<SatPoint>> {
+    if let Some(satpoint) = self.get_inscription_satpoint_by_id(inscription_id)? {
+      return Ok(Some(satpoint));
+    }
+
+    let Some(tx) = self.get_transaction(inscription_id.txid)? else {
+      return Ok(None);
+    };
+
+    let Ok(index) = usize::try_from(inscription_id.index) else {
+      return Ok(None);
+    };
+
+    let packed = Inscription::all_from_transaction(&tx);
+    let Some(satpoint) = packed
+      .get(index)
+      .and_then(|inscription| inscription.satpoint_in(&tx))
+    else {
+      return Ok(None);
+    };
+
+    // the updater skips an envelope landing on a sat an earlier one took
+    if packed[..index]
+      .iter()
+      .any(|earlier| earlier.satpoint_in(&tx) == Some(satpoint))
+    {
+      return Ok(None);
+    }
+
+    Ok(Some(satpoint))
+  }
+
+  pub(crate) fn get_inscription_by_id(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<Inscription>> {
+    if self
+      .database
+      .begin_read()?
+      .open_table(INSCRIPTION_ID_TO_SATPOINT)?
+      .get(&inscription_id.store())?
+      .is_none()
+    {
+      return Ok(None);
+    }
+
+    Ok(self.get_transaction(inscription_id.txid)?.and_then(|tx| {
+      Inscription::all_from_transaction(&tx)
+        .into_iter()
+        .nth(usize::try_from(inscription_id.index).ok()?)
+    }))
+  }
+
+  pub(crate) fn get_inscriptions_on_output(
+    &self,
+    outpoint: OutPoint,
+  ) -> Result<Vec<InscriptionId>> {
+    Ok(
+      Self::inscriptions_on_output(
+        &self
+          .database
+          .begin_read()?
+          .open_table(SATPOINT_TO_INSCRIPTION_ID)?,
+        outpoint,
+      )?
+      .map(|(_satpoint, inscription_id)| inscription_id)
+      .collect(),
+    )
+  }
+
+  pub(crate) fn get_transaction(&self, txid: Txid) -> Result<Option<Transaction>> {
+    if txid == self.genesis_block_coinbase_txid {
+      Ok(Some(self.genesis_block_coinbase_transaction.clone()))
+    } else {
+      self.client.get_raw_transaction(&txid, None).into_option()
+    }
+  }
+
+  pub(crate) fn get_transaction_blockhash(&self, txid: Txid) -> Result<Option<BlockHash>> {
+    Ok(
+      self
+        .client
+        .get_raw_transaction_info(&txid, None)
+        .into_option()?
+        .and_then(|info| {
+          if info.in_active_chain.unwrap_or_default() {
+            info.blockhash
+          } else {
+            None
+          }
+        }),
+    )
+  }
+
+  pub(crate) fn is_transaction_in_active_chain(&self, txid: Txid) -> Result<bool> {
+    Ok(
+      self
+        .client
+        .get_raw_transaction_info(&txid, None)
+        .into_option()?
+        .and_then(|info| info.in_active_chain)
+        .unwrap_or(false),
+    )
+  }
+
+  pub(crate) fn find(&self, sat: u64) -> Result<Option<SatPoint>> {
+    self.require_sat_index("find")?;
+
+    let rtx = self.begin_read()?;
+
+    if rtx.block_count()? <= Sat(sat).height().n() {
+      return Ok(None);
+    }
+
+    let outpoint_to_sat_ranges = rtx.0.open_table(OUTPOINT_TO_SAT_RANGES)?;
+
+    for (key, value) in outpoint_to_sat_ranges.range::<&[u8; 36]>(&[0; 36]..)? {
+      let mut offset = 0;
+      for chunk in value.value().chunks_exact(11) {
+        let (start, end) = SatRange::load(chunk.try_into().unwrap());
+        if start <= sat && sat < end {
+          return Ok(Some(SatPoint {
+            outpoint: Entry::load(*key.value()),
+            offset: offset + sat - start,
+          }));
+        }
+        offset += end - start;
+      }
+    }
+
+    Ok(None)
+  }
+
+  fn list_inner(&self, outpoint: OutPointValue) -> Result<Option<Vec<u8>>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(OUTPOINT_TO_SAT_RANGES)?
+        .get(&outpoint)?
+        .map(|outpoint| outpoint.value().to_vec()),
+    )
+  }
+
+  pub(crate) fn list(&self, outpoint: OutPoint) -> Result<Option<List>> {
+    self.require_sat_index("list")?;
+
+    let array = outpoint.store();
+
+    let sat_ranges = self.list_inner(array)?;
+
+    match sat_ranges {
+      Some(sat_ranges) => Ok(Some(List::Unspent(
+        sat_ranges
+          .chunks_exact(11)
+          .map(|chunk| SatRange::load(chunk.try_into().unwrap()))
+          .collect(),
+      ))),
+      None => {
+        if self.is_transaction_in_active_chain(outpoint.txid)? {
+          Ok(Some(List::Spent))
+        } else {
+          Ok(None)
+        }
+      }
+    }
+  }
+
+  pub(crate) fn blocktime(&self, height: Height) -> Result<Blocktime> {
+    let height = height.n();
+
+    match self.get_block_by_height(height)? {
+      Some(block) => Ok(Blocktime::confirmed(block.header.time)),
+      None => {
+        let tx = self.database.begin_read()?;
+
+        let current = tx
+          .open_table(HEIGHT_TO_BLOCK_HASH)?
+          .range(0..)?
+          .rev()
+          .next()
+          .map(|(height, _hash)| height)
+          .map(|x| x.value())
+          .unwrap_or(0);
+
+        let expected_blocks = height.checked_sub(current).with_context(|| {
+          format!("current {current} height is greater than sat height {height}")
+        })?;
+
+        Ok(Blocktime::Expected(
+          Utc::now()
+            .round_subsecs(0)
+            .checked_add_signed(chrono::Duration::seconds(
+              10 * 60 * i64::try_from(expected_blocks)?,
+            ))
+            .ok_or_else(|| anyhow!("block timestamp out of range"))?,
+        ))
+      }
+    }
+  }
+
+  /// Inscriptions on `outpoints`, read from a single transaction. Memory
+  /// grows with the number of outpoints, not with the size of the index.
+  pub(crate) fn get_inscriptions_for_outpoints<'a>(
+    &self,
+    outpoints: impl IntoIterator<Item = &'a OutPoint>,
+  ) -> Result<BTreeMap<SatPoint, InscriptionId>> {
+    let rtx = self.database.begin_read()?;
+    let satpoint_to_id = rtx.open_table(SATPOINT_TO_INSCRIPTION_ID)?;
+
+    let mut inscriptions = BTreeMap::new();
+    for outpoint in outpoints {
+      inscriptions.extend(Self::inscriptions_on_output(&satpoint_to_id, *outpoint)?);
+    }
+    Ok(inscriptions)
+  }
+
+  pub(crate) fn get_homepage_inscriptions(&self) -> Result<Vec<InscriptionId>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
+        .iter()?
+        .rev()
+        .take(8)
+        .map(|(_number, id)| Entry::load(*id.value()))
+        .collect(),
+    )
+  }
+
+  pub(crate) fn get_latest_inscriptions_with_prev_and_next(
+    &self,
+    n: usize,
+    from: Option<u64>,
+  ) -> Result<(Vec<InscriptionId>, Option<u64>, Option<u64>)> {
+    let rtx = self.database.begin_read()?;
+
+    let inscription_number_to_inscription_id =
+      rtx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
+
+    let latest = match inscription_number_to_inscription_id.iter()?.rev().next() {
+      Some((number, _id)) => number.value(),
+      None => return Ok(Default::default()),
+    };
+
+    let from = from.unwrap_or(latest);
+
+    let prev = if let Some(prev) = from.checked_sub(n.try_into()?) {
+      inscription_number_to_inscription_id
+        .get(&prev)?
+        .map(|_| prev)
+    } else {
+      None
+    };
+
+    let next = if from < latest {
+      Some(
+        from
+          .checked_add(n.try_into()?)
+          .unwrap_or(latest)
+          .min(latest),
+      )
+    } else {
+      None
+    };
+
+    let inscriptions = inscription_number_to_inscription_id
+      .range(..=from)?
+      .rev()
+      .take(n)
+      .map(|(_number, id)| Entry::load(*id.value()))
+      .collect();
+
+    Ok((inscriptions, prev, next))
+  }
+
+  pub(crate) fn get_feed_inscriptions(&self, n: usize) -> Result<Vec<(u64, InscriptionId)>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?
+        .iter()?
+        .rev()
+        .take(n)
+        .map(|(number, id)| (number.value(), Entry::load(*id.value())))
+        .collect(),
+    )
+  }
+
+  pub(crate) fn get_inscription_entry(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<InscriptionEntry>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?
+        .get(&inscription_id.store())?
+        .map(|value| InscriptionEntry::load(value.value())),
+    )
+  }
+
+  /// `None` when `inscription_id` is not indexed. The owner is the address
+  /// of the output holding the inscription, when it has one.
+  pub fn get_inscription_details(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<InscriptionDetails>> {
+    let Some(satpoint) = self.get_inscription_satpoint_by_id(inscription_id)? else {
+      return Ok(None);
+    };
+    let entry = self
+      .get_inscription_entry(inscription_id)?
+      .ok_or_else(|| anyhow!("inscription {inscription_id} not indexed"))?;
+
+    let content_type = self
+      .get_transaction(inscription_id.txid)?
+      .and_then(|tx| Inscription::from_transaction(&tx))
+      .and_then(|inscription| inscription.content_type().map(str::to_owned));
+
+    let owner = self
+      .get_transaction(satpoint.outpoint.txid)?
+      .and_then(|tx| {
+        tx.output
+          .into_iter()
+          .nth(usize::try_from(satpoint.outpoint.vout).unwrap())
+      })
+      .and_then(|output| {
+        Address::from_script(&output.script_pubkey, self.options.chain().network()).ok()
+      })
+      .map(|address| address.to_string());
+
+    Ok(Some(InscriptionDetails {
+      id: inscription_id,
+      number: entry.number,
+      satpoint,
+      owner,
+      genesis_height: entry.height,
+      content_type,
+    }))
+  }
+
+  /// Export lines for `holdings`. Values are those of the output each
+  /// holding was on, spent or not, so historical holdings keep theirs.
+  pub fn export_rows(&self, holdings: &[Holding]) -> Result<Vec<ExportRow>> {
+    let mut rows = Vec::with_capacity(holdings.len());
+
+    for holding in holdings {
+      let entry = self
+        .get_inscription_entry(holding.inscription_id)?
+        .ok_or_else(|| anyhow!("inscription {} not indexed", holding.inscription_id))?;
+
+      let content_type = self
+        .get_transaction(holding.inscription_id.txid)?
+        .and_then(|tx| Inscription::from_transaction(&tx))
+        .and_then(|inscription| inscription.content_type().map(str::to_owned));
+
+      let value = self
+        .get_transaction(holding.satpoint.outpoint.txid)?
+        .and_then(|tx| {
+          tx.output
+            .get(usize::try_from(holding.satpoint.outpoint.vout).unwrap())
+            .map(|output| output.value)
+        });
+
+      rows.push(ExportRow {
+        id: holding.inscription_id,
+        number: entry.number,
+        satpoint: holding.satpoint,
+        value,
+        content_type,
+        genesis_height: entry.height,
+        last_transfer_height: holding.last_transfer_height,
+      });
+    }
+
+    Ok(rows)
+  }
+
+  #[cfg(test)]
+  fn assert_inscription_location(
+    &self,
+    inscription_id: InscriptionId,
+    satpoint: SatPoint,
+    sat: Option<u64>,
+  ) {
+    let rtx = self.database.begin_read().unwrap();
+
+    let satpoint_to_inscription_id = rtx.open_table(SATPOINT_TO_INSCRIPTION_ID).unwrap();
+
+    let inscription_id_to_satpoint = rtx.open_table(INSCRIPTION_ID_TO_SATPOINT).unwrap();
+
+    assert_eq!(
+      satpoint_to_inscription_id.len().unwrap(),
+      inscription_id_to_satpoint.len().unwrap(),
+    );
+
+    assert_eq!(
+      SatPoint::load(
+        *inscription_id_to_satpoint
+          .get(&inscription_id.store())
+          .unwrap()
+          .unwrap()
+          .value()
+      ),
+      satpoint,
+    );
+
+    assert_eq!(
+      InscriptionId::load(
+        *satpoint_to_inscription_id
+          .get(&satpoint.store())
+          .unwrap()
+          .unwrap()
+          .value()
+      ),
+      inscription_id,
+    );
+
+    if let Some(sat) = sat {
+      if self.has_sat_index().unwrap() {
+        assert_eq!(
+          InscriptionId::load(
+            *rtx
+              .open_table(SAT_TO_INSCRIPTION_ID)
+              .unwrap()
+              .get(&sat)
+              .unwrap()
+              .unwrap()
+              .value()
+          ),
+          inscription_id,
+        );
+
+        assert_eq!(
+          SatPoint::load(
+            *rtx
+              .open_table(SAT_TO_SATPOINT)
+              .unwrap()
+              .get(&sat)
+              .unwrap()
+              .unwrap()
+              .value()
+          ),
+          satpoint,
+        );
+      }
+    }
+  }
+
+  fn inscriptions_on_output<'a: 'tx, 'tx>(
+    satpoint_to_id: &'a impl ReadableTable<&'static SatPointValue, &'static InscriptionIdValue>,
+    outpoint: OutPoint,
+  ) -> Result<impl Iterator<Item = (SatPoint, InscriptionId)> + 'tx> {
+    let start = SatPoint {
+      outpoint,
+      offset: 0,
+    }
+    .store();
+
+    let end = SatPoint {
+      outpoint,
+      offset: u64::MAX,
+    }
+    .store();
+
+    Ok(
+      satpoint_to_id
+        .range::<&[u8; 44]>(&start..=&end)?
+        .map(|(satpoint, id)| (Entry::load(*satpoint.value()), Entry::load(*id.value()))),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bip39::Language;
+  use {
+    super::*,
+    bitcoin::secp256k1::rand::{self, RngCore},
+  };
+
+  struct ContextBuilder {
+    args: Vec<OsString>,
+    tempdir: Option<TempDir>,
+    watched_only: Option<Vec<String>>,
+  }
+
+  impl ContextBuilder {
+    fn build(self) -> Context {
+      self.try_build().unwrap()
+    }
+
+    fn try_build(self) -> Result<Context> {
+      let rpc_server = test_bitcoincore_rpc::builder()
+        .network(Network::Regtest)
+        .build();
+
+      let tempdir = self.tempdir.unwrap_or_else(|| TempDir::new().unwrap());
+      let cookie_file = tempdir.path().join("cookie");
+      fs::write(&cookie_file, "username:password").unwrap();
+
+      let command: Vec<OsString> = vec![
+        "ord".into(),
+        "--rpc-url".into(),
+        rpc_server.url().into(),
+        "--data-dir".into(),
+        tempdir.path().into(),
+        "--cookie-file".into(),
+        cookie_file.into(),
+        "--regtest".into(),
+      ];
+
+      let options = Options::try_parse_from(command.into_iter().chain(self.args)).unwrap();
+      let mut index = Index::open(&options)?;
+      if let Some(extra) = self.watched_only {
+        index = index.with_watched_only(extra);
+      }
+      index.update().unwrap();
+
+      Ok(Context {
+        options,
+        rpc_server,
+        tempdir,
+        index,
+      })
+    }
+
+    fn arg(mut self, arg: impl Into<OsString>) -> Self {
+      self.args.push(arg.into());
+      self
+    }
+
+    fn args<T: Into<OsString>, I: IntoIterator<Item = T>>(mut self, args: I) -> Self {
+      self.args.extend(args.into_iter().map(|arg| arg.into()));
+      self
+    }
+
+    fn tempdir(mut self, tempdir: TempDir) -> Self {
+      self.tempdir = Some(tempdir);
+      self
+    }
+
+    fn watched_only(mut self, addresses: &[Address]) -> Self {
+      self.watched_only = Some(addresses.iter().map(Address::to_string).collect());
+      self
+    }
+  }
+
+  struct Context {
+    options: Options,
+    rpc_server: test_bitcoincore_rpc::Handle,
+    #[allow(unused)]
+    tempdir: TempDir,
+    index: Index,
+  }
+
+  impl Context {
+    fn builder() -> ContextBuilder {
+      ContextBuilder {
+        args: Vec::new(),
+        tempdir: None,
+        watched_only: None,
+      }
+    }
+
+    fn mine_blocks(&self, n: u64) -> Vec<Block> {
+      let blocks = self.rpc_server.mine_blocks(n);
+      self.index.update().unwrap();
+      blocks
+    }
+
+    fn mine_blocks_with_subsidy(&self, n: u64, subsidy: u64) -> Vec<Block> {
+      let blocks = self.rpc_server.mine_blocks_with_subsidy(n, subsidy);
+      self.index.update().unwrap();
+      blocks
+    }
+
+    fn configurations() -> Vec<Context> {
+      vec![
+        Context::builder().build(),
+        Context::builder().arg("--index-sats").build(),
+      ]
+    }
+  }
+
+  #[test]
+  fn height_limit() {
+    {
+      let context = Context::builder().args(["--height-limit", "0"]).build();
+      context.mine_blocks(1);
+      assert_eq!(context.index.height().unwrap(), None);
+      assert_eq!(context.index.block_count().unwrap(), 0);
+    }
+
+    {
+      let context = Context::builder().args(["--height-limit", "1"]).build();
+      context.mine_blocks(1);
+      assert_eq!(context.index.height().unwrap(), Some(Height(0)));
+      assert_eq!(context.index.block_count().unwrap(), 1);
+    }
+
+    {
+      let context = Context::builder().args(["--height-limit", "2"]).build();
+      context.mine_blocks(2);
+      assert_eq!(context.index.height().unwrap(), Some(Height(1)));
+      assert_eq!(context.index.block_count().unwrap(), 2);
+    }
+  }
+
+  #[test]
+  fn inscriptions_paid_to_follow_current_location() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      witness: inscription("text/plain;charset=utf-8", "hello").to_witness(),
+      ..Default::default()
+    });
+    let inscription_id = InscriptionId::from(txid);
+    let reveal_block = context.mine_blocks(1).remove(0);
+
+    let reveal = reveal_block
+      .txdata
+      .iter()
+      .find(|tx| tx.txid() == txid)
+      .unwrap();
+    let scripts = BTreeMap::from([(reveal.output[0].script_pubkey.clone(), "a".to_string())]);
+
+    let data = context
+      .index
+      .inscriptions_paid_to(&reveal_block, &scripts)
+      .unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].inscription_id, inscription_id);
+    assert_eq!(
+      data[0].new_satpoint,
+      context
+        .index
+        .get_inscription_satpoint_by_id(inscription_id)
+        .unwrap()
+        .unwrap()
+    );
+    assert_eq!(data[0].new_address, "a");
+
+    let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0)],
+      ..Default::default()
+    });
+    let send_block = context.mine_blocks(1).remove(0);
+
+    // once moved on, the reveal output no longer holds it
+    assert!(context
+      .index
+      .inscriptions_paid_to(&reveal_block, &scripts)
+      .unwrap()
+      .is_empty());
+
+    let send = send_block
+      .txdata
+      .iter()
+      .find(|tx| tx.txid() == send_txid)
+      .unwrap();
+    let scripts = BTreeMap::from([(send.output[0].script_pubkey.clone(), "a".to_string())]);
+    let data = context
+      .index
+      .inscriptions_paid_to(&send_block, &scripts)
+      .unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(
+      data[0].new_satpoint,
+      SatPoint {
+        outpoint: OutPoint {
+          txid: send_txid,
+          vout: 0,
+        },
+        offset: 0,
+      }
+    );
+  }
+
+  #[test]
+  fn watched_only_skips_envelopes_paying_no_watched_script() {
+    let context = Context::builder().watched_only(&[change(0)]).build();
+    context.mine_blocks(2);
+
+    let to_watched = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      recipient: Some(change(0)),
+      witness: inscription("text/plain", "watched").to_witness(),
+      ..Default::default()
+    });
+    let to_unwatched = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 0, 0)],
+      recipient: Some(recipient()),
+      witness: inscription("text/plain", "unwatched").to_witness(),
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+
+    assert_eq!(
+      context
+        .index
+        .get_inscription_satpoint_by_id(to_watched.into())
+        .unwrap(),
+      Some(SatPoint {
+        outpoint: OutPoint {
+          txid: to_watched,
+          vout: 0
+        },
+        offset: 0,
+      })
+    );
+    assert_eq!(
+      context
+        .index
+        .get_inscription_satpoint_by_id(to_unwatched.into())
+        .unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn lock_time_is_enforced_until_its_height() {
+    use crate::subcommand::wallet::{apply_lock_time, check_lock_time};
+
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+
+    let tip = context.index.node_height().unwrap();
+    let lock_time = u32::try_from(tip).unwrap() + 2;
+    check_lock_time(lock_time, tip).unwrap();
+
+    let send = |lock_time, rbf| {
+      let mut tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::PackedLockTime::ZERO,
+        input: vec![tx_in(outpoint(1))],
+        output: vec![tx_out(1000, recipient())],
+      };
+      apply_lock_time(&mut tx, lock_time, rbf);
+      context.index.client.send_raw_transaction(&tx)
+    };
+
+    for rbf in [true, false] {
+      assert_regex_match!(
+        send(Some(lock_time), rbf).unwrap_err().to_string(),
+        ".*non-final.*"
+      );
+    }
+
+    context.mine_blocks(1);
+    assert_regex_match!(
+      send(Some(lock_time), true).unwrap_err().to_string(),
+      ".*non-final.*"
+    );
+
+    context.mine_blocks(1);
+    for rbf in [true, false] {
+      send(Some(lock_time), rbf).unwrap();
+    }
+    send(None, false).unwrap();
+  }
+
+  #[test]
+  fn expected_reveal_rows_are_read_from_the_reveal() {
+    let reveal = |inscription: Option<Inscription>| Transaction {
+      version: 2,
+      lock_time: bitcoin::PackedLockTime::ZERO,
+      input: vec![TxIn {
+        witness: inscription
+          .map(|inscription| inscription.to_witness())
+          .unwrap_or_default(),
+        ..tx_in(outpoint(1))
+      }],
+      output: vec![tx_out(1000, change(0)), tx_out(1000, recipient())],
+    };
+
+    let pointed = reveal(Some(inscription("text/plain", "ord").with_pointer(1500)));
+    let plain = reveal(Some(inscription("text/plain", "ord")));
+    let cardinal = reveal(None);
+
+    let rows =
+      updater::Updater::expected_reveal_rows(&[&pointed, &plain, &cardinal], Network::Testnet);
+
+    assert_eq!(
+      rows
+        .iter()
+        .map(|row| (
+          row.inscription_id,
+          row.new_satpoint,
+          row.new_address.as_str(),
+          row.created
+        ))
+        .collect::<Vec<_>>(),
+      vec![
+        (
+          pointed.txid().into(),
+          SatPoint {
+            outpoint: OutPoint {
+              txid: pointed.txid(),
+              vout: 1,
+            },
+            offset: 500,
+          },
+          "tb1q6en7qjxgw4ev8xwx94pzdry6a6ky7wlfeqzunz",
+          true,
+        ),
+        (
+          plain.txid().into(),
+          SatPoint {
+            outpoint: OutPoint {
+              txid: plain.txid(),
+              vout: 0,
+            },
+            offset: 0,
+          },
+          "tb1qjsv26lap3ffssj6hfy8mzn0lg5vte6a42j75ww",
+          true,
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn pending_inscription_satpoint_follows_the_pointer() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+
+    // the pointer puts the inscription on the first sat of the second output
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      outputs: 2,
+      witness: inscription("text/plain;charset=utf-8", "hello")
+        .with_pointer(25 * COIN_VALUE)
+        .to_witness(),
+      ..Default::default()
+    });
+    let inscription_id = InscriptionId::from(txid);
+    let satpoint = SatPoint {
+      outpoint: OutPoint { txid, vout: 1 },
+      offset: 0,
+    };
+
+    // still in the mempool, read from the reveal
+    context.index.update().unwrap();
+    assert_eq!(
+      context
+        .index
+        .get_inscription_satpoint_by_id(inscription_id)
+        .unwrap(),
+      None
+    );
+    assert_eq!(
+      context
+        .index
+        .get_pending_inscription_satpoint_by_id(inscription_id)
+        .unwrap(),
+      Some(satpoint)
+    );
+
+    // mined, read from the index, which agrees
+    context.mine_blocks(1);
+    assert_eq!(
+      context
+        .index
+        .get_inscription_satpoint_by_id(inscription_id)
+        .unwrap(),
+      Some(satpoint)
+    );
+    assert_eq!(
+      context
+        .index
+        .get_pending_inscription_satpoint_by_id(inscription_id)
+        .unwrap(),
+      Some(satpoint)
+    );
+
+    assert_eq!(
+      context
+        .index
+        .get_pending_inscription_satpoint_by_id(InscriptionId { txid, index: 1 })
+        .unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn brc20_transfer_spends_a_packed_inscription_from_its_output() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+
+    // the transfer inscription is the second envelope of its reveal, its
+    // pointer puts it on the second output
+    let inscriptions = [
+      inscription("text/plain;charset=utf-8", "hello"),
+      inscription(
+        "text/plain;charset=utf-8",
+        r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"1"}"#,
+      )
+      .with_pointer(546),
+    ];
+    let script = Inscription::append_batch_reveal_script(
+      &inscriptions,
+      bitcoin::blockdata::script::Builder::new(),
+    );
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      outputs: 2,
+      output_values: &[546, 546],
+      witness: bitcoin::Witness::from_vec(vec![script.into_bytes(), Vec::new()]),
+      ..Default::default()
+    });
+    let first = SatPoint {
+      outpoint: OutPoint { txid, vout: 0 },
+      offset: 0,
+    };
+    let transfer = InscriptionId { txid, index: 1 };
+    let satpoint = SatPoint {
+      outpoint: OutPoint { txid, vout: 1 },
+      offset: 0,
+    };
+
+    // still in the mempool, the transfer is built from the reveal
+    context.index.update().unwrap();
+    assert_eq!(
+      context
+        .index
+        .get_pending_inscription_satpoint_by_id(transfer)
+        .unwrap(),
+      Some(satpoint)
+    );
+
+    let transaction = crate::builder::transfer(crate::builder::TransferParams {
+      input_type: bitcoin::AddressType::P2tr,
+      postages: vec![(satpoint, TransactionBuilder::TARGET_POSTAGE)],
+      inscriptions: [
+        (first, InscriptionId { txid, index: 0 }),
+        (satpoint, transfer),
+      ]
+      .into(),
+      utxos: [
+        (first.outpoint, Amount::from_sat(546)),
+        (satpoint.outpoint, Amount::from_sat(546)),
+        (outpoint(1), Amount::from_sat(100_000)),
+      ]
+      .into(),
+      destination: recipient(),
+      destinations: Vec::new(),
+      additional_value: Amount::ZERO,
+      change: [change(0), change(1)],
+      fee_rate: FeeRate::try_from(1.0).unwrap(),
+      op_return: None,
+      locktime: None,
+      rbf: true,
+      witness_sizes: BTreeMap::new(),
+    })
+    .unwrap();
+
+    assert_eq!(
+      transaction
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<OutPoint>>(),
+      [satpoint.outpoint, outpoint(1)]
+    );
+    assert_eq!(
+      transaction.output[0].script_pubkey,
+      recipient().script_pubkey()
+    );
+
+    // mined, the index puts it where the transfer spent it from
+    context.mine_blocks(1);
+    assert_eq!(
+      context
+        .index
+        .get_inscription_satpoint_by_id(transfer)
+        .unwrap(),
+      Some(satpoint)
+    );
+  }
+
+  #[test]
+  fn backfill_job_state_from_str() {
+    for state in [
+      BackfillJobState::Pending,
+      BackfillJobState::Running,
+      BackfillJobState::Done,
+      BackfillJobState::Failed,
+    ] {
+      assert_eq!(state.as_str().parse::<BackfillJobState>().unwrap(), state);
+    }
+    assert!("finished".parse::<BackfillJobState>().is_err());
+  }
+
+  #[test]
+  fn block_timing_phases_add_up_to_total() {
+    let context = Context::builder().build();
+    context.mine_blocks(3);
+
+    // other tests index blocks concurrently, so only check invariants
+    let stats = block_timing_stats();
+    assert!(stats.indexed >= 3);
+    for timings in [stats.last.unwrap(), stats.average.unwrap()] {
+      let phases = timings.fetch
+        + timings.inputs
+        + timings.parse
+        + timings.redb
+        + timings.mysql
+        + timings.commit;
+      assert!(phases <= timings.total, "{timings:?}");
+      assert!(timings.total - phases < 50_000, "{timings:?}");
+    }
+  }
+
+  #[test]
+  fn inscriptions_below_first_inscription_height_are_skipped() {
+    let inscription = inscription("text/plain;charset=utf-8", "hello");
+    let template = TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      witness: inscription.to_witness(),
+      ..Default::default()
+    };
+
+    {
+      let context = Context::builder().build();
+      context.mine_blocks(1);
+      let txid = context.rpc_server.broadcast_tx(template.clone());
+      let inscription_id = InscriptionId::from(txid);
+      context.mine_blocks(1);
+
+      assert_eq!(
+        context.index.get_inscription_by_id(inscription_id).unwrap(),
+        Some(inscription)
+      );
+
+      assert_eq!(
+        context
+          .index
+          .get_inscription_satpoint_by_id(inscription_id)
+          .unwrap(),
+        Some(SatPoint {
+          outpoint: OutPoint { txid, vout: 0 },
+          offset: 0,
+        })
+      );
+    }
+
+    {
+      let context = Context::builder()
+        .arg("--first-inscription-height=3")
+        .build();
+      context.mine_blocks(1);
+      let txid = context.rpc_server.broadcast_tx(template);
+      let inscription_id = InscriptionId::from(txid);
+      context.mine_blocks(1);
+
+      assert_eq!(
+        context
+          .index
+          .get_inscription_satpoint_by_id(inscription_id)
+          .unwrap(),
+        None,
+      );
+    }
+  }
+
+  #[test]
+  fn list_first_coinbase_transaction() {
+    let context = Context::builder().arg("--index-sats").build();
+    assert_eq!(
+      context
+        .index
+        .list(
+          "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
+            .parse()
+            .unwrap()
+        )
+        .unwrap()
+        .unwrap(),
+      List::Unspent(vec![(0, 50 * COIN_VALUE)])
+    )
+  }
+
+  #[test]
+  fn list_second_coinbase_transaction() {
+    let context = Context::builder().arg("--index-sats").build();
+    let txid = context.mine_blocks(1)[0].txdata[0].txid();
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Unspent(vec![(50 * COIN_VALUE, 100 * COIN_VALUE)])
+    )
+  }
+
+  #[test]
+  fn list_split_ranges_are_tracked_correctly() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    context.mine_blocks(1);
+    let split_coinbase_output = TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      outputs: 2,
+      fee: 0,
+      ..Default::default()
+    };
+    let txid = context.rpc_server.broadcast_tx(split_coinbase_output);
+
+    context.mine_blocks(1);
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Unspent(vec![(50 * COIN_VALUE, 75 * COIN_VALUE)])
+    );
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 1)).unwrap().unwrap(),
+      List::Unspent(vec![(75 * COIN_VALUE, 100 * COIN_VALUE)])
+    );
+  }
+
+  #[test]
+  fn list_merge_ranges_are_tracked_correctly() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    context.mine_blocks(2);
+    let merge_coinbase_outputs = TransactionTemplate {
+      inputs: &[(1, 0, 0), (2, 0, 0)],
+      fee: 0,
+      ..Default::default()
+    };
+
+    let txid = context.rpc_server.broadcast_tx(merge_coinbase_outputs);
+    context.mine_blocks(1);
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Unspent(vec![
+        (50 * COIN_VALUE, 100 * COIN_VALUE),
+        (100 * COIN_VALUE, 150 * COIN_VALUE),
+      ]),
+    );
+  }
+
+  #[test]
+  fn list_fee_paying_transaction_range() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    context.mine_blocks(1);
+    let fee_paying_tx = TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      outputs: 2,
+      fee: 10,
+      ..Default::default()
+    };
+    let txid = context.rpc_server.broadcast_tx(fee_paying_tx);
+    let coinbase_txid = context.mine_blocks(1)[0].txdata[0].txid();
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Unspent(vec![(50 * COIN_VALUE, 7499999995)]),
+    );
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 1)).unwrap().unwrap(),
+      List::Unspent(vec![(7499999995, 9999999990)]),
+    );
+
+    assert_eq!(
+      context
+        .index
+        .list(OutPoint::new(coinbase_txid, 0))
+        .unwrap()
+        .unwrap(),
+      List::Unspent(vec![(10000000000, 15000000000), (9999999990, 10000000000)])
+    );
+  }
+
+  #[test]
+  fn list_two_fee_paying_transaction_range() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    context.mine_blocks(2);
+    let first_fee_paying_tx = TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      fee: 10,
+      ..Default::default()
+    };
+    let second_fee_paying_tx = TransactionTemplate {
+      inputs: &[(2, 0, 0)],
+      fee: 10,
+      ..Default::default()
+    };
+    context.rpc_server.broadcast_tx(first_fee_paying_tx);
+    context.rpc_server.broadcast_tx(second_fee_paying_tx);
+
+    let coinbase_txid = context.mine_blocks(1)[0].txdata[0].txid();
+
+    assert_eq!(
+      context
+        .index
+        .list(OutPoint::new(coinbase_txid, 0))
+        .unwrap()
+        .unwrap(),
+      List::Unspent(vec![
+        (15000000000, 20000000000),
+        (9999999990, 10000000000),
+        (14999999990, 15000000000),
+      ])
+    );
+  }
+
+  #[test]
+  fn list_null_output() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    context.mine_blocks(1);
+    let no_value_output = TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      fee: 50 * COIN_VALUE,
+      ..Default::default()
+    };
+    let txid = context.rpc_server.broadcast_tx(no_value_output);
+    context.mine_blocks(1);
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Unspent(Vec::new())
+    );
+  }
+
+  #[test]
+  fn list_null_input() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    context.mine_blocks(1);
+    let no_value_output = TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      fee: 50 * COIN_VALUE,
+      ..Default::default()
+    };
+    context.rpc_server.broadcast_tx(no_value_output);
+    context.mine_blocks(1);
+
+    let no_value_input = TransactionTemplate {
+      inputs: &[(2, 1, 0)],
+      fee: 0,
+      ..Default::default()
+    };
+    let txid = context.rpc_server.broadcast_tx(no_value_input);
+    context.mine_blocks(1);
+
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Unspent(Vec::new())
+    );
+  }
+
+  #[test]
+  fn list_spent_output() {
+    let context = Context::builder().arg("--index-sats").build();
+    context.mine_blocks(1);
+    context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      fee: 0,
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+    let txid = context.rpc_server.tx(1, 0).txid();
+    assert_eq!(
+      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
+      List::Spent,
+    );
+  }
+
+  #[test]
+  fn list_unknown_output() {
+    let context = Context::builder().arg("--index-sats").build();
+
+    assert_eq!(
+      context
+        .index
+        .list(
+          "0000000000000000000000000000000000000000000000000000000000000000:0"
+            .parse()
+            .unwrap()
+        )
+        .unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn find_first_sat() {
+    let context = Context::builder().arg("--index-sats").build();
+    assert_eq!(
+      context.index.find(0).unwrap().unwrap(),
+      SatPoint {
+        outpoint: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
+          .parse()
+          .unwrap(),
+        offset: 0,
+      }
+    )
+  }
+
+  #[test]
+  fn find_second_sat() {
+    let context = Context::builder().arg("--index-sats").build();
+    assert_eq!(
+      context.index.find(1).unwrap().unwrap(),
+      SatPoint {
+        outpoint: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
+          .parse()
+          .unwrap(),
+        offset: 1,
+      }
+    )
+  }
+
+  #[test]
+  fn find_first_sat_of_second_block() {
+    let context = Context::builder().arg("--index-sats").build();
+    context.mine_blocks(1);
+    assert_eq!(
+      context.index.find(50 * COIN_VALUE).unwrap().unwrap(),
+      SatPoint {
+        outpoint: "30f2f037629c6a21c1f40ed39b9bd6278df39762d68d07f49582b23bcb23386a:0"
+          .parse()
+          .unwrap(),
+        offset: 0,
+      }
+    )
+  }
+
+  #[test]
+  fn find_unmined_sat() {
+    let context = Context::builder().arg("--index-sats").build();
+    assert_eq!(context.index.find(50 * COIN_VALUE).unwrap(), None);
+  }
+
+  #[test]
+  fn find_first_sat_spent_in_second_block() {
+    let context = Context::builder().arg("--index-sats").build();
+    context.mine_blocks(1);
+    let spend_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      fee: 0,
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+    assert_eq!(
+      context.index.find(50 * COIN_VALUE).unwrap().unwrap(),
+      SatPoint {
+        outpoint: OutPoint::new(spend_txid, 0),
+        offset: 0,
+      }
+    )
+  }
+
+  #[test]
+  fn inscriptions_are_tracked_correctly() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 0 },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn inscription_details() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      witness: inscription("text/plain", "hello").to_witness(),
+      ..Default::default()
+    });
+    let inscription_id = InscriptionId::from(txid);
+    context.mine_blocks(1);
+
+    let details = context
+      .index
+      .get_inscription_details(inscription_id)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      details.satpoint,
+      SatPoint {
+        outpoint: OutPoint { txid, vout: 0 },
+        offset: 0,
+      }
+    );
+    assert_eq!(details.genesis_height, 2);
+    assert_eq!(details.content_type.as_deref(), Some("text/plain"));
+
+    assert_eq!(
+      context
+        .index
+        .get_inscription_details(InscriptionId::from(Txid::all_zeros()))
+        .unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn inscriptions_without_sats_are_unbound() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        fee: 50 * 100_000_000,
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 1, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: unbound_outpoint(),
+          offset: 0,
+        },
+        None,
+      );
+
+      context.mine_blocks(1);
+
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(4, 0, 0)],
+        fee: 50 * 100_000_000,
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(5, 1, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: unbound_outpoint(),
+          offset: 1,
+        },
+        None,
+      );
+    }
+  }
+
+  #[test]
+  fn unaligned_inscriptions_are_tracked_correctly() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 0 },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+
+      let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 0, 0), (2, 1, 0)],
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: send_txid,
+            vout: 0,
+          },
+          offset: 50 * COIN_VALUE,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn merged_inscriptions_are_tracked_correctly() {
+    for context in Context::configurations() {
+      context.mine_blocks(2);
+
+      let first_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+
+      let first_inscription_id = InscriptionId::from(first_txid);
+
+      let second_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 0, 0)],
+        witness: inscription("text/png", [1; 100]).to_witness(),
+        ..Default::default()
+      });
+      let second_inscription_id = InscriptionId::from(second_txid);
+
+      context.mine_blocks(1);
+
+      let merged_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(3, 1, 0), (3, 2, 0)],
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        first_inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: merged_txid,
+            vout: 0,
+          },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+
+      context.index.assert_inscription_location(
+        second_inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: merged_txid,
+            vout: 0,
+          },
+          offset: 50 * COIN_VALUE,
+        },
+        Some(100 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn packed_inscriptions_land_on_their_pointers() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let inscriptions = [
+        inscription("text/plain", "foo"),
+        inscription("text/plain", "bar").with_pointer(546),
+      ];
+
+      let script = Inscription::append_batch_reveal_script(
+        &inscriptions,
+        bitcoin::blockdata::script::Builder::new(),
+      );
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        outputs: 2,
+        output_values: &[546, 50 * COIN_VALUE - 546],
+        witness: bitcoin::Witness::from_vec(vec![script.into_bytes(), Vec::new()]),
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        InscriptionId { txid, index: 0 },
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 0 },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+
+      let second = InscriptionId { txid, index: 1 };
+
+      context.index.assert_inscription_location(
+        second,
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 1 },
+          offset: 0,
+        },
+        None,
+      );
+
+      if context.index.has_sat_index().unwrap() {
+        assert_eq!(
+          context
+            .index
+            .get_inscription_entry(second)
+            .unwrap()
+            .unwrap()
+            .sat,
+          Some(Sat(50 * COIN_VALUE + 546)),
+        );
+      }
+
+      assert_eq!(
+        context.index.get_inscription_by_id(second).unwrap(),
+        Some(inscriptions[1].clone()),
+      );
+    }
+  }
+
+  #[test]
+  fn inscriptions_that_are_sent_to_second_output_are_are_tracked_correctly() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 0 },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+
+      let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 0, 0), (2, 1, 0)],
+        outputs: 2,
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: send_txid,
+            vout: 1,
+          },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn missing_inputs_are_fetched_from_bitcoin_core() {
+    for args in [
+      ["--first-inscription-height", "2"].as_slice(),
+      ["--first-inscription-height", "2", "--index-sats"].as_slice(),
+    ] {
+      let context = Context::builder().args(args).build();
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 0 },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+
+      let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 0, 0), (2, 1, 0)],
+        ..Default::default()
+      });
+
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: send_txid,
+            vout: 0,
+          },
+          offset: 50 * COIN_VALUE,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn one_input_fee_spent_inscriptions_are_tracked_correctly() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 1, 0)],
+        fee: 50 * COIN_VALUE,
+        ..Default::default()
+      });
+
+      let coinbase_tx = context.mine_blocks(1)[0].txdata[0].txid();
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: coinbase_tx,
+            vout: 0,
+          },
+          offset: 50 * COIN_VALUE,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn two_input_fee_spent_inscriptions_are_tracked_correctly() {
+    for context in Context::configurations() {
+      context.mine_blocks(2);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      context.mine_blocks(1);
+
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 0, 0), (3, 1, 0)],
+        fee: 50 * COIN_VALUE,
+        ..Default::default()
+      });
+
+      let coinbase_tx = context.mine_blocks(1)[0].txdata[0].txid();
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: coinbase_tx,
+            vout: 0,
+          },
+          offset: 50 * COIN_VALUE,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn inscription_can_be_fee_spent_in_first_transaction() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        fee: 50 * COIN_VALUE,
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+
+      let coinbase_tx = context.mine_blocks(1)[0].txdata[0].txid();
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint {
+            txid: coinbase_tx,
+            vout: 0,
+          },
+          offset: 50 * COIN_VALUE,
+        },
+        Some(50 * COIN_VALUE),
+      );
     }
   }
 
   #[test]
-  fn inscriptions_below_first_inscription_height_are_skipped() {
-    let inscription = inscription("text/plain;charset=utf-8", "hello");
-    let template = TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      witness: inscription.to_witness(),
-      ..Default::default()
-    };
-
-    {
-      let context = Context::builder().build();
+  fn lost_inscriptions() {
+    for context in Context::configurations() {
       context.mine_blocks(1);
-      let txid = context.rpc_server.broadcast_tx(template.clone());
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        fee: 50 * COIN_VALUE,
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
       let inscription_id = InscriptionId::from(txid);
-      context.mine_blocks(1);
 
-      assert_eq!(
-        context.index.get_inscription_by_id(inscription_id).unwrap(),
-        Some(inscription)
-      );
+      context.mine_blocks_with_subsidy(1, 0);
 
-      assert_eq!(
-        context
-          .index
-          .get_inscription_satpoint_by_id(inscription_id)
-          .unwrap(),
-        Some(SatPoint {
-          outpoint: OutPoint { txid, vout: 0 },
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint::null(),
           offset: 0,
-        })
+        },
+        Some(50 * COIN_VALUE),
       );
     }
+  }
 
-    {
-      let context = Context::builder()
-        .arg("--first-inscription-height=3")
-        .build();
+  #[test]
+  fn multiple_inscriptions_can_be_lost() {
+    for context in Context::configurations() {
       context.mine_blocks(1);
-      let txid = context.rpc_server.broadcast_tx(template);
-      let inscription_id = InscriptionId::from(txid);
+
+      let first_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        fee: 50 * COIN_VALUE,
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let first_inscription_id = InscriptionId::from(first_txid);
+
+      context.mine_blocks_with_subsidy(1, 0);
       context.mine_blocks(1);
 
-      assert_eq!(
-        context
-          .index
-          .get_inscription_satpoint_by_id(inscription_id)
-          .unwrap(),
-        None,
+      let second_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(3, 0, 0)],
+        fee: 50 * COIN_VALUE,
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let second_inscription_id = InscriptionId::from(second_txid);
+
+      context.mine_blocks_with_subsidy(1, 0);
+
+      context.index.assert_inscription_location(
+        first_inscription_id,
+        SatPoint {
+          outpoint: OutPoint::null(),
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+
+      context.index.assert_inscription_location(
+        second_inscription_id,
+        SatPoint {
+          outpoint: OutPoint::null(),
+          offset: 50 * COIN_VALUE,
+        },
+        Some(150 * COIN_VALUE),
       );
     }
   }
 
   #[test]
-  fn list_first_coinbase_transaction() {
+  fn lost_sats_are_tracked_correctly() {
     let context = Context::builder().arg("--index-sats").build();
+    assert_eq!(context.index.statistic(Statistic::LostSats), 0);
+
+    context.mine_blocks(1);
+    assert_eq!(context.index.statistic(Statistic::LostSats), 0);
+
+    context.mine_blocks_with_subsidy(1, 0);
     assert_eq!(
-      context
-        .index
-        .list(
-          "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
-            .parse()
-            .unwrap()
-        )
-        .unwrap()
-        .unwrap(),
-      List::Unspent(vec![(0, 50 * COIN_VALUE)])
-    )
-  }
+      context.index.statistic(Statistic::LostSats),
+      50 * COIN_VALUE
+    );
 
-  #[test]
-  fn list_second_coinbase_transaction() {
-    let context = Context::builder().arg("--index-sats").build();
-    let txid = context.mine_blocks(1)[0].txdata[0].txid();
+    context.mine_blocks_with_subsidy(1, 0);
     assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Unspent(vec![(50 * COIN_VALUE, 100 * COIN_VALUE)])
-    )
+      context.index.statistic(Statistic::LostSats),
+      100 * COIN_VALUE
+    );
+
+    context.mine_blocks(1);
+    assert_eq!(
+      context.index.statistic(Statistic::LostSats),
+      100 * COIN_VALUE
+    );
   }
 
   #[test]
-  fn list_split_ranges_are_tracked_correctly() {
+  fn lost_sat_ranges_are_tracked_correctly() {
     let context = Context::builder().arg("--index-sats").build();
 
-    context.mine_blocks(1);
-    let split_coinbase_output = TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      outputs: 2,
-      fee: 0,
-      ..Default::default()
+    let null_ranges = || match context.index.list(OutPoint::null()).unwrap().unwrap() {
+      List::Unspent(ranges) => ranges,
+      _ => panic!(),
     };
-    let txid = context.rpc_server.broadcast_tx(split_coinbase_output);
+
+    assert!(null_ranges().is_empty());
+
+    context.mine_blocks(1);
+
+    assert!(null_ranges().is_empty());
+
+    context.mine_blocks_with_subsidy(1, 0);
+
+    assert_eq!(null_ranges(), [(100 * COIN_VALUE, 150 * COIN_VALUE)]);
+
+    context.mine_blocks_with_subsidy(1, 0);
+
+    assert_eq!(
+      null_ranges(),
+      [
+        (100 * COIN_VALUE, 150 * COIN_VALUE),
+        (150 * COIN_VALUE, 200 * COIN_VALUE)
+      ]
+    );
 
     context.mine_blocks(1);
 
     assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Unspent(vec![(50 * COIN_VALUE, 75 * COIN_VALUE)])
+      null_ranges(),
+      [
+        (100 * COIN_VALUE, 150 * COIN_VALUE),
+        (150 * COIN_VALUE, 200 * COIN_VALUE)
+      ]
     );
 
+    context.mine_blocks_with_subsidy(1, 0);
+
     assert_eq!(
-      context.index.list(OutPoint::new(txid, 1)).unwrap().unwrap(),
-      List::Unspent(vec![(75 * COIN_VALUE, 100 * COIN_VALUE)])
+      null_ranges(),
+      [
+        (100 * COIN_VALUE, 150 * COIN_VALUE),
+        (150 * COIN_VALUE, 200 * COIN_VALUE),
+        (250 * COIN_VALUE, 300 * COIN_VALUE)
+      ]
     );
   }
 
   #[test]
-  fn list_merge_ranges_are_tracked_correctly() {
-    let context = Context::builder().arg("--index-sats").build();
+  fn lost_inscriptions_get_lost_satpoints() {
+    for context in Context::configurations() {
+      context.mine_blocks_with_subsidy(1, 0);
+      context.mine_blocks(1);
 
-    context.mine_blocks(2);
-    let merge_coinbase_outputs = TransactionTemplate {
-      inputs: &[(1, 0, 0), (2, 0, 0)],
-      fee: 0,
-      ..Default::default()
-    };
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 0, 0)],
+        outputs: 2,
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+      context.mine_blocks(1);
 
-    let txid = context.rpc_server.broadcast_tx(merge_coinbase_outputs);
-    context.mine_blocks(1);
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(3, 1, 1), (3, 1, 0)],
+        fee: 50 * COIN_VALUE,
+        ..Default::default()
+      });
+      context.mine_blocks_with_subsidy(1, 0);
 
-    assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Unspent(vec![
-        (50 * COIN_VALUE, 100 * COIN_VALUE),
-        (100 * COIN_VALUE, 150 * COIN_VALUE),
-      ]),
-    );
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint::null(),
+          offset: 75 * COIN_VALUE,
+        },
+        Some(100 * COIN_VALUE),
+      );
+    }
+  }
+
+  #[test]
+  fn inscription_skips_zero_value_first_output_of_inscribe_transaction() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        outputs: 2,
+        witness: inscription("text/plain", "hello").to_witness(),
+        output_values: &[0, 50 * COIN_VALUE],
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+      context.mine_blocks(1);
+
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint { txid, vout: 1 },
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
   }
 
   #[test]
-  fn list_fee_paying_transaction_range() {
-    let context = Context::builder().arg("--index-sats").build();
+  fn inscription_can_be_lost_in_first_transaction() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
 
-    context.mine_blocks(1);
-    let fee_paying_tx = TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      outputs: 2,
-      fee: 10,
-      ..Default::default()
-    };
-    let txid = context.rpc_server.broadcast_tx(fee_paying_tx);
-    let coinbase_txid = context.mine_blocks(1)[0].txdata[0].txid();
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0)],
+        fee: 50 * COIN_VALUE,
+        witness: inscription("text/plain", "hello").to_witness(),
+        ..Default::default()
+      });
+      let inscription_id = InscriptionId::from(txid);
+      context.mine_blocks_with_subsidy(1, 0);
 
-    assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Unspent(vec![(50 * COIN_VALUE, 7499999995)]),
-    );
+      context.index.assert_inscription_location(
+        inscription_id,
+        SatPoint {
+          outpoint: OutPoint::null(),
+          offset: 0,
+        },
+        Some(50 * COIN_VALUE),
+      );
+    }
+  }
 
-    assert_eq!(
-      context.index.list(OutPoint::new(txid, 1)).unwrap().unwrap(),
-      List::Unspent(vec![(7499999995, 9999999990)]),
-    );
+  #[test]
+  fn lost_rare_sats_are_tracked() {
+    let context = Context::builder().arg("--index-sats").build();
+    context.mine_blocks_with_subsidy(1, 0);
+    context.mine_blocks_with_subsidy(1, 0);
 
     assert_eq!(
       context
         .index
-        .list(OutPoint::new(coinbase_txid, 0))
+        .rare_sat_satpoint(Sat(50 * COIN_VALUE))
         .unwrap()
         .unwrap(),
-      List::Unspent(vec![(10000000000, 15000000000), (9999999990, 10000000000)])
+      SatPoint {
+        outpoint: OutPoint::null(),
+        offset: 0,
+      },
     );
-  }
-
-  #[test]
-  fn list_two_fee_paying_transaction_range() {
-    let context = Context::builder().arg("--index-sats").build();
-
-    context.mine_blocks(2);
-    let first_fee_paying_tx = TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      fee: 10,
-      ..Default::default()
-    };
-    let second_fee_paying_tx = TransactionTemplate {
-      inputs: &[(2, 0, 0)],
-      fee: 10,
-      ..Default::default()
-    };
-    context.rpc_server.broadcast_tx(first_fee_paying_tx);
-    context.rpc_server.broadcast_tx(second_fee_paying_tx);
-
-    let coinbase_txid = context.mine_blocks(1)[0].txdata[0].txid();
 
     assert_eq!(
       context
         .index
-        .list(OutPoint::new(coinbase_txid, 0))
+        .rare_sat_satpoint(Sat(100 * COIN_VALUE))
         .unwrap()
         .unwrap(),
-      List::Unspent(vec![
-        (15000000000, 20000000000),
-        (9999999990, 10000000000),
-        (14999999990, 15000000000),
-      ])
+      SatPoint {
+        outpoint: OutPoint::null(),
+        offset: 50 * COIN_VALUE,
+      },
     );
   }
 
   #[test]
-  fn list_null_output() {
-    let context = Context::builder().arg("--index-sats").build();
-
-    context.mine_blocks(1);
-    let no_value_output = TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      fee: 50 * COIN_VALUE,
-      ..Default::default()
-    };
-    let txid = context.rpc_server.broadcast_tx(no_value_output);
-    context.mine_blocks(1);
+  fn old_schema_gives_correct_error() {
+    let tempdir = {
+      let context = Context::builder().build();
 
-    assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Unspent(Vec::new())
-    );
-  }
+      let wtx = context.index.database.begin_write().unwrap();
 
-  #[test]
-  fn list_null_input() {
-    let context = Context::builder().arg("--index-sats").build();
+      wtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .insert(&Statistic::Schema.key(), &0)
+        .unwrap();
 
-    context.mine_blocks(1);
-    let no_value_output = TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      fee: 50 * COIN_VALUE,
-      ..Default::default()
-    };
-    context.rpc_server.broadcast_tx(no_value_output);
-    context.mine_blocks(1);
+      wtx.commit().unwrap();
 
-    let no_value_input = TransactionTemplate {
-      inputs: &[(2, 1, 0)],
-      fee: 0,
-      ..Default::default()
+      context.tempdir
     };
-    let txid = context.rpc_server.broadcast_tx(no_value_input);
-    context.mine_blocks(1);
-
-    assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Unspent(Vec::new())
-    );
-  }
-
-  #[test]
-  fn list_spent_output() {
-    let context = Context::builder().arg("--index-sats").build();
-    context.mine_blocks(1);
-    context.rpc_server.broadcast_tx(TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      fee: 0,
-      ..Default::default()
-    });
-    context.mine_blocks(1);
-    let txid = context.rpc_server.tx(1, 0).txid();
-    assert_eq!(
-      context.index.list(OutPoint::new(txid, 0)).unwrap().unwrap(),
-      List::Spent,
-    );
-  }
 
-  #[test]
-  fn list_unknown_output() {
-    let context = Context::builder().arg("--index-sats").build();
+    let path = tempdir.path().to_owned();
 
-    assert_eq!(
-      context
-        .index
-        .list(
-          "0000000000000000000000000000000000000000000000000000000000000000:0"
-            .parse()
-            .unwrap()
-        )
-        .unwrap(),
-      None
-    );
-  }
+    let delimiter = if cfg!(windows) { '\\' } else { '/' };
 
-  #[test]
-  fn find_first_sat() {
-    let context = Context::builder().arg("--index-sats").build();
     assert_eq!(
-      context.index.find(0).unwrap().unwrap(),
-      SatPoint {
-        outpoint: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
-          .parse()
-          .unwrap(),
-        offset: 0,
-      }
-    )
+      Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
+      format!("index at `{}{delimiter}regtest{delimiter}index.redb` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema 0, ord schema {SCHEMA_VERSION}", path.display()));
   }
 
   #[test]
-  fn find_second_sat() {
-    let context = Context::builder().arg("--index-sats").build();
-    assert_eq!(
-      context.index.find(1).unwrap().unwrap(),
-      SatPoint {
-        outpoint: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
-          .parse()
-          .unwrap(),
-        offset: 1,
-      }
-    )
-  }
+  fn new_schema_gives_correct_error() {
+    let tempdir = {
+      let context = Context::builder().build();
 
-  #[test]
-  fn find_first_sat_of_second_block() {
-    let context = Context::builder().arg("--index-sats").build();
-    context.mine_blocks(1);
-    assert_eq!(
-      context.index.find(50 * COIN_VALUE).unwrap().unwrap(),
-      SatPoint {
-        outpoint: "30f2f037629c6a21c1f40ed39b9bd6278df39762d68d07f49582b23bcb23386a:0"
-          .parse()
-          .unwrap(),
-        offset: 0,
-      }
-    )
-  }
+      let wtx = context.index.database.begin_write().unwrap();
 
-  #[test]
-  fn find_unmined_sat() {
-    let context = Context::builder().arg("--index-sats").build();
-    assert_eq!(context.index.find(50 * COIN_VALUE).unwrap(), None);
-  }
+      wtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .insert(&Statistic::Schema.key(), &u64::MAX)
+        .unwrap();
 
-  #[test]
-  fn find_first_sat_spent_in_second_block() {
-    let context = Context::builder().arg("--index-sats").build();
-    context.mine_blocks(1);
-    let spend_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-      inputs: &[(1, 0, 0)],
-      fee: 0,
-      ..Default::default()
-    });
-    context.mine_blocks(1);
-    assert_eq!(
-      context.index.find(50 * COIN_VALUE).unwrap().unwrap(),
-      SatPoint {
-        outpoint: OutPoint::new(spend_txid, 0),
-        offset: 0,
-      }
-    )
-  }
+      wtx.commit().unwrap();
 
-  #[test]
-  fn inscriptions_are_tracked_correctly() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+      context.tempdir
+    };
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
+    let path = tempdir.path().to_owned();
 
-      context.mine_blocks(1);
+    let delimiter = if cfg!(windows) { '\\' } else { '/' };
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint { txid, vout: 0 },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
+    assert_eq!(
+      Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
+      format!("index at `{}{delimiter}regtest{delimiter}index.redb` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {}, ord schema {SCHEMA_VERSION}", path.display(), u64::MAX));
   }
 
   #[test]
-  fn inscriptions_without_sats_are_unbound() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn other_chain_gives_correct_error() {
+    let tempdir = {
+      let context = Context::builder().build();
 
-      context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        fee: 50 * 100_000_000,
-        ..Default::default()
-      });
+      let wtx = context.index.database.begin_write().unwrap();
 
-      context.mine_blocks(1);
+      wtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .insert(&Statistic::Chain.key(), &chain_code(Chain::Mainnet))
+        .unwrap();
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 1, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
+      wtx.commit().unwrap();
 
-      let inscription_id = InscriptionId::from(txid);
+      context.tempdir
+    };
 
-      context.mine_blocks(1);
+    let path = tempdir.path().to_owned();
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: unbound_outpoint(),
-          offset: 0,
-        },
-        None,
-      );
+    let delimiter = if cfg!(windows) { '\\' } else { '/' };
 
-      context.mine_blocks(1);
+    assert_eq!(
+      Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
+      format!("index at `{}{delimiter}regtest{delimiter}index.redb` was built for mainnet, refusing to open it for regtest", path.display()));
+  }
 
-      context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(4, 0, 0)],
-        fee: 50 * 100_000_000,
-        ..Default::default()
-      });
+  #[test]
+  fn every_network_has_its_own_mysql_database() {
+    let databases = [
+      Network::Bitcoin,
+      Network::Testnet,
+      Network::Signet,
+      Network::Regtest,
+    ]
+    .map(MysqlDatabase::get_database);
+    assert_eq!(
+      databases,
+      ["ord_mainnet", "ord_testnet", "ord_signet", "ord_regtest"]
+    );
+  }
 
-      context.mine_blocks(1);
+  #[test]
+  fn mysql_network_stamp_is_checked() {
+    assert!(!MysqlDatabase::recorded_network("ord_regtest", None, "regtest").unwrap());
+    assert!(
+      MysqlDatabase::recorded_network("ord_regtest", Some("regtest".into()), "regtest").unwrap()
+    );
+    assert_eq!(
+      MysqlDatabase::recorded_network("ord_regtest", Some("bitcoin".into()), "regtest")
+        .unwrap_err()
+        .to_string(),
+      "mysql database ord_regtest was built for bitcoin, refusing to use it for regtest"
+    );
+  }
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(5, 1, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
+  #[test]
+  fn unmarked_index_requires_adoption() {
+    let tempdir = {
+      let context = Context::builder().build();
 
-      let inscription_id = InscriptionId::from(txid);
+      let wtx = context.index.database.begin_write().unwrap();
 
-      context.mine_blocks(1);
+      wtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .remove(&Statistic::Chain.key())
+        .unwrap();
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: unbound_outpoint(),
-          offset: 1,
-        },
-        None,
-      );
-    }
+      wtx.commit().unwrap();
+
+      context.tempdir
+    };
+
+    let index = tempdir.path().join("regtest").join("index.redb");
+
+    assert_eq!(
+      Context::builder()
+        .arg("--index")
+        .arg(&index)
+        .try_build()
+        .err()
+        .unwrap()
+        .to_string(),
+      format!(
+        "index at `{}` does not record which chain it was built for, rerun with --adopt-data-dir to mark it as regtest",
+        index.display()
+      )
+    );
+
+    let context = Context::builder()
+      .arg("--index")
+      .arg(&index)
+      .arg("--adopt-data-dir")
+      .build();
+
+    assert_eq!(
+      context
+        .index
+        .database
+        .begin_read()
+        .unwrap()
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .get(&Statistic::Chain.key())
+        .unwrap()
+        .map(|x| x.value()),
+      Some(chain_code(Chain::Regtest)),
+    );
   }
 
   #[test]
-  fn unaligned_inscriptions_are_tracked_correctly() {
+  fn inscriptions_on_output() {
     for context in Context::configurations() {
       context.mine_blocks(1);
 
@@ -1914,121 +8575,216 @@ mod tests {
         witness: inscription("text/plain", "hello").to_witness(),
         ..Default::default()
       });
+
       let inscription_id = InscriptionId::from(txid);
 
+      assert_eq!(
+        context
+          .index
+          .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
+          .unwrap(),
+        []
+      );
+
       context.mine_blocks(1);
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint { txid, vout: 0 },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
+      assert_eq!(
+        context
+          .index
+          .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
+          .unwrap(),
+        [inscription_id]
       );
 
-      let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 0, 0), (2, 1, 0)],
+      let send_id = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 1, 0)],
         ..Default::default()
       });
 
       context.mine_blocks(1);
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: send_txid,
+      assert_eq!(
+        context
+          .index
+          .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
+          .unwrap(),
+        []
+      );
+
+      assert_eq!(
+        context
+          .index
+          .get_inscriptions_on_output(OutPoint {
+            txid: send_id,
             vout: 0,
-          },
-          offset: 50 * COIN_VALUE,
-        },
-        Some(50 * COIN_VALUE),
+          })
+          .unwrap(),
+        [inscription_id]
       );
     }
   }
 
-  #[test]
-  fn merged_inscriptions_are_tracked_correctly() {
-    for context in Context::configurations() {
-      context.mine_blocks(2);
+  fn synthetic_outpoint(n: u32) -> OutPoint {
+    let mut txid = [0; 32];
+    txid[..4].copy_from_slice(&n.to_le_bytes());
+    OutPoint {
+      txid: Txid::from_inner(txid),
+      vout: 0,
+    }
+  }
 
-      let first_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
+  fn insert_synthetic_inscriptions(index: &Index, n: u32) {
+    let wtx = index.begin_write().unwrap();
+    {
+      let mut satpoint_to_id = wtx.open_table(SATPOINT_TO_INSCRIPTION_ID).unwrap();
+      for i in 0..n {
+        let outpoint = synthetic_outpoint(i);
+        satpoint_to_id
+          .insert(
+            &SatPoint {
+              outpoint,
+              offset: 0,
+            }
+            .store(),
+            &InscriptionId {
+              txid: outpoint.txid,
+              index: 0,
+            }
+            .store(),
+          )
+          .unwrap();
+      }
+    }
+    wtx.commit().unwrap();
+  }
 
-      let first_inscription_id = InscriptionId::from(first_txid);
+  #[test]
+  fn inscriptions_for_outpoints() {
+    let context = Context::builder().build();
+    insert_synthetic_inscriptions(&context.index, 1000);
 
-      let second_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 0, 0)],
-        witness: inscription("text/png", [1; 100]).to_witness(),
-        ..Default::default()
-      });
-      let second_inscription_id = InscriptionId::from(second_txid);
+    let inscribed = |n| {
+      let outpoint = synthetic_outpoint(n);
+      (
+        SatPoint {
+          outpoint,
+          offset: 0,
+        },
+        InscriptionId {
+          txid: outpoint.txid,
+          index: 0,
+        },
+      )
+    };
 
-      context.mine_blocks(1);
+    assert_eq!(
+      context
+        .index
+        .get_inscriptions_for_outpoints(&[
+          synthetic_outpoint(7),
+          synthetic_outpoint(900),
+          synthetic_outpoint(5000),
+        ])
+        .unwrap(),
+      [inscribed(7), inscribed(900)].into()
+    );
+  }
 
-      let merged_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(3, 1, 0), (3, 2, 0)],
-        ..Default::default()
-      });
+  #[test]
+  fn funding_inputs_holding_inscriptions_are_found() {
+    use crate::subcommand::wallet::funding::{funding_inscriptions, FundingInput};
 
-      context.mine_blocks(1);
+    let context = Context::builder().build();
+    insert_synthetic_inscriptions(&context.index, 10);
 
-      context.index.assert_inscription_location(
-        first_inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: merged_txid,
-            vout: 0,
-          },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
+    let input = |n| FundingInput {
+      outpoint: synthetic_outpoint(n),
+      value: 10000,
+      script_pubkey: recipient().script_pubkey(),
+    };
 
-      context.index.assert_inscription_location(
-        second_inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: merged_txid,
-            vout: 0,
-          },
-          offset: 50 * COIN_VALUE,
-        },
-        Some(100 * COIN_VALUE),
-      );
-    }
+    assert_eq!(
+      funding_inscriptions(&context.index, &[input(3), input(50)], None)
+        .unwrap()
+        .into_keys()
+        .collect::<Vec<SatPoint>>(),
+      [SatPoint {
+        outpoint: synthetic_outpoint(3),
+        offset: 0,
+      }]
+    );
   }
 
+  // cargo test --release scoped_inscription_lookup -- --ignored
   #[test]
-  fn inscriptions_that_are_sent_to_second_output_are_are_tracked_correctly() {
+  #[ignore]
+  fn scoped_inscription_lookup_benchmark() {
+    const INSCRIPTIONS: u32 = 2_000_000;
+
+    let context = Context::builder().build();
+    insert_synthetic_inscriptions(&context.index, INSCRIPTIONS);
+
+    let outpoints = (0..100)
+      .map(|i| synthetic_outpoint(i * (INSCRIPTIONS / 100)))
+      .collect::<Vec<OutPoint>>();
+
+    let start = Instant::now();
+    let inscriptions = context
+      .index
+      .get_inscriptions_for_outpoints(&outpoints)
+      .unwrap();
+
+    // only the requested outpoints are ever held in memory
+    assert_eq!(inscriptions.len(), outpoints.len());
+    assert!(
+      start.elapsed() < Duration::from_secs(1),
+      "looked up {} of {INSCRIPTIONS} inscriptions in {:?}",
+      inscriptions.len(),
+      start.elapsed()
+    );
+  }
+
+  #[test]
+  fn inscriptions_on_same_sat_after_the_first_are_ignored() {
     for context in Context::configurations() {
       context.mine_blocks(1);
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      let first = context.rpc_server.broadcast_tx(TransactionTemplate {
         inputs: &[(1, 0, 0)],
         witness: inscription("text/plain", "hello").to_witness(),
         ..Default::default()
       });
-      let inscription_id = InscriptionId::from(txid);
 
       context.mine_blocks(1);
 
+      let inscription_id = InscriptionId::from(first);
+
+      assert_eq!(
+        context
+          .index
+          .get_inscriptions_on_output(OutPoint {
+            txid: first,
+            vout: 0,
+          })
+          .unwrap(),
+        [inscription_id]
+      );
+
       context.index.assert_inscription_location(
         inscription_id,
         SatPoint {
-          outpoint: OutPoint { txid, vout: 0 },
+          outpoint: OutPoint {
+            txid: first,
+            vout: 0,
+          },
           offset: 0,
         },
         Some(50 * COIN_VALUE),
       );
 
-      let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 0, 0), (2, 1, 0)],
-        outputs: 2,
+      let second = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(2, 1, 0)],
+        witness: inscription("text/plain", "hello").to_witness(),
         ..Default::default()
       });
 
@@ -2038,23 +8794,31 @@ mod tests {
         inscription_id,
         SatPoint {
           outpoint: OutPoint {
-            txid: send_txid,
-            vout: 1,
+            txid: second,
+            vout: 0,
           },
           offset: 0,
         },
         Some(50 * COIN_VALUE),
       );
+
+      assert!(context
+        .index
+        .get_inscription_entry(second.into())
+        .unwrap()
+        .is_none());
+
+      assert!(context
+        .index
+        .get_inscription_by_id(second.into())
+        .unwrap()
+        .is_none());
     }
   }
 
   #[test]
-  fn missing_inputs_are_fetched_from_bitcoin_core() {
-    for args in [
-      ["--first-inscription-height", "2"].as_slice(),
-      ["--first-inscription-height", "2", "--index-sats"].as_slice(),
-    ] {
-      let context = Context::builder().args(args).build();
+  fn get_latest_inscriptions_with_no_prev_and_next() {
+    for context in Context::configurations() {
       context.mine_blocks(1);
 
       let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
@@ -2066,695 +8830,1510 @@ mod tests {
 
       context.mine_blocks(1);
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint { txid, vout: 0 },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
+      let (inscriptions, prev, next) = context
+        .index
+        .get_latest_inscriptions_with_prev_and_next(100, None)
+        .unwrap();
+      assert_eq!(inscriptions, &[inscription_id]);
+      assert_eq!(prev, None);
+      assert_eq!(next, None);
+    }
+  }
+
+  #[test]
+  fn get_latest_inscriptions_with_prev_and_next() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let mut ids = Vec::new();
+
+      for i in 0..103 {
+        let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+          inputs: &[(i + 1, 0, 0)],
+          witness: inscription("text/plain", "hello").to_witness(),
+          ..Default::default()
+        });
+        ids.push(InscriptionId::from(txid));
+        context.mine_blocks(1);
+      }
+
+      ids.reverse();
+
+      let (inscriptions, prev, next) = context
+        .index
+        .get_latest_inscriptions_with_prev_and_next(100, None)
+        .unwrap();
+      assert_eq!(inscriptions, &ids[..100]);
+      assert_eq!(prev, Some(2));
+      assert_eq!(next, None);
+
+      let (inscriptions, prev, next) = context
+        .index
+        .get_latest_inscriptions_with_prev_and_next(100, Some(101))
+        .unwrap();
+      assert_eq!(inscriptions, &ids[1..101]);
+      assert_eq!(prev, Some(1));
+      assert_eq!(next, Some(102));
+
+      let (inscriptions, prev, next) = context
+        .index
+        .get_latest_inscriptions_with_prev_and_next(100, Some(0))
+        .unwrap();
+      assert_eq!(inscriptions, &ids[102..103]);
+      assert_eq!(prev, None);
+      assert_eq!(next, Some(100));
+    }
+  }
+
+  #[test]
+  fn unsynced_index_fails() {
+    for context in Context::configurations() {
+      let mut entropy = [0; 16];
+      rand::thread_rng().fill_bytes(&mut entropy);
+      let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+      crate::subcommand::wallet::initialize_wallet(&context.options, mnemonic.to_seed("")).unwrap();
+      context.rpc_server.mine_blocks(1);
+      assert_regex_match!(
+        context
+          .index
+          .get_unspent_outputs(Wallet::load(&context.options).unwrap())
+          .unwrap_err()
+          .to_string(),
+        r"output in Bitcoin Core wallet but not in ord index: [[:xdigit:]]{64}:\d+"
       );
+    }
+  }
 
-      let send_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 0, 0), (2, 1, 0)],
-        ..Default::default()
-      });
+  #[test]
+  fn get_unspent_outputs_by_mempool_fails() {
+    for context in Context::configurations() {
+      let mut entropy = [0; 16];
+      rand::thread_rng().fill_bytes(&mut entropy);
+      let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+      crate::subcommand::wallet::initialize_wallet(&context.options, mnemonic.to_seed("")).unwrap();
+      context.rpc_server.mine_blocks(1);
+      let result = context
+        .index
+        .get_unspent_outputs_by_mempool(
+          "tb1phsaern0qpcpqpv2h6cmu6fgae4y0lyx2tqhmqmgvv7c9whffm3rqjmlrqs",
+          BTreeMap::new(),
+        )
+        .unwrap_err()
+        .to_string();
+      assert_regex_match!(
+        result,
+        r"output in Bitcoin Core wallet but not in ord index: [[:xdigit:]]{64}:\d+"
+      );
+    }
+  }
 
-      context.mine_blocks(1);
+  #[test]
+  fn prune_cutoff_keeps_reorg_depth() {
+    assert_eq!(MysqlDatabase::prune_cutoff(1000, 100), Some(900));
+    assert_eq!(
+      MysqlDatabase::prune_cutoff(1000, 0),
+      Some(1000 - MAX_REORG_DEPTH)
+    );
+    assert_eq!(MysqlDatabase::prune_cutoff(3, 0), None);
+  }
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: send_txid,
-            vout: 0,
-          },
-          offset: 50 * COIN_VALUE,
+  // needs a scratch mysql server, pruning runs against the whole table
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test unsettled_reveal_expectations_are_pruned -- --ignored
+  #[test]
+  #[ignore]
+  fn unsettled_reveal_expectations_are_pruned() {
+    let mysql = MysqlDatabase::new(
+      env::var("ORD_TEST_MYSQL_HOST").ok(),
+      env::var("ORD_TEST_MYSQL_USER").ok(),
+      env::var("ORD_TEST_MYSQL_PASS").ok(),
+      Network::Regtest,
+    )
+    .unwrap();
+
+    let table = mysql.get_reveal_expectation_table();
+    let txids = (1..=7).map(txid).collect::<Vec<Txid>>();
+    for txid in &txids {
+      mysql
+        .get_conn()
+        .unwrap()
+        .exec_drop(
+          format!("DELETE FROM {table} WHERE reveal_txid = :reveal_txid"),
+          params! { "reveal_txid" => txid.to_string() },
+        )
+        .unwrap();
+    }
+
+    let expectations = |ns: &[u64]| {
+      ns.iter()
+        .map(|n| RevealExpectation {
+          commit_txid: txid(*n),
+          reveal_txid: txid(*n),
+          reveal_hex: String::new(),
+          source: "test".into(),
+        })
+        .collect::<Vec<RevealExpectation>>()
+    };
+
+    mysql
+      .insert_reveal_expectations(expectations(&[1, 2, 3, 7]))
+      .unwrap();
+    mysql.expect_reveals(&[txid(2), txid(3)]).unwrap();
+    mysql.stamp_reveal_expectations(100).unwrap();
+    mysql.fulfill_reveal_expectations(&[txid(3)], 100).unwrap();
+
+    // 7 was built long ago but only broadcast now
+    mysql
+      .insert_reveal_expectations(expectations(&[4, 5, 6]))
+      .unwrap();
+    mysql.expect_reveals(&[txid(5), txid(6), txid(7)]).unwrap();
+    mysql.stamp_reveal_expectations(995).unwrap();
+    mysql.fulfill_reveal_expectations(&[txid(6)], 995).unwrap();
+
+    mysql
+      .prune(
+        1000,
+        &PruneOptions {
+          depth: 100,
+          batch_size: 2,
+          sleep: Duration::ZERO,
         },
-        Some(50 * COIN_VALUE),
-      );
+      )
+      .unwrap();
+
+    let states = txids
+      .iter()
+      .map(|txid| {
+        mysql
+          .get_conn()
+          .unwrap()
+          .exec_first(
+            format!("SELECT state FROM {table} WHERE reveal_txid = :reveal_txid"),
+            params! { "reveal_txid" => txid.to_string() },
+          )
+          .unwrap()
+      })
+      .collect::<Vec<Option<String>>>();
+
+    assert_eq!(
+      states,
+      [
+        None,
+        None,
+        None,
+        Some("built".into()),
+        Some("pending".into()),
+        Some("fulfilled".into()),
+        Some("pending".into()),
+      ]
+    );
+  }
+
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test built_reveals_are_expected -- --ignored
+  #[test]
+  #[ignore]
+  fn built_reveals_are_expected_without_a_broadcast() {
+    let mysql = MysqlDatabase::new(
+      env::var("ORD_TEST_MYSQL_HOST").ok(),
+      env::var("ORD_TEST_MYSQL_USER").ok(),
+      env::var("ORD_TEST_MYSQL_PASS").ok(),
+      Network::Regtest,
+    )
+    .unwrap();
+
+    let table = mysql.get_reveal_expectation_table();
+    let (built, broadcast) = (txid(8), txid(9));
+    for txid in [built, broadcast] {
+      mysql
+        .get_conn()
+        .unwrap()
+        .exec_drop(
+          format!("DELETE FROM {table} WHERE reveal_txid = :reveal_txid"),
+          params! { "reveal_txid" => txid.to_string() },
+        )
+        .unwrap();
+    }
+
+    mysql
+      .insert_reveal_expectations(
+        [built, broadcast]
+          .into_iter()
+          .map(|txid| RevealExpectation {
+            commit_txid: txid,
+            reveal_txid: txid,
+            reveal_hex: String::new(),
+            source: "test".into(),
+          })
+          .collect(),
+      )
+      .unwrap();
+    mysql.expect_reveals(&[broadcast]).unwrap();
+
+    // the client broadcast the first reveal itself
+    let expected = mysql.get_expected_reveal_txids().unwrap();
+    assert!(expected.contains(&built) && expected.contains(&broadcast));
+
+    mysql
+      .fulfill_reveal_expectations(&[built, broadcast], 100)
+      .unwrap();
+    let expected = mysql.get_expected_reveal_txids().unwrap();
+    assert!(!expected.contains(&built) && !expected.contains(&broadcast));
+  }
+
+  #[test]
+  fn genesis_rows_are_created_inscriptions() {
+    let tx_indices = [(txid(1), 4), (txid(2), 7)].into();
+    let data = [
+      MysqlInscription {
+        inscription_id: InscriptionId::from(txid(1)),
+        ..activity_item(1, txid(1), "alice", true)
+      },
+      MysqlInscription {
+        inscription_id: inscription_id(2),
+        ..activity_item(2, txid(2), "bob", false)
+      },
+      MysqlInscription {
+        inscription_id: InscriptionId::from(txid(3)),
+        ..activity_item(3, txid(3), "carol", true)
+      },
+    ];
+    let content_types = [(InscriptionId::from(txid(1)), Some("text/plain".to_owned()))].into();
+
+    assert_eq!(
+      InscriptionGenesis::rows(100, &tx_indices, &data, &content_types),
+      [InscriptionGenesis {
+        inscription_id: InscriptionId::from(txid(1)),
+        height: 100,
+        tx_index: 4,
+        input_index: 0,
+        content_type: Some("text/plain".into()),
+        address: "alice".into(),
+      }]
+    );
+  }
+
+  #[test]
+  fn content_hash_rows_skip_inscriptions_without_body() {
+    let genesis = [1, 2].map(|n| InscriptionGenesis {
+      inscription_id: inscription_id(n),
+      height: 100,
+      tx_index: n,
+      input_index: 0,
+      content_type: None,
+      address: "alice".into(),
+    });
+    let hashes = [(inscription_id(2), ContentHash::of(b"{}"))].into();
+
+    assert_eq!(
+      ContentHash::rows(&genesis, &hashes),
+      [ContentHash {
+        address: "alice".into(),
+        content_hash: ContentHash::of(b"{}"),
+        inscription_id: inscription_id(2),
+        height: Some(100),
+      }]
+    );
+  }
+
+  #[test]
+  fn mint_quota_rolls_over_at_utc_midnight() {
+    let before = Utc.with_ymd_and_hms(2023, 5, 1, 23, 59, 59).unwrap();
+    let after = Utc.with_ymd_and_hms(2023, 5, 2, 0, 0, 0).unwrap();
+
+    assert_eq!(
+      MintQuota::day(before),
+      NaiveDate::from_ymd_opt(2023, 5, 1).unwrap()
+    );
+    assert_eq!(
+      MintQuota::day(after),
+      NaiveDate::from_ymd_opt(2023, 5, 2).unwrap()
+    );
+    assert_eq!(MintQuota::reset_at(MintQuota::day(before)), after);
+    assert_eq!(
+      MintQuota::reset_at(MintQuota::day(after)),
+      Utc.with_ymd_and_hms(2023, 5, 3, 0, 0, 0).unwrap()
+    );
+  }
+
+  #[test]
+  fn mint_quota_day_ignores_local_offsets() {
+    let late = chrono::FixedOffset::east_opt(9 * 3600)
+      .unwrap()
+      .with_ymd_and_hms(2023, 5, 2, 8, 30, 0)
+      .unwrap()
+      .with_timezone(&Utc);
+    assert_eq!(
+      MintQuota::day(late),
+      NaiveDate::from_ymd_opt(2023, 5, 1).unwrap()
+    );
+  }
+
+  #[test]
+  fn mint_quota_rolls_over_across_months_and_years() {
+    assert_eq!(
+      MintQuota::reset_at(NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()),
+      Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+    );
+    assert_eq!(
+      MintQuota::reset_at(NaiveDate::from_ymd_opt(2024, 2, 28).unwrap()),
+      Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap()
+    );
+  }
+
+  #[test]
+  fn genesis_cursor_round_trips() {
+    let cursor = GenesisCursor {
+      height: 800_000,
+      tx_index: 12,
+      input_index: 0,
+    };
+    assert_eq!(cursor.to_string(), "800000:12:0");
+    assert_eq!("800000:12:0".parse::<GenesisCursor>().unwrap(), cursor);
+    assert!("800000:12".parse::<GenesisCursor>().is_err());
+    assert!(
+      GenesisCursor {
+        height: 800_000,
+        tx_index: 13,
+        input_index: 0,
+      } > cursor
+    );
+  }
+
+  #[test]
+  fn content_type_prefixes_are_matched_literally() {
+    assert_eq!(MysqlDatabase::escape_like("image/"), "image/");
+    assert_eq!(MysqlDatabase::escape_like("text/x_%\\"), "text/x\\_\\%\\\\");
+  }
+
+  fn activity_item(inscription: u32, txid: Txid, address: &str, created: bool) -> MysqlInscription {
+    MysqlInscription {
+      inscription_id: inscription_id(inscription),
+      new_satpoint: SatPoint {
+        outpoint: OutPoint { txid, vout: 0 },
+        offset: 0,
+      },
+      new_address: address.into(),
+      created,
     }
   }
 
   #[test]
-  fn one_input_fee_spent_inscriptions_are_tracked_correctly() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn activity_cursor_from_str() {
+    assert_eq!(
+      "800000:12:3".parse::<ActivityCursor>().unwrap(),
+      ActivityCursor {
+        height: 800000,
+        tx_index: 12,
+        seq: 3,
+      }
+    );
+    assert_eq!(
+      "800000:12".parse::<ActivityCursor>().unwrap(),
+      ActivityCursor {
+        height: 800000,
+        tx_index: 12,
+        seq: 0,
+      }
+    );
+    assert!("800000".parse::<ActivityCursor>().is_err());
+    assert!("800000:12:3:4".parse::<ActivityCursor>().is_err());
+    assert!("800000:-1".parse::<ActivityCursor>().is_err());
+  }
+
+  #[test]
+  fn activity_cursor_round_trips_and_orders() {
+    let cursor = ActivityCursor {
+      height: 10,
+      tx_index: 2,
+      seq: 1,
+    };
+    assert_eq!(
+      cursor.to_string().parse::<ActivityCursor>().unwrap(),
+      cursor
+    );
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
+    // a two part cursor sorts before every event of its transaction
+    assert!("10:2".parse::<ActivityCursor>().unwrap() < cursor);
+    assert!(
+      ActivityCursor {
+        height: 9,
+        tx_index: 100,
+        seq: 100,
+      } < "10:0".parse::<ActivityCursor>().unwrap()
+    );
+  }
 
-      context.mine_blocks(1);
+  #[test]
+  fn activity_events_for_creation_and_transfers() {
+    let tx_indices = [(txid(1), 1), (txid(2), 2)].into();
+    let data = [
+      activity_item(1, txid(1), "alice", true),
+      activity_item(1, txid(2), "bob", false),
+      activity_item(2, txid(2), "carol", false),
+    ];
+    let previous = [(inscription_id(2), "dave".to_owned())].into();
+
+    let events = MysqlDatabase::activity_events(100, &tx_indices, &data, &previous)
+      .into_iter()
+      .map(|event| {
+        (
+          event.address,
+          event.cursor.to_string(),
+          event.kind,
+          event.txid,
+          event.counterparty,
+        )
+      })
+      .collect::<Vec<_>>();
 
-      context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 1, 0)],
-        fee: 50 * COIN_VALUE,
-        ..Default::default()
-      });
+    assert_eq!(
+      events,
+      [
+        (
+          "alice".into(),
+          "100:1:0".into(),
+          ActivityKind::InscriptionCreated,
+          txid(1),
+          None
+        ),
+        (
+          "alice".into(),
+          "100:2:0".into(),
+          ActivityKind::InscriptionSent,
+          txid(2),
+          Some("bob".into())
+        ),
+        (
+          "bob".into(),
+          "100:2:1".into(),
+          ActivityKind::InscriptionReceived,
+          txid(2),
+          Some("alice".into())
+        ),
+        (
+          "dave".into(),
+          "100:2:2".into(),
+          ActivityKind::InscriptionSent,
+          txid(2),
+          Some("carol".into())
+        ),
+        (
+          "carol".into(),
+          "100:2:3".into(),
+          ActivityKind::InscriptionReceived,
+          txid(2),
+          Some("dave".into())
+        ),
+      ]
+    );
+  }
 
-      let coinbase_tx = context.mine_blocks(1)[0].txdata[0].txid();
+  #[test]
+  fn activity_events_without_addresses() {
+    let tx_indices = [(txid(1), 1)].into();
+
+    // unknown history is a receive without counterparty, a move to an
+    // unparseable script is a send without one, moves to self are dropped
+    let events = MysqlDatabase::activity_events(
+      5,
+      &tx_indices,
+      &[
+        activity_item(1, txid(1), "alice", false),
+        activity_item(2, txid(1), "", false),
+        activity_item(3, txid(1), "carol", false),
+        activity_item(4, txid(9), "erin", false),
+      ],
+      &[
+        (inscription_id(2), "bob".to_owned()),
+        (inscription_id(3), "carol".to_owned()),
+      ]
+      .into(),
+    );
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: coinbase_tx,
-            vout: 0,
-          },
-          offset: 50 * COIN_VALUE,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].kind, ActivityKind::InscriptionReceived);
+    assert_eq!(events[0].counterparty, None);
+    assert_eq!(events[1].address, "bob");
+    assert_eq!(events[1].kind, ActivityKind::InscriptionSent);
+    assert_eq!(events[1].counterparty, None);
   }
 
   #[test]
-  fn two_input_fee_spent_inscriptions_are_tracked_correctly() {
-    for context in Context::configurations() {
-      context.mine_blocks(2);
+  fn activity_page_next_cursor() {
+    let event = |height| Activity {
+      kind: ActivityKind::InscriptionReceived,
+      inscription_id: inscription_id(1),
+      txid: txid(1),
+      height,
+      tx_index: 0,
+      counterparty: None,
+      cursor: format!("{height}:0:1"),
+    };
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
+    assert_eq!(ActivityPage::new(vec![], 2).next, None);
+    assert_eq!(ActivityPage::new(vec![event(3)], 2).next, None);
+    assert_eq!(
+      ActivityPage::new(vec![event(3), event(2)], 2).next,
+      Some("2:0:1".into())
+    );
+  }
 
-      context.mine_blocks(1);
+  #[test]
+  fn voucher_policy() {
+    let fee = Amount::from_sat(3000);
+    assert_eq!(VoucherPolicy::Free.apply(fee), Amount::ZERO);
+    assert_eq!(
+      VoucherPolicy::Discount(25).apply(fee),
+      Amount::from_sat(2250)
+    );
 
-      context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 0, 0), (3, 1, 0)],
-        fee: 50 * COIN_VALUE,
-        ..Default::default()
-      });
+    assert_eq!(VoucherPolicy::Free.discount_percent().unwrap(), 100);
+    assert_eq!(VoucherPolicy::Discount(25).discount_percent().unwrap(), 25);
+    assert!(VoucherPolicy::Discount(0).discount_percent().is_err());
+    assert!(VoucherPolicy::Discount(100).discount_percent().is_err());
 
-      let coinbase_tx = context.mine_blocks(1)[0].txdata[0].txid();
+    assert_eq!(
+      VoucherPolicy::from_discount_percent(100),
+      VoucherPolicy::Free
+    );
+    assert_eq!(
+      VoucherPolicy::from_discount_percent(25),
+      VoucherPolicy::Discount(25)
+    );
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: coinbase_tx,
-            vout: 0,
-          },
-          offset: 50 * COIN_VALUE,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
+    assert_eq!(
+      serde_json::from_str::<VoucherPolicy>(r#""free""#).unwrap(),
+      VoucherPolicy::Free
+    );
+    assert_eq!(
+      serde_json::from_str::<VoucherPolicy>(r#"{"discount":50}"#).unwrap(),
+      VoucherPolicy::Discount(50)
+    );
   }
 
   #[test]
-  fn inscription_can_be_fee_spent_in_first_transaction() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
-
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        fee: 50 * COIN_VALUE,
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
+  fn voucher_checks() {
+    assert!(MysqlDatabase::check_voucher("a", false, None, None).is_ok());
+    assert_eq!(
+      MysqlDatabase::check_voucher("a", true, None, None)
+        .unwrap_err()
+        .to_string(),
+      "voucher `a` has expired"
+    );
+    assert_eq!(
+      MysqlDatabase::check_voucher("a", false, Some("commit"), None)
+        .unwrap_err()
+        .to_string(),
+      "voucher `a` has already been redeemed"
+    );
+    assert!(MysqlDatabase::check_voucher("a", false, Some("commit"), Some("other")).is_err());
+  }
 
-      let coinbase_tx = context.mine_blocks(1)[0].txdata[0].txid();
+  #[test]
+  fn blocklist_values_are_normalized() {
+    assert_eq!(
+      BlocklistKind::Inscription
+        .normalize(&inscription_id(1).to_string())
+        .unwrap(),
+      inscription_id(1).to_string()
+    );
+    assert!(BlocklistKind::Inscription.normalize("foo").is_err());
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: coinbase_tx,
-            vout: 0,
-          },
-          offset: 50 * COIN_VALUE,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
+    let hash = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+    assert_eq!(
+      BlocklistKind::ContentHash.normalize(hash).unwrap(),
+      hash.to_lowercase()
+    );
+    assert!(BlocklistKind::ContentHash.normalize("e3b0").is_err());
+    assert!(BlocklistKind::ContentHash
+      .normalize(&"z".repeat(64))
+      .is_err());
   }
 
   #[test]
-  fn lost_inscriptions() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn confirmation_stats() {
+    let entry = |method: &str, fee_rate, broadcast_height, confirmed_height| JournalEntry {
+      method: method.into(),
+      fee_rate,
+      broadcast_height,
+      confirmed_height,
+    };
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        fee: 50 * COIN_VALUE,
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
+    assert_eq!(
+      ConfirmationStat::aggregate(
+        vec![
+          entry("mint", 3.0, 100, Some(101)),
+          entry("mint", 2.0, 100, Some(103)),
+          entry("mint", 4.9, 100, Some(110)),
+          entry("mint", 3.0, 100, None),
+          entry("transfer", 250.0, 112, None),
+        ],
+        115,
+      ),
+      [
+        ConfirmationStat {
+          method: "mint".into(),
+          fee_rate_bucket: "2-5".into(),
+          broadcast: 4,
+          confirmed: 3,
+          p50_blocks: Some(3),
+          p90_blocks: Some(10),
+          unconfirmed_after_6_percent: Some(50.0),
+        },
+        ConfirmationStat {
+          method: "transfer".into(),
+          fee_rate_bucket: "200+".into(),
+          broadcast: 1,
+          confirmed: 0,
+          p50_blocks: None,
+          p90_blocks: None,
+          unconfirmed_after_6_percent: None,
+        },
+      ]
+    );
 
-      context.mine_blocks_with_subsidy(1, 0);
+    assert_eq!(
+      ConfirmationStat::bucket_label(ConfirmationStat::bucket(0.5)),
+      "0-1"
+    );
+  }
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint::null(),
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
+  fn test_mysql() -> MysqlDatabase {
+    MysqlDatabase::new(
+      env::var("ORD_TEST_MYSQL_HOST").ok(),
+      env::var("ORD_TEST_MYSQL_USER").ok(),
+      env::var("ORD_TEST_MYSQL_PASS").ok(),
+      Network::Regtest,
+    )
+    .unwrap()
   }
 
+  // needs a scratch mysql server nothing else audits to while it runs
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test audit_sequence_has_no_gaps -- --ignored
   #[test]
-  fn multiple_inscriptions_can_be_lost() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
-
-      let first_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        fee: 50 * COIN_VALUE,
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let first_inscription_id = InscriptionId::from(first_txid);
-
-      context.mine_blocks_with_subsidy(1, 0);
-      context.mine_blocks(1);
+  #[ignore]
+  fn audit_sequence_has_no_gaps_under_concurrent_failures() {
+    let mysql = test_mysql();
+    let start = mysql.get_audit_sequence().unwrap();
+
+    // every third audit fails after taking its number and rolls back
+    let committed = thread::scope(|scope| {
+      (0..8)
+        .map(|thread| {
+          let mysql = &mysql;
+          scope.spawn(move || {
+            let mut committed = 0;
+            for i in 0..12 {
+              let mut conn = mysql.get_conn().unwrap();
+              let mut tx = conn.start_transaction(TxOpts::default()).unwrap();
+              tx.exec_drop(
+                format!(
+                  "INSERT INTO {} (endpoint, source, is_unsafe, reveal_weight)
+                   VALUES ('test', :source, false, 0)",
+                  mysql.get_build_audit_table()
+                ),
+                params! { "source" => format!("{thread}-{i}") },
+              )
+              .unwrap();
+              mysql
+                .sequence_audit(&mut tx, &mysql.get_build_audit_table(), None)
+                .unwrap();
+              if i % 3 == 0 {
+                tx.rollback().unwrap();
+              } else {
+                tx.commit().unwrap();
+                committed += 1;
+              }
+            }
+            committed
+          })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .sum::<u64>()
+    });
 
-      let second_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(3, 0, 0)],
-        fee: 50 * COIN_VALUE,
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let second_inscription_id = InscriptionId::from(second_txid);
+    let seqs: Vec<u64> = mysql
+      .get_conn()
+      .unwrap()
+      .exec(
+        format!(
+          "SELECT seq FROM {} WHERE seq > :start ORDER BY seq",
+          mysql.get_audit_sequence_table()
+        ),
+        params! { "start" => start },
+      )
+      .unwrap();
 
-      context.mine_blocks_with_subsidy(1, 0);
+    assert_eq!(seqs, (start + 1..=start + committed).collect::<Vec<u64>>());
+    assert_eq!(mysql.get_audit_sequence().unwrap(), start + committed);
+  }
 
-      context.index.assert_inscription_location(
-        first_inscription_id,
-        SatPoint {
-          outpoint: OutPoint::null(),
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test replayed_audits -- --ignored
+  #[test]
+  #[ignore]
+  fn replayed_audits_keep_their_sequence_number() {
+    let mysql = test_mysql();
+    let audit = |key: &str| {
+      mysql
+        .insert_build_audit("test", 1, "source", None, false, 0, Some(key))
+        .unwrap()
+    };
 
-      context.index.assert_inscription_location(
-        second_inscription_id,
-        SatPoint {
-          outpoint: OutPoint::null(),
-          offset: 50 * COIN_VALUE,
-        },
-        Some(150 * COIN_VALUE),
-      );
-    }
+    let key = format!("{:016x}", rand::random::<u64>());
+    let Audited::New(seq) = audit(&key) else {
+      panic!("first audit of a key is new");
+    };
+    assert_eq!(audit(&key), Audited::Replay(seq));
+    assert_eq!(mysql.get_replayed_sequence(&key).unwrap(), Some(seq));
+
+    // concurrent retries of one request share a single number
+    let key = format!("{:016x}", rand::random::<u64>());
+    let audited = thread::scope(|scope| {
+      (0..4)
+        .map(|_| scope.spawn(|| audit(&key)))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<Audited>>()
+    });
+    let new = audited
+      .iter()
+      .filter_map(|audited| match audited {
+        Audited::New(seq) => Some(*seq),
+        Audited::Replay(_) => None,
+      })
+      .collect::<Vec<u64>>();
+    assert_eq!(new.len(), 1);
+    assert!(audited.iter().all(
+      |audited| matches!(audited, Audited::New(seq) | Audited::Replay(seq) if *seq == new[0])
+    ));
   }
 
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test add_column -- --ignored
   #[test]
-  fn lost_sats_are_tracked_correctly() {
-    let context = Context::builder().arg("--index-sats").build();
-    assert_eq!(context.index.statistic(Statistic::LostSats), 0);
+  #[ignore]
+  fn add_column_migrates_tables_once() {
+    let mysql = test_mysql();
+    let table = format!("MIGRATION_{:016x}", rand::random::<u64>());
+    let mut conn = mysql.get_conn().unwrap();
+    conn
+      .query_drop(format!(
+        "CREATE TABLE {table} (id BIGINT UNSIGNED PRIMARY KEY)"
+      ))
+      .unwrap();
+    conn
+      .query_drop(format!("INSERT INTO {table} (id) VALUES (1)"))
+      .unwrap();
 
-    context.mine_blocks(1);
-    assert_eq!(context.index.statistic(Statistic::LostSats), 0);
+    for _ in 0..2 {
+      MysqlDatabase::add_column(&mut conn, &table, "api_version", "INT UNSIGNED NULL").unwrap();
+    }
 
-    context.mine_blocks_with_subsidy(1, 0);
-    assert_eq!(
-      context.index.statistic(Statistic::LostSats),
-      50 * COIN_VALUE
-    );
+    let api_version: Option<Option<u32>> = conn
+      .query_first(format!("SELECT api_version FROM {table} WHERE id = 1"))
+      .unwrap();
+    conn.query_drop(format!("DROP TABLE {table}")).unwrap();
+    assert_eq!(api_version, Some(None));
+  }
 
-    context.mine_blocks_with_subsidy(1, 0);
-    assert_eq!(
-      context.index.statistic(Statistic::LostSats),
-      100 * COIN_VALUE
-    );
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test api_key_sources -- --ignored
+  #[test]
+  #[ignore]
+  fn api_key_sources_stay_under_the_cap_under_concurrent_requests() {
+    let mysql = test_mysql();
+    let api_key = format!("{:016x}", rand::random::<u64>());
+    let day = Utc::now().date_naive();
+
+    // each thread asks for its own source twice, only the first three
+    // sources to get the key's counter row are taken
+    let taken = thread::scope(|scope| {
+      (0..8)
+        .map(|thread| {
+          let (mysql, api_key) = (&mysql, &api_key);
+          scope.spawn(move || {
+            let source = format!("source-{thread}");
+            (0..2)
+              .map(|_| {
+                mysql
+                  .use_api_key_source(api_key, &source, day, Some(3))
+                  .unwrap()
+              })
+              .collect::<Vec<bool>>()
+          })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<Vec<bool>>>()
+    });
 
-    context.mine_blocks(1);
+    assert_eq!(taken.iter().filter(|taken| taken[0]).count(), 3);
+    assert!(taken.iter().all(|taken| taken[0] == taken[1]));
+    assert_eq!(mysql.get_api_key_sources(&api_key, day).unwrap().len(), 3);
     assert_eq!(
-      context.index.statistic(Statistic::LostSats),
-      100 * COIN_VALUE
+      mysql
+        .get_api_key_usage(day)
+        .unwrap()
+        .into_iter()
+        .find(|usage| usage.api_key == api_key),
+      Some(ApiKeyUsage {
+        api_key: api_key.clone(),
+        day,
+        distinct_sources: 3,
+        requests: 6,
+        violations: 0,
+      })
     );
   }
 
   #[test]
-  fn lost_sat_ranges_are_tracked_correctly() {
-    let context = Context::builder().arg("--index-sats").build();
+  fn content_type_counts() {
+    let mut counts = BTreeMap::new();
+    ContentTypeCount::record(&mut counts, &inscription("text/plain", "hello"));
+    ContentTypeCount::record(&mut counts, &inscription("text/plain", "hi"));
+    ContentTypeCount::record(&mut counts, &inscription("image/png", [1; 100]));
+    ContentTypeCount::record(&mut counts, &Inscription::new(None, Some(vec![1, 2, 3])));
+    ContentTypeCount::record(&mut counts, &Inscription::new(Some(vec![b'a'; 300]), None));
 
-    let null_ranges = || match context.index.list(OutPoint::null()).unwrap().unwrap() {
-      List::Unspent(ranges) => ranges,
-      _ => panic!(),
-    };
+    assert_eq!(
+      counts,
+      [
+        (
+          "".into(),
+          ContentTypeCount {
+            count: 1,
+            total_bytes: 3,
+          }
+        ),
+        (
+          "a".repeat(255),
+          ContentTypeCount {
+            count: 1,
+            total_bytes: 0,
+          }
+        ),
+        (
+          "image/png".into(),
+          ContentTypeCount {
+            count: 1,
+            total_bytes: 100,
+          }
+        ),
+        (
+          "text/plain".into(),
+          ContentTypeCount {
+            count: 2,
+            total_bytes: 7,
+          }
+        ),
+      ]
+      .into()
+    );
+  }
 
-    assert!(null_ranges().is_empty());
+  #[test]
+  fn snapshot_import_continues_indexing() {
+    let context = Context::builder().arg("--index-sats").build();
+    context.mine_blocks(1);
 
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      witness: inscription("text/plain", "hello").to_witness(),
+      ..Default::default()
+    });
+    let inscription_id = InscriptionId::from(txid);
     context.mine_blocks(1);
 
-    assert!(null_ranges().is_empty());
+    let snapshot = context.tempdir.path().join("snapshot");
+    assert_eq!(context.index.export_snapshot(&snapshot).unwrap(), 3);
 
-    context.mine_blocks_with_subsidy(1, 0);
+    let data_dir = TempDir::new().unwrap();
+    let options = Options {
+      data_dir: Some(data_dir.path().into()),
+      ..context.options.clone()
+    };
 
-    assert_eq!(null_ranges(), [(100 * COIN_VALUE, 150 * COIN_VALUE)]);
+    assert_eq!(Index::import_snapshot(&options, &snapshot).unwrap(), 3);
+    assert!(!data_dir
+      .path()
+      .join("regtest")
+      .join("index.redb.partial")
+      .exists());
 
-    context.mine_blocks_with_subsidy(1, 0);
+    let blocks = context.mine_blocks(2);
+
+    let index = Index::open(&options).unwrap();
+    index.update().unwrap();
 
     assert_eq!(
-      null_ranges(),
-      [
-        (100 * COIN_VALUE, 150 * COIN_VALUE),
-        (150 * COIN_VALUE, 200 * COIN_VALUE)
-      ]
+      index
+        .database
+        .begin_read()
+        .unwrap()
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .get(&Statistic::SnapshotHeight.key())
+        .unwrap()
+        .map(|x| x.value()),
+      Some(3),
     );
 
-    context.mine_blocks(1);
-
+    assert_eq!(index.block_count().unwrap(), 5);
     assert_eq!(
-      null_ranges(),
-      [
-        (100 * COIN_VALUE, 150 * COIN_VALUE),
-        (150 * COIN_VALUE, 200 * COIN_VALUE)
-      ]
+      index.block_count().unwrap(),
+      context.index.block_count().unwrap()
     );
 
-    context.mine_blocks_with_subsidy(1, 0);
+    assert_eq!(
+      index
+        .get_inscription_satpoint_by_id(inscription_id)
+        .unwrap(),
+      context
+        .index
+        .get_inscription_satpoint_by_id(inscription_id)
+        .unwrap(),
+    );
 
+    let outpoint = OutPoint {
+      txid: blocks[1].txdata[0].txid(),
+      vout: 0,
+    };
+    assert!(index.list(outpoint).unwrap().is_some());
     assert_eq!(
-      null_ranges(),
-      [
-        (100 * COIN_VALUE, 150 * COIN_VALUE),
-        (150 * COIN_VALUE, 200 * COIN_VALUE),
-        (250 * COIN_VALUE, 300 * COIN_VALUE)
-      ]
+      index.list(outpoint).unwrap(),
+      context.index.list(outpoint).unwrap()
     );
   }
 
   #[test]
-  fn lost_inscriptions_get_lost_satpoints() {
-    for context in Context::configurations() {
-      context.mine_blocks_with_subsidy(1, 0);
-      context.mine_blocks(1);
+  fn snapshot_import_refuses_other_chain() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 0, 0)],
-        outputs: 2,
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
-      context.mine_blocks(1);
+    let snapshot = context.tempdir.path().join("snapshot");
+    context.index.export_snapshot(&snapshot).unwrap();
 
-      context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(3, 1, 1), (3, 1, 0)],
-        fee: 50 * COIN_VALUE,
-        ..Default::default()
-      });
-      context.mine_blocks_with_subsidy(1, 0);
+    let data_dir = TempDir::new().unwrap();
+    let options = Options {
+      data_dir: Some(data_dir.path().into()),
+      regtest: false,
+      signet: true,
+      ..context.options.clone()
+    };
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint::null(),
-          offset: 75 * COIN_VALUE,
-        },
-        Some(100 * COIN_VALUE),
-      );
-    }
+    assert_eq!(
+      Index::import_snapshot(&options, &snapshot)
+        .err()
+        .unwrap()
+        .to_string(),
+      "snapshot was taken on regtest, refusing to import it for signet",
+    );
+    assert!(!data_dir.path().join("signet").exists());
   }
 
   #[test]
-  fn inscription_skips_zero_value_first_output_of_inscribe_transaction() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
-
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        outputs: 2,
-        witness: inscription("text/plain", "hello").to_witness(),
-        output_values: &[0, 50 * COIN_VALUE],
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
-      context.mine_blocks(1);
+  fn snapshot_import_verifies_checksum_first() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint { txid, vout: 1 },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
-  }
+    let snapshot = context.tempdir.path().join("snapshot");
+    context.index.export_snapshot(&snapshot).unwrap();
 
-  #[test]
-  fn inscription_can_be_lost_in_first_transaction() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+    let mut bytes = fs::read(&snapshot).unwrap();
+    let last = bytes.len() - 40;
+    bytes[last] ^= 1;
+    fs::write(&snapshot, bytes).unwrap();
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        fee: 50 * COIN_VALUE,
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
-      context.mine_blocks_with_subsidy(1, 0);
+    let data_dir = TempDir::new().unwrap();
+    let options = Options {
+      data_dir: Some(data_dir.path().into()),
+      ..context.options.clone()
+    };
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint::null(),
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
-    }
+    assert_eq!(
+      Index::import_snapshot(&options, &snapshot)
+        .err()
+        .unwrap()
+        .to_string(),
+      "snapshot checksum mismatch",
+    );
+    assert!(!data_dir.path().join("regtest").exists());
   }
 
   #[test]
-  fn lost_rare_sats_are_tracked() {
-    let context = Context::builder().arg("--index-sats").build();
-    context.mine_blocks_with_subsidy(1, 0);
-    context.mine_blocks_with_subsidy(1, 0);
+  fn snapshot_import_refuses_existing_index() {
+    let context = Context::builder().build();
 
-    assert_eq!(
-      context
-        .index
-        .rare_sat_satpoint(Sat(50 * COIN_VALUE))
-        .unwrap()
-        .unwrap(),
-      SatPoint {
-        outpoint: OutPoint::null(),
-        offset: 0,
-      },
-    );
+    let snapshot = context.tempdir.path().join("snapshot");
+    context.index.export_snapshot(&snapshot).unwrap();
+
+    let index = context.tempdir.path().join("regtest").join("index.redb");
 
     assert_eq!(
-      context
-        .index
-        .rare_sat_satpoint(Sat(100 * COIN_VALUE))
+      Index::import_snapshot(&context.options, &snapshot)
+        .err()
         .unwrap()
-        .unwrap(),
-      SatPoint {
-        outpoint: OutPoint::null(),
-        offset: 50 * COIN_VALUE,
-      },
+        .to_string(),
+      format!(
+        "index at `{}` already exists, snapshots are only imported into a fresh data dir",
+        index.display()
+      ),
     );
   }
 
   #[test]
-  fn old_schema_gives_correct_error() {
-    let tempdir = {
-      let context = Context::builder().build();
-
-      let wtx = context.index.database.begin_write().unwrap();
+  fn tip_cache_follows_reorgs() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
 
-      wtx
-        .open_table(STATISTIC_TO_COUNT)
+    let tip = tip_cache::Tip::new(Duration::ZERO);
+    let cache = TipCache::new("reorg_test", Duration::from_secs(60));
+    let query = || {
+      cache
+        .get(
+          tip
+            .get(|| Ok(context.index.client.get_best_block_hash()?))
+            .unwrap(),
+          (),
+          || Ok(context.index.client.get_block_hash(1)?),
+        )
         .unwrap()
-        .insert(&Statistic::Schema.key(), &0)
-        .unwrap();
-
-      wtx.commit().unwrap();
-
-      context.tempdir
     };
 
-    let path = tempdir.path().to_owned();
+    let before = query();
+    assert_eq!(query(), before);
 
-    let delimiter = if cfg!(windows) { '\\' } else { '/' };
+    context.rpc_server.invalidate_tip();
+    context.rpc_server.mine_blocks(1);
 
-    assert_eq!(
-      Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
-      format!("index at `{}{delimiter}regtest{delimiter}index.redb` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema 0, ord schema {SCHEMA_VERSION}", path.display()));
+    let after = query();
+    assert_ne!(after, before);
+    assert_eq!(after, context.index.client.get_block_hash(1).unwrap());
+    assert_eq!(cache_invalidation_counts()["reorg_test"], 1);
   }
 
   #[test]
-  fn new_schema_gives_correct_error() {
-    let tempdir = {
-      let context = Context::builder().build();
+  fn tip_cached_node_lookups_do_not_wait_on_themselves() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
 
-      let wtx = context.index.database.begin_write().unwrap();
+    // the test node answers neither call, what matters is that both return
+    assert!(context.index.fast_fee_rate().is_err());
+    assert!(context.index.mempool_min_fee_rate().is_err());
+  }
 
-      wtx
-        .open_table(STATISTIC_TO_COUNT)
-        .unwrap()
-        .insert(&Statistic::Schema.key(), &u64::MAX)
-        .unwrap();
+  #[test]
+  fn unknown_envelope_fields_are_indexed_and_reported() {
+    use bitcoin::blockdata::{opcodes, script};
 
-      wtx.commit().unwrap();
+    let context = Context::builder().build();
+    context.mine_blocks(1);
 
-      context.tempdir
-    };
+    let script = script::Builder::new()
+      .push_opcode(opcodes::OP_FALSE)
+      .push_opcode(opcodes::all::OP_IF)
+      .push_slice(b"ord")
+      .push_slice(&[1])
+      .push_slice(b"text/plain;charset=utf-8")
+      .push_slice(&[10])
+      .push_slice(b"future")
+      .push_slice(&[13])
+      .push_slice(b"field")
+      .push_slice(&[])
+      .push_slice(b"hello")
+      .push_opcode(opcodes::all::OP_ENDIF)
+      .into_script();
+
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      witness: bitcoin::Witness::from_vec(vec![script.into_bytes(), Vec::new()]),
+      ..Default::default()
+    });
+    let inscription_id = InscriptionId::from(txid);
+    context.mine_blocks(1);
 
-    let path = tempdir.path().to_owned();
+    assert_eq!(
+      context.index.get_inscription_by_id(inscription_id).unwrap(),
+      Some(inscription("text/plain;charset=utf-8", "hello"))
+    );
 
-    let delimiter = if cfg!(windows) { '\\' } else { '/' };
+    context.mine_blocks(1);
 
+    assert_eq!(context.index.block_count().unwrap(), 4);
     assert_eq!(
-      Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
-      format!("index at `{}{delimiter}regtest{delimiter}index.redb` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {}, ord schema {SCHEMA_VERSION}", path.display(), u64::MAX));
+      context.index.partially_parsed_inscriptions(10).unwrap(),
+      (1, vec![(2, 1)])
+    );
   }
 
   #[test]
-  fn inscriptions_on_output() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn holdings_are_reconstructed_at_historical_heights() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
+    let genesis_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      witness: inscription("text/plain", "hello").to_witness(),
+      ..Default::default()
+    });
+    let inscription_id = InscriptionId::from(genesis_txid);
+    context.mine_blocks(1);
 
-      let inscription_id = InscriptionId::from(txid);
+    let transfer_txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 1, 0)],
+      fee: 0,
+      ..Default::default()
+    });
+    context.mine_blocks(1);
 
-      assert_eq!(
-        context
-          .index
-          .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
-          .unwrap(),
-        []
-      );
+    let genesis = SatPoint {
+      outpoint: OutPoint {
+        txid: genesis_txid,
+        vout: 0,
+      },
+      offset: 0,
+    };
+    let transferred = SatPoint {
+      outpoint: OutPoint {
+        txid: transfer_txid,
+        vout: 0,
+      },
+      offset: 0,
+    };
+    context
+      .index
+      .assert_inscription_location(inscription_id, transferred, None);
 
-      context.mine_blocks(1);
+    let history = [
+      SatpointChange {
+        inscription_id,
+        height: 2,
+        satpoint: genesis,
+        address: "alice".into(),
+      },
+      SatpointChange {
+        inscription_id,
+        height: 3,
+        satpoint: transferred,
+        address: "bob".into(),
+      },
+    ];
 
-      assert_eq!(
-        context
-          .index
-          .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
-          .unwrap(),
-        [inscription_id]
-      );
+    let row = |satpoint, last_transfer_height| ExportRow {
+      id: inscription_id,
+      number: 0,
+      satpoint,
+      value: Some(50 * COIN_VALUE),
+      content_type: Some("text/plain".into()),
+      genesis_height: 2,
+      last_transfer_height: Some(last_transfer_height),
+    };
+
+    assert_eq!(
+      context
+        .index
+        .export_rows(&Holding::at_height("alice", 2, &history))
+        .unwrap(),
+      [row(genesis, 2)]
+    );
+    assert_eq!(
+      context
+        .index
+        .export_rows(&Holding::at_height("bob", 3, &history))
+        .unwrap(),
+      [row(transferred, 3)]
+    );
+    assert!(Holding::at_height("alice", 3, &history).is_empty());
+    assert!(Holding::at_height("bob", 2, &history).is_empty());
+  }
 
-      let send_id = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 1, 0)],
-        ..Default::default()
-      });
+  #[test]
+  fn holdings_follow_the_latest_change_in_any_order() {
+    let change = |height, address: &str| SatpointChange {
+      inscription_id: inscription_id(1),
+      height,
+      satpoint: satpoint(height, 0),
+      address: address.into(),
+    };
+    let history = [change(7, "alice"), change(3, "alice"), change(5, "bob")];
 
-      context.mine_blocks(1);
+    assert_eq!(
+      Holding::at_height("alice", 4, &history),
+      [Holding {
+        inscription_id: inscription_id(1),
+        satpoint: satpoint(3, 0),
+        last_transfer_height: Some(3),
+      }]
+    );
+    assert!(Holding::at_height("alice", 6, &history).is_empty());
+    assert_eq!(
+      Holding::at_height("alice", 7, &history)[0].satpoint,
+      satpoint(7, 0)
+    );
+    assert!(Holding::at_height("alice", 2, &history).is_empty());
+  }
 
-      assert_eq!(
-        context
-          .index
-          .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
-          .unwrap(),
-        []
-      );
+  #[test]
+  fn export_csv_quotes_content_types_that_would_split() {
+    let row = ExportRow {
+      id: inscription_id(1),
+      number: 4,
+      satpoint: satpoint(1, 0),
+      value: Some(10_000),
+      content_type: Some("text/plain;charset=utf-8".into()),
+      genesis_height: 2,
+      last_transfer_height: None,
+    };
+    assert_eq!(
+      row.to_csv(),
+      format!(
+        "{},4,{},10000,text/plain;charset=utf-8,2,",
+        inscription_id(1),
+        satpoint(1, 0)
+      )
+    );
 
-      assert_eq!(
-        context
-          .index
-          .get_inscriptions_on_output(OutPoint {
-            txid: send_id,
-            vout: 0,
-          })
-          .unwrap(),
-        [inscription_id]
-      );
-    }
+    let row = ExportRow {
+      content_type: Some("a,\"b\"".into()),
+      value: None,
+      ..row
+    };
+    assert!(row.to_csv().contains(",,\"a,\"\"b\"\"\",2,"));
   }
 
   #[test]
-  fn inscriptions_on_same_sat_after_the_first_are_ignored() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn revenue_totals_include_idle_service_addresses() {
+    let total = |address: Option<&str>, service_fee| RevenueTotal {
+      service_address: address.map(str::to_owned),
+      outputs: u64::from(service_fee > 0),
+      service_fee,
+      ..Default::default()
+    };
 
-      let first = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
+    assert_eq!(
+      RevenueTotal::with_addresses(
+        vec![total(Some("b"), 3000), total(None, 0)],
+        &["a".into(), "b".into()],
+      ),
+      [total(None, 0), total(Some("a"), 0), total(Some("b"), 3000)]
+    );
+  }
 
-      context.mine_blocks(1);
+  #[test]
+  fn revenue_rows_locate_the_service_output() {
+    let inscribed = builder::inscribe(
+      builder::CommitParams {
+        input_type: bitcoin::AddressType::P2wpkh,
+        network: Network::Testnet,
+        satpoints: Vec::new(),
+        inscriptions: BTreeMap::new(),
+        utxos: [(outpoint(1), Amount::from_sat(100_000))].into(),
+        change: [change(0), change(1)],
+        destination: recipient(),
+        groups: Vec::new(),
+        commit_fee_rate: FeeRate::try_from(1.0).unwrap(),
+        reveal_fee_rate: FeeRate::try_from(1.0).unwrap(),
+        max_nonstandard_weight: None,
+        service_address: change(2),
+        service_fee: Amount::from_sat(1000),
+        additional_service_fee: Amount::ZERO,
+        target_postage: Amount::from_sat(546),
+        recipients: Vec::new(),
+        parent: None,
+        key_source: builder::KeySource::Random,
+      },
+      vec![inscription("text/plain", "foo"); 2],
+    )
+    .unwrap();
+
+    let rows = Revenue::rows(
+      &inscribed.commit,
+      &inscribed.reveals,
+      &inscribed.breakdown,
+      &change(0),
+      Some("launchpad"),
+      &change(2),
+    );
 
-      let inscription_id = InscriptionId::from(first);
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].service_output, Some((change(2).to_string(), 1)));
+    assert_eq!(rows[0].breakdown.service_fee, 2000);
+    assert_eq!(rows[1].service_output, None);
+    for (i, row) in rows.iter().enumerate() {
+      assert_eq!(row.commit_vout, u32::try_from(i).unwrap());
+      assert_eq!(row.reveal_txid, inscribed.reveals[i].txid());
+      assert_eq!(row.purpose.as_deref(), Some("launchpad"));
+    }
+  }
 
-      assert_eq!(
-        context
-          .index
-          .get_inscriptions_on_output(OutPoint {
-            txid: first,
-            vout: 0,
-          })
-          .unwrap(),
-        [inscription_id]
-      );
+  #[test]
+  fn purpose_filter_from_query() {
+    assert_eq!(PurposeFilter::from_query(None), PurposeFilter::Any);
+    assert_eq!(
+      PurposeFilter::from_query(Some("".into())),
+      PurposeFilter::Untagged
+    );
+    assert_eq!(
+      PurposeFilter::from_query(Some("treasury".into())),
+      PurposeFilter::Tagged("treasury".into())
+    );
+  }
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: first,
-            vout: 0,
-          },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
-      );
+  #[test]
+  fn purpose_filter_sql() {
+    assert_eq!(PurposeFilter::Any.condition(), "TRUE");
+    assert_eq!(PurposeFilter::Any.params(Vec::new()), mysql::Params::Empty);
+    assert_eq!(
+      PurposeFilter::Any.params(vec![("from".into(), mysql::Value::from(1u64))]),
+      mysql::Params::from(vec![("from".to_owned(), mysql::Value::from(1u64))])
+    );
 
-      let second = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(2, 1, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
+    assert_eq!(PurposeFilter::Untagged.condition(), "purpose IS NULL");
+    assert_eq!(
+      PurposeFilter::Untagged.params(Vec::new()),
+      mysql::Params::Empty
+    );
 
-      context.mine_blocks(1);
+    let tagged = PurposeFilter::Tagged("treasury".into());
+    assert_eq!(tagged.condition(), "purpose = :purpose");
+    assert_eq!(
+      tagged.params(Vec::new()),
+      mysql::Params::from(vec![(
+        "purpose".to_owned(),
+        mysql::Value::from("treasury".to_owned())
+      )])
+    );
+  }
 
-      context.index.assert_inscription_location(
-        inscription_id,
-        SatPoint {
-          outpoint: OutPoint {
-            txid: second,
-            vout: 0,
-          },
-          offset: 0,
-        },
-        Some(50 * COIN_VALUE),
+  #[test]
+  fn reveal_expectation_state_from_str() {
+    for state in [
+      RevealExpectationState::Built,
+      RevealExpectationState::Pending,
+      RevealExpectationState::Fulfilled,
+      RevealExpectationState::Superseded,
+      RevealExpectationState::Expired,
+    ] {
+      assert_eq!(
+        state.as_str().parse::<RevealExpectationState>().unwrap(),
+        state
       );
-
-      assert!(context
-        .index
-        .get_inscription_entry(second.into())
-        .unwrap()
-        .is_none());
-
-      assert!(context
-        .index
-        .get_inscription_by_id(second.into())
-        .unwrap()
-        .is_none());
     }
+    assert!("settled".parse::<RevealExpectationState>().is_err());
   }
 
   #[test]
-  fn get_latest_inscriptions_with_no_prev_and_next() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn reconcile_reveal_expectation() {
+    use RevealExpectationState::*;
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0)],
-        witness: inscription("text/plain", "hello").to_witness(),
-        ..Default::default()
-      });
-      let inscription_id = InscriptionId::from(txid);
+    assert_eq!(
+      RevealExpectationState::reconcile(Some(90), CommitOutput::SpentByReveal, 80, 100, 144),
+      Some((Fulfilled, 90))
+    );
+    assert_eq!(
+      RevealExpectationState::reconcile(None, CommitOutput::SpentByReveal, 80, 100, 144),
+      None
+    );
+    assert_eq!(
+      RevealExpectationState::reconcile(None, CommitOutput::Unspent, 0, 1000, 144),
+      None
+    );
+    assert_eq!(
+      RevealExpectationState::reconcile(None, CommitOutput::SpentElsewhere, 80, 100, 144),
+      Some((Superseded, 100))
+    );
+    assert_eq!(
+      RevealExpectationState::reconcile(None, CommitOutput::Unconfirmed, 80, 223, 144),
+      None
+    );
+    assert_eq!(
+      RevealExpectationState::reconcile(None, CommitOutput::Unconfirmed, 80, 224, 144),
+      Some((Expired, 224))
+    );
+  }
 
-      context.mine_blocks(1);
+  #[test]
+  fn reveal_expectation_page_next() {
+    let row = |reveal_txid: &str| RevealExpectationRow {
+      reveal_txid: reveal_txid.into(),
+      commit_txid: "c".into(),
+      source: "s".into(),
+      state: "pending".into(),
+      height: None,
+      created_height: Some(1),
+    };
 
-      let (inscriptions, prev, next) = context
-        .index
-        .get_latest_inscriptions_with_prev_and_next(100, None)
-        .unwrap();
-      assert_eq!(inscriptions, &[inscription_id]);
-      assert_eq!(prev, None);
-      assert_eq!(next, None);
-    }
+    assert_eq!(
+      RevealExpectationPage::new(vec![row("a"), row("b")], 2).next,
+      Some("b".into())
+    );
+    assert_eq!(RevealExpectationPage::new(vec![row("a")], 2).next, None);
   }
 
   #[test]
-  fn get_latest_inscriptions_with_prev_and_next() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
-
-      let mut ids = Vec::new();
+  fn address_inscriptions_page_next() {
+    let inscription = |n: u8| AddressInscription {
+      inscription_id: InscriptionId::from_str(&format!("{}i0", hex::encode([n; 32]))).unwrap(),
+      satpoint: SatPoint::from_str(&format!("{}:0:0", hex::encode([n; 32]))).unwrap(),
+      content_type: None,
+    };
 
-      for i in 0..103 {
-        let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-          inputs: &[(i + 1, 0, 0)],
-          witness: inscription("text/plain", "hello").to_witness(),
-          ..Default::default()
-        });
-        ids.push(InscriptionId::from(txid));
-        context.mine_blocks(1);
-      }
+    let page = AddressInscriptionsPage::new(vec![inscription(1), inscription(2)], 5, 2);
+    assert_eq!(page.total_count, 5);
+    assert_eq!(page.next, Some(inscription(2).inscription_id.to_string()));
+    assert_eq!(
+      AddressInscriptionsPage::new(vec![inscription(1)], 1, 2).next,
+      None
+    );
+  }
 
-      ids.reverse();
+  #[test]
+  fn whitelist_page_next() {
+    let entry = |address: &str| WhitelistEntry {
+      address: address.into(),
+      note: None,
+      added_by: Some("default".into()),
+    };
 
-      let (inscriptions, prev, next) = context
-        .index
-        .get_latest_inscriptions_with_prev_and_next(100, None)
-        .unwrap();
-      assert_eq!(inscriptions, &ids[..100]);
-      assert_eq!(prev, Some(2));
-      assert_eq!(next, None);
+    assert_eq!(
+      WhitelistPage::new(vec![entry("a"), entry("b")], 2).next,
+      Some("b".into())
+    );
+    assert_eq!(WhitelistPage::new(vec![entry("a")], 2).next, None);
+  }
 
-      let (inscriptions, prev, next) = context
-        .index
-        .get_latest_inscriptions_with_prev_and_next(100, Some(101))
+  #[test]
+  fn inscription_checksum() {
+    let a =
+      InscriptionId::from_str("1111111111111111111111111111111111111111111111111111111111111111i0")
         .unwrap();
-      assert_eq!(inscriptions, &ids[1..101]);
-      assert_eq!(prev, Some(1));
-      assert_eq!(next, Some(102));
-
-      let (inscriptions, prev, next) = context
-        .index
-        .get_latest_inscriptions_with_prev_and_next(100, Some(0))
+    let b =
+      InscriptionId::from_str("2222222222222222222222222222222222222222222222222222222222222222i0")
         .unwrap();
-      assert_eq!(inscriptions, &ids[102..103]);
-      assert_eq!(prev, None);
-      assert_eq!(next, Some(100));
-    }
-  }
 
-  #[test]
-  fn unsynced_index_fails() {
-    for context in Context::configurations() {
-      let mut entropy = [0; 16];
-      rand::thread_rng().fill_bytes(&mut entropy);
-      let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
-      crate::subcommand::wallet::initialize_wallet(&context.options, mnemonic.to_seed("")).unwrap();
-      context.rpc_server.mine_blocks(1);
-      assert_regex_match!(
-        context
-          .index
-          .get_unspent_outputs(Wallet::load(&context.options).unwrap())
-          .unwrap_err()
-          .to_string(),
-        r"output in Bitcoin Core wallet but not in ord index: [[:xdigit:]]{64}:\d+"
-      );
-    }
-  }
+    let first = InscriptionChecksum::new(None, 100, &[a, b]);
+    assert_eq!(first, InscriptionChecksum::new(None, 100, &[a, b]));
+    assert_ne!(
+      first.block,
+      InscriptionChecksum::new(None, 100, &[b, a]).block
+    );
+    assert_ne!(
+      first.rolling,
+      InscriptionChecksum::new(None, 101, &[a, b]).rolling
+    );
 
-  #[test]
-  fn get_unspent_outputs_by_mempool_fails() {
-    for context in Context::configurations() {
-      let mut entropy = [0; 16];
-      rand::thread_rng().fill_bytes(&mut entropy);
-      let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
-      crate::subcommand::wallet::initialize_wallet(&context.options, mnemonic.to_seed("")).unwrap();
-      context.rpc_server.mine_blocks(1);
-      let result = context
-        .index
-        .get_unspent_outputs_by_mempool(
-          "tb1phsaern0qpcpqpv2h6cmu6fgae4y0lyx2tqhmqmgvv7c9whffm3rqjmlrqs",
-          BTreeMap::new(),
-        )
-        .unwrap_err()
-        .to_string();
-      assert_regex_match!(
-        result,
-        r"output in Bitcoin Core wallet but not in ord index: [[:xdigit:]]{64}:\d+"
-      );
-    }
+    let mut engine = sha256::Hash::engine();
+    engine.input(format!("{a}\n{b}\n").as_bytes());
+    assert_eq!(first.block, sha256::Hash::from_engine(engine));
+
+    // empty blocks still roll the checksum on
+    let empty = InscriptionChecksum::new(Some(first.rolling), 101, &[]);
+    assert_eq!(empty.block, sha256::Hash::hash(&[]));
+    assert_ne!(empty.rolling, first.rolling);
+    assert_ne!(
+      empty.rolling,
+      InscriptionChecksum::new(None, 101, &[]).rolling
+    );
   }
 }
@@ -0,0 +1,78 @@
+use super::*;
+
+/// Blocks the rolling averages are taken over.
+const WINDOW: usize = 100;
+
+static STATS: Mutex<Window> = Mutex::new(Window {
+  blocks: Vec::new(),
+  indexed: 0,
+});
+
+struct Window {
+  blocks: Vec<BlockTimings>,
+  indexed: u64,
+}
+
+/// Where the time indexing one block went, in microseconds. `fetch` is
+/// spent waiting for the block from the node, `inputs` waiting for input
+/// values, `parse` parsing envelopes, `mysql` writing mysql rows and
+/// `commit` committing redb, for the block that triggered the commit.
+/// `redb` is the rest of the block's indexing, mostly redb table writes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct BlockTimings {
+  pub height: u64,
+  pub fetch: u64,
+  pub inputs: u64,
+  pub parse: u64,
+  pub redb: u64,
+  pub mysql: u64,
+  pub commit: u64,
+  pub total: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct BlockTimingStats {
+  pub indexed: u64,
+  pub last: Option<BlockTimings>,
+  pub average: Option<BlockTimings>,
+}
+
+pub(crate) fn micros(duration: Duration) -> u64 {
+  u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+pub(crate) fn record(timings: BlockTimings) {
+  let mut stats = STATS.lock().unwrap();
+  if stats.blocks.len() == WINDOW {
+    stats.blocks.remove(0);
+  }
+  stats.blocks.push(timings);
+  stats.indexed += 1;
+}
+
+/// The last block's breakdown and averages over the last blocks indexed
+/// since startup. The averaged `height` is the last block's.
+pub fn block_timing_stats() -> BlockTimingStats {
+  let stats = STATS.lock().unwrap();
+  let Some(last) = stats.blocks.last().copied() else {
+    return BlockTimingStats::default();
+  };
+
+  let n = u64::try_from(stats.blocks.len()).unwrap();
+  let average = |phase: fn(&BlockTimings) -> u64| stats.blocks.iter().map(phase).sum::<u64>() / n;
+
+  BlockTimingStats {
+    indexed: stats.indexed,
+    last: Some(last),
+    average: Some(BlockTimings {
+      height: last.height,
+      fetch: average(|timings| timings.fetch),
+      inputs: average(|timings| timings.inputs),
+      parse: average(|timings| timings.parse),
+      redb: average(|timings| timings.redb),
+      mysql: average(|timings| timings.mysql),
+      commit: average(|timings| timings.commit),
+      total: average(|timings| timings.total),
+    }),
+  }
+}
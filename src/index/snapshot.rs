@@ -0,0 +1,344 @@
+use {
+  super::*,
+  bitcoin::hashes::{sha256, HashEngine},
+  redb::{ReadTransaction, RedbKey, RedbValue},
+  std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+const MAGIC: &[u8; 8] = b"ordsnap\0";
+const FORMAT_VERSION: u32 = 1;
+const CHECKSUM_LEN: u64 = 32;
+
+/// Snapshot contents are a header, every table as length prefixed key and
+/// value bytes, an empty table name, and the sha256 of everything before it.
+struct Header {
+  chain: u64,
+  schema_version: u64,
+  height: u64,
+}
+
+struct Hashing<W> {
+  inner: W,
+  engine: sha256::HashEngine,
+}
+
+impl<W: Write> Write for Hashing<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    self.engine.input(&buf[..n]);
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+fn write_u32(writer: &mut impl Write, n: u32) -> Result {
+  writer.write_all(&n.to_le_bytes())?;
+  Ok(())
+}
+
+fn write_u64(writer: &mut impl Write, n: u64) -> Result {
+  writer.write_all(&n.to_le_bytes())?;
+  Ok(())
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> Result {
+  write_u32(writer, u32::try_from(bytes.len())?)?;
+  writer.write_all(bytes)?;
+  Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+  let mut buf = [0; 4];
+  reader.read_exact(&mut buf)?;
+  Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+  let mut buf = [0; 8];
+  reader.read_exact(&mut buf)?;
+  Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>> {
+  let mut buf = vec![0; usize::try_from(read_u32(reader)?)?];
+  reader.read_exact(&mut buf)?;
+  Ok(buf)
+}
+
+fn export_table<K: RedbKey + 'static, V: RedbValue + 'static>(
+  rtx: &ReadTransaction,
+  name: &str,
+  definition: TableDefinition<K, V>,
+  writer: &mut impl Write,
+) -> Result {
+  let table = match rtx.open_table(definition) {
+    Ok(table) => table,
+    Err(redb::Error::TableDoesNotExist(_)) => return Ok(()),
+    Err(err) => return Err(err.into()),
+  };
+
+  write_bytes(writer, name.as_bytes())?;
+  write_u64(writer, table.len()? as u64)?;
+  for (key, value) in table.iter()? {
+    write_bytes(writer, K::as_bytes(&key.value()).as_ref())?;
+    write_bytes(writer, V::as_bytes(&value.value()).as_ref())?;
+  }
+
+  Ok(())
+}
+
+fn import_table<K: RedbKey + 'static, V: RedbValue + 'static>(
+  wtx: &WriteTransaction,
+  definition: TableDefinition<K, V>,
+  reader: &mut impl Read,
+) -> Result {
+  let mut table = wtx.open_table(definition)?;
+  for _ in 0..read_u64(reader)? {
+    let key = read_bytes(reader)?;
+    let value = read_bytes(reader)?;
+    table.insert(K::from_bytes(&key), V::from_bytes(&value))?;
+  }
+  Ok(())
+}
+
+fn index_path(options: &Options) -> Result<PathBuf> {
+  Ok(match &options.index {
+    Some(path) => path.clone(),
+    None => options.data_dir()?.join("index.redb"),
+  })
+}
+
+// reads the header after checking the trailing checksum, leaving `file`
+// positioned at the first table
+fn verify(file: &mut File) -> Result<Header> {
+  let len = file.metadata()?.len();
+  if len < CHECKSUM_LEN {
+    bail!("snapshot is truncated");
+  }
+
+  let mut engine = sha256::Hash::engine();
+  io::copy(
+    &mut BufReader::new(&mut *file).take(len - CHECKSUM_LEN),
+    &mut engine,
+  )?;
+  let mut checksum = [0; 32];
+  file.seek(SeekFrom::Start(len - CHECKSUM_LEN))?;
+  file.read_exact(&mut checksum)?;
+  if sha256::Hash::from_engine(engine).into_inner() != checksum {
+    bail!("snapshot checksum mismatch");
+  }
+
+  file.seek(SeekFrom::Start(0))?;
+  let mut magic = [0; 8];
+  file.read_exact(&mut magic)?;
+  if magic != *MAGIC {
+    bail!("not an ord index snapshot");
+  }
+
+  let version = read_u32(file)?;
+  if version != FORMAT_VERSION {
+    bail!("unsupported snapshot format {version}, expected {FORMAT_VERSION}");
+  }
+
+  Ok(Header {
+    chain: read_u64(file)?,
+    schema_version: read_u64(file)?,
+    height: read_u64(file)?,
+  })
+}
+
+impl Index {
+  /// Write every table to `path` from a single read transaction, so the
+  /// snapshot is consistent even while the index is being updated.
+  pub(crate) fn export_snapshot(&self, path: &Path) -> Result<u64> {
+    let rtx = rtx::Rtx(self.database.begin_read()?);
+    let height = rtx.block_count()?;
+    let rtx = rtx.0;
+
+    let mut writer = Hashing {
+      inner: BufWriter::new(File::create(path)?),
+      engine: sha256::Hash::engine(),
+    };
+
+    writer.write_all(MAGIC)?;
+    write_u32(&mut writer, FORMAT_VERSION)?;
+    write_u64(&mut writer, chain_code(self.options.chain()))?;
+    write_u64(&mut writer, SCHEMA_VERSION)?;
+    write_u64(&mut writer, height)?;
+
+    export_table(
+      &rtx,
+      "HEIGHT_TO_BLOCK_HASH",
+      HEIGHT_TO_BLOCK_HASH,
+      &mut writer,
+    )?;
+    export_table(
+      &rtx,
+      "HEIGHT_TO_PARTIALLY_PARSED",
+      HEIGHT_TO_PARTIALLY_PARSED,
+      &mut writer,
+    )?;
+    export_table(
+      &rtx,
+      "INSCRIPTION_ID_TO_INSCRIPTION_ENTRY",
+      INSCRIPTION_ID_TO_INSCRIPTION_ENTRY,
+      &mut writer,
+    )?;
+    export_table(
+      &rtx,
+      "INSCRIPTION_ID_TO_SATPOINT",
+      INSCRIPTION_ID_TO_SATPOINT,
+      &mut writer,
+    )?;
+    export_table(
+      &rtx,
+      "INSCRIPTION_NUMBER_TO_INSCRIPTION_ID",
+      INSCRIPTION_NUMBER_TO_INSCRIPTION_ID,
+      &mut writer,
+    )?;
+    export_table(
+      &rtx,
+      "OUTPOINT_TO_SAT_RANGES",
+      OUTPOINT_TO_SAT_RANGES,
+      &mut writer,
+    )?;
+    export_table(&rtx, "OUTPOINT_TO_VALUE", OUTPOINT_TO_VALUE, &mut writer)?;
+    export_table(
+      &rtx,
+      "SATPOINT_TO_INSCRIPTION_ID",
+      SATPOINT_TO_INSCRIPTION_ID,
+      &mut writer,
+    )?;
+    export_table(
+      &rtx,
+      "SAT_TO_INSCRIPTION_ID",
+      SAT_TO_INSCRIPTION_ID,
+      &mut writer,
+    )?;
+    export_table(&rtx, "SAT_TO_SATPOINT", SAT_TO_SATPOINT, &mut writer)?;
+    export_table(&rtx, "STATISTIC_TO_COUNT", STATISTIC_TO_COUNT, &mut writer)?;
+    export_table(
+      &rtx,
+      "WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP",
+      WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP,
+      &mut writer,
+    )?;
+    write_bytes(&mut writer, &[])?;
+
+    let checksum = sha256::Hash::from_engine(writer.engine).into_inner();
+    let mut file = writer.inner;
+    file.write_all(&checksum)?;
+    file.flush()?;
+
+    Ok(height)
+  }
+
+  /// Restore a snapshot into the index path of `options`, which must not
+  /// exist yet. The snapshot is verified before anything is written and
+  /// the index only appears at its final path once fully restored.
+  pub(crate) fn import_snapshot(options: &Options, path: &Path) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let header = verify(&mut file)?;
+
+    let chain = options.chain();
+    if header.chain != chain_code(chain) {
+      bail!(
+        "snapshot was taken on {}, refusing to import it for {chain}",
+        code_chain(header.chain).map_or_else(
+          || format!("unknown chain {}", header.chain),
+          |chain| chain.to_string()
+        ),
+      );
+    }
+
+    if header.schema_version != SCHEMA_VERSION {
+      bail!(
+        "snapshot has index schema {}, ord schema {SCHEMA_VERSION}",
+        header.schema_version
+      );
+    }
+
+    let destination = index_path(options)?;
+    if destination.exists() {
+      bail!(
+        "index at `{}` already exists, snapshots are only imported into a fresh data dir",
+        destination.display()
+      );
+    }
+
+    if let Some(parent) = destination.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    let partial = destination.with_extension("redb.partial");
+    if partial.exists() {
+      fs::remove_file(&partial)?;
+    }
+
+    {
+      let database = unsafe {
+        Database::builder()
+          .set_write_strategy(if cfg!(test) {
+            WriteStrategy::Checksum
+          } else {
+            WriteStrategy::TwoPhase
+          })
+          .create_mmapped(&partial)?
+      };
+      let wtx = database.begin_write()?;
+      let mut reader = BufReader::new(&mut file);
+
+      loop {
+        let name = read_bytes(&mut reader)?;
+        match name.as_slice() {
+          b"" => break,
+          b"HEIGHT_TO_BLOCK_HASH" => import_table(&wtx, HEIGHT_TO_BLOCK_HASH, &mut reader)?,
+          b"HEIGHT_TO_PARTIALLY_PARSED" => {
+            import_table(&wtx, HEIGHT_TO_PARTIALLY_PARSED, &mut reader)?
+          }
+          b"INSCRIPTION_ID_TO_INSCRIPTION_ENTRY" => {
+            import_table(&wtx, INSCRIPTION_ID_TO_INSCRIPTION_ENTRY, &mut reader)?
+          }
+          b"INSCRIPTION_ID_TO_SATPOINT" => {
+            import_table(&wtx, INSCRIPTION_ID_TO_SATPOINT, &mut reader)?
+          }
+          b"INSCRIPTION_NUMBER_TO_INSCRIPTION_ID" => {
+            import_table(&wtx, INSCRIPTION_NUMBER_TO_INSCRIPTION_ID, &mut reader)?
+          }
+          b"OUTPOINT_TO_SAT_RANGES" => import_table(&wtx, OUTPOINT_TO_SAT_RANGES, &mut reader)?,
+          b"OUTPOINT_TO_VALUE" => import_table(&wtx, OUTPOINT_TO_VALUE, &mut reader)?,
+          b"SATPOINT_TO_INSCRIPTION_ID" => {
+            import_table(&wtx, SATPOINT_TO_INSCRIPTION_ID, &mut reader)?
+          }
+          b"SAT_TO_INSCRIPTION_ID" => import_table(&wtx, SAT_TO_INSCRIPTION_ID, &mut reader)?,
+          b"SAT_TO_SATPOINT" => import_table(&wtx, SAT_TO_SATPOINT, &mut reader)?,
+          b"STATISTIC_TO_COUNT" => import_table(&wtx, STATISTIC_TO_COUNT, &mut reader)?,
+          b"WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP" => import_table(
+            &wtx,
+            WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP,
+            &mut reader,
+          )?,
+          name => bail!(
+            "snapshot contains unknown table `{}`",
+            String::from_utf8_lossy(name)
+          ),
+        }
+      }
+
+      {
+        let mut statistics = wtx.open_table(STATISTIC_TO_COUNT)?;
+        statistics.insert(&Statistic::Chain.key(), &chain_code(chain))?;
+        statistics.insert(&Statistic::SnapshotHeight.key(), &header.height)?;
+      }
+
+      wtx.commit()?;
+    }
+
+    fs::rename(&partial, &destination)?;
+
+    Ok(header.height)
+  }
+}
@@ -0,0 +1,166 @@
+use super::*;
+
+/// How often the node is asked for its best block hash.
+const TIP_REFRESH: Duration = Duration::from_secs(1);
+
+pub(crate) static TIP: Tip = Tip::new(TIP_REFRESH);
+
+static INVALIDATIONS: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+/// Tip cache entries dropped because the tip moved, by cache name since
+/// startup.
+pub fn cache_invalidation_counts() -> BTreeMap<&'static str, u64> {
+  INVALIDATIONS.lock().unwrap().clone()
+}
+
+/// The node's best block hash, asked for at most once per `refresh`.
+pub(crate) struct Tip {
+  refresh: Duration,
+  last: Mutex<Option<(Instant, BlockHash)>>,
+}
+
+impl Tip {
+  pub(crate) const fn new(refresh: Duration) -> Self {
+    Self {
+      refresh,
+      last: Mutex::new(None),
+    }
+  }
+
+  pub(crate) fn get(&self, fetch: impl FnOnce() -> Result<BlockHash>) -> Result<BlockHash> {
+    let mut last = self.last.lock().unwrap();
+
+    if let Some((fetched, hash)) = *last {
+      if fetched.elapsed() < self.refresh {
+        return Ok(hash);
+      }
+    }
+
+    let hash = fetch()?;
+    *last = Some((Instant::now(), hash));
+    Ok(hash)
+  }
+}
+
+/// Values that hold for one tip. Entries expire after `ttl`, and all of them
+/// are dropped as soon as a lookup sees a different tip, so a new block or a
+/// reorg never serves values from the old chain.
+pub(crate) struct TipCache<K, V> {
+  name: &'static str,
+  ttl: Duration,
+  state: Mutex<(Option<BlockHash>, BTreeMap<K, (Instant, V)>)>,
+}
+
+impl<K: Ord, V: Clone> TipCache<K, V> {
+  pub(crate) const fn new(name: &'static str, ttl: Duration) -> Self {
+    Self {
+      name,
+      ttl,
+      state: Mutex::new((None, BTreeMap::new())),
+    }
+  }
+
+  /// The value of `key` at the node's current tip, computed uncached when
+  /// the tip cannot be fetched.
+  pub(crate) fn get_at_tip(
+    &self,
+    fetch_tip: impl FnOnce() -> Result<BlockHash>,
+    key: K,
+    compute: impl FnOnce() -> Result<V>,
+  ) -> Result<V> {
+    match TIP.get(fetch_tip) {
+      Ok(tip) => self.get(tip, key, compute),
+      Err(err) => {
+        log::warn!("Bypass {} cache, tip unknown: {err}", self.name);
+        compute()
+      }
+    }
+  }
+
+  /// The value of `key` at `tip`, computed by `compute` on a miss. Errors
+  /// are not cached.
+  pub(crate) fn get(
+    &self,
+    tip: BlockHash,
+    key: K,
+    compute: impl FnOnce() -> Result<V>,
+  ) -> Result<V> {
+    {
+      let mut state = self.state.lock().unwrap();
+      let (cached_tip, entries) = &mut *state;
+
+      if *cached_tip != Some(tip) {
+        if !entries.is_empty() {
+          *INVALIDATIONS.lock().unwrap().entry(self.name).or_default() += 1;
+          entries.clear();
+        }
+        *cached_tip = Some(tip);
+      }
+
+      if let Some((inserted, value)) = entries.get(&key) {
+        if inserted.elapsed() < self.ttl {
+          return Ok(value.clone());
+        }
+      }
+    }
+
+    // computed unlocked, these are node and database reads
+    let value = compute()?;
+
+    let mut state = self.state.lock().unwrap();
+    let (cached_tip, entries) = &mut *state;
+    if *cached_tip == Some(tip) {
+      entries.insert(key, (Instant::now(), value.clone()));
+    }
+
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hash(n: u8) -> BlockHash {
+    BlockHash::from_inner([n; 32])
+  }
+
+  #[test]
+  fn new_tip_invalidates() {
+    let cache = TipCache::new("new_tip_test", Duration::from_secs(60));
+
+    assert_eq!(cache.get(hash(1), "a", || Ok(1)).unwrap(), 1);
+    assert_eq!(cache.get(hash(1), "a", || Ok(2)).unwrap(), 1);
+    assert_eq!(cache.get(hash(2), "a", || Ok(3)).unwrap(), 3);
+    assert_eq!(cache_invalidation_counts()["new_tip_test"], 1);
+  }
+
+  #[test]
+  fn entries_expire() {
+    let cache = TipCache::new("expire_test", Duration::ZERO);
+
+    assert_eq!(cache.get(hash(1), "a", || Ok(1)).unwrap(), 1);
+    assert_eq!(cache.get(hash(1), "a", || Ok(2)).unwrap(), 2);
+  }
+
+  #[test]
+  fn errors_are_not_cached() {
+    let cache = TipCache::new("error_test", Duration::from_secs(60));
+
+    assert!(cache
+      .get(hash(1), "a", || Err(anyhow!("node down")))
+      .is_err());
+    assert_eq!(cache.get(hash(1), "a", || Ok(1)).unwrap(), 1);
+  }
+
+  #[test]
+  fn tip_is_refreshed() {
+    let tip = Tip::new(Duration::from_secs(60));
+    assert_eq!(tip.get(|| Ok(hash(1))).unwrap(), hash(1));
+    assert_eq!(tip.get(|| Ok(hash(2))).unwrap(), hash(1));
+
+    let tip = Tip::new(Duration::ZERO);
+    assert_eq!(tip.get(|| Ok(hash(1))).unwrap(), hash(1));
+    assert_eq!(tip.get(|| Ok(hash(2))).unwrap(), hash(2));
+  }
+}
@@ -37,6 +37,11 @@ pub(crate) struct Updater {
   outputs_cached: u64,
   outputs_inserted_since_flush: u64,
   outputs_traversed: u64,
+  pending_reveals: HashSet<Txid>,
+  pending_broadcasts: HashSet<Txid>,
+  watched_addresses: Option<HashSet<String>>,
+  watched_scripts: Option<Arc<HashSet<Script>>>,
+  tracked_inscriptions: HashSet<InscriptionId>,
 }
 
 impl Updater {
@@ -52,10 +57,12 @@ impl Updater {
 
     {
       let mut height_to_block_hash = wtx.open_table(HEIGHT_TO_BLOCK_HASH)?;
+      let mut height_to_partially_parsed = wtx.open_table(HEIGHT_TO_PARTIALLY_PARSED)?;
 
       while height > target_height {
         log::info!("Reorg height to {height}");
         height_to_block_hash.remove(height)?;
+        height_to_partially_parsed.remove(height)?;
         height = height
           .checked_sub(1)
           .ok_or_else(|| anyhow!("Check sub fail"))?;
@@ -63,6 +70,17 @@ impl Updater {
     }
 
     wtx.commit()?;
+
+    if let Some(mysql) = &index.mysql_database {
+      mysql.rollback_activity(target_height)?;
+      mysql.rollback_satpoint_history(target_height)?;
+      mysql.rollback_genesis(target_height)?;
+      mysql.rollback_content_hashes(target_height)?;
+      mysql.rollback_content_type_stats(target_height)?;
+      mysql.rollback_inscription_checksum(target_height)?;
+      mysql.rollback_broadcast_confirmations(target_height)?;
+    }
+
     Ok(())
   }
 
@@ -95,8 +113,41 @@ impl Updater {
       outputs_cached: 0,
       outputs_inserted_since_flush: 0,
       outputs_traversed: 0,
+      pending_reveals: match &index.mysql_database {
+        Some(mysql) => mysql.get_expected_reveal_txids().unwrap_or_else(|err| {
+          log::warn!("Load expected reveals fail:{err}");
+          HashSet::new()
+        }),
+        None => HashSet::new(),
+      },
+      pending_broadcasts: match &index.mysql_database {
+        Some(mysql) => mysql.get_pending_broadcast_txids().unwrap_or_else(|err| {
+          log::warn!("Load pending broadcasts fail:{err}");
+          HashSet::new()
+        }),
+        None => HashSet::new(),
+      },
+      watched_addresses: None,
+      watched_scripts: None,
+      tracked_inscriptions: HashSet::new(),
     };
 
+    if let Some(watched_addresses) = index.watched_addresses()? {
+      updater.watched_scripts = Some(Arc::new(
+        watched_addresses
+          .iter()
+          .filter_map(|address| match Address::from_str(address) {
+            Ok(address) => Some(address.script_pubkey()),
+            Err(err) => {
+              log::warn!("Skip watched address {address}:{err}");
+              None
+            }
+          })
+          .collect(),
+      ));
+      updater.watched_addresses = Some(watched_addresses);
+    }
+
     updater.update_index(index, wtx)
   }
 
@@ -128,12 +179,22 @@ impl Updater {
 
     let mut uncommitted = 0;
     let mut value_cache = HashMap::new();
+    // the block just indexed and when we started waiting for it, kept until
+    // any commit it triggers is timed
+    let mut last: Option<(BlockTimings, Instant)> = None;
     loop {
+      let wait = Instant::now();
       let block = match rx.recv() {
         Ok(block) => block,
         Err(mpsc::RecvError) => break,
       };
 
+      let mut timings = BlockTimings {
+        height: self.height,
+        fetch: block_stats::micros(wait.elapsed()),
+        ..Default::default()
+      };
+
       self.index_block(
         index,
         &mut outpoint_sender,
@@ -141,8 +202,11 @@ impl Updater {
         &mut wtx,
         block,
         &mut value_cache,
+        &mut timings,
       )?;
 
+      last = Some((timings, wait));
+
       if let Some(progress_bar) = &mut progress_bar {
         progress_bar.inc(1);
 
@@ -158,7 +222,11 @@ impl Updater {
       uncommitted += 1;
 
       if uncommitted == 5000 {
+        let start = Instant::now();
         self.commit(wtx, value_cache)?;
+        if let Some((timings, _)) = &mut last {
+          timings.commit = block_stats::micros(start.elapsed());
+        }
         value_cache = HashMap::new();
         uncommitted = 0;
         wtx = index.begin_write()?;
@@ -185,13 +253,29 @@ impl Updater {
           )?;
       }
 
+      Self::record_block_timings(index, last.take());
+
       if SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
         break;
       }
     }
 
     if uncommitted > 0 {
+      let start = Instant::now();
       self.commit(wtx, value_cache)?;
+      if let Some((timings, _)) = &mut last {
+        timings.commit = block_stats::micros(start.elapsed());
+      }
+    }
+
+    Self::record_block_timings(index, last.take());
+
+    if let (Some(retain_days), Some(mysql)) =
+      (index.block_stats_retention, index.mysql_database.as_ref())
+    {
+      if let Err(err) = mysql.prune_block_stats(retain_days) {
+        log::warn!("Failed to prune block stats: {err}");
+      }
     }
 
     if let Some(progress_bar) = &mut progress_bar {
@@ -201,6 +285,22 @@ impl Updater {
     Ok(())
   }
 
+  fn record_block_timings(index: &Index, last: Option<(BlockTimings, Instant)>) {
+    let Some((mut timings, wait)) = last else {
+      return;
+    };
+    timings.total = block_stats::micros(wait.elapsed());
+    block_stats::record(timings);
+
+    if index.block_stats_retention.is_some() {
+      if let Some(mysql) = &index.mysql_database {
+        if let Err(err) = mysql.insert_block_stats(&timings) {
+          log::warn!("Failed to record stats for block {}: {err}", timings.height);
+        }
+      }
+    }
+  }
+
   fn fetch_blocks_from(
     index: &Index,
     mut height: u64,
@@ -363,7 +463,10 @@ impl Updater {
     wtx: &mut WriteTransaction,
     block: BlockData,
     value_cache: &mut HashMap<OutPoint, u64>,
+    timings: &mut BlockTimings,
   ) -> Result<()> {
+    let begin = Instant::now();
+
     // If value_receiver still has values something went wrong with the last block
     // Could be an assert, shouldn't recover from this and commit the last block
     let Err(TryRecvError::Empty) = value_receiver.try_recv() else {
@@ -374,6 +477,13 @@ impl Updater {
 
     let index_inscriptions = self.height >= index.first_inscription_height;
 
+    let confirmed_broadcasts = block
+      .txdata
+      .iter()
+      .map(|(_, txid)| *txid)
+      .filter(|txid| self.pending_broadcasts.contains(txid))
+      .collect::<Vec<Txid>>();
+
     if index_inscriptions {
       // Send all missing input outpoints to be fetched right away
       let txids = block
@@ -441,6 +551,46 @@ impl Updater {
       }
     }
 
+    // Reveals our own service built are known ahead of time, whoever
+    // broadcasts them, so their rows are written before the block is parsed. The full pass below
+    // writes them again, along with any move later in the block.
+    if let (true, Some(mysql)) = (index_inscriptions, &index.mysql_database) {
+      let expected_reveals = block
+        .txdata
+        .iter()
+        .filter(|(_, txid)| self.pending_reveals.contains(txid))
+        .map(|(tx, _)| tx)
+        .collect::<Vec<&Transaction>>();
+
+      if !expected_reveals.is_empty() {
+        let mut rows = Self::expected_reveal_rows(&expected_reveals, mysql.network);
+        if let Some(watched_addresses) = &self.watched_addresses {
+          rows.retain(|item| watched_addresses.contains(&item.new_address));
+        }
+        let txids = expected_reveals
+          .iter()
+          .map(|tx| tx.txid())
+          .collect::<Vec<Txid>>();
+
+        let data_length = rows.len();
+        match mysql
+          .insert_inscriptions(rows)
+          .and_then(|_| mysql.fulfill_reveal_expectations(&txids, self.height))
+        {
+          Ok(_) => {
+            for txid in &txids {
+              self.pending_reveals.remove(txid);
+            }
+            log::info!(
+              "Fulfill {} expected reveals with {data_length} item",
+              txids.len()
+            )
+          }
+          Err(err) => log::info!("Fulfill expected reveals fail:{err}"),
+        }
+      }
+    }
+
     let mut inscription_id_to_inscription_entry =
       wtx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
     let mut inscription_id_to_satpoint = wtx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
@@ -475,6 +625,7 @@ impl Updater {
       value_cache,
       index.mysql_database.clone(),
     )?;
+    inscription_updater.watched_scripts = self.watched_scripts.clone();
 
     let mut mysql_data: Vec<MysqlInscription> = vec![];
     if self.index_sats {
@@ -576,13 +727,110 @@ impl Updater {
       }
     }
 
+    // in watched-only mode rows are kept for watched addresses, and for
+    // inscriptions already tracked so that moves away from them are recorded
+    if let Some(watched_addresses) = &self.watched_addresses {
+      let tracked_inscriptions = &mut self.tracked_inscriptions;
+      if let Some(mysql) = &index.mysql_database {
+        let unknown = mysql_data
+          .iter()
+          .filter(|item| {
+            !watched_addresses.contains(&item.new_address)
+              && !tracked_inscriptions.contains(&item.inscription_id)
+          })
+          .map(|item| item.inscription_id)
+          .collect::<HashSet<InscriptionId>>()
+          .into_iter()
+          .collect::<Vec<InscriptionId>>();
+        tracked_inscriptions.extend(mysql.get_tracked_inscription_ids(&unknown)?);
+      }
+      mysql_data.retain(|item| {
+        watched_addresses.contains(&item.new_address)
+          || tracked_inscriptions.contains(&item.inscription_id)
+      });
+      tracked_inscriptions.extend(mysql_data.iter().map(|item| item.inscription_id));
+    }
+
+    let mysql_start = Instant::now();
     if let Some(mysql) = index.mysql_database.clone() {
+      let tx_indices = block
+        .txdata
+        .iter()
+        .enumerate()
+        .map(|(tx_index, (_, txid))| (*txid, u32::try_from(tx_index).unwrap()))
+        .collect::<HashMap<Txid, u32>>();
+      match mysql.insert_activity(self.height, &tx_indices, &mysql_data) {
+        Ok(count) => log::info!("Insert {count} activity"),
+        Err(err) => log::info!("Insert activity fail:{err}"),
+      }
+
+      match mysql.insert_satpoint_history(self.height, &mysql_data) {
+        Ok(_) => log::info!("Insert {} satpoint history", mysql_data.len()),
+        Err(err) => log::info!("Insert satpoint history fail:{err}"),
+      }
+
+      let genesis = InscriptionGenesis::rows(
+        self.height,
+        &tx_indices,
+        &mysql_data,
+        &inscription_updater.genesis_content_types,
+      );
+      match mysql.insert_genesis(&genesis) {
+        Ok(_) => log::info!("Insert {} genesis", genesis.len()),
+        Err(err) => log::info!("Insert genesis fail:{err}"),
+      }
+
+      let content_hashes = ContentHash::rows(&genesis, &inscription_updater.genesis_content_hashes);
+      match mysql.insert_content_hashes(&content_hashes) {
+        Ok(_) => log::info!("Insert {} content hashes", content_hashes.len()),
+        Err(err) => log::info!("Insert content hashes fail:{err}"),
+      }
+
       let data_length = mysql_data.len();
       match mysql.insert_inscriptions(mysql_data) {
         Ok(_) => log::info!("Insert {data_length} item success"),
         Err(err) => log::info!("Insert {data_length} item fail:{err}"),
       }
+
+      match mysql.insert_content_type_stats(
+        self.height,
+        time.naive_utc().date(),
+        &inscription_updater.content_types,
+      ) {
+        Ok(_) => log::info!(
+          "Insert {} content type stats",
+          inscription_updater.content_types.len()
+        ),
+        Err(err) => log::info!("Insert content type stats fail:{err}"),
+      }
+
+      match mysql.insert_inscription_checksum(
+        self.height,
+        block.header.block_hash(),
+        time.naive_utc().date(),
+        &inscription_updater.new_inscriptions,
+        inscription_updater.next_number,
+      ) {
+        Ok(_) => log::info!(
+          "Insert checksum of {} inscriptions",
+          inscription_updater.new_inscriptions.len()
+        ),
+        Err(err) => log::info!("Insert inscription checksum fail:{err}"),
+      }
+
+      if !confirmed_broadcasts.is_empty() {
+        match mysql.confirm_broadcasts(&confirmed_broadcasts, self.height) {
+          Ok(_) => {
+            for txid in &confirmed_broadcasts {
+              self.pending_broadcasts.remove(txid);
+            }
+            log::info!("Confirm {} broadcasts", confirmed_broadcasts.len())
+          }
+          Err(err) => log::info!("Confirm broadcasts fail:{err}"),
+        }
+      }
     }
+    timings.mysql = block_stats::micros(mysql_start.elapsed());
 
     statistic_to_count.insert(&Statistic::LostSats.key(), &inscription_updater.lost_sats)?;
 
@@ -591,6 +839,31 @@ impl Updater {
       &inscription_updater.unbound_inscriptions,
     )?;
 
+    if inscription_updater.partially_parsed > 0 {
+      log::warn!(
+        "Indexed {} inscriptions without their unknown tags in block {}",
+        inscription_updater.partially_parsed,
+        self.height
+      );
+
+      let partially_parsed = statistic_to_count
+        .get(&Statistic::PartiallyParsedInscriptions.key())?
+        .map(|partially_parsed| partially_parsed.value())
+        .unwrap_or(0);
+
+      statistic_to_count.insert(
+        &Statistic::PartiallyParsedInscriptions.key(),
+        &(partially_parsed + inscription_updater.partially_parsed),
+      )?;
+
+      wtx
+        .open_table(HEIGHT_TO_PARTIALLY_PARSED)?
+        .insert(&self.height, &inscription_updater.partially_parsed)?;
+    }
+
+    timings.inputs = block_stats::micros(inscription_updater.input_wait);
+    timings.parse = block_stats::micros(inscription_updater.parse_time);
+
     height_to_block_hash.insert(&self.height, &block.header.block_hash().store())?;
 
     self.height += 1;
@@ -601,9 +874,34 @@ impl Updater {
       (Instant::now() - start).as_millis(),
     );
 
+    timings.redb = block_stats::micros(begin.elapsed())
+      .saturating_sub(timings.inputs + timings.parse + timings.mysql);
+
     Ok(())
   }
 
+  /// The rows the updater writes for `reveals`, read straight from them.
+  pub(super) fn expected_reveal_rows(
+    reveals: &[&Transaction],
+    network: Network,
+  ) -> Vec<MysqlInscription> {
+    reveals
+      .iter()
+      .filter_map(|reveal| {
+        let satpoint = Inscription::from_transaction(reveal)?.satpoint_in(reveal)?;
+        let output = &reveal.output[usize::try_from(satpoint.outpoint.vout).unwrap()];
+        Some(MysqlInscription {
+          inscription_id: reveal.txid().into(),
+          new_satpoint: satpoint,
+          new_address: Address::from_script(&output.script_pubkey, network)
+            .map(|address| address.to_string())
+            .unwrap_or_default(),
+          created: true,
+        })
+      })
+      .collect()
+  }
+
   fn index_transaction_sats(
     &mut self,
     tx: &Transaction,
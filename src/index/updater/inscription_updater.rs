@@ -1,6 +1,10 @@
 use super::*;
 use bitcoin::Address;
 
+/// Inscriptions indexed without some of their fields since startup, a warning
+/// is logged for the first and then at every power of two.
+static PARTIALLY_PARSED: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
 pub(super) struct Flotsam {
   inscription_id: InscriptionId,
   offset: u64,
@@ -19,7 +23,8 @@ pub(super) struct InscriptionUpdater<'a, 'db, 'tx> {
   value_receiver: &'a mut Receiver<u64>,
   id_to_entry: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, InscriptionEntryValue>,
   pub(super) lost_sats: u64,
-  next_number: u64,
+  pub(super) next_number: u64,
+  pub(super) new_inscriptions: Vec<InscriptionId>,
   number_to_id: &'a mut Table<'db, 'tx, u64, &'static InscriptionIdValue>,
   outpoint_to_value: &'a mut Table<'db, 'tx, &'static OutPointValue, u64>,
   reward: u64,
@@ -27,8 +32,17 @@ pub(super) struct InscriptionUpdater<'a, 'db, 'tx> {
   satpoint_to_id: &'a mut Table<'db, 'tx, &'static SatPointValue, &'static InscriptionIdValue>,
   timestamp: u32,
   pub(super) unbound_inscriptions: u64,
+  pub(super) partially_parsed: u64,
+  pub(super) input_wait: Duration,
+  pub(super) parse_time: Duration,
   value_cache: &'a mut HashMap<OutPoint, u64>,
   mysql_database: Option<Arc<MysqlDatabase>>,
+  pub(super) content_types: BTreeMap<String, ContentTypeCount>,
+  pub(super) genesis_content_types: HashMap<InscriptionId, Option<String>>,
+  pub(super) genesis_content_hashes: HashMap<InscriptionId, sha256::Hash>,
+  /// In watched-only mode, the scripts a transaction must pay for its
+  /// envelope to be parsed.
+  pub(super) watched_scripts: Option<Arc<HashSet<Script>>>,
 }
 
 impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
@@ -62,6 +76,7 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       id_to_entry,
       lost_sats,
       next_number,
+      new_inscriptions: Vec::new(),
       number_to_id,
       outpoint_to_value,
       reward: Height(height).subsidy(),
@@ -69,8 +84,25 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       satpoint_to_id,
       timestamp,
       unbound_inscriptions,
+      partially_parsed: 0,
+      input_wait: Duration::ZERO,
+      parse_time: Duration::ZERO,
       value_cache,
       mysql_database,
+      content_types: BTreeMap::new(),
+      genesis_content_types: HashMap::new(),
+      genesis_content_hashes: HashMap::new(),
+      watched_scripts: None,
+    })
+  }
+
+  // envelopes of transactions paying no watched script are never parsed in
+  // watched-only mode, so their inscriptions are not indexed at all
+  fn pays_watched_script(&self, tx: &Transaction) -> bool {
+    self.watched_scripts.as_ref().map_or(true, |scripts| {
+      tx.output
+        .iter()
+        .any(|output| scripts.contains(&output.script_pubkey))
     })
   }
 
@@ -105,24 +137,79 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
         {
           value.value()
         } else {
-          self.value_receiver.blocking_recv().ok_or_else(|| {
+          let start = Instant::now();
+          let value = self.value_receiver.blocking_recv().ok_or_else(|| {
             anyhow!(
               "failed to get transaction for {}",
               tx_in.previous_output.txid
             )
-          })?
+          })?;
+          self.input_wait += start.elapsed();
+          value
         }
       }
     }
 
-    if inscriptions.iter().all(|flotsam| flotsam.offset != 0)
-      && Inscription::from_transaction(tx).is_some()
-    {
+    let output_value = tx.output.iter().map(|txout| txout.value).sum::<u64>();
+
+    // inscriptions packed into one reveal share its fee, and each lands on
+    // the sat its pointer names unless that sat is already inscribed
+    let parse_start = Instant::now();
+    let packed = if self.pays_watched_script(tx) {
+      Inscription::all_from_transaction_partially(tx)
+    } else {
+      Vec::new()
+    };
+    self.parse_time += parse_start.elapsed();
+    let count = u64::try_from(packed.len()).unwrap();
+
+    for (index, (inscription, unrecognized)) in packed.into_iter().enumerate() {
+      // a pointer past the outputs falls back to the first sat
+      let offset = inscription
+        .pointer()
+        .filter(|pointer| *pointer < output_value)
+        .unwrap_or(0);
+
+      if inscriptions.iter().any(|flotsam| flotsam.offset == offset) {
+        continue;
+      }
+
+      if !unrecognized.is_empty() {
+        self.partially_parsed += 1;
+        let total = PARTIALLY_PARSED.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        if total.is_power_of_two() {
+          log::warn!(
+            "Inscription {txid}i{index} at height {} has unknown tags {}, indexed without them ({total} since startup)",
+            self.height,
+            unrecognized
+              .iter()
+              .map(hex::encode)
+              .collect::<Vec<String>>()
+              .join(", "),
+          );
+        }
+      }
+
+      let inscription_id = InscriptionId {
+        txid,
+        index: index.try_into().unwrap(),
+      };
+
+      ContentTypeCount::record(&mut self.content_types, &inscription);
+      self
+        .genesis_content_types
+        .insert(inscription_id, inscription.content_type().map(str::to_owned));
+      if let Some(body) = inscription.body() {
+        self
+          .genesis_content_hashes
+          .insert(inscription_id, ContentHash::of(body));
+      }
+
       let flotsam = Flotsam {
-        inscription_id: txid.into(),
-        offset: 0,
+        inscription_id,
+        offset,
         origin: Origin::New {
-          fee: input_value - tx.output.iter().map(|txout| txout.value).sum::<u64>(),
+          fee: (input_value - output_value) / count,
         },
       };
 
@@ -139,7 +226,7 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       } else {
         inscriptions.push(flotsam);
       }
-    };
+    }
 
     let is_coinbase = tx
       .input
@@ -187,6 +274,7 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
           inscription_id: flotsam.inscription_id,
           new_satpoint,
           new_address,
+          created: matches!(flotsam.origin, Origin::New { .. }),
         });
 
         self.update_inscription_location(input_sat_ranges, flotsam, new_satpoint)?;
@@ -239,6 +327,7 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
         self
           .number_to_id
           .insert(&self.next_number, &inscription_id)?;
+        self.new_inscriptions.push(flotsam.inscription_id);
 
         let mut sat = None;
         if let Some(input_sat_ranges) = input_sat_ranges {
@@ -0,0 +1,106 @@
+use super::*;
+
+/// How long a single request for utxos or transactions may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+static ERRORS: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+/// The node did not answer a read in time, or could not be reached, even
+/// after a retry. The server answers these with 503 rather than 400.
+#[derive(Debug)]
+pub struct NodeUnavailable {
+  pub call: &'static str,
+  pub error: String,
+}
+
+impl Display for NodeUnavailable {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(
+      f,
+      "node unavailable during {}: {}, please try again later",
+      self.call, self.error
+    )
+  }
+}
+
+impl std::error::Error for NodeUnavailable {}
+
+/// Failed node requests by call name since startup.
+pub fn node_error_counts() -> BTreeMap<&'static str, u64> {
+  ERRORS.lock().unwrap().clone()
+}
+
+pub(crate) fn get(call: &'static str, url: &str) -> Result<String> {
+  get_with_timeout(call, url, TIMEOUT)
+}
+
+// the calls are reads, so a timeout or refused connection is retried once
+fn get_with_timeout(call: &'static str, url: &str, timeout: Duration) -> Result<String> {
+  let client = reqwest::blocking::Client::builder()
+    .timeout(timeout)
+    .build()?;
+
+  let mut retried = false;
+  loop {
+    let err = match client.get(url).send().and_then(|response| response.text()) {
+      Ok(text) => return Ok(text),
+      Err(err) => err,
+    };
+
+    *ERRORS.lock().unwrap().entry(call).or_default() += 1;
+
+    if !(err.is_timeout() || err.is_connect()) {
+      return Err(err.into());
+    }
+
+    if retried {
+      return Err(
+        NodeUnavailable {
+          call,
+          error: err.to_string(),
+        }
+        .into(),
+      );
+    }
+
+    log::warn!("Retry {call} after error: {err}");
+    retried = true;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, std::net::TcpListener};
+
+  #[test]
+  fn stalled_node_is_unavailable() {
+    // accepted by the backlog, never answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/address/utxo", listener.local_addr().unwrap());
+
+    let err = get_with_timeout("stalled_test", &url, Duration::from_millis(100)).unwrap_err();
+
+    assert_eq!(
+      err.downcast_ref::<NodeUnavailable>().unwrap().call,
+      "stalled_test"
+    );
+    assert_eq!(node_error_counts()["stalled_test"], 2);
+  }
+
+  #[test]
+  fn unreachable_node_is_unavailable() {
+    let port = TcpListener::bind("127.0.0.1:0")
+      .unwrap()
+      .local_addr()
+      .unwrap()
+      .port();
+
+    assert!(get_with_timeout(
+      "refused_test",
+      &format!("http://127.0.0.1:{port}/"),
+      TIMEOUT
+    )
+    .unwrap_err()
+    .is::<NodeUnavailable>());
+  }
+}
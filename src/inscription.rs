@@ -8,72 +8,225 @@ use {
     util::taproot::TAPROOT_ANNEX_PREFIX,
     Script, Witness,
   },
-  std::{iter::Peekable, str},
+  std::{
+    iter::{self, Peekable},
+    str,
+  },
 };
 
 const PROTOCOL_ID: &[u8] = b"ord";
 
 const BODY_TAG: &[u8] = &[];
 const CONTENT_TYPE_TAG: &[u8] = &[1];
+const POINTER_TAG: &[u8] = &[2];
+const PARENT_TAG: &[u8] = &[3];
+const METADATA_TAG: &[u8] = &[5];
+const METAPROTOCOL_TAG: &[u8] = &[7];
+const CONTENT_ENCODING_TAG: &[u8] = &[9];
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct Inscription {
+pub struct Inscription {
   body: Option<Vec<u8>>,
   content_type: Option<Vec<u8>>,
+  content_encoding: Option<Vec<u8>>,
+  metadata: Option<Vec<u8>>,
+  metaprotocol: Option<Vec<u8>>,
+  parent: Option<Vec<u8>>,
+  pointer: Option<Vec<u8>>,
 }
 
 impl Inscription {
-  #[cfg(test)]
-  pub(crate) fn new(content_type: Option<Vec<u8>>, body: Option<Vec<u8>>) -> Self {
-    Self { content_type, body }
+  pub fn new(content_type: Option<Vec<u8>>, body: Option<Vec<u8>>) -> Self {
+    Self {
+      content_type,
+      body,
+      content_encoding: None,
+      metadata: None,
+      metaprotocol: None,
+      parent: None,
+      pointer: None,
+    }
   }
 
   pub(crate) fn from_transaction(tx: &Transaction) -> Option<Inscription> {
     InscriptionParser::parse(&tx.input.get(0)?.witness).ok()
   }
 
-  pub(crate) fn from_content(
+  /// Every inscription packed into the script of the first input, in
+  /// envelope order, up to the first envelope that does not parse. The
+  /// first one is the one `from_transaction` returns.
+  pub(crate) fn all_from_transaction(tx: &Transaction) -> Vec<Inscription> {
+    Self::all_from_transaction_partially(tx)
+      .into_iter()
+      .map(|(inscription, _unrecognized)| inscription)
+      .collect()
+  }
+
+  /// Like `all_from_transaction`, along with the tags of fields each
+  /// inscription has that are not understood, which are dropped rather than
+  /// rejecting the inscription.
+  pub(crate) fn all_from_transaction_partially(
+    tx: &Transaction,
+  ) -> Vec<(Inscription, Vec<Vec<u8>>)> {
+    tx.input
+      .first()
+      .map(|input| InscriptionParser::parse_all(&input.witness))
+      .unwrap_or_default()
+  }
+
+  pub(crate) fn envelope_fields(tx: &Transaction) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, Error> {
+    let input = tx
+      .input
+      .first()
+      .ok_or_else(|| anyhow!("transaction has no inputs"))?;
+    InscriptionParser::parse_fields(&input.witness)
+      .map_err(|err| anyhow!("transaction has no inscription envelope: {err:?}"))
+  }
+
+  /// Inscribes `body` tagged with `content_type` as given, see
+  /// `Media::content_type_for_mint`. With `encoding` the content is
+  /// inscribed compressed, and refused if compressing does not make it
+  /// smaller.
+  pub fn from_content(
     chain: Chain,
-    extension: &str,
-    content: String,
+    content_type: &str,
+    mut body: Vec<u8>,
+    encoding: Option<ContentEncoding>,
   ) -> Result<Self, Error> {
-    let body = content.as_bytes().to_vec();
-
-    if let Some(limit) = chain.inscription_content_size_limit() {
-      let len = body.len();
-      if len > limit {
-        bail!("content size of {len} bytes exceeds {limit} byte limit for {chain} inscriptions");
+    if let Some(encoding) = encoding {
+      let compressed = encoding.compress(&body)?;
+      if compressed.len() >= body.len() {
+        bail!(
+          "compressing content with {encoding} does not shrink it, {} bytes compress to {}",
+          body.len(),
+          compressed.len()
+        );
       }
+      body = compressed;
     }
 
-    let content_type = Media::content_type_for_path(Path::new(extension))?;
+    Self::check_content_size(chain, body.len())?;
 
     Ok(Self {
       body: Some(body),
       content_type: Some(content_type.into()),
+      content_encoding: encoding.map(|encoding| encoding.to_string().into_bytes()),
+      metadata: None,
+      metaprotocol: None,
+      parent: None,
+      pointer: None,
     })
   }
 
-  pub(crate) fn from_file(chain: Chain, path: impl AsRef<Path>) -> Result<Self, Error> {
-    let path = path.as_ref();
+  /// Tags the inscription with `metadata`, encoded as CBOR, and the
+  /// `metaprotocol` it follows.
+  pub(crate) fn with_metadata(
+    mut self,
+    metadata: Option<&serde_json::Value>,
+    metaprotocol: Option<String>,
+  ) -> Self {
+    self.metadata = metadata.map(cbor::from_json);
+    self.metaprotocol = metaprotocol.map(String::into_bytes);
+    self
+  }
 
-    let body = fs::read(path).with_context(|| format!("io error reading {}", path.display()))?;
+  /// Tags the inscription as a child of `parent`, which only counts if the
+  /// reveal spends `parent` too. The id is the txid's bytes followed by the
+  /// index in little endian, trailing zeros dropped.
+  pub(crate) fn with_parent(mut self, parent: InscriptionId) -> Self {
+    let mut value = parent.txid.into_inner().to_vec();
+    let index = parent.index.to_le_bytes();
+    let len = index
+      .iter()
+      .rposition(|byte| *byte != 0)
+      .map_or(0, |i| i + 1);
+    value.extend_from_slice(&index[..len]);
+    self.parent = Some(value);
+    self
+  }
 
+  /// Refuses content of `len` bytes where `chain` limits inscription size.
+  pub fn check_content_size(chain: Chain, len: usize) -> Result<(), Error> {
     if let Some(limit) = chain.inscription_content_size_limit() {
-      let len = body.len();
       if len > limit {
         bail!("content size of {len} bytes exceeds {limit} byte limit for {chain} inscriptions");
       }
     }
+    Ok(())
+  }
+
+  pub(crate) fn from_file(chain: Chain, path: impl AsRef<Path>) -> Result<Self, Error> {
+    let path = path.as_ref();
+
+    let body = fs::read(path).with_context(|| format!("io error reading {}", path.display()))?;
+
+    Self::check_content_size(chain, body.len())?;
 
     let content_type = Media::content_type_for_path(path)?;
 
     Ok(Self {
       body: Some(body),
       content_type: Some(content_type.into()),
+      content_encoding: None,
+      metadata: None,
+      metaprotocol: None,
+      parent: None,
+      pointer: None,
     })
   }
 
+  /// Points the inscription at the sat `pointer` sats into the reveal's
+  /// outputs instead of the first one, in little endian with trailing
+  /// zeros dropped.
+  pub(crate) fn with_pointer(mut self, pointer: u64) -> Self {
+    let bytes = pointer.to_le_bytes();
+    let len = bytes
+      .iter()
+      .rposition(|byte| *byte != 0)
+      .map_or(0, |i| i + 1);
+    self.pointer = Some(bytes[..len].to_vec());
+    self
+  }
+
+  /// The offset the inscription points to, ignored if it is longer than
+  /// eight bytes.
+  pub(crate) fn pointer(&self) -> Option<u64> {
+    let value = self.pointer.as_ref()?;
+    if value.len() > 8 {
+      return None;
+    }
+    let mut bytes = [0; 8];
+    bytes[..value.len()].copy_from_slice(value);
+    Some(u64::from_le_bytes(bytes))
+  }
+
+  /// Where the updater places the inscription in its `reveal`: on the sat
+  /// its pointer names, or the first one when the pointer is missing or
+  /// past the outputs.
+  pub(crate) fn satpoint_in(&self, reveal: &Transaction) -> Option<SatPoint> {
+    let output_value = reveal.output.iter().map(|txout| txout.value).sum::<u64>();
+    let offset = self
+      .pointer()
+      .filter(|pointer| *pointer < output_value)
+      .unwrap_or(0);
+
+    let mut start = 0;
+    for (vout, txout) in reveal.output.iter().enumerate() {
+      if offset < start + txout.value {
+        return Some(SatPoint {
+          outpoint: OutPoint {
+            txid: reveal.txid(),
+            vout: vout.try_into().unwrap(),
+          },
+          offset: offset - start,
+        });
+      }
+      start += txout.value;
+    }
+
+    None
+  }
+
   fn append_reveal_script_to_builder(&self, mut builder: script::Builder) -> script::Builder {
     builder = builder
       .push_opcode(opcodes::OP_FALSE)
@@ -86,6 +239,33 @@ impl Inscription {
         .push_slice(content_type);
     }
 
+    if let Some(pointer) = &self.pointer {
+      builder = builder.push_slice(POINTER_TAG).push_slice(pointer);
+    }
+
+    if let Some(parent) = &self.parent {
+      builder = builder.push_slice(PARENT_TAG).push_slice(parent);
+    }
+
+    if let Some(metadata) = &self.metadata {
+      // split over repeated tags, pushes are limited to 520 bytes
+      for chunk in metadata.chunks(520) {
+        builder = builder.push_slice(METADATA_TAG).push_slice(chunk);
+      }
+    }
+
+    if let Some(metaprotocol) = &self.metaprotocol {
+      builder = builder
+        .push_slice(METAPROTOCOL_TAG)
+        .push_slice(metaprotocol);
+    }
+
+    if let Some(content_encoding) = &self.content_encoding {
+      builder = builder
+        .push_slice(CONTENT_ENCODING_TAG)
+        .push_slice(content_encoding);
+    }
+
     if let Some(body) = &self.body {
       builder = builder.push_slice(BODY_TAG);
       for chunk in body.chunks(520) {
@@ -100,6 +280,19 @@ impl Inscription {
     self.append_reveal_script_to_builder(builder).into_script()
   }
 
+  /// Packs the envelopes of `inscriptions` one after another into a single
+  /// reveal script.
+  pub(crate) fn append_batch_reveal_script(
+    inscriptions: &[Inscription],
+    mut builder: script::Builder,
+  ) -> Script {
+    for inscription in inscriptions {
+      builder = inscription.append_reveal_script_to_builder(builder);
+    }
+
+    builder.into_script()
+  }
+
   pub(crate) fn media(&self) -> Media {
     if self.body.is_none() {
       return Media::Unknown;
@@ -150,7 +343,6 @@ enum InscriptionError {
   KeyPathSpend,
   NoInscription,
   Script(script::Error),
-  UnrecognizedEvenField,
 }
 
 type Result<T, E = InscriptionError> = std::result::Result<T, E>;
@@ -161,6 +353,48 @@ struct InscriptionParser<'a> {
 
 impl<'a> InscriptionParser<'a> {
   fn parse(witness: &Witness) -> Result<Inscription> {
+    Self::parse_partially(witness).map(|(inscription, _unrecognized)| inscription)
+  }
+
+  fn parse_partially(witness: &Witness) -> Result<(Inscription, Vec<Vec<u8>>)> {
+    let script = Self::tapscript(witness)?;
+
+    InscriptionParser {
+      instructions: Script::from(Vec::from(script)).instructions().peekable(),
+    }
+    .parse_script()
+  }
+
+  fn parse_all(witness: &Witness) -> Vec<(Inscription, Vec<Vec<u8>>)> {
+    let Ok(script) = Self::tapscript(witness) else {
+      return Vec::new();
+    };
+
+    let script = Script::from(Vec::from(script));
+
+    let mut parser = InscriptionParser {
+      instructions: script.instructions().peekable(),
+    };
+
+    iter::from_fn(|| parser.parse_script().ok()).collect()
+  }
+
+  fn parse_fields(witness: &Witness) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+    let script = Self::tapscript(witness)?;
+
+    InscriptionParser {
+      instructions: Script::from(Vec::from(script)).instructions().peekable(),
+    }
+    .parse_envelope()
+    .map(|fields| {
+      fields
+        .into_iter()
+        .map(|(tag, value)| (tag.to_vec(), value))
+        .collect()
+    })
+  }
+
+  fn tapscript(witness: &Witness) -> Result<&[u8]> {
     if witness.is_empty() {
       return Err(InscriptionError::EmptyWitness);
     }
@@ -178,28 +412,53 @@ impl<'a> InscriptionParser<'a> {
       return Err(InscriptionError::KeyPathSpend);
     }
 
-    let script = witness
-      .iter()
-      .nth(if annex {
-        witness.len() - 1
-      } else {
-        witness.len() - 2
-      })
-      .unwrap();
+    Ok(
+      witness
+        .iter()
+        .nth(if annex {
+          witness.len() - 1
+        } else {
+          witness.len() - 2
+        })
+        .unwrap(),
+    )
+  }
 
-    InscriptionParser {
-      instructions: Script::from(Vec::from(script)).instructions().peekable(),
-    }
-    .parse_script()
+  // fields added by later versions of the protocol, even ones included,
+  // must not keep an inscription out of the index, its known fields are
+  // still good
+  fn parse_script(&mut self) -> Result<(Inscription, Vec<Vec<u8>>)> {
+    let mut fields = self.parse_envelope()?;
+
+    let body = fields.remove(BODY_TAG);
+    let content_type = fields.remove(CONTENT_TYPE_TAG);
+    let content_encoding = fields.remove(CONTENT_ENCODING_TAG);
+    let metadata = fields.remove(METADATA_TAG);
+    let metaprotocol = fields.remove(METAPROTOCOL_TAG);
+    let parent = fields.remove(PARENT_TAG);
+    let pointer = fields.remove(POINTER_TAG);
+
+    Ok((
+      Inscription {
+        body,
+        content_type,
+        content_encoding,
+        metadata,
+        metaprotocol,
+        parent,
+        pointer,
+      },
+      fields.into_keys().map(<[u8]>::to_vec).collect(),
+    ))
   }
 
-  fn parse_script(mut self) -> Result<Inscription> {
+  fn parse_envelope(&mut self) -> Result<BTreeMap<&'a [u8], Vec<u8>>> {
     loop {
       let next = self.advance()?;
 
       if next == Instruction::PushBytes(&[]) {
-        if let Some(inscription) = self.parse_inscription()? {
-          return Ok(inscription);
+        if let Some(fields) = self.parse_inscription()? {
+          return Ok(fields);
         }
       }
     }
@@ -213,7 +472,7 @@ impl<'a> InscriptionParser<'a> {
       .map_err(InscriptionError::Script)
   }
 
-  fn parse_inscription(&mut self) -> Result<Option<Inscription>> {
+  fn parse_inscription(&mut self) -> Result<Option<BTreeMap<&'a [u8], Vec<u8>>>> {
     if self.advance()? == Instruction::Op(opcodes::all::OP_IF) {
       if !self.accept(Instruction::PushBytes(PROTOCOL_ID))? {
         return Err(InscriptionError::NoInscription);
@@ -232,28 +491,22 @@ impl<'a> InscriptionParser<'a> {
             break;
           }
           Instruction::PushBytes(tag) => {
-            if fields.contains_key(tag) {
-              return Err(InscriptionError::InvalidInscription);
+            let value = self.expect_push()?;
+            match fields.get_mut(tag) {
+              // newer fields, like metadata, are split over repeated tags
+              Some(existing) if tag != CONTENT_TYPE_TAG => existing.extend_from_slice(value),
+              Some(_) => return Err(InscriptionError::InvalidInscription),
+              None => {
+                fields.insert(tag, value.to_vec());
+              }
             }
-            fields.insert(tag, self.expect_push()?.to_vec());
           }
           Instruction::Op(opcodes::all::OP_ENDIF) => break,
           _ => return Err(InscriptionError::InvalidInscription),
         }
       }
 
-      let body = fields.remove(BODY_TAG);
-      let content_type = fields.remove(CONTENT_TYPE_TAG);
-
-      for tag in fields.keys() {
-        if let Some(lsb) = tag.first() {
-          if lsb % 2 == 0 {
-            return Err(InscriptionError::UnrecognizedEvenField);
-          }
-        }
-      }
-
-      return Ok(Some(Inscription { body, content_type }));
+      return Ok(Some(fields));
     }
 
     Ok(None)
@@ -380,7 +633,7 @@ mod tests {
         b"ord",
         &[1],
         b"text/plain;charset=utf-8",
-        &[3],
+        &[13],
         b"bar",
         &[],
         b"ord",
@@ -396,6 +649,11 @@ mod tests {
       Ok(Inscription {
         content_type: Some(b"text/plain;charset=utf-8".to_vec()),
         body: None,
+        content_encoding: None,
+        metadata: None,
+        metaprotocol: None,
+        parent: None,
+        pointer: None,
       }),
     );
   }
@@ -407,6 +665,11 @@ mod tests {
       Ok(Inscription {
         content_type: None,
         body: Some(b"foo".to_vec()),
+        content_encoding: None,
+        metadata: None,
+        metaprotocol: None,
+        parent: None,
+        pointer: None,
       }),
     );
   }
@@ -648,6 +911,77 @@ mod tests {
     );
   }
 
+  #[test]
+  fn extract_all_packed_envelopes() {
+    let inscriptions = vec![
+      inscription("foo", [1; 100]),
+      inscription("bar", [2; 100]).with_pointer(546),
+      inscription("baz", [3; 100]).with_pointer(1092),
+    ];
+
+    let script = Inscription::append_batch_reveal_script(&inscriptions, script::Builder::new());
+
+    let tx = Transaction {
+      version: 0,
+      lock_time: bitcoin::PackedLockTime(0),
+      input: vec![TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: Script::new(),
+        sequence: Sequence(0),
+        witness: Witness::from_vec(vec![script.into_bytes(), Vec::new()]),
+      }],
+      output: Vec::new(),
+    };
+
+    assert_eq!(Inscription::all_from_transaction(&tx), inscriptions);
+    assert_eq!(
+      Inscription::from_transaction(&tx),
+      Some(inscription("foo", [1; 100]))
+    );
+  }
+
+  #[test]
+  fn extract_packed_envelopes_up_to_the_first_invalid_one() {
+    let script = script::Builder::new()
+      .push_opcode(opcodes::OP_FALSE)
+      .push_opcode(opcodes::all::OP_IF)
+      .push_slice(b"ord")
+      .push_slice(&[])
+      .push_slice(b"foo")
+      .push_opcode(opcodes::all::OP_ENDIF)
+      .push_opcode(opcodes::OP_FALSE)
+      .push_opcode(opcodes::all::OP_IF)
+      .push_slice(b"ord")
+      .push_slice(&[1])
+      .push_opcode(opcodes::all::OP_ENDIF)
+      .push_opcode(opcodes::OP_FALSE)
+      .push_opcode(opcodes::all::OP_IF)
+      .push_slice(b"ord")
+      .push_slice(&[])
+      .push_slice(b"bar")
+      .push_opcode(opcodes::all::OP_ENDIF)
+      .into_script();
+
+    assert_eq!(
+      InscriptionParser::parse_all(&Witness::from_vec(vec![script.into_bytes(), Vec::new()])),
+      [(Inscription::new(None, Some(b"foo".to_vec())), Vec::new())],
+    );
+  }
+
+  #[test]
+  fn pointer_round_trips_through_the_envelope() {
+    for (pointer, encoded) in [(0, Vec::new()), (10000, vec![0x10, 0x27])] {
+      let inscription = inscription("text/plain", "ord").with_pointer(pointer);
+
+      let fields = InscriptionParser::parse_fields(&inscription.to_witness()).unwrap();
+      assert_eq!(fields[POINTER_TAG], encoded);
+
+      let parsed = InscriptionParser::parse(&inscription.to_witness()).unwrap();
+      assert_eq!(parsed.pointer(), Some(pointer));
+      assert_eq!(parsed, inscription);
+    }
+  }
+
   #[test]
   fn inscribe_png() {
     assert_eq!(
@@ -721,6 +1055,142 @@ mod tests {
     );
   }
 
+  #[test]
+  fn compressed_content_round_trips() {
+    let content = r#"{"name":"ordinal","attributes":[]}"#.repeat(30);
+
+    for encoding in [ContentEncoding::Br, ContentEncoding::Gzip] {
+      let inscription = Inscription::from_content(
+        Chain::Mainnet,
+        "application/json",
+        content.clone().into_bytes(),
+        Some(encoding),
+      )
+      .unwrap();
+
+      let parsed = InscriptionParser::parse(&inscription.to_witness()).unwrap();
+      assert_eq!(parsed, inscription);
+      assert_eq!(
+        parsed.content_encoding,
+        Some(encoding.to_string().into_bytes())
+      );
+      assert!(parsed.content_length().unwrap() < content.len());
+      assert_eq!(
+        encoding.decompress(parsed.body().unwrap()).unwrap(),
+        content.as_bytes()
+      );
+    }
+  }
+
+  #[test]
+  fn compression_must_shrink_content() {
+    assert_regex_match!(
+      Inscription::from_content(
+        Chain::Mainnet,
+        "text/plain",
+        "ord".into(),
+        Some(ContentEncoding::Gzip)
+      )
+      .unwrap_err()
+      .to_string(),
+      r"compressing content with gzip does not shrink it, 3 bytes compress to \d+"
+    );
+  }
+
+  #[test]
+  fn content_is_not_compressed_by_default() {
+    assert_eq!(
+      Inscription::from_content(Chain::Mainnet, "text/plain", "ord".into(), None).unwrap(),
+      inscription("text/plain", "ord")
+    );
+  }
+
+  #[test]
+  fn reveal_script_embeds_content_type_verbatim() {
+    let content_type = "text/markdown; charset=utf-8";
+    let inscription =
+      Inscription::from_content(Chain::Mainnet, content_type, "# ord".into(), None).unwrap();
+
+    let script = inscription.append_reveal_script(script::Builder::new());
+    assert!(script
+      .instructions()
+      .any(|instruction| instruction.unwrap() == Instruction::PushBytes(content_type.as_bytes())));
+
+    let mut witness = Witness::new();
+    witness.push(&script);
+    witness.push([]);
+    assert_eq!(
+      InscriptionParser::parse(&witness).unwrap().content_type(),
+      Some(content_type)
+    );
+  }
+
+  #[test]
+  fn metadata_and_metaprotocol_round_trip() {
+    let metadata = serde_json::json!({
+      "name": "ordinal",
+      "attributes": [{"trait": "x".repeat(600)}],
+    });
+    let inscription = Inscription::from_content(Chain::Mainnet, "text/plain", "ord".into(), None)
+      .unwrap()
+      .with_metadata(Some(&metadata), Some("brc-20".into()));
+
+    let fields = InscriptionParser::parse_fields(&inscription.to_witness()).unwrap();
+    assert_eq!(fields[METADATA_TAG], cbor::from_json(&metadata));
+    assert_eq!(fields[METAPROTOCOL_TAG], b"brc-20");
+
+    assert_eq!(
+      InscriptionParser::parse_partially(&inscription.to_witness()).unwrap(),
+      (inscription, Vec::new())
+    );
+  }
+
+  #[test]
+  fn metadata_is_split_over_repeated_tags() {
+    let metadata = serde_json::json!("x".repeat(1000));
+    let script = inscription("text/plain", "ord")
+      .with_metadata(Some(&metadata), None)
+      .append_reveal_script(script::Builder::new());
+
+    assert_eq!(
+      script
+        .instructions()
+        .filter(|instruction| *instruction.as_ref().unwrap() == Instruction::PushBytes(METADATA_TAG))
+        .count(),
+      2
+    );
+  }
+
+  #[test]
+  fn parent_tag_round_trips_through_the_envelope() {
+    for index in [0, 1, 256] {
+      let parent = InscriptionId {
+        txid: txid(1),
+        index,
+      };
+      let inscription = inscription("text/plain", "ord").with_parent(parent);
+
+      let mut tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::PackedLockTime::ZERO,
+        input: vec![tx_in(outpoint(1))],
+        output: Vec::new(),
+      };
+      tx.input[0].witness = inscription.to_witness();
+
+      assert_eq!(
+        crate::envelope::Envelope::from_transaction(&tx)
+          .unwrap()
+          .parent,
+        Some(parent.to_string())
+      );
+      assert_eq!(
+        InscriptionParser::parse(&inscription.to_witness()).unwrap(),
+        inscription
+      );
+    }
+  }
+
   #[test]
   fn round_trip_with_no_fields() {
     let mut witness = Witness::new();
@@ -729,6 +1199,11 @@ mod tests {
       &Inscription {
         content_type: None,
         body: None,
+        content_encoding: None,
+        metadata: None,
+        metaprotocol: None,
+        parent: None,
+        pointer: None,
       }
       .append_reveal_script(script::Builder::new()),
     );
@@ -740,6 +1215,11 @@ mod tests {
       Inscription {
         content_type: None,
         body: None,
+        content_encoding: None,
+        metadata: None,
+        metaprotocol: None,
+        parent: None,
+        pointer: None,
       }
     );
   }
@@ -747,19 +1227,72 @@ mod tests {
   #[test]
   fn unknown_odd_fields_are_ignored() {
     assert_eq!(
-      InscriptionParser::parse(&envelope(&[b"ord", &[3], &[0]])),
+      InscriptionParser::parse(&envelope(&[b"ord", &[13], &[0]])),
       Ok(Inscription {
         content_type: None,
         body: None,
+        content_encoding: None,
+        metadata: None,
+        metaprotocol: None,
+        parent: None,
+        pointer: None,
       }),
     );
   }
 
   #[test]
-  fn unknown_even_fields_are_invalid() {
+  fn unknown_even_fields_are_ignored() {
+    assert_eq!(
+      InscriptionParser::parse_partially(&envelope(&[
+        b"ord",
+        &[1],
+        b"text/plain;charset=utf-8",
+        &[4],
+        &[0],
+        &[],
+        b"ord",
+      ])),
+      Ok((
+        inscription("text/plain;charset=utf-8", "ord"),
+        vec![vec![4]]
+      )),
+    );
+  }
+
+  #[test]
+  fn repeated_unknown_fields_are_ignored() {
     assert_eq!(
-      InscriptionParser::parse(&envelope(&[b"ord", &[2], &[0]])),
-      Err(InscriptionError::UnrecognizedEvenField),
+      InscriptionParser::parse_partially(&envelope(&[
+        b"ord",
+        &[11],
+        b"foo",
+        &[11],
+        b"bar",
+        &[1],
+        b"text/plain;charset=utf-8",
+        &[],
+        b"ord",
+      ])),
+      Ok((
+        inscription("text/plain;charset=utf-8", "ord"),
+        vec![vec![11]]
+      )),
+    );
+  }
+
+  #[test]
+  fn known_fields_are_not_reported_as_unknown() {
+    assert_eq!(
+      InscriptionParser::parse_partially(&envelope(&[
+        b"ord",
+        &[1],
+        b"text/plain;charset=utf-8",
+        &[],
+        b"ord",
+      ]))
+      .unwrap()
+      .1,
+      Vec::<Vec<u8>>::new(),
     );
   }
 }
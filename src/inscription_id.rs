@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Debug, PartialEq, Copy, Clone, Hash, Eq)]
+#[derive(Debug, PartialEq, Copy, Clone, Hash, Eq, PartialOrd, Ord)]
 pub struct InscriptionId {
   pub(crate) txid: Txid,
   pub(crate) index: u32,
@@ -85,6 +85,58 @@ impl FromStr for InscriptionId {
   }
 }
 
+impl InscriptionId {
+  pub(crate) const ACCEPTED_FORMATS: &'static str =
+    "`<TXID>i<INDEX>`, `<TXID>:<INDEX>` or `<TXID>`";
+
+  pub(crate) fn is_txid_prefixed(s: &str) -> bool {
+    s.get(..64)
+      .map(|txid| txid.chars().all(|c| c.is_ascii_hexdigit()))
+      .unwrap_or(false)
+  }
+
+  /// Parse an inscription id as sent by API clients. Besides the canonical
+  /// `<TXID>i<INDEX>` form this accepts `<TXID>:<INDEX>`, read as
+  /// `<TXID>i<INDEX>`, and a bare `<TXID>`, which refers to index 0. Anything
+  /// else is rejected rather than guessed.
+  pub fn parse_normalized(s: &str) -> Result<Self, Error> {
+    let s = s.trim();
+
+    let invalid = || {
+      anyhow!(
+        "invalid inscription id `{s}`, expected one of {}",
+        Self::ACCEPTED_FORMATS
+      )
+    };
+
+    if !Self::is_txid_prefixed(s) {
+      return Err(invalid());
+    }
+
+    let (txid, rest) = s.split_at(64);
+
+    let index = if rest.is_empty() {
+      0
+    } else {
+      let index = rest
+        .strip_prefix('i')
+        .or_else(|| rest.strip_prefix(':'))
+        .ok_or_else(invalid)?;
+
+      if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+      }
+
+      index.parse().map_err(|_| invalid())?
+    };
+
+    Ok(Self {
+      txid: txid.parse().map_err(|_| invalid())?,
+      index,
+    })
+  }
+}
+
 impl From<Txid> for InscriptionId {
   fn from(txid: Txid) -> Self {
     Self { txid, index: 0 }
@@ -177,6 +229,53 @@ mod tests {
     );
   }
 
+  #[test]
+  fn parse_normalized() {
+    let hex = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    for (input, index) in [
+      (format!("{hex}i1"), 1),
+      (format!("{hex}i0"), 0),
+      (format!("{hex}:1"), 1),
+      (format!("{hex}:0"), 0),
+      (hex.to_string(), 0),
+      (format!(" {hex}i1 "), 1),
+      (format!("{hex}i4294967295"), 0xFFFFFFFF),
+    ] {
+      assert_eq!(
+        InscriptionId::parse_normalized(&input).unwrap(),
+        InscriptionId {
+          txid: txid(1),
+          index,
+        },
+        "{input}",
+      );
+    }
+  }
+
+  #[test]
+  fn parse_normalized_rejects_ambiguous_ids() {
+    let hex = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    for input in [
+      format!("{hex}i"),
+      format!("{hex}:"),
+      format!("{hex}x1"),
+      format!("{hex}i-1"),
+      format!("{hex}i+1"),
+      format!("{hex}:0:0"),
+      format!("{hex}i4294967296"),
+      hex[1..].to_string(),
+      "foo".to_string(),
+      "→".repeat(64),
+    ] {
+      assert_regex_match!(
+        InscriptionId::parse_normalized(&input).unwrap_err(),
+        "invalid inscription id `.*`, expected one of .*",
+      );
+    }
+  }
+
   #[test]
   fn from_str_bad_txid() {
     assert_matches!(
@@ -20,9 +20,7 @@ use {
     deserialize_from_str::DeserializeFromStr,
     epoch::Epoch,
     height::Height,
-    index::{Index, List},
-    inscription::Inscription,
-    inscription_id::InscriptionId,
+    index::{Index, List, SharedIndex},
     media::Media,
     options::Options,
     outgoing::Outgoing,
@@ -62,7 +60,7 @@ use {
     process::{self, Command},
     str::FromStr,
     sync::{
-      atomic::{self, AtomicBool},
+      atomic::{self, AtomicBool, AtomicU64},
       Arc, Mutex,
     },
     thread,
@@ -73,7 +71,14 @@ use {
 };
 
 pub use crate::{
-  fee_rate::FeeRate, object::Object, rarity::Rarity, sat::Sat, sat_point::SatPoint,
+  content_encoding::{ContentEncoding, ContentFormat},
+  fee_rate::FeeRate,
+  inscription::Inscription,
+  inscription_id::InscriptionId,
+  object::Object,
+  rarity::Rarity,
+  sat::Sat,
+  sat_point::SatPoint,
   subcommand::wallet::transaction_builder::TransactionBuilder,
 };
 
@@ -96,17 +101,25 @@ macro_rules! tprintln {
 
 mod arguments;
 mod blocktime;
+pub mod builder;
+mod cbor;
 pub mod chain;
 mod config;
+mod content_encoding;
 mod decimal;
 mod degree;
 mod deserialize_from_str;
+pub mod envelope;
 mod epoch;
 mod fee_rate;
+#[cfg(test)]
+mod fixtures;
 mod height;
 pub mod index;
 mod inscription;
 mod inscription_id;
+pub mod logging;
+pub mod manifest;
 mod media;
 mod object;
 pub mod options;
@@ -114,8 +127,12 @@ pub mod outgoing;
 mod page_config;
 mod rarity;
 mod representation;
+pub mod reveal_key;
+pub mod rpc_failover;
 mod sat;
 mod sat_point;
+pub mod scenario;
+pub mod signer;
 pub mod subcommand;
 mod tally;
 mod templates;
@@ -149,7 +166,7 @@ fn unbound_outpoint() -> OutPoint {
 }
 
 pub fn main() {
-  env_logger::init();
+  logging::init_cli().expect("Error setting logger");
 
   ctrlc::set_handler(move || {
     LISTENERS
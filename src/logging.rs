@@ -0,0 +1,67 @@
+use {
+  super::*,
+  tracing_subscriber::{fmt, EnvFilter},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+  Json,
+  Text,
+}
+
+impl FromStr for LogFormat {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "json" => Ok(Self::Json),
+      "text" => Ok(Self::Text),
+      _ => bail!("invalid log format `{s}`, expected json or text"),
+    }
+  }
+}
+
+/// Installs the global subscriber for the server, sync and reorg binaries.
+/// `level` takes precedence over `RUST_LOG`, and both accept per-module
+/// directives such as `info,ord::index=debug`. Records from the `log` crate
+/// are forwarded, so they pick up the current span too.
+pub fn init(format: LogFormat, level: Option<&str>) -> Result {
+  let filter = match level {
+    Some(level) => EnvFilter::try_new(level)?,
+    None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+  };
+
+  let builder = fmt().with_env_filter(filter);
+
+  match format {
+    LogFormat::Json => builder
+      .json()
+      .flatten_event(true)
+      .with_current_span(true)
+      .try_init(),
+    LogFormat::Text => builder.try_init(),
+  }
+  .map_err(|err| anyhow!(err))
+}
+
+/// Installs the global subscriber for the ord CLI, which keeps stdout for
+/// command output. Only errors are logged unless `RUST_LOG` says otherwise.
+pub fn init_cli() -> Result {
+  fmt()
+    .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error")))
+    .with_writer(io::stderr)
+    .try_init()
+    .map_err(|err| anyhow!(err))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn log_format_from_str() {
+    assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    assert_eq!("text".parse::<LogFormat>().unwrap(), LogFormat::Text);
+    assert!("yaml".parse::<LogFormat>().is_err());
+  }
+}
@@ -0,0 +1,325 @@
+use {
+  super::*,
+  bitcoin::{
+    consensus::encode::deserialize,
+    hashes::{
+      hex::{FromHex, ToHex},
+      hmac, sha256, HashEngine,
+    },
+    psbt::Psbt,
+  },
+};
+
+/// What an output of a built transaction is for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputRole {
+  Destination,
+  Change,
+  ServiceFee,
+  Commit,
+  OpReturn,
+  Other,
+}
+
+/// Scripts known from the request, used to give outputs their roles.
+#[derive(Debug, Default)]
+pub struct Roles {
+  pub destination: Option<Script>,
+  pub change: Option<Script>,
+  pub service: Option<Script>,
+}
+
+impl Roles {
+  // a build may pay the service and the destination with the same script, the
+  // service fee wins since it is the one auditors ask about
+  fn role(&self, script_pubkey: &Script) -> OutputRole {
+    let is = |script: &Option<Script>| script.as_ref() == Some(script_pubkey);
+
+    if script_pubkey.is_op_return() {
+      OutputRole::OpReturn
+    } else if is(&self.service) {
+      OutputRole::ServiceFee
+    } else if is(&self.destination) {
+      OutputRole::Destination
+    } else if is(&self.change) {
+      OutputRole::Change
+    } else if script_pubkey.is_v1_p2tr() {
+      OutputRole::Commit
+    } else {
+      OutputRole::Other
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestInput {
+  pub outpoint: OutPoint,
+  pub value: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestOutput {
+  pub value: u64,
+  pub script_pubkey: String,
+  pub role: OutputRole,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestTransaction {
+  pub txid: Txid,
+  pub inputs: Vec<ManifestInput>,
+  pub outputs: Vec<ManifestOutput>,
+}
+
+impl ManifestTransaction {
+  /// Reads a built psbt or raw transaction. Psbt inputs carry their values,
+  /// raw transaction inputs do not.
+  pub fn from_hex(hex: &str, roles: &Roles) -> Result<Self> {
+    let bytes = Vec::from_hex(hex)?;
+    let (tx, values) = match deserialize::<Psbt>(&bytes) {
+      Ok(psbt) => {
+        let values = psbt
+          .inputs
+          .iter()
+          .map(|input| input.witness_utxo.as_ref().map(|txout| txout.value))
+          .collect();
+        (psbt.unsigned_tx, values)
+      }
+      Err(_) => {
+        let tx = deserialize::<Transaction>(&bytes)?;
+        let values = vec![None; tx.input.len()];
+        (tx, values)
+      }
+    };
+
+    Ok(Self {
+      txid: tx.txid(),
+      inputs: tx
+        .input
+        .iter()
+        .zip(values)
+        .map(|(input, value)| ManifestInput {
+          outpoint: input.previous_output,
+          value,
+        })
+        .collect(),
+      outputs: tx
+        .output
+        .iter()
+        .map(|output| ManifestOutput {
+          value: output.value,
+          script_pubkey: output.script_pubkey.to_hex(),
+          role: roles.role(&output.script_pubkey),
+        })
+        .collect(),
+    })
+  }
+}
+
+/// Everything the HMAC covers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestBody {
+  pub method: String,
+  pub request_sha256: String,
+  pub transactions: Vec<ManifestTransaction>,
+  pub fees: BTreeMap<String, u64>,
+  pub index_height: Option<u64>,
+  pub server_version: String,
+  pub timestamp: u64,
+}
+
+impl ManifestBody {
+  pub fn new(
+    method: &str,
+    request: &[u8],
+    transactions: Vec<ManifestTransaction>,
+    fees: BTreeMap<String, u64>,
+    index_height: Option<u64>,
+  ) -> Result<Self> {
+    Ok(Self {
+      method: method.into(),
+      request_sha256: sha256::Hash::hash(request).to_hex(),
+      transactions,
+      fees,
+      index_height,
+      server_version: env!("CARGO_PKG_VERSION").into(),
+      timestamp: SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs(),
+    })
+  }
+
+  /// Compact JSON with object keys sorted, so the same body always hashes
+  /// the same regardless of field order in the archive.
+  pub fn canonical_json(&self) -> Result<String> {
+    Ok(serde_json::to_value(self)?.to_string())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+  pub body: ManifestBody,
+  pub hmac: String,
+}
+
+impl Manifest {
+  pub fn sign(body: ManifestBody, key: &[u8]) -> Result<Self> {
+    let hmac = Self::hmac(&body, key)?;
+    Ok(Self { body, hmac })
+  }
+
+  pub fn verify(&self, key: &[u8]) -> Result<bool> {
+    let expected = Self::hmac(&self.body, key)?;
+    Ok(
+      expected.len() == self.hmac.len()
+        && expected
+          .bytes()
+          .zip(self.hmac.to_lowercase().bytes())
+          .fold(0, |diff, (a, b)| diff | (a ^ b))
+          == 0,
+    )
+  }
+
+  fn hmac(body: &ManifestBody, key: &[u8]) -> Result<String> {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(key);
+    engine.input(body.canonical_json()?.as_bytes());
+    Ok(hmac::Hmac::<sha256::Hash>::from_engine(engine).to_hex())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    bitcoin::{consensus::encode::serialize_hex, PackedLockTime},
+  };
+
+  fn roles() -> Roles {
+    Roles {
+      destination: Some(recipient().script_pubkey()),
+      change: Some(change(0).script_pubkey()),
+      service: Some(change(1).script_pubkey()),
+    }
+  }
+
+  fn body() -> ManifestBody {
+    ManifestBody {
+      method: "transfer".into(),
+      request_sha256: sha256::Hash::hash(b"{}").to_hex(),
+      transactions: Vec::new(),
+      fees: [("network_fee".into(), 300)].into(),
+      index_height: Some(800000),
+      server_version: "0.5.1".into(),
+      timestamp: 1700000000,
+    }
+  }
+
+  #[test]
+  fn canonical_json_sorts_keys() {
+    assert_eq!(
+      body().canonical_json().unwrap(),
+      format!(
+        "{{\"fees\":{{\"network_fee\":300}},\"index_height\":800000,\"method\":\"transfer\",\
+         \"request_sha256\":\"{}\",\"server_version\":\"0.5.1\",\"timestamp\":1700000000,\
+         \"transactions\":[]}}",
+        sha256::Hash::hash(b"{}").to_hex()
+      )
+    );
+  }
+
+  #[test]
+  fn hmac_covers_canonical_json() {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(b"key");
+    engine.input(body().canonical_json().unwrap().as_bytes());
+
+    assert_eq!(
+      Manifest::sign(body(), b"key").unwrap().hmac,
+      hmac::Hmac::<sha256::Hash>::from_engine(engine).to_hex()
+    );
+  }
+
+  #[test]
+  fn verify() {
+    let manifest = Manifest::sign(body(), b"key").unwrap();
+    assert!(manifest.verify(b"key").unwrap());
+    assert!(!manifest.verify(b"other key").unwrap());
+
+    let mut tampered = manifest.clone();
+    tampered.body.fees.insert("network_fee".into(), 1);
+    assert!(!tampered.verify(b"key").unwrap());
+
+    let mut truncated = manifest;
+    truncated.hmac.pop();
+    assert!(!truncated.verify(b"key").unwrap());
+  }
+
+  #[test]
+  fn survives_round_trip() {
+    let manifest = Manifest::sign(body(), b"key").unwrap();
+    let archived: Manifest =
+      serde_json::from_str(&serde_json::to_string_pretty(&manifest).unwrap()).unwrap();
+    assert!(archived.verify(b"key").unwrap());
+  }
+
+  #[test]
+  fn output_roles() {
+    let roles = roles();
+    assert_eq!(
+      roles.role(&recipient().script_pubkey()),
+      OutputRole::Destination
+    );
+    assert_eq!(roles.role(&change(0).script_pubkey()), OutputRole::Change);
+    assert_eq!(
+      roles.role(&change(1).script_pubkey()),
+      OutputRole::ServiceFee
+    );
+    assert_eq!(
+      roles.role(&Script::new_op_return(b"ord")),
+      OutputRole::OpReturn
+    );
+    assert_eq!(roles.role(&address().script_pubkey()), OutputRole::Other);
+  }
+
+  #[test]
+  fn psbt_inputs_carry_values() {
+    let tx = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1))],
+      output: vec![
+        tx_out(1000, recipient()),
+        tx_out(2000, change(0)),
+        tx_out(3000, change(1)),
+      ],
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+    psbt.inputs[0].witness_utxo = Some(tx_out(7000, change(0)));
+
+    let manifest = ManifestTransaction::from_hex(&serialize_hex(&psbt), &roles()).unwrap();
+    assert_eq!(manifest.txid, tx.txid());
+    assert_eq!(
+      manifest.inputs,
+      [ManifestInput {
+        outpoint: outpoint(1),
+        value: Some(7000),
+      }]
+    );
+    assert_eq!(
+      manifest
+        .outputs
+        .iter()
+        .map(|output| (output.value, output.role))
+        .collect::<Vec<(u64, OutputRole)>>(),
+      [
+        (1000, OutputRole::Destination),
+        (2000, OutputRole::Change),
+        (3000, OutputRole::ServiceFee),
+      ]
+    );
+
+    let raw = ManifestTransaction::from_hex(&serialize_hex(&tx), &roles()).unwrap();
+    assert_eq!(raw.inputs[0].value, None);
+    assert_eq!(raw.outputs, manifest.outputs);
+  }
+}
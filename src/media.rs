@@ -71,6 +71,71 @@ impl Media {
     ))
   }
 
+  /// The content type a mint inscribes: `content_type` verbatim if given,
+  /// otherwise the one for `extension`, `txt` by default.
+  pub(crate) fn content_type_for_mint(
+    content_type: Option<&str>,
+    extension: Option<&str>,
+  ) -> Result<String, Error> {
+    match content_type {
+      Some(content_type) => {
+        Self::check_content_type(content_type)?;
+        Ok(content_type.into())
+      }
+      None => Ok(
+        Self::content_type_for_path(Path::new(&format!(
+          "data.{}",
+          extension.unwrap_or("txt").trim_start_matches('.')
+        )))?
+        .into(),
+      ),
+    }
+  }
+
+  /// Checks `content_type` is a MIME type, `type/subtype` followed by any
+  /// number of `;name=value` parameters, per RFC 2045.
+  pub(crate) fn check_content_type(content_type: &str) -> Result<(), Error> {
+    fn is_token(s: &str) -> bool {
+      !s.is_empty()
+        && s
+          .chars()
+          .all(|c| c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?=".contains(c))
+    }
+
+    fn is_quoted_string(s: &str) -> bool {
+      s.len() >= 2
+        && s.starts_with('"')
+        && s.ends_with('"')
+        && s[1..s.len() - 1]
+          .chars()
+          .all(|c| c == ' ' || c == '\t' || c.is_ascii_graphic())
+    }
+
+    let mut parts = content_type.split(';');
+
+    let valid = !content_type
+      .chars()
+      .any(|c| c.is_ascii_control() && c != '\t')
+      && parts
+        .next()
+        .and_then(|media_type| media_type.trim().split_once('/'))
+        .map_or(false, |(kind, subtype)| is_token(kind) && is_token(subtype))
+      && parts.all(|parameter| {
+        parameter
+          .trim()
+          .split_once('=')
+          .map_or(false, |(name, value)| {
+            is_token(name) && (is_token(value) || is_quoted_string(value))
+          })
+      });
+
+    if !valid {
+      bail!("invalid content type `{content_type}`, expected <TYPE>/<SUBTYPE> with optional ;<NAME>=<VALUE> parameters");
+    }
+
+    Ok(())
+  }
+
   pub(crate) fn check_mp4_codec(path: &Path) -> Result<(), Error> {
     let f = File::open(path)?;
     let size = f.metadata()?.len();
@@ -132,6 +197,56 @@ mod tests {
     );
   }
 
+  #[test]
+  fn mint_content_type_defaults_to_extension() {
+    assert_eq!(
+      Media::content_type_for_mint(None, None).unwrap(),
+      "text/plain"
+    );
+    assert_eq!(
+      Media::content_type_for_mint(None, Some(".json")).unwrap(),
+      "application/json"
+    );
+    assert_eq!(
+      Media::content_type_for_mint(Some("text/markdown;charset=utf-8"), Some("foo")).unwrap(),
+      "text/markdown;charset=utf-8"
+    );
+  }
+
+  #[test]
+  fn content_types_must_be_mime_types() {
+    for content_type in [
+      "text/plain",
+      "image/svg+xml",
+      "application/vnd.api+json",
+      "text/html;charset=utf-8",
+      "text/plain; charset=utf-8; format=flowed",
+      "multipart/mixed; boundary=\"a b\"",
+    ] {
+      assert!(
+        Media::check_content_type(content_type).is_ok(),
+        "{content_type}"
+      );
+    }
+
+    for content_type in [
+      "",
+      "text",
+      "text/",
+      "/plain",
+      "text/plain/extra",
+      "text/pl ain",
+      "text/plain;",
+      "text/plain; charset",
+      "text/plain\n",
+    ] {
+      assert_regex_match!(
+        Media::check_content_type(content_type).unwrap_err(),
+        "invalid content type .*"
+      );
+    }
+  }
+
   #[test]
   fn h264_in_mp4_is_allowed() {
     assert!(Media::check_mp4_codec(Path::new("examples/h264.mp4")).is_ok(),);
@@ -1,4 +1,4 @@
-use {super::*, bitcoincore_rpc::Auth};
+use {super::*, bitcoin::AddressType, bitcoincore_rpc::Auth};
 
 #[derive(Clone, Default, Debug, Parser)]
 #[clap(group(
@@ -7,6 +7,17 @@ use {super::*, bitcoincore_rpc::Auth};
     .args(&["chain-argument", "signet", "regtest", "testnet"]),
 ))]
 pub struct Options {
+  #[clap(
+    long,
+    help = "Mark an index that does not record its chain as belonging to the configured chain."
+  )]
+  pub adopt_data_dir: bool,
+  #[clap(
+    long,
+    use_value_delimiter = true,
+    help = "Only build outputs to <ALLOWED_DESTINATION_TYPES>, a comma separated list of p2pkh, p2sh, p2wpkh, p2wsh and p2tr. Every type is allowed when unset."
+  )]
+  pub allowed_destination_types: Vec<AddressType>,
   #[clap(long, help = "Load Bitcoin Core data dir from <BITCOIN_DATA_DIR>.")]
   pub bitcoin_data_dir: Option<PathBuf>,
   #[clap(long, help = "Authenticate to Bitcoin Core RPC with <RPC_PASS>.")]
@@ -41,8 +52,13 @@ pub struct Options {
   pub index_sats: bool,
   #[clap(long, short, help = "Use regtest. Equivalent to `--chain regtest`.")]
   pub regtest: bool,
-  #[clap(long, help = "Connect to Bitcoin Core RPC at <RPC_URL>.")]
-  pub rpc_url: Option<String>,
+  #[clap(
+    long,
+    help = "Connect to Bitcoin Core RPC at <RPC_URL>. May be repeated, later endpoints are failed over to in order when earlier ones are unreachable or in initial block download."
+  )]
+  pub rpc_url: Vec<String>,
+  #[clap(skip)]
+  pub shared_index: Option<SharedIndex>,
   #[clap(long, short, help = "Use signet. Equivalent to `--chain signet`.")]
   pub signet: bool,
   #[clap(long, short, help = "Use testnet. Equivalent to `--chain testnet`.")]
@@ -51,6 +67,30 @@ pub struct Options {
   pub wallet: String,
 }
 
+/// Why an RPC endpoint cannot serve. Only errors are not failed over, as
+/// they come from the configuration rather than the endpoint.
+pub(crate) enum RpcUnusable {
+  Unreachable(String),
+  InitialBlockDownload(Client),
+  Error(Error),
+}
+
+impl Display for RpcUnusable {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      Self::Unreachable(err) => write!(f, "unreachable: {err}"),
+      Self::InitialBlockDownload(_) => write!(f, "in initial block download"),
+      Self::Error(err) => write!(f, "{err}"),
+    }
+  }
+}
+
+impl From<Error> for RpcUnusable {
+  fn from(err: Error) -> Self {
+    Self::Error(err)
+  }
+}
+
 impl Options {
   pub fn chain(&self) -> Chain {
     if self.signet {
@@ -76,14 +116,18 @@ impl Options {
     }
   }
 
+  /// The endpoint currently serving, the first one unless failed over.
   pub fn rpc_url(&self) -> String {
-    self.rpc_url.clone().unwrap_or_else(|| {
-      format!(
-        "127.0.0.1:{}/wallet/{}",
-        self.chain().default_rpc_port(),
-        self.wallet
-      )
-    })
+    rpc_failover::active(&self.rpc_url)
+      .or(self.rpc_url.first())
+      .cloned()
+      .unwrap_or_else(|| {
+        format!(
+          "127.0.0.1:{}/wallet/{}",
+          self.chain().default_rpc_port(),
+          self.wallet
+        )
+      })
   }
 
   pub fn cookie_file(&self) -> Result<PathBuf> {
@@ -190,11 +234,70 @@ impl Options {
   }
 
   pub fn bitcoin_rpc_client(&self) -> Result<Client> {
-    let rpc_url = self.rpc_url();
+    Ok(self.pinned_rpc_client()?.1)
+  }
+
+  /// A client for the endpoint currently serving, failing over to the next
+  /// configured one when it is unreachable or in initial block download,
+  /// along with these options pinned to that endpoint. Everything built
+  /// from the pinned options talks to the same node, so a single build
+  /// never mixes UTXO or mempool views from different nodes.
+  pub fn pinned_rpc_client(&self) -> Result<(Options, Client)> {
+    let active = self.rpc_url();
+    let candidates = std::iter::once(active.clone())
+      .chain(self.rpc_url.iter().filter(|url| **url != active).cloned());
+
+    // a node in initial block download still serves when no other can
+    let mut syncing = None;
+    let mut reasons = Vec::new();
+    for rpc_url in candidates {
+      match self.connect_rpc(&rpc_url) {
+        Ok(client) => return Ok(self.pin_rpc_url(&active, rpc_url, client, &reasons)),
+        Err(RpcUnusable::Error(err)) => return Err(err),
+        Err(RpcUnusable::InitialBlockDownload(client)) => {
+          reasons.push(format!("{rpc_url} is in initial block download"));
+          syncing.get_or_insert((rpc_url, client));
+        }
+        Err(RpcUnusable::Unreachable(err)) => {
+          reasons.push(format!("{rpc_url} is unreachable: {err}"));
+        }
+      }
+      log::warn!(
+        "Bitcoin Core RPC endpoint unusable: {}",
+        reasons.last().unwrap()
+      );
+    }
 
+    match syncing {
+      Some((rpc_url, client)) => Ok(self.pin_rpc_url(&active, rpc_url, client, &reasons)),
+      None => Err(anyhow!(
+        "failed to connect to Bitcoin Core RPC: {}",
+        reasons.join(", ")
+      )),
+    }
+  }
+
+  fn pin_rpc_url(
+    &self,
+    active: &str,
+    rpc_url: String,
+    client: Client,
+    reasons: &[String],
+  ) -> (Options, Client) {
+    if rpc_url != active {
+      rpc_failover::fail_over(active, &rpc_url, &reasons.join(", "));
+    }
+    let mut pinned = self.clone();
+    if !self.rpc_url.is_empty() {
+      pinned.rpc_url = vec![rpc_url];
+    }
+    (pinned, client)
+  }
+
+  pub(crate) fn connect_rpc(&self, rpc_url: &str) -> Result<Client, RpcUnusable> {
     let auth = self.auth()?;
 
-    log::info!("Connecting to Bitcoin Core at {}", self.rpc_url());
+    log::info!("Connecting to Bitcoin Core at {rpc_url}");
 
     if let Auth::CookieFile(cookie_file) = &auth {
       log::info!(
@@ -203,21 +306,33 @@ impl Options {
       );
     }
 
-    let client = Client::new(&rpc_url, auth)
+    let client = Client::new(rpc_url, auth)
       .with_context(|| format!("failed to connect to Bitcoin Core RPC at {rpc_url}"))?;
 
-    let rpc_chain = match client.get_blockchain_info()?.chain.as_str() {
+    let blockchain_info = match client.get_blockchain_info() {
+      Ok(blockchain_info) => blockchain_info,
+      Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Transport(err))) => {
+        return Err(RpcUnusable::Unreachable(err.to_string()))
+      }
+      Err(err) => return Err(RpcUnusable::Error(err.into())),
+    };
+
+    let rpc_chain = match blockchain_info.chain.as_str() {
       "main" => Chain::Mainnet,
       "test" => Chain::Testnet,
       "regtest" => Chain::Regtest,
       "signet" => Chain::Signet,
-      other => bail!("Bitcoin RPC server on unknown chain: {other}"),
+      other => return Err(anyhow!("Bitcoin RPC server on unknown chain: {other}").into()),
     };
 
     let ord_chain = self.chain();
 
     if rpc_chain != ord_chain {
-      bail!("Bitcoin RPC server is on {rpc_chain} but ord is on {ord_chain}");
+      return Err(anyhow!("Bitcoin RPC server is on {rpc_chain} but ord is on {ord_chain}").into());
+    }
+
+    if blockchain_info.initial_block_download {
+      return Err(RpcUnusable::InitialBlockDownload(client));
     }
 
     Ok(client)
@@ -1,9 +1,11 @@
-use super::*;
+use {super::*, crate::subcommand::wallet::error::BuildError};
 
 #[derive(Debug, PartialEq)]
 pub enum Outgoing {
   Amount(Amount),
   InscriptionId(InscriptionId),
+  /// An inscription number as shown by explorers, written `#<NUMBER>`.
+  InscriptionNumber(u64),
   SatPoint(SatPoint),
 }
 
@@ -11,10 +13,18 @@ impl FromStr for Outgoing {
   type Err = Error;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    Ok(if s.contains(':') {
+    let s = s.trim();
+
+    Ok(if s.matches(':').count() == 2 {
       Self::SatPoint(s.parse()?)
-    } else if s.len() >= 66 {
-      Self::InscriptionId(s.parse()?)
+    } else if let Some(number) = s.strip_prefix('#') {
+      Self::InscriptionNumber(
+        number
+          .parse()
+          .map_err(|_| anyhow!("invalid inscription number `{s}`"))?,
+      )
+    } else if InscriptionId::is_txid_prefixed(s) {
+      Self::InscriptionId(InscriptionId::parse_normalized(s)?)
     } else if s.contains(' ') {
       Self::Amount(s.parse()?)
     } else if let Some(i) = s.find(|c: char| c.is_alphabetic()) {
@@ -27,6 +37,33 @@ impl FromStr for Outgoing {
   }
 }
 
+impl Outgoing {
+  /// `outgoings` with every inscription number replaced by the id `lookup`
+  /// finds for it. Fails with all the numbers `lookup` finds nothing for.
+  pub fn resolve_numbers(
+    outgoings: Vec<Self>,
+    lookup: impl Fn(u64) -> Result<Option<InscriptionId>>,
+  ) -> Result<Vec<Self>> {
+    let mut resolved = Vec::new();
+    let mut missing = Vec::new();
+    for outgoing in outgoings {
+      match outgoing {
+        Self::InscriptionNumber(number) => match lookup(number)? {
+          Some(id) => resolved.push(Self::InscriptionId(id)),
+          None => missing.push(number),
+        },
+        outgoing => resolved.push(outgoing),
+      }
+    }
+
+    if !missing.is_empty() {
+      return Err(BuildError::InscriptionNumbersNotFound(missing).into());
+    }
+
+    Ok(resolved)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -66,5 +103,97 @@ mod tests {
     );
 
     assert!("0".parse::<Outgoing>().is_err());
+
+    assert_eq!(
+      "#123456".parse::<Outgoing>().unwrap(),
+      Outgoing::InscriptionNumber(123456),
+    );
+
+    assert_regex_match!(
+      "#-1".parse::<Outgoing>().unwrap_err(),
+      "invalid inscription number `#-1`",
+    );
+  }
+
+  #[test]
+  fn resolve_numbers() {
+    let lookup = |number: u64| -> Result<Option<InscriptionId>> {
+      Ok((number < 2).then(|| inscription_id(number.try_into().unwrap())))
+    };
+
+    assert_eq!(
+      Outgoing::resolve_numbers(
+        vec![
+          Outgoing::InscriptionNumber(1),
+          Outgoing::SatPoint(satpoint(1, 0)),
+        ],
+        lookup,
+      )
+      .unwrap(),
+      vec![
+        Outgoing::InscriptionId(inscription_id(1)),
+        Outgoing::SatPoint(satpoint(1, 0)),
+      ],
+    );
+
+    assert_eq!(
+      Outgoing::resolve_numbers(
+        vec![
+          Outgoing::InscriptionNumber(2),
+          Outgoing::InscriptionNumber(0),
+          Outgoing::InscriptionNumber(3),
+        ],
+        lookup,
+      )
+      .unwrap_err()
+      .downcast::<BuildError>()
+      .unwrap(),
+      BuildError::InscriptionNumbersNotFound(vec![2, 3]),
+    );
+  }
+
+  #[test]
+  fn parse_inscription_id_variants() {
+    let hex = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    for (input, expected) in [
+      (format!("{hex}i1"), inscription_id(1)),
+      (format!("{hex}:1"), inscription_id(1)),
+      (
+        hex.to_string(),
+        InscriptionId {
+          txid: txid(1),
+          index: 0,
+        },
+      ),
+    ] {
+      assert_eq!(
+        input.parse::<Outgoing>().unwrap(),
+        Outgoing::InscriptionId(expected),
+        "{input}",
+      );
+    }
+
+    assert_eq!(
+      format!("{hex}:1:0").parse::<Outgoing>().unwrap(),
+      Outgoing::SatPoint(satpoint(1, 0)),
+    );
+  }
+
+  #[test]
+  fn parse_rejects_ambiguous_inscription_ids() {
+    let hex = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    for input in [
+      format!("{hex}i"),
+      format!("{hex}x1"),
+      format!("{hex}1"),
+      format!("{hex}:"),
+    ] {
+      assert_regex_match!(
+        input.parse::<Outgoing>().unwrap_err(),
+        "invalid inscription id `.*`, expected one of .*",
+      );
+    }
   }
 }
@@ -1,15 +1,14 @@
 use bitcoin::Network;
-use clap::{Arg, Command};
-use log::{error, info};
+use clap::{Arg, ArgAction, Command};
 use ord::chain::Chain;
 use ord::index::{Index, MysqlDatabase};
+use ord::logging::{self, LogFormat};
 use ord::options::Options;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tracing::{error, info};
 
 fn main() {
-  std::env::set_var("RUST_LOG", "info");
-  env_logger::init();
   let args = Command::new("Reorg")
     .arg(
       Arg::new("chain")
@@ -42,11 +41,20 @@ fn main() {
         .takes_value(true)
         .help("Store index in <DATA_DIR>."),
     )
+    .arg(
+      Arg::new("adopt-data-dir")
+        .long("adopt-data-dir")
+        .takes_value(false)
+        .help(
+          "Mark an index or mysql database that does not record its chain as belonging to <CHAIN>.",
+        ),
+    )
     .arg(
       Arg::new("rpc-url")
         .long("rpc-url")
         .takes_value(true)
-        .help("Connect to Bitcoin Core RPC at <RPC_URL>."),
+        .action(ArgAction::Append)
+        .help("Connect to Bitcoin Core RPC at <RPC_URL>. May be repeated, later endpoints are failed over to in order."),
     )
     .arg(
       Arg::new("mysql-host")
@@ -71,9 +79,31 @@ fn main() {
         .long("target-height")
         .takes_value(true)
         .help("Target height."),
+    )
+    .arg(
+      Arg::new("log-format")
+        .long("log-format")
+        .takes_value(true)
+        .default_value("text")
+        .help("Write logs as <LOG_FORMAT>, one of json or text."),
+    )
+    .arg(
+      Arg::new("log-level")
+        .long("log-level")
+        .takes_value(true)
+        .help("Log at <LOG_LEVEL>, e.g. `info,ord::index=debug`. Overrides RUST_LOG."),
     );
 
   let matches = args.get_matches();
+  logging::init(
+    matches
+      .get_one::<String>("log-format")
+      .unwrap()
+      .parse::<LogFormat>()
+      .unwrap(),
+    matches.get_one::<String>("log-level").map(|s| s.as_str()),
+  )
+  .unwrap();
   let chain = matches
     .get_one::<String>("chain")
     .map(|s| s.as_str())
@@ -103,11 +133,16 @@ fn main() {
 
   let data_dir: Option<PathBuf> = matches.get_one::<String>("data-dir").map(|s| s.into());
 
+  let adopt_data_dir = matches.contains_id("adopt-data-dir");
+
   let mysql_host = matches.get_one::<String>("mysql-host").cloned();
   let mysql_username = matches.get_one::<String>("mysql-username").cloned();
   let mysql_password = matches.get_one::<String>("mysql-password").cloned();
 
-  let rpc_url = matches.get_one::<String>("rpc-url").cloned();
+  let rpc_url: Vec<String> = matches
+    .get_many::<String>("rpc-url")
+    .map(|urls| urls.cloned().collect())
+    .unwrap_or_default();
 
   let target_height: u64 = matches
     .get_one::<String>("target-height")
@@ -115,6 +150,8 @@ fn main() {
     .unwrap();
 
   let options = Options {
+    adopt_data_dir,
+    allowed_destination_types: Vec::new(),
     bitcoin_data_dir,
     bitcoin_rpc_pass,
     bitcoin_rpc_user,
@@ -129,6 +166,7 @@ fn main() {
     index_sats: false,
     regtest: false,
     rpc_url,
+    shared_index: None,
     signet: false,
     testnet: false,
     wallet: "ord".to_string(),
@@ -139,9 +177,9 @@ fn main() {
     None
   } else {
     info!("Use mysql...");
-    Some(Arc::new(
-      MysqlDatabase::new(mysql_host, mysql_username, mysql_password, network).unwrap(),
-    ))
+    let database = MysqlDatabase::new(mysql_host, mysql_username, mysql_password, network).unwrap();
+    database.check_network(adopt_data_dir).unwrap();
+    Some(Arc::new(database))
   };
 
   let open_result = if let Some(db) = database {
@@ -153,13 +191,13 @@ fn main() {
   match open_result {
     Ok(index) => {
       if let Err(e) = index.reorg_height(target_height) {
-        error!("Index reorg error:{e}")
+        error!(error = %e, target_height, "Index reorg error")
       } else {
-        info!("Index reorg success")
+        info!(target_height, "Index reorg success")
       }
     }
     Err(e) => {
-      error!("Index reorg error:{e}")
+      error!(error = %e, target_height, "Index reorg error")
     }
   }
 }
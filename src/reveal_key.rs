@@ -0,0 +1,197 @@
+//! Reveal signing keys that can be recovered after the build.
+//!
+//! Each build signs its reveals with a throwaway key, so a stuck reveal can
+//! normally not be re-signed. With a server secret configured, the key is
+//! instead derived from that secret and a random per-commit salt, and only
+//! the salt is stored. The database alone is not enough to sign anything.
+//!
+//! Anyone holding both the secret and the stored salt of a commit can sign
+//! spends of its outputs until the reveal confirms. Keep the secret off the
+//! database host. Rotating it makes reveals built under the old secret
+//! impossible to bump, it never affects reveals already signed.
+//!
+//! A caller may instead supply a seed of its own, from which the key is
+//! derived and with which reveals are signed without auxiliary randomness,
+//! so the same request always builds the same commit addresses and
+//! byte-identical reveals. Seeds must be unique per request, mints sharing
+//! a seed share a reveal key, and anyone who learns the seed can spend the
+//! commit outputs until the reveals confirm.
+
+use {
+  super::*,
+  bitcoin::{
+    hashes::{
+      hex::{FromHex, ToHex},
+      hmac, sha256, HashEngine,
+    },
+    schnorr::UntweakedKeyPair,
+    secp256k1::{rand, rand::RngCore, Secp256k1},
+  },
+};
+
+pub struct RevealKeys {
+  secret: Vec<u8>,
+}
+
+impl fmt::Debug for RevealKeys {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    f.write_str("RevealKeys")
+  }
+}
+
+impl RevealKeys {
+  pub fn new(secret: String) -> Self {
+    Self {
+      secret: secret.into_bytes(),
+    }
+  }
+
+  /// A fresh salt, hex encoded for storage.
+  pub fn salt() -> String {
+    let mut salt = [0; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt.to_hex()
+  }
+
+  pub fn key_pair(&self, salt: &str) -> Result<UntweakedKeyPair> {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(&self.secret);
+    engine.input(salt.as_bytes());
+    let key = hmac::Hmac::<sha256::Hash>::from_engine(engine);
+
+    Ok(UntweakedKeyPair::from_seckey_slice(
+      &Secp256k1::new(),
+      key.as_inner(),
+    )?)
+  }
+}
+
+/// 32 bytes a caller derives its reveal key from, see the module docs.
+#[derive(Clone, Copy, PartialEq)]
+pub struct RevealSeed([u8; 32]);
+
+impl fmt::Debug for RevealSeed {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    f.write_str("RevealSeed")
+  }
+}
+
+impl FromStr for RevealSeed {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let seed = <[u8; 32]>::try_from(
+      Vec::from_hex(s)
+        .map_err(|err| anyhow!("invalid reveal seed: {err}"))?
+        .as_slice(),
+    )
+    .map_err(|_| anyhow!("reveal seed must be 32 bytes of hex"))?;
+
+    if seed.iter().all(|byte| *byte == seed[0]) {
+      bail!("reveal seed repeats a single byte, use 32 random bytes");
+    }
+
+    Ok(Self(seed))
+  }
+}
+
+impl<'de> Deserialize<'de> for RevealSeed {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Ok(DeserializeFromStr::deserialize(deserializer)?.0)
+  }
+}
+
+impl Serialize for RevealSeed {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.serialize_str(&self.0.to_hex())
+  }
+}
+
+impl RevealSeed {
+  // keeps seed derived keys apart from any other use of the same bytes
+  const DOMAIN: &'static [u8] = b"ord reveal seed";
+
+  pub fn key_pair(&self) -> Result<UntweakedKeyPair> {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(Self::DOMAIN);
+    engine.input(&self.0);
+    let key = hmac::Hmac::<sha256::Hash>::from_engine(engine);
+
+    Ok(UntweakedKeyPair::from_seckey_slice(
+      &Secp256k1::new(),
+      key.as_inner(),
+    )?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keys_depend_on_secret_and_salt() {
+    let keys = RevealKeys::new("secret".into());
+    let salt = RevealKeys::salt();
+
+    assert_eq!(keys.key_pair(&salt).unwrap(), keys.key_pair(&salt).unwrap());
+    assert_ne!(
+      keys.key_pair(&salt).unwrap(),
+      keys.key_pair(&RevealKeys::salt()).unwrap()
+    );
+    assert_ne!(
+      keys.key_pair(&salt).unwrap(),
+      RevealKeys::new("other".into()).key_pair(&salt).unwrap()
+    );
+  }
+
+  #[test]
+  fn seeds_derive_the_same_key() {
+    let seed = "01".repeat(31) + "02";
+    assert_eq!(
+      seed.parse::<RevealSeed>().unwrap().key_pair().unwrap(),
+      seed.parse::<RevealSeed>().unwrap().key_pair().unwrap()
+    );
+    assert_ne!(
+      seed.parse::<RevealSeed>().unwrap().key_pair().unwrap(),
+      ("01".repeat(31) + "03")
+        .parse::<RevealSeed>()
+        .unwrap()
+        .key_pair()
+        .unwrap()
+    );
+  }
+
+  #[test]
+  fn weak_seeds_are_rejected() {
+    assert!("00".repeat(32).parse::<RevealSeed>().is_err());
+    assert!("ff".repeat(32).parse::<RevealSeed>().is_err());
+    assert!("01".repeat(31).parse::<RevealSeed>().is_err());
+    assert!("zz".repeat(32).parse::<RevealSeed>().is_err());
+  }
+
+  #[test]
+  fn seeds_round_trip_through_json() {
+    let seed = "01".repeat(31) + "02";
+    assert_eq!(
+      serde_json::to_string(&seed.parse::<RevealSeed>().unwrap()).unwrap(),
+      format!("\"{seed}\"")
+    );
+    assert_eq!(
+      serde_json::from_str::<RevealSeed>(&format!("\"{seed}\"")).unwrap(),
+      seed.parse().unwrap()
+    );
+    assert!(serde_json::from_str::<RevealSeed>(&format!("\"{}\"", "00".repeat(32))).is_err());
+  }
+
+  #[test]
+  fn debug_hides_secret() {
+    assert_eq!(
+      format!("{:?}", RevealKeys::new("secret".into())),
+      "RevealKeys"
+    );
+  }
+}
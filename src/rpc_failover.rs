@@ -0,0 +1,134 @@
+use super::*;
+
+static STATE: Mutex<Failover> = Mutex::new(Failover {
+  active: None,
+  failovers: 0,
+  failbacks: 0,
+});
+
+struct Failover {
+  active: Option<String>,
+  failovers: u64,
+  failbacks: u64,
+}
+
+/// Which Bitcoin Core RPC endpoint is serving, and how often it changed
+/// since startup. `active` is unset while the primary has always served.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RpcEndpointStatus {
+  pub active: Option<String>,
+  pub failovers: u64,
+  pub failbacks: u64,
+}
+
+pub fn rpc_endpoint_status() -> RpcEndpointStatus {
+  let state = STATE.lock().unwrap();
+  RpcEndpointStatus {
+    active: state.active.clone(),
+    failovers: state.failovers,
+    failbacks: state.failbacks,
+  }
+}
+
+// the active endpoint if it is one of `urls`, so options pinned to a single
+// endpoint keep talking to it
+pub(crate) fn active(urls: &[String]) -> Option<&String> {
+  let state = STATE.lock().unwrap();
+  let active = state.active.as_ref()?;
+  urls.iter().find(|url| *url == active)
+}
+
+pub(crate) fn fail_over(from: &str, to: &str, reason: &str) {
+  let mut state = STATE.lock().unwrap();
+  state.active = Some(to.into());
+  state.failovers += 1;
+  tracing::warn!(
+    from,
+    to,
+    reason,
+    failovers = state.failovers,
+    "Bitcoin Core RPC failover"
+  );
+}
+
+fn fail_back(from: &str, to: &str) {
+  let mut state = STATE.lock().unwrap();
+  state.active = Some(to.into());
+  state.failbacks += 1;
+  tracing::info!(
+    from,
+    to,
+    failbacks = state.failbacks,
+    "Bitcoin Core RPC failback"
+  );
+}
+
+/// Checks the primary endpoint every `interval` while a secondary serves,
+/// and fails back to it once it answers and has left initial block
+/// download. Does nothing with a single endpoint.
+pub fn spawn_failback(options: Options, interval: Duration) {
+  if options.rpc_url.len() < 2 {
+    return;
+  }
+
+  thread::spawn(move || loop {
+    thread::sleep(interval);
+
+    let primary = &options.rpc_url[0];
+    let active = options.rpc_url();
+    if active == *primary {
+      continue;
+    }
+
+    match options.connect_rpc(primary) {
+      Ok(_) => fail_back(&active, primary),
+      Err(err) => tracing::debug!(%primary, %err, "Bitcoin Core RPC primary still unusable"),
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fails_over_to_secondary_and_back() {
+    let rpc_server = test_bitcoincore_rpc::builder()
+      .network(Network::Regtest)
+      .build();
+
+    let tempdir = TempDir::new().unwrap();
+    let cookie_file = tempdir.path().join("cookie");
+    fs::write(&cookie_file, "username:password").unwrap();
+
+    let port = TcpListener::bind("127.0.0.1:0")
+      .unwrap()
+      .local_addr()
+      .unwrap()
+      .port();
+    let primary = format!("127.0.0.1:{port}");
+
+    let options = Options::try_parse_from([
+      "ord".into(),
+      "--rpc-url".into(),
+      primary.clone(),
+      "--rpc-url".into(),
+      rpc_server.url(),
+      "--cookie-file".into(),
+      cookie_file.display().to_string(),
+      "--regtest".into(),
+    ])
+    .unwrap();
+
+    assert_eq!(options.rpc_url(), primary);
+
+    let (pinned, _client) = options.pinned_rpc_client().unwrap();
+    assert_eq!(pinned.rpc_url, vec![rpc_server.url()]);
+    assert_eq!(options.rpc_url(), rpc_server.url());
+    assert_eq!(active(&options.rpc_url), Some(&rpc_server.url()));
+    assert_eq!(active(std::slice::from_ref(&primary)), None);
+
+    fail_back(&rpc_server.url(), &primary);
+    assert_eq!(options.rpc_url(), primary);
+  }
+}
@@ -0,0 +1,595 @@
+//! Dry runs of the builders from a JSON scenario, with no node, index or
+//! database.
+//!
+//! A scenario is a wallet and the operations built from it, in order:
+//!
+//! ```json
+//! {
+//!   "network": "testnet",
+//!   "key_seed": "0101010101010101010101010101010101010101010101010101010101010101",
+//!   "source": "tb1q...",
+//!   "utxos": [{ "outpoint": "<TXID>:<VOUT>", "value": 50000 }],
+//!   "inscriptions": [{ "satpoint": "<TXID>:<VOUT>:<OFFSET>", "id": "<TXID>i0" }],
+//!   "operations": [
+//!     { "op": "mint", "destination": "tb1q...", "content": "hello", "repeat": 2, "fee_rate": 5.0 },
+//!     { "op": "mints", "destination": "tb1q...", "contents": ["foo", "bar"], "fee_rate": 5.0 },
+//!     { "op": "transfer", "destination": "tb1q...", "postages": [{ "satpoint": "...", "amount": 10000 }], "fee_rate": 3.0 },
+//!     { "op": "cancel", "inputs": ["<TXID>:<VOUT>"], "fee_rate": 4.0 }
+//!   ]
+//! }
+//! ```
+//!
+//! `key_seed` is the 32 byte secret reveals are signed with, `[1; 32]` when
+//! unset, so a scenario always builds the same transactions. `source` funds
+//! every operation and receives its change, and its type sets how inputs
+//! are sized. Each operation spends from what the earlier ones left, and
+//! inscriptions minted to `source` can be transferred later on.
+//!
+//! `mint` and `mints` take `content_type` (`text/plain;charset=utf-8`),
+//! `postage` (546), `service_address` and `service_fee` (none),
+//! `commit_fee_rate` and `reveal_fee_rate` (`fee_rate`). `transfer` takes
+//! `additional_value` (0) and `op_return`. `cancel` takes `service_address`
+//! and `service_fee`.
+
+use {
+  super::*,
+  crate::builder::{
+    self, CancelParams, CommitOutputBreakdown, CommitParams, KeySource, TransferParams,
+  },
+  bitcoin::{
+    consensus::encode::serialize_hex, schnorr::UntweakedKeyPair, secp256k1::Secp256k1, AddressType,
+  },
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+  pub network: Network,
+  #[serde(default)]
+  pub key_seed: Option<String>,
+  pub source: Address,
+  pub utxos: Vec<Utxo>,
+  #[serde(default)]
+  pub inscriptions: Vec<Holding>,
+  pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Utxo {
+  pub outpoint: OutPoint,
+  pub value: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Holding {
+  pub satpoint: SatPoint,
+  pub id: InscriptionId,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Postage {
+  pub satpoint: SatPoint,
+  pub amount: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+  Mint {
+    destination: Address,
+    content: String,
+    #[serde(default = "default_content_type")]
+    content_type: String,
+    #[serde(default = "default_repeat")]
+    repeat: u64,
+    #[serde(flatten)]
+    fees: InscribeFees,
+  },
+  Mints {
+    destination: Address,
+    contents: Vec<String>,
+    #[serde(default = "default_content_type")]
+    content_type: String,
+    #[serde(flatten)]
+    fees: InscribeFees,
+  },
+  Transfer {
+    destination: Address,
+    postages: Vec<Postage>,
+    fee_rate: f64,
+    #[serde(default)]
+    additional_value: u64,
+    #[serde(default)]
+    op_return: Option<String>,
+  },
+  Cancel {
+    inputs: Vec<OutPoint>,
+    fee_rate: f64,
+    #[serde(default)]
+    service_address: Option<Address>,
+    #[serde(default)]
+    service_fee: u64,
+  },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InscribeFees {
+  pub fee_rate: f64,
+  #[serde(default)]
+  pub commit_fee_rate: Option<f64>,
+  #[serde(default)]
+  pub reveal_fee_rate: Option<f64>,
+  #[serde(default = "default_postage")]
+  pub postage: u64,
+  #[serde(default)]
+  pub service_address: Option<Address>,
+  #[serde(default)]
+  pub service_fee: u64,
+}
+
+fn default_content_type() -> String {
+  "text/plain;charset=utf-8".into()
+}
+
+fn default_repeat() -> u64 {
+  1
+}
+
+fn default_postage() -> u64 {
+  TransactionBuilder::TARGET_POSTAGE.to_sat()
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Report {
+  pub operations: Vec<OperationReport>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OperationReport {
+  pub op: &'static str,
+  pub transactions: Vec<TransactionReport>,
+  pub network_fee: u64,
+  pub service_fee: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub breakdown: Option<Vec<CommitOutputBreakdown>>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TransactionReport {
+  pub txid: Txid,
+  pub hex: String,
+  pub vsize: usize,
+  pub fee: u64,
+  pub outputs: Vec<OutputReport>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OutputReport {
+  pub value: u64,
+  pub address: Option<String>,
+  pub role: &'static str,
+}
+
+/// What the wallet holds between operations, and the value of every output
+/// seen so far, so fees of later transactions can be computed.
+struct State {
+  network: Network,
+  source: Address,
+  input_type: AddressType,
+  key_source: KeySource,
+  utxos: BTreeMap<OutPoint, Amount>,
+  inscriptions: BTreeMap<SatPoint, InscriptionId>,
+  values: BTreeMap<OutPoint, Amount>,
+}
+
+impl Scenario {
+  pub fn load(path: &Path) -> Result<Self> {
+    serde_json::from_str(
+      &fs::read_to_string(path)
+        .with_context(|| format!("failed to read scenario `{}`", path.display()))?,
+    )
+    .with_context(|| format!("invalid scenario `{}`", path.display()))
+  }
+
+  pub fn run(&self) -> Result<Report> {
+    if !self.source.is_valid_for_network(self.network) {
+      bail!("source {} is not valid for {}", self.source, self.network);
+    }
+
+    let seed = match &self.key_seed {
+      Some(seed) => {
+        let seed = hex::decode(seed).context("key_seed is not hex")?;
+        if seed.len() != 32 {
+          bail!("key_seed must be 32 bytes, got {}", seed.len());
+        }
+        seed
+      }
+      None => vec![1; 32],
+    };
+
+    let utxos = self
+      .utxos
+      .iter()
+      .map(|utxo| (utxo.outpoint, Amount::from_sat(utxo.value)))
+      .collect::<BTreeMap<OutPoint, Amount>>();
+
+    let mut state = State {
+      network: self.network,
+      source: self.source.clone(),
+      input_type: self
+        .source
+        .address_type()
+        .ok_or_else(|| anyhow!("source {} has no standard type", self.source))?,
+      key_source: KeySource::Fixed(UntweakedKeyPair::from_seckey_slice(
+        &Secp256k1::new(),
+        &seed,
+      )?),
+      values: utxos.clone(),
+      utxos,
+      inscriptions: self
+        .inscriptions
+        .iter()
+        .map(|holding| (holding.satpoint, holding.id))
+        .collect(),
+    };
+
+    let mut operations = vec![];
+    for (i, operation) in self.operations.iter().enumerate() {
+      operations.push(
+        state
+          .apply(operation)
+          .with_context(|| format!("operation {i} failed"))?,
+      );
+    }
+
+    Ok(Report { operations })
+  }
+}
+
+impl State {
+  fn apply(&mut self, operation: &Operation) -> Result<OperationReport> {
+    match operation {
+      Operation::Mint {
+        destination,
+        content,
+        content_type,
+        repeat,
+        fees,
+      } => self.inscribe(
+        "mint",
+        destination,
+        vec![inscription(content_type, content); usize::try_from(*repeat)?],
+        fees,
+      ),
+      Operation::Mints {
+        destination,
+        contents,
+        content_type,
+        fees,
+      } => self.inscribe(
+        "mints",
+        destination,
+        contents
+          .iter()
+          .map(|content| inscription(content_type, content))
+          .collect(),
+        fees,
+      ),
+      Operation::Transfer {
+        destination,
+        postages,
+        fee_rate,
+        additional_value,
+        op_return,
+      } => {
+        let transaction = builder::transfer(TransferParams {
+          input_type: self.input_type,
+          postages: postages
+            .iter()
+            .map(|postage| (postage.satpoint, Amount::from_sat(postage.amount)))
+            .collect(),
+          inscriptions: self.inscriptions.clone(),
+          utxos: self.utxos.clone(),
+          destination: destination.clone(),
+          destinations: Vec::new(),
+          additional_value: Amount::from_sat(*additional_value),
+          change: [self.source.clone(), self.source.clone()],
+          fee_rate: FeeRate::try_from(*fee_rate)?,
+          op_return: op_return.clone(),
+          locktime: None,
+          rbf: true,
+          witness_sizes: BTreeMap::new(),
+        })?;
+        let network_fee = builder::fee(&transaction, &self.values);
+        Ok(OperationReport {
+          op: "transfer",
+          transactions: vec![self.record(&transaction, Some(destination), None, false)],
+          network_fee,
+          service_fee: 0,
+          breakdown: None,
+        })
+      }
+      Operation::Cancel {
+        inputs,
+        fee_rate,
+        service_address,
+        service_fee,
+      } => {
+        let cancelled = builder::cancel(CancelParams {
+          input_type: self.input_type,
+          inputs: inputs.clone(),
+          utxos: self.values.clone(),
+          source: self.source.clone(),
+          service: service_address
+            .clone()
+            .map(|address| (address, Amount::from_sat(*service_fee))),
+          fee_rate: FeeRate::try_from(*fee_rate)?,
+          locktime: None,
+          rbf: true,
+          witness_sizes: BTreeMap::new(),
+        })?;
+        Ok(OperationReport {
+          op: "cancel",
+          transactions: vec![self.record(
+            &cancelled.transaction,
+            None,
+            service_address.as_ref(),
+            false,
+          )],
+          network_fee: cancelled.network_fee,
+          service_fee: cancelled.service_fee,
+          breakdown: None,
+        })
+      }
+    }
+  }
+
+  fn inscribe(
+    &mut self,
+    op: &'static str,
+    destination: &Address,
+    inscriptions: Vec<Inscription>,
+    fees: &InscribeFees,
+  ) -> Result<OperationReport> {
+    // without a service address the fee is paid back to the source, as the
+    // wallet commands do
+    let service_address = fees
+      .service_address
+      .clone()
+      .unwrap_or_else(|| self.source.clone());
+
+    let inscribed = builder::inscribe(
+      CommitParams {
+        input_type: self.input_type,
+        network: self.network,
+        satpoints: Vec::new(),
+        inscriptions: self.inscriptions.clone(),
+        utxos: self.utxos.clone(),
+        change: [self.source.clone(), self.source.clone()],
+        destination: destination.clone(),
+        groups: Vec::new(),
+        commit_fee_rate: FeeRate::try_from(fees.commit_fee_rate.unwrap_or(fees.fee_rate))?,
+        reveal_fee_rate: FeeRate::try_from(fees.reveal_fee_rate.unwrap_or(fees.fee_rate))?,
+        max_nonstandard_weight: None,
+        service_address: service_address.clone(),
+        service_fee: Amount::from_sat(fees.service_fee),
+        additional_service_fee: Amount::ZERO,
+        target_postage: Amount::from_sat(fees.postage),
+        recipients: Vec::new(),
+        parent: None,
+        key_source: self.key_source,
+      },
+      inscriptions,
+    )?;
+
+    let network_fee = builder::fee(&inscribed.commit, &self.values) + inscribed.network_fee;
+
+    let mut transactions = vec![self.record(
+      &inscribed.commit,
+      None,
+      fees.service_address.as_ref(),
+      false,
+    )];
+    for reveal in &inscribed.reveals {
+      transactions.push(self.record(
+        reveal,
+        Some(destination),
+        fees.service_address.as_ref(),
+        true,
+      ));
+    }
+
+    Ok(OperationReport {
+      op,
+      transactions,
+      network_fee,
+      service_fee: inscribed.service_fee,
+      breakdown: Some(inscribed.breakdown),
+    })
+  }
+
+  /// Spends the inputs of `transaction` from the wallet and adds the
+  /// outputs it pays to the source.
+  fn record(
+    &mut self,
+    transaction: &Transaction,
+    destination: Option<&Address>,
+    service_address: Option<&Address>,
+    reveal: bool,
+  ) -> TransactionReport {
+    let fee = builder::fee(transaction, &self.values);
+    let vsize = if reveal {
+      transaction.vsize()
+    } else {
+      builder::estimate_vsize(transaction, self.input_type)
+    };
+
+    for input in &transaction.input {
+      self.utxos.remove(&input.previous_output);
+      self
+        .inscriptions
+        .retain(|satpoint, _| satpoint.outpoint != input.previous_output);
+    }
+
+    let txid = transaction.txid();
+    let mut outputs = vec![];
+    for (vout, output) in transaction.output.iter().enumerate() {
+      let outpoint = OutPoint {
+        txid,
+        vout: u32::try_from(vout).unwrap(),
+      };
+      let value = Amount::from_sat(output.value);
+      self.values.insert(outpoint, value);
+
+      let role = if output.script_pubkey.is_op_return() {
+        "op_return"
+      } else if reveal && vout == 0 {
+        "inscription"
+      } else if service_address.map(|address| address.script_pubkey())
+        == Some(output.script_pubkey.clone())
+      {
+        "service"
+      } else if destination.map(|address| address.script_pubkey())
+        == Some(output.script_pubkey.clone())
+      {
+        "destination"
+      } else if output.script_pubkey == self.source.script_pubkey() {
+        "change"
+      } else if output.script_pubkey.is_v1_p2tr() && !reveal {
+        "commit"
+      } else {
+        "other"
+      };
+
+      if output.script_pubkey == self.source.script_pubkey() {
+        if role == "inscription" {
+          self.inscriptions.insert(
+            SatPoint {
+              outpoint,
+              offset: 0,
+            },
+            InscriptionId::from(txid),
+          );
+        }
+        self.utxos.insert(outpoint, value);
+      }
+
+      outputs.push(OutputReport {
+        value: output.value,
+        address: Address::from_script(&output.script_pubkey, self.network)
+          .map(|address| address.to_string())
+          .ok(),
+        role,
+      });
+    }
+
+    TransactionReport {
+      txid,
+      hex: serialize_hex(transaction),
+      vsize,
+      fee,
+      outputs,
+    }
+  }
+}
+
+fn inscription(content_type: &str, content: &str) -> Inscription {
+  Inscription::new(
+    Some(content_type.as_bytes().to_vec()),
+    Some(content.as_bytes().to_vec()),
+  )
+}
+
+/// The scenarios bundled with the crate, as `(name, path)`.
+#[cfg(test)]
+pub(crate) fn bundled() -> Result<Vec<(String, PathBuf)>> {
+  let mut scenarios = vec![];
+  for entry in fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scenarios"))? {
+    let path = entry?.path();
+    if path.extension() == Some("json".as_ref()) {
+      let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+      scenarios.push((name, path));
+    }
+  }
+  scenarios.sort();
+  Ok(scenarios)
+}
+
+impl Report {
+  pub fn transactions(&self) -> impl Iterator<Item = &TransactionReport> {
+    self
+      .operations
+      .iter()
+      .flat_map(|operation| operation.transactions.iter())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bundled_scenarios_run() {
+    let scenarios = bundled().unwrap();
+    assert!(scenarios.len() >= 5);
+    for (name, path) in scenarios {
+      let report = Scenario::load(&path)
+        .unwrap()
+        .run()
+        .unwrap_or_else(|err| panic!("scenario {name} failed: {err:#}"));
+      assert!(report.transactions().next().is_some(), "{name}");
+    }
+  }
+
+  #[test]
+  fn operations_spend_what_earlier_ones_left() {
+    let scenario: Scenario = serde_json::from_value(serde_json::json!({
+      "network": "testnet",
+      "source": change(0),
+      "utxos": [{ "outpoint": outpoint(1), "value": 100_000 }],
+      "operations": [
+        { "op": "mint", "destination": change(0), "content": "foo", "fee_rate": 2.0 },
+        { "op": "mint", "destination": recipient(), "content": "bar", "fee_rate": 2.0 },
+      ],
+    }))
+    .unwrap();
+
+    let report = scenario.run().unwrap();
+    let first = report.operations[0]
+      .transactions
+      .iter()
+      .map(|transaction| transaction.txid)
+      .collect::<Vec<Txid>>();
+    let second_commit: Transaction =
+      consensus::deserialize(&hex::decode(&report.operations[1].transactions[0].hex).unwrap())
+        .unwrap();
+
+    assert!(second_commit
+      .input
+      .iter()
+      .all(|input| first.contains(&input.previous_output.txid)));
+  }
+
+  #[test]
+  fn runs_are_reproducible() {
+    let (_, path) = bundled().unwrap().remove(0);
+    let scenario = Scenario::load(&path).unwrap();
+    assert_eq!(scenario.run().unwrap(), scenario.run().unwrap());
+  }
+
+  #[test]
+  fn invalid_seed() {
+    let scenario: Scenario = serde_json::from_value(serde_json::json!({
+      "network": "testnet",
+      "key_seed": "0101",
+      "source": change(0),
+      "utxos": [],
+      "operations": [],
+    }))
+    .unwrap();
+    assert!(scenario.run().is_err());
+  }
+}
@@ -0,0 +1,40 @@
+use clap::{Arg, Command};
+use ord::scenario::Scenario;
+use std::path::PathBuf;
+use std::process;
+
+fn main() {
+  let matches = Command::new("Scenario")
+    .about("Build the transactions of a JSON scenario without a node, index or database")
+    .arg(
+      Arg::new("file")
+        .required(true)
+        .takes_value(true)
+        .help("Read the scenario from <FILE>."),
+    )
+    .arg(
+      Arg::new("compact")
+        .long("compact")
+        .takes_value(false)
+        .help("Print the report on a single line."),
+    )
+    .get_matches();
+
+  let path = PathBuf::from(matches.get_one::<String>("file").unwrap());
+
+  let report = match Scenario::load(&path).and_then(|scenario| scenario.run()) {
+    Ok(report) => report,
+    Err(err) => {
+      eprintln!("error: {err:#}");
+      process::exit(1);
+    }
+  };
+
+  let output = if matches.contains_id("compact") {
+    serde_json::to_string(&report)
+  } else {
+    serde_json::to_string_pretty(&report)
+  };
+
+  println!("{}", output.unwrap());
+}
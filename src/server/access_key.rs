@@ -0,0 +1,224 @@
+use crate::api_key::{self, Brand};
+use crate::jsonrpc;
+use crate::key_policy::KeyPolicy;
+use anyhow::{anyhow, Error};
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Bearer tokens required on every non-admin POST once any are configured,
+/// each with the label logged for the requests it authenticates, the
+/// policy on the sources it may act for and optionally an OP_RETURN brand
+/// stamped into its transfers.
+#[derive(Debug, Default)]
+pub struct AccessKeys {
+  labels: BTreeMap<String, String>,
+  policies: BTreeMap<String, KeyPolicy>,
+  brands: BTreeMap<String, String>,
+}
+
+impl AccessKeys {
+  pub const MAX_BRAND_SIZE: usize = 16;
+
+  /// `keys` is a comma-separated list of `<LABEL>=<TOKEN>`, or the path of
+  /// a file with one per line. Blank lines and `#` comments are skipped.
+  /// A key may be followed by its policy, as whitespace-separated
+  /// `sources=<ADDRESS>,...` and `max_daily_sources=<COUNT>`. `brands` are
+  /// `<LABEL>=<PREFIX>` for some of those labels.
+  pub fn new(keys: Option<&str>, brands: &[String]) -> Result<Self, Error> {
+    let Some(keys) = keys else {
+      if let Some(brand) = brands.first() {
+        return Err(anyhow!("op_return brand `{brand}` requires --api-keys"));
+      }
+      return Ok(Self::default());
+    };
+
+    let entries = if Path::new(keys).is_file() {
+      fs::read_to_string(keys)
+        .map_err(|err| anyhow!("failed to read api keys from `{keys}`: {err}"))?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect::<Vec<String>>()
+    } else {
+      keys.split(',').map(|key| key.trim().to_owned()).collect()
+    };
+
+    let mut labels = BTreeMap::new();
+    let mut policies = BTreeMap::new();
+    for entry in &entries {
+      let mut fields = entry.split_whitespace();
+      let key = fields.next().unwrap_or_default();
+      api_key::insert_labels(&mut labels, "api key", [key])?;
+      let label = &labels[key.split_once('=').unwrap().1];
+      policies.insert(label.clone(), KeyPolicy::parse(label, fields)?);
+    }
+    if labels.is_empty() {
+      return Err(anyhow!("no api keys in `{keys}`"));
+    }
+
+    let mut prefixes = BTreeMap::new();
+    for brand in brands {
+      let (label, prefix) = brand
+        .split_once('=')
+        .filter(|(label, prefix)| !label.is_empty() && !prefix.is_empty())
+        .ok_or_else(|| anyhow!("invalid op_return brand `{brand}`, expected <LABEL>=<PREFIX>"))?;
+      if !policies.contains_key(label) {
+        return Err(anyhow!("op_return brand for unknown api key `{label}`"));
+      }
+      if prefix.len() > Self::MAX_BRAND_SIZE {
+        return Err(anyhow!(
+          "op_return brand of `{label}` is {} bytes, at most {} are allowed",
+          prefix.len(),
+          Self::MAX_BRAND_SIZE
+        ));
+      }
+      prefixes.insert(label.to_owned(), prefix.to_owned());
+    }
+
+    Ok(Self {
+      labels,
+      policies,
+      brands: prefixes,
+    })
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    !self.labels.is_empty()
+  }
+
+  pub fn label(&self, req: &Request<Body>) -> Option<&str> {
+    api_key::bearer_token(req)
+      .and_then(|token| self.labels.get(token))
+      .map(|label| label.as_str())
+  }
+
+  pub fn brand(&self, req: &Request<Body>) -> Option<Brand> {
+    let label = self.label(req)?;
+    self.brands.get(label).map(|prefix| Brand {
+      label: label.to_owned(),
+      prefix: prefix.clone(),
+    })
+  }
+
+  pub fn unauthorized() -> Response<Body> {
+    let body = json!({
+      "jsonrpc": "2.0",
+      "id": null,
+      "error": {
+        "code": jsonrpc::UNAUTHORIZED,
+        "message": "Missing or invalid api key",
+        "data": null,
+      },
+    });
+
+    Response::builder()
+      .status(StatusCode::UNAUTHORIZED)
+      .header("Content-Type", "application/json")
+      .body(Body::from(body.to_string()))
+      .unwrap()
+  }
+
+  pub fn policy(&self, label: &str) -> Option<&KeyPolicy> {
+    self.policies.get(label)
+  }
+
+  pub fn policies(&self) -> impl Iterator<Item = (String, KeyPolicy)> + '_ {
+    self
+      .policies
+      .iter()
+      .map(|(label, policy)| (label.clone(), policy.clone()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  fn request(token: &str) -> Request<Body> {
+    Request::builder()
+      .header("Authorization", format!("Bearer {token}"))
+      .body(Body::empty())
+      .unwrap()
+  }
+
+  #[test]
+  fn comma_separated() {
+    let keys = AccessKeys::new(Some("acme=hunter2, ops=swordfish"), &[]).unwrap();
+    assert!(keys.is_enabled());
+    assert_eq!(keys.label(&request("hunter2")), Some("acme"));
+    assert_eq!(keys.label(&request("swordfish")), Some("ops"));
+    assert_eq!(keys.label(&request("acme")), None);
+    assert_eq!(keys.label(&Request::new(Body::empty())), None);
+  }
+
+  #[test]
+  fn from_file() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("keys");
+    fs::write(&path, "# partners\nacme=hunter2\n\nops=swordfish\n").unwrap();
+
+    let keys = AccessKeys::new(Some(path.to_str().unwrap()), &[]).unwrap();
+    assert_eq!(keys.label(&request("hunter2")), Some("acme"));
+    assert_eq!(keys.label(&request("swordfish")), Some("ops"));
+  }
+
+  #[test]
+  fn policies() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("keys");
+    fs::write(
+      &path,
+      "acme=hunter2 sources=bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4 max_daily_sources=1\nops=swordfish\n",
+    )
+    .unwrap();
+
+    let keys = AccessKeys::new(Some(path.to_str().unwrap()), &[]).unwrap();
+    assert_eq!(keys.label(&request("hunter2")), Some("acme"));
+    assert_eq!(keys.policy("acme").unwrap().max_daily_sources, Some(1));
+    assert_eq!(keys.policy("ops"), Some(&KeyPolicy::default()));
+    assert_eq!(keys.policy("other"), None);
+
+    assert!(AccessKeys::new(Some("acme=hunter2 daily=1"), &[]).is_err());
+  }
+
+  #[test]
+  fn no_keys() {
+    let keys = AccessKeys::new(None, &[]).unwrap();
+    assert!(!keys.is_enabled());
+    assert_eq!(keys.label(&request("")), None);
+  }
+
+  #[test]
+  fn brands() {
+    let keys =
+      AccessKeys::new(Some("acme=hunter2,plain=swordfish"), &["acme=ACME".into()]).unwrap();
+    assert_eq!(
+      keys.brand(&request("hunter2")),
+      Some(Brand {
+        label: "acme".into(),
+        prefix: "ACME".into(),
+      })
+    );
+    assert_eq!(keys.brand(&request("swordfish")), None);
+    assert_eq!(keys.brand(&request("acme")), None);
+    assert_eq!(keys.brand(&Request::new(Body::empty())), None);
+
+    assert!(AccessKeys::new(None, &["acme=ACME".into()]).is_err());
+    assert!(AccessKeys::new(Some("acme=hunter2"), &["other=ACME".into()]).is_err());
+    assert!(AccessKeys::new(Some("acme=hunter2"), &["acme=".into()]).is_err());
+    assert!(AccessKeys::new(Some("acme=hunter2"), &[format!("acme={}", "a".repeat(17))]).is_err());
+  }
+
+  #[test]
+  fn invalid_keys() {
+    assert!(AccessKeys::new(Some("hunter2"), &[]).is_err());
+    assert!(AccessKeys::new(Some("a=hunter2,b=hunter2"), &[]).is_err());
+    assert!(AccessKeys::new(Some("acme="), &[]).is_err());
+    assert!(AccessKeys::new(Some(""), &[]).is_err());
+  }
+}
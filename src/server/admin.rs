@@ -0,0 +1,69 @@
+use crate::api_key;
+use anyhow::Error;
+use hyper::{Body, Request};
+use std::collections::BTreeMap;
+
+/// Bearer tokens accepted on admin endpoints, each with the label recorded
+/// when it is used to change something.
+#[derive(Debug, Default)]
+pub struct AdminKeys {
+  labels: BTreeMap<String, String>,
+}
+
+impl AdminKeys {
+  pub const DEFAULT_LABEL: &'static str = "default";
+
+  /// `token` is the unlabelled `--admin-token`, `keys` are `<LABEL>=<TOKEN>`.
+  pub fn new(token: Option<String>, keys: &[String]) -> Result<Self, Error> {
+    let mut labels = BTreeMap::new();
+
+    if let Some(token) = token {
+      labels.insert(token, Self::DEFAULT_LABEL.to_owned());
+    }
+
+    api_key::insert_labels(&mut labels, "admin key", keys)?;
+
+    Ok(Self { labels })
+  }
+
+  pub fn label(&self, req: &Request<Body>) -> Option<&str> {
+    api_key::bearer_token(req)
+      .and_then(|token| self.labels.get(token))
+      .map(|label| label.as_str())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn request(token: &str) -> Request<Body> {
+    Request::builder()
+      .header("Authorization", format!("Bearer {token}"))
+      .body(Body::empty())
+      .unwrap()
+  }
+
+  #[test]
+  fn labels() {
+    let keys = AdminKeys::new(Some("secret".into()), &["legal=hunter2".into()]).unwrap();
+    assert_eq!(keys.label(&request("secret")), Some("default"));
+    assert_eq!(keys.label(&request("hunter2")), Some("legal"));
+    assert_eq!(keys.label(&request("legal")), None);
+    assert_eq!(keys.label(&Request::new(Body::empty())), None);
+  }
+
+  #[test]
+  fn no_keys() {
+    let keys = AdminKeys::new(None, &[]).unwrap();
+    assert_eq!(keys.label(&request("")), None);
+  }
+
+  #[test]
+  fn invalid_keys() {
+    assert!(AdminKeys::new(None, &["hunter2".into()]).is_err());
+    assert!(AdminKeys::new(None, &["legal=".into()]).is_err());
+    assert!(AdminKeys::new(None, &["=hunter2".into()]).is_err());
+    assert!(AdminKeys::new(Some("hunter2".into()), &["legal=hunter2".into()]).is_err());
+  }
+}
@@ -0,0 +1,172 @@
+use crate::blocklist;
+use crate::service_address::ServiceAddresses;
+use anyhow::{anyhow, Error};
+use bitcoin::secp256k1::rand;
+use bitcoin::{Address, Amount};
+use ord::index::{AirdropJob, MysqlDatabase};
+use ord::options::Options;
+use ord::subcommand::wallet::airdrop::{self, Airdrop, Format, Row, RowError};
+use ord::FeeRate;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long the worker sleeps when no batch is pending.
+const IDLE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub struct Submitted {
+  pub job_id: String,
+  pub rows: usize,
+  pub batches: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rejected {
+  pub errors: Vec<RowError>,
+}
+
+/// Validates every row of `body` and stores the job, nothing is built until
+/// the worker picks its batches up.
+pub fn submit(
+  mysql: &MysqlDatabase,
+  options: &Options,
+  content_type: &str,
+  body: &str,
+  source: Address,
+  fee_rate: f64,
+  extension: Option<String>,
+) -> Result<Result<Submitted, Rejected>, Error> {
+  FeeRate::try_from(fee_rate)?;
+
+  let rows = match airdrop::parse(body, Format::from_content_type(content_type)?, options) {
+    Ok(rows) => rows,
+    Err(errors) => return Ok(Err(Rejected { errors })),
+  };
+
+  let errors = blocklist::blocked_rows(mysql, rows.iter().map(|row| (row.line, &row.content)))?
+    .into_iter()
+    .map(|line| RowError {
+      line,
+      error: "content is blocked".into(),
+    })
+    .collect::<Vec<RowError>>();
+  if !errors.is_empty() {
+    return Ok(Err(Rejected { errors }));
+  }
+
+  let row_count = rows.len();
+
+  let batches = airdrop::batches(rows)
+    .iter()
+    .map(serde_json::to_string)
+    .collect::<Result<Vec<String>, _>>()?;
+
+  let job = AirdropJob {
+    id: format!("{:032x}", rand::random::<u128>()),
+    source: source.to_string(),
+    fee_rate,
+    extension,
+  };
+  mysql.insert_airdrop_job(&job, &batches)?;
+
+  info!(job_id = %job.id, rows = row_count, batches = batches.len(), "Airdrop submitted");
+
+  Ok(Ok(Submitted {
+    job_id: job.id,
+    rows: row_count,
+    batches: batches.len(),
+  }))
+}
+
+/// Builds pending batches one at a time, forever. Batches of a job are
+/// built in order, each funded from utxos the earlier ones left unspent. A
+/// batch pays the service address keyed by its job and number, so rebuilding
+/// it pays the same one.
+pub fn run_worker(
+  options: Options,
+  mysql: Arc<MysqlDatabase>,
+  service_addresses: Arc<ServiceAddresses>,
+  service_fee: u64,
+) {
+  match mysql.reset_airdrop_batches() {
+    Ok(0) => {}
+    Ok(reset) => info!(reset, "Resume airdrop batches"),
+    Err(err) => warn!(error = %err, "Reset airdrop batches fail"),
+  }
+
+  loop {
+    let (job_id, batch) = match mysql.claim_airdrop_batch() {
+      Ok(Some(next)) => next,
+      Ok(None) => {
+        thread::sleep(IDLE);
+        continue;
+      }
+      Err(err) => {
+        warn!(error = %err, "Claim airdrop batch fail");
+        thread::sleep(IDLE);
+        continue;
+      }
+    };
+
+    let result = build_batch(
+      &options,
+      &mysql,
+      &service_addresses.for_key(&format!("{job_id}:{batch}")),
+      service_fee,
+      &job_id,
+      batch,
+    );
+    if let Err(err) = &result {
+      warn!(job_id = %job_id, batch, error = %err, "Build airdrop batch fail");
+    }
+
+    if let Err(err) = mysql.finish_airdrop_batch(&job_id, batch, result) {
+      warn!(job_id = %job_id, batch, error = %err, "Finish airdrop batch fail");
+    }
+  }
+}
+
+fn build_batch(
+  options: &Options,
+  mysql: &MysqlDatabase,
+  service_address: &Address,
+  service_fee: u64,
+  job_id: &str,
+  batch: u32,
+) -> Result<String, Error> {
+  let job = mysql
+    .get_airdrop_job(job_id)?
+    .ok_or_else(|| anyhow!("airdrop job {job_id} not found"))?;
+
+  let mut rows = None;
+  let mut spent = BTreeSet::new();
+  for other in mysql.get_airdrop_batches(job_id)? {
+    if other.batch == batch {
+      rows = Some(serde_json::from_str::<Vec<Row>>(&other.rows)?);
+    }
+    if let Some(output) = other.output {
+      spent.extend(serde_json::from_value::<airdrop::Output>(output)?.spent);
+    }
+  }
+
+  let output = Airdrop {
+    source: Address::from_str(&job.source)?,
+    fee_rate: FeeRate::try_from(job.fee_rate)?,
+    extension: job.extension,
+    rows: rows.ok_or_else(|| anyhow!("airdrop batch {batch} of {job_id} not found"))?,
+    spent,
+  }
+  .build(
+    options.clone(),
+    Some(service_address.clone()),
+    Some(Amount::from_sat(service_fee)),
+    mysql,
+  )?;
+
+  Ok(serde_json::to_string(&output)?)
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Error};
+use bitcoin::Amount;
+use hyper::{Body, Request};
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// The partner a transfer is stamped for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brand {
+  pub label: String,
+  pub prefix: String,
+}
+
+/// Adds `<LABEL>=<TOKEN>` keys to `labels`, which maps tokens to their
+/// labels. `kind` names the keys in errors.
+pub fn insert_labels(
+  labels: &mut BTreeMap<String, String>,
+  kind: &str,
+  keys: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<(), Error> {
+  for key in keys {
+    let key = key.as_ref();
+    let (label, token) = key
+      .split_once('=')
+      .filter(|(label, token)| !label.is_empty() && !token.is_empty())
+      .ok_or_else(|| anyhow!("invalid {kind} `{key}`, expected <LABEL>=<TOKEN>"))?;
+    if labels.insert(token.to_owned(), label.to_owned()).is_some() {
+      return Err(anyhow!("{kind} `{label}` reuses another key's token"));
+    }
+  }
+  Ok(())
+}
+
+/// The token of an `Authorization: Bearer <TOKEN>` header.
+pub fn bearer_token(req: &Request<Body>) -> Option<&str> {
+  req
+    .headers()
+    .get("Authorization")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// The service fee of a request asking for `requested`. Only requests
+/// authenticated with an access key, whose label is `partner`, get what
+/// they ask for, and never less than `floor`, the others pay `default`.
+/// Whitelisted sources are exempted later either way.
+pub fn service_fee(
+  partner: Option<&str>,
+  requested: Option<u64>,
+  default: Option<Amount>,
+  floor: Amount,
+) -> Option<Amount> {
+  match (partner, requested) {
+    (Some(partner), Some(requested)) => {
+      let fee = Amount::from_sat(requested).max(floor);
+      info!(
+        partner,
+        requested,
+        fee = fee.to_sat(),
+        "Service fee override"
+      );
+      Some(fee)
+    }
+    _ => default,
+  }
+}
+
+impl Brand {
+  /// The OP_RETURN of a transfer and the brand it was stamped with. A
+  /// request's own `op_return` is left as is, otherwise the brand prefix is
+  /// followed by `suffix`.
+  pub fn stamp(
+    brand: Option<Brand>,
+    op_return: String,
+    suffix: Option<String>,
+  ) -> Result<(Option<String>, Option<String>), Error> {
+    if !op_return.is_empty() {
+      return Ok((Some(op_return), None));
+    }
+
+    match (brand, suffix) {
+      (Some(brand), suffix) => Ok((
+        Some(brand.prefix + suffix.as_deref().unwrap_or_default()),
+        Some(brand.label),
+      )),
+      (None, Some(_)) => Err(anyhow!(
+        "op_return_suffix requires an api key with an op_return brand"
+      )),
+      (None, None) => Ok((None, None)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn acme() -> Brand {
+    Brand {
+      label: "acme".into(),
+      prefix: "ACME".into(),
+    }
+  }
+
+  #[test]
+  fn only_partners_override_service_fee() {
+    let default = Some(Amount::from_sat(3000));
+    let floor = Amount::from_sat(546);
+    assert_eq!(
+      service_fee(Some("acme"), Some(1000), default, floor),
+      Some(Amount::from_sat(1000))
+    );
+    assert_eq!(
+      service_fee(Some("acme"), Some(0), default, floor),
+      Some(floor)
+    );
+    assert_eq!(service_fee(Some("acme"), None, default, floor), default);
+    assert_eq!(service_fee(None, Some(1000), default, floor), default);
+  }
+
+  #[test]
+  fn stamp() {
+    assert_eq!(
+      Brand::stamp(Some(acme()), String::new(), Some(":42".into())).unwrap(),
+      (Some("ACME:42".into()), Some("acme".into()))
+    );
+    assert_eq!(
+      Brand::stamp(Some(acme()), String::new(), None).unwrap(),
+      (Some("ACME".into()), Some("acme".into()))
+    );
+    assert_eq!(
+      Brand::stamp(Some(acme()), "mine".into(), Some(":42".into())).unwrap(),
+      (Some("mine".into()), None)
+    );
+    assert_eq!(
+      Brand::stamp(None, String::new(), None).unwrap(),
+      (None, None)
+    );
+    assert!(Brand::stamp(None, String::new(), Some(":42".into())).is_err());
+  }
+}
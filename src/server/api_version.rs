@@ -0,0 +1,392 @@
+use anyhow::{anyhow, Error};
+use bitcoin::AddressType;
+use hyper::{Body, Request};
+use ord::signer::Delegation;
+use ord::subcommand::wallet::{cancel, mint, mints, recover::RecoveryKey, transfer};
+use serde_json::{json, Value};
+
+pub const ACCEPT_VERSION: &str = "Accept-Version";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiVersion {
+  V1,
+  V2,
+}
+
+impl ApiVersion {
+  pub const SUPPORTED: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+  // unversioned requests get the oldest supported shape
+  pub fn from_request(req: &Request<Body>) -> Result<Self, Error> {
+    match req.headers().get(ACCEPT_VERSION) {
+      Some(value) => value
+        .to_str()
+        .map_err(|_| anyhow!("invalid {ACCEPT_VERSION} header"))?
+        .parse(),
+      None => Ok(Self::SUPPORTED[0]),
+    }
+  }
+
+  pub fn number(self) -> u32 {
+    match self {
+      ApiVersion::V1 => 1,
+      ApiVersion::V2 => 2,
+    }
+  }
+}
+
+impl std::str::FromStr for ApiVersion {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    Self::SUPPORTED
+      .into_iter()
+      .find(|version| s.trim_start_matches('v') == version.number().to_string())
+      .ok_or_else(|| {
+        anyhow!(
+          "unsupported api version `{s}`, supported versions are {:?}",
+          Self::SUPPORTED.map(ApiVersion::number)
+        )
+      })
+  }
+}
+
+pub fn mint_response(output: &mint::Output, version: ApiVersion) -> Result<String, Error> {
+  let mut value = match version {
+    ApiVersion::V1 => json!({
+      "inscription": output.inscription,
+      "commit": output.commit,
+      "commit_custom": output.commit_custom,
+      "reveal": output.reveal,
+      "service_fee": output.service_fee,
+      "satpoint_fee": output.satpoint_fee,
+      "network_fee": output.network_fee,
+      "commit_vsize": output.commit_vsize,
+      "commit_fee": output.commit_fee,
+    }),
+    ApiVersion::V2 => serde_json::to_value(output)?,
+  };
+  dry_run(&mut value, output.dry_run);
+  recovery_secret_keys(&mut value, &output.recovery_secret_keys);
+  Ok(value.to_string())
+}
+
+pub fn mints_response(output: &mints::Output, version: ApiVersion) -> Result<String, Error> {
+  let mut value = match version {
+    ApiVersion::V1 => json!({
+      "inscription": output.inscription,
+      "commit": output.commit,
+      "commit_custom": output.commit_custom,
+      "reveal": output.reveal,
+      "service_fee": output.service_fee,
+      "satpoint_fee": output.satpoint_fee,
+      "network_fee": output.network_fee,
+      "commit_vsize": output.commit_vsize,
+      "commit_fee": output.commit_fee,
+    }),
+    ApiVersion::V2 => serde_json::to_value(output)?,
+  };
+  dry_run(&mut value, output.dry_run);
+  recovery_secret_keys(&mut value, &output.recovery_secret_keys);
+  Ok(value.to_string())
+}
+
+pub fn transfer_response(output: &transfer::Output, version: ApiVersion) -> Result<String, Error> {
+  let mut value = match version {
+    ApiVersion::V1 => json!({
+      "transaction": output.transaction,
+      "commit_custom": output.commit_custom,
+      "network_fee": output.network_fee,
+    }),
+    ApiVersion::V2 => serde_json::to_value(output)?,
+  };
+  dry_run(&mut value, output.dry_run);
+  Ok(value.to_string())
+}
+
+pub fn cancel_response(output: &cancel::Output, version: ApiVersion) -> Result<String, Error> {
+  let mut value = match version {
+    ApiVersion::V1 => json!({
+      "transaction": output.transaction,
+      "commit_custom": output.commit_custom,
+      "network_fee": output.network_fee,
+      "service_fee": output.service_fee,
+      "commit_vsize": output.commit_vsize,
+      "commit_fee": output.commit_fee,
+    }),
+    ApiVersion::V2 => serde_json::to_value(output)?,
+  };
+  dry_run(&mut value, output.dry_run);
+  Ok(value.to_string())
+}
+
+// V1 has no room for new fields, but clients must still be able to tell
+// a dry run from a build they may sign
+fn dry_run(value: &mut Value, dry_run: bool) {
+  if dry_run {
+    value["dry_run"] = json!(true);
+  }
+}
+
+// asked for per request, so returned by every version
+fn recovery_secret_keys(value: &mut Value, keys: &[RecoveryKey]) {
+  if !keys.is_empty() {
+    value["recovery_secret_keys"] = json!(keys);
+  }
+}
+
+// the built output is kept as is so a failed delegation can still be
+// signed and broadcast by the caller
+pub fn with_signer(response: String, delegation: &Delegation) -> Result<String, Error> {
+  let mut value: Value = serde_json::from_str(&response)?;
+  value
+    .as_object_mut()
+    .ok_or_else(|| anyhow!("response is not an object"))?
+    .insert("signer".into(), serde_json::to_value(delegation)?);
+  Ok(value.to_string())
+}
+
+// an empty destination type list means every type is allowed
+pub fn info(allowed_destination_types: &[AddressType]) -> Value {
+  json!({
+    "api_versions": ApiVersion::SUPPORTED.map(ApiVersion::number),
+    "allowed_destination_types": allowed_destination_types
+      .iter()
+      .map(|address_type| address_type.to_string())
+      .collect::<Vec<String>>(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ord::subcommand::wallet::warning::{Warning, WarningCode};
+
+  fn transfer_output() -> transfer::Output {
+    transfer::Output {
+      transaction: "70736274ff".into(),
+      commit_custom: vec!["00".into()],
+      network_fee: 200,
+      locktime: Some(800000),
+      postage_padding: Vec::new(),
+      warnings: vec![],
+      op_return_brand: None,
+      proofs: vec![],
+      dry_run: false,
+    }
+  }
+
+  fn mint_output() -> mint::Output {
+    mint::Output {
+      inscription: vec![],
+      commit: "70736274ff".into(),
+      commit_custom: vec!["00".into()],
+      reveal: vec!["02000000".into()],
+      service_fee: 3000,
+      satpoint_fee: 546,
+      network_fee: 300,
+      commit_vsize: 150,
+      commit_fee: 150,
+      commit_fee_rate: 1.0,
+      reveal_fee_rate: 1.0,
+      commit_breakdown: vec![],
+      inscribed_sats: vec![],
+      warnings: vec![],
+      compression: None,
+      pointer: None,
+      parent_reveal: None,
+      recovery_secret_keys: vec![],
+      dry_run: false,
+    }
+  }
+
+  #[test]
+  fn parse_versions() {
+    assert_eq!("1".parse::<ApiVersion>().unwrap(), ApiVersion::V1);
+    assert_eq!("v2".parse::<ApiVersion>().unwrap(), ApiVersion::V2);
+    assert!("3".parse::<ApiVersion>().is_err());
+  }
+
+  #[test]
+  fn unversioned_requests_get_oldest_version() {
+    let req = Request::new(Body::empty());
+    assert_eq!(ApiVersion::from_request(&req).unwrap(), ApiVersion::V1);
+
+    let req = Request::builder()
+      .header(ACCEPT_VERSION, "2")
+      .body(Body::empty())
+      .unwrap();
+    assert_eq!(ApiVersion::from_request(&req).unwrap(), ApiVersion::V2);
+  }
+
+  #[test]
+  fn transfer_golden() {
+    assert_eq!(
+      transfer_response(&transfer_output(), ApiVersion::V1).unwrap(),
+      r#"{"commit_custom":["00"],"network_fee":200,"transaction":"70736274ff"}"#
+    );
+    assert_eq!(
+      transfer_response(&transfer_output(), ApiVersion::V2).unwrap(),
+      r#"{"commit_custom":["00"],"locktime":800000,"network_fee":200,"transaction":"70736274ff"}"#
+    );
+  }
+
+  #[test]
+  fn mint_golden() {
+    let golden = r#"{"commit":"70736274ff","commit_custom":["00"],"commit_fee":150,"commit_vsize":150,"inscription":[],"network_fee":300,"reveal":["02000000"],"satpoint_fee":546,"service_fee":3000}"#;
+    assert_eq!(
+      mint_response(&mint_output(), ApiVersion::V1).unwrap(),
+      golden
+    );
+    assert_eq!(
+      mint_response(&mint_output(), ApiVersion::V2).unwrap(),
+      r#"{"commit":"70736274ff","commit_custom":["00"],"commit_fee":150,"commit_fee_rate":1.0,"commit_vsize":150,"inscription":[],"network_fee":300,"reveal":["02000000"],"reveal_fee_rate":1.0,"satpoint_fee":546,"service_fee":3000}"#
+    );
+  }
+
+  #[test]
+  fn inscribed_sats_are_v2_only() {
+    let mut output = mint_output();
+    output.inscribed_sats = vec![mint::InscribedSat {
+      funding_outpoint: "1111111111111111111111111111111111111111111111111111111111111111:1"
+        .parse()
+        .unwrap(),
+      funding_offset: 600,
+      sat: None,
+      rarity: None,
+    }];
+
+    assert!(!mint_response(&output, ApiVersion::V1)
+      .unwrap()
+      .contains("inscribed_sats"));
+    assert_eq!(
+      serde_json::from_str::<Value>(&mint_response(&output, ApiVersion::V2).unwrap()).unwrap()
+        ["inscribed_sats"],
+      json!([{
+        "funding_outpoint": "1111111111111111111111111111111111111111111111111111111111111111:1",
+        "funding_offset": 600,
+      }]),
+    );
+  }
+
+  #[test]
+  fn postage_padding_is_v2_only() {
+    let mut output = transfer_output();
+    output.postage_padding = vec![transfer::PostagePadding {
+      output: 0,
+      sats: 216,
+      inputs: vec![
+        "1111111111111111111111111111111111111111111111111111111111111111:1"
+          .parse()
+          .unwrap(),
+      ],
+    }];
+
+    assert!(!transfer_response(&output, ApiVersion::V1)
+      .unwrap()
+      .contains("postage_padding"));
+    assert_eq!(
+      serde_json::from_str::<Value>(&transfer_response(&output, ApiVersion::V2).unwrap()).unwrap()
+        ["postage_padding"],
+      json!([{
+        "output": 0,
+        "sats": 216,
+        "inputs": ["1111111111111111111111111111111111111111111111111111111111111111:1"],
+      }]),
+    );
+  }
+
+  #[test]
+  fn warnings_are_v2_only() {
+    let mut output = transfer_output();
+    output.warnings = vec![Warning {
+      code: WarningCode::DestinationIsSource,
+      message: "destination is the source address".into(),
+      data: Value::Null,
+    }];
+
+    assert!(!transfer_response(&output, ApiVersion::V1)
+      .unwrap()
+      .contains("warnings"));
+    assert_eq!(
+      serde_json::from_str::<Value>(&transfer_response(&output, ApiVersion::V2).unwrap()).unwrap()
+        ["warnings"],
+      json!([{
+        "code": "destination_is_source",
+        "message": "destination is the source address",
+        "data": null,
+      }]),
+    );
+  }
+
+  #[test]
+  fn dry_runs_are_marked_in_every_version() {
+    let mut output = mint_output();
+    assert!(!mint_response(&output, ApiVersion::V2)
+      .unwrap()
+      .contains("dry_run"));
+
+    output.dry_run = true;
+    for version in ApiVersion::SUPPORTED {
+      let response: Value =
+        serde_json::from_str(&mint_response(&output, version).unwrap()).unwrap();
+      assert_eq!(response["dry_run"], json!(true));
+    }
+  }
+
+  #[test]
+  fn recovery_keys_are_only_returned_when_asked_for() {
+    let mut output = mint_output();
+    assert!(!mint_response(&output, ApiVersion::V2)
+      .unwrap()
+      .contains("recovery_secret_keys"));
+
+    output.recovery_secret_keys = vec![RecoveryKey {
+      commit_output: "0000000000000000000000000000000000000000000000000000000000000000:0"
+        .parse()
+        .unwrap(),
+      commit_address: "tb1phsaern0qpcpqpv2h6cmu6fgae4y0lyx2tqhmqmgvv7c9whffm3rqjmlrqs"
+        .parse()
+        .unwrap(),
+      secret_key: "<WIF>".into(),
+    }];
+    for version in ApiVersion::SUPPORTED {
+      let response: Value =
+        serde_json::from_str(&mint_response(&output, version).unwrap()).unwrap();
+      assert_eq!(
+        response["recovery_secret_keys"][0]["secret_key"],
+        json!("<WIF>")
+      );
+    }
+  }
+
+  #[test]
+  fn signer_is_added_alongside_output() {
+    let response = with_signer(
+      transfer_response(&transfer_output(), ApiVersion::V1).unwrap(),
+      &Delegation {
+        error: Some("signer webhook returned 500 Internal Server Error".into()),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(
+      response,
+      r#"{"commit_custom":["00"],"network_fee":200,"signer":{"error":"signer webhook returned 500 Internal Server Error"},"transaction":"70736274ff"}"#
+    );
+  }
+
+  #[test]
+  fn info_reports_destination_policy() {
+    assert_eq!(
+      info(&[AddressType::P2tr, AddressType::P2wpkh]).to_string(),
+      r#"{"allowed_destination_types":["p2tr","p2wpkh"],"api_versions":[1,2]}"#
+    );
+    assert_eq!(
+      info(&[]).to_string(),
+      r#"{"allowed_destination_types":[],"api_versions":[1,2]}"#
+    );
+  }
+}
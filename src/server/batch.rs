@@ -0,0 +1,187 @@
+use crate::jsonrpc::{self, RequestError};
+use anyhow::Error;
+use hyper::{Body, Response, StatusCode};
+use ord::index::SharedIndex;
+use ord::options::Options;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// Most calls a single batch may carry.
+pub const MAX_CALLS: usize = 25;
+
+/// Calls of a batch being handled at once, the others wait their turn.
+pub const PARALLELISM: usize = 8;
+
+/// The calls in a batch body, a non-empty JSON array of at most
+/// `MAX_CALLS` calls.
+pub fn calls(body: &[u8]) -> Result<Vec<Value>, RequestError> {
+  match jsonrpc::parse(body)? {
+    Value::Array(calls) if calls.is_empty() => {
+      Err(RequestError::InvalidRequest("empty batch".into()))
+    }
+    Value::Array(calls) if calls.len() > MAX_CALLS => Err(RequestError::InvalidRequest(format!(
+      "batch of {} calls exceeds the limit of {MAX_CALLS}",
+      calls.len()
+    ))),
+    Value::Array(calls) => Ok(calls),
+    _ => Err(RequestError::InvalidRequest(
+      "batch must be an array of calls".into(),
+    )),
+  }
+}
+
+/// The route `call` is dispatched to, one of the JSON-RPC routes.
+pub fn method(call: &Value) -> Result<&str, RequestError> {
+  let method = call
+    .get("method")
+    .and_then(Value::as_str)
+    .ok_or_else(|| RequestError::InvalidRequest("call without a method".into()))?;
+  if !jsonrpc::METHODS.contains(&method) {
+    return Err(RequestError::MethodNotFound(method.into()));
+  }
+  Ok(method)
+}
+
+/// The envelope a call's response carries, as an element of the batch
+/// response. Calls fail on their own, never the batch.
+pub async fn item(id: Value, response: Result<Response<Body>, Error>) -> Value {
+  let err = match response {
+    Ok(response) => match hyper::body::to_bytes(response.into_body()).await {
+      Ok(body) => match serde_json::from_slice(&body) {
+        Ok(envelope) => return envelope,
+        Err(err) => Error::from(err),
+      },
+      Err(err) => Error::from(err),
+    },
+    Err(err) => err,
+  };
+
+  let response = jsonrpc::error_response(id, StatusCode::OK, &err, err.to_string());
+  hyper::body::to_bytes(response.into_body())
+    .await
+    .ok()
+    .and_then(|body| serde_json::from_slice(&body).ok())
+    .unwrap_or(Value::Null)
+}
+
+/// The indexes the calls of a batch are handled against, each chain's
+/// opened by the first call needing it and shared with the others.
+#[derive(Clone, Default)]
+pub struct Indexes(Arc<Mutex<Vec<SharedIndex>>>);
+
+impl Indexes {
+  /// `options` carrying the index of their chain, so the builders they are
+  /// handed to do not open it again.
+  pub fn share(&self, options: &Options) -> Result<Options, Error> {
+    let mut indexes = self.0.lock().unwrap();
+    let index = match indexes
+      .iter()
+      .find(|index| index.chain() == options.chain())
+    {
+      Some(index) => index.clone(),
+      None => {
+        let index = SharedIndex::open(options)?;
+        indexes.push(index.clone());
+        index
+      }
+    };
+    Ok(Options {
+      shared_index: Some(index),
+      ..options.clone()
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bitcoin::Network;
+  use clap::Parser;
+  use ord::index::Index;
+  use serde_json::json;
+  use tempfile::TempDir;
+
+  #[test]
+  fn batch_must_be_a_bounded_array() {
+    assert_eq!(
+      calls(br#"[{"method":"mint"},{"method":"transfer"}]"#)
+        .unwrap()
+        .len(),
+      2
+    );
+    assert!(matches!(calls(b"[]"), Err(RequestError::InvalidRequest(_))));
+    assert!(matches!(
+      calls(br#"{"method":"mint"}"#),
+      Err(RequestError::InvalidRequest(_))
+    ));
+    assert!(matches!(calls(b"[{"), Err(RequestError::Parse(_))));
+
+    let batch = Value::Array(vec![json!({ "method": "mint" }); MAX_CALLS + 1]);
+    assert!(matches!(
+      calls(batch.to_string().as_bytes()),
+      Err(RequestError::InvalidRequest(_))
+    ));
+  }
+
+  #[test]
+  fn calls_only_reach_json_rpc_routes() {
+    assert_eq!(method(&json!({ "method": "transfer" })), Ok("transfer"));
+    assert_eq!(
+      method(&json!({ "method": "admin" })),
+      Err(RequestError::MethodNotFound("admin".into()))
+    );
+    assert!(matches!(
+      method(&json!({ "params": {} })),
+      Err(RequestError::InvalidRequest(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn failed_calls_become_error_items() {
+    assert_eq!(
+      item(
+        json!(1),
+        Ok(Response::new(Body::from(
+          r#"{"jsonrpc":"2.0","id":1,"result":{}}"#
+        )))
+      )
+      .await,
+      json!({ "jsonrpc": "2.0", "id": 1, "result": {} })
+    );
+
+    let err = item(json!(2), Err(anyhow::anyhow!("boom"))).await;
+    assert_eq!(err["id"], json!(2));
+    assert_eq!(err["error"]["code"], json!(jsonrpc::SERVER_ERROR));
+    assert_eq!(err["error"]["message"], json!("boom"));
+  }
+
+  #[test]
+  fn calls_share_one_index_per_chain() {
+    let rpc_server = test_bitcoincore_rpc::builder()
+      .network(Network::Regtest)
+      .build();
+    let tempdir = TempDir::new().unwrap();
+    let cookie_file = tempdir.path().join("cookie");
+    std::fs::write(&cookie_file, "username:password").unwrap();
+    let options = Options::try_parse_from([
+      "ord".as_ref(),
+      "--rpc-url".as_ref(),
+      rpc_server.url().as_ref(),
+      "--data-dir".as_ref(),
+      tempdir.path().as_os_str(),
+      "--cookie-file".as_ref(),
+      cookie_file.as_os_str(),
+      "--regtest".as_ref(),
+    ])
+    .unwrap();
+
+    let opens = Index::read_opens();
+    let indexes = Indexes::default();
+    let opened = (0..3)
+      .map(|_| Index::read_open(&indexes.share(&options).unwrap()).unwrap())
+      .collect::<Vec<_>>();
+
+    assert_eq!(Index::read_opens() - opens, 1);
+    assert!(opened.iter().all(|index| Arc::ptr_eq(index, &opened[0])));
+  }
+}
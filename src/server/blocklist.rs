@@ -0,0 +1,93 @@
+use anyhow::Error;
+use bitcoin::hashes::{sha256, Hash};
+use hyper::{Body, Response, StatusCode};
+use ord::index::{BlocklistKind, MysqlDatabase};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BlocklistChange {
+  pub kind: BlocklistKind,
+  pub value: String,
+}
+
+/// Hash of the body `Inscription::from_content` builds from `content`.
+pub fn content_hash(content: &str) -> String {
+  sha256::Hash::hash(content.as_bytes()).to_string()
+}
+
+/// Refuses a mint if any of `contents` has a blocked hash.
+pub fn check_contents<'a>(
+  mysql: Option<&MysqlDatabase>,
+  contents: impl IntoIterator<Item = &'a String>,
+) -> Result<Option<Response<Body>>, Error> {
+  let Some(mysql) = mysql else {
+    return Ok(None);
+  };
+
+  for content in contents {
+    let hash = content_hash(content);
+    if mysql.is_blocked(BlocklistKind::ContentHash, &hash)? {
+      warn!(%hash, "Refused blocked content");
+      return Ok(Some(
+        Response::builder()
+          .status(StatusCode::FORBIDDEN)
+          .body(Body::from("Forbidden: content is blocked"))
+          .unwrap(),
+      ));
+    }
+  }
+
+  Ok(None)
+}
+
+/// Lines of the airdrop `rows` whose content has a blocked hash.
+pub fn blocked_rows<'a>(
+  mysql: &MysqlDatabase,
+  rows: impl IntoIterator<Item = (usize, &'a String)>,
+) -> Result<Vec<usize>, Error> {
+  let mut blocked = vec![];
+  for (line, content) in rows {
+    let hash = content_hash(content);
+    if mysql.is_blocked(BlocklistKind::ContentHash, &hash)? {
+      warn!(%hash, line, "Refused blocked airdrop content");
+      blocked.push(line);
+    }
+  }
+  Ok(blocked)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hash() {
+    assert_eq!(
+      content_hash(""),
+      "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+      content_hash("hello"),
+      "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+  }
+
+  #[test]
+  fn without_database() {
+    assert!(check_contents(None, &["hello".to_owned()])
+      .unwrap()
+      .is_none());
+  }
+
+  #[test]
+  fn change_from_json() {
+    assert_eq!(
+      serde_json::from_str::<BlocklistChange>(r#"{"kind":"content_hash","value":"00"}"#).unwrap(),
+      BlocklistChange {
+        kind: BlocklistKind::ContentHash,
+        value: "00".into(),
+      }
+    );
+  }
+}
@@ -0,0 +1,74 @@
+use crate::jsonrpc::RequestError;
+use anyhow::Error;
+use hyper::body::{Bytes, HttpBody};
+use hyper::{header, Body, Request};
+
+/// Large enough for the biggest standard inscription, JSON-escaped.
+pub const DEFAULT_MAX_SIZE: &str = "524288";
+
+/// Reads the body of `req` into memory, refusing it as soon as it is known
+/// to exceed `limit` bytes, and puts it back for the routes to read again.
+pub async fn buffer(req: &mut Request<Body>, limit: usize) -> Result<Bytes, Error> {
+  let content_length = req
+    .headers()
+    .get(header::CONTENT_LENGTH)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<usize>().ok());
+  if content_length.map_or(false, |len| len > limit) {
+    return Err(RequestError::TooLarge(limit).into());
+  }
+
+  let mut body = std::mem::take(req.body_mut());
+  let mut buffer = Vec::with_capacity(content_length.unwrap_or_default());
+  while let Some(chunk) = body.data().await {
+    let chunk = chunk?;
+    if buffer.len() + chunk.len() > limit {
+      return Err(RequestError::TooLarge(limit).into());
+    }
+    buffer.extend_from_slice(&chunk);
+  }
+
+  let buffer = Bytes::from(buffer);
+  *req.body_mut() = Body::from(buffer.clone());
+  Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn request(len: usize) -> Request<Body> {
+    Request::new(Body::from(vec![b'a'; len]))
+  }
+
+  fn too_large(err: Error) -> bool {
+    matches!(
+      err.downcast_ref::<RequestError>(),
+      Some(RequestError::TooLarge(10))
+    )
+  }
+
+  #[tokio::test]
+  async fn under_the_limit_is_read() {
+    let mut req = request(10);
+    assert_eq!(buffer(&mut req, 10).await.unwrap().len(), 10);
+    assert_eq!(
+      hyper::body::to_bytes(req.into_body()).await.unwrap().len(),
+      10
+    );
+  }
+
+  #[tokio::test]
+  async fn one_byte_over_the_limit_is_refused() {
+    assert!(too_large(buffer(&mut request(11), 10).await.unwrap_err()));
+  }
+
+  #[tokio::test]
+  async fn declared_length_is_refused_unread() {
+    let mut req = Request::builder()
+      .header(header::CONTENT_LENGTH, "11")
+      .body(Body::empty())
+      .unwrap();
+    assert!(too_large(buffer(&mut req, 10).await.unwrap_err()));
+  }
+}
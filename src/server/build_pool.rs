@@ -0,0 +1,162 @@
+use anyhow::Error;
+use serde_json::{json, Value};
+use std::fmt::{self, Display, Formatter};
+use std::panic;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task;
+
+/// Runs builds on tokio's blocking threads, at most `max_concurrent` at a
+/// time, so their index reads, queries and signing work do not hold up the
+/// async workers. As many builds again may wait for a slot, beyond that they
+/// are refused with `BuildsBusy` instead of queued without bound.
+#[derive(Clone)]
+pub struct BuildPool {
+  max_concurrent: usize,
+  admitted: Arc<Semaphore>,
+  running: Arc<Semaphore>,
+}
+
+/// Refused because the build pool's queue is full.
+#[derive(Debug, PartialEq)]
+pub struct BuildsBusy {
+  pub retry_after: u64,
+}
+
+impl Display for BuildsBusy {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(
+      f,
+      "Too many builds in progress, retry in {} seconds",
+      self.retry_after
+    )
+  }
+}
+
+impl std::error::Error for BuildsBusy {}
+
+impl BuildPool {
+  // builds take a second or two, a client retrying sooner finds the queue
+  // just as full
+  const RETRY_AFTER: u64 = 2;
+
+  pub fn new(max_concurrent: usize) -> Self {
+    Self {
+      max_concurrent,
+      admitted: Arc::new(Semaphore::new(max_concurrent * 2)),
+      running: Arc::new(Semaphore::new(max_concurrent)),
+    }
+  }
+
+  /// Runs `build` once a slot frees up. A panicking build panics the
+  /// caller, so requests keep failing the way they did on the async worker.
+  pub async fn run<T: Send + 'static>(
+    &self,
+    build: impl FnOnce() -> Result<T, Error> + Send + 'static,
+  ) -> Result<T, Error> {
+    let Ok(admitted) = self.admitted.clone().try_acquire_owned() else {
+      return Err(
+        BuildsBusy {
+          retry_after: Self::RETRY_AFTER,
+        }
+        .into(),
+      );
+    };
+    let running = self.running.clone().acquire_owned().await?;
+
+    // the permits go with the build, which carries on when the client is gone
+    match task::spawn_blocking(move || {
+      let _permits = (admitted, running);
+      build()
+    })
+    .await
+    {
+      Ok(result) => result,
+      Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  /// Builds running and waiting for a slot, for the health check.
+  pub fn status(&self) -> Value {
+    let running = self.max_concurrent - self.running.available_permits();
+    let admitted = self.max_concurrent * 2 - self.admitted.available_permits();
+    json!({
+      "max_concurrent": self.max_concurrent,
+      "running": running,
+      "queued": admitted.saturating_sub(running),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::anyhow;
+  use std::sync::mpsc;
+
+  #[tokio::test]
+  async fn builds_run_off_the_async_workers() {
+    let pool = BuildPool::new(1);
+    assert_eq!(pool.run(|| Ok(7)).await.unwrap(), 7);
+    assert_eq!(
+      pool
+        .run(|| Err::<(), _>(anyhow!("no utxos")))
+        .await
+        .unwrap_err()
+        .to_string(),
+      "no utxos"
+    );
+    assert_eq!(pool.status()["running"], 0);
+  }
+
+  #[tokio::test]
+  async fn full_queue_is_refused() {
+    let pool = BuildPool::new(1);
+    let (release, released) = mpsc::channel::<()>();
+    let released = Arc::new(std::sync::Mutex::new(released));
+
+    let mut builds = Vec::new();
+    for _ in 0..2 {
+      let pool = pool.clone();
+      let released = released.clone();
+      builds.push(tokio::spawn(async move {
+        pool
+          .run(move || {
+            released.lock().unwrap().recv().unwrap();
+            Ok(())
+          })
+          .await
+      }));
+    }
+    while pool.status()["queued"] != 1 {
+      tokio::task::yield_now().await;
+    }
+
+    assert_eq!(
+      pool
+        .run(|| Ok(()))
+        .await
+        .unwrap_err()
+        .downcast::<BuildsBusy>()
+        .unwrap(),
+      BuildsBusy { retry_after: 2 }
+    );
+
+    for _ in 0..2 {
+      release.send(()).unwrap();
+    }
+    for build in builds {
+      build.await.unwrap().unwrap();
+    }
+  }
+
+  #[tokio::test]
+  #[should_panic(expected = "boom")]
+  async fn panics_reach_the_caller() {
+    BuildPool::new(1)
+      .run::<()>(|| panic!("boom"))
+      .await
+      .unwrap();
+  }
+}
@@ -0,0 +1,249 @@
+use crate::api_version::ApiVersion;
+use anyhow::Error;
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::Transaction;
+use hyper::{Body, Response, StatusCode};
+use ord::index::{Audited, MysqlDatabase};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+/// Endpoints that are refused unless the server was started with the
+/// matching flag.
+pub struct Capabilities {
+  allow_remint: bool,
+  refused_remint: AtomicU64,
+  allow_nonstandard: bool,
+  refused_nonstandard: AtomicU64,
+  max_nonstandard_weight: u64,
+}
+
+impl Capabilities {
+  pub fn new(allow_remint: bool, allow_nonstandard: bool, max_nonstandard_weight: u64) -> Self {
+    Self {
+      allow_remint,
+      refused_remint: AtomicU64::new(0),
+      allow_nonstandard,
+      refused_nonstandard: AtomicU64::new(0),
+      max_nonstandard_weight,
+    }
+  }
+
+  pub fn check_remint(&self, endpoint: &str) -> Option<Response<Body>> {
+    if self.allow_remint {
+      return None;
+    }
+
+    self.refused_remint.fetch_add(1, Ordering::Relaxed);
+    warn!(
+      endpoint,
+      "Refused remint, server started without --allow-remint"
+    );
+
+    Some(Self::forbidden(endpoint))
+  }
+
+  pub fn refused_remint(&self) -> u64 {
+    self.refused_remint.load(Ordering::Relaxed)
+  }
+
+  pub fn check_nonstandard(&self, endpoint: &str) -> Option<Response<Body>> {
+    if self.allow_nonstandard {
+      return None;
+    }
+
+    self.refused_nonstandard.fetch_add(1, Ordering::Relaxed);
+    warn!(
+      endpoint,
+      "Refused non-standard build, server started without --allow-nonstandard"
+    );
+
+    Some(Self::forbidden(endpoint))
+  }
+
+  pub fn refused_nonstandard(&self) -> u64 {
+    self.refused_nonstandard.load(Ordering::Relaxed)
+  }
+
+  /// Heaviest reveal a non-standard build may contain, even when allowed.
+  pub fn max_nonstandard_weight(&self) -> u64 {
+    self.max_nonstandard_weight
+  }
+
+  fn forbidden(endpoint: &str) -> Response<Body> {
+    Response::builder()
+      .status(StatusCode::FORBIDDEN)
+      .body(Body::from(format!(
+        "Forbidden: {endpoint} is disabled on this server"
+      )))
+      .unwrap()
+  }
+}
+
+/// Endpoints whose reveals may exceed MAX_STANDARD_TX_WEIGHT, gated by
+/// --allow-nonstandard.
+pub fn is_nonstandard(endpoint: &str) -> bool {
+  endpoint == "unsafeMintsWithPostage"
+}
+
+/// Records a build on an endpoint that is refused by default in the audit
+/// trail, with its heaviest reveal and the api version it was answered in.
+pub fn audit(
+  mysql: &MysqlDatabase,
+  endpoint: &str,
+  version: ApiVersion,
+  source: &str,
+  purpose: Option<&str>,
+  reveal: &[String],
+  idempotency_key: Option<&str>,
+) -> Result<Audited, Error> {
+  mysql.insert_build_audit(
+    endpoint,
+    version.number(),
+    source,
+    purpose,
+    true,
+    reveal_weight(reveal)?,
+    idempotency_key,
+  )
+}
+
+/// Heaviest reveal in a build, recorded in the audit trail.
+pub fn reveal_weight(reveal: &[String]) -> Result<u64, Error> {
+  let mut weight = 0;
+  for tx in reveal {
+    let tx: Transaction = deserialize(&Vec::from_hex(tx)?)?;
+    weight = weight.max(u64::try_from(tx.weight())?);
+  }
+  Ok(weight)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bitcoin::consensus::encode::serialize_hex;
+  use bitcoin::{PackedLockTime, TxIn};
+
+  #[test]
+  fn remint_refused_by_default() {
+    let capabilities = Capabilities::new(false, false, 1_000_000);
+    let response = capabilities.check_remint("reMint").unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(capabilities.check_remint("reMints").is_some());
+    assert_eq!(capabilities.refused_remint(), 2);
+  }
+
+  #[test]
+  fn remint_allowed() {
+    let capabilities = Capabilities::new(true, false, 1_000_000);
+    assert!(capabilities.check_remint("reMint").is_none());
+    assert_eq!(capabilities.refused_remint(), 0);
+  }
+
+  #[test]
+  fn nonstandard_refused_by_default() {
+    let capabilities = Capabilities::new(true, false, 1_000_000);
+    let response = capabilities
+      .check_nonstandard("unsafeMintsWithPostage")
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(capabilities.refused_nonstandard(), 1);
+    assert_eq!(capabilities.refused_remint(), 0);
+  }
+
+  #[test]
+  fn nonstandard_allowed() {
+    let capabilities = Capabilities::new(false, true, 500_000);
+    assert!(capabilities
+      .check_nonstandard("unsafeMintsWithPostage")
+      .is_none());
+    assert_eq!(capabilities.refused_nonstandard(), 0);
+    assert_eq!(capabilities.max_nonstandard_weight(), 500_000);
+  }
+
+  #[test]
+  fn only_unsafe_mints_are_nonstandard() {
+    assert!(is_nonstandard("unsafeMintsWithPostage"));
+    assert!(!is_nonstandard("mintsWithPostage"));
+    assert!(!is_nonstandard("reMints"));
+  }
+
+  #[test]
+  fn heaviest_reveal_weight() {
+    let tx = Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![TxIn::default()],
+      output: vec![],
+    };
+    assert_eq!(reveal_weight(&[]).unwrap(), 0);
+    assert_eq!(
+      reveal_weight(&[serialize_hex(&tx)]).unwrap(),
+      u64::try_from(tx.weight()).unwrap()
+    );
+    assert!(reveal_weight(&["zz".into()]).is_err());
+  }
+
+  // needs a scratch mysql server
+  // ORD_TEST_MYSQL_HOST=127.0.0.1 cargo test unsafe_builds_are_audited -- --ignored
+  #[test]
+  #[ignore]
+  fn unsafe_builds_are_audited() {
+    use mysql::params;
+    use mysql::prelude::Queryable;
+
+    let mysql = MysqlDatabase::new(
+      std::env::var("ORD_TEST_MYSQL_HOST").ok(),
+      std::env::var("ORD_TEST_MYSQL_USER").ok(),
+      std::env::var("ORD_TEST_MYSQL_PASS").ok(),
+      bitcoin::Network::Regtest,
+    )
+    .unwrap();
+
+    let tx = Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![TxIn::default()],
+      output: vec![],
+    };
+    let Audited::New(seq) = audit(
+      &mysql,
+      "unsafeMintsWithPostage",
+      ApiVersion::V2,
+      "source",
+      Some("test"),
+      &[serialize_hex(&tx)],
+      None,
+    )
+    .unwrap() else {
+      panic!("audit without an idempotency key is new");
+    };
+
+    type AuditRow = (String, Option<u32>, String, Option<String>, bool, u64);
+    let row: Option<AuditRow> = mysql
+      .get_conn()
+      .unwrap()
+      .exec_first(
+        format!(
+          "SELECT endpoint, api_version, source, purpose, is_unsafe, reveal_weight FROM {} WHERE id =
+           (SELECT audit_id FROM {} WHERE seq = :seq)",
+          mysql.get_build_audit_table(),
+          mysql.get_audit_sequence_table()
+        ),
+        params! { "seq" => seq },
+      )
+      .unwrap();
+
+    assert_eq!(
+      row,
+      Some((
+        "unsafeMintsWithPostage".into(),
+        Some(2),
+        "source".into(),
+        Some("test".into()),
+        true,
+        u64::try_from(tx.weight()).unwrap(),
+      ))
+    );
+  }
+}
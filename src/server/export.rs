@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Error};
+use hyper::body::Bytes;
+use hyper::{Body, Response};
+use ord::index::{ExportRow, Holding, Index};
+use std::sync::Arc;
+use tokio::task;
+use tracing::error;
+
+/// Holdings looked up per chunk, so long exports start streaming at once and
+/// never hold more than a page of rows.
+const PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+  Csv,
+  Ndjson,
+}
+
+impl Format {
+  pub fn from_query(format: Option<&str>) -> Result<Self, Error> {
+    match format.unwrap_or("csv") {
+      "csv" => Ok(Self::Csv),
+      "ndjson" => Ok(Self::Ndjson),
+      other => Err(anyhow!(
+        "unsupported format `{other}`, expected csv or ndjson"
+      )),
+    }
+  }
+
+  fn content_type(self) -> &'static str {
+    match self {
+      Self::Csv => "text/csv; charset=utf-8",
+      Self::Ndjson => "application/x-ndjson",
+    }
+  }
+
+  fn extension(self) -> &'static str {
+    match self {
+      Self::Csv => "csv",
+      Self::Ndjson => "ndjson",
+    }
+  }
+
+  fn lines(self, rows: &[ExportRow]) -> Result<String, Error> {
+    let mut lines = String::new();
+    for row in rows {
+      match self {
+        Self::Csv => lines.push_str(&row.to_csv()),
+        Self::Ndjson => lines.push_str(&serde_json::to_string(row)?),
+      }
+      lines.push('\n');
+    }
+    Ok(lines)
+  }
+}
+
+pub fn filename(address: &str, at_height: Option<u64>, format: Format) -> String {
+  match at_height {
+    Some(height) => format!("{address}-inscriptions-{height}.{}", format.extension()),
+    None => format!("{address}-inscriptions.{}", format.extension()),
+  }
+}
+
+/// Streams `holdings` a page at a time. A failure midway aborts the body,
+/// so clients never mistake a truncated export for a complete one.
+pub fn response(
+  index: Arc<Index>,
+  format: Format,
+  filename: String,
+  holdings: Vec<Holding>,
+) -> Response<Body> {
+  let (mut sender, body) = Body::channel();
+
+  task::spawn(async move {
+    if format == Format::Csv
+      && sender
+        .send_data(Bytes::from(format!("{}\n", ExportRow::CSV_HEADER)))
+        .await
+        .is_err()
+    {
+      return;
+    }
+
+    for page in holdings.chunks(PAGE_SIZE) {
+      let index = index.clone();
+      let page = page.to_vec();
+      let lines = task::spawn_blocking(move || format.lines(&index.export_rows(&page)?))
+        .await
+        .map_err(Error::from)
+        .and_then(|lines| lines);
+
+      match lines {
+        Ok(lines) => {
+          if sender.send_data(Bytes::from(lines)).await.is_err() {
+            return;
+          }
+        }
+        Err(err) => {
+          error!(error = %err, "Export error");
+          sender.abort();
+          return;
+        }
+      }
+    }
+  });
+
+  Response::builder()
+    .header(hyper::header::CONTENT_TYPE, format.content_type())
+    .header(
+      hyper::header::CONTENT_DISPOSITION,
+      format!("attachment; filename=\"{filename}\""),
+    )
+    .body(body)
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn formats() {
+    assert_eq!(Format::from_query(None).unwrap(), Format::Csv);
+    assert_eq!(Format::from_query(Some("csv")).unwrap(), Format::Csv);
+    assert_eq!(Format::from_query(Some("ndjson")).unwrap(), Format::Ndjson);
+    assert!(Format::from_query(Some("xlsx")).is_err());
+  }
+
+  #[test]
+  fn filenames() {
+    assert_eq!(
+      filename("bc1qexample", None, Format::Csv),
+      "bc1qexample-inscriptions.csv"
+    );
+    assert_eq!(
+      filename("bc1qexample", Some(800_000), Format::Ndjson),
+      "bc1qexample-inscriptions-800000.ndjson"
+    );
+  }
+}
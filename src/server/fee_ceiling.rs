@@ -0,0 +1,61 @@
+use ord::subcommand::wallet::error::BuildError;
+use serde_json::Value;
+
+/// The params of a call that set a fee rate, in sat/vB.
+const FEE_RATE_PARAMS: [&str; 3] = ["fee_rate", "commit_fee_rate", "reveal_fee_rate"];
+
+/// Refuses `call` if it asks for a fee rate above `max`, before anything is
+/// built for it.
+pub fn check(call: &Value, max: f64) -> Result<(), BuildError> {
+  for param in FEE_RATE_PARAMS {
+    if let Some(fee_rate) = call["params"][param].as_f64() {
+      if fee_rate > max {
+        return Err(BuildError::FeeRateAboveCeiling {
+          param,
+          fee_rate,
+          max,
+        });
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn fee_rate_at_ceiling_passes() {
+    assert_eq!(
+      check(&json!({ "params": { "fee_rate": 500.0 } }), 500.0),
+      Ok(())
+    );
+    assert_eq!(check(&json!({ "params": {} }), 500.0), Ok(()));
+    assert_eq!(check(&Value::Null, 500.0), Ok(()));
+  }
+
+  #[test]
+  fn any_fee_rate_over_ceiling_fails() {
+    assert_eq!(
+      check(&json!({ "params": { "fee_rate": 5000 } }), 500.0),
+      Err(BuildError::FeeRateAboveCeiling {
+        param: "fee_rate",
+        fee_rate: 5000.0,
+        max: 500.0,
+      })
+    );
+    assert_eq!(
+      check(
+        &json!({ "params": { "fee_rate": 50, "reveal_fee_rate": 500.5 } }),
+        500.0
+      ),
+      Err(BuildError::FeeRateAboveCeiling {
+        param: "reveal_fee_rate",
+        fee_rate: 500.5,
+        max: 500.0,
+      })
+    );
+  }
+}
@@ -0,0 +1,79 @@
+use bitcoincore_rpc::RpcApi;
+use ord::index::Index;
+use ord::options::Options;
+use std::time::Duration;
+use tokio::{task, time};
+
+/// How long a readiness check waits for bitcoind and the index, so a hung
+/// node answers the probe with 503 instead of hanging it.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks that bitcoind answers and the index opens, returning the indexed
+/// height, or the reason the server cannot serve builds.
+pub async fn check(options: Options, timeout: Duration) -> Result<Option<u64>, String> {
+  let check = task::spawn_blocking(move || {
+    options
+      .bitcoin_rpc_client()
+      .and_then(|client| Ok(client.get_block_count()?))
+      .map_err(|err| format!("bitcoind rpc unavailable: {err}"))?;
+    Index::read_open(&options)
+      .and_then(|index| index.index_height())
+      .map_err(|err| format!("index unavailable: {err}"))
+  });
+
+  match time::timeout(timeout, check).await {
+    Ok(Ok(result)) => result,
+    Ok(Err(err)) => Err(format!("health check failed: {err}")),
+    Err(_) => Err(format!(
+      "bitcoind rpc did not answer within {}ms",
+      timeout.as_millis()
+    )),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, std::net::TcpListener};
+
+  #[test]
+  fn stalled_node_times_out() {
+    // accepted by the backlog, never answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let options = Options {
+      rpc_url: vec![listener.local_addr().unwrap().to_string()],
+      bitcoin_rpc_user: Some("user".into()),
+      bitcoin_rpc_pass: Some("pass".into()),
+      ..Options::default()
+    };
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime.block_on(check(options, Duration::from_millis(100)));
+    runtime.shutdown_background();
+
+    assert_eq!(
+      result,
+      Err("bitcoind rpc did not answer within 100ms".into())
+    );
+  }
+
+  #[test]
+  fn unreachable_node_is_unavailable() {
+    let port = TcpListener::bind("127.0.0.1:0")
+      .unwrap()
+      .local_addr()
+      .unwrap()
+      .port();
+    let options = Options {
+      rpc_url: vec![format!("127.0.0.1:{port}")],
+      bitcoin_rpc_user: Some("user".into()),
+      bitcoin_rpc_pass: Some("pass".into()),
+      ..Options::default()
+    };
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let err = runtime.block_on(check(options, TIMEOUT)).unwrap_err();
+    runtime.shutdown_background();
+
+    assert!(err.starts_with("bitcoind rpc unavailable: "), "{err}");
+  }
+}
@@ -0,0 +1,133 @@
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::psbt::Psbt;
+use bitcoin::{Transaction, Txid};
+use bitcoincore_rpc::RpcApi;
+use ord::index::MysqlDatabase;
+use ord::options::Options;
+use ord::subcommand::wallet::broadcast;
+use tracing::warn;
+
+/// Txids of built psbts and raw transactions. Sources are segwit, so signing
+/// does not change them.
+pub fn txids<'a>(txs: impl IntoIterator<Item = &'a String>) -> Vec<Txid> {
+  txs
+    .into_iter()
+    .filter_map(|tx| {
+      let bytes = Vec::from_hex(tx).ok()?;
+      match deserialize::<Psbt>(&bytes) {
+        Ok(psbt) => Some(psbt.unsigned_tx.txid()),
+        Err(_) => deserialize::<Transaction>(&bytes).ok().map(|tx| tx.txid()),
+      }
+    })
+    .collect()
+}
+
+/// Journals what `method` built at `fee_rate` for `purpose`. The journal is
+/// only for statistics, so failing to write it does not fail the request.
+pub fn record_build<'a>(
+  mysql: Option<&MysqlDatabase>,
+  method: &str,
+  purpose: Option<&str>,
+  fee_rate: f64,
+  txs: impl IntoIterator<Item = &'a String>,
+) {
+  let Some(mysql) = mysql else {
+    return;
+  };
+
+  if let Err(err) = mysql.insert_broadcast_journal(method, purpose, fee_rate, &txids(txs)) {
+    warn!(error = %err, method, "Journal build fail");
+  }
+}
+
+/// Txids the node took, rejected ones are neither journaled nor expected.
+fn accepted(output: &broadcast::Output) -> Vec<Txid> {
+  output
+    .status
+    .iter()
+    .filter(|status| status.accepted)
+    .map(|status| status.txid)
+    .collect()
+}
+
+/// Stamps accepted transactions with the tip they were broadcast at, and
+/// marks the reveals among them pending.
+pub fn record_broadcast(
+  mysql: Option<&MysqlDatabase>,
+  options: &Options,
+  output: &broadcast::Output,
+) {
+  let Some(mysql) = mysql else {
+    return;
+  };
+
+  let accepted = accepted(output);
+  if accepted.is_empty() {
+    return;
+  }
+
+  let result = options
+    .bitcoin_rpc_client()
+    .and_then(|client| Ok(client.get_block_count()?))
+    .and_then(|tip| mysql.mark_broadcast(&accepted, tip));
+  if let Err(err) = result {
+    warn!(error = %err, "Journal broadcast fail");
+  }
+
+  if let Err(err) = mysql.expect_reveals(&accepted) {
+    warn!(error = %err, "Expect reveals fail");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bitcoin::consensus::encode::serialize_hex;
+  use bitcoin::hashes::Hash;
+  use bitcoin::{OutPoint, PackedLockTime, Script, Sequence, TxIn, Witness};
+
+  fn unsigned() -> Transaction {
+    Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: Script::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::new(),
+      }],
+      output: vec![],
+    }
+  }
+
+  #[test]
+  fn psbts_and_signed_transactions_share_txids() {
+    let psbt = Psbt::from_unsigned_tx(unsigned()).unwrap();
+    let mut signed = unsigned();
+    signed.input[0].witness = Witness::from_vec(vec![vec![1; 64]]);
+
+    assert_eq!(
+      txids(&[serialize_hex(&psbt), serialize_hex(&signed), "zz".into()]),
+      vec![unsigned().txid(), unsigned().txid()]
+    );
+  }
+
+  #[test]
+  fn only_accepted_transactions_are_recorded() {
+    let txid = |n: u8| Txid::from_inner([n; 32]);
+    let status = |n: u8, accepted: bool| broadcast::Status {
+      txid: txid(n),
+      accepted,
+      error: (!accepted).then(|| "txn-mempool-conflict".into()),
+    };
+
+    let output = broadcast::Output {
+      order: vec![txid(1), txid(2), txid(3)],
+      status: vec![status(1, true), status(2, false), status(3, true)],
+      package: None,
+    };
+
+    assert_eq!(accepted(&output), vec![txid(1), txid(3)]);
+  }
+}
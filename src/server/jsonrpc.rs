@@ -0,0 +1,415 @@
+use crate::build_pool::BuildsBusy;
+use anyhow::Error;
+use hyper::{header, Body, Method, Response, StatusCode};
+use ord::index::NodeUnavailable;
+use ord::subcommand::wallet::error::BuildError;
+use serde_json::{json, Value};
+use std::fmt::{self, Display, Formatter};
+
+/// Routes whose request bodies are JSON-RPC calls. Their responses are
+/// wrapped in JSON-RPC 2.0 envelopes, the others are left alone.
+pub const METHODS: [&str; 22] = [
+  "isWhitelist",
+  "watchAddress",
+  "decodeReveal",
+  "revealFeeCheck",
+  "broadcast",
+  "broadcastPackage",
+  "resumeReveal",
+  "bumpReveal",
+  "recover",
+  "accelerate",
+  "bumpFee",
+  "mint",
+  "estimateMint",
+  "mints",
+  "transfer",
+  "transferWithFee",
+  "cancel",
+  "mintWithPostage",
+  "mintsWithPostage",
+  "unsafeMintsWithPostage",
+  "reMint",
+  "reMints",
+];
+
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+
+// application errors, from the range JSON-RPC leaves to implementations,
+// next to `Maintenance::CODE`
+pub const SERVER_ERROR: i32 = -32000;
+pub const INSUFFICIENT_FUNDS: i32 = -32002;
+pub const UNAVAILABLE: i32 = -32003;
+pub const RATE_LIMITED: i32 = -32004;
+pub const UNAUTHORIZED: i32 = -32005;
+pub const FORBIDDEN: i32 = -32006;
+
+/// A call the routes could not make sense of.
+#[derive(Debug, PartialEq)]
+pub enum RequestError {
+  Parse(String),
+  InvalidRequest(String),
+  InvalidParams(String),
+  MethodNotFound(String),
+  TooLarge(usize),
+}
+
+impl Display for RequestError {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      Self::Parse(_) => write!(f, "Parse error"),
+      Self::InvalidRequest(_) => write!(f, "Invalid request"),
+      Self::InvalidParams(_) => write!(f, "Invalid params"),
+      Self::MethodNotFound(method) => write!(f, "Method not found: {method}"),
+      Self::TooLarge(limit) => write!(f, "Request body larger than {limit} bytes"),
+    }
+  }
+}
+
+impl std::error::Error for RequestError {}
+
+pub fn wraps(method: &Method, path: &str) -> bool {
+  *method == Method::POST && METHODS.contains(&path.trim_start_matches('/'))
+}
+
+pub fn parse(body: &[u8]) -> Result<Value, RequestError> {
+  serde_json::from_slice(body).map_err(|err| RequestError::Parse(err.to_string()))
+}
+
+/// The id of `call`, `Null` when it has none.
+pub fn request_id(call: &Value) -> Value {
+  call.get("id").cloned().unwrap_or(Value::Null)
+}
+
+pub fn code(err: &Error) -> i32 {
+  if let Some(err) = err.downcast_ref::<RequestError>() {
+    return match err {
+      RequestError::Parse(_) => PARSE_ERROR,
+      RequestError::InvalidRequest(_) => INVALID_REQUEST,
+      RequestError::InvalidParams(_) => INVALID_PARAMS,
+      RequestError::MethodNotFound(_) => METHOD_NOT_FOUND,
+      RequestError::TooLarge(_) => INVALID_REQUEST,
+    };
+  }
+  if err.is::<NodeUnavailable>() || err.is::<BuildsBusy>() {
+    return UNAVAILABLE;
+  }
+  match err.downcast_ref::<BuildError>() {
+    Some(BuildError::InsufficientFunds { .. }) => INSUFFICIENT_FUNDS,
+    Some(BuildError::QuotaExceeded { .. }) => RATE_LIMITED,
+    Some(BuildError::PolicyViolation { .. }) => FORBIDDEN,
+    _ => SERVER_ERROR,
+  }
+}
+
+// what a client needs to act on the error beyond its message
+fn data(err: &Error) -> Value {
+  match err.downcast_ref::<RequestError>() {
+    Some(
+      RequestError::Parse(detail)
+      | RequestError::InvalidRequest(detail)
+      | RequestError::InvalidParams(detail),
+    ) => json!(detail),
+    Some(RequestError::TooLarge(limit)) => json!({ "limit": limit }),
+    _ => match (
+      err.downcast_ref::<BuildError>(),
+      err.downcast_ref::<BuildsBusy>(),
+    ) {
+      (Some(err), _) => build_data(err),
+      (None, Some(busy)) => json!({ "retry_after": busy.retry_after }),
+      (None, None) => Value::Null,
+    },
+  }
+}
+
+// the build error's values, tagged with its name
+fn build_data(err: &BuildError) -> Value {
+  let mut data = match err.data() {
+    Value::Object(data) => data,
+    _ => Default::default(),
+  };
+  data.insert("code".into(), json!(err.code()));
+  Value::Object(data)
+}
+
+/// The body of a failed build outside of JSON-RPC, the error's name,
+/// `message` and the values it was built from. `None` for other errors,
+/// which keep their plain text body.
+pub fn build_error(err: &Error, message: &str) -> Option<Value> {
+  let err = err.downcast_ref::<BuildError>()?;
+  Some(json!({
+    "code": err.code(),
+    "message": message,
+    "data": err.data(),
+  }))
+}
+
+pub fn status(err: &Error) -> Option<StatusCode> {
+  match err.downcast_ref::<RequestError>()? {
+    RequestError::MethodNotFound(_) => Some(StatusCode::NOT_FOUND),
+    RequestError::TooLarge(_) => Some(StatusCode::PAYLOAD_TOO_LARGE),
+    _ => Some(StatusCode::BAD_REQUEST),
+  }
+}
+
+/// An error envelope for `err`, reported to the client as `message`.
+pub fn error_response(
+  id: Value,
+  status: StatusCode,
+  err: &Error,
+  message: String,
+) -> Response<Body> {
+  envelope(
+    id,
+    status,
+    json!({
+      "code": code(err),
+      "message": message,
+      "data": data(err),
+    }),
+  )
+}
+
+fn envelope(id: Value, status: StatusCode, error: Value) -> Response<Body> {
+  Response::builder()
+    .status(status)
+    .header(header::CONTENT_TYPE, "application/json")
+    .body(Body::from(
+      json!({ "jsonrpc": "2.0", "id": id, "error": error }).to_string(),
+    ))
+    .unwrap()
+}
+
+/// Wraps a response built by a route. Successful bodies become the result
+/// as they are, other statuses become an error with the body as message,
+/// unless the body already is an error envelope, like the maintenance one.
+pub async fn wrap(id: Value, response: Response<Body>) -> Response<Body> {
+  let (mut parts, body) = response.into_parts();
+  let body = match hyper::body::to_bytes(body).await {
+    Ok(body) => body,
+    Err(err) => {
+      return envelope(
+        id,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        json!({ "code": SERVER_ERROR, "message": err.to_string(), "data": null }),
+      )
+    }
+  };
+
+  let envelope = if parts.status.is_success() {
+    let result = if body.is_empty() {
+      Value::Null
+    } else {
+      serde_json::from_slice(&body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into()))
+    };
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+  } else {
+    let error = match serde_json::from_slice::<Value>(&body) {
+      Ok(Value::Object(mut envelope)) if envelope.contains_key("error") => {
+        envelope.remove("error").unwrap()
+      }
+      _ => {
+        let code = match parts.status {
+          StatusCode::NOT_FOUND => METHOD_NOT_FOUND,
+          StatusCode::SERVICE_UNAVAILABLE => UNAVAILABLE,
+          StatusCode::TOO_MANY_REQUESTS => RATE_LIMITED,
+          _ => SERVER_ERROR,
+        };
+        let message = match String::from_utf8_lossy(&body) {
+          message if message.is_empty() => parts
+            .status
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_owned(),
+          message => message.into_owned(),
+        };
+        json!({ "code": code, "message": message, "data": null })
+      }
+    };
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+  };
+
+  parts.headers.remove(header::CONTENT_LENGTH);
+  parts.headers.insert(
+    header::CONTENT_TYPE,
+    header::HeaderValue::from_static("application/json"),
+  );
+  Response::from_parts(parts, Body::from(envelope.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::anyhow;
+
+  async fn body(response: Response<Body>) -> Value {
+    serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap()
+  }
+
+  #[test]
+  fn only_json_rpc_routes_are_wrapped() {
+    assert!(wraps(&Method::POST, "/transfer"));
+    assert!(wraps(&Method::POST, "/broadcastPackage"));
+    assert!(!wraps(&Method::GET, "/transfer"));
+    assert!(!wraps(&Method::POST, "/admin/maintenance"));
+    assert!(!wraps(&Method::POST, "/airdrop"));
+  }
+
+  #[test]
+  fn id_is_read_from_any_json() {
+    assert_eq!(
+      request_id(&parse(br#"{"jsonrpc":"2.0","id":7,"method":"mint"}"#).unwrap()),
+      json!(7)
+    );
+    assert_eq!(
+      request_id(&parse(br#"{"id":"abc"}"#).unwrap()),
+      json!("abc")
+    );
+    assert_eq!(request_id(&parse(b"[]").unwrap()), Value::Null);
+    assert!(matches!(parse(b"{"), Err(RequestError::Parse(_))));
+  }
+
+  #[test]
+  fn errors_get_distinct_codes() {
+    assert_eq!(code(&RequestError::Parse("eof".into()).into()), PARSE_ERROR);
+    assert_eq!(
+      code(&RequestError::InvalidParams("missing field".into()).into()),
+      INVALID_PARAMS
+    );
+    assert_eq!(
+      code(&RequestError::MethodNotFound("mnit".into()).into()),
+      METHOD_NOT_FOUND
+    );
+    assert_eq!(code(&RequestError::TooLarge(10).into()), INVALID_REQUEST);
+    assert_eq!(
+      code(
+        &BuildError::InsufficientFunds {
+          available: 1,
+          required: 2
+        }
+        .into()
+      ),
+      INSUFFICIENT_FUNDS
+    );
+    assert_eq!(
+      code(
+        &BuildError::PolicyViolation {
+          api_key: "acme".into(),
+          source: Some("bc1qexample".into()),
+          max_daily_sources: Some(5),
+        }
+        .into()
+      ),
+      FORBIDDEN
+    );
+    assert_eq!(code(&anyhow!("anything else")), SERVER_ERROR);
+  }
+
+  #[test]
+  fn build_errors_carry_their_name_and_values() {
+    let err = Error::from(BuildError::NoCardinalUtxos {
+      min_value: Some(1000),
+    });
+    assert_eq!(
+      build_error(&err, "no utxos"),
+      Some(json!({
+        "code": "no_cardinal_utxos",
+        "message": "no utxos",
+        "data": { "min_value": 1000 },
+      }))
+    );
+    assert_eq!(
+      build_error(&anyhow!("anything else"), "anything else"),
+      None
+    );
+  }
+
+  #[tokio::test]
+  async fn error_envelope_echoes_id() {
+    let err = Error::from(BuildError::InsufficientFunds {
+      available: 1_000,
+      required: 5_000,
+    });
+    let response = error_response(json!(3), StatusCode::BAD_REQUEST, &err, err.to_string());
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+      body(response).await,
+      json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "error": {
+          "code": INSUFFICIENT_FUNDS,
+          "message": err.to_string(),
+          "data": {
+            "code": "insufficient_funds",
+            "available": 1_000,
+            "required": 5_000,
+            "shortfall": 4_000,
+          },
+        },
+      })
+    );
+  }
+
+  #[tokio::test]
+  async fn result_keeps_payload_shape() {
+    let response = wrap(
+      json!("a"),
+      Response::new(Body::from(r#"{"network_fee":200}"#)),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      body(response).await,
+      json!({ "jsonrpc": "2.0", "id": "a", "result": { "network_fee": 200 } })
+    );
+  }
+
+  #[tokio::test]
+  async fn unsuccessful_responses_become_errors() {
+    let response = wrap(
+      json!(1),
+      Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .body(Body::from("node is syncing"))
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+      body(response).await,
+      json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "error": { "code": UNAVAILABLE, "message": "node is syncing", "data": null },
+      })
+    );
+  }
+
+  #[tokio::test]
+  async fn error_envelopes_get_the_id() {
+    let error = json!({ "code": -32001, "message": "Service is under maintenance", "data": null });
+    let response = wrap(
+      json!(9),
+      Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .body(Body::from(
+          json!({ "jsonrpc": "2.0", "id": null, "error": error }).to_string(),
+        ))
+        .unwrap(),
+    )
+    .await;
+
+    assert_eq!(
+      body(response).await,
+      json!({ "jsonrpc": "2.0", "id": 9, "error": error })
+    );
+  }
+}
@@ -0,0 +1,559 @@
+use crate::access_key::AccessKeys;
+use crate::query_param;
+use anyhow::{anyhow, Error};
+use bitcoin::Address;
+use chrono::{NaiveDate, Utc};
+use hyper::{Body, Method, Request, StatusCode};
+use ord::index::{ApiKeyUsage, MysqlDatabase};
+use ord::subcommand::wallet::error::BuildError;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// The sources an api key may act for, given after its token in the api
+/// keys file as `sources=<ADDRESS>,...` and `max_daily_sources=<COUNT>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KeyPolicy {
+  /// Any source is allowed when `None`.
+  pub sources: Option<BTreeSet<String>>,
+  pub max_daily_sources: Option<u64>,
+}
+
+impl KeyPolicy {
+  pub fn parse<'a>(label: &str, fields: impl IntoIterator<Item = &'a str>) -> Result<Self, Error> {
+    let mut policy = Self::default();
+
+    for field in fields {
+      match field.split_once('=') {
+        Some(("sources", sources)) => {
+          let sources = sources
+            .split(',')
+            .filter(|source| !source.is_empty())
+            .map(|source| {
+              source
+                .parse::<Address>()
+                .map(|address| address.to_string())
+                .map_err(|err| anyhow!("invalid source `{source}` for api key `{label}`: {err}"))
+            })
+            .collect::<Result<BTreeSet<String>, Error>>()?;
+          if sources.is_empty() {
+            return Err(anyhow!("empty sources for api key `{label}`"));
+          }
+          policy.sources = Some(sources);
+        }
+        Some(("max_daily_sources", count)) => {
+          policy.max_daily_sources = Some(count.parse().map_err(|err| {
+            anyhow!("invalid max_daily_sources `{count}` for api key `{label}`: {err}")
+          })?);
+        }
+        _ => return Err(anyhow!("invalid policy `{field}` for api key `{label}`")),
+      }
+    }
+
+    Ok(policy)
+  }
+
+  /// Whether the key may only act for some sources.
+  pub fn is_restricted(&self) -> bool {
+    self.sources.is_some() || self.max_daily_sources.is_some()
+  }
+
+  fn allows(&self, source: &str) -> bool {
+    self
+      .sources
+      .as_ref()
+      .map_or(true, |sources| sources.contains(source))
+  }
+}
+
+/// Sources each api key acted for today, kept in memory when no MySQL is
+/// configured, where they are kept across restarts instead.
+#[derive(Debug, Default)]
+pub struct SourceUsage {
+  days: Mutex<BTreeMap<(String, NaiveDate), DayUsage>>,
+}
+
+#[derive(Debug, Default)]
+struct DayUsage {
+  requests: BTreeMap<String, u64>,
+  violations: u64,
+}
+
+impl SourceUsage {
+  fn sources(&self, api_key: &str, day: NaiveDate) -> Vec<String> {
+    self
+      .days
+      .lock()
+      .unwrap()
+      .get(&(api_key.to_owned(), day))
+      .map(|usage| usage.requests.keys().cloned().collect())
+      .unwrap_or_default()
+  }
+
+  /// Counts a request of `api_key` for `source`, unless it is a new source
+  /// for the day and the key already has `max_sources`.
+  fn take(&self, api_key: &str, source: &str, day: NaiveDate, max_sources: Option<u64>) -> bool {
+    let mut days = self.days.lock().unwrap();
+    days.retain(|(_, other), _| *other >= day);
+
+    let usage = days.entry((api_key.to_owned(), day)).or_default();
+    if !usage.requests.contains_key(source)
+      && max_sources.map_or(false, |max| usage.requests.len() as u64 >= max)
+    {
+      return false;
+    }
+    *usage.requests.entry(source.to_owned()).or_default() += 1;
+    true
+  }
+
+  fn violate(&self, api_key: &str, day: NaiveDate) {
+    let mut days = self.days.lock().unwrap();
+    days
+      .entry((api_key.to_owned(), day))
+      .or_default()
+      .violations += 1;
+  }
+
+  fn usage(&self, day: NaiveDate) -> Vec<ApiKeyUsage> {
+    self
+      .days
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|((_, other), _)| *other == day)
+      .map(|((api_key, day), usage)| ApiKeyUsage {
+        api_key: api_key.clone(),
+        day: *day,
+        distinct_sources: usage.requests.len() as u64,
+        requests: usage.requests.values().sum(),
+        violations: usage.violations,
+      })
+      .collect()
+  }
+}
+
+/// A request of a restricted api key for a source its policy allows, to be
+/// counted against the key once it has built.
+#[derive(Debug)]
+pub struct SourceUse {
+  api_key: String,
+  policy: KeyPolicy,
+  source: String,
+}
+
+/// Holds a POST made with a restricted api key to the key's policy, before
+/// anything is built. The source is read from `params.source` of the call,
+/// or the `source` query parameter, and requests naming none are refused.
+/// Refused requests are recorded in MySQL when it is configured.
+pub fn check_request(
+  mysql: Option<&MysqlDatabase>,
+  keys: &AccessKeys,
+  usage: &SourceUsage,
+  req: &Request<Body>,
+  call: Option<&Value>,
+) -> Result<Option<SourceUse>, Error> {
+  let Some((api_key, policy)) = keys
+    .label(req)
+    .filter(|_| req.method() == Method::POST && !req.uri().path().starts_with("/admin"))
+    .and_then(|api_key| Some((api_key, keys.policy(api_key)?)))
+    .filter(|(_, policy)| policy.is_restricted())
+  else {
+    return Ok(None);
+  };
+
+  let source = call
+    .and_then(|call| call["params"]["source"].as_str().map(str::to_owned))
+    .or_else(|| query_param(req, "source"))
+    .and_then(|source| source.parse::<Address>().ok())
+    .map(|source| source.to_string());
+
+  check(mysql, usage, api_key, policy, source).map(Some)
+}
+
+fn check(
+  mysql: Option<&MysqlDatabase>,
+  usage: &SourceUsage,
+  api_key: &str,
+  policy: &KeyPolicy,
+  source: Option<String>,
+) -> Result<SourceUse, Error> {
+  let day = Utc::now().date_naive();
+  let Some(source) = source else {
+    return Err(violation(mysql, usage, api_key, None, day, None));
+  };
+  if !policy.allows(&source) {
+    return Err(violation(mysql, usage, api_key, Some(source), day, None));
+  }
+  if let Some(max) = policy.max_daily_sources {
+    let sources = match mysql {
+      Some(mysql) => mysql.get_api_key_sources(api_key, day)?,
+      None => usage.sources(api_key, day),
+    };
+    if !sources.contains(&source) && sources.len() as u64 >= max {
+      return Err(violation(
+        mysql,
+        usage,
+        api_key,
+        Some(source),
+        day,
+        Some(max),
+      ));
+    }
+  }
+
+  Ok(SourceUse {
+    api_key: api_key.to_owned(),
+    policy: policy.clone(),
+    source,
+  })
+}
+
+/// Counts a request that built against its api key, failing with
+/// `BuildError::PolicyViolation` if concurrent requests used up the key's
+/// sources for the day since it was checked.
+pub fn record(
+  mysql: Option<&MysqlDatabase>,
+  usage: &SourceUsage,
+  source_use: SourceUse,
+) -> Result<(), Error> {
+  let SourceUse {
+    api_key,
+    policy,
+    source,
+  } = source_use;
+  let day = Utc::now().date_naive();
+  let taken = match mysql {
+    Some(mysql) => mysql.use_api_key_source(&api_key, &source, day, policy.max_daily_sources)?,
+    None => usage.take(&api_key, &source, day, policy.max_daily_sources),
+  };
+  if taken {
+    return Ok(());
+  }
+  Err(violation(
+    mysql,
+    usage,
+    &api_key,
+    Some(source),
+    day,
+    policy.max_daily_sources,
+  ))
+}
+
+fn violation(
+  mysql: Option<&MysqlDatabase>,
+  usage: &SourceUsage,
+  api_key: &str,
+  source: Option<String>,
+  day: NaiveDate,
+  max_daily_sources: Option<u64>,
+) -> Error {
+  let reason = reason(source.as_deref(), max_daily_sources);
+  warn!(
+    api_key,
+    source = source.as_deref(),
+    reason,
+    "Policy violation"
+  );
+  match mysql {
+    Some(mysql) => {
+      if let Err(err) =
+        mysql.insert_api_key_violation(api_key, source.as_deref().unwrap_or_default(), day, reason)
+      {
+        warn!(error = %err, api_key, "Record policy violation fail");
+      }
+    }
+    None => usage.violate(api_key, day),
+  }
+
+  BuildError::PolicyViolation {
+    api_key: api_key.to_owned(),
+    source,
+    max_daily_sources,
+  }
+  .into()
+}
+
+fn reason(source: Option<&str>, max_daily_sources: Option<u64>) -> &'static str {
+  match (source, max_daily_sources) {
+    (None, _) => "source_missing",
+    (Some(_), None) => "source_not_allowed",
+    (Some(_), Some(_)) => "max_daily_sources",
+  }
+}
+
+pub fn status(err: &Error) -> Option<StatusCode> {
+  match err.downcast_ref::<BuildError>() {
+    Some(BuildError::PolicyViolation { .. }) => Some(StatusCode::FORBIDDEN),
+    _ => None,
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyReport {
+  #[serde(flatten)]
+  pub usage: ApiKeyUsage,
+  /// `None` for keys that made requests but are no longer configured.
+  pub policy: Option<KeyPolicy>,
+}
+
+/// Handles `/admin/apiKeys`: the usage of every configured api key on
+/// `day` with its policy, followed by that of keys no longer configured.
+pub fn report(
+  mysql: Option<&MysqlDatabase>,
+  usage: &SourceUsage,
+  policies: impl IntoIterator<Item = (String, KeyPolicy)>,
+  day: NaiveDate,
+) -> Result<Vec<KeyReport>, Error> {
+  let mut usage = match mysql {
+    Some(mysql) => mysql.get_api_key_usage(day)?,
+    None => usage.usage(day),
+  }
+  .into_iter()
+  .map(|usage| (usage.api_key.clone(), usage))
+  .collect::<BTreeMap<String, ApiKeyUsage>>();
+
+  let mut reports = policies
+    .into_iter()
+    .map(|(api_key, policy)| KeyReport {
+      usage: usage.remove(&api_key).unwrap_or(ApiKeyUsage {
+        api_key,
+        day,
+        distinct_sources: 0,
+        requests: 0,
+        violations: 0,
+      }),
+      policy: Some(policy),
+    })
+    .collect::<Vec<KeyReport>>();
+  reports.extend(usage.into_values().map(|usage| KeyReport {
+    usage,
+    policy: None,
+  }));
+  Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const SOURCE: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+  const OTHER: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+  // a request that builds, counted once it has
+  fn build(
+    usage: &SourceUsage,
+    api_key: &str,
+    policy: &KeyPolicy,
+    source: Option<&str>,
+  ) -> Result<(), Error> {
+    let source_use = check(None, usage, api_key, policy, source.map(str::to_owned))?;
+    record(None, usage, source_use)
+  }
+
+  fn violation(err: Error) -> Option<u64> {
+    match err.downcast_ref::<BuildError>() {
+      Some(BuildError::PolicyViolation {
+        max_daily_sources, ..
+      }) => *max_daily_sources,
+      other => panic!("unexpected error {other:?}"),
+    }
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!(KeyPolicy::parse("acme", []).unwrap(), KeyPolicy::default());
+    assert_eq!(
+      KeyPolicy::parse(
+        "acme",
+        [
+          format!("sources={SOURCE},{OTHER}").as_str(),
+          "max_daily_sources=1"
+        ]
+      )
+      .unwrap(),
+      KeyPolicy {
+        sources: Some([SOURCE.to_owned(), OTHER.to_owned()].into()),
+        max_daily_sources: Some(1),
+      }
+    );
+    assert!(KeyPolicy::parse("acme", ["sources="]).is_err());
+    assert!(KeyPolicy::parse("acme", ["sources=nope"]).is_err());
+    assert!(KeyPolicy::parse("acme", ["max_daily_sources=-1"]).is_err());
+    assert!(KeyPolicy::parse("acme", ["daily=1"]).is_err());
+  }
+
+  #[test]
+  fn allowlist() {
+    let usage = SourceUsage::default();
+    let policy = KeyPolicy {
+      sources: Some([SOURCE.to_owned()].into()),
+      max_daily_sources: None,
+    };
+
+    build(&usage, "acme", &policy, Some(SOURCE)).unwrap();
+    assert_eq!(
+      violation(build(&usage, "acme", &policy, Some(OTHER)).unwrap_err()),
+      None
+    );
+
+    let day = Utc::now().date_naive();
+    assert_eq!(
+      usage.usage(day),
+      vec![ApiKeyUsage {
+        api_key: "acme".into(),
+        day,
+        distinct_sources: 1,
+        requests: 1,
+        violations: 1,
+      }]
+    );
+  }
+
+  #[test]
+  fn max_daily_sources() {
+    let usage = SourceUsage::default();
+    let policy = KeyPolicy {
+      sources: None,
+      max_daily_sources: Some(1),
+    };
+
+    build(&usage, "acme", &policy, Some(SOURCE)).unwrap();
+    build(&usage, "acme", &policy, Some(SOURCE)).unwrap();
+    assert_eq!(
+      violation(build(&usage, "acme", &policy, Some(OTHER)).unwrap_err()),
+      Some(1)
+    );
+    build(&usage, "other", &policy, Some(OTHER)).unwrap();
+
+    let day = Utc::now().date_naive();
+    let report = report(
+      None,
+      &usage,
+      [
+        ("acme".to_owned(), policy),
+        ("idle".to_owned(), KeyPolicy::default()),
+      ],
+      day,
+    )
+    .unwrap();
+    assert_eq!(
+      report
+        .iter()
+        .map(|report| (
+          report.usage.api_key.as_str(),
+          report.usage.distinct_sources,
+          report.usage.requests,
+          report.usage.violations,
+          report.policy.is_some(),
+        ))
+        .collect::<Vec<_>>(),
+      vec![
+        ("acme", 1, 2, 1, true),
+        ("idle", 0, 0, 0, true),
+        ("other", 1, 1, 0, false),
+      ]
+    );
+  }
+
+  #[test]
+  fn restricted_keys_must_name_a_source() {
+    let usage = SourceUsage::default();
+    let keys = AccessKeys::new(
+      Some(&format!("acme=hunter2 sources={SOURCE},ops=swordfish")),
+      &[],
+    )
+    .unwrap();
+    let request = |token: &str, uri: &str| {
+      Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Authorization", format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap()
+    };
+    let call = json!({ "method": "accelerate", "params": { "txid": "00" } });
+
+    assert_eq!(
+      violation(
+        check_request(
+          None,
+          &keys,
+          &usage,
+          &request("hunter2", "/accelerate"),
+          Some(&call)
+        )
+        .unwrap_err()
+      ),
+      None
+    );
+    assert!(check_request(
+      None,
+      &keys,
+      &usage,
+      &request("hunter2", &format!("/airdrop?source={SOURCE}")),
+      None
+    )
+    .unwrap()
+    .is_some());
+    assert!(check_request(
+      None,
+      &keys,
+      &usage,
+      &request("swordfish", "/accelerate"),
+      Some(&call)
+    )
+    .unwrap()
+    .is_none());
+  }
+
+  #[test]
+  fn only_built_requests_count() {
+    let usage = SourceUsage::default();
+    let policy = KeyPolicy {
+      sources: None,
+      max_daily_sources: Some(1),
+    };
+
+    // dry runs and failed builds are checked, never recorded
+    check(None, &usage, "acme", &policy, Some(SOURCE.into())).unwrap();
+    assert!(usage.usage(Utc::now().date_naive()).is_empty());
+    build(&usage, "acme", &policy, Some(OTHER)).unwrap();
+
+    // both pass the check, the second to build finds the day used up
+    let first = check(None, &usage, "other", &policy, Some(SOURCE.into())).unwrap();
+    let second = check(None, &usage, "other", &policy, Some(OTHER.into())).unwrap();
+    record(None, &usage, first).unwrap();
+    assert_eq!(
+      violation(record(None, &usage, second).unwrap_err()),
+      Some(1)
+    );
+  }
+
+  #[test]
+  fn earlier_days_are_dropped() {
+    let usage = SourceUsage::default();
+    let day = NaiveDate::from_ymd_opt(2023, 5, 1).unwrap();
+    assert!(usage.take("acme", SOURCE, day, Some(1)));
+    assert!(!usage.take("acme", OTHER, day, Some(1)));
+    assert!(usage.take("acme", OTHER, day.succ_opt().unwrap(), Some(1)));
+    assert!(usage.usage(day).is_empty());
+  }
+
+  #[test]
+  fn policy_violation_is_forbidden() {
+    let err = Error::from(BuildError::PolicyViolation {
+      api_key: "acme".into(),
+      source: Some("bc1qexample".into()),
+      max_daily_sources: Some(5),
+    });
+    assert_eq!(status(&err), Some(StatusCode::FORBIDDEN));
+    assert_eq!(
+      err.to_string(),
+      "api key `acme` has acted for its 5 distinct sources today and cannot act for source `bc1qexample`"
+    );
+    assert_eq!(status(&anyhow!("other")), None);
+  }
+}
@@ -1,35 +1,165 @@
+use access_key::AccessKeys;
+use admin::AdminKeys;
 use anyhow::{anyhow, Error};
-use bitcoin::{Address, Amount, Network, OutPoint, Txid};
-use clap::{Arg, Command};
+use api_key::Brand;
+use api_version::{
+  cancel_response, mint_response, mints_response, transfer_response, with_signer, ApiVersion,
+};
+use bitcoin::secp256k1::rand;
+use bitcoin::{Address, AddressType, Amount, OutPoint, Txid};
+use bitcoincore_rpc::RpcApi;
+use blocklist::BlocklistChange;
+use build_pool::{BuildPool, BuildsBusy};
+use capability::Capabilities;
+use chrono::{NaiveDate, Utc};
+use clap::{Arg, ArgAction, Command};
+use futures::stream::{self, StreamExt};
+use hyper::server::conn::AddrStream;
 use hyper::server::Server;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, StatusCode};
-use log::{error, info};
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use jsonrpc::RequestError;
+use key_policy::SourceUsage;
+use maintenance::{Maintenance, MaintenanceState};
+use manifest::Manifests;
+use networks::{Network, Networks};
 use ord::chain::Chain;
-use ord::index::MysqlDatabase;
+use ord::index::{
+  cache_invalidation_counts, node_error_counts, ActivityCursor, ActivityPage,
+  AddressInscriptionsPage, BackfillJobState, BlocklistKind, GenesisCursor, GenesisFilter, Index,
+  InscriptionsByHeightPage, MysqlDatabase, NodeUnavailable, PurposeFilter, RevealExpectationPage,
+  RevealExpectationState, VoucherPolicy, WhitelistPage,
+};
+use ord::logging::{self, LogFormat};
 use ord::options::Options;
 use ord::outgoing::Outgoing;
+use ord::reveal_key::{RevealKeys, RevealSeed};
+use ord::rpc_failover::{self, rpc_endpoint_status};
+use ord::signer::SignerWebhooks;
+use ord::subcommand::wallet::accelerate::Accelerate;
+use ord::subcommand::wallet::address_utxos;
+use ord::subcommand::wallet::broadcast::Broadcast;
+use ord::subcommand::wallet::bump_fee::BumpFee;
+use ord::subcommand::wallet::bump_reveal::BumpReveal;
 use ord::subcommand::wallet::cancel::Cancel;
+use ord::subcommand::wallet::decode_reveal::DecodeReveal;
+use ord::subcommand::wallet::fee_rates::FeeRates;
+use ord::subcommand::wallet::funding::FundingInput;
 use ord::subcommand::wallet::mint::Mint;
 use ord::subcommand::wallet::mints;
+use ord::subcommand::wallet::recover::Recover;
+use ord::subcommand::wallet::resume_reveal::ResumeReveal;
+use ord::subcommand::wallet::reveal_fee_check::RevealFeeCheck;
+use ord::subcommand::wallet::script_path::ScriptPathInput;
 use ord::subcommand::wallet::transfer::Transfer;
-use ord::{FeeRate, TransactionBuilder};
+use ord::subcommand::wallet::unsafe_destination::UnsafeDestination;
+use ord::{ContentEncoding, ContentFormat, FeeRate, InscriptionId, TransactionBuilder};
+use purpose::Purposes;
+use quota::QuotaRequest;
+use rate_limit::RateLimiter;
 use serde::{Deserialize, Serialize};
+use service_address::ServiceAddresses;
+use shutdown::InFlight;
 use std::collections::BTreeMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 use tokio::task;
+use tracing::{error, info, info_span, Instrument, Span};
+use warning::DisabledWarnings;
+
+mod access_key;
+mod admin;
+mod airdrop;
+mod api_key;
+mod api_version;
+mod batch;
+mod blocklist;
+mod body;
+mod build_pool;
+mod capability;
+mod export;
+mod fee_ceiling;
+mod health;
+mod journal;
+mod jsonrpc;
+mod key_policy;
+mod maintenance;
+mod manifest;
+mod networks;
+mod purpose;
+mod quota;
+mod rate_limit;
+mod service_address;
+mod shutdown;
+mod warning;
+mod ws;
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 struct MintParam {
   fee_rate: f64,
   source: Address,
+  #[serde(default)]
+  change_address: Option<Address>,
   content: String,
   destination: Option<Address>,
   extension: Option<String>,
+  #[serde(default)]
+  content_type: Option<String>,
+  #[serde(default)]
+  content_encoding: Option<ContentFormat>,
+  #[serde(default)]
+  metadata: Option<serde_json::Value>,
+  #[serde(default)]
+  metaprotocol: Option<String>,
+  #[serde(default)]
+  pointer: Option<u64>,
   repeat: Option<u64>,
+  #[serde(default)]
+  allow_burn: bool,
+  #[serde(default)]
+  funding_inputs: Vec<FundingInput>,
+  #[serde(default)]
+  inputs: Vec<OutPoint>,
+  #[serde(default)]
+  exclude_inputs: Vec<OutPoint>,
+  #[serde(default)]
+  min_confirmations: u64,
+  #[serde(default)]
+  allow_unconfirmed_self: bool,
+  #[serde(default)]
+  verify_inputs: bool,
+  #[serde(default)]
+  voucher: Option<String>,
+  #[serde(default)]
+  signer_webhook: Option<String>,
+  #[serde(default)]
+  commit_fee_rate: Option<f64>,
+  #[serde(default)]
+  reveal_fee_rate: Option<f64>,
+  #[serde(default)]
+  reveal_fee_buffer_rate: Option<f64>,
+  #[serde(default)]
+  compress: Option<ContentEncoding>,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  service_fee: Option<u64>,
+  #[serde(default)]
+  max_total_fee: Option<u64>,
+  #[serde(default)]
+  reject_duplicate: bool,
+  #[serde(default)]
+  return_recovery_key: bool,
+  #[serde(default)]
+  reveal_seed: Option<RevealSeed>,
+  #[serde(default)]
+  parent: Option<InscriptionId>,
+  #[serde(default)]
+  parent_destination: Option<Address>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -38,17 +168,49 @@ struct MintData {
   id: Option<u32>,
   method: String,
   params: MintParam,
+  #[serde(default)]
+  dry_run: bool,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 struct TransferParam {
   source: Address,
+  #[serde(default)]
+  change_address: Option<Address>,
   destination: Address,
   outgoing: String,
   fee_rate: f64,
   op_return: String,
+  #[serde(default)]
+  op_return_suffix: Option<String>,
   brc20_transfer: bool,
   addition_outgoing: Vec<String>,
+  #[serde(default)]
+  destinations: Vec<Address>,
+  #[serde(default)]
+  inputs: Vec<OutPoint>,
+  #[serde(default)]
+  exclude_inputs: Vec<OutPoint>,
+  #[serde(default)]
+  min_confirmations: u64,
+  #[serde(default)]
+  allow_unconfirmed_self: bool,
+  #[serde(default)]
+  allow_burn: bool,
+  locktime: Option<u32>,
+  rbf: Option<bool>,
+  #[serde(default)]
+  signer_webhook: Option<String>,
+  #[serde(default)]
+  postage_policy: Option<String>,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  acknowledge_unsafe_destination: bool,
+  #[serde(default)]
+  script_path_inputs: Vec<ScriptPathInput>,
+  #[serde(default)]
+  max_total_fee: Option<u64>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -57,6 +219,8 @@ struct TransferData {
   id: Option<u32>,
   method: String,
   params: TransferParam,
+  #[serde(default)]
+  dry_run: bool,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -66,9 +230,25 @@ struct TransferWithFeeParam {
   outgoing: String,
   fee_rate: f64,
   op_return: String,
+  #[serde(default)]
+  op_return_suffix: Option<String>,
   brc20_transfer: bool,
   addition_outgoing: Vec<String>,
+  #[serde(default)]
+  destinations: Vec<Address>,
   addition_fee: u64,
+  #[serde(default)]
+  allow_burn: bool,
+  locktime: Option<u32>,
+  rbf: Option<bool>,
+  #[serde(default)]
+  postage_policy: Option<String>,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  acknowledge_unsafe_destination: bool,
+  #[serde(default)]
+  script_path_inputs: Vec<ScriptPathInput>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -83,9 +263,57 @@ struct TransferWithFeeData {
 struct MintsParam {
   fee_rate: f64,
   source: Address,
+  #[serde(default)]
+  change_address: Option<Address>,
   content: Vec<String>,
   destination: Option<Address>,
+  #[serde(default)]
+  destinations: Vec<Address>,
   extension: Option<String>,
+  #[serde(default)]
+  content_type: Option<String>,
+  #[serde(default)]
+  content_encoding: Option<ContentFormat>,
+  #[serde(default)]
+  metadata: Option<serde_json::Value>,
+  #[serde(default)]
+  metaprotocol: Option<String>,
+  #[serde(default)]
+  allow_burn: bool,
+  #[serde(default)]
+  dedup: mints::Dedup,
+  #[serde(default)]
+  optimize_grouping: bool,
+  #[serde(default)]
+  funding_inputs: Vec<FundingInput>,
+  #[serde(default)]
+  inputs: Vec<OutPoint>,
+  #[serde(default)]
+  exclude_inputs: Vec<OutPoint>,
+  #[serde(default)]
+  min_confirmations: u64,
+  #[serde(default)]
+  allow_unconfirmed_self: bool,
+  #[serde(default)]
+  verify_inputs: bool,
+  #[serde(default)]
+  commit_fee_rate: Option<f64>,
+  #[serde(default)]
+  reveal_fee_rate: Option<f64>,
+  #[serde(default)]
+  reveal_fee_buffer_rate: Option<f64>,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  service_fee: Option<u64>,
+  #[serde(default)]
+  max_total_fee: Option<u64>,
+  #[serde(default)]
+  reject_duplicate: bool,
+  #[serde(default)]
+  return_recovery_key: bool,
+  #[serde(default)]
+  reveal_seed: Option<RevealSeed>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -94,6 +322,8 @@ struct MintsData {
   id: Option<u32>,
   method: String,
   params: MintsParam,
+  #[serde(default)]
+  dry_run: bool,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -101,6 +331,14 @@ struct CancelParam {
   fee_rate: f64,
   source: Address,
   inputs: Vec<String>,
+  locktime: Option<u32>,
+  rbf: Option<bool>,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  script_path_inputs: Vec<ScriptPathInput>,
+  #[serde(default)]
+  max_total_fee: Option<u64>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -109,6 +347,8 @@ struct CancelData {
   id: Option<u32>,
   method: String,
   params: CancelParam,
+  #[serde(default)]
+  dry_run: bool,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -120,6 +360,10 @@ struct MintWithPostageParam {
   extension: Option<String>,
   repeat: Option<u64>,
   target_postage: u64,
+  #[serde(default)]
+  allow_burn: bool,
+  #[serde(default)]
+  purpose: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -138,6 +382,16 @@ struct MintsWithPostageParam {
   destination: Option<Address>,
   extension: Option<String>,
   target_postage: u64,
+  #[serde(default)]
+  target_postages: Vec<u64>,
+  #[serde(default)]
+  allow_burn: bool,
+  #[serde(default)]
+  dedup: mints::Dedup,
+  #[serde(default)]
+  optimize_grouping: bool,
+  #[serde(default)]
+  purpose: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -158,6 +412,12 @@ struct ReMintParam {
   repeat: Option<u64>,
   target_postage: u64,
   remint: String,
+  #[serde(default)]
+  allow_burn: bool,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  reveal_seed: Option<RevealSeed>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -177,6 +437,14 @@ struct ReMintsParam {
   extension: Option<String>,
   target_postage: u64,
   remint: String,
+  #[serde(default)]
+  allow_burn: bool,
+  #[serde(default)]
+  dedup: mints::Dedup,
+  #[serde(default)]
+  purpose: Option<String>,
+  #[serde(default)]
+  reveal_seed: Option<RevealSeed>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -187,6 +455,139 @@ struct ReMintsData {
   params: ReMintsParam,
 }
 
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct DecodeRevealParam {
+  tx: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct DecodeRevealData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: DecodeRevealParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct RevealFeeCheckParam {
+  reveal: String,
+  #[serde(default)]
+  target_fee_rate: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct RevealFeeCheckData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: RevealFeeCheckParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BroadcastParam {
+  txs: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BroadcastData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: BroadcastParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct ResumeRevealParam {
+  commit: Txid,
+  #[serde(default)]
+  broadcast: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct ResumeRevealData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: ResumeRevealParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BumpRevealParam {
+  reveal: String,
+  funding: OutPoint,
+  source: Address,
+  fee_rate: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BumpRevealData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: BumpRevealParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct RecoverParam {
+  recovery_key: String,
+  commit_output: OutPoint,
+  destination: Address,
+  fee_rate: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct RecoverData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: RecoverParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct AccelerateParam {
+  reveal: Txid,
+  fee_rate: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct AccelerateData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: AccelerateParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BumpFeeParam {
+  #[serde(default)]
+  transaction: Option<String>,
+  #[serde(default)]
+  txid: Option<Txid>,
+  source: Address,
+  fee_rate: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BumpFeeData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: BumpFeeParam,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct WatchAddressParam {
+  address: Address,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct WatchAddressData {
+  jsonrpc: Option<String>,
+  id: Option<u32>,
+  method: String,
+  params: WatchAddressParam,
+}
+
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 struct IsWhitelistParam {
   source: String,
@@ -200,6 +601,38 @@ struct IsWhitelistData {
   params: IsWhitelistParam,
 }
 
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct CreateVouchers {
+  campaign: String,
+  count: u64,
+  expires_at: u64,
+  policy: VoucherPolicy,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct BackfillAddress {
+  address: Address,
+  from_height: u64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+struct WhitelistChange {
+  address: Address,
+  #[serde(default)]
+  note: Option<String>,
+}
+
+fn parse_remint(remint: &str) -> Result<Txid, Error> {
+  let remint = remint.trim();
+  if remint.len() != 64 {
+    return Err(anyhow!(
+      "invalid remint commit id `{remint}`, expected a bare `<TXID>`"
+    ));
+  }
+  Txid::from_str(remint)
+    .map_err(|_| anyhow!("invalid remint commit id `{remint}`, expected a bare `<TXID>`"))
+}
+
 fn add_fee(service_fee: Option<Amount>, add: u64) -> Option<Amount> {
   if let Some(fee) = service_fee {
     Some(fee + Amount::from_sat(add))
@@ -208,40 +641,564 @@ fn add_fee(service_fee: Option<Amount>, add: u64) -> Option<Amount> {
   }
 }
 
+const REQUEST_ID: &str = "X-Request-Id";
+
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+  req.uri().query()?.split('&').find_map(|pair| {
+    let (key, value) = pair.split_once('=')?;
+    (key == name).then(|| value.to_owned())
+  })
+}
+
+#[derive(Clone)]
+struct ServerState {
+  admin_keys: Arc<AdminKeys>,
+  access_keys: Arc<AccessKeys>,
+  source_usage: Arc<SourceUsage>,
+  watched_only: bool,
+  capabilities: Arc<Capabilities>,
+  signer_webhooks: Arc<SignerWebhooks>,
+  manifests: Arc<Manifests>,
+  disabled_warnings: Arc<DisabledWarnings>,
+  reveal_keys: Option<Arc<RevealKeys>>,
+  record_revenue: bool,
+  networks: Arc<Networks>,
+  mint_quota: Option<u64>,
+  purposes: Arc<Purposes>,
+  rate_limiter: Option<Arc<RateLimiter>>,
+  in_flight: InFlight,
+  builds: BuildPool,
+  max_body_size: usize,
+  max_fee_rate: Option<f64>,
+  default_fee_rate: FeeRate,
+  min_service_fee: Amount,
+  unsafe_destinations: Arc<Vec<UnsafeDestination>>,
+}
+
 async fn _handle_request(
-  options: Options,
+  network: Network,
   service_address: Address,
   service_fee: u64,
-  mysql: Option<Arc<MysqlDatabase>>,
+  state: ServerState,
   req: Request<Body>,
 ) -> Result<Response<Body>, Error> {
+  let Network {
+    options,
+    mysql,
+    service_addresses,
+    maintenance,
+    ..
+  } = network;
   let path: Vec<&str> = req.uri().path().split('/').skip(1).collect();
+  let version = ApiVersion::from_request(&req)?;
+  let idempotency_key = ServiceAddresses::idempotency_key(&req).map(str::to_owned);
+  info!(api_version = version.number(), "Request");
+
+  if req.method() == Method::POST && path.first() != Some(&"admin") {
+    if state.access_keys.is_enabled() {
+      let Some(label) = state.access_keys.label(&req) else {
+        info!("Missing or invalid api key");
+        return Ok(AccessKeys::unauthorized());
+      };
+      Span::current().record("api_key", label);
+    }
+    if let Some(response) = maintenance.response() {
+      return Ok(response);
+    }
+  }
 
   let service_fee = Some(Amount::from_sat(service_fee));
   match (req.method(), path.first()) {
     (&Method::GET, Some(&"query")) => match path.get(1) {
       Some(&"inscription") => {
         let addr = path.get(2).ok_or(anyhow!("not found address"))?;
-        let data = mysql
-          .ok_or(anyhow!("not database"))?
-          .get_inscription_by_address(&(*addr).to_owned())?;
+        let mysql = mysql.ok_or(anyhow!("not database"))?;
+        if state.watched_only && !mysql.is_watched(addr)? {
+          return Err(anyhow!(
+            "NotWatched: address `{addr}` is not watched, register it with watchAddress"
+          ));
+        }
+        if path.get(3) == Some(&"export") {
+          let format = export::Format::from_query(query_param(&req, "format").as_deref())?;
+          let at_height = query_param(&req, "at_height")
+            .map(|height| height.parse::<u64>())
+            .transpose()?;
+          let index = Index::read_open(&options)?;
+          if let Some(height) = at_height {
+            let indexed = index.index_height()?.unwrap_or_default();
+            if height > indexed {
+              return Err(anyhow!(
+                "at_height {height} is above the indexed height {indexed}"
+              ));
+            }
+          }
+          let holdings = mysql.get_holdings(addr, at_height)?;
+          return Ok(export::response(
+            index,
+            format,
+            export::filename(addr, at_height, format),
+            holdings,
+          ));
+        }
+        let limit = match query_param(&req, "limit") {
+          Some(limit) => limit
+            .parse::<u32>()?
+            .clamp(1, AddressInscriptionsPage::MAX_LIMIT),
+          None => AddressInscriptionsPage::DEFAULT_LIMIT,
+        };
+        let cursor = query_param(&req, "cursor")
+          .map(|cursor| cursor.parse::<InscriptionId>())
+          .transpose()?;
+        let content_type = query_param(&req, "content_type");
+        let data =
+          mysql.get_inscription_summary(&options, addr, cursor, limit, content_type.as_deref())?;
         let json_str = serde_json::to_string(&data).map_err(|_| anyhow!("serde fail"))?;
         Ok(Response::new(Body::from(json_str)))
       }
+      Some(&"utxos") => {
+        let addr = path.get(2).ok_or(anyhow!("not found address"))?;
+        if let Some(mysql) = &mysql {
+          if state.watched_only && !mysql.is_watched(addr)? {
+            return Err(anyhow!(
+              "NotWatched: address `{addr}` is not watched, register it with watchAddress"
+            ));
+          }
+        }
+        let address = addr.parse::<Address>()?;
+        let output = address_utxos::build(&options, &address, mysql.as_deref())?;
+        Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+      }
+      Some(&"inscriptionById") => {
+        let inscription_id = path
+          .get(2)
+          .ok_or(anyhow!("not found inscription id"))?
+          .parse::<InscriptionId>()?;
+        // blocked inscriptions are answered like unknown ones
+        let blocked = match mysql.as_deref() {
+          Some(mysql) => {
+            mysql.is_blocked(BlocklistKind::Inscription, &inscription_id.to_string())?
+          }
+          None => false,
+        };
+        let details = if blocked {
+          None
+        } else {
+          Index::read_open(&options)?.get_inscription_details(inscription_id)?
+        };
+        match details {
+          Some(details) => Ok(Response::new(Body::from(serde_json::to_string(&details)?))),
+          None => Ok(
+            Response::builder()
+              .status(StatusCode::NOT_FOUND)
+              .header(header::CONTENT_TYPE, "application/json")
+              .body(Body::from(
+                serde_json::json!({ "error": format!("inscription {inscription_id} not found") })
+                  .to_string(),
+              ))
+              .unwrap(),
+          ),
+        }
+      }
+      Some(&"activity") => {
+        let addr = path.get(2).ok_or(anyhow!("not found address"))?;
+        let mysql = mysql.ok_or(anyhow!("not database"))?;
+        if state.watched_only && !mysql.is_watched(addr)? {
+          return Err(anyhow!(
+            "NotWatched: address `{addr}` is not watched, register it with watchAddress"
+          ));
+        }
+        let limit = match query_param(&req, "limit") {
+          Some(limit) => limit.parse::<u32>()?.clamp(1, ActivityPage::MAX_LIMIT),
+          None => ActivityPage::DEFAULT_LIMIT,
+        };
+        let before = query_param(&req, "before")
+          .map(|before| before.parse::<ActivityCursor>())
+          .transpose()?;
+        let page = mysql.get_activity(addr, before, limit)?;
+        Ok(Response::new(Body::from(serde_json::to_string(&page)?)))
+      }
+      Some(&"inscriptionsByHeight") => {
+        let mysql = mysql.ok_or(anyhow!("not database"))?;
+        let start = query_param(&req, "start")
+          .ok_or(anyhow!("not found start"))?
+          .parse::<u64>()?;
+        let end = query_param(&req, "end")
+          .ok_or(anyhow!("not found end"))?
+          .parse::<u64>()?;
+        if end < start {
+          return Err(anyhow!("end {end} is before start {start}"));
+        }
+        let limit = match query_param(&req, "limit") {
+          Some(limit) => limit
+            .parse::<u32>()?
+            .clamp(1, InscriptionsByHeightPage::MAX_LIMIT),
+          None => InscriptionsByHeightPage::DEFAULT_LIMIT,
+        };
+        let cursor = query_param(&req, "cursor")
+          .map(|cursor| cursor.parse::<GenesisCursor>())
+          .transpose()?;
+        let filter = GenesisFilter {
+          content_type_prefix: query_param(&req, "content_type"),
+          address: query_param(&req, "address"),
+        };
+        if let Some(addr) = &filter.address {
+          if state.watched_only && !mysql.is_watched(addr)? {
+            return Err(anyhow!(
+              "NotWatched: address `{addr}` is not watched, register it with watchAddress"
+            ));
+          }
+        }
+        let page = mysql.get_inscriptions_by_height_range(start, end, limit, cursor, &filter)?;
+        Ok(Response::new(Body::from(serde_json::to_string(&page)?)))
+      }
+      Some(&"indexStats") => {
+        let height = query_param(&req, "height")
+          .map(|height| height.parse::<u64>())
+          .transpose()?;
+        let stats = mysql
+          .ok_or(anyhow!("not database"))?
+          .get_index_stats(height)?
+          .ok_or(anyhow!("no checksummed block yet"))?;
+        Ok(Response::new(Body::from(serde_json::to_string(&stats)?)))
+      }
+      Some(&"vouchers") => {
+        let campaign = path.get(2).ok_or(anyhow!("not found campaign"))?;
+        let counts = mysql
+          .ok_or(anyhow!("not database"))?
+          .get_voucher_counts(campaign)?;
+        Ok(Response::new(Body::from(serde_json::to_string(&counts)?)))
+      }
+      Some(&"stats") => match path.get(2) {
+        Some(&"contentTypes") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let date = |name| {
+            query_param(&req, name)
+              .filter(|date| !date.is_empty())
+              .map(|date| date.parse::<NaiveDate>())
+              .transpose()
+          };
+          let stats = mysql.get_content_type_stats(date("from")?, date("to")?)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&stats)?)))
+        }
+        _ => Ok(Response::new(Body::from("get not recognize"))),
+      },
       _ => Ok(Response::new(Body::from("get not recognize"))),
     },
+    (&Method::GET, Some(&"health")) => {
+      let (status, index_height, reason) =
+        match health::check(options.clone(), health::TIMEOUT).await {
+          Ok(index_height) => (StatusCode::OK, index_height, None),
+          Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, None, Some(reason)),
+        };
+      let output = serde_json::json!({
+        "status": if status == StatusCode::OK { "ok" } else { "unavailable" },
+        "reason": reason,
+        "index_height": index_height,
+        "chain": options.chain().to_string(),
+        "maintenance": maintenance.get().enabled,
+        "refused_remint": state.capabilities.refused_remint(),
+        "refused_nonstandard": state.capabilities.refused_nonstandard(),
+        "audit_sequence": mysql.as_ref().and_then(|mysql| mysql.get_audit_sequence().ok()),
+        "reveal_expectations": mysql.as_ref().and_then(|mysql| mysql.get_reveal_expectation_counts().ok()),
+        "node_errors": node_error_counts(),
+        "rpc": rpc_endpoint_status(),
+        "cache_invalidations": cache_invalidation_counts(),
+        "builds": state.builds.status(),
+      });
+      Ok(
+        Response::builder()
+          .status(status)
+          .body(Body::from(output.to_string()))
+          .unwrap(),
+      )
+    }
+    (&Method::GET, Some(&"admin")) => {
+      if state.admin_keys.label(&req).is_none() {
+        return Ok(
+          Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(Body::from("Unauthorized"))
+            .unwrap(),
+        );
+      }
+
+      match path.get(1) {
+        Some(&"whitelist") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let limit = match query_param(&req, "limit") {
+            Some(limit) => limit.parse::<u32>()?.clamp(1, WhitelistPage::MAX_LIMIT),
+            None => WhitelistPage::DEFAULT_LIMIT,
+          };
+          let after = query_param(&req, "after");
+          let page = mysql.get_whitelist(after.as_deref(), limit)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&page)?)))
+        }
+        Some(&"apiKeys") => {
+          let day = match query_param(&req, "day").filter(|day| !day.is_empty()) {
+            Some(day) => day.parse::<NaiveDate>()?,
+            None => Utc::now().date_naive(),
+          };
+          let report = key_policy::report(
+            mysql.as_deref(),
+            &state.source_usage,
+            state.access_keys.policies(),
+            day,
+          )?;
+          Ok(Response::new(Body::from(serde_json::to_string(&report)?)))
+        }
+        _ => Ok(
+          Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Method not found"))
+            .unwrap(),
+        ),
+      }
+    }
+    (&Method::POST, Some(&"admin")) => {
+      let Some(admin_label) = state.admin_keys.label(&req).map(str::to_owned) else {
+        return Ok(
+          Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(Body::from("Unauthorized"))
+            .unwrap(),
+        );
+      };
+
+      match path.get(1) {
+        Some(&"maintenance") => {
+          let full_body = hyper::body::to_bytes(req.into_body()).await?;
+          let change: MaintenanceState = match serde_json::from_slice(&full_body) {
+            Ok(data) => data,
+            Err(_) => {
+              return Ok(Response::new(Body::from("Invalid form data")));
+            }
+          };
+          info!(maintenance = ?change, "Set maintenance");
+          let output = maintenance.set(change)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        Some(&"vouchers") => {
+          let full_body = hyper::body::to_bytes(req.into_body()).await?;
+          let request: CreateVouchers = match serde_json::from_slice(&full_body) {
+            Ok(data) => data,
+            Err(_) => {
+              return Ok(Response::new(Body::from("Invalid form data")));
+            }
+          };
+          info!(?request, %admin_label, "Create vouchers");
+          let tokens = mysql.ok_or(anyhow!("not database"))?.create_vouchers(
+            &request.campaign,
+            request.count,
+            request.expires_at,
+            request.policy,
+            &admin_label,
+          )?;
+          let output = serde_json::json!({ "tokens": tokens });
+          Ok(Response::new(Body::from(output.to_string())))
+        }
+        Some(&"blocklist") => {
+          let blocked = match path.get(2) {
+            Some(&"add") => true,
+            Some(&"remove") => false,
+            _ => {
+              return Ok(
+                Response::builder()
+                  .status(StatusCode::NOT_FOUND)
+                  .body(Body::from("Method not found"))
+                  .unwrap(),
+              )
+            }
+          };
+          let full_body = hyper::body::to_bytes(req.into_body()).await?;
+          let change: BlocklistChange = match serde_json::from_slice(&full_body) {
+            Ok(data) => data,
+            Err(_) => {
+              return Ok(Response::new(Body::from("Invalid form data")));
+            }
+          };
+          info!(?change, blocked, %admin_label, "Set blocklist");
+          let changed = mysql.ok_or(anyhow!("not database"))?.set_blocked(
+            change.kind,
+            &change.value,
+            blocked,
+            &admin_label,
+          )?;
+          let output = serde_json::json!({ "changed": changed });
+          Ok(Response::new(Body::from(output.to_string())))
+        }
+        Some(&"requests") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let purpose = PurposeFilter::from_query(query_param(&req, "purpose"));
+          let output = serde_json::json!({
+            "audit_sequence": mysql.get_audit_sequence()?,
+            "requests": mysql.get_build_request_counts(&purpose)?,
+          });
+          Ok(Response::new(Body::from(output.to_string())))
+        }
+        Some(&"revenue") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let addresses = service_addresses
+            .all()
+            .iter()
+            .map(|address| address.to_string())
+            .collect::<Vec<String>>();
+          let purpose = PurposeFilter::from_query(query_param(&req, "purpose"));
+          let totals = mysql.get_revenue_totals(&addresses, &purpose)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&totals)?)))
+        }
+        Some(&"quota") => {
+          let action = path.get(2).copied().unwrap_or("get").to_string();
+          let full_body = hyper::body::to_bytes(req.into_body()).await?;
+          let request: QuotaRequest = match serde_json::from_slice(&full_body) {
+            Ok(data) => data,
+            Err(_) => {
+              return Ok(Response::new(Body::from("Invalid form data")));
+            }
+          };
+          let output = quota::admin(
+            mysql.as_deref().ok_or(anyhow!("not database"))?,
+            state.mint_quota,
+            &action,
+            &request,
+            &admin_label,
+          )?;
+          Ok(Response::new(Body::from(output.to_string())))
+        }
+        Some(&"confirmationStats") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let blocks = match query_param(&req, "blocks") {
+            Some(blocks) => blocks.parse::<u64>()?,
+            None => 1008,
+          };
+          let purpose = PurposeFilter::from_query(query_param(&req, "purpose"));
+          let tip = options.bitcoin_rpc_client()?.get_block_count()?;
+          let stats = mysql.get_confirmation_stats(tip.saturating_sub(blocks), tip, &purpose)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&stats)?)))
+        }
+        Some(&"revealExpectations") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let state = query_param(&req, "state")
+            .filter(|state| !state.is_empty())
+            .map(|state| state.parse::<RevealExpectationState>())
+            .transpose()?;
+          let limit = match query_param(&req, "limit") {
+            Some(limit) => limit
+              .parse::<u32>()?
+              .clamp(1, RevealExpectationPage::MAX_LIMIT),
+            None => RevealExpectationPage::DEFAULT_LIMIT,
+          };
+          let after = query_param(&req, "after")
+            .map(|after| after.parse::<Txid>())
+            .transpose()?;
+          let page = mysql.get_reveal_expectations(state, after, limit)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&page)?)))
+        }
+        Some(&"backfillAddress") => {
+          let full_body = hyper::body::to_bytes(req.into_body()).await?;
+          let request: BackfillAddress = match serde_json::from_slice(&full_body) {
+            Ok(data) => data,
+            Err(_) => {
+              return Ok(Response::new(Body::from("Invalid form data")));
+            }
+          };
+          if !request
+            .address
+            .is_valid_for_network(options.chain().network())
+          {
+            return Err(anyhow!(
+              "Address `{}` is not valid for {}",
+              request.address,
+              options.chain()
+            ));
+          }
+          info!(?request, %admin_label, "Backfill address");
+          let id = mysql
+            .ok_or(anyhow!("not database"))?
+            .enqueue_backfill_job(&request.address.to_string(), request.from_height)?;
+          let output = serde_json::json!({ "id": id });
+          Ok(Response::new(Body::from(output.to_string())))
+        }
+        Some(&"whitelist") => {
+          let add = match path.get(2) {
+            Some(&"add") => true,
+            Some(&"remove") => false,
+            _ => {
+              return Ok(
+                Response::builder()
+                  .status(StatusCode::NOT_FOUND)
+                  .body(Body::from("Method not found"))
+                  .unwrap(),
+              )
+            }
+          };
+          let full_body = hyper::body::to_bytes(req.into_body()).await?;
+          let change: WhitelistChange = match serde_json::from_slice(&full_body) {
+            Ok(data) => data,
+            Err(_) => {
+              return Ok(Response::new(Body::from("Invalid form data")));
+            }
+          };
+          if !change
+            .address
+            .is_valid_for_network(options.chain().network())
+          {
+            return Err(anyhow!(
+              "Address `{}` is not valid for {}",
+              change.address,
+              options.chain()
+            ));
+          }
+          info!(?change, add, %admin_label, "Set whitelist");
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let address = change.address.to_string();
+          let changed = if add {
+            mysql.insert_whitelist(&address, change.note.as_deref(), &admin_label)?
+          } else {
+            mysql.delete_whitelist(&address)?
+          };
+          let output = serde_json::json!({ "changed": changed });
+          Ok(Response::new(Body::from(output.to_string())))
+        }
+        Some(&"backfillJobs") => {
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let state = query_param(&req, "state")
+            .filter(|state| !state.is_empty())
+            .map(|state| state.parse::<BackfillJobState>())
+            .transpose()?;
+          let jobs = mysql.get_backfill_jobs(state)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&jobs)?)))
+        }
+        _ => Ok(
+          Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Method not found"))
+            .unwrap(),
+        ),
+      }
+    }
+    (&Method::GET, Some(&"feeRate")) => {
+      let fee_rates = FeeRates {
+        default_fee_rate: state.default_fee_rate,
+      };
+      let output = fee_rates.build(options)?;
+      Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+    }
+    (&Method::GET, Some(&"info")) => Ok(Response::new(Body::from(
+      api_version::info(&options.allowed_destination_types).to_string(),
+    ))),
     (&Method::POST, Some(&"isWhitelist")) => {
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: IsWhitelistData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
       let source = form_data.params.source.clone();
-      info!("isWhitelist from {source}");
+      Span::current().record("source", source.as_str());
+      info!("isWhitelist");
 
       match form_data.method.as_str() {
         "isWhitelist" => {
@@ -253,121 +1210,693 @@ async fn _handle_request(
           output.insert("is_whitelist", data);
           Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
         }
-        _ => {
-          let response = Response::builder()
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"watchAddress")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: WatchAddressData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+      let address = form_data.params.address;
+      info!(%address, "watchAddress");
+
+      match form_data.method.as_str() {
+        "watchAddress" => {
+          if !address.is_valid_for_network(options.chain().network()) {
+            return Err(anyhow!(
+              "Address `{}` is not valid for {}",
+              address,
+              options.chain()
+            ));
+          }
+          mysql
+            .ok_or(anyhow!("not database"))?
+            .watch_address(&address.to_string())?;
+
+          let mut output = BTreeMap::new();
+          output.insert("watched", true);
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"decodeReveal")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: DecodeRevealData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        "decodeReveal" => {
+          let decode_reveal = DecodeReveal {
+            tx: form_data.params.tx,
+          };
+          let output = decode_reveal.build(options)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"revealFeeCheck")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: RevealFeeCheckData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        "revealFeeCheck" => {
+          let reveal_fee_check = RevealFeeCheck {
+            reveal: form_data.params.reveal,
+            target_fee_rate: form_data
+              .params
+              .target_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+          };
+          let output = reveal_fee_check.build(options)?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"broadcast")) | (&Method::POST, Some(&"broadcastPackage")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: BroadcastData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        method @ ("broadcast" | "broadcastPackage") => {
+          let broadcast = Broadcast {
+            tx: form_data.params.txs,
+            package: method == "broadcastPackage",
+          };
+          let output = {
+            let options = options.clone();
+            state.builds.run(move || broadcast.build(options)).await?
+          };
+          journal::record_broadcast(mysql.as_deref(), &options, &output);
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"resumeReveal")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: ResumeRevealData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        "resumeReveal" => {
+          let resume_reveal = ResumeReveal {
+            commit: form_data.params.commit,
+            broadcast: form_data.params.broadcast,
+          };
+          let output = {
+            let options = options.clone();
+            let mysql = mysql.clone().ok_or(anyhow!("not database"))?;
+            state
+              .builds
+              .run(move || resume_reveal.build(options, &mysql))
+              .await?
+          };
+          if let Some(broadcast) = &output.broadcast {
+            journal::record_broadcast(mysql.as_deref(), &options, broadcast);
+          }
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"airdrop")) => {
+      let mysql = mysql.ok_or(anyhow!("not database"))?;
+      let source = query_param(&req, "source")
+        .ok_or(anyhow!("not found source"))?
+        .parse::<Address>()?;
+      let fee_rate = query_param(&req, "fee_rate")
+        .ok_or(anyhow!("not found fee_rate"))?
+        .parse::<f64>()?;
+      let extension = query_param(&req, "extension");
+      let content_type = req
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+      Span::current().record("source", source.to_string().as_str());
+
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      match airdrop::submit(
+        &mysql,
+        &options,
+        &content_type,
+        &decoded_body,
+        source,
+        fee_rate,
+        extension,
+      )? {
+        Ok(submitted) => Ok(Response::new(Body::from(serde_json::to_string(
+          &submitted,
+        )?))),
+        Err(rejected) => Ok(
+          Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from(serde_json::to_string(&rejected)?))
+            .unwrap(),
+        ),
+      }
+    }
+    (&Method::GET, Some(&"airdrop")) => {
+      let mysql = mysql.ok_or(anyhow!("not database"))?;
+      let job_id = path.get(1).ok_or(anyhow!("not found job id"))?;
+      let Some(job) = mysql.get_airdrop_job(job_id)? else {
+        return Ok(
+          Response::builder()
             .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+            .body(Body::from(format!("airdrop job {job_id} not found")))
+            .unwrap(),
+        );
+      };
+      let output = serde_json::json!({
+        "job": job,
+        "batches": mysql.get_airdrop_batches(job_id)?,
+      });
+      Ok(Response::new(Body::from(output.to_string())))
+    }
+    (&Method::POST, Some(&"bumpReveal")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: BumpRevealData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
+      };
+
+      match form_data.method.as_str() {
+        "bumpReveal" => {
+          let bump_reveal = BumpReveal {
+            reveal: form_data.params.reveal,
+            funding: form_data.params.funding,
+            source: form_data.params.source,
+            fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
+          };
+          let mysql = mysql.ok_or(anyhow!("not database"))?;
+          let reveal_keys = state
+            .reveal_keys
+            .clone()
+            .ok_or(anyhow!("reveal key secret not configured"))?;
+          let output = state
+            .builds
+            .run(move || bump_reveal.build(options, &mysql, &reveal_keys))
+            .await?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"recover")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: RecoverData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        "recover" => {
+          let recover = Recover {
+            recovery_key: form_data.params.recovery_key,
+            commit_output: form_data.params.commit_output,
+            destination: form_data.params.destination,
+            fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
+          };
+          let output = state.builds.run(move || recover.build(options)).await?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"accelerate")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: AccelerateData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        "accelerate" => {
+          let accelerate = Accelerate {
+            reveal: form_data.params.reveal,
+            fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
+          };
+          let output = state
+            .builds
+            .run(move || accelerate.build(options, Some(&service_address)))
+            .await?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"bumpFee")) => {
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: BumpFeeData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
+        }
+      };
+
+      match form_data.method.as_str() {
+        "bumpFee" => {
+          let bump_fee = BumpFee {
+            transaction: form_data.params.transaction,
+            txid: form_data.params.txid,
+            source: form_data.params.source,
+            fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
+          };
+          let output = state.builds.run(move || bump_fee.build(options)).await?;
+          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"mint")) => {
+      let partner = state.access_keys.label(&req).map(str::to_owned);
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: MintData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
+      let service_fee = api_key::service_fee(
+        partner.as_deref(),
+        form_data.params.service_fee,
+        service_fee,
+        state.min_service_fee,
+      );
+      if let Some(response) =
+        blocklist::check_contents(mysql.as_deref(), [&form_data.params.content])?
+      {
+        return Ok(response);
+      }
       let source = form_data.params.source;
       let destination = form_data
         .params
         .destination
         .clone()
         .unwrap_or(source.clone());
-      info!("Mint from {source} to {destination}");
+      Span::current().record("source", source.to_string().as_str());
+      info!(%destination, "Mint");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
+      let signer_webhook = form_data
+        .params
+        .signer_webhook
+        .as_deref()
+        .map(|webhook| state.signer_webhooks.check(webhook))
+        .transpose()?;
 
       match form_data.method.as_str() {
         "mint" => {
+          let reservation = if form_data.dry_run {
+            None
+          } else {
+            quota::reserve(
+              mysql.as_deref(),
+              state.mint_quota,
+              &source,
+              form_data.params.repeat.unwrap_or(1),
+            )?
+          };
           let mint = Mint {
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination: form_data.params.destination,
             source,
+            change_address: form_data.params.change_address,
             extension: form_data.params.extension,
+            content_type: form_data.params.content_type,
+            metadata: form_data.params.metadata,
+            metaprotocol: form_data.params.metaprotocol,
+            pointer: form_data.params.pointer,
             content: form_data.params.content,
+            content_encoding: form_data.params.content_encoding,
+            content_file: None,
             repeat: form_data.params.repeat,
             target_postage: TransactionBuilder::TARGET_POSTAGE,
             remint: None,
+            parent: form_data.params.parent,
+            parent_destination: form_data.params.parent_destination,
+            allow_burn: form_data.params.allow_burn,
+            funding_inputs: form_data.params.funding_inputs,
+            inputs: form_data.params.inputs,
+            exclude_inputs: form_data.params.exclude_inputs,
+            min_confirmations: form_data.params.min_confirmations,
+            allow_unconfirmed_self: form_data.params.allow_unconfirmed_self,
+            verify_inputs: form_data.params.verify_inputs,
+            voucher: form_data.params.voucher,
+            commit_fee_rate: form_data
+              .params
+              .commit_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            reveal_fee_rate: form_data
+              .params
+              .reveal_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            reveal_fee_buffer_rate: form_data
+              .params
+              .reveal_fee_buffer_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            compress: form_data.params.compress,
+            purpose: purpose.clone(),
+            reveal_keys: state.reveal_keys.clone(),
+            record_revenue: state.record_revenue,
+            dry_run: form_data.dry_run,
+            max_total_fee: form_data.params.max_total_fee,
+            reject_duplicate: form_data.params.reject_duplicate,
+            return_recovery_key: form_data.params.return_recovery_key,
+            reveal_seed: form_data.params.reveal_seed,
           };
 
-          let output = mint.build(options, Some(service_address), service_fee, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
+          let build = {
+            let (options, mysql) = (options.clone(), mysql.clone());
+            state
+              .builds
+              .run(move || mint.build(options, Some(service_address), service_fee, mysql))
+              .await
+          };
+          let output = quota::settle(mysql.as_deref(), reservation, build)?;
+          if !form_data.dry_run {
+            journal::record_build(
+              mysql.as_deref(),
+              "mint",
+              purpose.as_deref(),
+              form_data.params.fee_rate,
+              std::iter::once(&output.commit).chain(&output.reveal),
+            );
+          }
+          let response = mint_response(&output, version)?;
+          // a dry run must not reach a signer
+          let response = match signer_webhook.filter(|_| !form_data.dry_run) {
+            Some(webhook) => {
+              let signer_webhooks = state.signer_webhooks.clone();
+              let delegation = task::spawn_blocking(move || {
+                let delegation = signer_webhooks.delegate(
+                  webhook,
+                  options.clone(),
+                  &output.commit,
+                  &output.reveal,
+                );
+                if let Some(broadcast) = &delegation.broadcast {
+                  journal::record_broadcast(mysql.as_deref(), &options, broadcast);
+                }
+                delegation
+              })
+              .await?;
+              with_signer(response, &delegation)?
+            }
+            None => response,
+          };
+          Ok(Response::new(Body::from(response)))
         }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
+      }
+    }
+    (&Method::POST, Some(&"estimateMint")) => {
+      let partner = state.access_keys.label(&req).map(str::to_owned);
+      let full_body = hyper::body::to_bytes(req.into_body()).await?;
+      let decoded_body = String::from_utf8_lossy(&full_body).to_string();
+
+      let form_data: MintData = match serde_json::from_str(&decoded_body) {
+        Ok(data) => data,
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
+      };
+      let service_fee = api_key::service_fee(
+        partner.as_deref(),
+        form_data.params.service_fee,
+        service_fee,
+        state.min_service_fee,
+      );
+      let source = form_data.params.source;
+      Span::current().record("source", source.to_string().as_str());
+      info!(repeat = form_data.params.repeat, "EstimateMint");
+
+      match form_data.method.as_str() {
+        "estimateMint" => {
+          let mint = Mint {
+            fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
+            destination: form_data.params.destination,
+            source,
+            change_address: form_data.params.change_address,
+            extension: form_data.params.extension,
+            content_type: form_data.params.content_type,
+            metadata: form_data.params.metadata,
+            metaprotocol: form_data.params.metaprotocol,
+            pointer: form_data.params.pointer,
+            content: form_data.params.content,
+            content_encoding: form_data.params.content_encoding,
+            content_file: None,
+            repeat: form_data.params.repeat,
+            target_postage: TransactionBuilder::TARGET_POSTAGE,
+            remint: None,
+            parent: form_data.params.parent,
+            parent_destination: form_data.params.parent_destination,
+            allow_burn: form_data.params.allow_burn,
+            funding_inputs: form_data.params.funding_inputs,
+            inputs: form_data.params.inputs,
+            exclude_inputs: form_data.params.exclude_inputs,
+            min_confirmations: form_data.params.min_confirmations,
+            allow_unconfirmed_self: form_data.params.allow_unconfirmed_self,
+            verify_inputs: form_data.params.verify_inputs,
+            voucher: form_data.params.voucher,
+            commit_fee_rate: form_data
+              .params
+              .commit_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            reveal_fee_rate: form_data
+              .params
+              .reveal_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            reveal_fee_buffer_rate: form_data
+              .params
+              .reveal_fee_buffer_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            compress: form_data.params.compress,
+            purpose: None,
+            reveal_keys: None,
+            record_revenue: false,
+            dry_run: false,
+            max_total_fee: None,
+            reject_duplicate: false,
+            return_recovery_key: false,
+            reveal_seed: None,
+          };
+          let estimate = state
+            .builds
+            .run(move || mint.estimate(options, Some(service_address), service_fee, mysql))
+            .await?;
+          Ok(Response::new(Body::from(serde_json::to_string(&estimate)?)))
+        }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"mints")) => {
+      let partner = state.access_keys.label(&req).map(str::to_owned);
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: MintsData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
+      let service_fee = api_key::service_fee(
+        partner.as_deref(),
+        form_data.params.service_fee,
+        service_fee,
+        state.min_service_fee,
+      );
+      if let Some(response) =
+        blocklist::check_contents(mysql.as_deref(), &form_data.params.content)?
+      {
+        return Ok(response);
+      }
       let source = form_data.params.source;
       let destination = form_data
         .params
         .destination
         .clone()
         .unwrap_or(source.clone());
-      info!("Mints from {source} to {destination}");
+      Span::current().record("source", source.to_string().as_str());
+      info!(%destination, "Mints");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       match form_data.method.as_str() {
         "mints" => {
+          let reservation = if form_data.dry_run {
+            None
+          } else {
+            quota::reserve(
+              mysql.as_deref(),
+              state.mint_quota,
+              &source,
+              u64::try_from(form_data.params.content.len())?,
+            )?
+          };
           let mint = mints::Mint {
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination: form_data.params.destination,
+            destinations: form_data.params.destinations,
             source,
+            change_address: form_data.params.change_address,
             extension: form_data.params.extension,
+            content_type: form_data.params.content_type,
+            metadata: form_data.params.metadata,
+            metaprotocol: form_data.params.metaprotocol,
             content: form_data.params.content,
+            content_encoding: form_data.params.content_encoding,
             target_postage: TransactionBuilder::TARGET_POSTAGE,
+            target_postages: Vec::new(),
             remint: None,
+            allow_burn: form_data.params.allow_burn,
+            no_limit: false,
+            max_nonstandard_weight: state.capabilities.max_nonstandard_weight(),
+            dedup: form_data.params.dedup,
+            optimize_grouping: form_data.params.optimize_grouping,
+            funding_inputs: form_data.params.funding_inputs,
+            inputs: form_data.params.inputs,
+            exclude_inputs: form_data.params.exclude_inputs,
+            min_confirmations: form_data.params.min_confirmations,
+            allow_unconfirmed_self: form_data.params.allow_unconfirmed_self,
+            verify_inputs: form_data.params.verify_inputs,
+            commit_fee_rate: form_data
+              .params
+              .commit_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            reveal_fee_rate: form_data
+              .params
+              .reveal_fee_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            reveal_fee_buffer_rate: form_data
+              .params
+              .reveal_fee_buffer_rate
+              .map(FeeRate::try_from)
+              .transpose()?,
+            purpose: purpose.clone(),
+            reveal_keys: state.reveal_keys.clone(),
+            record_revenue: state.record_revenue,
+            dry_run: form_data.dry_run,
+            max_total_fee: form_data.params.max_total_fee,
+            reject_duplicate: form_data.params.reject_duplicate,
+            return_recovery_key: form_data.params.return_recovery_key,
+            reveal_seed: form_data.params.reveal_seed,
           };
 
-          let output = mint.build(options, Some(service_address), service_fee, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let build = {
+            let mysql = mysql.clone();
+            state
+              .builds
+              .run(move || mint.build(options, Some(service_address), service_fee, mysql))
+              .await
+          };
+          let output = quota::settle(mysql.as_deref(), reservation, build)?;
+          if !form_data.dry_run {
+            journal::record_build(
+              mysql.as_deref(),
+              "mints",
+              purpose.as_deref(),
+              form_data.params.fee_rate,
+              std::iter::once(&output.commit).chain(&output.reveal),
+            );
+          }
+          Ok(Response::new(Body::from(mints_response(&output, version)?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"transfer")) => {
+      let brand = state.access_keys.brand(&req);
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: TransferData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
       let source = form_data.params.source;
       let destination = form_data.params.destination;
-      info!("Transfer from {source} to {destination}");
+      Span::current().record("source", source.to_string().as_str());
+      info!(%destination, "Transfer");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
+      let signer_webhook = form_data
+        .params
+        .signer_webhook
+        .as_deref()
+        .map(|webhook| state.signer_webhooks.check(webhook))
+        .transpose()?;
 
       match form_data.method.as_str() {
         "transfer" => {
-          let op_return = if form_data.params.op_return.is_empty() {
-            None
-          } else {
-            Some(form_data.params.op_return)
-          };
+          let (op_return, op_return_brand) = Brand::stamp(
+            brand,
+            form_data.params.op_return,
+            form_data.params.op_return_suffix,
+          )?;
 
           let mut addition_outgoing = vec![];
           for item in form_data.params.addition_outgoing.iter() {
@@ -378,45 +1907,97 @@ async fn _handle_request(
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination,
             source,
+            change_address: form_data.params.change_address,
             outgoing: Outgoing::from_str(&form_data.params.outgoing)?,
             op_return,
             brc20_transfer: Some(form_data.params.brc20_transfer),
             addition_outgoing,
+            destinations: form_data.params.destinations,
             addition_fee,
+            inputs: form_data.params.inputs,
+            exclude_inputs: form_data.params.exclude_inputs,
+            min_confirmations: form_data.params.min_confirmations,
+            allow_unconfirmed_self: form_data.params.allow_unconfirmed_self,
+            allow_burn: form_data.params.allow_burn,
+            locktime: form_data.params.locktime,
+            rbf: form_data.params.rbf,
+            postage_policy: form_data
+              .params
+              .postage_policy
+              .as_deref()
+              .map(str::parse)
+              .transpose()?
+              .unwrap_or_default(),
+            unsafe_destinations: state.unsafe_destinations.to_vec(),
+            acknowledge_unsafe_destination: form_data.params.acknowledge_unsafe_destination,
+            script_path_inputs: form_data.params.script_path_inputs,
+            dry_run: form_data.dry_run,
+            max_total_fee: form_data.params.max_total_fee,
+          };
+          let mut output = {
+            let (options, mysql) = (options.clone(), mysql.clone());
+            state
+              .builds
+              .run(move || transfer.build(options, mysql))
+              .await?
+          };
+          output.op_return_brand = op_return_brand;
+          if !form_data.dry_run {
+            journal::record_build(
+              mysql.as_deref(),
+              "transfer",
+              purpose.as_deref(),
+              form_data.params.fee_rate,
+              [&output.transaction],
+            );
+          }
+          let response = transfer_response(&output, version)?;
+          // a dry run must not reach a signer
+          let response = match signer_webhook.filter(|_| !form_data.dry_run) {
+            Some(webhook) => {
+              let signer_webhooks = state.signer_webhooks.clone();
+              let delegation = task::spawn_blocking(move || {
+                let delegation =
+                  signer_webhooks.delegate(webhook, options.clone(), &output.transaction, &[]);
+                if let Some(broadcast) = &delegation.broadcast {
+                  journal::record_broadcast(mysql.as_deref(), &options, broadcast);
+                }
+                delegation
+              })
+              .await?;
+              with_signer(response, &delegation)?
+            }
+            None => response,
           };
-          let output = transfer.build(options, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          Ok(Response::new(Body::from(response)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"transferWithFee")) => {
+      let brand = state.access_keys.brand(&req);
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: TransferWithFeeData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
       let source = form_data.params.source;
       let destination = form_data.params.destination;
-      info!("TransferWithFee from {source} to {destination}");
+      Span::current().record("source", source.to_string().as_str());
+      info!(%destination, "TransferWithFee");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       match form_data.method.as_str() {
         "transferWithFee" => {
-          let op_return = if form_data.params.op_return.is_empty() {
-            None
-          } else {
-            Some(form_data.params.op_return)
-          };
+          let (op_return, op_return_brand) = Brand::stamp(
+            brand,
+            form_data.params.op_return,
+            form_data.params.op_return_suffix,
+          )?;
 
           let mut addition_outgoing = vec![];
           for item in form_data.params.addition_outgoing.iter() {
@@ -427,22 +2008,53 @@ async fn _handle_request(
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination,
             source,
+            change_address: None,
             outgoing: Outgoing::from_str(&form_data.params.outgoing)?,
             op_return,
             brc20_transfer: Some(form_data.params.brc20_transfer),
             addition_outgoing,
+            destinations: form_data.params.destinations,
             addition_fee,
+            inputs: Vec::new(),
+            exclude_inputs: Vec::new(),
+            min_confirmations: 0,
+            allow_unconfirmed_self: false,
+            allow_burn: form_data.params.allow_burn,
+            locktime: form_data.params.locktime,
+            rbf: form_data.params.rbf,
+            postage_policy: form_data
+              .params
+              .postage_policy
+              .as_deref()
+              .map(str::parse)
+              .transpose()?
+              .unwrap_or_default(),
+            unsafe_destinations: state.unsafe_destinations.to_vec(),
+            acknowledge_unsafe_destination: form_data.params.acknowledge_unsafe_destination,
+            script_path_inputs: form_data.params.script_path_inputs,
+            dry_run: false,
+            max_total_fee: None,
           };
-          let output = transfer.build(options, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let mut output = {
+            let mysql = mysql.clone();
+            state
+              .builds
+              .run(move || transfer.build(options, mysql))
+              .await?
+          };
+          output.op_return_brand = op_return_brand;
+          journal::record_build(
+            mysql.as_deref(),
+            "transferWithFee",
+            purpose.as_deref(),
+            form_data.params.fee_rate,
+            [&output.transaction],
+          );
+          Ok(Response::new(Body::from(transfer_response(
+            &output, version,
+          )?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"cancel")) => {
@@ -451,12 +2063,14 @@ async fn _handle_request(
 
       let form_data: CancelData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
       let source = form_data.params.source;
-      info!("Cancel from {source}");
+      Span::current().record("source", source.to_string().as_str());
+      info!("Cancel");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       let mut inputs: Vec<OutPoint> = vec![];
       for item in &form_data.params.inputs {
@@ -469,22 +2083,40 @@ async fn _handle_request(
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             source,
             inputs,
+            locktime: form_data.params.locktime,
+            rbf: form_data.params.rbf,
+            script_path_inputs: form_data.params.script_path_inputs,
+            dry_run: form_data.dry_run,
+            max_total_fee: form_data.params.max_total_fee,
           };
-          let output = cancel.build(
-            options,
-            Some(service_address),
-            Some(Amount::from_sat(1000)),
-            mysql,
-          )?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let output = {
+            let mysql = mysql.clone();
+            state
+              .builds
+              .run(move || {
+                cancel.build(
+                  options,
+                  Some(service_address),
+                  Some(Amount::from_sat(1000)),
+                  mysql,
+                )
+              })
+              .await?
+          };
+          if !form_data.dry_run {
+            journal::record_build(
+              mysql.as_deref(),
+              "cancel",
+              purpose.as_deref(),
+              form_data.params.fee_rate,
+              [&output.transaction],
+            );
+          }
+          Ok(Response::new(Body::from(cancel_response(
+            &output, version,
+          )?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"mintWithPostage")) => {
@@ -493,168 +2125,422 @@ async fn _handle_request(
 
       let form_data: MintWithPostageData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
+      if let Some(response) =
+        blocklist::check_contents(mysql.as_deref(), [&form_data.params.content])?
+      {
+        return Ok(response);
+      }
       let source = form_data.params.source;
       let destination = form_data
         .params
         .destination
         .clone()
         .unwrap_or(source.clone());
-      info!("MintWithPostage from {source} to {destination}");
+      Span::current().record("source", source.to_string().as_str());
+      info!(%destination, "MintWithPostage");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       match form_data.method.as_str() {
         "mintWithPostage" => {
+          let reservation = quota::reserve(
+            mysql.as_deref(),
+            state.mint_quota,
+            &source,
+            form_data.params.repeat.unwrap_or(1),
+          )?;
           let mint = Mint {
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination: form_data.params.destination,
             source,
+            change_address: None,
             extension: form_data.params.extension,
+            content_type: None,
+            metadata: None,
+            metaprotocol: None,
+            pointer: None,
             content: form_data.params.content,
+            content_encoding: None,
+            content_file: None,
             repeat: form_data.params.repeat,
             target_postage: Amount::from_sat(form_data.params.target_postage),
             remint: None,
+            parent: None,
+            parent_destination: None,
+            allow_burn: form_data.params.allow_burn,
+            funding_inputs: Vec::new(),
+            inputs: Vec::new(),
+            exclude_inputs: Vec::new(),
+            min_confirmations: 0,
+            allow_unconfirmed_self: false,
+            verify_inputs: false,
+            voucher: None,
+            commit_fee_rate: None,
+            reveal_fee_rate: None,
+            reveal_fee_buffer_rate: None,
+            compress: None,
+            purpose: purpose.clone(),
+            reveal_keys: state.reveal_keys.clone(),
+            record_revenue: state.record_revenue,
+            dry_run: false,
+            max_total_fee: None,
+            reject_duplicate: false,
+            return_recovery_key: false,
+            reveal_seed: None,
           };
 
-          let output = mint.build(options, Some(service_address), service_fee, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let build = {
+            let mysql = mysql.clone();
+            state
+              .builds
+              .run(move || mint.build(options, Some(service_address), service_fee, mysql))
+              .await
+          };
+          let output = quota::settle(mysql.as_deref(), reservation, build)?;
+          journal::record_build(
+            mysql.as_deref(),
+            "mintWithPostage",
+            purpose.as_deref(),
+            form_data.params.fee_rate,
+            std::iter::once(&output.commit).chain(&output.reveal),
+          );
+          Ok(Response::new(Body::from(mint_response(&output, version)?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
-    (&Method::POST, Some(&"mintsWithPostage")) => {
+    (&Method::POST, Some(&"mintsWithPostage"))
+    | (&Method::POST, Some(&"unsafeMintsWithPostage")) => {
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: MintsWithPostageData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
+      if let Some(response) =
+        blocklist::check_contents(mysql.as_deref(), &form_data.params.content)?
+      {
+        return Ok(response);
+      }
       let source = form_data.params.source;
       let destination = form_data
         .params
         .destination
         .clone()
         .unwrap_or(source.clone());
-      info!("MintsWithPostage from {source} to {destination}");
+      let audit_source = source.to_string();
+      Span::current().record("source", audit_source.as_str());
+      info!(%destination, "MintsWithPostage");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       match form_data.method.as_str() {
-        "mintsWithPostage" => {
+        method @ ("mintsWithPostage" | "unsafeMintsWithPostage") => {
+          let nonstandard = capability::is_nonstandard(method);
+          if nonstandard {
+            if let Some(response) = state.capabilities.check_nonstandard(method) {
+              return Ok(response);
+            }
+          }
+          // non-standard builds are only served when they can be audited
+          let audit = if nonstandard {
+            Some(mysql.clone().ok_or(anyhow!("not database"))?)
+          } else {
+            None
+          };
+          let reservation = quota::reserve(
+            mysql.as_deref(),
+            state.mint_quota,
+            &source,
+            u64::try_from(form_data.params.content.len())?,
+          )?;
           let mint = mints::Mint {
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination: form_data.params.destination,
+            destinations: Vec::new(),
             source,
+            change_address: None,
             extension: form_data.params.extension,
+            content_type: None,
+            metadata: None,
+            metaprotocol: None,
             content: form_data.params.content,
+            content_encoding: None,
             target_postage: Amount::from_sat(form_data.params.target_postage),
+            target_postages: form_data
+              .params
+              .target_postages
+              .into_iter()
+              .map(Amount::from_sat)
+              .collect(),
             remint: None,
+            allow_burn: form_data.params.allow_burn,
+            no_limit: nonstandard,
+            max_nonstandard_weight: state.capabilities.max_nonstandard_weight(),
+            dedup: form_data.params.dedup,
+            optimize_grouping: form_data.params.optimize_grouping,
+            funding_inputs: Vec::new(),
+            inputs: Vec::new(),
+            exclude_inputs: Vec::new(),
+            min_confirmations: 0,
+            allow_unconfirmed_self: false,
+            verify_inputs: false,
+            commit_fee_rate: None,
+            reveal_fee_rate: None,
+            reveal_fee_buffer_rate: None,
+            purpose: purpose.clone(),
+            reveal_keys: state.reveal_keys.clone(),
+            record_revenue: state.record_revenue,
+            dry_run: false,
+            max_total_fee: None,
+            reject_duplicate: false,
+            return_recovery_key: false,
+            reveal_seed: None,
           };
 
-          let output = mint.build(options, Some(service_address), service_fee, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let build = {
+            let mysql = mysql.clone();
+            state
+              .builds
+              .run(move || mint.build(options, Some(service_address), service_fee, mysql))
+              .await
+          };
+          let output = quota::settle(mysql.as_deref(), reservation, build)?;
+          journal::record_build(
+            mysql.as_deref(),
+            method,
+            purpose.as_deref(),
+            form_data.params.fee_rate,
+            std::iter::once(&output.commit).chain(&output.reveal),
+          );
+          if let Some(audit) = audit {
+            capability::audit(
+              &audit,
+              method,
+              version,
+              &audit_source,
+              purpose.as_deref(),
+              &output.reveal,
+              idempotency_key.as_deref(),
+            )?;
+          }
+          Ok(Response::new(Body::from(mints_response(&output, version)?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"reMint")) => {
+      if let Some(response) = state.capabilities.check_remint("reMint") {
+        return Ok(response);
+      }
+
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: ReMintData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
+      if let Some(response) =
+        blocklist::check_contents(mysql.as_deref(), [&form_data.params.content])?
+      {
+        return Ok(response);
+      }
       let source = form_data.params.source;
       let destination = form_data
         .params
         .destination
         .clone()
         .unwrap_or(source.clone());
-      info!("reMint from {source} to {destination}");
+      let audit_source = source.to_string();
+      Span::current().record("source", audit_source.as_str());
+      info!(%destination, "reMint");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       match form_data.method.as_str() {
         "reMint" => {
+          let reservation = quota::reserve(
+            mysql.as_deref(),
+            state.mint_quota,
+            &source,
+            form_data.params.repeat.unwrap_or(1),
+          )?;
           let mint = Mint {
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination: form_data.params.destination,
             source,
+            change_address: None,
             extension: form_data.params.extension,
+            content_type: None,
+            metadata: None,
+            metaprotocol: None,
+            pointer: None,
             content: form_data.params.content,
+            content_encoding: None,
+            content_file: None,
             repeat: form_data.params.repeat,
             target_postage: Amount::from_sat(form_data.params.target_postage),
-            remint: Some(Txid::from_str(&form_data.params.remint)?),
+            remint: Some(parse_remint(&form_data.params.remint)?),
+            parent: None,
+            parent_destination: None,
+            allow_burn: form_data.params.allow_burn,
+            funding_inputs: Vec::new(),
+            inputs: Vec::new(),
+            exclude_inputs: Vec::new(),
+            min_confirmations: 0,
+            allow_unconfirmed_self: false,
+            verify_inputs: false,
+            voucher: None,
+            commit_fee_rate: None,
+            reveal_fee_rate: None,
+            reveal_fee_buffer_rate: None,
+            compress: None,
+            purpose: purpose.clone(),
+            reveal_keys: state.reveal_keys.clone(),
+            record_revenue: state.record_revenue,
+            dry_run: false,
+            max_total_fee: None,
+            reject_duplicate: false,
+            return_recovery_key: false,
+            reveal_seed: form_data.params.reveal_seed,
           };
 
-          let output = mint.build(options, Some(service_address), service_fee, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let audit = mysql.clone().ok_or(anyhow!("not database"))?;
+          let build = state
+            .builds
+            .run(move || mint.build(options, Some(service_address), service_fee, mysql))
+            .await;
+          let output = quota::settle(Some(&*audit), reservation, build)?;
+          journal::record_build(
+            Some(&audit),
+            "reMint",
+            purpose.as_deref(),
+            form_data.params.fee_rate,
+            std::iter::once(&output.commit).chain(&output.reveal),
+          );
+          capability::audit(
+            &audit,
+            "reMint",
+            version,
+            &audit_source,
+            purpose.as_deref(),
+            &output.reveal,
+            idempotency_key.as_deref(),
+          )?;
+          Ok(Response::new(Body::from(mint_response(&output, version)?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     (&Method::POST, Some(&"reMints")) => {
+      if let Some(response) = state.capabilities.check_remint("reMints") {
+        return Ok(response);
+      }
+
       let full_body = hyper::body::to_bytes(req.into_body()).await?;
       let decoded_body = String::from_utf8_lossy(&full_body).to_string();
 
       let form_data: ReMintsData = match serde_json::from_str(&decoded_body) {
         Ok(data) => data,
-        Err(_) => {
-          return Ok(Response::new(Body::from("Invalid form data")));
+        Err(err) => {
+          return Err(RequestError::InvalidParams(err.to_string()).into());
         }
       };
+      if let Some(response) =
+        blocklist::check_contents(mysql.as_deref(), &form_data.params.content)?
+      {
+        return Ok(response);
+      }
       let source = form_data.params.source;
       let destination = form_data
         .params
         .destination
         .clone()
         .unwrap_or(source.clone());
-      info!("reMints from {source} to {destination}");
+      let audit_source = source.to_string();
+      Span::current().record("source", audit_source.as_str());
+      info!(%destination, "reMints");
+      let purpose = state.purposes.check(form_data.params.purpose.clone())?;
 
       match form_data.method.as_str() {
         "reMints" => {
+          let reservation = quota::reserve(
+            mysql.as_deref(),
+            state.mint_quota,
+            &source,
+            u64::try_from(form_data.params.content.len())?,
+          )?;
           let mint = mints::Mint {
             fee_rate: FeeRate::try_from(form_data.params.fee_rate)?,
             destination: form_data.params.destination,
+            destinations: Vec::new(),
             source,
+            change_address: None,
             extension: form_data.params.extension,
+            content_type: None,
+            metadata: None,
+            metaprotocol: None,
             content: form_data.params.content,
+            content_encoding: None,
             target_postage: Amount::from_sat(form_data.params.target_postage),
-            remint: Some(Txid::from_str(&form_data.params.remint)?),
+            target_postages: Vec::new(),
+            remint: Some(parse_remint(&form_data.params.remint)?),
+            allow_burn: form_data.params.allow_burn,
+            no_limit: false,
+            max_nonstandard_weight: state.capabilities.max_nonstandard_weight(),
+            dedup: form_data.params.dedup,
+            optimize_grouping: false,
+            funding_inputs: Vec::new(),
+            inputs: Vec::new(),
+            exclude_inputs: Vec::new(),
+            min_confirmations: 0,
+            allow_unconfirmed_self: false,
+            verify_inputs: false,
+            commit_fee_rate: None,
+            reveal_fee_rate: None,
+            reveal_fee_buffer_rate: None,
+            purpose: purpose.clone(),
+            reveal_keys: state.reveal_keys.clone(),
+            record_revenue: state.record_revenue,
+            dry_run: false,
+            max_total_fee: None,
+            reject_duplicate: false,
+            return_recovery_key: false,
+            reveal_seed: form_data.params.reveal_seed,
           };
 
-          let output = mint.build(options, Some(service_address), service_fee, mysql)?;
-          Ok(Response::new(Body::from(serde_json::to_string(&output)?)))
-        }
-        _ => {
-          let response = Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(Body::from("Method not found"))
-            .unwrap();
-          Ok(response)
+          let audit = mysql.clone().ok_or(anyhow!("not database"))?;
+          let build = state
+            .builds
+            .run(move || mint.build(options, Some(service_address), service_fee, mysql))
+            .await;
+          let output = quota::settle(Some(&*audit), reservation, build)?;
+          journal::record_build(
+            Some(&audit),
+            "reMints",
+            purpose.as_deref(),
+            form_data.params.fee_rate,
+            std::iter::once(&output.commit).chain(&output.reveal),
+          );
+          capability::audit(
+            &audit,
+            "reMints",
+            version,
+            &audit_source,
+            purpose.as_deref(),
+            &output.reveal,
+            idempotency_key.as_deref(),
+          )?;
+          Ok(Response::new(Body::from(mints_response(&output, version)?)))
         }
+        method => Err(RequestError::MethodNotFound(method.into()).into()),
       }
     }
     _ => {
@@ -668,67 +2554,351 @@ async fn _handle_request(
   }
 }
 
+/// Dispatches each call of a JSON-RPC batch to its route as a request of
+/// its own, a few at a time, answering with their envelopes in order.
+async fn handle_batch(
+  prefix: Option<String>,
+  service_fee: u64,
+  state: ServerState,
+  remote_addr: SocketAddr,
+  mut req: Request<Body>,
+) -> Result<Response<Body>, Error> {
+  let calls = match body::buffer(&mut req, state.max_body_size)
+    .await
+    .and_then(|body| Ok(batch::calls(&body)?))
+  {
+    Ok(calls) => calls,
+    Err(err) => {
+      info!(error = %err, "Batch refused");
+      let status = jsonrpc::status(&err).unwrap_or(StatusCode::BAD_REQUEST);
+      return Ok(jsonrpc::error_response(
+        serde_json::Value::Null,
+        status,
+        &err,
+        err.to_string(),
+      ));
+    }
+  };
+  info!(calls = calls.len(), "Batch");
+
+  let request_id = req
+    .headers()
+    .get(REQUEST_ID)
+    .and_then(|value| value.to_str().ok())
+    .map(str::to_owned)
+    .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+  let request_id = &request_id;
+  let indexes = batch::Indexes::default();
+  let items = stream::iter(calls.into_iter().enumerate().map(|(i, call)| {
+    let prefix = prefix.clone();
+    let state = state.clone();
+    let indexes = indexes.clone();
+    let mut headers = req.headers().clone();
+    async move {
+      let id = jsonrpc::request_id(&call);
+      let method = match batch::method(&call) {
+        Ok(method) => method,
+        Err(err) => return batch::item(id, Err(err.into())).await,
+      };
+
+      // each call is a request of its own, authenticated, rate limited and
+      // logged like any other, with the batch's request id as prefix, only
+      // the index is opened once for all of them
+      headers.remove(header::CONTENT_LENGTH);
+      headers.insert(
+        REQUEST_ID,
+        header::HeaderValue::from_str(&format!("{request_id}-{i}")).unwrap(),
+      );
+      let mut call_req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/{method}"))
+        .body(Body::from(call.to_string()))
+        .unwrap();
+      *call_req.headers_mut() = headers;
+
+      let response = handle_request(
+        prefix,
+        service_fee,
+        state,
+        remote_addr,
+        Some(indexes),
+        call_req,
+      )
+      .await;
+      batch::item(id, response).await
+    }
+  }))
+  .buffered(batch::PARALLELISM)
+  .collect::<Vec<serde_json::Value>>()
+  .await;
+
+  Ok(
+    Response::builder()
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(serde_json::Value::Array(items).to_string()))
+      .unwrap(),
+  )
+}
+
 async fn handle_request(
-  options: Options,
-  service_address: Address,
+  prefix: Option<String>,
   service_fee: u64,
-  mysql: Option<Arc<MysqlDatabase>>,
-  req: Request<Body>,
+  state: ServerState,
+  remote_addr: SocketAddr,
+  indexes: Option<batch::Indexes>,
+  mut req: Request<Body>,
 ) -> Result<Response<Body>, Error> {
-  let result = task::spawn(async move {
-    match _handle_request(options, service_address, service_fee, mysql, req).await {
-      Ok(v) => Ok(v),
-      Err(e) => {
-        error!("Req fail:{e}");
-        let format_error = format!("{}", e).to_lowercase();
-        let final_error = if format_error.contains("database") {
-          String::from("API requests are too frequent, please try again later")
+  let _in_flight = state.in_flight.start();
+  let start = Instant::now();
+
+  // JSON-RPC calls get their id echoed back, even when they fail
+  let wrapped = jsonrpc::wraps(req.method(), req.uri().path());
+  let call = if req.method() == Method::POST {
+    body::buffer(&mut req, state.max_body_size)
+      .await
+      .and_then(|body| {
+        if wrapped {
+          Ok(jsonrpc::parse(&body)?)
         } else {
-          format!("{}", e)
-        };
+          Ok(serde_json::Value::Null)
+        }
+      })
+  } else {
+    Ok(serde_json::Value::Null)
+  };
+  let (call, request_error) = match call {
+    Ok(call) => (Some(call), None),
+    Err(err) => (None, Some(err)),
+  };
+  let id = call
+    .as_ref()
+    .map_or(serde_json::Value::Null, jsonrpc::request_id);
+
+  // requests naming a chain that is not served fail like any other, from
+  // the default one
+  let (network, request_error) = match state.networks.select(prefix.as_deref(), call.as_ref()) {
+    Ok(network) => (network, request_error),
+    Err(err) => (
+      state.networks.default().clone(),
+      request_error.or(Some(err)),
+    ),
+  };
+  let service_address = network.service_addresses.select(&req);
+  let idempotency_key = ServiceAddresses::idempotency_key(&req).map(str::to_owned);
+
+  // every log emitted while handling the request, including those from the
+  // builders, inherits the request id from this span
+  let request_id = req
+    .headers()
+    .get(REQUEST_ID)
+    .and_then(|value| value.to_str().ok())
+    .map(str::to_owned)
+    .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+  let span = info_span!(
+    "request",
+    request_id = %request_id,
+    method = %req.method(),
+    path = %req.uri().path(),
+    network = %network.chain,
+    source = tracing::field::Empty,
+    purpose = tracing::field::Empty,
+    api_key = tracing::field::Empty,
+    service = %service_address,
+  );
+
+  // builds are expensive, each client ip and source address gets its own
+  // budget of them
+  let limiter = state
+    .rate_limiter
+    .clone()
+    .filter(|_| req.method() == Method::POST && !req.uri().path().starts_with("/admin"));
+  let rate_limit_keys = limiter
+    .as_ref()
+    .map(|limiter| rate_limit::keys(limiter.client_ip(&req, remote_addr), call.as_ref()));
+
+  let result = task::spawn(
+    {
+      let id = id.clone();
+      async move {
+        let result: Result<Response<Body>, Error> = async {
+          if let Some(err) = request_error {
+            return Err(err);
+          }
+          if let (Some(limiter), Some(keys)) = (limiter, rate_limit_keys) {
+            if let Err(wait) = limiter.check(&keys) {
+              info!(?keys, "Rate limit exceeded");
+              return Ok(rate_limit::too_many_requests(wait));
+            }
+          }
+          if let (Some(max_fee_rate), Some(call)) = (state.max_fee_rate, &call) {
+            fee_ceiling::check(call, max_fee_rate)?;
+          }
+          let source_use = key_policy::check_request(
+            network.mysql.as_deref(),
+            &state.access_keys,
+            &state.source_usage,
+            &req,
+            call.as_ref(),
+          )?;
+          let source_usage = state.source_usage.clone();
+          // looked up before handling, so the first request is not taken
+          // for a replay of itself once it is audited
+          let replay_of = match (&idempotency_key, network.mysql.as_deref()) {
+            (Some(key), Some(mysql)) if req.method() == Method::POST => {
+              mysql.get_replayed_sequence(key)?
+            }
+            _ => None,
+          };
+          let manifests = state.manifests.clone();
+          let disabled_warnings = state.disabled_warnings.clone();
+          let (req, captured) = manifests.capture(req).await?;
+          let mut handled = network.clone();
+          if let Some(indexes) = &indexes {
+            handled.options = indexes.share(&network.options)?;
+          }
+          let response =
+            _handle_request(handled, service_address.clone(), service_fee, state, req).await?;
+          // dry runs, failed builds and replays of earlier ones do not count
+          // against the api key
+          let dry_run = call
+            .as_ref()
+            .and_then(|call| call["dry_run"].as_bool())
+            .unwrap_or(false);
+          if let Some(source_use) =
+            source_use.filter(|_| response.status().is_success() && replay_of.is_none() && !dry_run)
+          {
+            key_policy::record(network.mysql.as_deref(), &source_usage, source_use)?;
+          }
+          let response = disabled_warnings.apply(response).await?;
+          let mut response = match captured {
+            Some(captured) => {
+              manifests
+                .attach(
+                  captured,
+                  response,
+                  &network.options,
+                  &service_address,
+                  network.mysql.as_deref(),
+                )
+                .await?
+            }
+            None => response,
+          };
+          if let Some(seq) = replay_of {
+            response
+              .headers_mut()
+              .insert(ServiceAddresses::REPLAY_HEADER, seq.into());
+          }
+          Ok(response)
+        }
+        .await;
+        match result {
+          Ok(v) if wrapped => Ok(jsonrpc::wrap(id, v).await),
+          Ok(v) => Ok(v),
+          Err(e) => {
+            error!(error = %e, "Request failed");
+            let format_error = format!("{}", e).to_lowercase();
+            let final_error = if format_error.contains("database") {
+              String::from("Database unavailable, please try again later")
+            } else {
+              format!("{}", e)
+            };
+            let status = if e.is::<NodeUnavailable>() || e.is::<BuildsBusy>() {
+              StatusCode::SERVICE_UNAVAILABLE
+            } else if let Some(status) = quota::status(&e) {
+              status
+            } else if let Some(status) = key_policy::status(&e) {
+              status
+            } else if let Some(status) = jsonrpc::status(&e) {
+              status
+            } else {
+              StatusCode::BAD_REQUEST
+            };
+            let mut response = if wrapped {
+              jsonrpc::error_response(id, status, &e, final_error)
+            } else if let Some(body) = jsonrpc::build_error(&e, &final_error) {
+              Response::builder()
+                .status(status)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+            } else {
+              Response::builder()
+                .status(status)
+                .body(Body::from(final_error))
+                .unwrap()
+            };
+            if let Some(busy) = e.downcast_ref::<BuildsBusy>() {
+              response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, busy.retry_after.into());
+            }
+            Ok(response)
+          }
+        }
+      }
+    }
+    .instrument(span.clone()),
+  )
+  .await;
+
+  let mut response = match result {
+    Ok(response) => response,
+    Err(panic) => {
+      span.in_scope(|| error!(%panic, "Request panicked"));
+      let message = "Internal error, please try again later";
+      if wrapped {
+        Ok(jsonrpc::error_response(
+          id,
+          StatusCode::BAD_REQUEST,
+          &anyhow!(message),
+          message.into(),
+        ))
+      } else {
         Ok(
           Response::builder()
             .status(StatusCode::BAD_REQUEST)
-            .body(Body::from(final_error))
+            .body(Body::from(message))
             .unwrap(),
         )
       }
     }
-  })
-  .await;
-  match result {
-    Ok(response) => response,
-    Err(panic) => {
-      error!("Req panic:{panic}");
-      Ok(
-        Response::builder()
-          .status(StatusCode::BAD_REQUEST)
-          .body(Body::from(
-            "API requests are too frequent, please try again later",
-          ))
-          .unwrap(),
+  };
+
+  if let Ok(response) = &mut response {
+    span.in_scope(|| {
+      info!(
+        status = response.status().as_u16(),
+        duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        "Request finished"
       )
+    });
+    if let Ok(value) = request_id.parse() {
+      response.headers_mut().insert(REQUEST_ID, value);
     }
   }
+
+  response
 }
 
 #[tokio::main]
 async fn main() {
-  std::env::set_var("RUST_LOG", "info");
-  env_logger::init();
   let args = Command::new("Brc20 Server")
     .arg(
       Arg::new("chain")
         .long("chain")
         .takes_value(true)
+        .action(ArgAction::Append)
         .default_value("test")
-        .help("Sets the chain"),
+        .help("Sets the chain. Repeat to serve several, requests pick one with a /<CHAIN> path prefix or a `network` param and go to the first otherwise."),
     )
     .arg(
       Arg::new("service-address")
         .long("service-address")
         .takes_value(true)
-        .help("Sets the service address"),
+        .action(ArgAction::Append)
+        .required(true)
+        .help("Sets a service address. Repeat to rotate service fees between several, requests with the same Idempotency-Key header always pay the same one. Prefix with <CHAIN>: to use it on that chain only."),
     )
     .arg(
       Arg::new("service-fee")
@@ -737,6 +2907,13 @@ async fn main() {
         .default_value("3000")
         .help("Sets the service fee"),
     )
+    .arg(
+      Arg::new("min-service-fee")
+        .long("min-service-fee")
+        .takes_value(true)
+        .default_value("546")
+        .help("Charge at least <MIN_SERVICE_FEE> sats when a request authenticated with one of --api-keys asks for its own service_fee on /mint, /estimateMint or /mints."),
+    )
     .arg(
       Arg::new("bitcoin-data-dir")
         .long("bitcoin-data-dir")
@@ -761,18 +2938,41 @@ async fn main() {
         .takes_value(true)
         .help("Store index in <DATA_DIR>."),
     )
+    .arg(
+      Arg::new("adopt-data-dir")
+        .long("adopt-data-dir")
+        .takes_value(false)
+        .help("Mark an index or mysql database that does not record its chain as belonging to <CHAIN>."),
+    )
     .arg(
       Arg::new("rpc-url")
         .long("rpc-url")
         .takes_value(true)
-        .help("Connect to Bitcoin Core RPC at <RPC_URL>."),
+        .action(ArgAction::Append)
+        .help("Connect to Bitcoin Core RPC at <RPC_URL>. May be repeated, later endpoints are failed over to in order. Prefix with <CHAIN>: to use it on that chain only."),
+    )
+    .arg(
+      Arg::new("rpc-failback-interval")
+        .long("rpc-failback-interval")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64))
+        .default_value("30")
+        .help("Check the first <RPC_URL> every <RPC_FAILBACK_INTERVAL> seconds while failed over, and fail back once it recovers."),
     )
     .arg(
       Arg::new("ip")
         .long("ip")
         .takes_value(true)
+        .value_parser(clap::value_parser!(IpAddr))
         .default_value("0.0.0.0")
-        .help("Connect to Bitcoin Core RPC at <RPC_URL>."),
+        .help("Listen on <IP>."),
+    )
+    .arg(
+      Arg::new("port")
+        .long("port")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u16))
+        .help("Listen on <PORT>, defaults to 3080 on mainnet, 13080 on testnet, 23080 on signet and 33080 on regtest."),
     )
     .arg(
       Arg::new("mysql-host")
@@ -791,34 +2991,235 @@ async fn main() {
         .long("mysql-password")
         .takes_value(true)
         .help("Mysql password."),
+    )
+    .arg(
+      Arg::new("api-keys")
+        .long("api-keys")
+        .takes_value(true)
+        .help("Require a matching `Authorization: Bearer <TOKEN>` on every non-admin POST. <API_KEYS> is a comma-separated list of <LABEL>=<TOKEN>, or a file with one per line. Requests are logged with the <LABEL> of their key. In the file, a key may be followed by `sources=<ADDRESS>,...` to only act for those sources and `max_daily_sources=<COUNT>` to act for at most that many distinct sources per UTC day."),
+    )
+    .arg(
+      Arg::new("admin-token")
+        .long("admin-token")
+        .takes_value(true)
+        .help("Enable admin endpoints for requests bearing <ADMIN_TOKEN>."),
+    )
+    .arg(
+      Arg::new("admin-key")
+        .long("admin-key")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .help("Enable admin endpoints for requests bearing <TOKEN>, given as <LABEL>=<TOKEN>. Changes are audited under <LABEL>."),
+    )
+    .arg(
+      Arg::new("op-return-brand")
+        .long("op-return-brand")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .help("Stamp <PREFIX> into an OP_RETURN on transfers authenticated with the --api-keys key <LABEL> that set no op_return of their own, given as <LABEL>=<PREFIX>. At most 16 bytes."),
+    )
+    .arg(
+      Arg::new("watched-only")
+        .long("watched-only")
+        .takes_value(false)
+        .help("Refuse inscription queries for addresses that are not watched."),
+    )
+    .arg(
+      Arg::new("allow-remint")
+        .long("allow-remint")
+        .takes_value(false)
+        .help("Serve reMint and reMints, every build is written to the audit trail."),
+    )
+    .arg(
+      Arg::new("allow-nonstandard")
+        .long("allow-nonstandard")
+        .takes_value(false)
+        .help("Serve unsafeMintsWithPostage, which may build reveals heavier than MAX_STANDARD_TX_WEIGHT."),
+    )
+    .arg(
+      Arg::new("max-nonstandard-weight")
+        .long("max-nonstandard-weight")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64))
+        .default_value("1000000")
+        .help("Refuse non-standard reveals heavier than <MAX_NONSTANDARD_WEIGHT> weight units."),
+    )
+    .arg(
+      Arg::new("signer-webhook-domain")
+        .long("signer-webhook-domain")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .help("Allow mint and transfer requests to delegate signing to https webhooks on <SIGNER_WEBHOOK_DOMAIN> or its subdomains."),
+    )
+    .arg(
+      Arg::new("drain-timeout")
+        .long("drain-timeout")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64))
+        .default_value("30")
+        .help("On SIGTERM or SIGINT, stop accepting connections and give in-flight requests <DRAIN_TIMEOUT> seconds to finish."),
+    )
+    .arg(
+      Arg::new("signer-webhook-timeout")
+        .long("signer-webhook-timeout")
+        .takes_value(true)
+        .default_value("10")
+        .help("Give signer webhooks <SIGNER_WEBHOOK_TIMEOUT> seconds to respond."),
+    )
+    .arg(
+      Arg::new("signer-webhook-max-response")
+        .long("signer-webhook-max-response")
+        .takes_value(true)
+        .default_value("1000000")
+        .help("Reject signer webhook responses larger than <SIGNER_WEBHOOK_MAX_RESPONSE> bytes."),
+    )
+    .arg(
+      Arg::new("manifest-hmac-key")
+        .long("manifest-hmac-key")
+        .takes_value(true)
+        .help("Add a manifest signed with HMAC-SHA256 under <MANIFEST_HMAC_KEY> to every build response and archive it."),
+    )
+    .arg(
+      Arg::new("disable-warning")
+        .long("disable-warning")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .help("Leave <DISABLE_WARNING> out of build responses, one of index_lag, high_fee_rate, large_change, destination_is_source or unsafe_destination. May be repeated."),
+    )
+    .arg(
+      Arg::new("allowed-destination-types")
+        .long("allowed-destination-types")
+        .takes_value(true)
+        .use_value_delimiter(true)
+        .value_parser(|address_type: &str| address_type.trim().parse::<AddressType>())
+        .help("Only build outputs to <ALLOWED_DESTINATION_TYPES>, a comma separated list of p2pkh, p2sh, p2wpkh, p2wsh and p2tr. Applies to destination, change and service addresses. Every type is allowed when unset."),
+    )
+    .arg(
+      Arg::new("record-revenue")
+        .long("record-revenue")
+        .takes_value(false)
+        .help("Record what each commit output pays for in the revenue table, postage, reveal fee and service fee, with the reveal output that received the service fee."),
+    )
+    .arg(
+      Arg::new("mint-quota")
+        .long("mint-quota")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64))
+        .help("Let each source mint at most <MINT_QUOTA> inscriptions per UTC day, unless overridden or exempted through /admin/quota. Unlimited when unset."),
+    )
+    .arg(
+      Arg::new("default-fee-rate")
+        .long("default-fee-rate")
+        .takes_value(true)
+        .default_value("1.0")
+        .help("Recommend <DEFAULT_FEE_RATE> sats/vB on /feeRate for targets bitcoind cannot estimate yet, as on a fresh regtest chain."),
+    )
+    .arg(
+      Arg::new("max-fee-rate")
+        .long("max-fee-rate")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(f64))
+        .help("Refuse calls with a fee_rate, commit_fee_rate or reveal_fee_rate above <MAX_FEE_RATE> sats/vB before building. Unlimited when unset."),
+    )
+    .arg(
+      Arg::new("max-body-size")
+        .long("max-body-size")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(usize))
+        .default_value(body::DEFAULT_MAX_SIZE)
+        .help("Refuse POST bodies larger than <MAX_BODY_SIZE> bytes with 413, before reading the rest."),
+    )
+    .arg(
+      Arg::new("max-concurrent-builds")
+        .long("max-concurrent-builds")
+        .takes_value(true)
+        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+        .default_value("8")
+        .help("Run at most <MAX_CONCURRENT_BUILDS> builds at a time on blocking threads, with as many more waiting. Builds beyond that are refused with 503 and Retry-After."),
+    )
+    .arg(
+      Arg::new("rate-limit-per-minute")
+        .long("rate-limit-per-minute")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u32).range(1..))
+        .help("Accept at most <RATE_LIMIT_PER_MINUTE> POST requests a minute from each client IP, and as many naming each source address, answering 429 with Retry-After beyond that. The client IP is the peer's, or behind a --trusted-proxy the right-most X-Forwarded-For hop that is not one. Unlimited when unset."),
+    )
+    .arg(
+      Arg::new("trusted-proxy")
+        .long("trusted-proxy")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .value_parser(clap::value_parser!(IpAddr))
+        .help("Believe the X-Forwarded-For hops added by the proxy at <TRUSTED_PROXY> when rate limiting. May be repeated."),
+    )
+    .arg(
+      Arg::new("unsafe-destination")
+        .long("unsafe-destination")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .value_parser(UnsafeDestination::from_str)
+        .help("Refuse to transfer inscriptions to destinations matching <UNSAFE_DESTINATION>, written LABEL=PATTERN where PATTERN is an address, prefix:<ADDRESS_PREFIX> or script:<HEX_SCRIPT_PREFIX>. May be repeated, and extended through the UNSAFE_DESTINATION table."),
+    )
+    .arg(
+      Arg::new("purpose")
+        .long("purpose")
+        .takes_value(true)
+        .action(ArgAction::Append)
+        .help("Only accept build requests tagged with <PURPOSE>, or untagged. May be repeated. Any purpose is accepted when unset."),
+    )
+    .arg(
+      Arg::new("reveal-key-secret")
+        .long("reveal-key-secret")
+        .takes_value(true)
+        .help("Derive reveal keys from <REVEAL_KEY_SECRET> and store a salt per commit, so /bumpReveal can re-sign stuck reveals. The secret together with the database can spend unrevealed commit outputs, keep it off the database host."),
+    )
+    .arg(
+      Arg::new("maintenance-at-start")
+        .long("maintenance-at-start")
+        .takes_value(false)
+        .help("Start in maintenance mode."),
+    )
+    .arg(
+      Arg::new("log-format")
+        .long("log-format")
+        .takes_value(true)
+        .default_value("text")
+        .help("Write logs as <LOG_FORMAT>, one of json or text."),
+    )
+    .arg(
+      Arg::new("log-level")
+        .long("log-level")
+        .takes_value(true)
+        .help("Log at <LOG_LEVEL>, e.g. `info,ord::index=debug`. Overrides RUST_LOG."),
     );
 
   let matches = args.get_matches();
-  let chain = matches
-    .get_one::<String>("chain")
-    .map(|s| s.as_str())
-    .unwrap();
-  let service_address: Address = Address::from_str(
+  logging::init(
     matches
-      .get_one::<String>("service-address")
-      .map(|s| s.as_str())
+      .get_one::<String>("log-format")
+      .unwrap()
+      .parse::<LogFormat>()
       .unwrap(),
+    matches.get_one::<String>("log-level").map(|s| s.as_str()),
   )
   .unwrap();
-
-  let chain_argument = match chain {
-    "main" => Chain::Mainnet,
-    "regtest" => Chain::Regtest,
-    "signet" => Chain::Signet,
-    _ => Chain::Testnet,
+  let chains = match matches
+    .get_many::<String>("chain")
+    .unwrap()
+    .map(|chain| Networks::parse_chain(chain))
+    .collect::<Result<Vec<Chain>, Error>>()
+  {
+    Ok(chains) => chains,
+    Err(err) => {
+      error!(error = %err, "Chain fail");
+      std::process::exit(1);
+    }
   };
 
-  let network = match chain {
-    "main" => Network::Bitcoin,
-    "regtest" => Network::Regtest,
-    "signet" => Network::Signet,
-    _ => Network::Testnet,
-  };
+  let service_address_arguments = matches
+    .get_many::<String>("service-address")
+    .map(|addresses| addresses.cloned().collect::<Vec<String>>())
+    .unwrap_or_default();
 
   let bitcoin_data_dir: Option<PathBuf> = matches
     .get_one::<String>("bitcoin-data-dir")
@@ -830,9 +3231,21 @@ async fn main() {
 
   let data_dir: Option<PathBuf> = matches.get_one::<String>("data-dir").map(|s| s.into());
 
-  let rpc_url = matches.get_one::<String>("rpc-url").cloned();
+  let adopt_data_dir = matches.contains_id("adopt-data-dir");
+
+  let rpc_urls: Vec<String> = matches
+    .get_many::<String>("rpc-url")
+    .map(|urls| urls.cloned().collect())
+    .unwrap_or_default();
+  let rpc_failback_interval =
+    Duration::from_secs(*matches.get_one::<u64>("rpc-failback-interval").unwrap());
 
-  let ip = matches.get_one::<String>("ip").cloned().unwrap();
+  let allowed_destination_types: Vec<AddressType> = matches
+    .get_many::<AddressType>("allowed-destination-types")
+    .map(|types| types.copied().collect())
+    .unwrap_or_default();
+
+  let ip = *matches.get_one::<IpAddr>("ip").unwrap();
 
   let service_fee: u64 = matches
     .get_one::<String>("service-fee")
@@ -842,63 +3255,273 @@ async fn main() {
   let mysql_host = matches.get_one::<String>("mysql-host").cloned();
   let mysql_username = matches.get_one::<String>("mysql-username").cloned();
   let mysql_password = matches.get_one::<String>("mysql-password").cloned();
-  let database = if mysql_host.is_none() || mysql_username.is_none() || mysql_password.is_none() {
-    info!("Use redb...");
-    None
-  } else {
+  let use_mysql = mysql_host.is_some() && mysql_username.is_some() && mysql_password.is_some();
+  if use_mysql {
     info!("Use mysql...");
-    Some(Arc::new(
-      MysqlDatabase::new(mysql_host, mysql_username, mysql_password, network).unwrap(),
-    ))
+  } else {
+    info!("Use redb...");
+  }
+
+  let mut networks = Vec::new();
+  for chain_argument in chains {
+    let service_addresses = match ServiceAddresses::new(
+      &Networks::scoped(&service_address_arguments, chain_argument),
+      chain_argument.network(),
+    ) {
+      Ok(service_addresses) => Arc::new(service_addresses),
+      Err(err) => {
+        error!(chain = %chain_argument, error = %err, "Service address fail");
+        std::process::exit(1);
+      }
+    };
+
+    let database = use_mysql.then(|| {
+      let database = match MysqlDatabase::new(
+        mysql_host.clone(),
+        mysql_username.clone(),
+        mysql_password.clone(),
+        chain_argument.network(),
+      ) {
+        Ok(database) => database,
+        Err(err) => {
+          error!(chain = %chain_argument, error = %err, "Open mysql fail");
+          std::process::exit(1);
+        }
+      };
+      if let Err(err) = database.check_network(adopt_data_dir) {
+        error!(chain = %chain_argument, error = %err, "Mysql network fail");
+        std::process::exit(1);
+      }
+      Arc::new(database)
+    });
+
+    let options = Options {
+      adopt_data_dir,
+      allowed_destination_types: allowed_destination_types.clone(),
+      bitcoin_data_dir: bitcoin_data_dir.clone(),
+      bitcoin_rpc_pass: bitcoin_rpc_pass.clone(),
+      bitcoin_rpc_user: bitcoin_rpc_user.clone(),
+      chain_argument,
+      config: None,
+      config_dir: None,
+      cookie_file: None,
+      data_dir: data_dir.clone(),
+      first_inscription_height: None,
+      height_limit: None,
+      index: None,
+      index_sats: false,
+      regtest: false,
+      rpc_url: Networks::scoped(&rpc_urls, chain_argument),
+      shared_index: None,
+      signet: false,
+      testnet: false,
+      wallet: "ord".to_string(),
+    };
+
+    if let Err(err) = Index::read_open(&options) {
+      error!(chain = %chain_argument, error = %err, "Open index fail");
+      std::process::exit(1);
+    }
+
+    // each chain's maintenance window is persisted in its own database
+    let maintenance = Arc::new(Maintenance::new(
+      matches.contains_id("maintenance-at-start"),
+      database.clone(),
+    ));
+
+    networks.push(Network {
+      chain: chain_argument,
+      options,
+      mysql: database,
+      service_addresses,
+      maintenance,
+    });
+  }
+  let networks = match Networks::new(networks) {
+    Ok(networks) => Arc::new(networks),
+    Err(err) => {
+      error!(error = %err, "Chain fail");
+      std::process::exit(1);
+    }
+  };
+
+  let state = ServerState {
+    admin_keys: Arc::new(
+      AdminKeys::new(
+        matches.get_one::<String>("admin-token").cloned(),
+        &matches
+          .get_many::<String>("admin-key")
+          .map(|keys| keys.cloned().collect::<Vec<String>>())
+          .unwrap_or_default(),
+      )
+      .unwrap(),
+    ),
+    access_keys: Arc::new(
+      AccessKeys::new(
+        matches.get_one::<String>("api-keys").map(String::as_str),
+        &matches
+          .get_many::<String>("op-return-brand")
+          .map(|brands| brands.cloned().collect::<Vec<String>>())
+          .unwrap_or_default(),
+      )
+      .unwrap(),
+    ),
+    source_usage: Arc::new(SourceUsage::default()),
+    watched_only: matches.contains_id("watched-only"),
+    capabilities: Arc::new(Capabilities::new(
+      matches.contains_id("allow-remint"),
+      matches.contains_id("allow-nonstandard"),
+      *matches.get_one::<u64>("max-nonstandard-weight").unwrap(),
+    )),
+    signer_webhooks: Arc::new(SignerWebhooks::new(
+      matches
+        .get_many::<String>("signer-webhook-domain")
+        .map(|domains| domains.cloned().collect())
+        .unwrap_or_default(),
+      Duration::from_secs(
+        matches
+          .get_one::<String>("signer-webhook-timeout")
+          .unwrap()
+          .parse()
+          .unwrap(),
+      ),
+      matches
+        .get_one::<String>("signer-webhook-max-response")
+        .unwrap()
+        .parse()
+        .unwrap(),
+    )),
+    manifests: Arc::new(Manifests::new(
+      matches.get_one::<String>("manifest-hmac-key").cloned(),
+    )),
+    disabled_warnings: Arc::new(
+      DisabledWarnings::new(
+        &matches
+          .get_many::<String>("disable-warning")
+          .map(|codes| codes.cloned().collect::<Vec<String>>())
+          .unwrap_or_default(),
+      )
+      .unwrap(),
+    ),
+    reveal_keys: matches
+      .get_one::<String>("reveal-key-secret")
+      .map(|secret| Arc::new(RevealKeys::new(secret.clone()))),
+    record_revenue: matches.contains_id("record-revenue"),
+    networks: networks.clone(),
+    mint_quota: matches.get_one::<u64>("mint-quota").copied(),
+    purposes: Arc::new(Purposes::new(
+      &matches
+        .get_many::<String>("purpose")
+        .map(|purposes| purposes.cloned().collect::<Vec<String>>())
+        .unwrap_or_default(),
+    )),
+    rate_limiter: matches
+      .get_one::<u32>("rate-limit-per-minute")
+      .map(|per_minute| {
+        Arc::new(RateLimiter::new(
+          *per_minute,
+          matches
+            .get_many::<IpAddr>("trusted-proxy")
+            .map(|proxies| proxies.copied().collect())
+            .unwrap_or_default(),
+        ))
+      }),
+    in_flight: InFlight::default(),
+    builds: BuildPool::new(*matches.get_one::<usize>("max-concurrent-builds").unwrap()),
+    max_body_size: *matches.get_one::<usize>("max-body-size").unwrap(),
+    max_fee_rate: matches.get_one::<f64>("max-fee-rate").copied(),
+    default_fee_rate: matches
+      .get_one::<String>("default-fee-rate")
+      .unwrap()
+      .parse()
+      .unwrap(),
+    min_service_fee: Amount::from_sat(
+      matches
+        .get_one::<String>("min-service-fee")
+        .unwrap()
+        .parse()
+        .unwrap(),
+    ),
+    unsafe_destinations: Arc::new(
+      matches
+        .get_many::<UnsafeDestination>("unsafe-destination")
+        .map(|destinations| destinations.cloned().collect())
+        .unwrap_or_default(),
+    ),
   };
 
-  let options = Options {
-    bitcoin_data_dir,
-    bitcoin_rpc_pass,
-    bitcoin_rpc_user,
-    chain_argument,
-    config: None,
-    config_dir: None,
-    cookie_file: None,
-    data_dir,
-    first_inscription_height: None,
-    height_limit: None,
-    index: None,
-    index_sats: false,
-    regtest: false,
-    rpc_url,
-    signet: false,
-    testnet: false,
-    wallet: "ord".to_string(),
+  let port = matches
+    .get_one::<u16>("port")
+    .copied()
+    .unwrap_or_else(|| networks.default().chain.default_server_port());
+  let addr = SocketAddr::new(ip, port);
+  let server = match Server::try_bind(&addr) {
+    Ok(server) => server,
+    Err(err) => {
+      error!(%addr, error = %err, "Bind fail, is another instance listening on this port?");
+      std::process::exit(1);
+    }
   };
+  for network in networks.all() {
+    info!(
+      %addr,
+      chain = ?network.chain,
+      service = ?network
+        .service_addresses
+        .all()
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<String>>(),
+      "Server running"
+    );
+    rpc_failover::spawn_failback(network.options.clone(), rpc_failback_interval);
+    if let Some(database) = network.mysql.clone() {
+      let options = network.options.clone();
+      let service_addresses = network.service_addresses.clone();
+      std::thread::spawn(move || {
+        airdrop::run_worker(options, database, service_addresses, service_fee)
+      });
+    }
+  }
 
-  let addr = SocketAddr::new(ip.as_str().parse().unwrap(), 3080);
-  info!(
-    "Server running at http://{}, network:{:?}, service:{:?}",
-    addr,
-    chain_argument,
-    service_address.clone()
-  );
-  let make_svc = make_service_fn(move |_conn| {
-    let options = options.clone();
-    let service_address = service_address.clone();
-    let database = database.clone();
+  let drain_timeout = Duration::from_secs(*matches.get_one::<u64>("drain-timeout").unwrap());
+  let in_flight = state.in_flight.clone();
+  let make_svc = make_service_fn(move |conn: &AddrStream| {
+    let remote_addr = conn.remote_addr();
+    let state = state.clone();
     async move {
-      Ok::<_, Error>(service_fn(move |req| {
-        handle_request(
-          options.clone(),
-          service_address.clone(),
-          service_fee,
-          database.clone(),
-          req,
-        )
+      Ok::<_, Error>(service_fn(move |mut req: Request<Body>| {
+        let state = state.clone();
+        async move {
+          let prefix = Networks::strip_prefix(&mut req);
+          if ws::is_upgrade(&req) {
+            match state.networks.select(prefix.as_deref(), None) {
+              Ok(network) => Ok(ws::upgrade(req, network.options)),
+              Err(err) => Ok(
+                Response::builder()
+                  .status(StatusCode::BAD_REQUEST)
+                  .body(Body::from(err.to_string()))
+                  .unwrap(),
+              ),
+            }
+          } else if req.method() == Method::POST && req.uri().path() == "/batch" {
+            handle_batch(prefix, service_fee, state, remote_addr, req).await
+          } else {
+            handle_request(prefix, service_fee, state, remote_addr, None, req).await
+          }
+        }
       }))
     }
   });
 
-  let server = Server::bind(&addr).serve(make_svc);
+  let draining = Arc::new(Notify::new());
+  let server = server.serve(make_svc).with_graceful_shutdown({
+    let draining = draining.clone();
+    async move {
+      shutdown::signal().await;
+      draining.notify_one();
+    }
+  });
 
-  if let Err(e) = server.await {
-    error!("Server error: {}", e);
-  }
+  shutdown::drain(server, draining, in_flight, drain_timeout).await;
 }
@@ -0,0 +1,176 @@
+use anyhow::Error;
+use hyper::{Body, Response, StatusCode};
+use ord::index::MysqlDatabase;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MaintenanceState {
+  pub enabled: bool,
+  pub message: Option<String>,
+  pub until: Option<u64>,
+}
+
+pub struct Maintenance {
+  state: RwLock<MaintenanceState>,
+  mysql: Option<Arc<MysqlDatabase>>,
+}
+
+impl Maintenance {
+  pub const CODE: i32 = -32001;
+
+  pub fn new(at_start: bool, mysql: Option<Arc<MysqlDatabase>>) -> Self {
+    let persisted = mysql.as_ref().and_then(|mysql| {
+      mysql
+        .get_maintenance()
+        .map_err(|err| error!(error = %err, "Load maintenance fail"))
+        .ok()
+        .flatten()
+    });
+
+    let state = match persisted {
+      Some((enabled, message, until)) if !at_start => MaintenanceState {
+        enabled,
+        message,
+        until,
+      },
+      _ => MaintenanceState {
+        enabled: at_start,
+        ..Default::default()
+      },
+    };
+
+    Self {
+      state: RwLock::new(state),
+      mysql,
+    }
+  }
+
+  // the window closes on its own once `until` has passed
+  pub fn get(&self) -> MaintenanceState {
+    let state = self.state.read().unwrap().clone();
+    match state.until {
+      Some(until) if state.enabled && until <= now() => MaintenanceState::default(),
+      _ => state,
+    }
+  }
+
+  pub fn set(&self, state: MaintenanceState) -> Result<MaintenanceState, Error> {
+    if let Some(mysql) = &self.mysql {
+      mysql.set_maintenance(state.enabled, state.message.clone(), state.until)?;
+    }
+    *self.state.write().unwrap() = state;
+    Ok(self.get())
+  }
+
+  pub fn response(&self) -> Option<Response<Body>> {
+    let state = self.get();
+    if !state.enabled {
+      return None;
+    }
+
+    let body = json!({
+      "jsonrpc": "2.0",
+      "id": null,
+      "error": {
+        "code": Self::CODE,
+        "message": state
+          .message
+          .unwrap_or_else(|| "Service is under maintenance".to_owned()),
+        "data": {
+          "until": state.until,
+        },
+      },
+    });
+
+    Some(
+      Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap(),
+    )
+  }
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn toggle() {
+    let maintenance = Maintenance::new(false, None);
+    assert!(!maintenance.get().enabled);
+    assert!(maintenance.response().is_none());
+
+    let state = MaintenanceState {
+      enabled: true,
+      message: Some("migrating".into()),
+      until: Some(now() + 600),
+    };
+    assert_eq!(maintenance.set(state.clone()).unwrap(), state);
+    assert!(maintenance.response().is_some());
+
+    maintenance.set(MaintenanceState::default()).unwrap();
+    assert!(maintenance.response().is_none());
+  }
+
+  #[test]
+  fn at_start() {
+    assert!(Maintenance::new(true, None).get().enabled);
+  }
+
+  #[test]
+  fn expires() {
+    let maintenance = Maintenance::new(false, None);
+    maintenance
+      .set(MaintenanceState {
+        enabled: true,
+        message: None,
+        until: Some(now() - 1),
+      })
+      .unwrap();
+    assert!(!maintenance.get().enabled);
+  }
+
+  #[tokio::test]
+  async fn response_shape() {
+    let maintenance = Maintenance::new(false, None);
+    maintenance
+      .set(MaintenanceState {
+        enabled: true,
+        message: Some("migrating".into()),
+        until: Some(4102444800),
+      })
+      .unwrap();
+
+    let response = maintenance.response().unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(
+      serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+      json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {
+          "code": -32001,
+          "message": "migrating",
+          "data": {
+            "until": 4102444800u64,
+          },
+        },
+      })
+    );
+  }
+}
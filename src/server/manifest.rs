@@ -0,0 +1,276 @@
+use crate::service_address::ServiceAddresses;
+use anyhow::Error;
+use bitcoin::{Address, Script};
+use hyper::body::Bytes;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use ord::index::{Index, MysqlDatabase};
+use ord::manifest::{Manifest, ManifestBody, ManifestTransaction, Roles};
+use ord::options::Options;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tracing::warn;
+
+/// Endpoints whose responses carry built transactions.
+const BUILD_METHODS: [&str; 10] = [
+  "mint",
+  "mints",
+  "mintWithPostage",
+  "mintsWithPostage",
+  "unsafeMintsWithPostage",
+  "transfer",
+  "transferWithFee",
+  "cancel",
+  "reMint",
+  "reMints",
+];
+
+/// The raw body of a build request, kept so the manifest can hash exactly
+/// what the caller sent.
+pub struct Captured {
+  method: String,
+  body: Bytes,
+  idempotency_key: Option<String>,
+}
+
+pub struct Manifests {
+  key: Option<Vec<u8>>,
+}
+
+impl Manifests {
+  pub fn new(key: Option<String>) -> Self {
+    Self {
+      key: key.map(String::into_bytes),
+    }
+  }
+
+  /// Buffers the body of build requests when manifests are enabled, handing
+  /// back an equivalent request for the handler.
+  pub async fn capture(
+    &self,
+    req: Request<Body>,
+  ) -> Result<(Request<Body>, Option<Captured>), Error> {
+    let method = req.uri().path().trim_start_matches('/').to_owned();
+    let idempotency_key = ServiceAddresses::idempotency_key(&req).map(str::to_owned);
+    if self.key.is_none()
+      || req.method() != Method::POST
+      || !BUILD_METHODS.contains(&method.as_str())
+    {
+      return Ok((req, None));
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    Ok((
+      Request::from_parts(parts, Body::from(body.clone())),
+      Some(Captured {
+        method,
+        body,
+        idempotency_key,
+      }),
+    ))
+  }
+
+  /// Adds a signed manifest to a successful build response and archives it.
+  /// Responses that are not build outputs, like the maintenance error, are
+  /// passed through untouched.
+  pub async fn attach(
+    &self,
+    captured: Captured,
+    response: Response<Body>,
+    options: &Options,
+    service_address: &Address,
+    mysql: Option<&MysqlDatabase>,
+  ) -> Result<Response<Body>, Error> {
+    let Some(key) = &self.key else {
+      return Ok(response);
+    };
+    if response.status() != StatusCode::OK {
+      return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    let Ok(Value::Object(mut output)) = serde_json::from_slice::<Value>(&body) else {
+      return Ok(Response::from_parts(parts, Body::from(body)));
+    };
+
+    let manifest = Manifest::sign(
+      ManifestBody::new(
+        &captured.method,
+        &captured.body,
+        transactions(&output, &roles(&captured.body, service_address))?,
+        fees(&output),
+        Index::read_open(options)?.index_height()?,
+      )?,
+      key,
+    )?;
+
+    if let Some(mysql) = mysql {
+      mysql.insert_manifest_audit(&manifest, captured.idempotency_key.as_deref())?;
+    } else {
+      warn!(method = %captured.method, "Manifest not archived, no database");
+    }
+
+    output.insert("manifest".into(), serde_json::to_value(&manifest)?);
+    Ok(Response::from_parts(
+      parts,
+      Body::from(Value::Object(output).to_string()),
+    ))
+  }
+}
+
+// build requests fund from `source` and send change back to it, the
+// destination defaults to the source
+fn roles(request: &[u8], service_address: &Address) -> Roles {
+  let params = serde_json::from_slice::<Value>(request)
+    .ok()
+    .and_then(|request| request.get("params").cloned())
+    .unwrap_or_default();
+  let script = |name: &str| -> Option<Script> {
+    Some(
+      Address::from_str(params.get(name)?.as_str()?)
+        .ok()?
+        .script_pubkey(),
+    )
+  };
+
+  let change = script("source");
+  Roles {
+    destination: script("destination").or_else(|| change.clone()),
+    change,
+    service: Some(service_address.script_pubkey()),
+  }
+}
+
+// in broadcast order: the commit, its reveals, or a single transaction
+fn transactions(
+  output: &Map<String, Value>,
+  roles: &Roles,
+) -> Result<Vec<ManifestTransaction>, Error> {
+  output
+    .get("commit")
+    .into_iter()
+    .chain(
+      output
+        .get("reveal")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten(),
+    )
+    .chain(output.get("transaction"))
+    .filter_map(Value::as_str)
+    .filter(|tx| !tx.is_empty())
+    .map(|tx| ManifestTransaction::from_hex(tx, roles))
+    .collect()
+}
+
+fn fees(output: &Map<String, Value>) -> BTreeMap<String, u64> {
+  output
+    .iter()
+    .filter(|(name, _)| name.ends_with("_fee"))
+    .filter_map(|(name, value)| Some((name.clone(), value.as_u64()?)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bitcoin::consensus::encode::serialize_hex;
+  use bitcoin::psbt::Psbt;
+  use bitcoin::{OutPoint, PackedLockTime, Sequence, Transaction, TxIn, TxOut, Witness};
+  use ord::manifest::OutputRole;
+  use serde_json::json;
+
+  const SOURCE: &str = "tb1qjsv26lap3ffssj6hfy8mzn0lg5vte6a42j75ww";
+  const DESTINATION: &str = "tb1q6en7qjxgw4ev8xwx94pzdry6a6ky7wlfeqzunz";
+  const SERVICE: &str = "tb1qakxxzv9n7706kc3xdcycrtfv8cqv62hnwexc0l";
+
+  fn script(address: &str) -> Script {
+    Address::from_str(address).unwrap().script_pubkey()
+  }
+
+  fn psbt(outputs: &[(u64, &str)]) -> String {
+    serialize_hex(
+      &Psbt::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+          previous_output: OutPoint::null(),
+          script_sig: Script::new(),
+          sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+          witness: Witness::new(),
+        }],
+        output: outputs
+          .iter()
+          .map(|(value, address)| TxOut {
+            value: *value,
+            script_pubkey: script(address),
+          })
+          .collect(),
+      })
+      .unwrap(),
+    )
+  }
+
+  #[test]
+  fn request_roles() {
+    let service = Address::from_str(SERVICE).unwrap();
+    let request = json!({
+      "method": "transfer",
+      "params": {"source": SOURCE, "destination": DESTINATION},
+    })
+    .to_string();
+
+    let transfer = roles(request.as_bytes(), &service);
+    assert_eq!(transfer.destination, Some(script(DESTINATION)));
+    assert_eq!(transfer.change, Some(script(SOURCE)));
+    assert_eq!(transfer.service, Some(script(SERVICE)));
+
+    let request = json!({"method": "mint", "params": {"source": SOURCE}}).to_string();
+    assert_eq!(
+      roles(request.as_bytes(), &service).destination,
+      Some(script(SOURCE))
+    );
+
+    assert_eq!(roles(b"not json", &service).change, None);
+  }
+
+  #[test]
+  fn output_transactions_and_fees() {
+    let roles = Roles {
+      destination: Some(script(DESTINATION)),
+      change: Some(script(SOURCE)),
+      service: Some(script(SERVICE)),
+    };
+    let output = json!({
+      "commit": psbt(&[(10000, DESTINATION), (3000, SERVICE), (500, SOURCE)]),
+      "reveal": [psbt(&[(546, DESTINATION)])],
+      "commit_custom": [],
+      "service_fee": 3000,
+      "network_fee": 400,
+      "commit_vsize": 150,
+    });
+    let output = output.as_object().unwrap();
+
+    let transactions = transactions(output, &roles).unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(
+      transactions[0]
+        .outputs
+        .iter()
+        .map(|output| output.role)
+        .collect::<Vec<OutputRole>>(),
+      [
+        OutputRole::Destination,
+        OutputRole::ServiceFee,
+        OutputRole::Change
+      ]
+    );
+
+    assert_eq!(
+      fees(output),
+      [("network_fee".into(), 400), ("service_fee".into(), 3000)].into()
+    );
+  }
+}
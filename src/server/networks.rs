@@ -0,0 +1,159 @@
+use crate::maintenance::Maintenance;
+use crate::service_address::ServiceAddresses;
+use anyhow::{anyhow, Error};
+use clap::ValueEnum;
+use hyper::{Body, Request, Uri};
+use ord::chain::Chain;
+use ord::index::MysqlDatabase;
+use ord::options::Options;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A chain the server serves, with its own index, database, service
+/// addresses and maintenance window.
+#[derive(Clone)]
+pub struct Network {
+  pub chain: Chain,
+  pub options: Options,
+  pub mysql: Option<Arc<MysqlDatabase>>,
+  pub service_addresses: Arc<ServiceAddresses>,
+  pub maintenance: Arc<Maintenance>,
+}
+
+/// The chains the server serves. Requests pick one with a `/<CHAIN>` path
+/// prefix or a `network` param, those naming none go to the first.
+pub struct Networks(Vec<Network>);
+
+impl Networks {
+  pub fn new(networks: Vec<Network>) -> Result<Self, Error> {
+    if networks.is_empty() {
+      return Err(anyhow!("at least one chain is required"));
+    }
+    for (i, network) in networks.iter().enumerate() {
+      if networks[..i]
+        .iter()
+        .any(|other| other.chain == network.chain)
+      {
+        return Err(anyhow!("chain {} is given twice", network.chain));
+      }
+    }
+    Ok(Self(networks))
+  }
+
+  pub fn parse_chain(name: &str) -> Result<Chain, Error> {
+    Chain::from_str(name, true).map_err(|_| anyhow!("unknown network `{name}`"))
+  }
+
+  /// The values of a repeatable flag that apply to `chain`, those given as
+  /// `<CHAIN>:<VALUE>` for it and those given without a chain.
+  pub fn scoped(values: &[String], chain: Chain) -> Vec<String> {
+    values
+      .iter()
+      .filter_map(|value| {
+        match value
+          .split_once(':')
+          .and_then(|(name, value)| Some((Self::parse_chain(name).ok()?, value)))
+        {
+          Some((scope, value)) => (scope == chain).then(|| value.to_owned()),
+          None => Some(value.clone()),
+        }
+      })
+      .collect()
+  }
+
+  pub fn default(&self) -> &Network {
+    &self.0[0]
+  }
+
+  pub fn all(&self) -> &[Network] {
+    &self.0
+  }
+
+  /// Strips a leading `/<CHAIN>` from the request's path, returning the
+  /// chain it named.
+  pub fn strip_prefix(req: &mut Request<Body>) -> Option<String> {
+    let path = req.uri().path();
+    let (name, rest) = match path[1..].split_once('/') {
+      Some((name, rest)) => (name, format!("/{rest}")),
+      None => (&path[1..], "/".to_owned()),
+    };
+    Self::parse_chain(name).ok()?;
+    let name = name.to_owned();
+
+    let uri = match req.uri().query() {
+      Some(query) => format!("{rest}?{query}"),
+      None => rest,
+    };
+    *req.uri_mut() = uri.parse::<Uri>().ok()?;
+    Some(name)
+  }
+
+  /// The network named by the path `prefix`, otherwise by the call's
+  /// `network` param, otherwise the default one.
+  pub fn select(&self, prefix: Option<&str>, call: Option<&Value>) -> Result<Network, Error> {
+    let name = prefix.or_else(|| {
+      call
+        .and_then(|call| call.get("params"))
+        .and_then(|params| params.get("network"))
+        .and_then(Value::as_str)
+    });
+
+    let Some(name) = name else {
+      return Ok(self.default().clone());
+    };
+
+    let chain = Self::parse_chain(name)?;
+    self
+      .0
+      .iter()
+      .find(|network| network.chain == chain)
+      .cloned()
+      .ok_or_else(|| {
+        anyhow!(
+          "network `{name}` is not served here, only {}",
+          self
+            .0
+            .iter()
+            .map(|network| network.chain.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+        )
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn scoped_values_apply_to_their_chain() {
+    let values = [
+      "main:bc1qxyz".to_owned(),
+      "test:tb1qxyz".to_owned(),
+      "http://127.0.0.1:8332".to_owned(),
+    ];
+    assert_eq!(
+      Networks::scoped(&values, Chain::Testnet),
+      ["tb1qxyz", "http://127.0.0.1:8332"]
+    );
+    assert_eq!(
+      Networks::scoped(&values, Chain::Signet),
+      ["http://127.0.0.1:8332"]
+    );
+  }
+
+  #[test]
+  fn prefixes_name_known_chains_only() {
+    let mut req = Request::builder()
+      .uri("/signet/query/utxos/tb1qxyz?limit=1")
+      .body(Body::empty())
+      .unwrap();
+    assert_eq!(Networks::strip_prefix(&mut req), Some("signet".into()));
+    assert_eq!(req.uri(), "/query/utxos/tb1qxyz?limit=1");
+
+    let mut req = Request::builder().uri("/mint").body(Body::empty()).unwrap();
+    assert_eq!(Networks::strip_prefix(&mut req), None);
+    assert_eq!(req.uri(), "/mint");
+  }
+}
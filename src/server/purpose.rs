@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Error};
+use std::collections::BTreeSet;
+use tracing::Span;
+
+/// Purposes clients may tag build requests with, so products sharing one
+/// deployment can tell their builds apart in the audit trail, revenue and
+/// confirmation stats. Any purpose is accepted when none are configured.
+pub struct Purposes(Option<BTreeSet<String>>);
+
+impl Purposes {
+  // the width of the purpose columns
+  const MAX_LEN: usize = 64;
+
+  pub fn new(allowed: &[String]) -> Self {
+    if allowed.is_empty() {
+      Self(None)
+    } else {
+      Self(Some(allowed.iter().cloned().collect()))
+    }
+  }
+
+  /// The purpose to record for a request, also recorded on the request span
+  /// so logs can be broken down by it. An empty purpose counts as none.
+  pub fn check(&self, purpose: Option<String>) -> Result<Option<String>, Error> {
+    let Some(purpose) = purpose.filter(|purpose| !purpose.is_empty()) else {
+      return Ok(None);
+    };
+
+    if purpose.len() > Self::MAX_LEN {
+      return Err(anyhow!(
+        "purpose is {} bytes long, at most {} are allowed",
+        purpose.len(),
+        Self::MAX_LEN
+      ));
+    }
+
+    if let Some(allowed) = &self.0 {
+      if !allowed.contains(&purpose) {
+        return Err(anyhow!(
+          "unknown purpose `{purpose}`, expected one of {}",
+          allowed.iter().cloned().collect::<Vec<String>>().join(", ")
+        ));
+      }
+    }
+
+    Span::current().record("purpose", purpose.as_str());
+    Ok(Some(purpose))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn any_purpose_without_allowlist() {
+    let purposes = Purposes::new(&[]);
+    assert_eq!(purposes.check(None).unwrap(), None);
+    assert_eq!(purposes.check(Some("".into())).unwrap(), None);
+    assert_eq!(
+      purposes.check(Some("Launchpad Q3".into())).unwrap(),
+      Some("Launchpad Q3".into())
+    );
+    assert!(purposes.check(Some("a".repeat(65))).is_err());
+  }
+
+  #[test]
+  fn unknown_purposes_are_rejected_with_allowlist() {
+    let purposes = Purposes::new(&["marketplace".into(), "treasury".into()]);
+    assert_eq!(purposes.check(None).unwrap(), None);
+    assert_eq!(
+      purposes.check(Some("treasury".into())).unwrap(),
+      Some("treasury".into())
+    );
+    assert_eq!(
+      purposes
+        .check(Some("launchpad".into()))
+        .unwrap_err()
+        .to_string(),
+      "unknown purpose `launchpad`, expected one of marketplace, treasury"
+    );
+  }
+}
@@ -0,0 +1,135 @@
+use anyhow::{anyhow, Error};
+use bitcoin::Address;
+use chrono::{NaiveDate, Utc};
+use hyper::StatusCode;
+use ord::index::{MintQuota, MintQuotaReservation, MysqlDatabase};
+use ord::subcommand::wallet::error::BuildError;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+/// Body of `/admin/quota/<action>`. `day` defaults to the current UTC day.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct QuotaRequest {
+  pub source: String,
+  #[serde(default)]
+  pub day: Option<NaiveDate>,
+  #[serde(default)]
+  pub daily_limit: Option<u64>,
+  #[serde(default)]
+  pub exempt: Option<bool>,
+}
+
+impl QuotaRequest {
+  pub fn day(&self) -> NaiveDate {
+    self.day.unwrap_or_else(|| MintQuota::day(Utc::now()))
+  }
+}
+
+/// Counts `count` inscriptions against the daily quota of `source`, failing
+/// with `BuildError::QuotaExceeded` if that would take it over.
+pub fn reserve(
+  mysql: Option<&MysqlDatabase>,
+  default_limit: Option<u64>,
+  source: &Address,
+  count: u64,
+) -> Result<Option<MintQuotaReservation>, Error> {
+  let Some(mysql) = mysql else {
+    return Ok(None);
+  };
+  mysql.reserve_mint_quota(&source.to_string(), count.max(1), default_limit, Utc::now())
+}
+
+/// Releases `reservation` if `result` is a failed build.
+pub fn settle<T>(
+  mysql: Option<&MysqlDatabase>,
+  reservation: Option<MintQuotaReservation>,
+  result: Result<T, Error>,
+) -> Result<T, Error> {
+  if let (Err(_), Some(mysql), Some(reservation)) = (&result, mysql, &reservation) {
+    if let Err(err) = mysql.release_mint_quota(reservation) {
+      error!(error = %err, source = %reservation.source, "Release mint quota error");
+    }
+  }
+  result
+}
+
+pub fn status(err: &Error) -> Option<StatusCode> {
+  match err.downcast_ref::<BuildError>() {
+    Some(BuildError::QuotaExceeded { .. }) => Some(StatusCode::TOO_MANY_REQUESTS),
+    _ => None,
+  }
+}
+
+/// Handles `/admin/quota/<action>`: `get` and `reset` the counter of a day,
+/// `override` a source's daily limit (no `daily_limit` removes it), and set
+/// whether a source is `exempt`.
+pub fn admin(
+  mysql: &MysqlDatabase,
+  default_limit: Option<u64>,
+  action: &str,
+  request: &QuotaRequest,
+  admin_label: &str,
+) -> Result<serde_json::Value, Error> {
+  let day = request.day();
+  match action {
+    "get" => {}
+    "reset" => {
+      let reset = mysql.reset_mint_quota(&request.source, day)?;
+      info!(source = %request.source, %day, reset, %admin_label, "Reset mint quota");
+    }
+    "override" => {
+      mysql.set_mint_quota_override(&request.source, request.daily_limit)?;
+      info!(source = %request.source, daily_limit = ?request.daily_limit, %admin_label, "Set mint quota override");
+    }
+    "exempt" => {
+      let exempt = request
+        .exempt
+        .ok_or_else(|| anyhow!("exempt is required"))?;
+      mysql.set_mint_quota_exempt(&request.source, exempt)?;
+      info!(source = %request.source, exempt, %admin_label, "Set mint quota exemption");
+    }
+    _ => return Err(anyhow!("unknown quota action `{action}`")),
+  }
+  Ok(serde_json::to_value(mysql.get_mint_quota(
+    &request.source,
+    day,
+    default_limit,
+  )?)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::TimeZone;
+
+  #[test]
+  fn request_defaults() {
+    let request = serde_json::from_str::<QuotaRequest>(r#"{"source":"bc1qexample"}"#).unwrap();
+    assert_eq!(request.day, None);
+    assert_eq!(request.daily_limit, None);
+    assert_eq!(request.exempt, None);
+    assert_eq!(request.day(), MintQuota::day(Utc::now()));
+
+    let request =
+      serde_json::from_str::<QuotaRequest>(r#"{"source":"bc1qexample","day":"2023-05-01"}"#)
+        .unwrap();
+    assert_eq!(request.day(), NaiveDate::from_ymd_opt(2023, 5, 1).unwrap());
+  }
+
+  #[test]
+  fn quota_exceeded_is_too_many_requests() {
+    let err = Error::from(BuildError::QuotaExceeded {
+      source: "bc1qexample".into(),
+      limit: 10,
+      used: 9,
+      requested: 2,
+      reset_at: Utc.with_ymd_and_hms(2023, 5, 2, 0, 0, 0).unwrap(),
+    });
+    assert_eq!(status(&err), Some(StatusCode::TOO_MANY_REQUESTS));
+    assert_eq!(
+      err.to_string(),
+      "source `bc1qexample` has minted 9 of its 10 daily inscriptions and cannot mint 2 more, the quota resets at 2023-05-02T00:00:00+00:00"
+    );
+    assert_eq!(status(&anyhow!("other")), None);
+  }
+}
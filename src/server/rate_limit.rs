@@ -0,0 +1,281 @@
+use crate::jsonrpc;
+use hyper::{header, Body, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// a bucket left alone this long is full again, as good as a new one
+const REFILL: Duration = Duration::from_secs(60);
+
+/// Token buckets of `per_minute` requests each, refilled continuously, one
+/// per client IP and one per source address. Shared by every connection.
+pub struct RateLimiter {
+  per_minute: u32,
+  trusted_proxies: Vec<IpAddr>,
+  buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+  tokens: HashMap<String, Bucket>,
+  swept: Instant,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+}
+
+impl RateLimiter {
+  /// `trusted_proxies` are the proxies whose `X-Forwarded-For` hops are
+  /// believed, requests from any other peer are keyed by its own IP.
+  pub fn new(per_minute: u32, trusted_proxies: Vec<IpAddr>) -> Self {
+    Self {
+      per_minute,
+      trusted_proxies,
+      buckets: Mutex::new(Buckets {
+        tokens: HashMap::new(),
+        swept: Instant::now(),
+      }),
+    }
+  }
+
+  /// Takes a token from the bucket of each of `keys`, or, when one of them
+  /// is empty, takes none and returns how long until all have one.
+  pub fn check(&self, keys: &[String]) -> Result<(), Duration> {
+    self.check_at(keys, Instant::now())
+  }
+
+  fn check_at(&self, keys: &[String], now: Instant) -> Result<(), Duration> {
+    let capacity = f64::from(self.per_minute);
+    let refill = REFILL.as_secs_f64();
+    let mut buckets = self.buckets.lock().unwrap();
+
+    // buckets untouched for a whole refill are dropped, so a scan over many
+    // addresses doesn't grow the map for good
+    if now.saturating_duration_since(buckets.swept) >= REFILL {
+      buckets
+        .tokens
+        .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < REFILL);
+      buckets.swept = now;
+    }
+
+    let refilled = keys
+      .iter()
+      .map(|key| {
+        let tokens = buckets.tokens.get(key).map_or(capacity, |bucket| {
+          (bucket.tokens
+            + now.saturating_duration_since(bucket.updated).as_secs_f64() * capacity / refill)
+            .min(capacity)
+        });
+        (key, tokens)
+      })
+      .collect::<Vec<(&String, f64)>>();
+
+    let wait = refilled
+      .iter()
+      .map(|(_, tokens)| (1.0 - tokens).max(0.0) * refill / capacity)
+      .fold(0.0, f64::max);
+    if wait > 0.0 {
+      return Err(Duration::from_secs_f64(wait));
+    }
+
+    for (key, tokens) in refilled {
+      buckets.tokens.insert(
+        key.clone(),
+        Bucket {
+          tokens: tokens - 1.0,
+          updated: now,
+        },
+      );
+    }
+    Ok(())
+  }
+
+  /// The client's IP. Behind trusted proxies it is the right-most
+  /// `X-Forwarded-For` hop that is not one of them, as the hops left of it
+  /// are written by the client and can be anything.
+  pub fn client_ip(&self, req: &Request<Body>, remote_addr: SocketAddr) -> IpAddr {
+    let mut client = remote_addr.ip();
+    if !self.trusted_proxies.contains(&client) {
+      return client;
+    }
+
+    let hops = req
+      .headers()
+      .get_all("X-Forwarded-For")
+      .iter()
+      .filter_map(|value| value.to_str().ok())
+      .flat_map(|value| value.split(','))
+      .collect::<Vec<&str>>();
+
+    for hop in hops.into_iter().rev() {
+      let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+        break;
+      };
+      client = ip;
+      if !self.trusted_proxies.contains(&client) {
+        break;
+      }
+    }
+
+    client
+  }
+
+  #[cfg(test)]
+  fn len(&self) -> usize {
+    self.buckets.lock().unwrap().tokens.len()
+  }
+}
+
+/// The buckets a request draws from: its client IP and, for calls with one,
+/// its `params.source` address.
+pub fn keys(ip: IpAddr, call: Option<&Value>) -> Vec<String> {
+  let mut keys = vec![format!("ip:{ip}")];
+  if let Some(source) = call
+    .and_then(|call| call.get("params"))
+    .and_then(|params| params.get("source"))
+    .and_then(Value::as_str)
+  {
+    keys.push(format!("source:{source}"));
+  }
+  keys
+}
+
+pub fn too_many_requests(wait: Duration) -> Response<Body> {
+  // rounded up, a client retrying right on time must find a token
+  let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+  let body = json!({
+    "jsonrpc": "2.0",
+    "id": null,
+    "error": {
+      "code": jsonrpc::RATE_LIMITED,
+      "message": "Rate limit exceeded",
+      "data": { "retry_after": retry_after },
+    },
+  });
+
+  Response::builder()
+    .status(StatusCode::TOO_MANY_REQUESTS)
+    .header(header::CONTENT_TYPE, "application/json")
+    .header(header::RETRY_AFTER, retry_after)
+    .body(Body::from(body.to_string()))
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+  }
+
+  #[test]
+  fn bucket_empties_and_refills() {
+    let limiter = RateLimiter::new(2, Vec::new());
+    let start = Instant::now();
+    let ip = keys(&["ip:1.2.3.4"]);
+
+    assert_eq!(limiter.check_at(&ip, start), Ok(()));
+    assert_eq!(limiter.check_at(&ip, start), Ok(()));
+    assert_eq!(limiter.check_at(&ip, start), Err(Duration::from_secs(30)));
+
+    assert!(limiter
+      .check_at(&ip, start + Duration::from_secs(29))
+      .is_err());
+    assert_eq!(
+      limiter.check_at(&ip, start + Duration::from_secs(30)),
+      Ok(())
+    );
+  }
+
+  #[test]
+  fn any_empty_bucket_refuses_without_taking() {
+    let limiter = RateLimiter::new(1, Vec::new());
+    let now = Instant::now();
+
+    assert_eq!(limiter.check_at(&keys(&["ip:a", "source:x"]), now), Ok(()));
+    // the same source from another ip is still limited
+    assert!(limiter.check_at(&keys(&["ip:b", "source:x"]), now).is_err());
+    // and the refused request took nothing from ip:b
+    assert_eq!(limiter.check_at(&keys(&["ip:b"]), now), Ok(()));
+  }
+
+  #[test]
+  fn idle_buckets_are_evicted() {
+    let limiter = RateLimiter::new(1, Vec::new());
+    let now = Instant::now();
+
+    for i in 0..100 {
+      limiter
+        .check_at(&keys(&[format!("ip:{i}").as_str()]), now)
+        .unwrap();
+    }
+    assert_eq!(limiter.len(), 100);
+
+    limiter.check_at(&keys(&["ip:late"]), now + REFILL).unwrap();
+    assert_eq!(limiter.len(), 1);
+  }
+
+  #[test]
+  fn forwarded_ip_is_only_believed_from_trusted_proxies() {
+    let proxy = "10.0.0.1:5000".parse::<SocketAddr>().unwrap();
+    let limiter = RateLimiter::new(1, vec![proxy.ip(), "10.0.0.2".parse().unwrap()]);
+    let forwarded = |hops: &str| {
+      Request::builder()
+        .header("X-Forwarded-For", hops)
+        .body(Body::empty())
+        .unwrap()
+    };
+    let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+    // the client wrote the left-most hop, the proxies the rest
+    assert_eq!(
+      limiter.client_ip(&forwarded("198.51.100.1, 203.0.113.7, 10.0.0.2"), proxy),
+      ip("203.0.113.7")
+    );
+    assert_eq!(
+      limiter.client_ip(&forwarded("203.0.113.7"), proxy),
+      ip("203.0.113.7")
+    );
+    assert_eq!(
+      limiter.client_ip(&forwarded("garbage, 10.0.0.2"), proxy),
+      ip("10.0.0.2")
+    );
+    assert_eq!(
+      limiter.client_ip(&Request::new(Body::empty()), proxy),
+      proxy.ip()
+    );
+
+    // anyone else is who they connect as
+    let direct = "203.0.113.9:5000".parse::<SocketAddr>().unwrap();
+    assert_eq!(
+      limiter.client_ip(&forwarded("198.51.100.1"), direct),
+      direct.ip()
+    );
+    assert_eq!(
+      RateLimiter::new(1, Vec::new()).client_ip(&forwarded("198.51.100.1"), proxy),
+      proxy.ip()
+    );
+  }
+
+  #[test]
+  fn source_gets_its_own_bucket() {
+    let ip = "203.0.113.7".parse().unwrap();
+    assert_eq!(
+      super::keys(ip, Some(&json!({ "params": { "source": "bc1qsource" } }))),
+      keys(&["ip:203.0.113.7", "source:bc1qsource"])
+    );
+    assert_eq!(super::keys(ip, None), keys(&["ip:203.0.113.7"]));
+  }
+
+  #[test]
+  fn retry_after_is_rounded_up() {
+    let response = too_many_requests(Duration::from_millis(1_500));
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+  }
+}
@@ -0,0 +1,132 @@
+use anyhow::{anyhow, Error};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{Address, Network};
+use hyper::{Body, Request};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The addresses service fees are paid to, one chosen per build so revenue
+/// and users are not clustered on a single address.
+#[derive(Debug)]
+pub struct ServiceAddresses {
+  addresses: Vec<Address>,
+  next: AtomicUsize,
+}
+
+impl ServiceAddresses {
+  pub const HEADER: &'static str = "Idempotency-Key";
+  /// Set on responses to a request already audited under the same key,
+  /// to the audit sequence number of the first one.
+  pub const REPLAY_HEADER: &'static str = "Idempotent-Replay";
+
+  pub fn new(addresses: &[String], network: Network) -> Result<Self, Error> {
+    let mut parsed: Vec<Address> = vec![];
+
+    for address in addresses {
+      let address = Address::from_str(address)
+        .map_err(|err| anyhow!("invalid service address `{address}`: {err}"))?;
+      if !address.is_valid_for_network(network) {
+        return Err(anyhow!(
+          "service address `{address}` is not valid for {network}"
+        ));
+      }
+      if parsed.contains(&address) {
+        return Err(anyhow!("service address `{address}` is given twice"));
+      }
+      parsed.push(address);
+    }
+
+    if parsed.is_empty() {
+      return Err(anyhow!("at least one service address is required"));
+    }
+
+    Ok(Self {
+      addresses: parsed,
+      next: AtomicUsize::new(0),
+    })
+  }
+
+  pub fn all(&self) -> &[Address] {
+    &self.addresses
+  }
+
+  /// The address for the request's idempotency key, so a replay builds the
+  /// same transactions, otherwise the next one in turn.
+  pub fn select(&self, req: &Request<Body>) -> Address {
+    match Self::idempotency_key(req) {
+      Some(key) => self.for_key(key),
+      None => {
+        self.addresses[self.next.fetch_add(1, Ordering::Relaxed) % self.addresses.len()].clone()
+      }
+    }
+  }
+
+  pub fn idempotency_key(req: &Request<Body>) -> Option<&str> {
+    req
+      .headers()
+      .get(Self::HEADER)
+      .and_then(|value| value.to_str().ok())
+  }
+
+  pub fn for_key(&self, key: &str) -> Address {
+    let hash = sha256::Hash::hash(key.as_bytes());
+    let n = u64::from_be_bytes(hash[..8].try_into().unwrap());
+    self.addresses[usize::try_from(n % self.addresses.len() as u64).unwrap()].clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FIRST: &str = "tb1qjsv26lap3ffssj6hfy8mzn0lg5vte6a42j75ww";
+  const SECOND: &str = "tb1q6en7qjxgw4ev8xwx94pzdry6a6ky7wlfeqzunz";
+
+  fn addresses() -> ServiceAddresses {
+    ServiceAddresses::new(&[FIRST.into(), SECOND.into()], Network::Testnet).unwrap()
+  }
+
+  fn request(key: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder();
+    if let Some(key) = key {
+      builder = builder.header(ServiceAddresses::HEADER, key);
+    }
+    builder.body(Body::empty()).unwrap()
+  }
+
+  #[test]
+  fn round_robin() {
+    let addresses = addresses();
+    let selected = (0..4)
+      .map(|_| addresses.select(&request(None)).to_string())
+      .collect::<Vec<String>>();
+    assert_eq!(selected, [FIRST, SECOND, FIRST, SECOND]);
+  }
+
+  #[test]
+  fn idempotency_keys_are_deterministic() {
+    let addresses = addresses();
+    let first = addresses.select(&request(Some("order-1")));
+    for _ in 0..4 {
+      assert_eq!(addresses.select(&request(Some("order-1"))), first);
+      addresses.select(&request(None));
+    }
+    assert_eq!(
+      ServiceAddresses::new(&[FIRST.into(), SECOND.into()], Network::Testnet)
+        .unwrap()
+        .for_key("order-1"),
+      first
+    );
+    assert!((0..16)
+      .map(|n| addresses.for_key(&format!("order-{n}")))
+      .any(|address| address != first));
+  }
+
+  #[test]
+  fn invalid_addresses() {
+    assert!(ServiceAddresses::new(&[], Network::Testnet).is_err());
+    assert!(ServiceAddresses::new(&["nonsense".into()], Network::Testnet).is_err());
+    assert!(ServiceAddresses::new(&[FIRST.into()], Network::Bitcoin).is_err());
+    assert!(ServiceAddresses::new(&[FIRST.into(), FIRST.into()], Network::Testnet).is_err());
+  }
+}
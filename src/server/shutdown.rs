@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal;
+use tokio::sync::Notify;
+use tokio::time;
+use tracing::{error, info, warn};
+
+/// Counts the requests being handled, so a shutdown can tell how many it
+/// waited for.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+/// Held for as long as a request is handled.
+pub struct Guard(Arc<AtomicUsize>);
+
+impl InFlight {
+  pub fn start(&self) -> Guard {
+    self.0.fetch_add(1, Ordering::SeqCst);
+    Guard(self.0.clone())
+  }
+
+  pub fn count(&self) -> usize {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+impl Drop for Guard {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+/// Resolves on SIGTERM or SIGINT.
+pub async fn signal() {
+  #[cfg(unix)]
+  let terminate = async {
+    match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+      Ok(mut terminate) => {
+        terminate.recv().await;
+      }
+      Err(err) => {
+        warn!(error = %err, "Cannot listen for SIGTERM");
+        std::future::pending::<()>().await
+      }
+    }
+  };
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+    _ = signal::ctrl_c() => {}
+    _ = terminate => {}
+  }
+}
+
+/// Runs `server`, which stops accepting connections once `draining` is
+/// notified, until it has finished the requests it was handling or
+/// `timeout` has passed since, whichever comes first.
+pub async fn drain<F, E>(server: F, draining: Arc<Notify>, in_flight: InFlight, timeout: Duration)
+where
+  F: Future<Output = Result<(), E>>,
+  E: std::fmt::Display,
+{
+  let started = Arc::new(AtomicUsize::new(0));
+  let deadline = {
+    let started = started.clone();
+    let in_flight = in_flight.clone();
+    async move {
+      draining.notified().await;
+      let count = in_flight.count();
+      started.store(count, Ordering::SeqCst);
+      info!(
+        in_flight = count,
+        timeout_ms = timeout.as_millis() as u64,
+        "Shutting down, draining requests"
+      );
+      time::sleep(timeout).await;
+    }
+  };
+
+  tokio::select! {
+    result = server => match result {
+      Ok(()) => info!(drained = started.load(Ordering::SeqCst), "Server stopped"),
+      Err(err) => error!(error = %err, "Server error"),
+    },
+    _ = deadline => {
+      let abandoned = in_flight.count();
+      warn!(
+        drained = started.load(Ordering::SeqCst).saturating_sub(abandoned),
+        abandoned,
+        "Drain timeout, stopping with requests in flight"
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn guards_count_requests() {
+    let in_flight = InFlight::default();
+    let first = in_flight.start();
+    let second = in_flight.start();
+    assert_eq!(in_flight.count(), 2);
+    drop(first);
+    assert_eq!(in_flight.count(), 1);
+    drop(second);
+    assert_eq!(in_flight.count(), 0);
+  }
+
+  #[tokio::test]
+  async fn drain_waits_for_the_server() {
+    let draining = Arc::new(Notify::new());
+    draining.notify_one();
+    let finished = Arc::new(AtomicUsize::new(0));
+    let server = {
+      let finished = finished.clone();
+      async move {
+        time::sleep(Duration::from_millis(10)).await;
+        finished.store(1, Ordering::SeqCst);
+        Ok::<(), String>(())
+      }
+    };
+
+    drain(
+      server,
+      draining,
+      InFlight::default(),
+      Duration::from_secs(10),
+    )
+    .await;
+    assert_eq!(finished.load(Ordering::SeqCst), 1);
+  }
+
+  #[tokio::test]
+  async fn drain_gives_up_after_timeout() {
+    let draining = Arc::new(Notify::new());
+    draining.notify_one();
+    let in_flight = InFlight::default();
+    let _stuck = in_flight.start();
+
+    drain(
+      std::future::pending::<Result<(), String>>(),
+      draining,
+      in_flight,
+      Duration::from_millis(10),
+    )
+    .await;
+  }
+}
@@ -0,0 +1,102 @@
+use anyhow::Error;
+use hyper::{Body, Response, StatusCode};
+use ord::subcommand::wallet::warning::WarningCode;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+/// Warning codes the operator turned off. Builders always collect every
+/// warning, the ones disabled here are dropped before the response leaves.
+pub struct DisabledWarnings(BTreeSet<WarningCode>);
+
+impl DisabledWarnings {
+  pub fn new(codes: &[String]) -> Result<Self, Error> {
+    Ok(Self(
+      codes
+        .iter()
+        .map(|code| code.parse())
+        .collect::<Result<_, _>>()?,
+    ))
+  }
+
+  pub async fn apply(&self, response: Response<Body>) -> Result<Response<Body>, Error> {
+    if self.0.is_empty() || response.status() != StatusCode::OK {
+      return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    let Ok(Value::Object(mut output)) = serde_json::from_slice::<Value>(&body) else {
+      return Ok(Response::from_parts(parts, Body::from(body)));
+    };
+
+    if !self.filter(&mut output) {
+      return Ok(Response::from_parts(parts, Body::from(body)));
+    }
+
+    Ok(Response::from_parts(
+      parts,
+      Body::from(Value::Object(output).to_string()),
+    ))
+  }
+
+  // returns whether anything was dropped
+  fn filter(&self, output: &mut Map<String, Value>) -> bool {
+    let Some(Value::Array(warnings)) = output.get_mut("warnings") else {
+      return false;
+    };
+
+    let len = warnings.len();
+    warnings.retain(|warning| {
+      serde_json::from_value::<WarningCode>(warning["code"].clone())
+        .map_or(true, |code| !self.0.contains(&code))
+    });
+    if warnings.len() == len {
+      return false;
+    }
+
+    if warnings.is_empty() {
+      output.remove("warnings");
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn unknown_codes_are_rejected() {
+    assert!(DisabledWarnings::new(&["index_lag".into()]).is_ok());
+    assert!(DisabledWarnings::new(&["index-lag".into()]).is_err());
+  }
+
+  #[test]
+  fn disabled_warnings_are_dropped() {
+    let disabled = DisabledWarnings::new(&["index_lag".into()]).unwrap();
+
+    let mut output = json!({
+      "network_fee": 200,
+      "warnings": [
+        {"code": "index_lag", "message": "", "data": null},
+        {"code": "large_change", "message": "", "data": null},
+      ],
+    });
+    assert!(disabled.filter(output.as_object_mut().unwrap()));
+    assert_eq!(
+      output["warnings"],
+      json!([{"code": "large_change", "message": "", "data": null}])
+    );
+
+    let mut output = json!({
+      "network_fee": 200,
+      "warnings": [{"code": "index_lag", "message": "", "data": null}],
+    });
+    assert!(disabled.filter(output.as_object_mut().unwrap()));
+    assert_eq!(output, json!({"network_fee": 200}));
+
+    let mut output = json!({"network_fee": 200});
+    assert!(!disabled.filter(output.as_object_mut().unwrap()));
+  }
+}
@@ -0,0 +1,438 @@
+use anyhow::{anyhow, Error};
+use base64::Engine;
+use bitcoin::hashes::{sha1, Hash, HashEngine};
+use bitcoin::{BlockHash, Txid};
+use bitcoincore_rpc::RpcApi;
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use ord::options::Options;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::{task, time};
+use tracing::{info, warn};
+
+// RFC 6455, section 1.3
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+/// Most txids a single connection may watch at once.
+pub const MAX_TXIDS: usize = 100;
+
+// subscriptions are small, anything bigger is not one
+const MAX_MESSAGE: u64 = 64 * 1024;
+
+/// How often bitcoind is asked about the txids still being watched.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn is_upgrade(req: &Request<Body>) -> bool {
+  req.method() == Method::GET && req.uri().path() == "/ws"
+}
+
+/// Answers a websocket handshake on /ws and, once the connection is
+/// upgraded, serves its subscriptions until the client goes away.
+pub fn upgrade(mut req: Request<Body>, options: Options) -> Response<Body> {
+  let header_contains = |name: header::HeaderName, token: &str| {
+    req
+      .headers()
+      .get(name)
+      .and_then(|value| value.to_str().ok())
+      .map_or(false, |value| {
+        value
+          .split(',')
+          .any(|value| value.trim().eq_ignore_ascii_case(token))
+      })
+  };
+  if !header_contains(header::CONNECTION, "upgrade")
+    || !header_contains(header::UPGRADE, "websocket")
+  {
+    return Response::builder()
+      .status(StatusCode::BAD_REQUEST)
+      .body(Body::from("Expected a websocket upgrade"))
+      .unwrap();
+  }
+  if req
+    .headers()
+    .get(header::SEC_WEBSOCKET_VERSION)
+    .map_or(true, |version| version != "13")
+  {
+    return Response::builder()
+      .status(StatusCode::UPGRADE_REQUIRED)
+      .header(header::SEC_WEBSOCKET_VERSION, "13")
+      .body(Body::from("Unsupported websocket version"))
+      .unwrap();
+  }
+  let Some(key) = req.headers().get(header::SEC_WEBSOCKET_KEY) else {
+    return Response::builder()
+      .status(StatusCode::BAD_REQUEST)
+      .body(Body::from("Missing Sec-WebSocket-Key"))
+      .unwrap();
+  };
+  let accept = accept_key(key.as_bytes());
+
+  let on_upgrade = hyper::upgrade::on(&mut req);
+  task::spawn(async move {
+    match on_upgrade.await {
+      Ok(upgraded) => {
+        info!("Websocket opened");
+        match serve(upgraded, options, POLL_INTERVAL).await {
+          Ok(()) => info!("Websocket closed"),
+          Err(err) => warn!(error = %err, "Websocket failed"),
+        }
+      }
+      Err(err) => warn!(error = %err, "Websocket upgrade failed"),
+    }
+  });
+
+  Response::builder()
+    .status(StatusCode::SWITCHING_PROTOCOLS)
+    .header(header::CONNECTION, "Upgrade")
+    .header(header::UPGRADE, "websocket")
+    .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+    .body(Body::empty())
+    .unwrap()
+}
+
+fn accept_key(key: &[u8]) -> String {
+  let mut engine = sha1::Hash::engine();
+  engine.input(key);
+  engine.input(GUID.as_bytes());
+  base64::engine::general_purpose::STANDARD.encode(sha1::Hash::from_engine(engine).into_inner())
+}
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+  opcode: u8,
+  payload: Vec<u8>,
+}
+
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Frame, Error> {
+  let mut head = [0; 2];
+  reader.read_exact(&mut head).await?;
+  if head[0] & 0x80 == 0 {
+    return Err(anyhow!("fragmented websocket messages are not supported"));
+  }
+  if head[1] & 0x80 == 0 {
+    return Err(anyhow!("client websocket frames must be masked"));
+  }
+  let len = match head[1] & 0x7f {
+    126 => {
+      let mut len = [0; 2];
+      reader.read_exact(&mut len).await?;
+      u64::from(u16::from_be_bytes(len))
+    }
+    127 => {
+      let mut len = [0; 8];
+      reader.read_exact(&mut len).await?;
+      u64::from_be_bytes(len)
+    }
+    len => u64::from(len),
+  };
+  if len > MAX_MESSAGE {
+    return Err(anyhow!(
+      "websocket message of {len} bytes exceeds {MAX_MESSAGE} byte limit"
+    ));
+  }
+
+  let mut mask = [0; 4];
+  reader.read_exact(&mut mask).await?;
+  let mut payload = vec![0; usize::try_from(len)?];
+  reader.read_exact(&mut payload).await?;
+  for (i, byte) in payload.iter_mut().enumerate() {
+    *byte ^= mask[i % 4];
+  }
+
+  Ok(Frame {
+    opcode: head[0] & 0x0f,
+    payload,
+  })
+}
+
+// server frames are sent whole and unmasked
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+  let mut frame = vec![0x80 | opcode];
+  match payload.len() {
+    len if len < 126 => frame.push(len as u8),
+    len if len <= usize::from(u16::MAX) => {
+      frame.push(126);
+      frame.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    len => {
+      frame.push(127);
+      frame.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+  }
+  frame.extend_from_slice(payload);
+  frame
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Status {
+  Unseen,
+  Mempool,
+  Confirmed,
+}
+
+#[derive(Deserialize)]
+struct Subscribe {
+  subscribe: Vec<Txid>,
+}
+
+/// Adds the txids of a `{"subscribe": [<TXID>, ...]}` message, all or none,
+/// and returns the reply.
+fn subscribe(subscriptions: &mut BTreeMap<Txid, Status>, message: &[u8]) -> Value {
+  let request = match serde_json::from_slice::<Subscribe>(message) {
+    Ok(request) => request,
+    Err(err) => return json!({ "error": format!("invalid subscription: {err}") }),
+  };
+
+  let added = request
+    .subscribe
+    .iter()
+    .filter(|txid| !subscriptions.contains_key(*txid))
+    .collect::<BTreeSet<&Txid>>()
+    .len();
+  if subscriptions.len() + added > MAX_TXIDS {
+    return json!({ "error": format!("at most {MAX_TXIDS} txids may be watched per connection") });
+  }
+
+  for txid in request.subscribe {
+    subscriptions.entry(txid).or_insert(Status::Unseen);
+  }
+  json!({ "subscribed": subscriptions.keys().collect::<Vec<&Txid>>() })
+}
+
+// the txids whose status changed since the last poll, those bitcoind does
+// not know about yet are left unseen
+fn poll(
+  options: &Options,
+  pending: Vec<(Txid, Status)>,
+) -> Result<Vec<(Txid, Status, Option<BlockHash>)>, Error> {
+  let client = options.bitcoin_rpc_client()?;
+  let mut changes = Vec::new();
+  for (txid, status) in pending {
+    let Ok(info) = client.get_raw_transaction_info(&txid, None) else {
+      continue;
+    };
+    let current = match info.blockhash {
+      Some(_) => Status::Confirmed,
+      None => Status::Mempool,
+    };
+    if current != status {
+      changes.push((txid, current, info.blockhash));
+    }
+  }
+  Ok(changes)
+}
+
+fn notification(txid: Txid, status: Status, blockhash: Option<BlockHash>) -> Value {
+  match status {
+    Status::Unseen => json!({ "txid": txid, "status": "unseen" }),
+    Status::Mempool => json!({ "txid": txid, "status": "mempool" }),
+    Status::Confirmed => json!({ "txid": txid, "status": "confirmed", "blockhash": blockhash }),
+  }
+}
+
+/// Reads subscriptions from `stream` and pushes a notification when a
+/// watched txid enters the mempool and when it confirms, after which it is
+/// no longer watched. Everything is dropped with the connection.
+async fn serve<S>(stream: S, options: Options, interval: Duration) -> Result<(), Error>
+where
+  S: AsyncRead + AsyncWrite + Send + 'static,
+{
+  let (mut reader, mut writer) = tokio::io::split(stream);
+
+  // frames are read on their own, a partly read frame would be lost if
+  // the read was cancelled by a poll
+  let (frames, mut incoming) = mpsc::channel(16);
+  let reader = task::spawn(async move {
+    loop {
+      let frame = read_frame(&mut reader).await;
+      let failed = frame.is_err();
+      if frames.send(frame).await.is_err() || failed {
+        break;
+      }
+    }
+  });
+
+  let mut subscriptions = BTreeMap::new();
+  let mut ticks = time::interval_at(time::Instant::now() + interval, interval);
+  let result: Result<(), Error> = async {
+    loop {
+      tokio::select! {
+        frame = incoming.recv() => {
+          let frame = match frame {
+            Some(Ok(frame)) => frame,
+            Some(Err(err)) => return Err(err),
+            None => return Ok(()),
+          };
+          match frame.opcode {
+            TEXT => {
+              let reply = subscribe(&mut subscriptions, &frame.payload);
+              writer.write_all(&self::frame(TEXT, reply.to_string().as_bytes())).await?;
+            }
+            PING => writer.write_all(&self::frame(PONG, &frame.payload)).await?,
+            CLOSE => {
+              writer.write_all(&self::frame(CLOSE, &[])).await?;
+              return Ok(());
+            }
+            _ => {}
+          }
+        }
+        _ = ticks.tick(), if !subscriptions.is_empty() => {
+          let pending = subscriptions
+            .iter()
+            .map(|(txid, status)| (*txid, *status))
+            .collect::<Vec<(Txid, Status)>>();
+          let options = options.clone();
+          // bitcoind being away is no reason to drop the subscriptions
+          let changes = task::spawn_blocking(move || poll(&options, pending))
+            .await?
+            .unwrap_or_else(|err| {
+              warn!(error = %err, "Websocket poll failed");
+              Vec::new()
+            });
+          for (txid, status, blockhash) in changes {
+            if status == Status::Confirmed {
+              subscriptions.remove(&txid);
+            } else {
+              subscriptions.insert(txid, status);
+            }
+            let message = notification(txid, status, blockhash).to_string();
+            writer.write_all(&self::frame(TEXT, message.as_bytes())).await?;
+          }
+        }
+      }
+    }
+  }
+  .await;
+
+  reader.abort();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn txid(n: u8) -> Txid {
+    Txid::from_inner([n; 32])
+  }
+
+  // what a client sends, masked
+  fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [1, 2, 3, 4];
+    let mut frame = super::frame(opcode, payload);
+    let start = frame.len() - payload.len();
+    frame[1] |= 0x80;
+    for (i, byte) in frame[start..].iter_mut().enumerate() {
+      *byte ^= mask[i % 4];
+    }
+    frame.splice(start..start, mask);
+    frame
+  }
+
+  // what a server sends, unmasked
+  async fn server_frame(reader: &mut (impl AsyncRead + Unpin)) -> Frame {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).await.unwrap();
+    let mut payload = vec![0; usize::from(head[1] & 0x7f)];
+    reader.read_exact(&mut payload).await.unwrap();
+    Frame {
+      opcode: head[0] & 0x0f,
+      payload,
+    }
+  }
+
+  #[test]
+  fn handshake_accept_key() {
+    assert_eq!(
+      accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+      "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+  }
+
+  #[tokio::test]
+  async fn frames_round_trip() {
+    for len in [0, 125, 126, 65_535] {
+      let payload = vec![7; len];
+      assert_eq!(
+        read_frame(&mut client_frame(TEXT, &payload).as_slice())
+          .await
+          .unwrap(),
+        Frame {
+          opcode: TEXT,
+          payload
+        }
+      );
+    }
+  }
+
+  #[tokio::test]
+  async fn unmasked_and_oversized_frames_are_refused() {
+    assert!(read_frame(&mut frame(TEXT, b"{}").as_slice())
+      .await
+      .is_err());
+    assert!(
+      read_frame(&mut client_frame(TEXT, &vec![0; 65_537]).as_slice())
+        .await
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn subscriptions_are_capped() {
+    let mut subscriptions = BTreeMap::new();
+    let txids = (0..MAX_TXIDS as u8).map(txid).collect::<Vec<Txid>>();
+
+    let reply = subscribe(
+      &mut subscriptions,
+      json!({ "subscribe": txids }).to_string().as_bytes(),
+    );
+    assert_eq!(reply["subscribed"].as_array().unwrap().len(), MAX_TXIDS);
+
+    // watching again is free, one more is not
+    let reply = subscribe(
+      &mut subscriptions,
+      json!({ "subscribe": [txid(0)] }).to_string().as_bytes(),
+    );
+    assert!(reply.get("error").is_none());
+    let reply = subscribe(
+      &mut subscriptions,
+      json!({ "subscribe": [txid(200)] }).to_string().as_bytes(),
+    );
+    assert!(reply.get("error").is_some());
+    assert_eq!(subscriptions.len(), MAX_TXIDS);
+
+    assert!(subscribe(&mut subscriptions, b"{}").get("error").is_some());
+  }
+
+  #[tokio::test]
+  async fn subscribe_then_close() {
+    let (client, server) = tokio::io::duplex(1024);
+    let serving = task::spawn(serve(server, Options::default(), Duration::from_secs(3600)));
+    let (mut reader, mut writer) = tokio::io::split(client);
+
+    let subscription = json!({ "subscribe": [txid(1)] }).to_string();
+    writer
+      .write_all(&client_frame(TEXT, subscription.as_bytes()))
+      .await
+      .unwrap();
+    let reply = server_frame(&mut reader).await;
+    assert_eq!(reply.opcode, TEXT);
+    assert_eq!(
+      serde_json::from_slice::<Value>(&reply.payload).unwrap(),
+      json!({ "subscribed": [txid(1)] })
+    );
+
+    writer.write_all(&client_frame(CLOSE, &[])).await.unwrap();
+    assert_eq!(server_frame(&mut reader).await.opcode, CLOSE);
+    serving.await.unwrap().unwrap();
+  }
+}
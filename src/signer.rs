@@ -0,0 +1,379 @@
+use {
+  super::*,
+  crate::subcommand::wallet::broadcast::{self, Broadcast},
+  base64::Engine,
+  bitcoin::{
+    consensus::encode::{deserialize, serialize, serialize_hex},
+    hashes::hex::FromHex,
+    psbt::Psbt,
+    Witness,
+  },
+  reqwest::Url,
+  std::io::Read,
+};
+
+/// Webhooks the server may hand a PSBT to for signing, and the limits
+/// applied when calling them.
+#[derive(Debug)]
+pub struct SignerWebhooks {
+  domains: Vec<String>,
+  timeout: Duration,
+  max_response_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SignerRequest {
+  psbt: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignerResponse {
+  psbt: Option<String>,
+  tx: Option<String>,
+}
+
+/// What happened after the build, every step that succeeded leaves its
+/// artifact so a failed delegation can be finished by hand.
+#[derive(Debug, Default, Serialize)]
+pub struct Delegation {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub signed: Option<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub txids: Vec<Txid>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub broadcast: Option<broadcast::Output>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+impl SignerWebhooks {
+  pub fn new(domains: Vec<String>, timeout: Duration, max_response_bytes: u64) -> Self {
+    Self {
+      domains: domains
+        .into_iter()
+        .map(|domain| domain.to_lowercase())
+        .collect(),
+      timeout,
+      max_response_bytes,
+    }
+  }
+
+  /// Webhooks must be https on an allow-listed domain or one of its
+  /// subdomains.
+  pub fn check(&self, webhook: &str) -> Result<Url> {
+    if self.domains.is_empty() {
+      bail!("signer webhooks are not enabled on this server");
+    }
+
+    let url = Url::parse(webhook).map_err(|err| anyhow!("invalid signer webhook: {err}"))?;
+
+    if url.scheme() != "https" {
+      bail!("signer webhook `{webhook}` must use https");
+    }
+
+    let host = url
+      .host_str()
+      .ok_or_else(|| anyhow!("signer webhook `{webhook}` has no host"))?
+      .to_lowercase();
+
+    if !self
+      .domains
+      .iter()
+      .any(|domain| host == *domain || host.ends_with(&format!(".{domain}")))
+    {
+      bail!("signer webhook domain `{host}` is not allow-listed");
+    }
+
+    Ok(url)
+  }
+
+  /// Has `psbt_hex` signed by `webhook`, then broadcasts it followed by
+  /// `children`, which spend its outputs.
+  pub fn delegate(
+    &self,
+    webhook: Url,
+    options: Options,
+    psbt_hex: &str,
+    children: &[String],
+  ) -> Delegation {
+    let mut delegation = Delegation::default();
+    if let Err(err) = self.delegate_inner(webhook, options, psbt_hex, children, &mut delegation) {
+      delegation.error = Some(err.to_string());
+    }
+    delegation
+  }
+
+  fn delegate_inner(
+    &self,
+    webhook: Url,
+    options: Options,
+    psbt_hex: &str,
+    children: &[String],
+    delegation: &mut Delegation,
+  ) -> Result {
+    let psbt: Psbt = deserialize(&Vec::from_hex(psbt_hex)?)?;
+
+    let signed = self.sign(webhook, &psbt)?;
+    delegation.signed = Some(serialize_hex(&signed));
+
+    let output = Broadcast {
+      tx: std::iter::once(serialize_hex(&signed))
+        .chain(children.iter().cloned())
+        .collect(),
+      package: false,
+    }
+    .build(options)?;
+
+    let rejected = output
+      .status
+      .iter()
+      .find(|status| !status.accepted)
+      .map(|status| {
+        format!(
+          "broadcast of {} rejected: {}",
+          status.txid,
+          status.error.as_deref().unwrap_or("unknown error")
+        )
+      });
+
+    delegation.txids = output.order.clone();
+    delegation.broadcast = Some(output);
+
+    match rejected {
+      Some(rejected) => Err(anyhow!(rejected)),
+      None => Ok(()),
+    }
+  }
+
+  fn sign(&self, webhook: Url, psbt: &Psbt) -> Result<Transaction> {
+    let client = reqwest::blocking::Client::builder()
+      .timeout(self.timeout)
+      .redirect(reqwest::redirect::Policy::none())
+      .build()?;
+
+    let response = client
+      .post(webhook)
+      .json(&SignerRequest {
+        psbt: base64::engine::general_purpose::STANDARD.encode(serialize(psbt)),
+      })
+      .send()
+      .map_err(|err| anyhow!("signer webhook request failed: {err}"))?;
+
+    if !response.status().is_success() {
+      bail!("signer webhook returned {}", response.status());
+    }
+
+    if response
+      .content_length()
+      .map_or(false, |len| len > self.max_response_bytes)
+    {
+      bail!(
+        "signer webhook response exceeds {} bytes",
+        self.max_response_bytes
+      );
+    }
+
+    let mut body = Vec::new();
+    response
+      .take(self.max_response_bytes + 1)
+      .read_to_end(&mut body)?;
+    if body.len() as u64 > self.max_response_bytes {
+      bail!(
+        "signer webhook response exceeds {} bytes",
+        self.max_response_bytes
+      );
+    }
+
+    Self::signed_transaction(&psbt.unsigned_tx, serde_json::from_slice(&body)?)
+  }
+
+  fn signed_transaction(unsigned: &Transaction, response: SignerResponse) -> Result<Transaction> {
+    let signed = match (response.psbt, response.tx) {
+      (Some(psbt), None) => {
+        let psbt: Psbt = deserialize(
+          &base64::engine::general_purpose::STANDARD
+            .decode(psbt)
+            .map_err(|err| anyhow!("signer returned invalid psbt: {err}"))?,
+        )?;
+        for (i, input) in psbt.inputs.iter().enumerate() {
+          if input.final_script_witness.is_none() && input.final_script_sig.is_none() {
+            bail!("signer returned psbt with input {i} not finalized");
+          }
+        }
+        psbt.extract_tx()
+      }
+      (None, Some(tx)) => deserialize(&Vec::from_hex(&tx)?)?,
+      _ => bail!("signer response must contain exactly one of `psbt` or `tx`"),
+    };
+
+    Self::check_complete(unsigned, &signed)?;
+
+    Ok(signed)
+  }
+
+  // the signer may only add signatures, everything else must be as built
+  fn check_complete(unsigned: &Transaction, signed: &Transaction) -> Result {
+    let mut stripped = signed.clone();
+    for input in &mut stripped.input {
+      input.script_sig = Script::new();
+      input.witness = Witness::new();
+    }
+
+    if stripped != *unsigned {
+      bail!("signer returned a transaction that differs from the one built");
+    }
+
+    for (i, input) in signed.input.iter().enumerate() {
+      if input.witness.is_empty() && input.script_sig.is_empty() {
+        bail!("signer left input {i} unsigned");
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, bitcoin::PackedLockTime};
+
+  fn webhooks() -> SignerWebhooks {
+    SignerWebhooks::new(
+      vec!["Signer.example.com".into()],
+      Duration::from_secs(1),
+      1000,
+    )
+  }
+
+  fn unsigned() -> Transaction {
+    Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+      output: vec![tx_out(1000, recipient())],
+    }
+  }
+
+  fn signed() -> Transaction {
+    let mut tx = unsigned();
+    for input in &mut tx.input {
+      input.witness = Witness::from_vec(vec![vec![1; 64]]);
+    }
+    tx
+  }
+
+  #[test]
+  fn allow_listed_domains() {
+    let webhooks = webhooks();
+    assert!(webhooks.check("https://signer.example.com/sign").is_ok());
+    assert!(webhooks
+      .check("https://hsm.signer.example.com/sign")
+      .is_ok());
+    assert_eq!(
+      webhooks
+        .check("http://signer.example.com/sign")
+        .unwrap_err()
+        .to_string(),
+      "signer webhook `http://signer.example.com/sign` must use https"
+    );
+    assert_eq!(
+      webhooks
+        .check("https://evilsigner.example.com/sign")
+        .unwrap_err()
+        .to_string(),
+      "signer webhook domain `evilsigner.example.com` is not allow-listed"
+    );
+    assert!(webhooks.check("https://example.com/sign").is_err());
+    assert!(webhooks.check("not a url").is_err());
+  }
+
+  #[test]
+  fn disabled_without_domains() {
+    assert_eq!(
+      SignerWebhooks::new(Vec::new(), Duration::from_secs(1), 1000)
+        .check("https://signer.example.com")
+        .unwrap_err()
+        .to_string(),
+      "signer webhooks are not enabled on this server"
+    );
+  }
+
+  #[test]
+  fn complete_signatures() {
+    assert!(SignerWebhooks::check_complete(&unsigned(), &signed()).is_ok());
+
+    let mut partial = signed();
+    partial.input[1].witness = Witness::new();
+    assert_eq!(
+      SignerWebhooks::check_complete(&unsigned(), &partial)
+        .unwrap_err()
+        .to_string(),
+      "signer left input 1 unsigned"
+    );
+
+    let mut tampered = signed();
+    tampered.output[0].value = 2000;
+    assert!(SignerWebhooks::check_complete(&unsigned(), &tampered).is_err());
+  }
+
+  #[test]
+  fn signed_raw_transaction() {
+    assert_eq!(
+      SignerWebhooks::signed_transaction(
+        &unsigned(),
+        SignerResponse {
+          psbt: None,
+          tx: Some(serialize_hex(&signed())),
+        }
+      )
+      .unwrap(),
+      signed()
+    );
+  }
+
+  #[test]
+  fn signed_psbt() {
+    let mut psbt = Psbt::from_unsigned_tx(unsigned()).unwrap();
+    for input in &mut psbt.inputs {
+      input.final_script_witness = Some(Witness::from_vec(vec![vec![1; 64]]));
+    }
+
+    assert_eq!(
+      SignerWebhooks::signed_transaction(
+        &unsigned(),
+        SignerResponse {
+          psbt: Some(base64::engine::general_purpose::STANDARD.encode(serialize(&psbt))),
+          tx: None,
+        }
+      )
+      .unwrap(),
+      signed()
+    );
+
+    psbt.inputs[0].final_script_witness = None;
+    assert_eq!(
+      SignerWebhooks::signed_transaction(
+        &unsigned(),
+        SignerResponse {
+          psbt: Some(base64::engine::general_purpose::STANDARD.encode(serialize(&psbt))),
+          tx: None,
+        }
+      )
+      .unwrap_err()
+      .to_string(),
+      "signer returned psbt with input 0 not finalized"
+    );
+  }
+
+  #[test]
+  fn response_needs_one_transaction() {
+    assert!(SignerWebhooks::signed_transaction(
+      &unsigned(),
+      SignerResponse {
+        psbt: None,
+        tx: None,
+      }
+    )
+    .is_err());
+  }
+}
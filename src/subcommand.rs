@@ -8,9 +8,12 @@ pub mod list;
 pub mod parse;
 mod preview;
 mod server;
+pub mod snapshot;
 pub mod subsidy;
 pub mod supply;
 pub mod traits;
+pub mod verify_manifest;
+pub mod verify_transfer_proof;
 pub mod wallet;
 
 fn print_json(output: impl Serialize) -> Result {
@@ -39,10 +42,16 @@ pub(crate) enum Subcommand {
   Subsidy(subsidy::Subsidy),
   #[clap(about = "Run the explorer server")]
   Server(server::Server),
+  #[clap(subcommand, about = "Export or import index snapshots")]
+  Snapshot(snapshot::Snapshot),
   #[clap(about = "Display Bitcoin supply information")]
   Supply,
   #[clap(about = "Display satoshi traits")]
   Traits(traits::Traits),
+  #[clap(about = "Check the HMAC of a build manifest")]
+  VerifyManifest(verify_manifest::VerifyManifest),
+  #[clap(about = "Check that a transfer PSBT moves inscriptions where its proofs claim")]
+  VerifyTransferProof(verify_transfer_proof::VerifyTransferProof),
   #[clap(subcommand, about = "Wallet commands")]
   Wallet(wallet::Wallet),
 }
@@ -64,8 +73,11 @@ impl Subcommand {
         LISTENERS.lock().unwrap().push(handle.clone());
         server.run(options, index, handle)
       }
+      Self::Snapshot(snapshot) => snapshot.run(options),
       Self::Supply => supply::run(),
       Self::Traits(traits) => traits.run(),
+      Self::VerifyManifest(verify_manifest) => verify_manifest.run(),
+      Self::VerifyTransferProof(verify_transfer_proof) => verify_transfer_proof.run(),
       Self::Wallet(wallet) => wallet.run(options),
     }
   }
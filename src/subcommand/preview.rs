@@ -44,7 +44,7 @@ impl Preview {
       chain_argument: Chain::Regtest,
       bitcoin_data_dir: Some(bitcoin_data_dir),
       data_dir: Some(tmpdir.path().into()),
-      rpc_url: Some(format!("127.0.0.1:{rpc_port}")),
+      rpc_url: vec![format!("127.0.0.1:{rpc_port}")],
       index_sats: true,
       ..Options::default()
     };
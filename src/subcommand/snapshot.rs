@@ -0,0 +1,38 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) enum Snapshot {
+  #[clap(about = "Write the index to a portable snapshot")]
+  Export {
+    #[clap(long, help = "Write snapshot to <OUTPUT>.")]
+    output: PathBuf,
+  },
+  #[clap(about = "Restore the index from a snapshot into a fresh data dir")]
+  Import {
+    #[clap(long, help = "Read snapshot from <INPUT>.")]
+    input: PathBuf,
+  },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Output {
+  pub height: u64,
+  pub path: PathBuf,
+}
+
+impl Snapshot {
+  pub(crate) fn run(self, options: Options) -> Result {
+    let output = match self {
+      Self::Export { output } => Output {
+        height: Index::open(&options)?.export_snapshot(&output)?,
+        path: output,
+      },
+      Self::Import { input } => Output {
+        height: Index::import_snapshot(&options, &input)?,
+        path: input,
+      },
+    };
+
+    print_json(output)
+  }
+}
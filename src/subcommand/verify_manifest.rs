@@ -0,0 +1,94 @@
+use {super::*, crate::manifest::Manifest};
+
+#[derive(Debug, Parser)]
+pub(crate) struct VerifyManifest {
+  #[clap(long, help = "Verify with HMAC key <KEY>.")]
+  key: String,
+  #[clap(help = "Read a manifest, or a build response containing one, from <FILE>.")]
+  file: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+  pub method: String,
+  pub request_sha256: String,
+  pub txids: Vec<Txid>,
+  pub timestamp: u64,
+}
+
+impl VerifyManifest {
+  pub(crate) fn run(self) -> Result {
+    let manifest = Self::read(&fs::read_to_string(&self.file)?)?;
+
+    if !manifest.verify(self.key.as_bytes())? {
+      bail!("manifest HMAC does not match");
+    }
+
+    print_json(Output {
+      method: manifest.body.method,
+      request_sha256: manifest.body.request_sha256,
+      txids: manifest
+        .body
+        .transactions
+        .iter()
+        .map(|transaction| transaction.txid)
+        .collect(),
+      timestamp: manifest.body.timestamp,
+    })?;
+
+    Ok(())
+  }
+
+  fn read(json: &str) -> Result<Manifest> {
+    let mut value = serde_json::from_str::<serde_json::Value>(json)?;
+    if let Some(manifest) = value.get_mut("manifest") {
+      value = manifest.take();
+    }
+    Ok(serde_json::from_value(value)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::manifest::ManifestBody,
+    bitcoin::hashes::{hex::ToHex, sha256},
+  };
+
+  fn manifest() -> Manifest {
+    Manifest::sign(
+      ManifestBody {
+        method: "mint".into(),
+        request_sha256: sha256::Hash::hash(b"{}").to_hex(),
+        transactions: Vec::new(),
+        fees: BTreeMap::new(),
+        index_height: None,
+        server_version: "0.5.1".into(),
+        timestamp: 1700000000,
+      },
+      b"key",
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn reads_manifest_or_response() {
+    let manifest = manifest();
+    assert_eq!(
+      VerifyManifest::read(&serde_json::to_string(&manifest).unwrap()).unwrap(),
+      manifest
+    );
+
+    let response = serde_json::json!({
+      "commit": "70736274ff",
+      "manifest": manifest,
+    });
+    assert_eq!(
+      VerifyManifest::read(&response.to_string()).unwrap(),
+      manifest
+    );
+
+    assert!(VerifyManifest::read("{}").is_err());
+  }
+}
@@ -0,0 +1,112 @@
+use {
+  super::*,
+  crate::subcommand::wallet::transfer::{verify_transfer_proof, TransferProof},
+  bitcoin::psbt::Psbt,
+};
+
+#[derive(Debug, Parser)]
+pub(crate) struct VerifyTransferProof {
+  #[clap(
+    long,
+    help = "Verify hex encoded <PSBT> instead of the one in the response."
+  )]
+  psbt: Option<String>,
+  #[clap(
+    help = "Read a proof, a list of proofs, or a transfer response containing them, from <FILE>."
+  )]
+  file: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+  pub txid: Txid,
+  pub verified: Vec<TransferProof>,
+}
+
+impl VerifyTransferProof {
+  pub(crate) fn run(self) -> Result {
+    let (transaction, proofs) = Self::read(&fs::read_to_string(&self.file)?)?;
+
+    let psbt = self
+      .psbt
+      .or(transaction)
+      .ok_or_else(|| anyhow!("no psbt given and none in {}", self.file.display()))?;
+    let psbt: Psbt = consensus::deserialize(&hex::decode(psbt.trim())?)?;
+
+    if proofs.is_empty() {
+      bail!("no proofs in {}", self.file.display());
+    }
+
+    for proof in &proofs {
+      verify_transfer_proof(&psbt, proof)
+        .with_context(|| format!("proof for {} does not hold", proof.inscription_id))?;
+    }
+
+    print_json(Output {
+      txid: psbt.unsigned_tx.txid(),
+      verified: proofs,
+    })?;
+
+    Ok(())
+  }
+
+  fn read(json: &str) -> Result<(Option<String>, Vec<TransferProof>)> {
+    let value = serde_json::from_str::<serde_json::Value>(json)?;
+    if value.is_array() {
+      return Ok((None, serde_json::from_value(value)?));
+    }
+    if let Some(proofs) = value.get("proofs") {
+      return Ok((
+        value
+          .get("transaction")
+          .and_then(|transaction| transaction.as_str())
+          .map(str::to_owned),
+        serde_json::from_value(proofs.clone())?,
+      ));
+    }
+    Ok((None, vec![serde_json::from_value(value)?]))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn proof() -> TransferProof {
+    TransferProof {
+      inscription_id: inscription_id(1),
+      satpoint: satpoint(1, 0),
+      input: 0,
+      output: 0,
+      output_value: 546,
+      destination: recipient(),
+      index_height: Some(800_000),
+    }
+  }
+
+  #[test]
+  fn reads_proofs_or_response() {
+    let proof = proof();
+
+    assert_eq!(
+      VerifyTransferProof::read(&serde_json::to_string(&proof).unwrap()).unwrap(),
+      (None, vec![proof.clone()])
+    );
+    assert_eq!(
+      VerifyTransferProof::read(&serde_json::to_string(&[&proof, &proof]).unwrap()).unwrap(),
+      (None, vec![proof.clone(), proof.clone()])
+    );
+
+    let response = serde_json::json!({
+      "transaction": "70736274ff",
+      "network_fee": 200,
+      "proofs": [proof],
+    });
+    assert_eq!(
+      VerifyTransferProof::read(&response.to_string()).unwrap(),
+      (Some("70736274ff".into()), vec![proof])
+    );
+
+    assert!(VerifyTransferProof::read("{}").is_err());
+  }
+}
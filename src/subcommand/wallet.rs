@@ -1,42 +1,68 @@
 use {
   super::*,
+  crate::index::MysqlDatabase,
   bitcoin::secp256k1::{
     rand::{self, RngCore},
     All, Secp256k1,
   },
   bitcoin::{
+    blockdata::locktime::{PackedLockTime, LOCK_TIME_THRESHOLD},
+    util::address::Payload,
     util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, Fingerprint},
-    Network,
+    AddressType, Network,
   },
   bitcoincore_rpc::bitcoincore_rpc_json::{ImportDescriptors, Timestamp},
+  error::BuildError,
   fee_rate::FeeRate,
   miniscript::descriptor::{Descriptor, DescriptorSecretKey, DescriptorXKey, Wildcard},
   transaction_builder::TransactionBuilder,
 };
 
+pub mod accelerate;
+pub mod address_utxos;
+pub mod airdrop;
 pub mod balance;
+pub mod broadcast;
+pub mod bump_fee;
+pub mod bump_reveal;
 pub mod cancel;
 pub mod cardinals;
 pub mod create;
+pub mod decode_reveal;
+pub mod error;
+pub mod fee_rates;
+pub mod funding;
 pub(crate) mod inscribe;
 pub mod inscriptions;
 pub mod mint;
 pub mod mints;
 pub mod outputs;
 pub mod receive;
+pub mod recover;
 mod restore;
+pub mod resume_reveal;
+pub mod reveal_fee_check;
 pub mod sats;
+pub mod script_path;
 pub mod send;
 pub(crate) mod transaction_builder;
 pub mod transactions;
 pub mod transfer;
+pub mod unsafe_destination;
+pub mod warning;
 
 #[derive(Debug, Parser)]
 pub(crate) enum Wallet {
   #[clap(about = "Get wallet balance")]
   Balance,
+  #[clap(about = "Broadcast transactions in dependency order")]
+  Broadcast(broadcast::Broadcast),
   #[clap(about = "Create new wallet")]
   Create(create::Create),
+  #[clap(about = "Decode the inscription envelope of a reveal transaction")]
+  DecodeReveal(decode_reveal::DecodeReveal),
+  #[clap(about = "Recommend fee rates from the node's estimates")]
+  FeeRates(fee_rates::FeeRates),
   #[clap(about = "Create inscription")]
   Inscribe(inscribe::Inscribe),
   #[clap(about = "Transfer inscription|amount|satpoint")]
@@ -51,6 +77,10 @@ pub(crate) enum Wallet {
   Inscriptions,
   #[clap(about = "Generate receive address")]
   Receive,
+  #[clap(about = "Sweep a commit output back with its recovery key")]
+  Recover(recover::Recover),
+  #[clap(about = "Check whether a reveal still pays enough to confirm")]
+  RevealFeeCheck(reveal_fee_check::RevealFeeCheck),
   #[clap(about = "Restore wallet")]
   Restore(restore::Restore),
   #[clap(about = "List wallet satoshis")]
@@ -69,7 +99,10 @@ impl Wallet {
   pub(crate) fn run(self, options: Options) -> Result {
     match self {
       Self::Balance => balance::run(options),
+      Self::Broadcast(broadcast) => broadcast.run(options),
       Self::Create(create) => create.run(options),
+      Self::DecodeReveal(decode_reveal) => decode_reveal.run(options),
+      Self::FeeRates(fee_rates) => fee_rates.run(options),
       Self::Inscribe(inscribe) => inscribe.run(options),
       Self::Transfer(transfer) => transfer.run(options),
       Self::Mint(mint) => mint.run(options),
@@ -77,6 +110,8 @@ impl Wallet {
       Self::Cancel(cancel) => cancel.run(options),
       Self::Inscriptions => inscriptions::run(options),
       Self::Receive => receive::run(options),
+      Self::Recover(recover) => recover.run(options),
+      Self::RevealFeeCheck(reveal_fee_check) => reveal_fee_check.run(options),
       Self::Restore(restore) => restore.run(options),
       Self::Sats(sats) => sats.run(options),
       Self::Send(send) => send.run(options),
@@ -87,6 +122,135 @@ impl Wallet {
   }
 }
 
+// scripts of well known burn addresses, compared by script so that every
+// network encoding of the same payload matches
+const BURN_SCRIPTS: &[&str] = &[
+  // 1BitcoinEaterAddressDontSendf59kuE
+  "76a914759d6677091e973b9e9d99f19c68fbf43e3f05f988ac",
+  // 1CounterpartyXXXXXXXXXXXXXXXUWLpVr
+  "76a914818895f3dc2c178629d3d2d8fa3ec4a3f817982188ac",
+];
+
+pub(crate) fn is_burn_address(address: &Address) -> bool {
+  let script_pubkey = address.script_pubkey();
+
+  if script_pubkey.is_provably_unspendable()
+    || BURN_SCRIPTS.contains(&format!("{script_pubkey:x}").as_str())
+  {
+    return true;
+  }
+
+  // nobody knows the preimage of an all-zero hash or the key of an all-zero point
+  let program = match &address.payload {
+    Payload::PubkeyHash(hash) => hash.as_inner().as_slice(),
+    Payload::ScriptHash(hash) => hash.as_inner().as_slice(),
+    Payload::WitnessProgram { program, .. } => program.as_slice(),
+  };
+  program.iter().all(|byte| *byte == 0)
+}
+
+// change always returns to the source, so the source is held to the
+// destination policy as well
+pub(crate) fn check_source_address(source: &Address, options: &Options) -> Result<AddressType> {
+  let chain = options.chain();
+  if !source.is_valid_for_network(chain.network()) {
+    bail!("Address `{}` is not valid for {}", source, chain);
+  }
+
+  // check address types, only support p2tr and p2wpkh
+  let address_type = match source.address_type() {
+    Some(address_type @ (AddressType::P2tr | AddressType::P2wpkh)) => address_type,
+    Some(address_type) => return Err(BuildError::AddressTypeUnsupported(address_type).into()),
+    None => bail!("Address `{}` is not valid for {}", source, chain),
+  };
+
+  check_address_type(source, options)?;
+
+  Ok(address_type)
+}
+
+/// Rejects outputs to `address` unless its type is in
+/// `--allowed-destination-types`.
+pub(crate) fn check_address_type(address: &Address, options: &Options) -> Result {
+  let allowed = &options.allowed_destination_types;
+
+  match address.address_type() {
+    _ if allowed.is_empty() => Ok(()),
+    Some(address_type) if allowed.contains(&address_type) => Ok(()),
+    _ => Err(
+      BuildError::DestinationTypeNotAllowed {
+        address: address.clone(),
+        allowed: allowed.clone(),
+      }
+      .into(),
+    ),
+  }
+}
+
+pub(crate) fn check_destination_address(
+  destination: &Address,
+  options: &Options,
+  allow_burn: bool,
+) -> Result {
+  let chain = options.chain();
+  if !destination.is_valid_for_network(chain.network()) {
+    bail!("Address `{}` is not valid for {}", destination, chain);
+  }
+
+  check_address_type(destination, options)?;
+
+  if !allow_burn && is_burn_address(destination) {
+    return Err(BuildError::BurnDestination(destination.clone()).into());
+  }
+
+  Ok(())
+}
+
+pub(crate) fn check_lock_time(lock_time: u32, tip: u64) -> Result {
+  if lock_time >= LOCK_TIME_THRESHOLD {
+    return Err(BuildError::LockTimeNotHeight(lock_time).into());
+  }
+
+  if u64::from(lock_time) <= tip {
+    return Err(BuildError::LockTimeNotInFuture { lock_time, tip }.into());
+  }
+
+  Ok(())
+}
+
+/// Inscriptions held by `address`, from mysql when it is configured and
+/// otherwise from the redb index, where only `outpoints` are looked up.
+pub(crate) fn address_inscriptions<'a>(
+  index: &Index,
+  address: &Address,
+  outpoints: impl IntoIterator<Item = &'a OutPoint>,
+  mysql: Option<&MysqlDatabase>,
+) -> Result<BTreeMap<SatPoint, InscriptionId>> {
+  if let Some(mysql) = mysql {
+    tracing::info!(store = "mysql", "Get inscriptions...");
+    mysql.get_inscription_by_address(&address.to_string())
+  } else {
+    tracing::info!(store = "redb", "Get inscriptions...");
+    index.get_inscriptions_for_outpoints(outpoints)
+  }
+}
+
+// A locktime is only enforced when at least one input has a non-final
+// sequence, so inputs either signal BIP-125 or opt out with the highest
+// sequence that still keeps the locktime active.
+pub(crate) fn apply_lock_time(transaction: &mut Transaction, lock_time: Option<u32>, rbf: bool) {
+  let sequence = match (lock_time.is_some(), rbf) {
+    (_, true) => Sequence::ENABLE_RBF_NO_LOCKTIME,
+    (true, false) => Sequence::ENABLE_LOCKTIME_NO_RBF,
+    (false, false) => Sequence::MAX,
+  };
+
+  transaction.lock_time = PackedLockTime(lock_time.unwrap_or(0));
+  for input in &mut transaction.input {
+    input.sequence = sequence;
+  }
+}
+
 fn get_change_address(client: &Client) -> Result<Address> {
   client
     .call("getrawchangeaddress", &["bech32m".into()])
@@ -160,3 +324,177 @@ fn derive_and_import_descriptor(
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn burn_addresses() {
+    for address in [
+      "1BitcoinEaterAddressDontSendf59kuE",
+      "1CounterpartyXXXXXXXXXXXXXXXUWLpVr",
+      "1111111111111111111114oLvT2",
+      "bc1pqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqpqqenm",
+      "bc1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqq9e75rs",
+      "mrEqurom3cKudH7FaDrF3j1DJePLcjAU3m",
+      "msKs5wjqdcJ9ke19F6VuMSjrPX8EQZ63vX",
+      "tb1pqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqkgkkf5",
+      "tb1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqq0l98cr",
+    ] {
+      assert!(
+        is_burn_address(&address.parse().unwrap()),
+        "{address} should be a burn address"
+      );
+    }
+
+    assert!(!is_burn_address(&recipient()));
+    assert!(!is_burn_address(&change(0)));
+  }
+
+  #[test]
+  fn burn_destination_requires_allow_burn() {
+    let burn = "bc1pqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqpqqenm"
+      .parse::<Address>()
+      .unwrap();
+
+    assert_eq!(
+      check_destination_address(&burn, &options(Chain::Mainnet, &[]), false)
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::BurnDestination(burn.clone()),
+    );
+
+    check_destination_address(&burn, &options(Chain::Mainnet, &[]), true).unwrap();
+    check_destination_address(&recipient(), &options(Chain::Testnet, &[]), false).unwrap();
+  }
+
+  fn options(chain: Chain, allowed_destination_types: &[AddressType]) -> Options {
+    Options {
+      chain_argument: chain,
+      allowed_destination_types: allowed_destination_types.to_vec(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn destination_types_follow_policy() {
+    for (chain, addresses) in [
+      (
+        Chain::Mainnet,
+        [
+          "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+          "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+          "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+          "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+          "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ],
+      ),
+      (
+        Chain::Testnet,
+        [
+          "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn",
+          "2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc",
+          "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+          "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+          "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+        ],
+      ),
+    ] {
+      let restrictive = options(chain, &[AddressType::P2tr, AddressType::P2wpkh]);
+      let permissive = options(chain, &[]);
+
+      for address in addresses {
+        let address = address.parse::<Address>().unwrap();
+        let allowed = matches!(
+          address.address_type(),
+          Some(AddressType::P2tr | AddressType::P2wpkh)
+        );
+
+        check_destination_address(&address, &permissive, false).unwrap();
+        assert_eq!(
+          check_destination_address(&address, &restrictive, false).is_ok(),
+          allowed,
+          "{address}"
+        );
+        assert_eq!(
+          check_source_address(&address, &restrictive).is_ok(),
+          allowed,
+          "{address}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn destination_type_error_names_allowed_types() {
+    let address = "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"
+      .parse::<Address>()
+      .unwrap();
+
+    assert_eq!(
+      check_address_type(
+        &address,
+        &options(Chain::Mainnet, &[AddressType::P2tr, AddressType::P2wpkh])
+      )
+      .unwrap_err()
+      .to_string(),
+      "address `3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy` of type p2sh is not allowed, only p2tr, p2wpkh outputs are allowed",
+    );
+  }
+
+  #[test]
+  fn lock_time_must_be_future_height() {
+    check_lock_time(101, 100).unwrap();
+
+    assert_eq!(
+      check_lock_time(100, 100)
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::LockTimeNotInFuture {
+        lock_time: 100,
+        tip: 100
+      },
+    );
+
+    assert_eq!(
+      check_lock_time(LOCK_TIME_THRESHOLD, 100)
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::LockTimeNotHeight(LOCK_TIME_THRESHOLD),
+    );
+  }
+
+  #[test]
+  fn apply_lock_time_enforces_height() {
+    let mut transaction = Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+      output: vec![tx_out(1000, recipient())],
+    };
+
+    apply_lock_time(&mut transaction, Some(200), true);
+    assert_eq!(transaction.lock_time, PackedLockTime(200));
+    assert!(transaction.is_lock_time_enabled());
+    assert!(transaction
+      .input
+      .iter()
+      .all(|input| input.sequence.is_rbf()));
+
+    apply_lock_time(&mut transaction, Some(200), false);
+    assert_eq!(transaction.lock_time, PackedLockTime(200));
+    assert!(transaction.is_lock_time_enabled());
+    assert!(transaction
+      .input
+      .iter()
+      .all(|input| !input.sequence.is_rbf()));
+
+    apply_lock_time(&mut transaction, None, false);
+    assert_eq!(transaction.lock_time, PackedLockTime::ZERO);
+    assert!(!transaction.is_lock_time_enabled());
+  }
+}
@@ -0,0 +1,86 @@
+use {
+  super::*,
+  crate::builder::{self, AccelerateParams},
+  bitcoin::consensus::encode::serialize_hex,
+  reveal_fee_check::RevealFeeCheck,
+};
+
+/// Pays for a stuck reveal by CPFP, with a child spending one of its outputs
+/// back to the same address: the service fee output when the reveal has
+/// one, the inscription output otherwise.
+#[derive(Debug)]
+pub struct Accelerate {
+  pub reveal: Txid,
+  pub fee_rate: FeeRate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub transaction: String,
+  pub commit_custom: Vec<String>,
+  pub network_fee: u64,
+  pub spend: OutPoint,
+  pub reveal_fee_rate: f64,
+  /// Effective fee rate of the reveal and the child together.
+  pub package_fee_rate: f64,
+}
+
+impl Accelerate {
+  pub fn build(self, options: Options, service_address: Option<&Address>) -> Result<Output> {
+    let index = Index::read_open(&options)?;
+
+    let reveal = index.get_transaction_by_mempool(self.reveal)?;
+    let reveal_fee = RevealFeeCheck::fee(&index, &reveal)?;
+    let reveal_fee_rate = reveal_fee as f64 / reveal.vsize() as f64;
+
+    let vout = service_address
+      .and_then(|service_address| {
+        reveal
+          .output
+          .iter()
+          .position(|output| output.script_pubkey == service_address.script_pubkey())
+      })
+      .unwrap_or(0);
+    let spent = reveal
+      .output
+      .get(vout)
+      .cloned()
+      .ok_or_else(|| anyhow!("reveal {} has no outputs", self.reveal))?;
+    let owner = Address::from_script(&spent.script_pubkey, options.chain().network())
+      .map_err(|_| anyhow!("output {vout} of reveal {} pays no address", self.reveal))?;
+    let input_type = check_source_address(&owner, &options)?;
+
+    let accelerated = builder::accelerate(AccelerateParams {
+      parent: reveal,
+      parent_fee: reveal_fee,
+      vout: u32::try_from(vout)?,
+      input_type,
+      fee_rate: self.fee_rate,
+      rbf: true,
+    })?;
+
+    let spend = accelerated.transaction.input[0].previous_output;
+    let psbt = builder::psbt(
+      &accelerated.transaction,
+      &[(spend, Amount::from_sat(spent.value))].into(),
+      |_| spent.script_pubkey.clone(),
+    )?;
+
+    tracing::info!(
+      reveal = %self.reveal,
+      %spend,
+      network_fee = accelerated.network_fee,
+      package_fee_rate = accelerated.package_fee_rate,
+      "Build accelerate success"
+    );
+
+    Ok(Output {
+      transaction: serialize_hex(&psbt),
+      commit_custom: transfer::Transfer::get_custom(&psbt),
+      network_fee: accelerated.network_fee,
+      spend,
+      reveal_fee_rate,
+      package_fee_rate: accelerated.package_fee_rate,
+    })
+  }
+}
@@ -0,0 +1,109 @@
+use super::*;
+
+/// An unspent output of an address, inscribed when any inscription sits on
+/// one of its sats.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Utxo {
+  pub outpoint: OutPoint,
+  pub value: u64,
+  pub inscribed: bool,
+  pub inscription_ids: Vec<InscriptionId>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Output {
+  pub utxos: Vec<Utxo>,
+  /// Value of the outputs that are safe to spend.
+  pub cardinal_balance: u64,
+  pub inscribed_balance: u64,
+}
+
+impl Output {
+  fn new(
+    utxos: &BTreeMap<OutPoint, Amount>,
+    inscriptions: &BTreeMap<SatPoint, InscriptionId>,
+  ) -> Self {
+    let mut output = Self {
+      utxos: Vec::new(),
+      cardinal_balance: 0,
+      inscribed_balance: 0,
+    };
+
+    for (outpoint, value) in utxos {
+      let inscription_ids = inscriptions
+        .iter()
+        .filter(|(satpoint, _)| satpoint.outpoint == *outpoint)
+        .map(|(_, inscription_id)| *inscription_id)
+        .collect::<Vec<InscriptionId>>();
+      let inscribed = !inscription_ids.is_empty();
+
+      if inscribed {
+        output.inscribed_balance += value.to_sat();
+      } else {
+        output.cardinal_balance += value.to_sat();
+      }
+
+      output.utxos.push(Utxo {
+        outpoint: *outpoint,
+        value: value.to_sat(),
+        inscribed,
+        inscription_ids,
+      });
+    }
+
+    output
+  }
+}
+
+/// The unspent outputs of `address` as the node's mempool API reports them,
+/// each marked with the inscriptions it holds.
+pub fn build(
+  options: &Options,
+  address: &Address,
+  mysql: Option<&MysqlDatabase>,
+) -> Result<Output> {
+  let index = Index::read_open(options)?;
+
+  tracing::info!("Get utxo...");
+  let utxos = index.get_unspent_outputs_by_mempool_v1(&address.to_string(), BTreeMap::new())?;
+  let inscriptions = address_inscriptions(&index, address, utxos.keys(), mysql)?;
+
+  Ok(Output::new(&utxos, &inscriptions))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn utxos_are_split_by_inscriptions() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(10_000)),
+      (outpoint(2), Amount::from_sat(546)),
+      (outpoint(3), Amount::from_sat(20_000)),
+    ]
+    .into();
+    let inscriptions = [
+      (satpoint(2, 0), inscription_id(1)),
+      (satpoint(2, 100), inscription_id(2)),
+      (satpoint(4, 0), inscription_id(3)),
+    ]
+    .into();
+
+    let output = Output::new(&utxos, &inscriptions);
+
+    assert_eq!(output.cardinal_balance, 30_000);
+    assert_eq!(output.inscribed_balance, 546);
+    assert_eq!(
+      output.utxos[1],
+      Utxo {
+        outpoint: outpoint(2),
+        value: 546,
+        inscribed: true,
+        inscription_ids: vec![inscription_id(1), inscription_id(2)],
+      }
+    );
+    assert!(!output.utxos[0].inscribed);
+    assert!(output.utxos[2].inscription_ids.is_empty());
+  }
+}
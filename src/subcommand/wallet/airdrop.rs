@@ -0,0 +1,456 @@
+use crate::{
+  builder::{self, CommitParams, Inscribed, KeySource},
+  index::{MysqlDatabase, RevealExpectation},
+};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoincore_rpc::RawTx;
+use std::collections::BTreeSet;
+use {super::*, funding::input_script, mint};
+
+/// Reveals per commit. A commit and its reveals must fit in the default
+/// mempool descendant limit of 25 transactions.
+pub const MAX_BATCH_ITEMS: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+  Csv,
+  Jsonl,
+}
+
+impl Format {
+  pub fn from_content_type(content_type: &str) -> Result<Self> {
+    match content_type.split(';').next().unwrap_or_default().trim() {
+      "text/csv" => Ok(Self::Csv),
+      "application/jsonl" | "application/x-ndjson" | "application/x-jsonlines" => Ok(Self::Jsonl),
+      other => bail!("unsupported content type `{other}`, expected text/csv or application/jsonl"),
+    }
+  }
+}
+
+/// One airdropped inscription, `line` is where it was read from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Row {
+  pub line: usize,
+  pub destination: Address,
+  pub content: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub postage: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RowError {
+  pub line: usize,
+  pub error: String,
+}
+
+#[derive(Deserialize)]
+struct JsonRow {
+  destination: String,
+  content: String,
+  #[serde(default)]
+  postage: Option<u64>,
+}
+
+/// Reads every row of `body`, returning all rows that fail validation
+/// rather than stopping at the first.
+pub fn parse(body: &str, format: Format, options: &Options) -> Result<Vec<Row>, Vec<RowError>> {
+  let mut rows = vec![];
+  let mut errors = vec![];
+
+  for (i, text) in body.lines().enumerate() {
+    let line = i + 1;
+    if text.trim().is_empty() {
+      continue;
+    }
+
+    let fields = match format {
+      Format::Csv => {
+        let fields = split_csv(text);
+        if line == 1 && fields.first().map(String::as_str) == Some("destination") {
+          continue;
+        }
+        match fields.as_slice() {
+          [destination, content] => Ok((destination.clone(), content.clone(), None)),
+          [destination, content, postage] if postage.is_empty() => {
+            Ok((destination.clone(), content.clone(), None))
+          }
+          [destination, content, postage] => postage
+            .parse::<u64>()
+            .map(|postage| (destination.clone(), content.clone(), Some(postage)))
+            .map_err(|_| anyhow!("invalid postage `{postage}`")),
+          fields => Err(anyhow!(
+            "expected destination,content[,postage], found {} columns",
+            fields.len()
+          )),
+        }
+      }
+      Format::Jsonl => serde_json::from_str::<JsonRow>(text)
+        .map(|row| (row.destination, row.content, row.postage))
+        .map_err(|err| anyhow!("invalid json: {err}")),
+    };
+
+    match fields.and_then(|(destination, content, postage)| {
+      Row::new(line, &destination, content, postage, options)
+    }) {
+      Ok(row) => rows.push(row),
+      Err(err) => errors.push(RowError {
+        line,
+        error: err.to_string(),
+      }),
+    }
+  }
+
+  if rows.is_empty() && errors.is_empty() {
+    errors.push(RowError {
+      line: 0,
+      error: "no rows".into(),
+    });
+  }
+
+  if errors.is_empty() {
+    Ok(rows)
+  } else {
+    Err(errors)
+  }
+}
+
+// quoted fields may contain commas, a doubled quote is a literal quote
+fn split_csv(line: &str) -> Vec<String> {
+  let mut fields = vec![];
+  let mut field = String::new();
+  let mut quoted = false;
+  let mut chars = line.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if quoted && chars.peek() == Some(&'"') => {
+        field.push('"');
+        chars.next();
+      }
+      '"' => quoted = !quoted,
+      ',' if !quoted => fields.push(std::mem::take(&mut field)),
+      c => field.push(c),
+    }
+  }
+  fields.push(field);
+
+  fields
+    .into_iter()
+    .map(|field| field.trim().to_owned())
+    .collect()
+}
+
+impl Row {
+  fn new(
+    line: usize,
+    destination: &str,
+    content: String,
+    postage: Option<u64>,
+    options: &Options,
+  ) -> Result<Self> {
+    let destination = destination
+      .parse::<Address>()
+      .map_err(|err| anyhow!("invalid destination `{destination}`: {err}"))?;
+    check_destination_address(&destination, options, false)?;
+
+    if content.is_empty() {
+      bail!("content is empty");
+    }
+
+    if let Some(postage) = postage {
+      let dust = destination.script_pubkey().dust_value().to_sat();
+      if postage < dust {
+        bail!("postage {postage} is below the dust value {dust} of the destination");
+      }
+    }
+
+    Ok(Self {
+      line,
+      destination,
+      content,
+      postage,
+    })
+  }
+}
+
+/// Splits rows into batches that are each built as one commit.
+pub fn batches(rows: Vec<Row>) -> Vec<Vec<Row>> {
+  rows
+    .chunks(MAX_BATCH_ITEMS)
+    .map(|batch| batch.to_vec())
+    .collect()
+}
+
+/// Builds one airdrop batch. Commits are funded from confirmed utxos only,
+/// so `spent` carries the outpoints used by earlier batches of the job.
+#[derive(Debug)]
+pub struct Airdrop {
+  pub source: Address,
+  pub fee_rate: FeeRate,
+  pub extension: Option<String>,
+  pub rows: Vec<Row>,
+  pub spent: BTreeSet<OutPoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Output {
+  pub inscription: Vec<InscriptionId>,
+  pub commit: String,
+  pub commit_custom: Vec<String>,
+  pub reveal: Vec<String>,
+  pub service_fee: u64,
+  pub network_fee: u64,
+  /// Outpoints spent by the commit.
+  pub spent: Vec<OutPoint>,
+}
+
+impl Airdrop {
+  pub fn build(
+    self,
+    options: Options,
+    service_address: Option<Address>,
+    service_fee: Option<Amount>,
+    mysql: &MysqlDatabase,
+  ) -> Result<Output> {
+    let address_type = check_source_address(&self.source, &options)?;
+    let service_address = service_address.unwrap_or(self.source.clone());
+    check_address_type(&service_address, &options)?;
+
+    let content_type = Media::content_type_for_mint(None, self.extension.as_deref())?;
+
+    let mut inscription = vec![];
+    let mut recipients = vec![];
+    for row in &self.rows {
+      inscription.push(Inscription::from_content(
+        options.chain(),
+        &content_type,
+        row.content.clone().into_bytes(),
+        None,
+      )?);
+      recipients.push((
+        row.destination.clone(),
+        row
+          .postage
+          .map(Amount::from_sat)
+          .unwrap_or(TransactionBuilder::TARGET_POSTAGE),
+      ));
+    }
+
+    let start = Instant::now();
+    let index = Index::read_open(&options)?;
+
+    let query_address = &format!("{}", self.source);
+    let mut utxos = index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?;
+    utxos.retain(|outpoint, amount| amount.to_sat() > 546 && !self.spent.contains(outpoint));
+
+    let service_fee = mint::Mint::service_fee(mysql.is_whitelist(query_address), service_fee);
+
+    let Inscribed {
+      commit: unsigned_commit_tx,
+      reveals: reveal_txs,
+      service_fee,
+      network_fee,
+      ..
+    } = builder::inscribe(
+      CommitParams {
+        input_type: address_type,
+        network: options.chain().network(),
+        satpoints: Vec::new(),
+        inscriptions: mysql.get_inscription_by_address(query_address)?,
+        utxos: utxos.clone(),
+        change: [self.source.clone(), self.source.clone()],
+        destination: self.source.clone(),
+        groups: Vec::new(),
+        commit_fee_rate: self.fee_rate,
+        reveal_fee_rate: FeeRate::try_from(self.fee_rate.0 + 0.02)?,
+        max_nonstandard_weight: None,
+        service_address,
+        service_fee,
+        additional_service_fee: Amount::ZERO,
+        target_postage: TransactionBuilder::TARGET_POSTAGE,
+        recipients,
+        parent: None,
+        key_source: KeySource::Random,
+      },
+      inscription,
+    )?;
+
+    let network_fee = builder::fee(&unsigned_commit_tx, &utxos) + network_fee;
+
+    let expectations = reveal_txs
+      .iter()
+      .map(|tx| RevealExpectation {
+        commit_txid: unsigned_commit_tx.txid(),
+        reveal_txid: tx.txid(),
+        reveal_hex: tx.raw_hex(),
+        source: self.source.to_string(),
+      })
+      .collect();
+    if let Err(err) = mysql.insert_reveal_expectations(expectations) {
+      tracing::warn!(error = %err, "Insert reveal expectations fail");
+    }
+
+    let unsigned_commit_psbt = builder::psbt(&unsigned_commit_tx, &utxos, |outpoint| {
+      input_script(&[], outpoint, &self.source)
+    })?;
+
+    let output = Output {
+      inscription: reveal_txs.iter().map(|tx| tx.txid().into()).collect(),
+      commit: serialize_hex(&unsigned_commit_psbt),
+      commit_custom: transfer::Transfer::get_custom(&unsigned_commit_psbt),
+      reveal: reveal_txs.iter().map(|tx| tx.raw_hex()).collect(),
+      service_fee,
+      network_fee,
+      spent: unsigned_commit_tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect(),
+    };
+
+    tracing::info!(
+      source = %self.source,
+      inscriptions = output.inscription.len(),
+      network_fee = output.network_fee,
+      duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+      "Build airdrop batch success"
+    );
+
+    Ok(output)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options() -> Options {
+    Options {
+      chain_argument: Chain::Testnet,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn content_types() {
+    assert_eq!(
+      Format::from_content_type("text/csv; charset=utf-8").unwrap(),
+      Format::Csv
+    );
+    assert_eq!(
+      Format::from_content_type("application/x-ndjson").unwrap(),
+      Format::Jsonl
+    );
+    assert!(Format::from_content_type("application/json").is_err());
+  }
+
+  #[test]
+  fn csv_rows() {
+    let body = format!(
+      "destination,content,postage\n{},\"hello, world\"\n\n{},\"say \"\"hi\"\"\",1000\n",
+      recipient(),
+      change(0)
+    );
+
+    assert_eq!(
+      parse(&body, Format::Csv, &options()).unwrap(),
+      vec![
+        Row {
+          line: 2,
+          destination: recipient(),
+          content: "hello, world".into(),
+          postage: None,
+        },
+        Row {
+          line: 4,
+          destination: change(0),
+          content: "say \"hi\"".into(),
+          postage: Some(1000),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn jsonl_rows() {
+    let body = format!(
+      "{{\"destination\":\"{}\",\"content\":\"foo\",\"postage\":600}}\n",
+      recipient()
+    );
+
+    assert_eq!(
+      parse(&body, Format::Jsonl, &options()).unwrap(),
+      vec![Row {
+        line: 1,
+        destination: recipient(),
+        content: "foo".into(),
+        postage: Some(600),
+      }]
+    );
+  }
+
+  #[test]
+  fn every_row_error_is_reported() {
+    let body = format!(
+      "{},foo\nnot-an-address,foo\n{},\n{},foo,1\n{},foo,bar\n{}\n",
+      recipient(),
+      recipient(),
+      recipient(),
+      recipient(),
+      recipient(),
+    );
+
+    let errors = parse(&body, Format::Csv, &options()).unwrap_err();
+
+    assert_eq!(
+      errors
+        .iter()
+        .map(|error| error.line)
+        .collect::<Vec<usize>>(),
+      vec![2, 3, 4, 5, 6]
+    );
+    assert_eq!(errors[1].error, "content is empty");
+    assert_eq!(errors[3].error, "invalid postage `bar`");
+  }
+
+  #[test]
+  fn mainnet_destination_is_rejected_on_testnet() {
+    let errors = parse(
+      "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4,foo",
+      Format::Csv,
+      &options(),
+    )
+    .unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 1);
+  }
+
+  #[test]
+  fn empty_body_is_an_error() {
+    assert_eq!(
+      parse("\n", Format::Jsonl, &options()).unwrap_err(),
+      vec![RowError {
+        line: 0,
+        error: "no rows".into(),
+      }]
+    );
+  }
+
+  #[test]
+  fn batches_respect_descendant_limit() {
+    let row = Row {
+      line: 1,
+      destination: recipient(),
+      content: "foo".into(),
+      postage: None,
+    };
+
+    let batches = batches(vec![row; 50]);
+
+    assert_eq!(
+      batches.iter().map(Vec::len).collect::<Vec<usize>>(),
+      vec![24, 24, 2]
+    );
+  }
+}
@@ -0,0 +1,300 @@
+use {
+  super::*,
+  bitcoin::{consensus::encode::deserialize, hashes::hex::FromHex},
+  bitcoincore_rpc::RawTx,
+};
+
+#[derive(Debug, Parser)]
+pub struct Broadcast {
+  #[clap(long, help = "Broadcast raw transaction <TX> hex, may be repeated.")]
+  pub tx: Vec<String>,
+  #[clap(long, help = "Submit transactions as a package with submitpackage.")]
+  pub package: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+  pub txid: Txid,
+  pub accepted: bool,
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub order: Vec<Txid>,
+  pub status: Vec<Status>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub package: Option<serde_json::Value>,
+}
+
+impl Broadcast {
+  pub fn build(self, options: Options) -> Result<Output> {
+    let mut transactions = vec![];
+    for tx in &self.tx {
+      transactions.push(
+        deserialize::<Transaction>(&Vec::from_hex(tx.trim())?)
+          .map_err(|err| anyhow!("invalid transaction: {err}"))?,
+      );
+    }
+
+    let supplied = transactions
+      .iter()
+      .map(Transaction::txid)
+      .collect::<Vec<Txid>>();
+
+    let transactions = Self::sort(transactions)?;
+    let order = transactions
+      .iter()
+      .map(Transaction::txid)
+      .collect::<Vec<Txid>>();
+
+    let client = options.bitcoin_rpc_client()?;
+
+    // resubmitting a batch is safe, transactions the node already has are
+    // reported as accepted without being sent again. getrawtransaction only
+    // finds confirmed transactions on nodes running with -txindex, without
+    // it those are recognized by the node refusing them as already known.
+    let known = order
+      .iter()
+      .filter(|txid| client.get_raw_transaction(txid, None).is_ok())
+      .copied()
+      .collect::<Vec<Txid>>();
+
+    // every input spending outside the batch must be known to the node
+    // before anything is sent
+    for tx in &transactions {
+      if known.contains(&tx.txid()) {
+        continue;
+      }
+      for input in &tx.input {
+        let outpoint = input.previous_output;
+        if order.contains(&outpoint.txid) {
+          continue;
+        }
+        if client
+          .get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?
+          .is_none()
+        {
+          return Err(
+            BuildError::UnknownParent {
+              txid: tx.txid(),
+              outpoint,
+            }
+            .into(),
+          );
+        }
+      }
+    }
+
+    if self.package {
+      let hex = transactions
+        .iter()
+        .map(|tx| tx.raw_hex())
+        .collect::<Vec<String>>();
+      let package: serde_json::Value = client.call("submitpackage", &[hex.into()])?;
+      return Ok(Output {
+        order,
+        status: vec![],
+        package: Some(package),
+      });
+    }
+
+    let mut status = vec![];
+    let mut deferred = vec![];
+    for tx in &transactions {
+      if known.contains(&tx.txid()) {
+        status.push(Status {
+          txid: tx.txid(),
+          accepted: true,
+          error: None,
+        });
+        continue;
+      }
+      match client.send_raw_transaction(tx) {
+        Ok(txid) => status.push(Status {
+          txid,
+          accepted: true,
+          error: None,
+        }),
+        Err(err) if Self::is_already_known(&err.to_string()) => status.push(Status {
+          txid: tx.txid(),
+          accepted: true,
+          error: None,
+        }),
+        Err(err) if Self::is_missing_inputs(&err.to_string()) => deferred.push(tx),
+        Err(err) => status.push(Status {
+          txid: tx.txid(),
+          accepted: false,
+          error: Some(err.to_string()),
+        }),
+      }
+    }
+
+    // children rejected for missing inputs are retried once their parents
+    // have been accepted
+    for tx in deferred {
+      let parents_accepted = tx.input.iter().all(|input| {
+        !order.contains(&input.previous_output.txid)
+          || status
+            .iter()
+            .any(|status| status.accepted && status.txid == input.previous_output.txid)
+      });
+
+      let result = if parents_accepted {
+        match client.send_raw_transaction(tx) {
+          Ok(txid) => Ok(txid),
+          Err(err) if Self::is_already_known(&err.to_string()) => Ok(tx.txid()),
+          Err(err) => Err(err.to_string()),
+        }
+      } else {
+        Err("parent transaction was not accepted".into())
+      };
+
+      status.push(match result {
+        Ok(txid) => Status {
+          txid,
+          accepted: true,
+          error: None,
+        },
+        Err(err) => Status {
+          txid: tx.txid(),
+          accepted: false,
+          error: Some(err),
+        },
+      });
+    }
+
+    // reported in the order they were supplied, `order` is the one used
+    status.sort_by_key(|status| supplied.iter().position(|txid| *txid == status.txid));
+
+    Ok(Output {
+      order,
+      status,
+      package: None,
+    })
+  }
+
+  pub fn run(self, options: Options) -> Result {
+    print_json(self.build(options)?)?;
+    Ok(())
+  }
+
+  fn is_missing_inputs(err: &str) -> bool {
+    err.contains("missing-inputs") || err.contains("bad-txns-inputs-missingorspent")
+  }
+
+  fn is_already_known(err: &str) -> bool {
+    err.contains("txn-already-in-mempool")
+      || err.contains("txn-already-known")
+      || err.contains("already in block chain")
+      || err.contains("outputs already in utxo set")
+  }
+
+  /// Orders transactions so that parents come before the children spending
+  /// them, keeping the supplied order otherwise.
+  pub(crate) fn sort(transactions: Vec<Transaction>) -> Result<Vec<Transaction>> {
+    let txids = transactions
+      .iter()
+      .map(Transaction::txid)
+      .collect::<Vec<Txid>>();
+
+    for (i, txid) in txids.iter().enumerate() {
+      if txids[..i].contains(txid) {
+        return Err(BuildError::DuplicateTransaction(*txid).into());
+      }
+    }
+
+    let mut sorted: Vec<Transaction> = vec![];
+    let mut remaining = transactions;
+    while !remaining.is_empty() {
+      let sorted_txids = sorted.iter().map(Transaction::txid).collect::<Vec<Txid>>();
+      let ready = remaining.iter().position(|tx| {
+        tx.input.iter().all(|input| {
+          let parent = input.previous_output.txid;
+          !txids.contains(&parent) || sorted_txids.contains(&parent)
+        })
+      });
+
+      match ready {
+        Some(i) => sorted.push(remaining.remove(i)),
+        None => {
+          return Err(
+            BuildError::DependencyCycle(remaining.iter().map(Transaction::txid).collect()).into(),
+          )
+        }
+      }
+    }
+
+    Ok(sorted)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, bitcoin::PackedLockTime};
+
+  fn tx(inputs: Vec<OutPoint>, value: u64) -> Transaction {
+    Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: inputs.into_iter().map(tx_in).collect(),
+      output: vec![tx_out(value, recipient())],
+    }
+  }
+
+  fn spend(parent: &Transaction) -> OutPoint {
+    OutPoint {
+      txid: parent.txid(),
+      vout: 0,
+    }
+  }
+
+  #[test]
+  fn sort_parents_first() {
+    let parent = tx(vec![outpoint(1)], 3000);
+    let child = tx(vec![spend(&parent)], 2000);
+    let grandchild = tx(vec![spend(&child)], 1000);
+    let unrelated = tx(vec![outpoint(2)], 500);
+
+    let sorted = Broadcast::sort(vec![
+      grandchild.clone(),
+      unrelated.clone(),
+      child.clone(),
+      parent.clone(),
+    ])
+    .unwrap();
+
+    assert_eq!(sorted, vec![unrelated, parent, child, grandchild]);
+  }
+
+  #[test]
+  fn sort_rejects_duplicates() {
+    let parent = tx(vec![outpoint(1)], 3000);
+    assert_eq!(
+      Broadcast::sort(vec![parent.clone(), parent.clone()])
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::DuplicateTransaction(parent.txid())
+    );
+  }
+
+  #[test]
+  fn missing_inputs() {
+    assert!(Broadcast::is_missing_inputs(
+      "JSON-RPC error: RPC error response: RpcError { code: -25, message: \"bad-txns-inputs-missingorspent\" }"
+    ));
+    assert!(!Broadcast::is_missing_inputs("insufficient fee"));
+  }
+
+  #[test]
+  fn already_known() {
+    assert!(Broadcast::is_already_known(
+      "JSON-RPC error: RPC error response: RpcError { code: -27, message: \"Transaction already in block chain\" }"
+    ));
+    assert!(Broadcast::is_already_known(
+      "JSON-RPC error: RPC error response: RpcError { code: -26, message: \"txn-already-in-mempool\" }"
+    ));
+    assert!(!Broadcast::is_already_known("missing-inputs"));
+  }
+}
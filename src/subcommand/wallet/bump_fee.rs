@@ -0,0 +1,104 @@
+use {
+  super::*,
+  crate::builder::{self, BumpFeeParams, Bumped},
+  bitcoin::{
+    consensus::encode::{deserialize, serialize_hex},
+    hashes::hex::FromHex,
+    psbt::Psbt,
+  },
+};
+
+/// Replaces an unconfirmed transfer with one paying a higher fee rate, the
+/// inscription outputs untouched and the bump taken from the change output
+/// to `source`.
+#[derive(Debug)]
+pub struct BumpFee {
+  /// The transfer as raw transaction or PSBT hex, signed or not.
+  pub transaction: Option<String>,
+  /// The transfer's txid, looked up in the mempool when `transaction` is
+  /// not given.
+  pub txid: Option<Txid>,
+  pub source: Address,
+  pub fee_rate: FeeRate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub transaction: String,
+  pub commit_custom: Vec<String>,
+  pub network_fee: u64,
+  pub original_fee: u64,
+  pub replaces: Txid,
+}
+
+impl BumpFee {
+  pub fn build(self, options: Options) -> Result<Output> {
+    let input_type = check_source_address(&self.source, &options)?;
+    let index = Index::read_open(&options)?;
+
+    let original = match (&self.transaction, self.txid) {
+      (Some(transaction), _) => Self::decode(transaction)?,
+      (None, Some(txid)) => index.get_transaction_by_mempool(txid)?,
+      (None, None) => bail!("either a transaction or a txid is required"),
+    };
+    let replaces = original.txid();
+
+    let mut spent = BTreeMap::new();
+    for input in &original.input {
+      let outpoint = input.previous_output;
+      let output = index
+        .get_transaction_by_mempool(outpoint.txid)?
+        .output
+        .get(usize::try_from(outpoint.vout)?)
+        .cloned()
+        .ok_or_else(|| {
+          anyhow!("output {outpoint} spent by transaction {replaces} does not exist")
+        })?;
+      spent.insert(outpoint, output);
+    }
+    let utxos = spent
+      .iter()
+      .map(|(outpoint, output)| (*outpoint, Amount::from_sat(output.value)))
+      .collect::<BTreeMap<OutPoint, Amount>>();
+
+    let Bumped {
+      transaction,
+      network_fee,
+      original_fee,
+    } = builder::bump_fee(BumpFeeParams {
+      original,
+      utxos: utxos.clone(),
+      change: self.source.clone(),
+      input_type,
+      fee_rate: self.fee_rate,
+    })?;
+
+    let psbt = builder::psbt(&transaction, &utxos, |outpoint| {
+      spent[&outpoint].script_pubkey.clone()
+    })?;
+
+    tracing::info!(
+      %replaces,
+      source = %self.source,
+      original_fee,
+      network_fee,
+      "Build bump fee success"
+    );
+
+    Ok(Output {
+      transaction: serialize_hex(&psbt),
+      commit_custom: transfer::Transfer::get_custom(&psbt),
+      network_fee,
+      original_fee,
+      replaces,
+    })
+  }
+
+  fn decode(hex: &str) -> Result<Transaction> {
+    let bytes = Vec::from_hex(hex.trim())?;
+    if let Ok(psbt) = deserialize::<Psbt>(&bytes) {
+      return Ok(psbt.extract_tx());
+    }
+    deserialize(&bytes).map_err(|err| anyhow!("invalid transaction: {err}"))
+  }
+}
@@ -0,0 +1,162 @@
+use {
+  super::*,
+  crate::{
+    builder::{self, BumpParams},
+    index::MysqlDatabase,
+    reveal_key::RevealKeys,
+  },
+  bitcoin::{
+    consensus::encode::{deserialize, serialize_hex},
+    hashes::hex::FromHex,
+    psbt::Psbt,
+    Witness,
+  },
+};
+
+/// Re-signs a stuck reveal with an extra input from `source` paying a higher
+/// fee. Only reveals built while a reveal key secret was configured can be
+/// bumped.
+#[derive(Debug)]
+pub struct BumpReveal {
+  pub reveal: String,
+  pub funding: OutPoint,
+  pub source: Address,
+  pub fee_rate: FeeRate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub transaction: String,
+  pub commit_custom: Vec<String>,
+  pub network_fee: u64,
+  pub replaces: Txid,
+}
+
+impl BumpReveal {
+  pub fn build(self, options: Options, mysql: &MysqlDatabase, keys: &RevealKeys) -> Result<Output> {
+    let input_type = check_source_address(&self.source, &options)?;
+
+    let reveal = deserialize::<Transaction>(&Vec::from_hex(&self.reveal)?)
+      .map_err(|err| anyhow!("invalid reveal: {err}"))?;
+    let commit = reveal
+      .input
+      .first()
+      .ok_or_else(|| anyhow!("transaction {} is not a reveal", reveal.txid()))?
+      .previous_output;
+
+    let salt = mysql
+      .get_reveal_key_salt(commit.txid)?
+      .ok_or(BuildError::RevealKeyNotPersisted(commit.txid))?;
+
+    let index = Index::read_open(&options)?;
+
+    let commit_output = index
+      .get_transaction_by_mempool(commit.txid)?
+      .output
+      .get(commit.vout as usize)
+      .cloned()
+      .ok_or_else(|| anyhow!("commit output {commit} does not exist"))?;
+
+    let funding_output = index
+      .get_transaction_by_mempool(self.funding.txid)?
+      .output
+      .get(self.funding.vout as usize)
+      .cloned()
+      .ok_or_else(|| anyhow!("funding output {} does not exist", self.funding))?;
+
+    if funding_output.script_pubkey != self.source.script_pubkey() {
+      bail!(
+        "funding output {} does not belong to {}",
+        self.funding,
+        self.source
+      );
+    }
+
+    let bumped = builder::bump_reveal(BumpParams {
+      reveal: reveal.clone(),
+      commit_output: commit_output.clone(),
+      funding: self.funding,
+      funding_output: funding_output.clone(),
+      input_type,
+      change: self.source.clone(),
+      fee_rate: self.fee_rate,
+      key_pair: keys.key_pair(&salt)?,
+    })?;
+
+    let network_fee = commit_output.value + funding_output.value
+      - bumped.output.iter().map(|output| output.value).sum::<u64>();
+
+    let psbt = Self::get_psbt(&bumped, [commit_output, funding_output])?;
+
+    tracing::info!(
+      commit = %commit.txid,
+      replaces = %reveal.txid(),
+      network_fee,
+      "Bump reveal success"
+    );
+
+    Ok(Output {
+      transaction: serialize_hex(&psbt),
+      commit_custom: transfer::Transfer::get_custom(&psbt),
+      network_fee,
+      replaces: reveal.txid(),
+    })
+  }
+
+  // the reveal input stays finalized, only the funding input is left to sign
+  fn get_psbt(bumped: &Transaction, prevouts: [TxOut; 2]) -> Result<Psbt> {
+    let mut unsigned = bumped.clone();
+    for input in &mut unsigned.input {
+      input.witness = Witness::new();
+    }
+
+    let mut psbt = Psbt::from_unsigned_tx(unsigned)?;
+    for (input, prevout) in psbt.inputs.iter_mut().zip(prevouts) {
+      input.witness_utxo = Some(prevout);
+    }
+    psbt.inputs[0].final_script_witness = Some(bumped.input[0].witness.clone());
+
+    Ok(psbt)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, bitcoin::PackedLockTime};
+
+  #[test]
+  fn reveal_input_is_finalized() {
+    let mut bumped = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+      output: vec![tx_out(546, recipient())],
+    };
+    bumped.input[0].witness = Witness::from_vec(vec![vec![1; 64], vec![2], vec![3]]);
+
+    let psbt = BumpReveal::get_psbt(
+      &bumped,
+      [tx_out(1_000, recipient()), tx_out(2_000, change(0))],
+    )
+    .unwrap();
+
+    assert!(psbt.unsigned_tx.input[0].witness.is_empty());
+    assert_eq!(
+      psbt.inputs[0].final_script_witness,
+      Some(bumped.input[0].witness.clone())
+    );
+    assert_eq!(psbt.inputs[1].final_script_witness, None);
+    assert_eq!(psbt.inputs[1].witness_utxo, Some(tx_out(2_000, change(0))));
+  }
+
+  #[test]
+  fn unknown_commit_has_no_key() {
+    assert_eq!(
+      BuildError::RevealKeyNotPersisted(txid(1)).to_string(),
+      format!(
+        "commit {} was not built with a recoverable reveal key",
+        txid(1)
+      )
+    );
+  }
+}
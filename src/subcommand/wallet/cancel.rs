@@ -1,9 +1,14 @@
-use super::*;
-use crate::index::{ConstructTransaction, MysqlDatabase, TransactionOutputArray};
-use bitcoin::blockdata::{script, witness::Witness};
+use crate::{
+  builder::{self, CancelParams, Cancelled},
+  index::{ConstructTransaction, MysqlDatabase, TransactionOutputArray},
+};
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::psbt::Psbt;
-use bitcoin::{AddressType, PackedLockTime};
+use {
+  super::*,
+  script_path::ScriptPathInput,
+  warning::{Warning, Warnings},
+};
 
 #[derive(Debug, Parser)]
 pub struct Cancel {
@@ -13,6 +18,25 @@ pub struct Cancel {
   pub inputs: Vec<OutPoint>,
   #[clap(long, help = "Use fee rate of <FEE_RATE> sats/vB")]
   pub fee_rate: FeeRate,
+  #[clap(long, help = "Only valid after block height <LOCKTIME>.")]
+  pub locktime: Option<u32>,
+  #[clap(long, help = "Whether to signal BIP-125 replaceability, default true.")]
+  pub rbf: Option<bool>,
+  #[clap(
+    long = "script-path-input",
+    help = "Spend an input through a tapscript leaf, written <TXID>:<VOUT>:<LEAF_SCRIPT_HEX>:<CONTROL_BLOCK_HEX>. May be repeated."
+  )]
+  pub script_path_inputs: Vec<ScriptPathInput>,
+  #[clap(
+    long,
+    help = "Check and price the cancel, marking the result as not to be signed."
+  )]
+  pub dry_run: bool,
+  #[clap(
+    long,
+    help = "Refuse the build if its fees add up to more than <MAX_TOTAL_FEE> sats."
+  )]
+  pub max_total_fee: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,9 +44,16 @@ pub struct Output {
   pub transaction: String,
   pub commit_custom: Vec<String>,
   pub network_fee: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub locktime: Option<u32>,
   pub service_fee: u64,
   pub commit_vsize: u64,
   pub commit_fee: u64,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub warnings: Vec<Warning>,
+  /// Built only to validate and price, not to be signed.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub dry_run: bool,
 }
 
 impl Cancel {
@@ -33,90 +64,74 @@ impl Cancel {
     service_fee: Option<Amount>,
     _mysql: Option<Arc<MysqlDatabase>>,
   ) -> Result<Output> {
-    if !self.source.is_valid_for_network(options.chain().network()) {
-      bail!(
-        "Address `{}` is not valid for {}",
-        self.source,
-        options.chain()
-      );
-    }
-
-    // check address types, only support p2tr and p2wpkh
-    let address_type = if let Some(address_type) = self.source.address_type() {
-      if (address_type == AddressType::P2tr) || (address_type == AddressType::P2wpkh) {
-        address_type
-      } else {
-        bail!(
-          "Address type `{}` is not valid, only support p2tr and p2wpkh",
-          address_type
-        );
-      }
-    } else {
-      bail!(
-        "Address `{}` is not valid for {}",
-        self.source,
-        options.chain()
-      );
-    };
+    let address_type = check_source_address(&self.source, &options)?;
+    let script_spends = script_path::script_spends(&self.script_path_inputs, &self.source)?;
+    let witness_sizes = script_path::witness_sizes(&script_spends);
 
-    log::info!("Open index...");
+    let start = Instant::now();
+    tracing::info!("Open index...");
     let index = Index::read_open(&options)?;
     // index.update()?;
 
-    log::info!("Get utxo...");
-    let unspent_outputs = index.get_unspent_outputs_by_outpoints(&self.inputs)?;
+    let mut warnings = Warnings::default();
+    warnings.check_index(&index);
+    warnings.check_fee_rate(&index, self.fee_rate);
 
-    let mut service_fee = service_fee.unwrap_or(Amount::ZERO).to_sat();
-    if service_address.is_none() {
-      service_fee = 0;
+    if let Some(locktime) = self.locktime {
+      check_lock_time(locktime, index.node_height()?)?;
     }
 
-    let output = if service_fee == 0 {
-      vec![TxOut {
-        script_pubkey: self.source.script_pubkey(),
-        value: 0,
-      }]
-    } else {
-      vec![
-        TxOut {
-          script_pubkey: self.source.script_pubkey(),
-          value: 0,
-        },
-        TxOut {
-          script_pubkey: service_address.unwrap().script_pubkey(),
-          value: service_fee,
-        },
-      ]
-    };
-    let (mut cancel_tx, network_fee) =
-      Self::build_cancel_transaction(self.fee_rate, self.inputs, output, address_type);
-    let commit_vsize = cancel_tx.vsize() as u64;
+    tracing::info!("Get utxo...");
+    let unspent_outputs = index.get_unspent_outputs_by_outpoints(&self.inputs)?;
 
-    let input_amount = Self::get_amount(&cancel_tx, &unspent_outputs)?;
-    if input_amount <= network_fee {
-      bail!("Input amount less than network fee");
-    }
-    if input_amount <= network_fee + service_fee {
-      service_fee = input_amount - network_fee;
-      cancel_tx.output[1].value = service_fee;
-    }
-    cancel_tx.output[0].value = input_amount - network_fee - service_fee;
-    for input in &mut cancel_tx.input {
-      input.witness = Witness::new();
+    let service = service_address.zip(service_fee);
+    if let Some((service_address, _)) = service.as_ref().filter(|(_, fee)| *fee > Amount::ZERO) {
+      check_address_type(service_address, &options)?;
     }
 
-    let unsigned_transaction_psbt = Self::get_psbt(&cancel_tx, &unspent_outputs, &self.source)?;
+    let Cancelled {
+      transaction: cancel_tx,
+      network_fee,
+      service_fee,
+    } = builder::cancel(CancelParams {
+      input_type: address_type,
+      inputs: self.inputs.clone(),
+      utxos: unspent_outputs.clone(),
+      source: self.source.clone(),
+      service,
+      fee_rate: self.fee_rate,
+      locktime: self.locktime,
+      rbf: self.rbf.unwrap_or(true),
+      witness_sizes: witness_sizes.clone(),
+    })?;
+    builder::check_total_fee(0, 0, network_fee, self.max_total_fee)?;
+    let commit_vsize =
+      builder::estimate_vsize_with_witnesses(&cancel_tx, address_type, &witness_sizes) as u64;
+
+    warnings.check_change(&cancel_tx, &self.source);
+
+    let mut unsigned_transaction_psbt = Self::get_psbt(&cancel_tx, &unspent_outputs, &self.source)?;
+    script_path::add_to_psbt(&mut unsigned_transaction_psbt, &script_spends);
     let unsigned_commit_custom = Self::get_custom(&unsigned_transaction_psbt);
 
-    log::info!("Build cancel success");
+    tracing::info!(
+      source = %self.source,
+      inputs = cancel_tx.input.len(),
+      network_fee,
+      duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+      "Build cancel success"
+    );
 
     Ok(Output {
       transaction: serialize_hex(&unsigned_transaction_psbt),
       commit_custom: unsigned_commit_custom,
       network_fee,
+      locktime: self.locktime,
       service_fee,
       commit_vsize,
       commit_fee: network_fee,
+      warnings: warnings.into_vec(),
+      dry_run: self.dry_run,
     })
   }
 
@@ -125,17 +140,6 @@ impl Cancel {
     Ok(())
   }
 
-  fn get_amount(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> Result<u64> {
-    let mut amount = 0;
-    for i in 0..tx.input.len() {
-      amount += utxos
-        .get(&tx.input[i].previous_output)
-        .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
-        .to_sat();
-    }
-    Ok(amount)
-  }
-
   fn get_psbt(
     tx: &Transaction,
     utxos: &BTreeMap<OutPoint, Amount>,
@@ -146,7 +150,7 @@ impl Cancel {
       tx_psbt.inputs[i].witness_utxo = Some(TxOut {
         value: utxos
           .get(&tx_psbt.unsigned_tx.input[i].previous_output)
-          .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
+          .ok_or(BuildError::NoCardinalUtxos { min_value: None })?
           .to_sat(),
         script_pubkey: source.script_pubkey(),
       });
@@ -174,35 +178,4 @@ impl Cancel {
 
     result
   }
-
-  fn build_cancel_transaction(
-    fee_rate: FeeRate,
-    input: Vec<OutPoint>,
-    output: Vec<TxOut>,
-    input_type: AddressType,
-  ) -> (Transaction, u64) {
-    let witness_size = if input_type == AddressType::P2tr {
-      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
-    } else {
-      TransactionBuilder::P2WPKH_WINETSS_SIZE
-    };
-
-    let cancel_tx = Transaction {
-      input: input
-        .iter()
-        .map(|item| TxIn {
-          previous_output: *item,
-          script_sig: script::Builder::new().into_script(),
-          witness: Witness::from_vec(vec![vec![0; witness_size]]),
-          sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        })
-        .collect(),
-      output,
-      lock_time: PackedLockTime::ZERO,
-      version: 1,
-    };
-
-    let fee = fee_rate.fee(cancel_tx.vsize());
-    (cancel_tx, fee.to_sat())
-  }
 }
@@ -10,14 +10,15 @@ pub(crate) fn run(options: Options) -> Result {
   let index = Index::open(&options)?;
   index.update()?;
 
+  let unspent_outputs = index.get_unspent_outputs(Wallet::load(&options)?)?;
+
   let inscribed_utxos = index
-    .get_inscriptions(None)?
+    .get_inscriptions_for_outpoints(unspent_outputs.keys())?
     .keys()
     .map(|satpoint| satpoint.outpoint)
     .collect::<BTreeSet<OutPoint>>();
 
-  let cardinal_utxos = index
-    .get_unspent_outputs(Wallet::load(&options)?)?
+  let cardinal_utxos = unspent_outputs
     .iter()
     .filter_map(|(output, amount)| {
       if inscribed_utxos.contains(output) {
@@ -0,0 +1,49 @@
+use {
+  super::*,
+  crate::envelope::Envelope,
+  bitcoin::{consensus::encode::deserialize, hashes::hex::FromHex},
+};
+
+#[derive(Debug, Parser)]
+pub struct DecodeReveal {
+  #[clap(long, help = "Decode raw reveal transaction <TX> hex.")]
+  pub tx: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub txid: Txid,
+  #[serde(flatten)]
+  pub envelope: Envelope,
+  pub destination_script: Option<String>,
+  pub destination: Option<String>,
+  pub weight: usize,
+  pub standard: bool,
+}
+
+impl DecodeReveal {
+  pub fn build(self, options: Options) -> Result<Output> {
+    let tx: Transaction = deserialize(&Vec::from_hex(self.tx.trim())?)
+      .map_err(|err| anyhow!("invalid reveal transaction: {err}"))?;
+
+    let envelope = Envelope::from_transaction(&tx)?;
+
+    let destination_script = tx.output.first().map(|output| &output.script_pubkey);
+
+    Ok(Output {
+      txid: tx.txid(),
+      envelope,
+      destination_script: destination_script.map(|script| format!("{script:x}")),
+      destination: destination_script
+        .and_then(|script| Address::from_script(script, options.chain().network()).ok())
+        .map(|address| address.to_string()),
+      weight: tx.weight(),
+      standard: Envelope::is_standard(&tx),
+    })
+  }
+
+  pub fn run(self, options: Options) -> Result {
+    print_json(self.build(options)?)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,516 @@
+use {
+  super::*,
+  bitcoin::{
+    blockdata::locktime::LOCK_TIME_THRESHOLD, util::taproot::TAPROOT_CONTROL_MAX_NODE_COUNT,
+  },
+};
+
+#[derive(Debug, PartialEq)]
+pub enum BuildError {
+  AddressTypeUnsupported(AddressType),
+  AlreadyInscribed {
+    satpoint: SatPoint,
+  },
+  BurnDestination(Address),
+  ChangeAddressRequired(OutPoint),
+  CommitOutputSpent(OutPoint),
+  DependencyCycle(Vec<Txid>),
+  DestinationTypeNotAllowed {
+    address: Address,
+    allowed: Vec<AddressType>,
+  },
+  DuplicateContent(Vec<Vec<usize>>),
+  DuplicateInscription {
+    item: usize,
+    inscription_id: InscriptionId,
+  },
+  DuplicateTransaction(Txid),
+  DustOutput {
+    value: u64,
+    min: u64,
+  },
+  FeeRateAboveCeiling {
+    param: &'static str,
+    fee_rate: f64,
+    max: f64,
+  },
+  FeeRateBelowRelayFloor {
+    component: &'static str,
+    fee_rate: f64,
+  },
+  FundingInputMismatch {
+    outpoint: OutPoint,
+    value: u64,
+    script_pubkey: Script,
+  },
+  FundingInputScriptType {
+    outpoint: OutPoint,
+    expected: AddressType,
+  },
+  FundingInputSpent(OutPoint),
+  InputNotOwned {
+    outpoint: OutPoint,
+    source: Address,
+  },
+  InscriptionNumbersNotFound(Vec<u64>),
+  InsufficientFunds {
+    available: u64,
+    required: u64,
+  },
+  LockTimeNotHeight(u32),
+  LockTimeNotInFuture {
+    lock_time: u32,
+    tip: u64,
+  },
+  MerklePathTooDeep {
+    outpoint: OutPoint,
+    depth: usize,
+  },
+  NoCardinalUtxos {
+    min_value: Option<u64>,
+  },
+  OpReturnTooLarge {
+    size: usize,
+    limit: usize,
+  },
+  ParentNotOwned {
+    inscription_id: InscriptionId,
+    source: Address,
+  },
+  PointerOutOfRange {
+    item: usize,
+    pointer: u64,
+    postage: u64,
+  },
+  PolicyViolation {
+    api_key: String,
+    /// `None` when the request names no source.
+    source: Option<String>,
+    /// `None` when the source is not on the key's allowlist.
+    max_daily_sources: Option<u64>,
+  },
+  QuotaExceeded {
+    source: String,
+    limit: u64,
+    used: u64,
+    requested: u64,
+    reset_at: DateTime<Utc>,
+  },
+  RevealKeyNotPersisted(Txid),
+  RevealsNotPersisted(Txid),
+  TotalFeeAboveMax {
+    service_fee: u64,
+    satpoint_fee: u64,
+    network_fee: u64,
+    max: u64,
+  },
+  UnknownParent {
+    txid: Txid,
+    outpoint: OutPoint,
+  },
+  UnsafeDestination {
+    address: Address,
+    label: String,
+  },
+}
+
+impl fmt::Display for BuildError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BuildError::AddressTypeUnsupported(address_type) => write!(
+        f,
+        "Address type `{address_type}` is not valid, only support p2tr and p2wpkh"
+      ),
+      BuildError::AlreadyInscribed { satpoint } => write!(f, "sat at {satpoint} already inscribed"),
+      BuildError::BurnDestination(address) => write!(
+        f,
+        "destination `{address}` is a burn address, set allow_burn to send to it anyway"
+      ),
+      BuildError::ChangeAddressRequired(outpoint) => write!(
+        f,
+        "funding input {outpoint} is not held by the source, set change_address to return its change"
+      ),
+      BuildError::CommitOutputSpent(outpoint) => write!(
+        f,
+        "commit output {outpoint} is already spent or was never broadcast"
+      ),
+      BuildError::DependencyCycle(txids) => write!(
+        f,
+        "transactions {} spend each other in a cycle",
+        txids
+          .iter()
+          .map(|txid| txid.to_string())
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      BuildError::DestinationTypeNotAllowed { address, allowed } => write!(
+        f,
+        "address `{address}` of type {} is not allowed, only {} outputs are allowed",
+        address
+          .address_type()
+          .map(|address_type| address_type.to_string())
+          .unwrap_or_else(|| "unknown".into()),
+        allowed
+          .iter()
+          .map(|address_type| address_type.to_string())
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      BuildError::DuplicateContent(duplicates) => write!(
+        f,
+        "duplicate content at indices {}, set dedup to collapse or allow to mint anyway",
+        duplicates
+          .iter()
+          .map(|indices| format!("{indices:?}"))
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      BuildError::DuplicateInscription {
+        item,
+        inscription_id,
+      } => write!(
+        f,
+        "content of item {item} was already inscribed from this source as {inscription_id}"
+      ),
+      BuildError::DuplicateTransaction(txid) => {
+        write!(f, "transaction {txid} is included more than once")
+      }
+      BuildError::DustOutput { value, min } => write!(
+        f,
+        "commit transaction output would be dust: {value} sats is below the dust value of {min} sats"
+      ),
+      BuildError::FeeRateAboveCeiling {
+        param,
+        fee_rate,
+        max,
+      } => write!(
+        f,
+        "{param} {fee_rate} sat/vB is above the ceiling of {max} sat/vB"
+      ),
+      BuildError::FeeRateBelowRelayFloor {
+        component,
+        fee_rate,
+      } => write!(
+        f,
+        "{component} fee rate {fee_rate} sat/vB is below the relay floor of {} sat/vB",
+        builder::MIN_RELAY_FEE_RATE
+      ),
+      BuildError::FundingInputMismatch {
+        outpoint,
+        value,
+        script_pubkey,
+      } => write!(
+        f,
+        "funding input {outpoint} does not match the node, which has value {value} and script {script_pubkey:x}"
+      ),
+      BuildError::FundingInputScriptType { outpoint, expected } => write!(
+        f,
+        "funding input {outpoint} must have a {expected} script like the source"
+      ),
+      BuildError::FundingInputSpent(outpoint) => {
+        write!(f, "funding input {outpoint} is spent or unknown to the node")
+      }
+      BuildError::InputNotOwned { outpoint, source } => {
+        write!(f, "input {outpoint} is not held by source `{source}`")
+      }
+      BuildError::InscriptionNumbersNotFound(numbers) => write!(
+        f,
+        "inscriptions {} not found",
+        numbers
+          .iter()
+          .map(|number| format!("#{number}"))
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      BuildError::InsufficientFunds {
+        available,
+        required,
+      } => write!(
+        f,
+        "inputs provide {available} sats but postage, fees and service fee need {required}, short by {} sats",
+        required.saturating_sub(*available)
+      ),
+      BuildError::LockTimeNotHeight(lock_time) => write!(
+        f,
+        "locktime {lock_time} is not a block height, must be below {LOCK_TIME_THRESHOLD}"
+      ),
+      BuildError::LockTimeNotInFuture { lock_time, tip } => write!(
+        f,
+        "locktime {lock_time} is not in the future, current tip is {tip}"
+      ),
+      BuildError::MerklePathTooDeep { outpoint, depth } => write!(
+        f,
+        "merkle path of script path input {outpoint} has {depth} nodes, over the limit of {TAPROOT_CONTROL_MAX_NODE_COUNT}"
+      ),
+      BuildError::NoCardinalUtxos { min_value: None } => {
+        write!(f, "wallet contains no cardinal utxos")
+      }
+      BuildError::NoCardinalUtxos {
+        min_value: Some(min_value),
+      } => write!(
+        f,
+        "wallet contains no cardinal utxos, not support lower {min_value} satoshi"
+      ),
+      BuildError::OpReturnTooLarge { size, limit } => write!(
+        f,
+        "op_return of {size} bytes is over the standard limit of {limit} bytes"
+      ),
+      BuildError::ParentNotOwned {
+        inscription_id,
+        source,
+      } => write!(
+        f,
+        "parent inscription {inscription_id} is not held by source `{source}`"
+      ),
+      BuildError::PointerOutOfRange {
+        item,
+        pointer,
+        postage,
+      } => write!(
+        f,
+        "pointer {pointer} of item {item} is outside its output of {postage} sats, must be below the postage"
+      ),
+      BuildError::PolicyViolation {
+        api_key,
+        source: None,
+        ..
+      } => write!(
+        f,
+        "api key `{api_key}` may only act for the sources its policy allows, and the request names none"
+      ),
+      BuildError::PolicyViolation {
+        api_key,
+        source: Some(source),
+        max_daily_sources: None,
+      } => write!(
+        f,
+        "api key `{api_key}` may not act for source `{source}`, which is not on its allowlist"
+      ),
+      BuildError::PolicyViolation {
+        api_key,
+        source: Some(source),
+        max_daily_sources: Some(max_daily_sources),
+      } => write!(
+        f,
+        "api key `{api_key}` has acted for its {max_daily_sources} distinct sources today and cannot act for source `{source}`"
+      ),
+      BuildError::QuotaExceeded {
+        source,
+        limit,
+        used,
+        requested,
+        reset_at,
+      } => write!(
+        f,
+        "source `{source}` has minted {used} of its {limit} daily inscriptions and cannot mint {requested} more, the quota resets at {}",
+        reset_at.to_rfc3339()
+      ),
+      BuildError::RevealKeyNotPersisted(commit) => write!(
+        f,
+        "commit {commit} was not built with a recoverable reveal key"
+      ),
+      BuildError::RevealsNotPersisted(commit) => {
+        write!(f, "no reveals were persisted for commit {commit}")
+      }
+      BuildError::TotalFeeAboveMax {
+        service_fee,
+        satpoint_fee,
+        network_fee,
+        max,
+      } => write!(
+        f,
+        "fees of {} sats (service {service_fee}, satpoint {satpoint_fee}, network {network_fee}) are above max_total_fee of {max} sats",
+        service_fee + satpoint_fee + network_fee
+      ),
+      BuildError::UnknownParent { txid, outpoint } => write!(
+        f,
+        "transaction {txid} spends unknown output {outpoint}, which is neither in the batch nor unspent on the node"
+      ),
+      BuildError::UnsafeDestination { address, label } => write!(
+        f,
+        "destination `{address}` matches unsafe destination `{label}`, which may not credit inscriptions, set acknowledge_unsafe_destination to send to it anyway"
+      ),
+    }
+  }
+}
+
+impl BuildError {
+  /// A stable name for the error, for clients to match on instead of the
+  /// message.
+  pub fn code(&self) -> &'static str {
+    match self {
+      BuildError::AddressTypeUnsupported(_) => "address_type_unsupported",
+      BuildError::AlreadyInscribed { .. } => "already_inscribed",
+      BuildError::BurnDestination(_) => "burn_destination",
+      BuildError::ChangeAddressRequired(_) => "change_address_required",
+      BuildError::CommitOutputSpent(_) => "commit_output_spent",
+      BuildError::DependencyCycle(_) => "dependency_cycle",
+      BuildError::DestinationTypeNotAllowed { .. } => "destination_type_not_allowed",
+      BuildError::DuplicateContent(_) => "duplicate_content",
+      BuildError::DuplicateInscription { .. } => "duplicate_inscription",
+      BuildError::DuplicateTransaction(_) => "duplicate_transaction",
+      BuildError::DustOutput { .. } => "dust_output",
+      BuildError::FeeRateAboveCeiling { .. } => "fee_rate_above_ceiling",
+      BuildError::FeeRateBelowRelayFloor { .. } => "fee_rate_below_relay_floor",
+      BuildError::FundingInputMismatch { .. } => "funding_input_mismatch",
+      BuildError::FundingInputScriptType { .. } => "funding_input_script_type",
+      BuildError::FundingInputSpent(_) => "funding_input_spent",
+      BuildError::InputNotOwned { .. } => "input_not_owned",
+      BuildError::InscriptionNumbersNotFound(_) => "inscription_numbers_not_found",
+      BuildError::InsufficientFunds { .. } => "insufficient_funds",
+      BuildError::LockTimeNotHeight(_) => "lock_time_not_height",
+      BuildError::LockTimeNotInFuture { .. } => "lock_time_not_in_future",
+      BuildError::MerklePathTooDeep { .. } => "merkle_path_too_deep",
+      BuildError::NoCardinalUtxos { .. } => "no_cardinal_utxos",
+      BuildError::OpReturnTooLarge { .. } => "op_return_too_large",
+      BuildError::ParentNotOwned { .. } => "parent_not_owned",
+      BuildError::PointerOutOfRange { .. } => "pointer_out_of_range",
+      BuildError::PolicyViolation { .. } => "policy_violation",
+      BuildError::QuotaExceeded { .. } => "quota_exceeded",
+      BuildError::RevealKeyNotPersisted(_) => "reveal_key_not_persisted",
+      BuildError::RevealsNotPersisted(_) => "reveals_not_persisted",
+      BuildError::TotalFeeAboveMax { .. } => "total_fee_above_max",
+      BuildError::UnknownParent { .. } => "unknown_parent",
+      BuildError::UnsafeDestination { .. } => "unsafe_destination",
+    }
+  }
+
+  /// The values the message was built from.
+  pub fn data(&self) -> serde_json::Value {
+    use serde_json::json;
+
+    match self {
+      BuildError::AddressTypeUnsupported(address_type) => json!({
+        "address_type": address_type.to_string(),
+        "supported": [AddressType::P2tr.to_string(), AddressType::P2wpkh.to_string()],
+      }),
+      BuildError::AlreadyInscribed { satpoint } => json!({ "satpoint": satpoint }),
+      BuildError::BurnDestination(address) => json!({ "address": address }),
+      BuildError::ChangeAddressRequired(outpoint) | BuildError::CommitOutputSpent(outpoint) => {
+        json!({ "outpoint": outpoint })
+      }
+      BuildError::DependencyCycle(txids) => json!({ "txids": txids }),
+      BuildError::DestinationTypeNotAllowed { address, allowed } => json!({
+        "address": address,
+        "allowed": allowed
+          .iter()
+          .map(|address_type| address_type.to_string())
+          .collect::<Vec<String>>(),
+      }),
+      BuildError::DuplicateContent(duplicates) => json!({ "duplicates": duplicates }),
+      BuildError::DuplicateInscription {
+        item,
+        inscription_id,
+      } => json!({ "item": item, "inscription_id": inscription_id }),
+      BuildError::DuplicateTransaction(txid) => json!({ "txid": txid }),
+      BuildError::DustOutput { value, min } => json!({ "value": value, "min": min }),
+      BuildError::FeeRateAboveCeiling {
+        param,
+        fee_rate,
+        max,
+      } => json!({ "param": param, "fee_rate": fee_rate, "max": max }),
+      BuildError::FeeRateBelowRelayFloor {
+        component,
+        fee_rate,
+      } => json!({
+        "component": component,
+        "fee_rate": fee_rate,
+        "min": builder::MIN_RELAY_FEE_RATE,
+      }),
+      BuildError::FundingInputMismatch {
+        outpoint,
+        value,
+        script_pubkey,
+      } => json!({
+        "outpoint": outpoint,
+        "value": value,
+        "script_pubkey": script_pubkey,
+      }),
+      BuildError::FundingInputScriptType { outpoint, expected } => json!({
+        "outpoint": outpoint,
+        "expected": expected.to_string(),
+      }),
+      BuildError::FundingInputSpent(outpoint) => json!({ "outpoint": outpoint }),
+      BuildError::InputNotOwned { outpoint, source } => {
+        json!({ "outpoint": outpoint, "source": source })
+      }
+      BuildError::InscriptionNumbersNotFound(numbers) => json!({ "numbers": numbers }),
+      BuildError::InsufficientFunds {
+        available,
+        required,
+      } => json!({
+        "available": available,
+        "required": required,
+        "shortfall": required.saturating_sub(*available),
+      }),
+      BuildError::LockTimeNotHeight(lock_time) => json!({
+        "lock_time": lock_time,
+        "threshold": LOCK_TIME_THRESHOLD,
+      }),
+      BuildError::LockTimeNotInFuture { lock_time, tip } => {
+        json!({ "lock_time": lock_time, "tip": tip })
+      }
+      BuildError::MerklePathTooDeep { outpoint, depth } => json!({
+        "outpoint": outpoint,
+        "depth": depth,
+        "limit": TAPROOT_CONTROL_MAX_NODE_COUNT,
+      }),
+      BuildError::NoCardinalUtxos { min_value } => json!({ "min_value": min_value }),
+      BuildError::OpReturnTooLarge { size, limit } => json!({ "size": size, "limit": limit }),
+      BuildError::ParentNotOwned {
+        inscription_id,
+        source,
+      } => json!({ "inscription_id": inscription_id, "source": source }),
+      BuildError::PointerOutOfRange {
+        item,
+        pointer,
+        postage,
+      } => json!({ "item": item, "pointer": pointer, "postage": postage }),
+      BuildError::PolicyViolation {
+        api_key,
+        source,
+        max_daily_sources,
+      } => json!({
+        "api_key": api_key,
+        "source": source,
+        "max_daily_sources": max_daily_sources,
+      }),
+      BuildError::QuotaExceeded {
+        source,
+        limit,
+        used,
+        requested,
+        reset_at,
+      } => json!({
+        "source": source,
+        "limit": limit,
+        "used": used,
+        "requested": requested,
+        "reset_at": reset_at.to_rfc3339(),
+      }),
+      BuildError::RevealKeyNotPersisted(commit) | BuildError::RevealsNotPersisted(commit) => {
+        json!({ "commit": commit })
+      }
+      BuildError::TotalFeeAboveMax {
+        service_fee,
+        satpoint_fee,
+        network_fee,
+        max,
+      } => json!({
+        "service_fee": service_fee,
+        "satpoint_fee": satpoint_fee,
+        "network_fee": network_fee,
+        "total": service_fee + satpoint_fee + network_fee,
+        "max": max,
+      }),
+      BuildError::UnknownParent { txid, outpoint } => {
+        json!({ "txid": txid, "outpoint": outpoint })
+      }
+      BuildError::UnsafeDestination { address, label } => {
+        json!({ "address": address, "label": label })
+      }
+    }
+  }
+}
+
+impl std::error::Error for BuildError {}
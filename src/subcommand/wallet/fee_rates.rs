@@ -0,0 +1,106 @@
+use {super::*, crate::builder};
+
+/// Confirmation targets estimated, in blocks.
+pub const TARGETS: [u16; 3] = [1, 3, 6];
+
+/// Recommends fee rates from the node's estimates, so clients do not have
+/// to guess one for the build methods.
+#[derive(Debug, Parser)]
+pub struct FeeRates {
+  #[clap(
+    long,
+    default_value = "1.0",
+    help = "Recommend <DEFAULT_FEE_RATE> sats/vB for targets the node cannot estimate yet."
+  )]
+  pub default_fee_rate: FeeRate,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Output {
+  pub estimates: Vec<Estimate>,
+  pub mempool_min_fee_rate: f64,
+  pub min_relay_fee_rate: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Estimate {
+  pub target: u16,
+  pub fee_rate: f64,
+  /// Set when the node had no estimate and `fee_rate` is the default.
+  pub fallback: bool,
+}
+
+impl Estimate {
+  // never below what the node would relay, a lower rate would be refused
+  fn new(target: u16, estimate: Option<f64>, default: FeeRate, floor: f64) -> Self {
+    Self {
+      target,
+      fee_rate: estimate.unwrap_or(default.0).max(floor),
+      fallback: estimate.is_none(),
+    }
+  }
+}
+
+impl FeeRates {
+  pub fn build(self, options: Options) -> Result<Output> {
+    let index = Index::read_open(&options)?;
+
+    let mempool_min_fee_rate = index.mempool_min_fee_rate()?;
+    let min_relay_fee_rate = index.min_relay_fee_rate()?;
+    let floor = mempool_min_fee_rate
+      .max(min_relay_fee_rate)
+      .max(builder::MIN_RELAY_FEE_RATE);
+
+    Ok(Output {
+      estimates: TARGETS
+        .into_iter()
+        .map(|target| {
+          Ok(Estimate::new(
+            target,
+            index.fee_rate_estimate(target)?,
+            self.default_fee_rate,
+            floor,
+          ))
+        })
+        .collect::<Result<Vec<Estimate>>>()?,
+      mempool_min_fee_rate,
+      min_relay_fee_rate,
+    })
+  }
+
+  pub fn run(self, options: Options) -> Result {
+    print_json(self.build(options)?)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn missing_estimates_fall_back_to_default() {
+    assert_eq!(
+      Estimate::new(6, None, FeeRate(2.0), 1.0),
+      Estimate {
+        target: 6,
+        fee_rate: 2.0,
+        fallback: true,
+      }
+    );
+    assert_eq!(
+      Estimate::new(1, Some(12.5), FeeRate(2.0), 1.0),
+      Estimate {
+        target: 1,
+        fee_rate: 12.5,
+        fallback: false,
+      }
+    );
+  }
+
+  #[test]
+  fn estimates_never_go_below_relay_floor() {
+    assert_eq!(Estimate::new(6, Some(0.5), FeeRate(2.0), 1.0).fee_rate, 1.0);
+    assert_eq!(Estimate::new(6, None, FeeRate(0.5), 3.0).fee_rate, 3.0);
+  }
+}
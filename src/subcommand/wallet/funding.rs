@@ -0,0 +1,633 @@
+use {
+  super::*,
+  bitcoin::{AddressType, Witness},
+};
+
+/// A prevout supplied by the caller to fund a commit transaction, used
+/// instead of selecting from the source address's mempool utxos.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FundingInput {
+  pub outpoint: OutPoint,
+  pub value: u64,
+  pub script_pubkey: Script,
+}
+
+impl FromStr for FundingInput {
+  type Err = Error;
+
+  // <TXID>:<VOUT>:<VALUE>:<SCRIPT_PUBKEY_HEX>
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = s.rsplitn(3, ':');
+    let script_pubkey = parts.next().unwrap_or_default();
+    let (Some(value), Some(outpoint)) = (parts.next(), parts.next()) else {
+      bail!("invalid funding input `{s}`, expected <TXID>:<VOUT>:<VALUE>:<SCRIPT_PUBKEY_HEX>");
+    };
+
+    Ok(Self {
+      outpoint: outpoint.parse()?,
+      value: value.parse()?,
+      script_pubkey: Script::from_str(script_pubkey)?,
+    })
+  }
+}
+
+/// Returns the funding inputs as a utxo set, checking each one against
+/// `gettxout` when a client is given.
+pub(crate) fn funding_utxos(
+  inputs: &[FundingInput],
+  client: Option<&Client>,
+) -> Result<BTreeMap<OutPoint, Amount>> {
+  let mut utxos = BTreeMap::new();
+  for input in inputs {
+    if let Some(client) = client {
+      let outpoint = input.outpoint;
+      let txout = client
+        .get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?
+        .ok_or(BuildError::FundingInputSpent(outpoint))?;
+      let script_pubkey = Script::from(txout.script_pub_key.hex);
+      if txout.value.to_sat() != input.value || script_pubkey != input.script_pubkey {
+        return Err(
+          BuildError::FundingInputMismatch {
+            outpoint,
+            value: txout.value.to_sat(),
+            script_pubkey,
+          }
+          .into(),
+        );
+      }
+    }
+
+    if utxos
+      .insert(input.outpoint, Amount::from_sat(input.value))
+      .is_some()
+    {
+      bail!("funding input {} is listed more than once", input.outpoint);
+    }
+  }
+  Ok(utxos)
+}
+
+/// Refuses funding inputs whose script is not of `input_type`, the type of
+/// the source, since witness sizes are estimated from it.
+pub(crate) fn check_funding_scripts(
+  inputs: &[FundingInput],
+  input_type: AddressType,
+  network: Network,
+) -> Result {
+  for input in inputs {
+    let script_type = Address::from_script(&input.script_pubkey, network)
+      .ok()
+      .and_then(|address| address.address_type());
+    if script_type != Some(input_type) {
+      return Err(
+        BuildError::FundingInputScriptType {
+          outpoint: input.outpoint,
+          expected: input_type,
+        }
+        .into(),
+      );
+    }
+  }
+  Ok(())
+}
+
+/// Inscriptions on the funding inputs, looked up under the address of
+/// each input's script since the inputs need not be held by the source.
+pub(crate) fn funding_inscriptions(
+  index: &Index,
+  inputs: &[FundingInput],
+  mysql: Option<&MysqlDatabase>,
+) -> Result<BTreeMap<SatPoint, InscriptionId>> {
+  let network = index.options().chain().network();
+  let scripts = inputs
+    .iter()
+    .map(|input| &input.script_pubkey)
+    .collect::<HashSet<&Script>>();
+
+  let mut inscriptions = BTreeMap::new();
+  for script in scripts {
+    let address = Address::from_script(script, network)?;
+    inscriptions.extend(address_inscriptions(
+      index,
+      &address,
+      inputs
+        .iter()
+        .filter(|input| input.script_pubkey == *script)
+        .map(|input| &input.outpoint),
+      mysql,
+    )?);
+  }
+  Ok(inscriptions)
+}
+
+/// Where commit change goes. Funding inputs held elsewhere than `source`
+/// need `change_address`, their change would otherwise be paid to the
+/// source.
+pub(crate) fn change_address(
+  change_address: Option<Address>,
+  source: &Address,
+  funding_inputs: &[FundingInput],
+) -> Result<Address> {
+  if let Some(change_address) = change_address {
+    return Ok(change_address);
+  }
+
+  match funding_inputs
+    .iter()
+    .find(|input| input.script_pubkey != source.script_pubkey())
+  {
+    Some(input) => Err(BuildError::ChangeAddressRequired(input.outpoint).into()),
+    None => Ok(source.clone()),
+  }
+}
+
+/// Returns the outputs the caller pinned the build to, checking with
+/// `gettxout` that each is unspent and held by `source`.
+pub(crate) fn pinned_utxos(
+  inputs: &[OutPoint],
+  source: &Address,
+  client: &Client,
+) -> Result<BTreeMap<OutPoint, Amount>> {
+  let mut utxos = BTreeMap::new();
+  for outpoint in inputs {
+    let tx_out = client
+      .get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?
+      .map(|txout| TxOut {
+        value: txout.value.to_sat(),
+        script_pubkey: Script::from(txout.script_pub_key.hex),
+      });
+    if utxos
+      .insert(*outpoint, pinned_utxo(*outpoint, tx_out, source)?)
+      .is_some()
+    {
+      bail!("input {outpoint} is listed more than once");
+    }
+  }
+  Ok(utxos)
+}
+
+fn pinned_utxo(outpoint: OutPoint, tx_out: Option<TxOut>, source: &Address) -> Result<Amount> {
+  let tx_out = tx_out.ok_or(BuildError::FundingInputSpent(outpoint))?;
+  if tx_out.script_pubkey != source.script_pubkey() {
+    return Err(
+      BuildError::InputNotOwned {
+        outpoint,
+        source: source.clone(),
+      }
+      .into(),
+    );
+  }
+  Ok(Amount::from_sat(tx_out.value))
+}
+
+/// Refuses pinned `inputs` holding any of `inscriptions`, which would
+/// otherwise be left out of the build without a word.
+pub(crate) fn check_pinned_cardinal(
+  inputs: &[OutPoint],
+  inscriptions: &BTreeMap<SatPoint, InscriptionId>,
+) -> Result {
+  match inscriptions
+    .keys()
+    .find(|satpoint| inputs.contains(&satpoint.outpoint))
+  {
+    Some(satpoint) => Err(
+      BuildError::AlreadyInscribed {
+        satpoint: *satpoint,
+      }
+      .into(),
+    ),
+    None => Ok(()),
+  }
+}
+
+/// Takes the outputs the caller excluded out of `utxos`, returning those
+/// it held.
+pub(crate) fn exclude_utxos(
+  utxos: &mut BTreeMap<OutPoint, Amount>,
+  exclude: &[OutPoint],
+) -> BTreeMap<OutPoint, Amount> {
+  exclude
+    .iter()
+    .filter_map(|outpoint| Some((*outpoint, utxos.remove(outpoint)?)))
+    .collect()
+}
+
+/// Points out the `excluded` outputs when the build came up short without
+/// them.
+pub(crate) fn explain_exclusion<T>(
+  result: Result<T>,
+  excluded: &BTreeMap<OutPoint, Amount>,
+) -> Result<T> {
+  match result {
+    Err(err)
+      if !excluded.is_empty()
+        && matches!(
+          err.downcast_ref::<BuildError>(),
+          Some(BuildError::InsufficientFunds { .. })
+        ) =>
+    {
+      let message = format!(
+        "{err}, after excluding {} inputs holding {} sats",
+        excluded.len(),
+        excluded.values().map(|amount| amount.to_sat()).sum::<u64>()
+      );
+      Err(err.context(message))
+    }
+    result => result,
+  }
+}
+
+/// The utxos of `source` with fewer than `min_confirmations`, which coin
+/// selection leaves alone since a replaced parent would invalidate the
+/// build. Unconfirmed change of the service's own broadcasts is kept when
+/// `allow_unconfirmed_self` is set, chained mints spend it.
+pub(crate) fn shallow_utxos(
+  index: &Index,
+  source: &Address,
+  min_confirmations: u64,
+  allow_unconfirmed_self: bool,
+  mysql: Option<&MysqlDatabase>,
+) -> Result<Vec<OutPoint>> {
+  if min_confirmations == 0 {
+    return Ok(Vec::new());
+  }
+
+  let own = match mysql {
+    Some(mysql) if allow_unconfirmed_self => mysql.get_pending_broadcast_txids()?,
+    _ => HashSet::new(),
+  };
+
+  Ok(shallow(
+    &index.get_unspent_output_heights(&source.to_string())?,
+    index.node_height()?,
+    min_confirmations,
+    &own,
+  ))
+}
+
+fn shallow(
+  heights: &BTreeMap<OutPoint, Option<u64>>,
+  tip: u64,
+  min_confirmations: u64,
+  own: &HashSet<Txid>,
+) -> Vec<OutPoint> {
+  heights
+    .iter()
+    .filter(|(outpoint, height)| match height {
+      Some(height) => tip.saturating_sub(*height) + 1 < min_confirmations,
+      None => !own.contains(&outpoint.txid),
+    })
+    .map(|(outpoint, _)| *outpoint)
+    .collect()
+}
+
+/// Points out the `shallow` outputs when the build came up short without
+/// them, the shortfall then being of confirmed funds.
+pub(crate) fn explain_shallow<T>(
+  result: Result<T>,
+  shallow: &BTreeMap<OutPoint, Amount>,
+  min_confirmations: u64,
+) -> Result<T> {
+  match result {
+    Err(err)
+      if !shallow.is_empty()
+        && matches!(
+          err.downcast_ref::<BuildError>(),
+          Some(BuildError::InsufficientFunds { .. } | BuildError::NoCardinalUtxos { .. })
+        ) =>
+    {
+      let message = format!(
+        "{err}, counting only inputs with at least {min_confirmations} confirmations, {} inputs holding {} sats have fewer",
+        shallow.len(),
+        shallow.values().map(|amount| amount.to_sat()).sum::<u64>()
+      );
+      Err(err.context(message))
+    }
+    result => result,
+  }
+}
+
+/// Estimates what spending every utxo into `outputs` plus change would cost,
+/// for reporting how far short the utxos fall.
+pub(crate) fn insufficient_funds(
+  input_type: AddressType,
+  utxos: &BTreeMap<OutPoint, Amount>,
+  outputs: &[(Address, Amount)],
+  change: &Address,
+  fee_rate: FeeRate,
+) -> BuildError {
+  let witness_size = if input_type == AddressType::P2tr {
+    TransactionBuilder::SCHNORR_SIGNATURE_SIZE
+  } else {
+    TransactionBuilder::P2WPKH_WINETSS_SIZE
+  };
+
+  let tx = Transaction {
+    version: 1,
+    lock_time: PackedLockTime::ZERO,
+    input: utxos
+      .keys()
+      .map(|outpoint| TxIn {
+        previous_output: *outpoint,
+        script_sig: Script::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::from_vec(vec![vec![0; witness_size]]),
+      })
+      .collect(),
+    output: outputs
+      .iter()
+      .chain([(change.clone(), Amount::ZERO)].iter())
+      .map(|(address, amount)| TxOut {
+        script_pubkey: address.script_pubkey(),
+        value: amount.to_sat(),
+      })
+      .collect(),
+  };
+
+  BuildError::InsufficientFunds {
+    available: utxos.values().map(|amount| amount.to_sat()).sum(),
+    required: outputs
+      .iter()
+      .map(|(_, amount)| amount.to_sat())
+      .sum::<u64>()
+      + fee_rate.fee(tx.vsize()).to_sat(),
+  }
+}
+
+/// Uses the caller's script for funding inputs and `source`'s otherwise.
+pub(crate) fn input_script(
+  funding_inputs: &[FundingInput],
+  outpoint: OutPoint,
+  source: &Address,
+) -> Script {
+  funding_inputs
+    .iter()
+    .find(|input| input.outpoint == outpoint)
+    .map(|input| input.script_pubkey.clone())
+    .unwrap_or_else(|| source.script_pubkey())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn from_str() {
+    let script_pubkey = recipient().script_pubkey();
+    assert_eq!(
+      format!("{}:10000:{script_pubkey:x}", outpoint(1))
+        .parse::<FundingInput>()
+        .unwrap(),
+      FundingInput {
+        outpoint: outpoint(1),
+        value: 10000,
+        script_pubkey,
+      }
+    );
+    assert!("10000:0014".parse::<FundingInput>().is_err());
+  }
+
+  #[test]
+  fn unverified_funding_utxos() {
+    let input = FundingInput {
+      outpoint: outpoint(1),
+      value: 10000,
+      script_pubkey: recipient().script_pubkey(),
+    };
+
+    assert_eq!(
+      funding_utxos(std::slice::from_ref(&input), None).unwrap(),
+      [(outpoint(1), Amount::from_sat(10000))].into()
+    );
+    assert!(funding_utxos(&[input.clone(), input], None).is_err());
+  }
+
+  #[test]
+  fn funding_scripts_must_match_the_source_type() {
+    let input = |script_pubkey: Script| FundingInput {
+      outpoint: outpoint(1),
+      value: 10000,
+      script_pubkey,
+    };
+
+    assert!(check_funding_scripts(
+      &[input(change(1).script_pubkey())],
+      AddressType::P2wpkh,
+      Network::Regtest
+    )
+    .is_ok());
+    assert_eq!(
+      check_funding_scripts(
+        &[input(change(1).script_pubkey())],
+        AddressType::P2tr,
+        Network::Regtest
+      )
+      .unwrap_err()
+      .downcast::<BuildError>()
+      .unwrap(),
+      BuildError::FundingInputScriptType {
+        outpoint: outpoint(1),
+        expected: AddressType::P2tr,
+      }
+    );
+    assert!(check_funding_scripts(
+      &[input(Script::new_op_return(&[]))],
+      AddressType::P2wpkh,
+      Network::Regtest
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn change_of_foreign_funding_inputs_needs_an_address() {
+    let input = |script_pubkey: Script| FundingInput {
+      outpoint: outpoint(1),
+      value: 10000,
+      script_pubkey,
+    };
+
+    assert_eq!(
+      change_address(None, &recipient(), &[input(recipient().script_pubkey())]).unwrap(),
+      recipient()
+    );
+    assert_eq!(
+      change_address(
+        Some(change(0)),
+        &recipient(),
+        &[input(change(1).script_pubkey())]
+      )
+      .unwrap(),
+      change(0)
+    );
+    assert_eq!(
+      change_address(None, &recipient(), &[input(change(1).script_pubkey())])
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::ChangeAddressRequired(outpoint(1))
+    );
+  }
+
+  #[test]
+  fn pinned_inputs_must_be_unspent_and_held_by_source() {
+    assert_eq!(
+      pinned_utxo(outpoint(1), Some(tx_out(10000, recipient())), &recipient()).unwrap(),
+      Amount::from_sat(10000)
+    );
+    assert_eq!(
+      pinned_utxo(outpoint(1), None, &recipient())
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::FundingInputSpent(outpoint(1))
+    );
+    assert_eq!(
+      pinned_utxo(outpoint(1), Some(tx_out(10000, change(0))), &recipient())
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::InputNotOwned {
+        outpoint: outpoint(1),
+        source: recipient(),
+      }
+    );
+  }
+
+  #[test]
+  fn pinned_inputs_must_be_cardinal() {
+    let inscriptions = [(satpoint(2, 0), inscription_id(1))].into();
+
+    assert!(check_pinned_cardinal(&[outpoint(1)], &inscriptions).is_ok());
+    assert_eq!(
+      check_pinned_cardinal(&[outpoint(1), outpoint(2)], &inscriptions)
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::AlreadyInscribed {
+        satpoint: satpoint(2, 0)
+      }
+    );
+  }
+
+  #[test]
+  fn shortfall_names_excluded_inputs() {
+    let mut utxos = [
+      (outpoint(1), Amount::from_sat(5000)),
+      (outpoint(2), Amount::from_sat(100_000)),
+    ]
+    .into();
+    let excluded = exclude_utxos(&mut utxos, &[outpoint(2), outpoint(3)]);
+    assert_eq!(excluded, [(outpoint(2), Amount::from_sat(100_000))].into());
+    assert_eq!(utxos, [(outpoint(1), Amount::from_sat(5000))].into());
+
+    let err = explain_exclusion::<()>(
+      Err(
+        BuildError::InsufficientFunds {
+          available: 5000,
+          required: 10000,
+        }
+        .into(),
+      ),
+      &excluded,
+    )
+    .unwrap_err();
+    assert!(err
+      .to_string()
+      .ends_with(", after excluding 1 inputs holding 100000 sats"));
+    assert!(matches!(
+      err.downcast_ref::<BuildError>(),
+      Some(BuildError::InsufficientFunds { .. })
+    ));
+
+    assert_eq!(
+      explain_exclusion::<()>(Err(anyhow!("no utxos")), &excluded)
+        .unwrap_err()
+        .to_string(),
+      "no utxos"
+    );
+  }
+
+  #[test]
+  fn shallow_utxos_are_held_back() {
+    let heights = [
+      (outpoint(1), Some(100)),
+      (outpoint(2), Some(98)),
+      (outpoint(3), None),
+      (OutPoint::new(txid(4), 1), None),
+    ]
+    .into();
+
+    assert_eq!(
+      shallow(&heights, 100, 3, &HashSet::new()),
+      [outpoint(1), outpoint(3), OutPoint::new(txid(4), 1)]
+    );
+    assert_eq!(shallow(&heights, 100, 1, &[txid(4)].into()), [outpoint(3)]);
+
+    let mut utxos = [
+      (outpoint(1), Amount::from_sat(5000)),
+      (outpoint(2), Amount::from_sat(100_000)),
+    ]
+    .into();
+    let held = exclude_utxos(&mut utxos, &shallow(&heights, 100, 3, &HashSet::new()));
+    assert_eq!(utxos, [(outpoint(2), Amount::from_sat(100_000))].into());
+
+    let err = explain_shallow::<()>(
+      Err(
+        BuildError::InsufficientFunds {
+          available: 100_000,
+          required: 200_000,
+        }
+        .into(),
+      ),
+      &held,
+      3,
+    )
+    .unwrap_err();
+    assert!(err.to_string().starts_with("inputs provide 100000 sats"));
+    assert!(err.to_string().ends_with(
+      ", counting only inputs with at least 3 confirmations, 1 inputs holding 5000 sats have fewer"
+    ));
+  }
+
+  #[test]
+  fn shortfall() {
+    let utxos = [(outpoint(1), Amount::from_sat(5000))].into();
+    let outputs = [(recipient(), Amount::from_sat(10000))];
+
+    let BuildError::InsufficientFunds {
+      available,
+      required,
+    } = insufficient_funds(
+      AddressType::P2wpkh,
+      &utxos,
+      &outputs,
+      &change(0),
+      FeeRate::try_from(1.0).unwrap(),
+    )
+    else {
+      panic!("expected insufficient funds");
+    };
+
+    assert_eq!(available, 5000);
+    assert!(required > 10000);
+  }
+
+  #[test]
+  fn caller_scripts_are_used() {
+    let script_pubkey = change(1).script_pubkey();
+    let inputs = [FundingInput {
+      outpoint: outpoint(1),
+      value: 10000,
+      script_pubkey: script_pubkey.clone(),
+    }];
+
+    assert_eq!(
+      input_script(&inputs, outpoint(1), &recipient()),
+      script_pubkey
+    );
+    assert_eq!(
+      input_script(&inputs, outpoint(2), &recipient()),
+      recipient().script_pubkey()
+    );
+  }
+}
@@ -65,7 +65,11 @@ impl Inscribe {
 
     let mut utxos = index.get_unspent_outputs(Wallet::load(&options)?)?;
 
-    let inscriptions = index.get_inscriptions(None)?;
+    let inscriptions = index.get_inscriptions_for_outpoints(
+      utxos
+        .keys()
+        .chain(self.satpoint.iter().map(|satpoint| &satpoint.outpoint)),
+    )?;
 
     let commit_tx_change = [get_change_address(&client)?, get_change_address(&client)?];
 
@@ -169,12 +173,12 @@ impl Inscribe {
           outpoint: *outpoint,
           offset: 0,
         })
-        .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
+        .ok_or(BuildError::NoCardinalUtxos { min_value: None })?
     };
 
     for (inscribed_satpoint, inscription_id) in &inscriptions {
       if inscribed_satpoint == &satpoint {
-        return Err(anyhow!("sat at {} already inscribed", satpoint));
+        return Err(BuildError::AlreadyInscribed { satpoint }.into());
       }
 
       if inscribed_satpoint.outpoint == satpoint.outpoint {
@@ -255,8 +259,15 @@ impl Inscribe {
       .checked_sub(fee.to_sat())
       .context("commit transaction output value insufficient to pay transaction fee")?;
 
-    if reveal_tx.output[0].value < reveal_tx.output[0].script_pubkey.dust_value().to_sat() {
-      bail!("commit transaction output would be dust");
+    let dust_value = reveal_tx.output[0].script_pubkey.dust_value().to_sat();
+    if reveal_tx.output[0].value < dust_value {
+      return Err(
+        BuildError::DustOutput {
+          value: reveal_tx.output[0].value,
+          min: dust_value,
+        }
+        .into(),
+      );
     }
 
     let mut sighash_cache = SighashCache::new(&mut reveal_tx);
@@ -11,8 +11,8 @@ pub(crate) fn run(options: Options) -> Result {
   let index = Index::open(&options)?;
   index.update()?;
 
-  let inscriptions = index.get_inscriptions(None)?;
   let unspent_outputs = index.get_unspent_outputs(Wallet::load(&options)?)?;
+  let inscriptions = index.get_inscriptions_for_outpoints(unspent_outputs.keys())?;
 
   let explorer = match options.chain() {
     Chain::Mainnet => "https://ordinals.com/inscription/",
@@ -24,13 +24,11 @@ pub(crate) fn run(options: Options) -> Result {
   let mut output = Vec::new();
 
   for (location, inscription) in inscriptions {
-    if unspent_outputs.contains_key(&location.outpoint) {
-      output.push(Output {
-        location,
-        inscription,
-        explorer: format!("{explorer}{inscription}"),
-      });
-    }
+    output.push(Output {
+      location,
+      inscription,
+      explorer: format!("{explorer}{inscription}"),
+    });
   }
 
   print_json(&output)?;
@@ -1,21 +1,24 @@
-use crate::index::{ConstructTransaction, MysqlDatabase, TransactionOutputArray};
+use crate::{
+  builder::{self, CommitOutputBreakdown, CommitParams, Inscribed, KeySource},
+  index::{
+    ConstructTransaction, ContentHash, MysqlDatabase, RevealExpectation, Revenue,
+    TransactionOutputArray,
+  },
+  reveal_key::{RevealKeys, RevealSeed},
+};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::sha256;
 use bitcoin::psbt::Psbt;
-use bitcoin::{consensus::encode::serialize_hex, AddressType};
 use bitcoincore_rpc::RawTx;
 use {
   super::*,
-  bitcoin::{
-    blockdata::{opcodes, script},
-    policy::MAX_STANDARD_TX_WEIGHT,
-    schnorr::{TapTweak, TweakedKeyPair, TweakedPublicKey, UntweakedKeyPair},
-    secp256k1::{
-      self, constants::SCHNORR_SIGNATURE_SIZE, rand, schnorr::Signature, Secp256k1, XOnlyPublicKey,
-    },
-    util::sighash::{Prevouts, SighashCache},
-    util::taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder},
-    PackedLockTime, SchnorrSighashType, Witness,
+  funding::{
+    change_address, check_funding_scripts, check_pinned_cardinal, exclude_utxos, explain_exclusion,
+    explain_shallow, funding_inscriptions, funding_utxos, input_script, pinned_utxos,
+    shallow_utxos, FundingInput,
   },
-  std::collections::BTreeSet,
+  recover::RecoveryKey,
+  warning::{Warning, Warnings},
 };
 
 #[derive(Debug, Serialize)]
@@ -29,6 +32,65 @@ pub struct Output {
   pub network_fee: u64,
   pub commit_vsize: u64,
   pub commit_fee: u64,
+  pub commit_fee_rate: f64,
+  pub reveal_fee_rate: f64,
+  /// What each commit output pays for, in the order of `reveal`.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub commit_breakdown: Vec<CommitOutputBreakdown>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub inscribed_sats: Vec<InscribedSat>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub warnings: Vec<Warning>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub compression: Option<Compression>,
+  /// The offset of the inscribed sat within each reveal's first output.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pointer: Option<u64>,
+  /// With a parent, the reveal as a PSBT. The source signs the parent's
+  /// input before broadcasting it in place of `reveal`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub parent_reveal: Option<String>,
+  /// Secret keys sweeping each commit output back if its reveal is lost,
+  /// only when asked for. Whoever holds them can take the commit funds.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub recovery_secret_keys: Vec<RecoveryKey>,
+  /// Built only to validate and price, not to be signed.
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub dry_run: bool,
+}
+
+/// How much compressing the content saved. Fees and weights are for the
+/// compressed body, which is what goes on chain.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Compression {
+  pub content_encoding: ContentEncoding,
+  pub content_size: u64,
+  pub compressed_size: u64,
+}
+
+/// The sat a reveal inscribes, located within the utxo that funds the commit.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InscribedSat {
+  pub funding_outpoint: OutPoint,
+  pub funding_offset: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sat: Option<Sat>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub rarity: Option<Rarity>,
+}
+
+/// What a mint can spend, see `Mint::funds`. Whitelisted sources pay no
+/// service fee and remints pay `additional_service_fee` on top.
+pub(crate) struct Funds {
+  pub(crate) utxos: BTreeMap<OutPoint, Amount>,
+  /// Held back for having too few confirmations.
+  pub(crate) shallow: BTreeMap<OutPoint, Amount>,
+  pub(crate) satpoints: Vec<SatPoint>,
+  pub(crate) inscriptions: BTreeMap<SatPoint, InscriptionId>,
+  pub(crate) is_whitelist: bool,
+  pub(crate) additional_service_fee: Amount,
+  /// The commit a remint replaces.
+  pub(crate) recommit: Option<Transaction>,
 }
 
 #[derive(Debug, Parser)]
@@ -39,21 +101,185 @@ pub struct Mint {
   pub destination: Option<Address>,
   #[clap(long, help = "Send inscription from <SOURCE>.")]
   pub source: Address,
+  #[clap(
+    long = "change",
+    help = "Send change to <CHANGE_ADDRESS> instead of back to --source."
+  )]
+  pub change_address: Option<Address>,
   #[clap(long, help = "Content type of mint, '.txt'.")]
   pub extension: Option<String>,
-  #[clap(long, help = "Content of mint.")]
+  #[clap(
+    long,
+    help = "Inscribe with MIME type <CONTENT_TYPE> as given, instead of the one for --extension."
+  )]
+  pub content_type: Option<String>,
+  #[clap(
+    long,
+    help = "Tag the inscription with <METADATA> JSON, inscribed as CBOR."
+  )]
+  pub metadata: Option<serde_json::Value>,
+  #[clap(long, help = "Tag the inscription with the <METAPROTOCOL> it follows.")]
+  pub metaprotocol: Option<String>,
+  #[clap(
+    long,
+    help = "Inscribe the sat <POINTER> sats into the reveal's output instead of the first, must be below --target-postage."
+  )]
+  pub pointer: Option<u64>,
+  #[clap(long, default_value = "", help = "Content of mint.")]
   pub content: String,
+  #[clap(
+    long,
+    help = "Read --content as <CONTENT_ENCODING>, base64 for binary content."
+  )]
+  pub content_encoding: Option<ContentFormat>,
+  #[clap(
+    long,
+    conflicts_with = "content",
+    help = "Inscribe the raw bytes of <CONTENT_FILE> instead of --content."
+  )]
+  pub content_file: Option<PathBuf>,
   #[clap(long, help = "Repeat count of mint.")]
   pub repeat: Option<u64>,
   #[clap(long, help = "Target postage.")]
   pub target_postage: Amount,
   #[clap(long, help = "Remint comint id.")]
   pub remint: Option<Txid>,
+  #[clap(
+    long,
+    help = "Inscribe as a child of <PARENT>, an inscription held by --source that the reveal spends."
+  )]
+  pub parent: Option<InscriptionId>,
+  #[clap(
+    long,
+    requires = "parent",
+    help = "Send the parent to <PARENT_DESTINATION> instead of back to --source."
+  )]
+  pub parent_destination: Option<Address>,
+  #[clap(long, help = "Allow sending inscription to a burn address.")]
+  pub allow_burn: bool,
+  #[clap(
+    long = "funding-input",
+    help = "Fund the commit only with <FUNDING_INPUT>, given as <TXID>:<VOUT>:<VALUE>:<SCRIPT_PUBKEY_HEX>, may be repeated."
+  )]
+  pub funding_inputs: Vec<FundingInput>,
+  #[clap(
+    long = "input",
+    help = "Fund the commit only with <INPUT>, an unspent output of --source given as <TXID>:<VOUT>, may be repeated."
+  )]
+  pub inputs: Vec<OutPoint>,
+  #[clap(
+    long = "exclude",
+    help = "Never spend <EXCLUDE>, an output of --source given as <TXID>:<VOUT>, may be repeated."
+  )]
+  pub exclude_inputs: Vec<OutPoint>,
+  #[clap(
+    long,
+    default_value = "0",
+    help = "Only spend outputs of --source with at least <MIN_CONFIRMATIONS> confirmations."
+  )]
+  pub min_confirmations: u64,
+  #[clap(
+    long,
+    help = "Spend unconfirmed change of our own earlier builds despite --min-confirmations."
+  )]
+  pub allow_unconfirmed_self: bool,
+  #[clap(long, help = "Check funding inputs against the node with gettxout.")]
+  pub verify_inputs: bool,
+  #[clap(long, help = "Redeem <VOUCHER> for a free or discounted service fee.")]
+  pub voucher: Option<String>,
+  #[clap(
+    long,
+    help = "Build the commit at <COMMIT_FEE_RATE> sats/vB instead of --fee-rate."
+  )]
+  pub commit_fee_rate: Option<FeeRate>,
+  #[clap(
+    long,
+    help = "Build reveals at <REVEAL_FEE_RATE> sats/vB instead of --fee-rate."
+  )]
+  pub reveal_fee_rate: Option<FeeRate>,
+  #[clap(
+    long,
+    help = "Add <REVEAL_FEE_BUFFER_RATE> sats/vB to the reveal fee rate, so reveals still confirm if the commit is slow to."
+  )]
+  pub reveal_fee_buffer_rate: Option<FeeRate>,
+  #[clap(
+    long,
+    help = "Inscribe the content compressed with <COMPRESS>, br or gzip, and tag it with that content encoding."
+  )]
+  pub compress: Option<ContentEncoding>,
+  #[clap(long, help = "Tag revenue rows with <PURPOSE>.")]
+  pub purpose: Option<String>,
+  #[clap(
+    long,
+    help = "Check and price the mint without recording it. Reveals are signed with throwaway keys, do not sign or broadcast the result."
+  )]
+  pub dry_run: bool,
+  #[clap(
+    long,
+    help = "Refuse the build if its fees add up to more than <MAX_TOTAL_FEE> sats."
+  )]
+  pub max_total_fee: Option<u64>,
+  #[clap(
+    long,
+    help = "Refuse content this source already built or was inscribed with, as recorded in the database."
+  )]
+  pub reject_duplicate: bool,
+  #[clap(
+    long,
+    help = "Return the secret keys that sweep the commit outputs back if a reveal is lost. Keep them secret."
+  )]
+  pub return_recovery_key: bool,
+  #[clap(
+    long,
+    help = "Derive the reveal key from <REVEAL_SEED>, 32 random bytes of hex unique to this mint, so that it always builds the same reveals."
+  )]
+  pub reveal_seed: Option<RevealSeed>,
+  /// Derive the reveal key so the reveals can be bumped later. Only used
+  /// with a database to keep the salt in.
+  #[clap(skip)]
+  pub reveal_keys: Option<Arc<RevealKeys>>,
+  /// Record what each commit output pays for in the revenue table. Only
+  /// used with a database.
+  #[clap(skip)]
+  pub record_revenue: bool,
 }
 
 impl Mint {
   pub const SERVICE_FEE: Amount = Amount::from_sat(3000);
 
+  /// The service fee per inscription, nothing for whitelisted sources
+  /// whether they mint a single item or a batch.
+  pub(crate) fn service_fee(is_whitelist: bool, service_fee: Option<Amount>) -> Amount {
+    if is_whitelist {
+      Amount::ZERO
+    } else {
+      service_fee.unwrap_or(Self::SERVICE_FEE)
+    }
+  }
+
+  /// Refuses to remint `recommit` when the rebuilt `commit` pays reveal
+  /// addresses it did not, as it does when the seed or the content differ
+  /// from those of the replaced mint.
+  pub(crate) fn check_remint(
+    recommit: &Transaction,
+    commit: &Transaction,
+    reveals: usize,
+  ) -> Result {
+    for output in commit.output.iter().take(reveals) {
+      if !recommit
+        .output
+        .iter()
+        .any(|replaced| replaced.script_pubkey == output.script_pubkey)
+      {
+        bail!(
+          "remint {} was not built from this reveal seed and content",
+          recommit.txid()
+        );
+      }
+    }
+    Ok(())
+  }
+
   pub fn build(
     self,
     options: Options,
@@ -62,124 +288,292 @@ impl Mint {
     mysql: Option<Arc<MysqlDatabase>>,
   ) -> Result<Output> {
     let repeat: u64 = self.repeat.unwrap_or(1);
-    let extension = "data.".to_owned() + &self.extension.unwrap_or(".txt".to_owned());
+    let (commit_fee_rate, reveal_fee_rate) =
+      builder::component_fee_rates(self.fee_rate, self.commit_fee_rate, self.reveal_fee_rate)?;
+    let reveal_fee_rate = builder::buffered_fee_rate(reveal_fee_rate, self.reveal_fee_buffer_rate)?;
+
+    let content_type =
+      Media::content_type_for_mint(self.content_type.as_deref(), self.extension.as_deref())?;
 
-    let inscription = Inscription::from_content(options.chain(), &extension, self.content)?;
+    let body = self.body()?;
+    let content_size = u64::try_from(body.len())?;
+    let inscription =
+      Inscription::from_content(options.chain(), &content_type, body, self.compress)?
+        .with_metadata(self.metadata.as_ref(), self.metaprotocol.clone());
+    let inscription = match self.pointer {
+      Some(pointer) => inscription.with_pointer(pointer),
+      None => inscription,
+    };
+    let compression = match self.compress {
+      Some(content_encoding) => Some(Compression {
+        content_encoding,
+        content_size,
+        compressed_size: u64::try_from(inscription.content_length().unwrap_or_default())?,
+      }),
+      None => None,
+    };
+
+    let content_hashes = vec![ContentHash::of(inscription.body().unwrap_or_default())];
+    if self.reject_duplicate {
+      Self::check_duplicates(&self.source, &content_hashes, mysql.as_deref())?;
+    }
 
-    log::info!("Open index...");
+    // checked before building and only redeemed once a commit exists, so a
+    // failed build leaves the voucher unused
+    let voucher = match &self.voucher {
+      Some(token) => {
+        let mysql = mysql
+          .as_ref()
+          .ok_or_else(|| anyhow!("vouchers require a mysql database"))?;
+        Some((token, mysql.get_voucher_policy(token)?))
+      }
+      None => None,
+    };
+
+    let start = Instant::now();
+    tracing::info!("Open index...");
     let index = Index::read_open(&options)?;
     // index.update()?;
 
+    let mut warnings = Warnings::default();
+    warnings.check_index(&index);
+    warnings.check_fee_rate(
+      &index,
+      if commit_fee_rate.0 > reveal_fee_rate.0 {
+        commit_fee_rate
+      } else {
+        reveal_fee_rate
+      },
+    );
+
     let source = self.source;
+    if let Some(destination) = &self.destination {
+      warnings.check_destination(destination, &source);
+    }
     let reveal_tx_destination = self.destination.unwrap_or_else(|| source.clone());
 
-    if !source.is_valid_for_network(options.chain().network()) {
-      bail!("Address `{}` is not valid for {}", source, options.chain());
-    }
-    if !reveal_tx_destination.is_valid_for_network(options.chain().network()) {
-      bail!(
-        "Address `{}` is not valid for {}",
-        reveal_tx_destination,
-        options.chain()
-      );
+    let address_type = check_source_address(&source, &options)?;
+    check_funding_scripts(
+      &self.funding_inputs,
+      address_type,
+      options.chain().network(),
+    )?;
+    check_destination_address(&reveal_tx_destination, &options, self.allow_burn)?;
+    let change = change_address(self.change_address, &source, &self.funding_inputs)?;
+    check_source_address(&change, &options)?;
+    if let Some(parent_destination) = &self.parent_destination {
+      check_destination_address(parent_destination, &options, self.allow_burn)?;
     }
 
-    // check address types, only support p2tr and p2wpkh
-    let address_type = if let Some(address_type) = source.address_type() {
-      if (address_type == AddressType::P2tr) || (address_type == AddressType::P2wpkh) {
-        address_type
-      } else {
-        bail!(
-          "Address type `{}` is not valid, only support p2tr and p2wpkh",
-          address_type
-        );
-      }
-    } else {
-      bail!("Address `{}` is not valid for {}", source, options.chain());
-    };
-
     let service_address = service_address.unwrap_or(source.clone());
+    check_address_type(&service_address, &options)?;
 
-    log::info!("Get utxo...");
-    let query_address = &format!("{}", source);
-    let mut additional_service_fee = Amount::ZERO;
-    let (mut utxos, satpoints) = if let Some(commit_id) = self.remint {
-      additional_service_fee = Amount::from_sat(3000);
-      let (mut utxos, recommit_tx) =
-        index.get_unspent_outputs_by_commit_id(query_address, BTreeMap::new(), commit_id)?;
-      (
-        utxos,
-        recommit_tx
-          .input
-          .iter()
-          .map(|item| SatPoint {
-            outpoint: item.previous_output,
-            offset: 0,
-          })
-          .collect::<Vec<_>>(),
-      )
-    } else {
-      (
-        index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?,
-        vec![],
-      )
+    let Funds {
+      mut utxos,
+      shallow,
+      satpoints,
+      inscriptions,
+      is_whitelist,
+      additional_service_fee,
+      recommit,
+    } = Self::funds(
+      &index,
+      &source,
+      &self.funding_inputs,
+      &self.inputs,
+      self.verify_inputs,
+      self.remint,
+      self.min_confirmations,
+      self.allow_unconfirmed_self,
+      mysql.as_deref(),
+    )?;
+    let excluded = exclude_utxos(&mut utxos, &self.exclude_inputs);
+
+    let parent = self
+      .parent
+      .map(|id| {
+        Self::parent(
+          id,
+          self.parent_destination.clone(),
+          &source,
+          &inscriptions,
+          &utxos,
+          &self.funding_inputs,
+        )
+      })
+      .transpose()?;
+    let inscription = match &parent {
+      Some(parent) => inscription.with_parent(parent.id),
+      None => inscription,
     };
 
-    utxos.retain(|_, amount| amount.to_sat() > 546);
+    let commit_tx_change = [change.clone(), change.clone()];
 
-    let mut is_whitelist = false;
-    let inscriptions = if let Some(mysql) = mysql {
-      log::info!("Get inscriptions by mysql...");
-      is_whitelist = mysql.is_whitelist(query_address);
-      mysql.get_inscription_by_address(query_address)?
-    } else {
-      log::info!("Get inscriptions by redb...");
-      index.get_inscriptions(None)?
+    let service_fee = Self::service_fee(is_whitelist, service_fee);
+    let service_fee = match voucher {
+      Some((_, policy)) => policy.apply(service_fee),
+      None => service_fee,
     };
 
-    let commit_tx_change = [source.clone(), source.clone()];
+    // a dry run leaves no trace, its reveals are signed with random keys
+    // that are never stored
+    let records = mysql.as_ref().filter(|_| !self.dry_run);
 
-    let service_fee = if is_whitelist {
-      Amount::ZERO
-    } else {
-      service_fee.unwrap_or(Self::SERVICE_FEE)
+    // a seeded key is derived again from the seed, it needs no salt
+    let reveal_key = match (&self.reveal_keys, records) {
+      (Some(keys), Some(_)) if self.reveal_seed.is_none() => {
+        let salt = RevealKeys::salt();
+        Some((keys.key_pair(&salt)?, salt))
+      }
+      _ => None,
     };
 
-    let reveal_fee_rate = FeeRate::try_from(self.fee_rate.0 + 0.02)?;
-    let (
-      unsigned_commit_tx,
-      reveal_txs,
-      _recovery_key_pair,
+    let Inscribed {
+      commit: unsigned_commit_tx,
+      reveals: reveal_txs,
       service_fee,
       satpoint_fee,
       network_fee,
-    ) = Mint::create_inscription_transactions(
-      address_type,
-      satpoints,
-      inscription,
-      inscriptions,
-      options.chain().network(),
-      utxos.clone(),
-      commit_tx_change,
-      reveal_tx_destination,
-      self.fee_rate,
-      reveal_fee_rate,
-      false,
-      service_address,
-      usize::try_from(repeat)?,
-      service_fee,
-      self.target_postage,
-      additional_service_fee,
+      breakdown,
+      recovery_key_pairs,
+      ..
+    } = explain_shallow(
+      explain_exclusion(
+        builder::inscribe(
+          CommitParams {
+            input_type: address_type,
+            network: options.chain().network(),
+            satpoints,
+            inscriptions,
+            utxos: utxos.clone(),
+            change: commit_tx_change,
+            destination: reveal_tx_destination,
+            groups: Vec::new(),
+            commit_fee_rate,
+            reveal_fee_rate: FeeRate::try_from(reveal_fee_rate.0 + 0.02)?,
+            max_nonstandard_weight: None,
+            service_address: service_address.clone(),
+            service_fee,
+            additional_service_fee,
+            target_postage: self.target_postage,
+            recipients: Vec::new(),
+            parent: parent.clone(),
+            key_source: match (&self.reveal_seed, &reveal_key) {
+              (Some(seed), _) => KeySource::Fixed(seed.key_pair()?),
+              (None, Some((key_pair, _salt))) => KeySource::Key(*key_pair),
+              (None, None) => KeySource::Random,
+            },
+          },
+          vec![inscription; usize::try_from(repeat)?],
+        ),
+        &excluded,
+      ),
+      &shallow,
+      self.min_confirmations,
     )?;
 
-    let commit_vsize = Self::estimate_vsize(&unsigned_commit_tx, address_type) as u64;
-    let commit_fee = Self::calculate_fee(&unsigned_commit_tx, &utxos);
+    warnings.check_change(&unsigned_commit_tx, &change);
+
+    // a seeded remint derives the replaced commit's addresses again
+    if let (Some(recommit), Some(_)) = (&recommit, &self.reveal_seed) {
+      Self::check_remint(recommit, &unsigned_commit_tx, reveal_txs.len())?;
+    }
+
+    let commit_vsize = builder::estimate_vsize(&unsigned_commit_tx, address_type) as u64;
+    let commit_fee = builder::fee(&unsigned_commit_tx, &utxos);
 
     let network_fee = commit_fee + network_fee;
+    builder::check_total_fee(service_fee, satpoint_fee, network_fee, self.max_total_fee)?;
+
+    if let (Some((token, _)), Some(mysql)) = (voucher, records) {
+      mysql.redeem_voucher(token, unsigned_commit_tx.txid())?;
+    }
+
+    if let (Some((_key_pair, salt)), Some(mysql)) = (&reveal_key, records) {
+      if let Err(err) = mysql.insert_reveal_key_salt(unsigned_commit_tx.txid(), salt) {
+        tracing::warn!(error = %err, "Insert reveal key salt fail");
+      }
+    }
+
+    if let (true, Some(mysql)) = (self.record_revenue, records) {
+      let rows = Revenue::rows(
+        &unsigned_commit_tx,
+        &reveal_txs,
+        &breakdown,
+        &source,
+        self.purpose.as_deref(),
+        &service_address,
+      );
+      if let Err(err) = mysql.insert_revenue(&rows) {
+        tracing::warn!(error = %err, "Insert revenue fail");
+      }
+    }
+
+    if let Some(mysql) = records {
+      let expectations = reveal_txs
+        .iter()
+        .map(|tx| RevealExpectation {
+          commit_txid: unsigned_commit_tx.txid(),
+          reveal_txid: tx.txid(),
+          reveal_hex: tx.raw_hex(),
+          source: source.to_string(),
+        })
+        .collect();
+      if let Err(err) = mysql.insert_reveal_expectations(expectations) {
+        tracing::warn!(error = %err, "Insert reveal expectations fail");
+      }
+
+      let rows = reveal_txs
+        .iter()
+        .zip(content_hashes.iter().cycle())
+        .map(|(tx, content_hash)| ContentHash {
+          address: source.to_string(),
+          content_hash: *content_hash,
+          inscription_id: tx.txid().into(),
+          height: None,
+        })
+        .collect::<Vec<_>>();
+      if let Err(err) = mysql.insert_content_hashes(&rows) {
+        tracing::warn!(error = %err, "Insert content hashes fail");
+      }
+    }
 
-    let unsigned_commit_psbt = Self::get_psbt(&unsigned_commit_tx, &utxos, &source)?;
+    let sat_index = index.has_sat_index()?;
+    let inscribed_sats = builder::inscribed_sats(
+      &unsigned_commit_tx,
+      &utxos,
+      reveal_txs.len(),
+      self.pointer.unwrap_or_default(),
+    )?
+    .into_iter()
+    .map(|satpoint| {
+      let sat = if sat_index {
+        Self::sat(&index, satpoint)?
+      } else {
+        None
+      };
+      Ok(InscribedSat {
+        funding_outpoint: satpoint.outpoint,
+        funding_offset: satpoint.offset,
+        sat,
+        rarity: sat.map(Sat::rarity),
+      })
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+    let unsigned_commit_psbt =
+      Self::get_psbt(&unsigned_commit_tx, &utxos, &source, &self.funding_inputs)?;
     let unsigned_commit_custom = Self::get_custom(&unsigned_commit_psbt);
 
+    let parent_reveal = match &parent {
+      Some(parent) => Some(serialize_hex(&builder::parent_reveal_psbt(
+        &unsigned_commit_tx,
+        &reveal_txs[0],
+        parent,
+      )?)),
+      None => None,
+    };
+
     let output = Output {
       commit: serialize_hex(&unsigned_commit_psbt),
       commit_custom: unsigned_commit_custom,
@@ -194,32 +588,355 @@ impl Mint {
       network_fee,
       commit_vsize,
       commit_fee,
+      commit_fee_rate: commit_fee_rate.0,
+      reveal_fee_rate: reveal_fee_rate.0,
+      commit_breakdown: breakdown,
+      inscribed_sats,
+      warnings: warnings.into_vec(),
+      recovery_secret_keys: if self.return_recovery_key {
+        RecoveryKey::all(
+          &unsigned_commit_tx,
+          &recovery_key_pairs,
+          options.chain().network(),
+        )
+      } else {
+        Vec::new()
+      },
+      dry_run: self.dry_run,
+      compression,
+      pointer: self.pointer,
+      parent_reveal,
     };
-    log::info!("Build mint success");
+    tracing::info!(
+      source = %source,
+      inscriptions = output.inscription.len(),
+      network_fee = output.network_fee,
+      duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+      "Build mint success"
+    );
     Ok(output)
   }
 
+  /// What `build` would charge, worked out without building the commit or
+  /// reveals. A source short of funds gets the shortfall back instead of
+  /// an error. Vouchers are checked but not redeemed.
+  pub fn estimate(
+    self,
+    options: Options,
+    service_address: Option<Address>,
+    service_fee: Option<Amount>,
+    mysql: Option<Arc<MysqlDatabase>>,
+  ) -> Result<builder::Estimate> {
+    let repeat: u64 = self.repeat.unwrap_or(1);
+    let (commit_fee_rate, reveal_fee_rate) =
+      builder::component_fee_rates(self.fee_rate, self.commit_fee_rate, self.reveal_fee_rate)?;
+    let reveal_fee_rate = builder::buffered_fee_rate(reveal_fee_rate, self.reveal_fee_buffer_rate)?;
+
+    let content_type =
+      Media::content_type_for_mint(self.content_type.as_deref(), self.extension.as_deref())?;
+    let body = self.body()?;
+    let inscription =
+      Inscription::from_content(options.chain(), &content_type, body, self.compress)?
+        .with_metadata(self.metadata.as_ref(), self.metaprotocol.clone());
+    let inscription = match self.pointer {
+      Some(pointer) => inscription.with_pointer(pointer),
+      None => inscription,
+    };
+
+    let voucher_policy = match &self.voucher {
+      Some(token) => Some(
+        mysql
+          .as_ref()
+          .ok_or_else(|| anyhow!("vouchers require a mysql database"))?
+          .get_voucher_policy(token)?,
+      ),
+      None => None,
+    };
+
+    let index = Index::read_open(&options)?;
+
+    let source = self.source;
+    let destination = self.destination.unwrap_or_else(|| source.clone());
+    let address_type = check_source_address(&source, &options)?;
+    check_funding_scripts(
+      &self.funding_inputs,
+      address_type,
+      options.chain().network(),
+    )?;
+    check_destination_address(&destination, &options, self.allow_burn)?;
+    let change = change_address(self.change_address, &source, &self.funding_inputs)?;
+    check_source_address(&change, &options)?;
+    if let Some(parent_destination) = &self.parent_destination {
+      check_destination_address(parent_destination, &options, self.allow_burn)?;
+    }
+
+    let service_address = service_address.unwrap_or(source.clone());
+    check_address_type(&service_address, &options)?;
+
+    let Funds {
+      mut utxos,
+      shallow: _,
+      satpoints,
+      inscriptions,
+      is_whitelist,
+      additional_service_fee,
+      recommit: _,
+    } = Self::funds(
+      &index,
+      &source,
+      &self.funding_inputs,
+      &self.inputs,
+      self.verify_inputs,
+      self.remint,
+      self.min_confirmations,
+      self.allow_unconfirmed_self,
+      mysql.as_deref(),
+    )?;
+    exclude_utxos(&mut utxos, &self.exclude_inputs);
+
+    let service_fee = Self::service_fee(is_whitelist, service_fee);
+    let service_fee = match voucher_policy {
+      Some(policy) => policy.apply(service_fee),
+      None => service_fee,
+    };
+
+    let parent = self
+      .parent
+      .map(|id| {
+        Self::parent(
+          id,
+          self.parent_destination.clone(),
+          &source,
+          &inscriptions,
+          &utxos,
+          &self.funding_inputs,
+        )
+      })
+      .transpose()?;
+    let inscription = match &parent {
+      Some(parent) => inscription.with_parent(parent.id),
+      None => inscription,
+    };
+
+    builder::estimate(
+      &CommitParams {
+        input_type: address_type,
+        network: options.chain().network(),
+        satpoints,
+        inscriptions,
+        utxos,
+        change: [change.clone(), change],
+        destination,
+        groups: Vec::new(),
+        commit_fee_rate,
+        reveal_fee_rate: FeeRate::try_from(reveal_fee_rate.0 + 0.02)?,
+        max_nonstandard_weight: None,
+        service_address,
+        service_fee,
+        additional_service_fee,
+        target_postage: self.target_postage,
+        recipients: Vec::new(),
+        parent,
+        key_source: KeySource::Random,
+      },
+      &vec![inscription; usize::try_from(repeat)?],
+    )
+  }
+
+  /// The reveal's view of parent `id`, which must be held by `source`.
+  fn parent(
+    id: InscriptionId,
+    destination: Option<Address>,
+    source: &Address,
+    inscriptions: &BTreeMap<SatPoint, InscriptionId>,
+    utxos: &BTreeMap<OutPoint, Amount>,
+    funding_inputs: &[FundingInput],
+  ) -> Result<builder::Parent> {
+    let not_owned = || BuildError::ParentNotOwned {
+      inscription_id: id,
+      source: source.clone(),
+    };
+
+    let satpoint = inscriptions
+      .iter()
+      .find(|(_, inscription_id)| **inscription_id == id)
+      .map(|(satpoint, _)| *satpoint)
+      .ok_or_else(not_owned)?;
+    let value = utxos.get(&satpoint.outpoint).ok_or_else(not_owned)?;
+
+    Ok(builder::Parent {
+      id,
+      satpoint,
+      output: TxOut {
+        value: value.to_sat(),
+        script_pubkey: input_script(funding_inputs, satpoint.outpoint, source),
+      },
+      destination: destination.unwrap_or_else(|| source.clone()),
+    })
+  }
+
+  /// The bytes to inscribe, read from `content_file` or decoded from
+  /// `content`.
+  fn body(&self) -> Result<Vec<u8>> {
+    match &self.content_file {
+      Some(path) => {
+        fs::read(path).with_context(|| format!("failed to read content file {}", path.display()))
+      }
+      None => ContentFormat::decode(self.content_encoding, self.content.clone()),
+    }
+  }
+
+  /// Fails on the first of `content_hashes` that `source` already built or
+  /// was inscribed with.
+  pub(crate) fn check_duplicates(
+    source: &Address,
+    content_hashes: &[sha256::Hash],
+    mysql: Option<&MysqlDatabase>,
+  ) -> Result {
+    let mysql = mysql.ok_or_else(|| anyhow!("reject_duplicate requires a mysql database"))?;
+    for (item, content_hash) in content_hashes.iter().enumerate() {
+      if let Some(inscription_id) =
+        mysql.get_content_duplicate(&source.to_string(), *content_hash)?
+      {
+        return Err(
+          BuildError::DuplicateInscription {
+            item,
+            inscription_id,
+          }
+          .into(),
+        );
+      }
+    }
+    Ok(())
+  }
+
   pub fn run(self, options: Options) -> Result {
     print_json(self.build(options, None, Some(Self::SERVICE_FEE), None)?)?;
     Ok(())
   }
 
+  /// Utxos `source` can fund a mint with, the sats it must spend first
+  /// and the inscriptions they hold.
+  pub(crate) fn funds(
+    index: &Index,
+    source: &Address,
+    funding_inputs: &[FundingInput],
+    inputs: &[OutPoint],
+    verify_inputs: bool,
+    remint: Option<Txid>,
+    min_confirmations: u64,
+    allow_unconfirmed_self: bool,
+    mysql: Option<&MysqlDatabase>,
+  ) -> Result<Funds> {
+    tracing::info!("Get utxo...");
+    let query_address = &format!("{}", source);
+    let mut additional_service_fee = Amount::ZERO;
+    let mut shallow = BTreeMap::new();
+    let mut recommit = None;
+    let (mut utxos, satpoints) = if !inputs.is_empty() {
+      if !funding_inputs.is_empty() || remint.is_some() {
+        bail!("pinned inputs cannot be combined with funding inputs or a remint");
+      }
+      let client = index.options().bitcoin_rpc_client()?;
+      (pinned_utxos(inputs, source, &client)?, vec![])
+    } else if !funding_inputs.is_empty() {
+      if remint.is_some() {
+        bail!("funding inputs cannot be used to remint");
+      }
+      let client = if verify_inputs {
+        Some(index.options().bitcoin_rpc_client()?)
+      } else {
+        None
+      };
+      (funding_utxos(funding_inputs, client.as_ref())?, vec![])
+    } else if let Some(commit_id) = remint {
+      additional_service_fee = Amount::from_sat(3000);
+      let (utxos, recommit_tx) =
+        index.get_unspent_outputs_by_commit_id(query_address, BTreeMap::new(), commit_id)?;
+      let satpoints = recommit_tx
+        .input
+        .iter()
+        .map(|item| SatPoint {
+          outpoint: item.previous_output,
+          offset: 0,
+        })
+        .collect::<Vec<_>>();
+      recommit = Some(recommit_tx);
+      (utxos, satpoints)
+    } else {
+      let mut utxos = index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?;
+      shallow = exclude_utxos(
+        &mut utxos,
+        &shallow_utxos(
+          index,
+          source,
+          min_confirmations,
+          allow_unconfirmed_self,
+          mysql,
+        )?,
+      );
+      (utxos, vec![])
+    };
+
+    if funding_inputs.is_empty() && inputs.is_empty() {
+      utxos.retain(|_, amount| amount.to_sat() > 546);
+    }
+
+    let is_whitelist = mysql.map_or(false, |mysql| mysql.is_whitelist(query_address));
+    let inscriptions = address_inscriptions(
+      index,
+      source,
+      utxos
+        .keys()
+        .chain(satpoints.iter().map(|satpoint| &satpoint.outpoint)),
+      mysql,
+    )?;
+    check_pinned_cardinal(inputs, &inscriptions)?;
+    check_pinned_cardinal(
+      &funding_inputs
+        .iter()
+        .map(|input| input.outpoint)
+        .collect::<Vec<OutPoint>>(),
+      &funding_inscriptions(index, funding_inputs, mysql)?,
+    )?;
+
+    Ok(Funds {
+      utxos,
+      shallow,
+      satpoints,
+      inscriptions,
+      is_whitelist,
+      additional_service_fee,
+      recommit,
+    })
+  }
+
+  // funding utxos still in the mempool are not in the sat index yet
+  fn sat(index: &Index, satpoint: SatPoint) -> Result<Option<Sat>> {
+    let Some(List::Unspent(ranges)) = index.list(satpoint.outpoint)? else {
+      return Ok(None);
+    };
+
+    let mut offset = satpoint.offset;
+    for (start, end) in ranges {
+      if offset < end - start {
+        return Ok(Some(Sat(start + offset)));
+      }
+      offset -= end - start;
+    }
+
+    Ok(None)
+  }
+
   fn get_psbt(
     tx: &Transaction,
     utxos: &BTreeMap<OutPoint, Amount>,
     source: &Address,
+    funding_inputs: &[FundingInput],
   ) -> Result<Psbt> {
-    let mut tx_psbt = Psbt::from_unsigned_tx(tx.clone())?;
-    for i in 0..tx_psbt.unsigned_tx.input.len() {
-      tx_psbt.inputs[i].witness_utxo = Some(TxOut {
-        value: utxos
-          .get(&tx_psbt.unsigned_tx.input[i].previous_output)
-          .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
-          .to_sat(),
-        script_pubkey: source.script_pubkey(),
-      });
-    }
-    Ok(tx_psbt)
+    builder::psbt(tx, utxos, |outpoint| {
+      input_script(funding_inputs, outpoint, source)
+    })
   }
 
   fn get_custom(tx: &Psbt) -> Vec<String> {
@@ -242,287 +959,119 @@ impl Mint {
 
     result
   }
+}
 
-  fn calculate_fee(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> u64 {
-    tx.input
-      .iter()
-      .map(|txin| utxos.get(&txin.previous_output).unwrap().to_sat())
-      .sum::<u64>()
-      .checked_sub(tx.output.iter().map(|txout| txout.value).sum::<u64>())
-      .unwrap()
-  }
-
-  fn create_inscription_transactions(
-    input_type: AddressType,
-    satpoints: Vec<SatPoint>,
-    inscription: Inscription,
-    inscriptions: BTreeMap<SatPoint, InscriptionId>,
-    network: Network,
-    utxos: BTreeMap<OutPoint, Amount>,
-    change: [Address; 2],
-    destination: Address,
-    commit_fee_rate: FeeRate,
-    reveal_fee_rate: FeeRate,
-    no_limit: bool,
-    service_address: Address,
-    repeat: usize,
-    service_fee: Amount,
-    target_postage: Amount,
-    additional_service_fee: Amount,
-  ) -> Result<(Transaction, Vec<Transaction>, TweakedKeyPair, u64, u64, u64)> {
-    let satpoints = if !satpoints.is_empty() {
-      satpoints
-    } else {
-      let inscribed_utxos = inscriptions
-        .keys()
-        .map(|satpoint| satpoint.outpoint)
-        .collect::<BTreeSet<OutPoint>>();
-
-      vec![utxos
-        .keys()
-        .find(|outpoint| !inscribed_utxos.contains(outpoint))
-        .map(|outpoint| SatPoint {
-          outpoint: *outpoint,
-          offset: 0,
-        })
-        .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?]
-    };
-
-    for (inscribed_satpoint, inscription_id) in &inscriptions {
-      for satpoint in &satpoints {
-        if inscribed_satpoint == satpoint {
-          return Err(anyhow!("sat at {} already inscribed", satpoint));
-        }
-
-        if inscribed_satpoint.outpoint == satpoint.outpoint {
-          return Err(anyhow!(
-          "utxo {} already inscribed with inscription {inscription_id} on sat {inscribed_satpoint}",
-          satpoint.outpoint,
-        ));
-        }
-      }
-    }
-
-    let secp256k1 = Secp256k1::new();
-    let key_pair = UntweakedKeyPair::new(&secp256k1, &mut rand::thread_rng());
-    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    bitcoin::{consensus::deserialize, hashes::hex::FromHex},
+  };
 
-    let reveal_script = inscription.append_reveal_script(
-      script::Builder::new()
-        .push_slice(&public_key.serialize())
-        .push_opcode(opcodes::all::OP_CHECKSIG),
+  #[test]
+  fn whitelisted_sources_pay_no_service_fee() {
+    assert_eq!(Mint::service_fee(true, None), Amount::ZERO);
+    assert_eq!(
+      Mint::service_fee(true, Some(Amount::from_sat(5000))),
+      Amount::ZERO
     );
+    assert_eq!(Mint::service_fee(false, None), Mint::SERVICE_FEE);
+    assert_eq!(
+      Mint::service_fee(false, Some(Amount::from_sat(1000))),
+      Amount::from_sat(1000)
+    );
+  }
 
-    let taproot_spend_info = TaprootBuilder::new()
-      .add_leaf(0, reveal_script.clone())
-      .expect("adding leaf should work")
-      .finalize(&secp256k1, public_key)
-      .expect("finalizing taproot builder should work");
-
-    let control_block = taproot_spend_info
-      .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
-      .expect("should compute control block");
-
-    let commit_tx_address = Address::p2tr_tweaked(taproot_spend_info.output_key(), network);
-
-    let mut reveal_fees: Vec<Amount> = vec![];
-
-    let mut service_fee = service_fee * (repeat as u64) + additional_service_fee;
-    if service_fee.to_sat() < 546 {
-      service_fee = Amount::ZERO;
-    }
-
-    let mut outputs = vec![];
-    for i in 0..repeat {
-      let reveal_output = if i == 0 {
-        let mut tx_out = vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: 0,
-        }];
-        if service_fee.to_sat() > 0 {
-          tx_out.push(TxOut {
-            script_pubkey: service_address.script_pubkey(),
-            value: 0,
-          });
-        }
-        tx_out
-      } else {
-        vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: 0,
-        }]
-      };
-      let (_, reveal_fee) = Self::build_reveal_transaction(
-        &control_block,
-        reveal_fee_rate,
-        OutPoint::null(),
-        reveal_output,
-        &reveal_script,
-      );
-      reveal_fees.push(reveal_fee);
-      if i == 0 {
-        outputs.push((
-          commit_tx_address.clone(),
-          reveal_fee + target_postage + service_fee,
-        ));
-      } else {
-        outputs.push((commit_tx_address.clone(), reveal_fee + target_postage));
-      }
-    }
-
-    let unsigned_commit_tx = TransactionBuilder::build_transaction_with_value_v1(
-      input_type,
-      satpoints,
-      inscriptions,
-      utxos,
-      outputs,
-      change,
-      commit_fee_rate,
-    )?;
-
-    let mut reveal_txs: Vec<Transaction> = vec![];
-
-    let satpoint_fee = (target_postage * (repeat as u64)).to_sat();
-    let network_fee = reveal_fees.clone().into_iter().sum::<Amount>().to_sat();
-    let service_fee = service_fee.to_sat();
-    for i in 0..repeat {
-      let reveal_output = if i == 0 {
-        let mut tx_out = vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: target_postage.to_sat(),
-        }];
-        if service_fee > 0 {
-          tx_out.push(TxOut {
-            script_pubkey: service_address.script_pubkey(),
-            value: service_fee,
-          })
-        }
-        tx_out
-      } else {
-        vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: target_postage.to_sat(),
-        }]
-      };
-
-      let (txid, vout) = (unsigned_commit_tx.txid(), u32::try_from(i).unwrap());
-
-      let (mut reveal_tx, _fee) = Self::build_reveal_transaction(
-        &control_block,
-        reveal_fee_rate,
-        OutPoint { txid, vout },
-        reveal_output,
-        &reveal_script,
-      );
-
-      if reveal_tx.output[0].value < reveal_tx.output[0].script_pubkey.dust_value().to_sat() {
-        bail!("commit transaction output would be dust");
-      }
-
-      let mut sighash_cache = SighashCache::new(&mut reveal_tx);
-
-      let prevout = unsigned_commit_tx.output[i].clone();
-
-      let signature_hash = sighash_cache
-        .taproot_script_spend_signature_hash(
-          0,
-          &Prevouts::All(&[prevout]),
-          TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
-          SchnorrSighashType::Default,
-        )
-        .expect("signature hash should compute");
+  #[test]
+  fn remints_must_pay_the_replaced_reveal_addresses() {
+    let transaction = |output| Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: Vec::new(),
+      output,
+    };
+    let recommit = transaction(vec![tx_out(10_000, recipient()), tx_out(5000, change(0))]);
 
-      let signature = secp256k1.sign_schnorr(
-        &secp256k1::Message::from_slice(signature_hash.as_inner())
-          .expect("should be cryptographically secure hash"),
-        &key_pair,
-      );
+    Mint::check_remint(
+      &recommit,
+      &transaction(vec![tx_out(12_000, recipient()), tx_out(2000, change(1))]),
+      1,
+    )
+    .unwrap();
 
-      let witness = sighash_cache
-        .witness_mut(0)
-        .expect("getting mutable witness reference should work");
-      witness.push(signature.as_ref());
-      witness.push(reveal_script.clone());
-      witness.push(&control_block.serialize());
+    assert!(Mint::check_remint(
+      &recommit,
+      &transaction(vec![tx_out(12_000, change(1)), tx_out(2000, change(0))]),
+      1,
+    )
+    .unwrap_err()
+    .to_string()
+    .contains("was not built from this reveal seed"));
+  }
 
-      let reveal_weight = reveal_tx.weight();
+  fn funding_input(n: u64, value: u64) -> String {
+    format!(
+      "--funding-input={}:{value}:{:x}",
+      outpoint(n),
+      recipient().script_pubkey()
+    )
+  }
 
-      if !no_limit && reveal_weight > MAX_STANDARD_TX_WEIGHT.try_into().unwrap() {
-        bail!(
-        "reveal transaction weight greater than {MAX_STANDARD_TX_WEIGHT} (MAX_STANDARD_TX_WEIGHT): {reveal_weight}"
-      );
-      }
+  fn build(args: &[String]) -> Output {
+    let rpc_server = test_bitcoincore_rpc::builder()
+      .network(Network::Signet)
+      .build();
+    let tempdir = TempDir::new().unwrap();
 
-      reveal_txs.push(reveal_tx);
-    }
+    Mint::try_parse_from(
+      [
+        "mint".into(),
+        "--fee-rate=1".into(),
+        format!("--source={}", recipient()),
+        "--target-postage=10000 sat".into(),
+        "--extension=.txt".into(),
+        "--content=foo".into(),
+      ]
+      .iter()
+      .chain(args),
+    )
+    .unwrap()
+    .build(signet_options(&rpc_server, &tempdir), None, None, None)
+    .unwrap()
+  }
 
-    let recovery_key_pair = key_pair.tap_tweak(&secp256k1, taproot_spend_info.merkle_root());
+  #[test]
+  fn excluded_funding_inputs_are_never_spent() {
+    let output = build(&[
+      funding_input(1, 1_000_000),
+      funding_input(2, 100_000),
+      format!("--exclude={}", outpoint(1)),
+    ]);
 
-    let (x_only_pub_key, _parity) = recovery_key_pair.to_inner().x_only_public_key();
+    let commit: Psbt = deserialize(&Vec::from_hex(&output.commit).unwrap()).unwrap();
     assert_eq!(
-      Address::p2tr_tweaked(
-        TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
-        network,
-      ),
-      commit_tx_address
+      commit
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<OutPoint>>(),
+      [outpoint(2)]
     );
-
-    Ok((
-      unsigned_commit_tx,
-      reveal_txs,
-      recovery_key_pair,
-      service_fee,
-      satpoint_fee,
-      network_fee,
-    ))
-  }
-
-  fn estimate_vsize(transaction: &Transaction, input_type: AddressType) -> usize {
-    let mut modified_tx = transaction.clone();
-    let witness_size = if input_type == AddressType::P2tr {
-      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
-    } else {
-      TransactionBuilder::P2WPKH_WINETSS_SIZE
-    };
-    for input in &mut modified_tx.input {
-      input.witness = Witness::from_vec(vec![vec![0; witness_size]]);
-    }
-    modified_tx.vsize()
   }
 
-  fn build_reveal_transaction(
-    control_block: &ControlBlock,
-    fee_rate: FeeRate,
-    input: OutPoint,
-    output: Vec<TxOut>,
-    script: &Script,
-  ) -> (Transaction, Amount) {
-    let reveal_tx = Transaction {
-      input: vec![TxIn {
-        previous_output: input,
-        script_sig: script::Builder::new().into_script(),
-        witness: Witness::new(),
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-      }],
-      output,
-      lock_time: PackedLockTime::ZERO,
-      version: 1,
-    };
+  #[test]
+  fn same_reveal_seed_builds_the_same_transactions() {
+    let args = [
+      funding_input(1, 100_000),
+      format!("--reveal-seed={}", "01".repeat(31) + "02"),
+    ];
 
-    let fee = {
-      let mut reveal_tx = reveal_tx.clone();
-
-      reveal_tx.input[0].witness.push(
-        Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-          .unwrap()
-          .as_ref(),
-      );
-      reveal_tx.input[0].witness.push(script);
-      reveal_tx.input[0].witness.push(&control_block.serialize());
-
-      fee_rate.fee(reveal_tx.vsize())
-    };
+    let first = build(&args);
+    let second = build(&args);
+    assert_eq!(first.commit, second.commit);
+    assert_eq!(first.reveal, second.reveal);
 
-    (reveal_tx, fee)
+    assert_ne!(build(&args[..1]).commit, first.commit);
   }
 }
@@ -1,26 +1,31 @@
-use crate::index::{ConstructTransaction, MysqlDatabase, TransactionOutputArray};
+use crate::{
+  builder::{self, CommitOutputBreakdown, CommitParams, Inscribed, KeySource},
+  index::{
+    ConstructTransaction, ContentHash, MysqlDatabase, RevealExpectation, Revenue,
+    TransactionOutputArray,
+  },
+  reveal_key::{RevealKeys, RevealSeed},
+};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::policy::MAX_STANDARD_TX_WEIGHT;
 use bitcoin::psbt::Psbt;
-use bitcoin::{consensus::encode::serialize_hex, AddressType};
 use bitcoincore_rpc::RawTx;
 use {
   super::*,
-  bitcoin::{
-    blockdata::{opcodes, script},
-    policy::MAX_STANDARD_TX_WEIGHT,
-    schnorr::{TapTweak, TweakedKeyPair, TweakedPublicKey, UntweakedKeyPair},
-    secp256k1::{
-      self, constants::SCHNORR_SIGNATURE_SIZE, rand, schnorr::Signature, Secp256k1, XOnlyPublicKey,
-    },
-    util::sighash::{Prevouts, SighashCache},
-    util::taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder},
-    PackedLockTime, SchnorrSighashType, Witness,
+  funding::{
+    change_address, check_funding_scripts, exclude_utxos, explain_exclusion, explain_shallow,
+    input_script, FundingInput,
   },
-  std::collections::BTreeSet,
+  recover::RecoveryKey,
+  std::mem,
+  warning::{Warning, Warnings},
 };
 
 #[derive(Debug, Serialize)]
 pub struct Output {
   pub inscription: Vec<InscriptionId>,
+  /// Where each inscription is sent, in the order of `inscription`.
+  pub destinations: Vec<Address>,
   pub commit: String,
   pub commit_custom: Vec<String>,
   pub reveal: Vec<String>,
@@ -29,6 +34,93 @@ pub struct Output {
   pub network_fee: u64,
   pub commit_vsize: u64,
   pub commit_fee: u64,
+  pub commit_fee_rate: f64,
+  pub reveal_fee_rate: f64,
+  /// What each commit output pays for, in the order of `reveal`.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub commit_breakdown: Vec<CommitOutputBreakdown>,
+  pub dedup: Dedup,
+  pub content_index: Vec<usize>,
+  /// The reveal, as an index into `reveal`, and the output of it holding
+  /// each inscription, in the order of `inscription`. Only when grouping.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub group_outputs: Vec<(usize, usize)>,
+  /// The weight of each reveal, in the order of `reveal`.
+  pub reveal_weights: Vec<usize>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub warnings: Vec<Warning>,
+  /// Secret keys sweeping each commit output back if its reveal is lost,
+  /// only when asked for. Whoever holds them can take the commit funds.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub recovery_secret_keys: Vec<RecoveryKey>,
+  /// Built only to validate and price, not to be signed.
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dedup {
+  #[default]
+  Reject,
+  Collapse,
+  Allow,
+}
+
+impl FromStr for Dedup {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "reject" => Ok(Self::Reject),
+      "collapse" => Ok(Self::Collapse),
+      "allow" => Ok(Self::Allow),
+      _ => bail!("invalid dedup `{s}`, expected one of reject, collapse or allow"),
+    }
+  }
+}
+
+impl Dedup {
+  /// Returns the content to mint and, for every requested item, the index of
+  /// the inscription minted for it.
+  pub(crate) fn apply(self, content: Vec<String>) -> Result<(Vec<String>, Vec<usize>)> {
+    let mut first_index: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut duplicates: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, item) in content.iter().enumerate() {
+      match first_index.get(item.as_str()) {
+        Some(first) => duplicates
+          .entry(*first)
+          .or_insert_with(|| vec![*first])
+          .push(i),
+        None => {
+          first_index.insert(item.as_str(), i);
+        }
+      }
+    }
+
+    match self {
+      Dedup::Reject if !duplicates.is_empty() => {
+        Err(BuildError::DuplicateContent(duplicates.into_values().collect()).into())
+      }
+      Dedup::Reject | Dedup::Allow => {
+        let content_index = (0..content.len()).collect();
+        Ok((content, content_index))
+      }
+      Dedup::Collapse => {
+        let mut minted: Vec<String> = vec![];
+        let mut minted_index: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut content_index = vec![];
+        for item in &content {
+          let index = *minted_index.entry(item.as_str()).or_insert_with(|| {
+            minted.push(item.clone());
+            minted.len() - 1
+          });
+          content_index.push(index);
+        }
+        Ok((minted, content_index))
+      }
+    }
+  }
 }
 
 #[derive(Debug, Parser)]
@@ -37,21 +129,161 @@ pub struct Mint {
   pub fee_rate: FeeRate,
   #[clap(long, help = "Send inscription to <DESTINATION>.")]
   pub destination: Option<Address>,
+  #[clap(
+    long = "item-destination",
+    help = "Send the inscription of the i-th --content to the i-th <ITEM_DESTINATION> instead of --destination, given once per --content."
+  )]
+  pub destinations: Vec<Address>,
   #[clap(long, help = "Send inscription from <SOURCE>.")]
   pub source: Address,
+  #[clap(
+    long = "change",
+    help = "Send change to <CHANGE_ADDRESS> instead of back to --source."
+  )]
+  pub change_address: Option<Address>,
   #[clap(long, help = "Content type of mint, '.txt'.")]
   pub extension: Option<String>,
+  #[clap(
+    long,
+    help = "Inscribe with MIME type <CONTENT_TYPE> as given, instead of the one for --extension."
+  )]
+  pub content_type: Option<String>,
+  #[clap(
+    long,
+    help = "Tag the inscription with <METADATA> JSON, inscribed as CBOR."
+  )]
+  pub metadata: Option<serde_json::Value>,
+  #[clap(long, help = "Tag the inscription with the <METAPROTOCOL> it follows.")]
+  pub metaprotocol: Option<String>,
   #[clap(long, help = "Content of mint.")]
   pub content: Vec<String>,
+  #[clap(
+    long,
+    help = "Read each --content as <CONTENT_ENCODING>, base64 for binary content."
+  )]
+  pub content_encoding: Option<ContentFormat>,
   #[clap(long, help = "Target postage.")]
   pub target_postage: Amount,
+  #[clap(
+    long = "item-target-postage",
+    help = "Give the inscription of the i-th --content the i-th <ITEM_TARGET_POSTAGE> instead of --target-postage, given once per --content."
+  )]
+  pub target_postages: Vec<Amount>,
   #[clap(long, help = "Remint comint id.")]
   pub remint: Option<Txid>,
+  #[clap(long, help = "Allow sending inscription to a burn address.")]
+  pub allow_burn: bool,
+  #[clap(
+    long,
+    help = "Build reveals heavier than MAX_STANDARD_TX_WEIGHT, which nodes will not relay and need to reach a miner out of band."
+  )]
+  pub no_limit: bool,
+  #[clap(
+    long,
+    default_value = "1000000",
+    help = "Refuse reveals heavier than <MAX_NONSTANDARD_WEIGHT> even with --no-limit."
+  )]
+  pub max_nonstandard_weight: u64,
+  #[clap(
+    long = "funding-input",
+    help = "Fund the commit only with <FUNDING_INPUT>, given as <TXID>:<VOUT>:<VALUE>:<SCRIPT_PUBKEY_HEX>, may be repeated."
+  )]
+  pub funding_inputs: Vec<FundingInput>,
+  #[clap(
+    long = "input",
+    help = "Fund the commit only with <INPUT>, an unspent output of --source given as <TXID>:<VOUT>, may be repeated."
+  )]
+  pub inputs: Vec<OutPoint>,
+  #[clap(
+    long = "exclude",
+    help = "Never spend <EXCLUDE>, an output of --source given as <TXID>:<VOUT>, may be repeated."
+  )]
+  pub exclude_inputs: Vec<OutPoint>,
+  #[clap(
+    long,
+    default_value = "0",
+    help = "Only spend outputs of --source with at least <MIN_CONFIRMATIONS> confirmations."
+  )]
+  pub min_confirmations: u64,
+  #[clap(
+    long,
+    help = "Spend unconfirmed change of our own earlier builds despite --min-confirmations."
+  )]
+  pub allow_unconfirmed_self: bool,
+  #[clap(long, help = "Check funding inputs against the node with gettxout.")]
+  pub verify_inputs: bool,
+  #[clap(
+    long,
+    default_value = "reject",
+    help = "Handle duplicate content with <DEDUP>, one of reject, collapse or allow."
+  )]
+  pub dedup: Dedup,
+  #[clap(
+    long,
+    help = "Pack the inscriptions sent to each destination into as few reveals as stay standard."
+  )]
+  pub optimize_grouping: bool,
+  #[clap(
+    long,
+    help = "Build the commit at <COMMIT_FEE_RATE> sats/vB instead of --fee-rate."
+  )]
+  pub commit_fee_rate: Option<FeeRate>,
+  #[clap(
+    long,
+    help = "Build reveals at <REVEAL_FEE_RATE> sats/vB instead of --fee-rate."
+  )]
+  pub reveal_fee_rate: Option<FeeRate>,
+  #[clap(
+    long,
+    help = "Add <REVEAL_FEE_BUFFER_RATE> sats/vB to the reveal fee rate, so reveals still confirm if the commit is slow to."
+  )]
+  pub reveal_fee_buffer_rate: Option<FeeRate>,
+  #[clap(long, help = "Tag revenue rows with <PURPOSE>.")]
+  pub purpose: Option<String>,
+  #[clap(
+    long,
+    help = "Check and price the mint without recording it. Reveals are signed with throwaway keys, do not sign or broadcast the result."
+  )]
+  pub dry_run: bool,
+  #[clap(
+    long,
+    help = "Refuse the build if its fees add up to more than <MAX_TOTAL_FEE> sats."
+  )]
+  pub max_total_fee: Option<u64>,
+  #[clap(
+    long,
+    help = "Refuse content this source already built or was inscribed with, as recorded in the database."
+  )]
+  pub reject_duplicate: bool,
+  #[clap(
+    long,
+    help = "Return the secret keys that sweep the commit outputs back if a reveal is lost. Keep them secret."
+  )]
+  pub return_recovery_key: bool,
+  #[clap(
+    long,
+    help = "Derive the reveal key from <REVEAL_SEED>, 32 random bytes of hex unique to this mint, so that it always builds the same reveals."
+  )]
+  pub reveal_seed: Option<RevealSeed>,
+  /// Derive the reveal key so the reveals can be bumped later. Only used
+  /// with a database to keep the salt in.
+  #[clap(skip)]
+  pub reveal_keys: Option<Arc<RevealKeys>>,
+  /// Record what each commit output pays for in the revenue table. Only
+  /// used with a database.
+  #[clap(skip)]
+  pub record_revenue: bool,
 }
 
 impl Mint {
   pub const SERVICE_FEE: Amount = Amount::from_sat(3000);
 
+  /// The cap on reveal weight when the build may be non-standard, otherwise
+  /// reveals are held to MAX_STANDARD_TX_WEIGHT.
+  fn max_reveal_weight(&self) -> Option<u64> {
+    self.no_limit.then_some(self.max_nonstandard_weight)
+  }
+
   pub fn build(
     self,
     options: Options,
@@ -59,131 +291,294 @@ impl Mint {
     service_fee: Option<Amount>,
     mysql: Option<Arc<MysqlDatabase>>,
   ) -> Result<Output> {
-    let extension = "data.".to_owned() + &self.extension.unwrap_or(".txt".to_owned());
+    let (commit_fee_rate, reveal_fee_rate) =
+      builder::component_fee_rates(self.fee_rate, self.commit_fee_rate, self.reveal_fee_rate)?;
+    let reveal_fee_rate = builder::buffered_fee_rate(reveal_fee_rate, self.reveal_fee_buffer_rate)?;
+    let max_reveal_weight = self.max_reveal_weight();
+
+    let content_type =
+      Media::content_type_for_mint(self.content_type.as_deref(), self.extension.as_deref())?;
+
+    let (content, content_index) = self.dedup.apply(self.content)?;
+    let mut bodies = vec![];
+    for (i, item) in content.into_iter().enumerate() {
+      bodies.push(
+        ContentFormat::decode(self.content_encoding, item)
+          .with_context(|| format!("content item {i}"))?,
+      );
+    }
+    // refuse an oversized item before building any of them
+    for body in &bodies {
+      Inscription::check_content_size(options.chain(), body.len())?;
+    }
 
     let mut inscription = vec![];
-    for item in &self.content {
-      inscription.push(Inscription::from_content(
-        options.chain(),
-        &extension,
-        item.clone(),
-      )?);
+    for body in bodies {
+      inscription.push(
+        Inscription::from_content(options.chain(), &content_type, body, None)?
+          .with_metadata(self.metadata.as_ref(), self.metaprotocol.clone()),
+      );
     }
 
-    log::info!("Open index...");
+    let content_hashes = inscription
+      .iter()
+      .map(|inscription| ContentHash::of(inscription.body().unwrap_or_default()))
+      .collect::<Vec<_>>();
+    if self.reject_duplicate {
+      mint::Mint::check_duplicates(&self.source, &content_hashes, mysql.as_deref())?;
+    }
+
+    let start = Instant::now();
+    tracing::info!("Open index...");
     let index = Index::read_open(&options)?;
     // index.update()?;
 
-    let source = self.source;
-    let reveal_tx_destination = self.destination.unwrap_or_else(|| source.clone());
+    let mut warnings = Warnings::default();
+    warnings.check_index(&index);
+    warnings.check_fee_rate(
+      &index,
+      if commit_fee_rate.0 > reveal_fee_rate.0 {
+        commit_fee_rate
+      } else {
+        reveal_fee_rate
+      },
+    );
 
-    if !source.is_valid_for_network(options.chain().network()) {
-      bail!("Address `{}` is not valid for {}", source, options.chain());
-    }
-    if !reveal_tx_destination.is_valid_for_network(options.chain().network()) {
-      bail!(
-        "Address `{}` is not valid for {}",
-        reveal_tx_destination,
-        options.chain()
-      );
+    let source = self.source;
+    if let Some(destination) = &self.destination {
+      warnings.check_destination(destination, &source);
     }
+    let reveal_tx_destination = self.destination.unwrap_or_else(|| source.clone());
+    let destinations = Self::per_inscription(
+      "destination",
+      self.destinations,
+      &reveal_tx_destination,
+      &content_index,
+      inscription.len(),
+    )?;
+    let target_postages = Self::per_inscription(
+      "target postage",
+      self.target_postages,
+      &self.target_postage,
+      &content_index,
+      inscription.len(),
+    )?;
 
-    // check address types, only support p2tr and p2wpkh
-    let address_type = if let Some(address_type) = source.address_type() {
-      if (address_type == AddressType::P2tr) || (address_type == AddressType::P2wpkh) {
-        address_type
-      } else {
-        bail!(
-          "Address type `{}` is not valid, only support p2tr and p2wpkh",
-          address_type
-        );
+    let address_type = check_source_address(&source, &options)?;
+    check_funding_scripts(
+      &self.funding_inputs,
+      address_type,
+      options.chain().network(),
+    )?;
+    check_destination_address(&reveal_tx_destination, &options, self.allow_burn)?;
+    let change = change_address(self.change_address, &source, &self.funding_inputs)?;
+    check_source_address(&change, &options)?;
+    for destination in &destinations {
+      if *destination != reveal_tx_destination {
+        warnings.check_destination(destination, &source);
+        check_destination_address(destination, &options, self.allow_burn)?;
       }
-    } else {
-      bail!("Address `{}` is not valid for {}", source, options.chain());
-    };
+    }
 
     let service_address = service_address.unwrap_or(source.clone());
+    check_address_type(&service_address, &options)?;
 
-    log::info!("Get utxo...");
-    let query_address = &format!("{}", source);
-    let mut additional_service_fee = Amount::ZERO;
-    let (mut utxos, satpoints) = if let Some(commit_id) = self.remint {
-      additional_service_fee = Amount::from_sat(3000);
-      let (mut utxos, recommit_tx) =
-        index.get_unspent_outputs_by_commit_id(query_address, BTreeMap::new(), commit_id)?;
-      (
-        utxos,
-        recommit_tx
-          .input
-          .iter()
-          .map(|item| SatPoint {
-            outpoint: item.previous_output,
-            offset: 0,
-          })
-          .collect::<Vec<_>>(),
+    let recipients = destinations
+      .iter()
+      .cloned()
+      .zip(target_postages)
+      .collect::<Vec<(Address, Amount)>>();
+
+    // grouped inscriptions are built in group order, `groups` maps them back
+    let groups = if self.optimize_grouping {
+      Self::group(
+        &inscription,
+        &recipients,
+        &TxOut {
+          script_pubkey: service_address.script_pubkey(),
+          value: 0,
+        },
+        MAX_STANDARD_TX_WEIGHT.try_into().unwrap(),
       )
     } else {
-      (
-        index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?,
-        vec![],
-      )
+      (0..inscription.len()).map(|i| vec![i]).collect()
     };
+    let inscription = groups
+      .iter()
+      .flatten()
+      .map(|i| inscription[*i].clone())
+      .collect::<Vec<Inscription>>();
+    let grouped_recipients = groups
+      .iter()
+      .flatten()
+      .map(|i| recipients[*i].clone())
+      .collect::<Vec<(Address, Amount)>>();
 
-    utxos.retain(|_, amount| amount.to_sat() > 546);
+    let mint::Funds {
+      mut utxos,
+      shallow,
+      satpoints,
+      inscriptions,
+      is_whitelist,
+      additional_service_fee,
+      recommit,
+    } = mint::Mint::funds(
+      &index,
+      &source,
+      &self.funding_inputs,
+      &self.inputs,
+      self.verify_inputs,
+      self.remint,
+      self.min_confirmations,
+      self.allow_unconfirmed_self,
+      mysql.as_deref(),
+    )?;
+    let excluded = exclude_utxos(&mut utxos, &self.exclude_inputs);
 
-    let mut is_whitelist = false;
-    let inscriptions = if let Some(mysql) = mysql {
-      log::info!("Get inscriptions by mysql...");
-      is_whitelist = mysql.is_whitelist(query_address);
-      mysql.get_inscription_by_address(query_address)?
-    } else {
-      log::info!("Get inscriptions by redb...");
-      index.get_inscriptions(None)?
-    };
+    let commit_tx_change = [change.clone(), change.clone()];
 
-    let commit_tx_change = [source.clone(), source.clone()];
+    let service_fee = mint::Mint::service_fee(is_whitelist, service_fee);
 
-    let service_fee = if is_whitelist {
-      Amount::ZERO
-    } else {
-      service_fee.unwrap_or(Self::SERVICE_FEE)
+    // a dry run leaves no trace, its reveals are signed with random keys
+    // that are never stored
+    let records = mysql.as_ref().filter(|_| !self.dry_run);
+
+    // a seeded key is derived again from the seed, it needs no salt
+    let reveal_key = match (&self.reveal_keys, records) {
+      (Some(keys), Some(_)) if self.reveal_seed.is_none() => {
+        let salt = RevealKeys::salt();
+        Some((keys.key_pair(&salt)?, salt))
+      }
+      _ => None,
     };
 
-    let reveal_fee_rate = FeeRate::try_from(self.fee_rate.0 + 0.02)?;
-    let (
-      unsigned_commit_tx,
-      reveal_txs,
-      _recovery_key_pair,
+    let Inscribed {
+      commit: unsigned_commit_tx,
+      reveals: reveal_txs,
       service_fee,
       satpoint_fee,
       network_fee,
-    ) = Mint::create_inscription_transactions(
-      address_type,
-      satpoints,
-      inscription,
-      inscriptions,
-      options.chain().network(),
-      utxos.clone(),
-      commit_tx_change,
-      reveal_tx_destination,
-      self.fee_rate,
-      reveal_fee_rate,
-      false,
-      service_address,
-      service_fee,
-      self.target_postage,
-      additional_service_fee,
+      breakdown,
+      recovery_key_pairs,
+      ..
+    } = explain_shallow(
+      explain_exclusion(
+        builder::inscribe(
+          CommitParams {
+            input_type: address_type,
+            network: options.chain().network(),
+            satpoints,
+            inscriptions,
+            utxos: utxos.clone(),
+            change: commit_tx_change,
+            destination: reveal_tx_destination,
+            groups: groups.iter().map(Vec::len).collect(),
+            commit_fee_rate,
+            reveal_fee_rate: FeeRate::try_from(reveal_fee_rate.0 + 0.02)?,
+            max_nonstandard_weight: max_reveal_weight,
+            service_address: service_address.clone(),
+            service_fee,
+            additional_service_fee,
+            target_postage: self.target_postage,
+            recipients: grouped_recipients,
+            parent: None,
+            key_source: match (&self.reveal_seed, &reveal_key) {
+              (Some(seed), _) => KeySource::Fixed(seed.key_pair()?),
+              (None, Some((key_pair, _salt))) => KeySource::Key(*key_pair),
+              (None, None) => KeySource::Random,
+            },
+          },
+          inscription,
+        ),
+        &excluded,
+      ),
+      &shallow,
+      self.min_confirmations,
     )?;
 
-    let commit_vsize = Self::estimate_vsize(&unsigned_commit_tx, address_type) as u64;
-    let commit_fee = Self::calculate_fee(&unsigned_commit_tx, &utxos);
+    warnings.check_change(&unsigned_commit_tx, &change);
+
+    // a seeded remint derives the replaced commit's addresses again
+    if let (Some(recommit), Some(_)) = (&recommit, &self.reveal_seed) {
+      mint::Mint::check_remint(recommit, &unsigned_commit_tx, reveal_txs.len())?;
+    }
+
+    let commit_vsize = builder::estimate_vsize(&unsigned_commit_tx, address_type) as u64;
+    let commit_fee = builder::fee(&unsigned_commit_tx, &utxos);
 
     let network_fee = commit_fee + network_fee;
+    builder::check_total_fee(service_fee, satpoint_fee, network_fee, self.max_total_fee)?;
 
-    let unsigned_commit_psbt = Self::get_psbt(&unsigned_commit_tx, &utxos, &source)?;
+    if let (Some((_key_pair, salt)), Some(mysql)) = (&reveal_key, records) {
+      if let Err(err) = mysql.insert_reveal_key_salt(unsigned_commit_tx.txid(), salt) {
+        tracing::warn!(error = %err, "Insert reveal key salt fail");
+      }
+    }
+
+    if let (true, Some(mysql)) = (self.record_revenue, records) {
+      let rows = Revenue::rows(
+        &unsigned_commit_tx,
+        &reveal_txs,
+        &breakdown,
+        &source,
+        self.purpose.as_deref(),
+        &service_address,
+      );
+      if let Err(err) = mysql.insert_revenue(&rows) {
+        tracing::warn!(error = %err, "Insert revenue fail");
+      }
+    }
+
+    let mut group_outputs = vec![(0, 0); groups.iter().map(Vec::len).sum()];
+    for (g, group) in groups.iter().enumerate() {
+      for (k, i) in group.iter().enumerate() {
+        group_outputs[*i] = (g, k);
+      }
+    }
+
+    let inscription_ids = group_outputs
+      .iter()
+      .map(|(g, k)| InscriptionId {
+        txid: reveal_txs[*g].txid(),
+        index: (*k).try_into().unwrap(),
+      })
+      .collect::<Vec<InscriptionId>>();
+
+    if let Some(mysql) = records {
+      let expectations = reveal_txs
+        .iter()
+        .map(|tx| RevealExpectation {
+          commit_txid: unsigned_commit_tx.txid(),
+          reveal_txid: tx.txid(),
+          reveal_hex: tx.raw_hex(),
+          source: source.to_string(),
+        })
+        .collect();
+      if let Err(err) = mysql.insert_reveal_expectations(expectations) {
+        tracing::warn!(error = %err, "Insert reveal expectations fail");
+      }
+
+      let rows = inscription_ids
+        .iter()
+        .zip(&content_hashes)
+        .map(|(inscription_id, content_hash)| ContentHash {
+          address: source.to_string(),
+          content_hash: *content_hash,
+          inscription_id: *inscription_id,
+          height: None,
+        })
+        .collect::<Vec<_>>();
+      if let Err(err) = mysql.insert_content_hashes(&rows) {
+        tracing::warn!(error = %err, "Insert content hashes fail");
+      }
+    }
+
+    let unsigned_commit_psbt =
+      Self::get_psbt(&unsigned_commit_tx, &utxos, &source, &self.funding_inputs)?;
     let unsigned_commit_custom = Self::get_custom(&unsigned_commit_psbt);
 
     let output = Output {
+      reveal_weights: reveal_txs.iter().map(Transaction::weight).collect(),
       commit: serialize_hex(&unsigned_commit_psbt),
       commit_custom: unsigned_commit_custom,
       reveal: reveal_txs
@@ -191,14 +586,42 @@ impl Mint {
         .into_iter()
         .map(|tx| tx.raw_hex())
         .collect(),
-      inscription: reveal_txs.into_iter().map(|tx| tx.txid().into()).collect(),
+      inscription: inscription_ids,
+      destinations,
       service_fee,
       satpoint_fee,
       network_fee,
       commit_vsize,
       commit_fee,
+      commit_fee_rate: commit_fee_rate.0,
+      reveal_fee_rate: reveal_fee_rate.0,
+      commit_breakdown: breakdown,
+      dedup: self.dedup,
+      content_index,
+      group_outputs: if self.optimize_grouping {
+        group_outputs
+      } else {
+        Vec::new()
+      },
+      warnings: warnings.into_vec(),
+      recovery_secret_keys: if self.return_recovery_key {
+        RecoveryKey::all(
+          &unsigned_commit_tx,
+          &recovery_key_pairs,
+          options.chain().network(),
+        )
+      } else {
+        Vec::new()
+      },
+      dry_run: self.dry_run,
     };
-    log::info!("Build mint success");
+    tracing::info!(
+      source = %source,
+      inscriptions = output.inscription.len(),
+      network_fee = output.network_fee,
+      duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+      "Build mint success"
+    );
     Ok(output)
   }
 
@@ -207,22 +630,105 @@ impl Mint {
     Ok(())
   }
 
+  /// Groups the inscriptions going to the same destination, in the order
+  /// destinations first appear, and splits a group where its reveal would
+  /// weigh more than `max_weight` when also paying `service`. Each group
+  /// lists indices into `inscriptions`, which go to `recipients`.
+  pub(crate) fn group(
+    inscriptions: &[Inscription],
+    recipients: &[(Address, Amount)],
+    service: &TxOut,
+    max_weight: usize,
+  ) -> Vec<Vec<usize>> {
+    let mut by_destination: Vec<(&Address, Vec<usize>)> = Vec::new();
+    for (i, (destination, _)) in recipients.iter().enumerate() {
+      match by_destination.iter_mut().find(|(d, _)| *d == destination) {
+        Some((_, items)) => items.push(i),
+        None => by_destination.push((destination, vec![i])),
+      }
+    }
+
+    let mut groups = vec![];
+    for (destination, items) in by_destination {
+      let mut group: Vec<usize> = vec![];
+      for i in items {
+        group.push(i);
+
+        let postages = group
+          .iter()
+          .map(|i| recipients[*i].1)
+          .collect::<Vec<Amount>>();
+
+        let mut output = postages
+          .iter()
+          .map(|postage| TxOut {
+            script_pubkey: destination.script_pubkey(),
+            value: postage.to_sat(),
+          })
+          .collect::<Vec<TxOut>>();
+        output.push(service.clone());
+
+        let packed = builder::point(
+          group.iter().map(|i| inscriptions[*i].clone()).collect(),
+          &postages,
+        );
+
+        if group.len() > 1 && builder::reveal_weight(&packed, output) > max_weight {
+          group.pop();
+          groups.push(mem::replace(&mut group, vec![i]));
+        }
+      }
+      groups.push(group);
+    }
+
+    groups
+  }
+
+  /// The `name` of each of the `minted` inscriptions, the one given for its
+  /// content item in `values` or else `default`. Items collapsed into one
+  /// inscription must share their value.
+  pub(crate) fn per_inscription<T: Clone + PartialEq>(
+    name: &str,
+    values: Vec<T>,
+    default: &T,
+    content_index: &[usize],
+    minted: usize,
+  ) -> Result<Vec<T>> {
+    if values.is_empty() {
+      return Ok(vec![default.clone(); minted]);
+    }
+
+    if values.len() != content_index.len() {
+      bail!(
+        "{} {name} values given for {} content items",
+        values.len(),
+        content_index.len()
+      );
+    }
+
+    let mut minted_values: Vec<Option<T>> = vec![None; minted];
+    for (item, (index, value)) in content_index.iter().zip(values).enumerate() {
+      match &minted_values[*index] {
+        Some(existing) if *existing != value => bail!(
+          "item {item} repeats the content of an earlier item with another {name}, set dedup to allow to mint each its own inscription"
+        ),
+        Some(_) => {}
+        None => minted_values[*index] = Some(value),
+      }
+    }
+
+    Ok(minted_values.into_iter().flatten().collect())
+  }
+
   fn get_psbt(
     tx: &Transaction,
     utxos: &BTreeMap<OutPoint, Amount>,
     source: &Address,
+    funding_inputs: &[FundingInput],
   ) -> Result<Psbt> {
-    let mut tx_psbt = Psbt::from_unsigned_tx(tx.clone())?;
-    for i in 0..tx_psbt.unsigned_tx.input.len() {
-      tx_psbt.inputs[i].witness_utxo = Some(TxOut {
-        value: utxos
-          .get(&tx_psbt.unsigned_tx.input[i].previous_output)
-          .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
-          .to_sat(),
-        script_pubkey: source.script_pubkey(),
-      });
-    }
-    Ok(tx_psbt)
+    builder::psbt(tx, utxos, |outpoint| {
+      input_script(funding_inputs, outpoint, source)
+    })
   }
 
   fn get_custom(tx: &Psbt) -> Vec<String> {
@@ -245,306 +751,214 @@ impl Mint {
 
     result
   }
+}
 
-  fn calculate_fee(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> u64 {
-    tx.input
-      .iter()
-      .map(|txin| utxos.get(&txin.previous_output).unwrap().to_sat())
-      .sum::<u64>()
-      .checked_sub(tx.output.iter().map(|txout| txout.value).sum::<u64>())
-      .unwrap()
-  }
-
-  fn create_inscription_transactions(
-    input_type: AddressType,
-    satpoints: Vec<SatPoint>,
-    inscription: Vec<Inscription>,
-    inscriptions: BTreeMap<SatPoint, InscriptionId>,
-    network: Network,
-    utxos: BTreeMap<OutPoint, Amount>,
-    change: [Address; 2],
-    destination: Address,
-    commit_fee_rate: FeeRate,
-    reveal_fee_rate: FeeRate,
-    no_limit: bool,
-    service_address: Address,
-    service_fee: Amount,
-    target_postage: Amount,
-    additional_service_fee: Amount,
-  ) -> Result<(
-    Transaction,
-    Vec<Transaction>,
-    Vec<TweakedKeyPair>,
-    u64,
-    u64,
-    u64,
-  )> {
-    let satpoints = if !satpoints.is_empty() {
-      satpoints
-    } else {
-      let inscribed_utxos = inscriptions
-        .keys()
-        .map(|satpoint| satpoint.outpoint)
-        .collect::<BTreeSet<OutPoint>>();
-
-      vec![utxos
-        .keys()
-        .find(|outpoint| !inscribed_utxos.contains(outpoint))
-        .map(|outpoint| SatPoint {
-          outpoint: *outpoint,
-          offset: 0,
-        })
-        .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?]
-    };
-
-    for (inscribed_satpoint, inscription_id) in &inscriptions {
-      for satpoint in &satpoints {
-        if inscribed_satpoint == satpoint {
-          return Err(anyhow!("sat at {} already inscribed", satpoint));
-        }
-
-        if inscribed_satpoint.outpoint == satpoint.outpoint {
-          return Err(anyhow!(
-          "utxo {} already inscribed with inscription {inscription_id} on sat {inscribed_satpoint}",
-          satpoint.outpoint,
-        ));
-        }
-      }
-    }
-
-    let secp256k1 = Secp256k1::new();
-    let key_pair = UntweakedKeyPair::new(&secp256k1, &mut rand::thread_rng());
-    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
-
-    let mut reveal_script = vec![];
-    let mut taproot_spend_info = vec![];
-    let mut control_block = vec![];
-    let mut commit_tx_address = vec![];
-    let mut recovery_key_pair = vec![];
-
-    for item in &inscription {
-      let r = item.append_reveal_script(
-        script::Builder::new()
-          .push_slice(&public_key.serialize())
-          .push_opcode(opcodes::all::OP_CHECKSIG),
-      );
-      let t = TaprootBuilder::new()
-        .add_leaf(0, r.clone())
-        .expect("adding leaf should work")
-        .finalize(&secp256k1, public_key)
-        .expect("finalizing taproot builder should work");
-      let c = t
-        .control_block(&(r.clone(), LeafVersion::TapScript))
-        .expect("should compute control block");
-      let ca = Address::p2tr_tweaked(t.output_key(), network);
-
-      let rk = key_pair.tap_tweak(&secp256k1, t.merkle_root());
-      let (x_only_pub_key, _parity) = rk.to_inner().x_only_public_key();
-      assert_eq!(
-        Address::p2tr_tweaked(
-          TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
-          network,
-        ),
-        ca
-      );
-
-      reveal_script.push(r);
-      taproot_spend_info.push(t);
-      control_block.push(c);
-      commit_tx_address.push(ca);
-      recovery_key_pair.push(rk);
-    }
-
-    let repeat = inscription.len();
-
-    let mut reveal_fees: Vec<Amount> = vec![];
-
-    let mut service_fee = service_fee * (repeat as u64) + additional_service_fee;
-    if service_fee.to_sat() < 546 {
-      service_fee = Amount::ZERO;
-    }
-
-    let mut outputs = vec![];
-    for i in 0..repeat {
-      let reveal_output = if i == 0 {
-        let mut tx_out = vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: 0,
-        }];
-        if service_fee.to_sat() > 0 {
-          tx_out.push(TxOut {
-            script_pubkey: service_address.script_pubkey(),
-            value: 0,
-          });
-        }
-        tx_out
-      } else {
-        vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: 0,
-        }]
-      };
-      let (_, reveal_fee) = Self::build_reveal_transaction(
-        &control_block[i],
-        reveal_fee_rate,
-        OutPoint::null(),
-        reveal_output,
-        &reveal_script[i],
-      );
-      reveal_fees.push(reveal_fee);
-      if i == 0 {
-        outputs.push((
-          commit_tx_address[i].clone(),
-          reveal_fee + target_postage + service_fee,
-        ));
-      } else {
-        outputs.push((commit_tx_address[i].clone(), reveal_fee + target_postage));
-      }
-    }
-    reveal_fees.reverse();
-
-    let unsigned_commit_tx = TransactionBuilder::build_transaction_with_value_v1(
-      input_type,
-      satpoints,
-      inscriptions,
-      utxos,
-      outputs,
-      change,
-      commit_fee_rate,
-    )?;
-
-    let mut reveal_txs: Vec<Transaction> = vec![];
-
-    let satpoint_fee = (target_postage * (repeat as u64)).to_sat();
-    let network_fee = reveal_fees.clone().into_iter().sum::<Amount>().to_sat();
-    let service_fee = service_fee.to_sat();
-    for i in 0..repeat {
-      let reveal_output = if i == 0 {
-        let mut tx_out = vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: target_postage.to_sat(),
-        }];
-        if service_fee > 0 {
-          tx_out.push(TxOut {
-            script_pubkey: service_address.script_pubkey(),
-            value: service_fee,
-          })
-        }
-        tx_out
-      } else {
-        vec![TxOut {
-          script_pubkey: destination.script_pubkey(),
-          value: target_postage.to_sat(),
-        }]
-      };
-
-      let (txid, vout) = (unsigned_commit_tx.txid(), u32::try_from(i).unwrap());
-
-      let (mut reveal_tx, _fee) = Self::build_reveal_transaction(
-        &control_block[i],
-        reveal_fee_rate,
-        OutPoint { txid, vout },
-        reveal_output,
-        &reveal_script[i],
-      );
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-      if reveal_tx.output[0].value < reveal_tx.output[0].script_pubkey.dust_value().to_sat() {
-        bail!("commit transaction output would be dust");
-      }
+  fn content(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+  }
 
-      let mut sighash_cache = SighashCache::new(&mut reveal_tx);
+  fn mint(args: &[&str]) -> Mint {
+    Mint::try_parse_from(
+      [
+        "mints",
+        "--fee-rate",
+        "1",
+        "--source",
+        "tb1qjsv26lap3ffssj6hfy8mzn0lg5vte6a42j75ww",
+        "--target-postage",
+        "546 sat",
+      ]
+      .iter()
+      .chain(args),
+    )
+    .unwrap()
+  }
 
-      let prevout = unsigned_commit_tx.output[i].clone();
+  #[test]
+  fn reveal_weight_is_capped_only_without_limit() {
+    assert_eq!(mint(&[]).max_reveal_weight(), None);
+    assert_eq!(mint(&["--no-limit"]).max_reveal_weight(), Some(1_000_000));
+    assert_eq!(
+      mint(&["--no-limit", "--max-nonstandard-weight", "500000"]).max_reveal_weight(),
+      Some(500_000)
+    );
+    assert_eq!(
+      mint(&["--max-nonstandard-weight", "500000"]).max_reveal_weight(),
+      None
+    );
+  }
 
-      let signature_hash = sighash_cache
-        .taproot_script_spend_signature_hash(
-          0,
-          &Prevouts::All(&[prevout]),
-          TapLeafHash::from_script(&reveal_script[i], LeafVersion::TapScript),
-          SchnorrSighashType::Default,
-        )
-        .expect("signature hash should compute");
+  #[test]
+  fn dedup_reject() {
+    assert_eq!(
+      Dedup::Reject.apply(content(&["a", "b"])).unwrap(),
+      (content(&["a", "b"]), vec![0, 1])
+    );
+
+    assert_eq!(
+      Dedup::Reject
+        .apply(content(&["a", "b", "a", "b", "a"]))
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::DuplicateContent(vec![vec![0, 2, 4], vec![1, 3]]),
+    );
+  }
 
-      let signature = secp256k1.sign_schnorr(
-        &secp256k1::Message::from_slice(signature_hash.as_inner())
-          .expect("should be cryptographically secure hash"),
-        &key_pair,
-      );
+  #[test]
+  fn dedup_collapse() {
+    assert_eq!(
+      Dedup::Collapse
+        .apply(content(&["a", "b", "a", "c", "b"]))
+        .unwrap(),
+      (content(&["a", "b", "c"]), vec![0, 1, 0, 2, 1])
+    );
+  }
 
-      let witness = sighash_cache
-        .witness_mut(0)
-        .expect("getting mutable witness reference should work");
-      witness.push(signature.as_ref());
-      witness.push(reveal_script[i].clone());
-      witness.push(&control_block[i].serialize());
+  #[test]
+  fn dedup_allow() {
+    assert_eq!(
+      Dedup::Allow.apply(content(&["a", "a"])).unwrap(),
+      (content(&["a", "a"]), vec![0, 1])
+    );
+  }
 
-      let reveal_weight = reveal_tx.weight();
+  fn service() -> TxOut {
+    tx_out(0, change(2))
+  }
 
-      if !no_limit && reveal_weight > MAX_STANDARD_TX_WEIGHT.try_into().unwrap() {
-        bail!(
-        "reveal transaction weight greater than {MAX_STANDARD_TX_WEIGHT} (MAX_STANDARD_TX_WEIGHT): {reveal_weight}"
-      );
-      }
+  #[test]
+  fn grouping_packs_each_destination() {
+    let inscriptions = vec![inscription("text/plain", "foo"); 5];
+    let recipients = [recipient(), change(0), recipient(), change(0), recipient()]
+      .map(|destination| (destination, Amount::from_sat(546)));
 
-      reveal_txs.push(reveal_tx);
-    }
+    assert_eq!(
+      Mint::group(&inscriptions, &recipients, &service(), 400_000),
+      [vec![0, 2, 4], vec![1, 3]],
+    );
+  }
 
-    Ok((
-      unsigned_commit_tx,
-      reveal_txs,
-      recovery_key_pair,
-      service_fee,
-      satpoint_fee,
-      network_fee,
-    ))
+  #[test]
+  fn grouping_splits_groups_over_max_weight() {
+    let inscriptions = vec![inscription("text/plain", [0; 150_000]); 3];
+
+    let groups = Mint::group(
+      &inscriptions,
+      &vec![(recipient(), Amount::from_sat(546)); 3],
+      &service(),
+      400_000,
+    );
+
+    assert_eq!(groups, [vec![0, 1], vec![2]]);
+
+    let packed = builder::point(inscriptions[..2].to_vec(), &[Amount::from_sat(546); 2]);
+    assert!(
+      builder::reveal_weight(
+        &packed,
+        vec![
+          tx_out(546, recipient()),
+          tx_out(546, recipient()),
+          service()
+        ]
+      ) <= 400_000
+    );
   }
 
-  fn estimate_vsize(transaction: &Transaction, input_type: AddressType) -> usize {
-    let mut modified_tx = transaction.clone();
-    let witness_size = if input_type == AddressType::P2tr {
-      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
-    } else {
-      TransactionBuilder::P2WPKH_WINETSS_SIZE
-    };
-    for input in &mut modified_tx.input {
-      input.witness = Witness::from_vec(vec![vec![0; witness_size]]);
-    }
-    modified_tx.vsize()
+  #[test]
+  fn grouping_keeps_heavy_inscriptions_alone() {
+    let inscriptions = vec![inscription("text/plain", [0; 450_000]); 2];
+
+    assert_eq!(
+      Mint::group(
+        &inscriptions,
+        &vec![(recipient(), Amount::from_sat(546)); 2],
+        &service(),
+        400_000,
+      ),
+      [vec![0], vec![1]],
+    );
   }
 
-  fn build_reveal_transaction(
-    control_block: &ControlBlock,
-    fee_rate: FeeRate,
-    input: OutPoint,
-    output: Vec<TxOut>,
-    script: &Script,
-  ) -> (Transaction, Amount) {
-    let reveal_tx = Transaction {
-      input: vec![TxIn {
-        previous_output: input,
-        script_sig: script::Builder::new().into_script(),
-        witness: Witness::new(),
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-      }],
-      output,
-      lock_time: PackedLockTime::ZERO,
-      version: 1,
-    };
+  #[test]
+  fn destinations_default_to_destination() {
+    assert_eq!(
+      Mint::per_inscription("destination", Vec::new(), &recipient(), &[0, 1, 0], 2).unwrap(),
+      [recipient(), recipient()]
+    );
+  }
 
-    let fee = {
-      let mut reveal_tx = reveal_tx.clone();
+  #[test]
+  fn destinations_follow_content_items() {
+    assert_eq!(
+      Mint::per_inscription(
+        "destination",
+        vec![change(0), change(1), change(2)],
+        &recipient(),
+        &[0, 1, 2],
+        3
+      )
+      .unwrap(),
+      [change(0), change(1), change(2)]
+    );
+    assert_eq!(
+      Mint::per_inscription(
+        "destination",
+        vec![change(0), change(1), change(0)],
+        &recipient(),
+        &[0, 1, 0],
+        2
+      )
+      .unwrap(),
+      [change(0), change(1)]
+    );
+  }
 
-      reveal_tx.input[0].witness.push(
-        Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-          .unwrap()
-          .as_ref(),
-      );
-      reveal_tx.input[0].witness.push(script);
-      reveal_tx.input[0].witness.push(&control_block.serialize());
+  #[test]
+  fn destinations_must_match_content_items() {
+    assert_eq!(
+      Mint::per_inscription("destination", vec![change(0)], &recipient(), &[0, 1], 2)
+        .unwrap_err()
+        .to_string(),
+      "1 destination values given for 2 content items"
+    );
+    assert!(Mint::per_inscription(
+      "destination",
+      vec![change(0), change(1)],
+      &recipient(),
+      &[0, 0],
+      1
+    )
+    .is_err());
+  }
 
-      fee_rate.fee(reveal_tx.vsize())
-    };
+  #[test]
+  fn target_postages_follow_content_items() {
+    let postage = Amount::from_sat;
+    assert_eq!(
+      Mint::per_inscription(
+        "target postage",
+        vec![postage(546), postage(10_000), postage(546)],
+        &postage(1_000),
+        &[0, 1, 0],
+        2
+      )
+      .unwrap(),
+      [postage(546), postage(10_000)]
+    );
+    assert_eq!(
+      Mint::per_inscription("target postage", Vec::new(), &postage(1_000), &[0, 1], 2).unwrap(),
+      [postage(1_000), postage(1_000)]
+    );
+  }
 
-    (reveal_tx, fee)
+  #[test]
+  fn dedup_from_str() {
+    assert_eq!("collapse".parse::<Dedup>().unwrap(), Dedup::Collapse);
+    assert!("skip".parse::<Dedup>().is_err());
   }
 }
@@ -0,0 +1,205 @@
+use {
+  super::*,
+  crate::builder::{self, RecoverParams},
+  bitcoin::{
+    consensus::encode::serialize_hex,
+    schnorr::{TweakedKeyPair, TweakedPublicKey},
+    secp256k1::KeyPair,
+    util::key::PrivateKey,
+  },
+};
+
+/// The key that spends a commit output back when its reveal is lost. It is
+/// a secret, whoever holds it can take the commit funds.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RecoveryKey {
+  pub commit_output: OutPoint,
+  pub commit_address: Address,
+  /// The tweaked secret key as WIF, spending `commit_output` by its key path.
+  pub secret_key: String,
+}
+
+impl RecoveryKey {
+  /// The recovery key of each output of `commit` one of `key_pairs`
+  /// unlocks. Reveals of the same content under the same key share a
+  /// commit address, so a key can unlock several outputs.
+  pub(crate) fn all(
+    commit: &Transaction,
+    key_pairs: &[TweakedKeyPair],
+    network: Network,
+  ) -> Vec<Self> {
+    let addresses = key_pairs
+      .iter()
+      .map(|key_pair| {
+        let (public_key, _parity) = key_pair.to_inner().x_only_public_key();
+        (
+          key_pair,
+          Address::p2tr_tweaked(
+            TweakedPublicKey::dangerous_assume_tweaked(public_key),
+            network,
+          ),
+        )
+      })
+      .collect::<Vec<_>>();
+
+    commit
+      .output
+      .iter()
+      .enumerate()
+      .filter_map(|(vout, output)| {
+        let (key_pair, commit_address) = addresses
+          .iter()
+          .find(|(_key_pair, address)| address.script_pubkey() == output.script_pubkey)?;
+
+        Some(Self {
+          commit_output: OutPoint {
+            txid: commit.txid(),
+            vout: u32::try_from(vout).ok()?,
+          },
+          commit_address: commit_address.clone(),
+          secret_key: PrivateKey::new(key_pair.to_inner().secret_key(), network).to_wif(),
+        })
+      })
+      .collect()
+  }
+
+  fn key_pair(secret_key: &str) -> Result<TweakedKeyPair> {
+    let private_key =
+      PrivateKey::from_wif(secret_key).map_err(|err| anyhow!("invalid recovery key: {err}"))?;
+    Ok(TweakedKeyPair::dangerous_assume_tweaked(
+      KeyPair::from_secret_key(&Secp256k1::new(), &private_key.inner),
+    ))
+  }
+}
+
+/// Sweeps a commit output whose reveal was lost back to `destination`,
+/// spending it with the key `mint --return-recovery-key` handed out.
+#[derive(Debug, Parser)]
+pub struct Recover {
+  #[clap(
+    long,
+    help = "Spend with <RECOVERY_KEY>, the WIF `secret_key` of a returned recovery key."
+  )]
+  pub recovery_key: String,
+  #[clap(long, help = "Sweep commit output <COMMIT_OUTPUT>.")]
+  pub commit_output: OutPoint,
+  #[clap(long, help = "Send the commit funds to <DESTINATION>.")]
+  pub destination: Address,
+  #[clap(long, help = "Use fee rate of <FEE_RATE> sats/vB")]
+  pub fee_rate: FeeRate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub transaction: String,
+  pub txid: Txid,
+  pub network_fee: u64,
+}
+
+impl Recover {
+  pub fn build(self, options: Options) -> Result<Output> {
+    check_destination_address(&self.destination, &options, false)?;
+    let key_pair = RecoveryKey::key_pair(&self.recovery_key)?;
+
+    let index = Index::read_open(&options)?;
+
+    let output = index
+      .get_transaction_by_mempool(self.commit_output.txid)?
+      .output
+      .get(self.commit_output.vout as usize)
+      .cloned()
+      .ok_or_else(|| anyhow!("commit output {} does not exist", self.commit_output))?;
+
+    let recovered = builder::recover(RecoverParams {
+      commit_output: self.commit_output,
+      output: output.clone(),
+      key_pair,
+      destination: self.destination,
+      fee_rate: self.fee_rate,
+    })?;
+
+    let network_fee = output.value - recovered.output[0].value;
+
+    tracing::info!(
+      commit_output = %self.commit_output,
+      network_fee,
+      "Recover commit output success"
+    );
+
+    Ok(Output {
+      transaction: serialize_hex(&recovered),
+      txid: recovered.txid(),
+      network_fee,
+    })
+  }
+
+  pub fn run(self, options: Options) -> Result {
+    print_json(self.build(options)?)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::builder::{inscribe, CommitParams, KeySource},
+  };
+
+  #[test]
+  fn recovery_keys_name_the_outputs_they_unlock() {
+    let inscribed = inscribe(
+      CommitParams {
+        input_type: AddressType::P2wpkh,
+        network: Network::Testnet,
+        satpoints: Vec::new(),
+        inscriptions: BTreeMap::new(),
+        utxos: [(outpoint(1), Amount::from_sat(100_000))].into(),
+        change: [change(0), change(1)],
+        destination: recipient(),
+        groups: Vec::new(),
+        commit_fee_rate: FeeRate::try_from(1.0).unwrap(),
+        reveal_fee_rate: FeeRate::try_from(1.0).unwrap(),
+        max_nonstandard_weight: None,
+        service_address: change(2),
+        service_fee: Amount::ZERO,
+        additional_service_fee: Amount::ZERO,
+        target_postage: Amount::from_sat(546),
+        recipients: Vec::new(),
+        parent: None,
+        key_source: KeySource::Random,
+      },
+      vec![inscription("text/plain", "foo"); 2],
+    )
+    .unwrap();
+
+    let keys = RecoveryKey::all(
+      &inscribed.commit,
+      &inscribed.recovery_key_pairs,
+      Network::Testnet,
+    );
+
+    assert_eq!(keys.len(), 2);
+    for (vout, key) in keys.iter().enumerate() {
+      assert_eq!(
+        key.commit_output,
+        OutPoint {
+          txid: inscribed.commit.txid(),
+          vout: u32::try_from(vout).unwrap(),
+        }
+      );
+      assert_eq!(
+        RecoveryKey::key_pair(&key.secret_key).unwrap(),
+        inscribed.recovery_key_pairs[vout]
+      );
+    }
+  }
+
+  #[test]
+  fn recovery_key_must_be_wif() {
+    assert!(RecoveryKey::key_pair("not a key")
+      .unwrap_err()
+      .to_string()
+      .starts_with("invalid recovery key"));
+  }
+}
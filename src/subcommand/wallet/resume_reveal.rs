@@ -0,0 +1,131 @@
+use {
+  super::*,
+  crate::index::MysqlDatabase,
+  bitcoin::{consensus::encode::deserialize, hashes::hex::FromHex},
+  bitcoincore_rpc::RawTx,
+};
+
+/// Hands back the reveals persisted for a commit that confirmed while its
+/// reveals were never broadcast.
+#[derive(Debug)]
+pub struct ResumeReveal {
+  pub commit: Txid,
+  pub broadcast: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+  pub commit: Txid,
+  pub reveal: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub broadcast: Option<broadcast::Output>,
+}
+
+impl ResumeReveal {
+  pub fn build(self, options: Options, mysql: &MysqlDatabase) -> Result<Output> {
+    let mut reveals = vec![];
+    for hex in mysql.get_reveal_hexes(self.commit)? {
+      reveals.push(
+        deserialize::<Transaction>(&Vec::from_hex(&hex)?)
+          .map_err(|err| anyhow!("invalid persisted reveal: {err}"))?,
+      );
+    }
+
+    if reveals.is_empty() {
+      return Err(BuildError::RevealsNotPersisted(self.commit).into());
+    }
+
+    let reveals = Self::order(self.commit, reveals);
+
+    let client = options.bitcoin_rpc_client()?;
+    for reveal in &reveals {
+      for input in &reveal.input {
+        let outpoint = input.previous_output;
+        if outpoint.txid != self.commit {
+          continue;
+        }
+        if client
+          .get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?
+          .is_none()
+        {
+          return Err(BuildError::CommitOutputSpent(outpoint).into());
+        }
+      }
+    }
+
+    let reveal = reveals
+      .iter()
+      .map(|tx| tx.raw_hex())
+      .collect::<Vec<String>>();
+
+    let broadcast = if self.broadcast {
+      Some(
+        broadcast::Broadcast {
+          tx: reveal.clone(),
+          package: false,
+        }
+        .build(options)?,
+      )
+    } else {
+      None
+    };
+
+    Ok(Output {
+      commit: self.commit,
+      reveal,
+      broadcast,
+    })
+  }
+
+  // reveals are persisted unordered, reveal i spends commit output i
+  fn order(commit: Txid, mut reveals: Vec<Transaction>) -> Vec<Transaction> {
+    reveals.sort_by_key(|reveal| {
+      reveal
+        .input
+        .iter()
+        .find(|input| input.previous_output.txid == commit)
+        .map(|input| input.previous_output.vout)
+    });
+    reveals
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, bitcoin::PackedLockTime};
+
+  fn reveal(commit: Txid, vout: u32) -> Transaction {
+    Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(OutPoint { txid: commit, vout })],
+      output: vec![tx_out(546, recipient())],
+    }
+  }
+
+  #[test]
+  fn reveals_follow_commit_outputs() {
+    let commit = txid(1);
+    let reveals = vec![reveal(commit, 2), reveal(commit, 0), reveal(commit, 1)];
+
+    assert_eq!(
+      ResumeReveal::order(commit, reveals),
+      vec![reveal(commit, 0), reveal(commit, 1), reveal(commit, 2)]
+    );
+  }
+
+  #[test]
+  fn errors_tell_missing_from_spent() {
+    assert_eq!(
+      BuildError::RevealsNotPersisted(txid(1)).to_string(),
+      format!("no reveals were persisted for commit {}", txid(1))
+    );
+    assert_eq!(
+      BuildError::CommitOutputSpent(outpoint(1)).to_string(),
+      format!(
+        "commit output {} is already spent or was never broadcast",
+        outpoint(1)
+      )
+    );
+  }
+}
@@ -0,0 +1,226 @@
+use {
+  super::*,
+  crate::builder,
+  bitcoin::{consensus::encode::deserialize, hashes::hex::FromHex, Denomination, Witness},
+};
+
+/// Compares what a signed reveal pays with what the node currently needs,
+/// and works out the child fee that would pull it through by CPFP.
+#[derive(Debug, Parser)]
+pub struct RevealFeeCheck {
+  #[clap(long, help = "Check raw reveal transaction <REVEAL> hex.")]
+  pub reveal: String,
+  #[clap(
+    long,
+    help = "Aim for <TARGET_FEE_RATE> sats/vB instead of the node's next block estimate."
+  )]
+  pub target_fee_rate: Option<FeeRate>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Output {
+  pub txid: Txid,
+  pub vsize: u64,
+  pub fee: u64,
+  pub fee_rate: f64,
+  pub mempool_min_fee_rate: f64,
+  pub target_fee_rate: f64,
+  pub below_mempool_min_fee: bool,
+  /// Absent when the reveal already pays the target.
+  pub cpfp: Option<Cpfp>,
+}
+
+/// A child spending `spend`, the reveal's inscription output, together with
+/// one more input of the same type for the fee, sending the inscription
+/// back and the rest to change.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Cpfp {
+  pub spend: OutPoint,
+  pub child_vsize: u64,
+  pub child_fee: u64,
+  pub package_fee_rate: f64,
+}
+
+impl Cpfp {
+  pub(crate) fn recommend(
+    spend: OutPoint,
+    fee: u64,
+    vsize: u64,
+    child_vsize: u64,
+    target_fee_rate: f64,
+  ) -> Option<Self> {
+    if fee as f64 >= target_fee_rate * vsize as f64 {
+      return None;
+    }
+
+    let package_vsize = vsize + child_vsize;
+    let child_fee = Amount::from_float_in(
+      (target_fee_rate * package_vsize as f64).ceil(),
+      Denomination::Satoshi,
+    )
+    .ok()?
+    .to_sat()
+    .saturating_sub(fee);
+
+    Some(Self {
+      spend,
+      child_vsize,
+      child_fee,
+      package_fee_rate: (fee + child_fee) as f64 / package_vsize as f64,
+    })
+  }
+}
+
+impl RevealFeeCheck {
+  pub fn build(self, options: Options) -> Result<Output> {
+    let reveal: Transaction = deserialize(&Vec::from_hex(self.reveal.trim())?)
+      .map_err(|err| anyhow!("invalid reveal transaction: {err}"))?;
+    let txid = reveal.txid();
+
+    let index = Index::read_open(&options)?;
+
+    let fee = Self::fee(&index, &reveal)?;
+    let vsize = u64::try_from(reveal.vsize())?;
+
+    let mempool_min_fee_rate = index.mempool_min_fee_rate()?;
+    let target_fee_rate = match self.target_fee_rate {
+      Some(target_fee_rate) => target_fee_rate.0,
+      None => index.fast_fee_rate()?.unwrap_or(mempool_min_fee_rate),
+    }
+    .max(mempool_min_fee_rate)
+    .max(builder::MIN_RELAY_FEE_RATE);
+
+    let destination = reveal
+      .output
+      .first()
+      .ok_or_else(|| anyhow!("reveal {txid} has no outputs"))?;
+    let child_vsize = u64::try_from(Self::child_vsize(
+      txid,
+      destination,
+      options.chain().network(),
+    ))?;
+
+    Ok(Output {
+      txid,
+      vsize,
+      fee,
+      fee_rate: fee as f64 / vsize as f64,
+      mempool_min_fee_rate,
+      target_fee_rate,
+      below_mempool_min_fee: (fee as f64) < mempool_min_fee_rate * vsize as f64,
+      cpfp: Cpfp::recommend(
+        OutPoint { txid, vout: 0 },
+        fee,
+        vsize,
+        child_vsize,
+        target_fee_rate,
+      ),
+    })
+  }
+
+  /// The fee `reveal` pays, from the outputs it spends as the node's
+  /// mempool API reports them.
+  pub(crate) fn fee(index: &Index, reveal: &Transaction) -> Result<u64> {
+    let txid = reveal.txid();
+    let mut input_value = 0;
+    for input in &reveal.input {
+      let outpoint = input.previous_output;
+      input_value += index
+        .get_transaction_by_mempool(outpoint.txid)?
+        .output
+        .get(usize::try_from(outpoint.vout)?)
+        .ok_or_else(|| anyhow!("output {outpoint} spent by reveal {txid} does not exist"))?
+        .value;
+    }
+    input_value
+      .checked_sub(reveal.output.iter().map(|output| output.value).sum())
+      .ok_or_else(|| anyhow!("reveal {txid} spends less than it creates"))
+  }
+
+  fn child_vsize(reveal: Txid, destination: &TxOut, network: Network) -> usize {
+    let input_type = Address::from_script(&destination.script_pubkey, network)
+      .ok()
+      .and_then(|address| address.address_type())
+      .unwrap_or(AddressType::P2tr);
+
+    let child = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: [0, 1]
+        .map(|vout| TxIn {
+          previous_output: OutPoint {
+            txid: if vout == 0 { reveal } else { Txid::all_zeros() },
+            vout,
+          },
+          script_sig: Script::new(),
+          sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+          witness: Witness::new(),
+        })
+        .to_vec(),
+      output: vec![destination.clone(), destination.clone()],
+    };
+
+    builder::estimate_vsize(&child, input_type)
+  }
+
+  pub fn run(self, options: Options) -> Result {
+    print_json(self.build(options)?)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reveals_paying_the_target_need_no_child() {
+    assert_eq!(Cpfp::recommend(outpoint(1), 1500, 150, 200, 10.0), None);
+    assert_eq!(Cpfp::recommend(outpoint(1), 1500, 150, 200, 5.0), None);
+  }
+
+  #[test]
+  fn child_fee_lifts_the_package_to_the_target() {
+    let cpfp = Cpfp::recommend(outpoint(1), 300, 150, 200, 10.0).unwrap();
+    assert_eq!(
+      cpfp,
+      Cpfp {
+        spend: outpoint(1),
+        child_vsize: 200,
+        child_fee: 3200,
+        package_fee_rate: 10.0,
+      }
+    );
+  }
+
+  #[test]
+  fn child_fee_rounds_up() {
+    let cpfp = Cpfp::recommend(outpoint(1), 1499, 150, 200, 10.0).unwrap();
+    assert_eq!(cpfp.child_fee, 2001);
+
+    let cpfp = Cpfp::recommend(outpoint(1), 100, 150, 201, 1.5).unwrap();
+    assert_eq!(cpfp.child_fee, 427);
+    assert!(cpfp.package_fee_rate >= 1.5);
+  }
+
+  #[test]
+  fn child_size_follows_the_destination_type() {
+    let p2wpkh = RevealFeeCheck::child_vsize(txid(1), &tx_out(546, change(0)), Network::Testnet);
+    let p2tr = RevealFeeCheck::child_vsize(
+      txid(1),
+      &TxOut {
+        value: 546,
+        script_pubkey: Script::new_v1_p2tr_tweaked(
+          bitcoin::schnorr::TweakedPublicKey::dangerous_assume_tweaked(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+              .parse()
+              .unwrap(),
+          ),
+        ),
+      },
+      Network::Testnet,
+    );
+    assert!(p2wpkh > 0);
+    assert_ne!(p2wpkh, p2tr);
+  }
+}
@@ -0,0 +1,439 @@
+use {
+  super::*,
+  bitcoin::{
+    blockdata::{opcodes::all, script::Instruction},
+    hashes::{hex::FromHex, sha256},
+    psbt::Psbt,
+    schnorr::TapTweak,
+    secp256k1::{self, Secp256k1, XOnlyPublicKey},
+    util::taproot::{ControlBlock, LeafVersion, TapBranchHash, TapLeafHash, TaprootMerkleBranch},
+    AddressType,
+  },
+};
+
+/// A source input spent through a tapscript leaf instead of the key path,
+/// such as a multisig held under taproot. The leaf is proven either by its
+/// `control_block`, or by the `internal_key` and the `merkle_path` from the
+/// leaf's sibling up to the root, empty for a single leaf tree.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScriptPathInput {
+  pub outpoint: OutPoint,
+  pub leaf_script: Script,
+  #[serde(default)]
+  pub control_block: Option<String>,
+  #[serde(default)]
+  pub internal_key: Option<XOnlyPublicKey>,
+  #[serde(default)]
+  pub merkle_path: Vec<sha256::Hash>,
+}
+
+impl FromStr for ScriptPathInput {
+  type Err = Error;
+
+  // <TXID>:<VOUT>:<LEAF_SCRIPT_HEX>:<CONTROL_BLOCK_HEX>
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = s.rsplitn(3, ':');
+    let control_block = parts.next().unwrap_or_default();
+    let (Some(leaf_script), Some(outpoint)) = (parts.next(), parts.next()) else {
+      bail!(
+        "invalid script path input `{s}`, expected <TXID>:<VOUT>:<LEAF_SCRIPT_HEX>:<CONTROL_BLOCK_HEX>"
+      );
+    };
+
+    Ok(Self {
+      outpoint: outpoint.parse()?,
+      leaf_script: Script::from_str(leaf_script)?,
+      control_block: Some(control_block.into()),
+      internal_key: None,
+      merkle_path: Vec::new(),
+    })
+  }
+}
+
+/// A script path input checked against the output it spends.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ScriptSpend {
+  leaf_script: Script,
+  control_block: ControlBlock,
+  merkle_root: TapBranchHash,
+}
+
+impl ScriptPathInput {
+  /// Checks that the leaf is committed to by the output key of
+  /// `script_pubkey`, which must be a P2TR output.
+  pub(crate) fn resolve(&self, script_pubkey: &Script) -> Result<ScriptSpend> {
+    if !script_pubkey.is_v1_p2tr() {
+      bail!(
+        "script path input {} does not spend a P2TR output",
+        self.outpoint
+      );
+    }
+    let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..])?;
+
+    let control_block = match (&self.control_block, self.internal_key) {
+      (Some(control_block), None) => ControlBlock::from_slice(&Vec::from_hex(control_block)?)?,
+      (None, Some(internal_key)) => ControlBlock {
+        leaf_version: LeafVersion::TapScript,
+        // set below, once the merkle root is known
+        output_key_parity: secp256k1::Parity::Even,
+        internal_key,
+        merkle_branch: TaprootMerkleBranch::try_from(self.merkle_path.clone()).map_err(|_| {
+          BuildError::MerklePathTooDeep {
+            outpoint: self.outpoint,
+            depth: self.merkle_path.len(),
+          }
+        })?,
+      },
+      _ => bail!(
+        "script path input {} needs either a control block or an internal key",
+        self.outpoint
+      ),
+    };
+
+    let mut node = sha256::Hash::from_inner(
+      TapLeafHash::from_script(&self.leaf_script, LeafVersion::TapScript).into_inner(),
+    );
+    for sibling in control_block.merkle_branch.as_inner() {
+      node = sha256::Hash::from_inner(TapBranchHash::from_node_hashes(node, *sibling).into_inner());
+    }
+    let merkle_root = TapBranchHash::from_inner(node.into_inner());
+
+    let (tweaked, parity) = control_block
+      .internal_key
+      .tap_tweak(&Secp256k1::verification_only(), Some(merkle_root));
+    if tweaked.to_inner() != output_key
+      || (self.control_block.is_some() && parity != control_block.output_key_parity)
+    {
+      bail!(
+        "leaf script of script path input {} is not committed to by its output key",
+        self.outpoint
+      );
+    }
+
+    Ok(ScriptSpend {
+      leaf_script: self.leaf_script.clone(),
+      control_block: ControlBlock {
+        output_key_parity: parity,
+        ..control_block
+      },
+      merkle_root,
+    })
+  }
+}
+
+impl ScriptSpend {
+  /// Witness bytes of the spend, with a signature for every signature
+  /// check in the leaf script, each with an explicit sighash byte to be
+  /// safe.
+  pub(crate) fn witness_size(&self) -> usize {
+    let signatures = self
+      .leaf_script
+      .instructions()
+      .filter(|instruction| {
+        matches!(
+          instruction,
+          Ok(Instruction::Op(op))
+            if *op == all::OP_CHECKSIG || *op == all::OP_CHECKSIGVERIFY || *op == all::OP_CHECKSIGADD
+        )
+      })
+      .count()
+      .max(1);
+
+    signatures * with_length(TransactionBuilder::SCHNORR_SIGNATURE_SIZE + 1)
+      + with_length(self.leaf_script.len())
+      + with_length(self.control_block.size())
+  }
+}
+
+// a witness element and its length prefix
+fn with_length(len: usize) -> usize {
+  len
+    + match len {
+      0..=0xfc => 1,
+      0xfd..=0xffff => 3,
+      _ => 5,
+    }
+}
+
+/// Resolves `inputs` against `source`, which they all spend, keyed by
+/// outpoint.
+pub(crate) fn script_spends(
+  inputs: &[ScriptPathInput],
+  source: &Address,
+) -> Result<BTreeMap<OutPoint, ScriptSpend>> {
+  if !inputs.is_empty() && source.address_type() != Some(AddressType::P2tr) {
+    bail!("script path inputs need a P2TR source, {source} is not one");
+  }
+
+  let mut spends = BTreeMap::new();
+  for input in inputs {
+    if spends
+      .insert(input.outpoint, input.resolve(&source.script_pubkey())?)
+      .is_some()
+    {
+      bail!(
+        "script path input {} is listed more than once",
+        input.outpoint
+      );
+    }
+  }
+  Ok(spends)
+}
+
+/// Witness sizes of `spends`, for fee estimation.
+pub(crate) fn witness_sizes(spends: &BTreeMap<OutPoint, ScriptSpend>) -> BTreeMap<OutPoint, usize> {
+  spends
+    .iter()
+    .map(|(outpoint, spend)| (*outpoint, spend.witness_size()))
+    .collect()
+}
+
+/// Sets the taproot script spend fields of the inputs of `psbt` that spend
+/// one of `spends`, so signers know which leaf to sign for.
+pub(crate) fn add_to_psbt(psbt: &mut Psbt, spends: &BTreeMap<OutPoint, ScriptSpend>) {
+  for (txin, input) in psbt.unsigned_tx.input.iter().zip(psbt.inputs.iter_mut()) {
+    let Some(spend) = spends.get(&txin.previous_output) else {
+      continue;
+    };
+    input.tap_scripts.insert(
+      spend.control_block.clone(),
+      (spend.leaf_script.clone(), LeafVersion::TapScript),
+    );
+    input.tap_internal_key = Some(spend.control_block.internal_key);
+    input.tap_merkle_root = Some(spend.merkle_root);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::builder::{self, CancelParams},
+    bitcoin::{
+      blockdata::script,
+      hashes::hex::ToHex,
+      secp256k1::{rand, KeyPair, Message},
+      util::sighash::{Prevouts, SighashCache},
+      util::taproot::TaprootBuilder,
+      SchnorrSighashType,
+    },
+  };
+
+  struct Multisig {
+    keys: [KeyPair; 2],
+    leaf_script: Script,
+    internal_key: XOnlyPublicKey,
+    source: Address,
+    control_block: ControlBlock,
+  }
+
+  // a 2-of-2 leaf next to a second leaf, so the merkle path isn't empty
+  fn multisig() -> Multisig {
+    let secp = Secp256k1::new();
+    let keys = [
+      KeyPair::new(&secp, &mut rand::thread_rng()),
+      KeyPair::new(&secp, &mut rand::thread_rng()),
+    ];
+    let leaf_script = script::Builder::new()
+      .push_slice(&XOnlyPublicKey::from_keypair(&keys[0]).0.serialize())
+      .push_opcode(all::OP_CHECKSIGVERIFY)
+      .push_slice(&XOnlyPublicKey::from_keypair(&keys[1]).0.serialize())
+      .push_opcode(all::OP_CHECKSIG)
+      .into_script();
+    let other_script = script::Builder::new()
+      .push_int(144)
+      .push_opcode(all::OP_CSV)
+      .into_script();
+
+    let internal_key =
+      XOnlyPublicKey::from_keypair(&KeyPair::new(&secp, &mut rand::thread_rng())).0;
+    let spend_info = TaprootBuilder::new()
+      .add_leaf(1, leaf_script.clone())
+      .unwrap()
+      .add_leaf(1, other_script)
+      .unwrap()
+      .finalize(&secp, internal_key)
+      .unwrap();
+
+    Multisig {
+      keys,
+      control_block: spend_info
+        .control_block(&(leaf_script.clone(), LeafVersion::TapScript))
+        .unwrap(),
+      leaf_script,
+      internal_key,
+      source: Address::p2tr_tweaked(spend_info.output_key(), Network::Regtest),
+    }
+  }
+
+  #[test]
+  fn parse() {
+    let input = format!("{}:00:ac", outpoint(1))
+      .parse::<ScriptPathInput>()
+      .unwrap();
+    assert_eq!(input.outpoint, outpoint(1));
+    assert_eq!(input.leaf_script, Script::from_str("00").unwrap());
+    assert_eq!(input.control_block, Some("ac".into()));
+
+    assert!("00:ac".parse::<ScriptPathInput>().is_err());
+    assert!(format!("{}:zz:ac", outpoint(1))
+      .parse::<ScriptPathInput>()
+      .is_err());
+  }
+
+  #[test]
+  fn control_block_and_merkle_path_resolve_alike() {
+    let multisig = multisig();
+
+    let from_control_block = ScriptPathInput {
+      outpoint: outpoint(1),
+      leaf_script: multisig.leaf_script.clone(),
+      control_block: Some(multisig.control_block.serialize().to_hex()),
+      internal_key: None,
+      merkle_path: Vec::new(),
+    }
+    .resolve(&multisig.source.script_pubkey())
+    .unwrap();
+
+    let from_merkle_path = ScriptPathInput {
+      outpoint: outpoint(1),
+      leaf_script: multisig.leaf_script.clone(),
+      control_block: None,
+      internal_key: Some(multisig.internal_key),
+      merkle_path: multisig.control_block.merkle_branch.as_inner().to_vec(),
+    }
+    .resolve(&multisig.source.script_pubkey())
+    .unwrap();
+
+    assert_eq!(from_control_block, from_merkle_path);
+    assert_eq!(from_control_block.control_block, multisig.control_block);
+  }
+
+  #[test]
+  fn leaf_must_be_committed_to() {
+    let multisig = multisig();
+
+    let input = ScriptPathInput {
+      outpoint: outpoint(1),
+      leaf_script: Script::from_str("51").unwrap(),
+      control_block: Some(multisig.control_block.serialize().to_hex()),
+      internal_key: None,
+      merkle_path: Vec::new(),
+    };
+    assert!(input.resolve(&multisig.source.script_pubkey()).is_err());
+
+    let input = ScriptPathInput {
+      leaf_script: multisig.leaf_script.clone(),
+      ..input
+    };
+    assert!(input.resolve(&recipient().script_pubkey()).is_err());
+    assert!(script_spends(std::slice::from_ref(&input), &recipient()).is_err());
+    assert!(script_spends(&[input.clone(), input], &multisig.source).is_err());
+  }
+
+  #[test]
+  fn merkle_path_depth_is_limited() {
+    let multisig = multisig();
+
+    let input = ScriptPathInput {
+      outpoint: outpoint(1),
+      leaf_script: multisig.leaf_script.clone(),
+      control_block: None,
+      internal_key: Some(multisig.internal_key),
+      merkle_path: vec![sha256::Hash::all_zeros(); 129],
+    };
+    assert_eq!(
+      input
+        .resolve(&multisig.source.script_pubkey())
+        .unwrap_err()
+        .downcast::<BuildError>()
+        .unwrap(),
+      BuildError::MerklePathTooDeep {
+        outpoint: outpoint(1),
+        depth: 129,
+      }
+    );
+  }
+
+  #[test]
+  fn two_of_two_cancel_signs_within_estimated_fee() {
+    let multisig = multisig();
+    let inputs = [ScriptPathInput {
+      outpoint: outpoint(1),
+      leaf_script: multisig.leaf_script.clone(),
+      control_block: Some(multisig.control_block.serialize().to_hex()),
+      internal_key: None,
+      merkle_path: Vec::new(),
+    }];
+    let spends = script_spends(&inputs, &multisig.source).unwrap();
+    let utxos = BTreeMap::from([(outpoint(1), Amount::from_sat(10_000))]);
+
+    let params = CancelParams {
+      input_type: AddressType::P2tr,
+      inputs: vec![outpoint(1)],
+      utxos: utxos.clone(),
+      source: multisig.source.clone(),
+      service: None,
+      fee_rate: FeeRate::try_from(2.0).unwrap(),
+      locktime: None,
+      rbf: true,
+      witness_sizes: BTreeMap::new(),
+    };
+    let key_path = builder::cancel(params.clone()).unwrap();
+    let cancelled = builder::cancel(CancelParams {
+      witness_sizes: witness_sizes(&spends),
+      ..params
+    })
+    .unwrap();
+    assert!(cancelled.network_fee > key_path.network_fee);
+
+    let mut psbt = builder::psbt(&cancelled.transaction, &utxos, |_| {
+      multisig.source.script_pubkey()
+    })
+    .unwrap();
+    add_to_psbt(&mut psbt, &spends);
+
+    let input = &psbt.inputs[0];
+    assert_eq!(input.tap_internal_key, Some(multisig.internal_key));
+    assert_eq!(
+      input.tap_scripts.get(&multisig.control_block),
+      Some(&(multisig.leaf_script.clone(), LeafVersion::TapScript))
+    );
+
+    // sign as each cosigner would, from the psbt alone
+    let secp = Secp256k1::new();
+    let (control_block, (leaf_script, leaf_version)) = input.tap_scripts.iter().next().unwrap();
+    let sighash = SighashCache::new(&psbt.unsigned_tx)
+      .taproot_script_spend_signature_hash(
+        0,
+        &Prevouts::All(&[input.witness_utxo.clone().unwrap()]),
+        TapLeafHash::from_script(leaf_script, *leaf_version),
+        SchnorrSighashType::Default,
+      )
+      .unwrap();
+    let message = Message::from_slice(sighash.as_inner()).unwrap();
+    let signatures = multisig.keys.map(|key| {
+      let signature = secp.sign_schnorr(&message, &key);
+      secp
+        .verify_schnorr(&signature, &message, &XOnlyPublicKey::from_keypair(&key).0)
+        .unwrap();
+      signature
+    });
+
+    let mut signed = psbt.unsigned_tx.clone();
+    let witness = &mut signed.input[0].witness;
+    witness.push(signatures[1].as_ref());
+    witness.push(signatures[0].as_ref());
+    witness.push(leaf_script.as_bytes());
+    witness.push(control_block.serialize());
+
+    assert!(
+      builder::estimate_vsize_with_witnesses(
+        &cancelled.transaction,
+        AddressType::P2tr,
+        &witness_sizes(&spends)
+      ) >= signed.vsize()
+    );
+    assert!(cancelled.network_fee >= 2 * signed.vsize() as u64);
+  }
+}
@@ -50,7 +50,13 @@ impl Send {
 
     let unspent_outputs = index.get_unspent_outputs(Wallet::load(&options)?)?;
 
-    let inscriptions = index.get_inscriptions(None)?;
+    let outgoing_outpoint = match &self.outgoing {
+      Outgoing::SatPoint(satpoint) => Some(satpoint.outpoint),
+      _ => None,
+    };
+
+    let inscriptions =
+      index.get_inscriptions_for_outpoints(unspent_outputs.keys().chain(&outgoing_outpoint))?;
 
     let satpoint = match self.outgoing {
       Outgoing::SatPoint(satpoint) => {
@@ -64,6 +70,14 @@ impl Send {
       Outgoing::InscriptionId(id) => index
         .get_inscription_satpoint_by_id(id)?
         .ok_or_else(|| anyhow!("Inscription {id} not found"))?,
+      Outgoing::InscriptionNumber(number) => {
+        let id = index
+          .get_inscription_id_by_inscription_number(number)?
+          .ok_or(BuildError::InscriptionNumbersNotFound(vec![number]))?;
+        index
+          .get_inscription_satpoint_by_id(id)?
+          .ok_or_else(|| anyhow!("Inscription {id} not found"))?
+      }
       Outgoing::Amount(amount) => {
         let all_inscription_outputs = inscriptions
           .keys()
@@ -107,11 +107,13 @@ pub struct TransactionBuilder {
   inscriptions: BTreeMap<SatPoint, InscriptionId>,
   outgoing: SatPoint,
   outputs: Vec<(Address, Amount)>,
+  postages: BTreeMap<SatPoint, Amount>,
   recipient: Address,
   unused_change_addresses: Vec<Address>,
   utxos: BTreeSet<OutPoint>,
   target: Target,
   op_return: Option<Vec<u8>>,
+  witness_sizes: BTreeMap<OutPoint, usize>,
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -124,6 +126,15 @@ impl TransactionBuilder {
   pub(crate) const P2WPKH_WINETSS_SIZE: usize = 108;
   pub const TARGET_POSTAGE: Amount = Amount::from_sat(546);
 
+  /// Witness bytes of a key path spend by an `input_type` wallet.
+  pub(crate) fn key_path_witness_size(input_type: AddressType) -> usize {
+    if input_type == AddressType::P2tr {
+      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
+    } else {
+      TransactionBuilder::P2WPKH_WINETSS_SIZE
+    }
+  }
+
   pub fn build_transaction_with_postage(
     input_type: AddressType,
     outgoing: SatPoint,
@@ -315,42 +326,129 @@ impl TransactionBuilder {
     )
   }
 
-  pub fn build_transaction_with_op_return_v1(
+  /// Sends every outgoing sat to `recipient`. The recipient output is sized
+  /// so each sat keeps at least its own postage behind it, with `value` paid
+  /// on top. Inputs in `witness_sizes` are estimated at their witness size
+  /// instead of a key path spend.
+  pub fn build_transaction_with_postages_v1(
     input_type: AddressType,
-    outgoings: Vec<SatPoint>,
+    witness_sizes: BTreeMap<OutPoint, usize>,
+    postages: Vec<(SatPoint, Amount)>,
     inscriptions: BTreeMap<SatPoint, InscriptionId>,
     amounts: BTreeMap<OutPoint, Amount>,
-    outputs: Vec<(Address, Amount)>,
+    recipient: Address,
+    value: Amount,
     change: [Address; 2],
     fee_rate: FeeRate,
-    op_return: String,
+    op_return: Option<String>,
   ) -> Result<Transaction> {
-    let recipient = outputs[outputs.len() - 1].0.clone();
-    let output_value = outputs[outputs.len() - 1].1;
-    let dust_value = recipient.script_pubkey().dust_value();
-
-    if output_value < dust_value {
-      return Err(Error::Dust {
-        output_value,
-        dust_value,
-      });
-    }
-
-    Self::new(
+    let mut builder = Self::new(
       input_type,
-      outgoings[0],
+      postages[0].0,
       inscriptions,
       amounts,
       recipient,
       change,
       fee_rate,
-      Target::Value(output_value),
-      Some(String::into_bytes(op_return)),
-    )?
-    .build_transaction_v1(
-      outgoings[1..].to_vec(),
-      outputs[..outputs.len() - 1].to_vec(),
-    )
+      Target::Value(value),
+      op_return.map(String::into_bytes),
+    )?;
+    builder.postages = postages.iter().copied().collect();
+    builder.witness_sizes = witness_sizes;
+
+    builder = builder.select_outgoing()?;
+    for (satpoint, _postage) in postages[1..].iter().rev() {
+      if !builder.inputs.contains(&satpoint.outpoint) {
+        builder = builder.add_outgoing(*satpoint)?;
+      }
+    }
+
+    builder
+      .align_outgoing()
+      .pad_alignment_output()?
+      .size_for_postages()?
+      .add_value()?
+      .strip_value()
+      .deduct_fee()
+      .build()
+  }
+
+  /// Sends each outgoing sat to its own destination with its postage. Each
+  /// outgoing utxo is spent into outputs of its own, so no sat crosses into
+  /// another destination's output. Cardinals then pay `value` and the fee,
+  /// with what is left going to `change`.
+  pub fn build_transaction_with_destinations(
+    input_type: AddressType,
+    witness_sizes: BTreeMap<OutPoint, usize>,
+    outgoings: Vec<(SatPoint, Amount, Address)>,
+    inscriptions: BTreeMap<SatPoint, InscriptionId>,
+    amounts: BTreeMap<OutPoint, Amount>,
+    value: (Address, Amount),
+    change: Address,
+    fee_rate: FeeRate,
+    op_return: Option<String>,
+  ) -> Result<Transaction> {
+    let mut builder = Self::new(
+      input_type,
+      outgoings[0].0,
+      inscriptions,
+      amounts,
+      outgoings[0].2.clone(),
+      [change.clone(), change.clone()],
+      fee_rate,
+      Target::Value(value.1),
+      op_return.map(String::into_bytes),
+    )?;
+    builder.witness_sizes = witness_sizes;
+
+    for (satpoint, postage, destination) in outgoings {
+      builder = builder.send_outgoing(satpoint, postage, destination, &change)?;
+    }
+
+    if value.1 > Amount::ZERO {
+      builder.outputs.push(value);
+    }
+
+    let builder = builder.fund_outputs(change)?;
+
+    let mut transaction = Transaction {
+      version: 1,
+      lock_time: PackedLockTime::ZERO,
+      input: builder
+        .inputs
+        .iter()
+        .map(|outpoint| TxIn {
+          previous_output: *outpoint,
+          script_sig: Script::new(),
+          sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+          witness: Witness::new(),
+        })
+        .collect(),
+      output: builder
+        .outputs
+        .iter()
+        .map(|(address, amount)| TxOut {
+          value: amount.to_sat(),
+          script_pubkey: address.script_pubkey(),
+        })
+        .collect(),
+    };
+
+    if let Some(op_return) = builder.op_return {
+      transaction.output.push(TxOut {
+        value: 0,
+        script_pubkey: Script::new_op_return(&op_return),
+      });
+    }
+
+    for tx_out in &transaction.output {
+      assert!(
+        Amount::from_sat(tx_out.value) >= tx_out.script_pubkey.dust_value(),
+        "invariant: all outputs are above dust limit",
+      );
+    }
+
+    Ok(transaction)
   }
 
   fn build_transaction(self) -> Result<Transaction> {
@@ -431,10 +529,12 @@ impl TransactionBuilder {
       inscriptions,
       outgoing,
       outputs: Vec::new(),
+      postages: BTreeMap::new(),
       recipient,
       unused_change_addresses: change.to_vec(),
       target,
       op_return,
+      witness_sizes: BTreeMap::new(),
     })
   }
 
@@ -606,6 +706,146 @@ impl TransactionBuilder {
     Ok(self)
   }
 
+  // Spend the utxo of `outgoing` into outputs that end where it ends: the
+  // sats in front of the outgoing sat go to change, the sat and `postage`
+  // sats behind it to `destination` and the rest back to change. A utxo
+  // short of the postage is topped up by a cardinal spent right after it.
+  fn send_outgoing(
+    mut self,
+    outgoing: SatPoint,
+    postage: Amount,
+    destination: Address,
+    change: &Address,
+  ) -> Result<Self> {
+    for (inscribed_satpoint, inscription_id) in &self.inscriptions {
+      if outgoing.outpoint == inscribed_satpoint.outpoint
+        && outgoing.offset != inscribed_satpoint.offset
+      {
+        return Err(Error::UtxoContainsAdditionalInscription {
+          outgoing_satpoint: outgoing,
+          inscribed_satpoint: *inscribed_satpoint,
+          inscription_id: *inscription_id,
+        });
+      }
+    }
+
+    let amount = *self
+      .amounts
+      .get(&outgoing.outpoint)
+      .ok_or(Error::NotInWallet(outgoing))?;
+
+    if outgoing.offset >= amount.to_sat() {
+      return Err(Error::OutOfRange(outgoing, amount.to_sat() - 1));
+    }
+
+    if !self.utxos.remove(&outgoing.outpoint) {
+      return Err(Error::NotInWallet(outgoing));
+    }
+    self.inputs.push(outgoing.outpoint);
+
+    let change_dust_value = change.script_pubkey().dust_value();
+    if outgoing.offset > 0 {
+      let padding = Amount::from_sat(outgoing.offset);
+      if padding < change_dust_value {
+        return Err(Error::Dust {
+          output_value: padding,
+          dust_value: change_dust_value,
+        });
+      }
+      self.outputs.push((change.clone(), padding));
+    }
+
+    let mut behind = amount - Amount::from_sat(outgoing.offset);
+    if let Some(deficit) = postage
+      .checked_sub(behind)
+      .filter(|deficit| *deficit > Amount::ZERO)
+    {
+      let (utxo, value) = self.select_cardinal_utxo(deficit)?;
+      self.inputs.push(utxo);
+      behind += value;
+      tprintln!("added {value} sat input to cover {deficit} sat postage deficit");
+    }
+
+    let excess = behind - postage;
+    let output_value = if excess >= change_dust_value {
+      postage
+    } else {
+      behind
+    };
+
+    let dust_value = destination.script_pubkey().dust_value();
+    if output_value < dust_value {
+      return Err(Error::Dust {
+        output_value,
+        dust_value,
+      });
+    }
+
+    self.outputs.push((destination, output_value));
+    if output_value < behind {
+      self.outputs.push((change.clone(), excess));
+    }
+
+    tprintln!(
+      "sent outgoing {outgoing} in {output_value} sat output, {} sat change",
+      (behind - output_value).to_sat()
+    );
+
+    Ok(self)
+  }
+
+  // Add cardinals until the inputs pay every output and the fee, returning
+  // what is left to `change` unless it is dust
+  fn fund_outputs(mut self, change: Address) -> Result<Self> {
+    let output_value = self
+      .outputs
+      .iter()
+      .map(|(_address, amount)| *amount)
+      .sum::<Amount>();
+    let mut input_value = self
+      .inputs
+      .iter()
+      .map(|outpoint| self.amounts[outpoint])
+      .sum::<Amount>();
+
+    let dust_value = change.script_pubkey().dust_value();
+    self.outputs.push((change, Amount::ZERO));
+
+    loop {
+      let required = output_value
+        .checked_add(self.estimate_fee())
+        .ok_or(Error::ValueOverflow)?;
+
+      if let Some(remainder) = input_value.checked_sub(required) {
+        if remainder >= dust_value {
+          self.outputs.last_mut().unwrap().1 = remainder;
+        } else {
+          tprintln!("dropped {remainder} sat change as dust");
+          self.outputs.pop();
+        }
+        break;
+      }
+
+      let (utxo, value) = self.select_max_cardinal_utxo()?;
+      self.inputs.push(utxo);
+      input_value += value;
+      tprintln!("added {value} sat input to pay outputs and fee");
+    }
+
+    Ok(self)
+  }
+
+  // Value of the outputs in front of the recipient that the inputs pay on
+  // top of it. The alignment output is carved out of the recipient's sats,
+  // so it is not counted again.
+  fn addition_output_value(&self) -> Amount {
+    self.outputs[..self.outputs.len() - 1]
+      .iter()
+      .map(|(_address, amount)| *amount)
+      .sum::<Amount>()
+      - Amount::from_sat(self.calculate_sat_offset())
+  }
+
   // Add outputs
   fn add_outputs(mut self, mut data: Vec<(Address, Amount)>) -> Result<Self> {
     data.reverse();
@@ -615,6 +855,38 @@ impl TransactionBuilder {
     Ok(self)
   }
 
+  // Raise the value target so every outgoing sat keeps its postage in the
+  // recipient output. A sat late in the output needs the sats in front of it
+  // as well, otherwise stripping would hand it to change.
+  fn size_for_postages(mut self) -> Result<Self> {
+    let Target::Value(value) = self.target else {
+      return Ok(self);
+    };
+
+    let mut required = Amount::ZERO;
+    for (satpoint, postage) in &self.postages {
+      required = required.max(
+        Amount::from_sat(self.recipient_offset(*satpoint))
+          .checked_add(*postage)
+          .ok_or(Error::ValueOverflow)?,
+      );
+    }
+
+    let output_value = required.checked_add(value).ok_or(Error::ValueOverflow)?;
+    let dust_value = self.recipient.script_pubkey().dust_value();
+    if output_value < dust_value {
+      return Err(Error::Dust {
+        output_value,
+        dust_value,
+      });
+    }
+
+    tprintln!("sized recipient output to {output_value} for postages");
+    self.target = Target::Value(output_value);
+
+    Ok(self)
+  }
+
   fn add_value(mut self) -> Result<Self> {
     let mut input_amount = self.outputs.last().unwrap().1;
     loop {
@@ -625,10 +897,7 @@ impl TransactionBuilder {
         Target::Value(value) => value,
       };
 
-      let mut addition_output_value = Amount::ZERO;
-      for item in &self.outputs[..self.outputs.len() - 1] {
-        addition_output_value += item.1;
-      }
+      let addition_output_value = self.addition_output_value();
 
       let total = min_value
         .checked_add(estimated_fee)
@@ -664,10 +933,7 @@ impl TransactionBuilder {
         Target::Value(value) => value,
       };
 
-      let mut addition_output_value = Amount::ZERO;
-      for item in &self.outputs[..self.outputs.len() - 1] {
-        addition_output_value += item.1;
-      }
+      let addition_output_value = self.addition_output_value();
 
       let total = min_value
         .checked_add(estimated_fee)
@@ -696,10 +962,7 @@ impl TransactionBuilder {
   fn strip_value(mut self) -> Self {
     let sat_offset = self.calculate_sat_offset();
 
-    let mut addition_output_value = Amount::ZERO;
-    for item in &self.outputs[..self.outputs.len() - 1] {
-      addition_output_value += item.1;
-    }
+    let addition_output_value = self.addition_output_value();
 
     let total_output_amount = self
       .outputs
@@ -787,50 +1050,53 @@ impl TransactionBuilder {
   /// Estimate the size in virtual bytes of the transaction under construction.
   /// We initialize wallets with taproot descriptors only, so we know that all
   /// inputs are taproot key path spends, which allows us to know that witnesses
-  /// will all consist of single Schnorr signatures.
+  /// will all consist of single Schnorr signatures, unless the caller gave
+  /// the witness size of a script path spend.
   fn estimate_vbytes(&self) -> usize {
-    if let Some(op_return) = &self.op_return {
-      Self::estimate_vbytes_with_op_return(
-        self.inputs.len(),
-        self.input_type,
-        self
-          .outputs
-          .iter()
-          .map(|(address, _amount)| address)
-          .cloned()
-          .collect(),
-        op_return.clone(),
-      )
-    } else {
-      Self::estimate_vbytes_with(
-        self.inputs.len(),
-        self.input_type,
-        self
-          .outputs
-          .iter()
-          .map(|(address, _amount)| address)
-          .cloned()
-          .collect(),
-      )
-    }
+    Self::estimate_vbytes_with_witnesses(
+      self
+        .inputs
+        .iter()
+        .map(|outpoint| self.witness_size(*outpoint))
+        .collect(),
+      self
+        .outputs
+        .iter()
+        .map(|(address, _amount)| address)
+        .cloned()
+        .collect(),
+      self.op_return.clone(),
+    )
   }
 
-  fn estimate_vbytes_with_op_return(
-    inputs: usize,
-    input_type: AddressType,
+  fn witness_size(&self, outpoint: OutPoint) -> usize {
+    self
+      .witness_sizes
+      .get(&outpoint)
+      .copied()
+      .unwrap_or_else(|| Self::key_path_witness_size(self.input_type))
+  }
+
+  #[cfg(test)]
+  fn estimate_vbytes_with(inputs: usize, input_type: AddressType, outputs: Vec<Address>) -> usize {
+    Self::estimate_vbytes_with_witnesses(
+      vec![Self::key_path_witness_size(input_type); inputs],
+      outputs,
+      None,
+    )
+  }
+
+  fn estimate_vbytes_with_witnesses(
+    witness_sizes: Vec<usize>,
     outputs: Vec<Address>,
-    op_return: Vec<u8>,
+    op_return: Option<Vec<u8>>,
   ) -> usize {
-    let witness_size = if input_type == AddressType::P2tr {
-      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
-    } else {
-      TransactionBuilder::P2WPKH_WINETSS_SIZE
-    };
     let mut tx = Transaction {
       version: 1,
       lock_time: PackedLockTime::ZERO,
-      input: (0..inputs)
-        .map(|_| TxIn {
+      input: witness_sizes
+        .into_iter()
+        .map(|witness_size| TxIn {
           previous_output: OutPoint::null(),
           script_sig: Script::new(),
           sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
@@ -846,39 +1112,13 @@ impl TransactionBuilder {
         .collect(),
     };
 
-    tx.output.push(TxOut {
-      value: 0,
-      script_pubkey: Script::new_op_return(&op_return),
-    });
-    tx.vsize()
-  }
-
-  fn estimate_vbytes_with(inputs: usize, input_type: AddressType, outputs: Vec<Address>) -> usize {
-    let witness_size = if input_type == AddressType::P2tr {
-      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
-    } else {
-      TransactionBuilder::P2WPKH_WINETSS_SIZE
-    };
-    Transaction {
-      version: 1,
-      lock_time: PackedLockTime::ZERO,
-      input: (0..inputs)
-        .map(|_| TxIn {
-          previous_output: OutPoint::null(),
-          script_sig: Script::new(),
-          sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-          witness: Witness::from_vec(vec![vec![0; witness_size]]),
-        })
-        .collect(),
-      output: outputs
-        .into_iter()
-        .map(|address| TxOut {
-          value: 0,
-          script_pubkey: address.script_pubkey(),
-        })
-        .collect(),
+    if let Some(op_return) = op_return {
+      tx.output.push(TxOut {
+        value: 0,
+        script_pubkey: Script::new_op_return(&op_return),
+      });
     }
-    .vsize()
+    tx.vsize()
   }
 
   fn estimate_fee(&self) -> Amount {
@@ -910,10 +1150,29 @@ impl TransactionBuilder {
         .collect(),
     };
 
-    if let Some(op_return) = self.op_return {
+    if !self.postages.is_empty() {
+      let recipient_value = Amount::from_sat(
+        transaction
+          .output
+          .iter()
+          .find(|tx_out| tx_out.script_pubkey == self.recipient.script_pubkey())
+          .expect("invariant: recipient is found in outputs")
+          .value,
+      );
+      for (satpoint, postage) in &self.postages {
+        assert!(
+          recipient_value
+            .checked_sub(Amount::from_sat(self.recipient_offset(*satpoint)))
+            .map_or(false, |behind| behind >= *postage),
+          "invariant: outgoing sat {satpoint} keeps its postage in recipient output",
+        );
+      }
+    }
+
+    if let Some(op_return) = &self.op_return {
       transaction.output.push(TxOut {
         value: 0,
-        script_pubkey: Script::new_op_return(&op_return),
+        script_pubkey: Script::new_op_return(op_return),
       });
     }
 
@@ -1046,13 +1305,8 @@ impl TransactionBuilder {
     }
 
     let mut modified_tx = transaction.clone();
-    let witness_size = if self.input_type == AddressType::P2tr {
-      TransactionBuilder::SCHNORR_SIGNATURE_SIZE
-    } else {
-      TransactionBuilder::P2WPKH_WINETSS_SIZE
-    };
     for input in &mut modified_tx.input {
-      input.witness = Witness::from_vec(vec![vec![0; witness_size]]);
+      input.witness = Witness::from_vec(vec![vec![0; self.witness_size(input.previous_output)]]);
     }
     let expected_fee = self.fee_rate.fee(modified_tx.vsize());
 
@@ -1084,6 +1338,30 @@ impl TransactionBuilder {
     panic!("Could not find outgoing sat in inputs");
   }
 
+  // Position of `satpoint` within the recipient output
+  fn recipient_offset(&self, satpoint: SatPoint) -> u64 {
+    let mut sat_offset = 0;
+    for outpoint in &self.inputs {
+      if *outpoint == satpoint.outpoint {
+        sat_offset += satpoint.offset;
+        break;
+      } else {
+        sat_offset += self.amounts[outpoint].to_sat();
+      }
+    }
+
+    let recipient_start = self
+      .outputs
+      .iter()
+      .take_while(|(address, _amount)| *address != self.recipient)
+      .map(|(_address, amount)| amount.to_sat())
+      .sum::<u64>();
+
+    sat_offset
+      .checked_sub(recipient_start)
+      .expect("invariant: outgoing sat is not before recipient output")
+  }
+
   fn select_cardinal_utxo(&mut self, minimum_value: Amount) -> Result<(OutPoint, Amount)> {
     let mut found = None;
 
@@ -1224,6 +1502,7 @@ mod tests {
       unused_change_addresses: vec![change(0), change(1)],
       change_addresses: vec![change(0), change(1)].into_iter().collect(),
       inputs: vec![outpoint(1), outpoint(2), outpoint(3)],
+      postages: BTreeMap::new(),
       outputs: vec![
         (recipient(), Amount::from_sat(5_000)),
         (change(0), Amount::from_sat(5_000)),
@@ -1231,6 +1510,7 @@ mod tests {
       ],
       target: Target::Postage,
       op_return: None,
+      witness_sizes: BTreeMap::new(),
     };
 
     pretty_assert_eq!(
@@ -1746,6 +2026,7 @@ mod tests {
       unused_change_addresses: vec![change(0), change(1)],
       change_addresses: vec![change(0), change(1)].into_iter().collect(),
       inputs: vec![outpoint(1), outpoint(2), outpoint(3)],
+      postages: BTreeMap::new(),
       outputs: vec![
         (recipient(), Amount::from_sat(5_000)),
         (recipient(), Amount::from_sat(5_000)),
@@ -1753,6 +2034,7 @@ mod tests {
       ],
       target: Target::Postage,
       op_return: None,
+      witness_sizes: BTreeMap::new(),
     }
     .build()
     .unwrap();
@@ -1777,6 +2059,7 @@ mod tests {
       unused_change_addresses: vec![change(0), change(1)],
       change_addresses: vec![change(0), change(1)].into_iter().collect(),
       inputs: vec![outpoint(1), outpoint(2), outpoint(3)],
+      postages: BTreeMap::new(),
       outputs: vec![
         (recipient(), Amount::from_sat(5_000)),
         (change(0), Amount::from_sat(5_000)),
@@ -1784,6 +2067,7 @@ mod tests {
       ],
       target: Target::Postage,
       op_return: None,
+      witness_sizes: BTreeMap::new(),
     }
     .build()
     .unwrap();
@@ -2176,4 +2460,86 @@ mod tests {
       }),
     );
   }
+
+  #[test]
+  fn each_outgoing_is_sent_to_its_destination() {
+    pretty_assert_eq!(
+      TransactionBuilder::build_transaction_with_destinations(
+        AddressType::P2wpkh,
+        BTreeMap::new(),
+        vec![
+          (satpoint(1, 0), Amount::from_sat(546), recipient()),
+          (satpoint(2, 0), Amount::from_sat(546), change(2)),
+        ],
+        [(satpoint(1, 0), inscription_id(1))].into(),
+        [
+          (outpoint(1), Amount::from_sat(10_000)),
+          (outpoint(2), Amount::from_sat(546)),
+        ]
+        .into(),
+        (recipient(), Amount::ZERO),
+        change(0),
+        FeeRate::try_from(0.0).unwrap(),
+        None,
+      ),
+      Ok(Transaction {
+        version: 1,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+        output: vec![
+          tx_out(546, recipient()),
+          tx_out(9_454, change(0)),
+          tx_out(546, change(2)),
+        ],
+      }),
+    );
+  }
+
+  #[test]
+  fn outgoing_short_of_postage_is_topped_up_in_place() {
+    pretty_assert_eq!(
+      TransactionBuilder::build_transaction_with_destinations(
+        AddressType::P2wpkh,
+        BTreeMap::new(),
+        vec![(satpoint(1, 0), Amount::from_sat(546), recipient())],
+        [(satpoint(1, 0), inscription_id(1))].into(),
+        [
+          (outpoint(1), Amount::from_sat(400)),
+          (outpoint(2), Amount::from_sat(5_000)),
+        ]
+        .into(),
+        (recipient(), Amount::ZERO),
+        change(0),
+        FeeRate::try_from(0.0).unwrap(),
+        None,
+      ),
+      Ok(Transaction {
+        version: 1,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+        output: vec![tx_out(546, recipient()), tx_out(4_854, change(0))],
+      }),
+    );
+  }
+
+  #[test]
+  fn outgoing_padding_to_its_destination_must_not_be_dust() {
+    pretty_assert_eq!(
+      TransactionBuilder::build_transaction_with_destinations(
+        AddressType::P2wpkh,
+        BTreeMap::new(),
+        vec![(satpoint(1, 100), Amount::from_sat(546), recipient())],
+        BTreeMap::new(),
+        [(outpoint(1), Amount::from_sat(10_000))].into(),
+        (recipient(), Amount::ZERO),
+        change(0),
+        FeeRate::try_from(0.0).unwrap(),
+        None,
+      ),
+      Err(Error::Dust {
+        output_value: Amount::from_sat(100),
+        dust_value: Amount::from_sat(294),
+      }),
+    );
+  }
 }
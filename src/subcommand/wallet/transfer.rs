@@ -1,9 +1,22 @@
-use super::*;
-use crate::index::{ConstructTransaction, MysqlDatabase, TransactionOutputArray};
+use crate::{
+  builder::{self, TransferParams},
+  index::{ConstructTransaction, MysqlDatabase, TransactionOutputArray},
+};
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::psbt::Psbt;
-use bitcoin::AddressType;
 use std::collections::BTreeSet;
+use std::iter;
+use std::ops::Range;
+use {
+  super::*,
+  funding::{
+    check_pinned_cardinal, exclude_utxos, explain_exclusion, explain_shallow, pinned_utxos,
+    shallow_utxos,
+  },
+  script_path::ScriptPathInput,
+  unsafe_destination::UnsafeDestination,
+  warning::{Warning, Warnings},
+};
 
 #[derive(Debug, Parser)]
 pub struct Transfer {
@@ -11,6 +24,11 @@ pub struct Transfer {
   pub destination: Address,
   #[clap(long, help = "Send inscription from <SOURCE>.")]
   pub source: Address,
+  #[clap(
+    long = "change",
+    help = "Send change to <CHANGE_ADDRESS> instead of back to --source."
+  )]
+  pub change_address: Option<Address>,
   pub outgoing: Outgoing,
   #[clap(long, help = "Use fee rate of <FEE_RATE> sats/vB")]
   pub fee_rate: FeeRate,
@@ -19,8 +37,366 @@ pub struct Transfer {
   #[clap(long, help = "Whether to transfer brc20.")]
   pub brc20_transfer: Option<bool>,
   pub addition_outgoing: Vec<Outgoing>,
+  #[clap(
+    long = "destinations",
+    help = "Send the i-th addition outgoing to the i-th of <DESTINATIONS> instead of <DESTINATION>. May be repeated."
+  )]
+  pub destinations: Vec<Address>,
   #[clap(long, help = "Addition Fee for destination address.")]
   pub addition_fee: Amount,
+  #[clap(
+    long = "input",
+    help = "Pay fees only from <INPUT>, an unspent output of --source given as <TXID>:<VOUT>, may be repeated."
+  )]
+  pub inputs: Vec<OutPoint>,
+  #[clap(
+    long = "exclude",
+    help = "Never pay fees from <EXCLUDE>, an output of --source given as <TXID>:<VOUT>, may be repeated."
+  )]
+  pub exclude_inputs: Vec<OutPoint>,
+  #[clap(
+    long,
+    default_value = "0",
+    help = "Only pay fees from outputs of --source with at least <MIN_CONFIRMATIONS> confirmations."
+  )]
+  pub min_confirmations: u64,
+  #[clap(
+    long,
+    help = "Pay fees from unconfirmed change of our own earlier builds despite --min-confirmations."
+  )]
+  pub allow_unconfirmed_self: bool,
+  #[clap(long, help = "Allow sending inscription to a burn address.")]
+  pub allow_burn: bool,
+  #[clap(long, help = "Only valid after block height <LOCKTIME>.")]
+  pub locktime: Option<u32>,
+  #[clap(long, help = "Whether to signal BIP-125 replaceability, default true.")]
+  pub rbf: Option<bool>,
+  #[clap(
+    long,
+    default_value = "target",
+    help = "Set the inscription output to <POSTAGE_POLICY>: `preserve` its current value, pad it to the `target` postage, or an explicit value in sats. Applies to each inscription."
+  )]
+  pub postage_policy: PostagePolicy,
+  #[clap(
+    long = "unsafe-destination",
+    help = "Refuse to send inscriptions to destinations matching <UNSAFE_DESTINATION>, written LABEL=PATTERN. May be repeated."
+  )]
+  pub unsafe_destinations: Vec<UnsafeDestination>,
+  #[clap(long, help = "Send inscriptions to an unsafe destination anyway.")]
+  pub acknowledge_unsafe_destination: bool,
+  #[clap(
+    long = "script-path-input",
+    help = "Spend a source input through a tapscript leaf, written <TXID>:<VOUT>:<LEAF_SCRIPT_HEX>:<CONTROL_BLOCK_HEX>. May be repeated."
+  )]
+  pub script_path_inputs: Vec<ScriptPathInput>,
+  #[clap(
+    long,
+    help = "Check and price the transfer, marking the result as not to be signed."
+  )]
+  pub dry_run: bool,
+  #[clap(
+    long,
+    help = "Refuse the build if its fees add up to more than <MAX_TOTAL_FEE> sats."
+  )]
+  pub max_total_fee: Option<u64>,
+}
+
+/// The value of the output that receives transferred inscriptions.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PostagePolicy {
+  /// The value the inscriptions are currently sitting on.
+  Preserve,
+  /// `TransactionBuilder::TARGET_POSTAGE` per inscription.
+  #[default]
+  Target,
+  /// An explicit value per inscription.
+  Value(Amount),
+}
+
+impl FromStr for PostagePolicy {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s.trim() {
+      "preserve" => Self::Preserve,
+      "target" => Self::Target,
+      sats => Self::Value(Amount::from_sat(sats.parse().map_err(|_| {
+        anyhow!("invalid postage policy `{s}`, expected `preserve`, `target` or a value in sats")
+      })?)),
+    })
+  }
+}
+
+impl PostagePolicy {
+  /// The postage each of `satpoints` keeps in the destination output.
+  /// Preserving keeps the sats behind each sat in its current output.
+  fn postages(
+    self,
+    satpoints: &[SatPoint],
+    utxos: &BTreeMap<OutPoint, Amount>,
+  ) -> Result<Vec<Amount>> {
+    satpoints
+      .iter()
+      .map(|satpoint| {
+        Ok(match self {
+          Self::Preserve => {
+            let value = utxos
+              .get(&satpoint.outpoint)
+              .ok_or_else(|| anyhow!("output {} is not spendable by source", satpoint.outpoint))?;
+            *value - Amount::from_sat(satpoint.offset.min(value.to_sat()))
+          }
+          Self::Target => TransactionBuilder::TARGET_POSTAGE,
+          Self::Value(value) => value,
+        })
+      })
+      .collect()
+  }
+}
+
+/// Sats an output receiving transferred sats got beyond those of the
+/// transferred outputs, and the source utxos that paid for them.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostagePadding {
+  pub output: usize,
+  pub sats: u64,
+  pub inputs: Vec<OutPoint>,
+}
+
+impl PostagePadding {
+  /// One for each padded output of `tx` the sats of `satpoints` land in by
+  /// ordinal theory, so each destination gets its own.
+  fn new(
+    tx: &Transaction,
+    satpoints: &[SatPoint],
+    utxos: &BTreeMap<OutPoint, Amount>,
+  ) -> Vec<Self> {
+    let sent = satpoints
+      .iter()
+      .map(|satpoint| satpoint.outpoint)
+      .collect::<BTreeSet<OutPoint>>();
+
+    let mut start = 0;
+    let inputs = tx
+      .input
+      .iter()
+      .map(|input| {
+        let value = utxos
+          .get(&input.previous_output)
+          .map_or(0, |amount| amount.to_sat());
+        start += value;
+        (input.previous_output, start - value..start)
+      })
+      .collect::<Vec<(OutPoint, Range<u64>)>>();
+
+    let landed = satpoints
+      .iter()
+      .filter_map(|satpoint| {
+        inputs
+          .iter()
+          .find(|(outpoint, _)| *outpoint == satpoint.outpoint)
+          .map(|(_, input)| input.start + satpoint.offset)
+      })
+      .collect::<Vec<u64>>();
+
+    let mut start = 0;
+    tx.output
+      .iter()
+      .enumerate()
+      .filter_map(|(vout, output)| {
+        start += output.value;
+        let output = start - output.value..start;
+        if !landed.iter().any(|sat| output.contains(sat)) {
+          return None;
+        }
+
+        let overlap = |input: &Range<u64>| {
+          input
+            .end
+            .min(output.end)
+            .saturating_sub(input.start.max(output.start))
+        };
+
+        let inscribed_value = inputs
+          .iter()
+          .filter(|(outpoint, _)| sent.contains(outpoint))
+          .map(|(_, input)| overlap(input))
+          .sum::<u64>();
+
+        let sats = (output.end - output.start)
+          .checked_sub(inscribed_value)
+          .filter(|sats| *sats > 0)?;
+
+        Some(Self {
+          output: vout,
+          sats,
+          inputs: inputs
+            .iter()
+            .filter(|(outpoint, input)| !sent.contains(outpoint) && overlap(input) > 0)
+            .map(|(outpoint, _)| *outpoint)
+            .collect(),
+        })
+      })
+      .collect()
+  }
+}
+
+/// Claims a transfer makes about one inscription, enough for someone with
+/// their own index to check the PSBT without trusting this service: that
+/// `inscription_id` sits at `satpoint`, and that spending it as input
+/// `input` lands it in output `output` paying `destination`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferProof {
+  pub inscription_id: InscriptionId,
+  pub satpoint: SatPoint,
+  pub input: usize,
+  pub output: usize,
+  pub output_value: u64,
+  pub destination: Address,
+  /// Height of the index `satpoint` was read from.
+  pub index_height: Option<u64>,
+}
+
+impl TransferProof {
+  fn new(
+    tx: &Transaction,
+    utxos: &BTreeMap<OutPoint, Amount>,
+    inscription_id: InscriptionId,
+    satpoint: SatPoint,
+    destination: &Address,
+    index_height: Option<u64>,
+  ) -> Result<Self> {
+    let input = tx
+      .input
+      .iter()
+      .position(|input| input.previous_output == satpoint.outpoint)
+      .ok_or_else(|| anyhow!("transfer does not spend {}", satpoint.outpoint))?;
+
+    let input_values = tx
+      .input
+      .iter()
+      .map(|input| {
+        utxos
+          .get(&input.previous_output)
+          .map(|amount| amount.to_sat())
+          .ok_or_else(|| anyhow!("value of input {} is unknown", input.previous_output))
+      })
+      .collect::<Result<Vec<u64>>>()?;
+
+    let output = landing_output(tx, &input_values, input, satpoint.offset)
+      .ok_or_else(|| anyhow!("inscription {inscription_id} would be spent as fee"))?;
+
+    Ok(Self {
+      inscription_id,
+      satpoint,
+      input,
+      output,
+      output_value: tx.output[output].value,
+      destination: destination.clone(),
+      index_height,
+    })
+  }
+}
+
+/// The output the sat at `offset` into input `input` is assigned to by
+/// ordinal theory, `None` if it goes to fees.
+fn landing_output(
+  tx: &Transaction,
+  input_values: &[u64],
+  input: usize,
+  offset: u64,
+) -> Option<usize> {
+  let mut position = input_values[..input].iter().sum::<u64>() + offset;
+  for (vout, output) in tx.output.iter().enumerate() {
+    if position < output.value {
+      return Some(vout);
+    }
+    position -= output.value;
+  }
+  None
+}
+
+/// Checks `proof` against `psbt` alone: that the PSBT spends the claimed
+/// satpoint, and that the sat lands in an output of the claimed value
+/// paying the claimed destination. Whether the inscription really is at
+/// that satpoint is for the verifier's own index to confirm.
+pub fn verify_transfer_proof(psbt: &Psbt, proof: &TransferProof) -> Result {
+  let tx = &psbt.unsigned_tx;
+
+  let input = tx
+    .input
+    .get(proof.input)
+    .ok_or_else(|| anyhow!("proof input {} is out of range", proof.input))?;
+  if input.previous_output != proof.satpoint.outpoint {
+    bail!(
+      "input {} spends {}, not the inscription's output {}",
+      proof.input,
+      input.previous_output,
+      proof.satpoint.outpoint
+    );
+  }
+  if tx
+    .input
+    .iter()
+    .filter(|input| input.previous_output == proof.satpoint.outpoint)
+    .count()
+    > 1
+  {
+    bail!("{} is spent more than once", proof.satpoint.outpoint);
+  }
+
+  if psbt.inputs.len() != tx.input.len() {
+    bail!(
+      "psbt has {} input entries for {} inputs",
+      psbt.inputs.len(),
+      tx.input.len()
+    );
+  }
+  let input_values = psbt
+    .inputs
+    .iter()
+    .enumerate()
+    .map(|(i, input)| {
+      input
+        .witness_utxo
+        .as_ref()
+        .map(|utxo| utxo.value)
+        .ok_or_else(|| anyhow!("input {i} has no witness utxo"))
+    })
+    .collect::<Result<Vec<u64>>>()?;
+
+  if proof.satpoint.offset >= input_values[proof.input] {
+    bail!(
+      "satpoint offset {} is outside input {} of {} sats",
+      proof.satpoint.offset,
+      proof.input,
+      input_values[proof.input]
+    );
+  }
+
+  let output = landing_output(tx, &input_values, proof.input, proof.satpoint.offset)
+    .ok_or_else(|| anyhow!("inscription {} would be spent as fee", proof.inscription_id))?;
+  if output != proof.output {
+    bail!(
+      "inscription {} lands in output {output}, not output {}",
+      proof.inscription_id,
+      proof.output
+    );
+  }
+
+  let output = &tx.output[output];
+  if output.value != proof.output_value {
+    bail!(
+      "output {} holds {} sats, not {}",
+      proof.output,
+      output.value,
+      proof.output_value
+    );
+  }
+  if output.script_pubkey != proof.destination.script_pubkey() {
+    bail!("output {} does not pay {}", proof.output, proof.destination);
+  }
+
+  Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,142 +404,183 @@ pub struct Output {
   pub transaction: String,
   pub commit_custom: Vec<String>,
   pub network_fee: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub locktime: Option<u32>,
+  /// One per padded destination output.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub postage_padding: Vec<PostagePadding>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub warnings: Vec<Warning>,
+  /// The API key whose brand was stamped into the OP_RETURN.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub op_return_brand: Option<String>,
+  /// One per transferred inscription, see `verify_transfer_proof`.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub proofs: Vec<TransferProof>,
+  /// Built only to validate and price, not to be signed.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub dry_run: bool,
 }
 
 impl Transfer {
-  pub fn build(self, options: Options, mysql: Option<Arc<MysqlDatabase>>) -> Result<Output> {
-    if !self
-      .destination
-      .is_valid_for_network(options.chain().network())
-    {
+  pub fn build(mut self, options: Options, mysql: Option<Arc<MysqlDatabase>>) -> Result<Output> {
+    if !self.destinations.is_empty() && self.destinations.len() != self.addition_outgoing.len() {
       bail!(
-        "Address `{}` is not valid for {}",
-        self.destination,
-        options.chain()
+        "{} destinations given for {} addition outgoings, give one destination per addition outgoing",
+        self.destinations.len(),
+        self.addition_outgoing.len()
       );
     }
-    if !self.source.is_valid_for_network(options.chain().network()) {
-      bail!(
-        "Address `{}` is not valid for {}",
-        self.source,
-        options.chain()
-      );
+    check_destination_address(&self.destination, &options, self.allow_burn)?;
+    for destination in &self.destinations {
+      check_destination_address(destination, &options, self.allow_burn)?;
     }
+    let address_type = check_source_address(&self.source, &options)?;
+    let change_address = self
+      .change_address
+      .clone()
+      .unwrap_or_else(|| self.source.clone());
+    check_source_address(&change_address, &options)?;
+    let script_spends = script_path::script_spends(&self.script_path_inputs, &self.source)?;
 
-    // check address types, only support p2tr and p2wpkh
-    let address_type = if let Some(address_type) = self.source.address_type() {
-      if (address_type == AddressType::P2tr) || (address_type == AddressType::P2wpkh) {
-        address_type
-      } else {
-        bail!(
-          "Address type `{}` is not valid, only support p2tr and p2wpkh",
-          address_type
+    let brc20_transfer = self.brc20_transfer.unwrap_or(false);
+    let start = Instant::now();
+    tracing::info!("Open index...");
+    let index = Index::read_open(&options)?;
+    // index.update()?;
+
+    let mut warnings = Warnings::default();
+    warnings.check_index(&index);
+    warnings.check_fee_rate(&index, self.fee_rate);
+    for destination in iter::once(&self.destination).chain(&self.destinations) {
+      warnings.check_destination(destination, &self.source);
+    }
+
+    let mut outgoings = Outgoing::resolve_numbers(
+      iter::once(self.outgoing)
+        .chain(self.addition_outgoing)
+        .collect(),
+      |number| match &mysql {
+        Some(mysql) => mysql.get_inscription_by_number(number),
+        None => index.get_inscription_id_by_inscription_number(number),
+      },
+    )?;
+    self.outgoing = outgoings.remove(0);
+    self.addition_outgoing = outgoings;
+
+    let mut unsafe_destinations = self.unsafe_destinations.clone();
+    if let Some(mysql) = &mysql {
+      unsafe_destinations.extend(mysql.get_unsafe_destinations()?);
+    }
+    for destination in iter::once(&self.destination).chain(&self.destinations) {
+      let Some(unsafe_destination) = UnsafeDestination::find(&unsafe_destinations, destination)
+      else {
+        continue;
+      };
+      // only inscriptions are lost, plain amounts are credited
+      if !matches!(self.outgoing, Outgoing::Amount(_)) && !self.acknowledge_unsafe_destination {
+        return Err(
+          BuildError::UnsafeDestination {
+            address: destination.clone(),
+            label: unsafe_destination.label.clone(),
+          }
+          .into(),
         );
       }
+      warnings.unsafe_destination(destination, unsafe_destination);
+    }
+
+    if let Some(locktime) = self.locktime {
+      check_lock_time(locktime, index.node_height()?)?;
+    }
+
+    tracing::info!("Get utxo...");
+    let query_address = &format!("{}", self.source);
+
+    let mysql_inscriptions = if let Some(mysql) = &mysql {
+      tracing::info!(store = "mysql", "Get inscriptions...");
+      Some(mysql.get_inscription_by_address(query_address)?)
     } else {
-      bail!(
-        "Address `{}` is not valid for {}",
-        self.source,
-        options.chain()
-      );
+      None
     };
 
-    let brc20_transfer = self.brc20_transfer.unwrap_or(false);
-    log::info!("Open index...");
-    let index = Index::read_open(&options)?;
-    // index.update()?;
+    // without mysql only the outpoints the transfer can spend are looked up,
+    // once they are known
+    let inscriptions_on = |outpoints: Vec<OutPoint>| match &mysql_inscriptions {
+      Some(inscriptions) => Ok(inscriptions.clone()),
+      None => {
+        tracing::info!(store = "redb", "Get inscriptions...");
+        index.get_inscriptions_for_outpoints(&outpoints)
+      }
+    };
 
-    log::info!("Get utxo...");
-    let query_address = &format!("{}", self.source);
+    let change = [change_address.clone(), change_address.clone()];
 
-    let inscriptions = if let Some(mysql) = mysql {
-      log::info!("Get inscriptions by mysql...");
-      mysql.get_inscription_by_address(query_address)?
+    let pinned = if self.inputs.is_empty() {
+      None
     } else {
-      log::info!("Get inscriptions by redb...");
-      index.get_inscriptions(None)?
+      let client = index.options().bitcoin_rpc_client()?;
+      Some(pinned_utxos(&self.inputs, &self.source, &client)?)
     };
 
-    let change = [self.source.clone(), self.source.clone()];
+    // pinned outputs are the caller's pick, however deep
+    let shallow = match &pinned {
+      Some(_) => Vec::new(),
+      None => shallow_utxos(
+        &index,
+        &self.source,
+        self.min_confirmations,
+        self.allow_unconfirmed_self,
+        mysql.as_deref(),
+      )?,
+    };
 
-    let (satpoints, amount, unspent_outputs) = match self.outgoing {
+    let (satpoints, postages, additional_value, unspent_outputs) = match self.outgoing {
       Outgoing::SatPoint(satpoint) => {
-        for inscription_satpoint in inscriptions.keys() {
-          if satpoint == *inscription_satpoint {
-            bail!("inscriptions must be sent by inscription ID");
-          }
-        }
-
         let mut satpoints = vec![satpoint];
 
         for item in &self.addition_outgoing {
           if let Outgoing::SatPoint(satpoint) = *item {
-            for inscription_satpoint in inscriptions.keys() {
-              if satpoint == *inscription_satpoint {
-                bail!("inscriptions must be sent by inscription ID");
-              }
-            }
             satpoints.push(satpoint)
           } else {
             bail!("Addition outgoing must be satpoint");
           }
         }
 
-        (
-          satpoints,
-          TransactionBuilder::TARGET_POSTAGE * (1 + (self.addition_outgoing.len() as u64))
-            + self.addition_fee,
-          index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?,
-        )
+        let unspent_outputs =
+          index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?;
+        let postages = self.postage_policy.postages(&satpoints, &unspent_outputs)?;
+        (satpoints, postages, self.addition_fee, unspent_outputs)
       }
       Outgoing::InscriptionId(id) => {
         if brc20_transfer {
-          let mut remain_outpoint = BTreeMap::new();
-          remain_outpoint.insert(
-            OutPoint {
-              txid: id.txid,
-              vout: 0,
-            },
-            true,
-          );
-
-          let satpoint = SatPoint {
-            outpoint: OutPoint {
-              txid: id.txid,
-              vout: 0,
-            },
-            offset: 0,
+          // a brc20 transfer inscription is usually still in the mempool, so
+          // its reveal is read from the node when it is not indexed yet
+          let satpoint_of = |id: InscriptionId| {
+            index
+              .get_pending_inscription_satpoint_by_id(id)?
+              .ok_or_else(|| anyhow!("Inscription {id} not found"))
           };
-          let mut satpoints = vec![satpoint];
+
+          let mut satpoints = vec![satpoint_of(id)?];
 
           for item in &self.addition_outgoing {
             if let Outgoing::InscriptionId(id) = *item {
-              remain_outpoint.insert(
-                OutPoint {
-                  txid: id.txid,
-                  vout: 0,
-                },
-                true,
-              );
-              let satpoint = SatPoint {
-                outpoint: OutPoint {
-                  txid: id.txid,
-                  vout: 0,
-                },
-                offset: 0,
-              };
-              satpoints.push(satpoint)
+              satpoints.push(satpoint_of(id)?);
             } else {
               bail!("Addition outgoing must be satpoint");
             }
           }
 
-          (
-            satpoints,
-            TransactionBuilder::TARGET_POSTAGE * (1 + (self.addition_outgoing.len() as u64))
-              + self.addition_fee,
-            index.get_unspent_outputs_by_mempool_v1(query_address, remain_outpoint)?,
-          )
+          let remain_outpoint = satpoints
+            .iter()
+            .map(|satpoint| (satpoint.outpoint, true))
+            .collect();
+
+          let unspent_outputs =
+            index.get_unspent_outputs_by_mempool_v1(query_address, remain_outpoint)?;
+          let postages = self.postage_policy.postages(&satpoints, &unspent_outputs)?;
+          (satpoints, postages, self.addition_fee, unspent_outputs)
         } else {
           let satpoint = index
             .get_inscription_satpoint_by_id(id)?
@@ -181,72 +598,199 @@ impl Transfer {
             }
           }
 
-          (
-            satpoints,
-            TransactionBuilder::TARGET_POSTAGE * (1 + (self.addition_outgoing.len() as u64)),
-            index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?,
-          )
+          let unspent_outputs =
+            index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?;
+          let postages = self.postage_policy.postages(&satpoints, &unspent_outputs)?;
+          (satpoints, postages, Amount::ZERO, unspent_outputs)
         }
       }
+      Outgoing::InscriptionNumber(_) => unreachable!("inscription numbers are resolved above"),
       Outgoing::Amount(amount) => {
-        let inscribed_utxos = inscriptions
+        if self.postage_policy != PostagePolicy::Target {
+          bail!("postage policy only applies to inscription and satpoint transfers");
+        }
+        if !self.destinations.is_empty() {
+          bail!("destinations only apply to inscription and satpoint transfers");
+        }
+
+        let unspent_outputs = match &pinned {
+          Some(pinned) => pinned.clone(),
+          None => index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?,
+        };
+        let inscribed_utxos = inscriptions_on(unspent_outputs.keys().copied().collect())?
           .keys()
           .map(|satpoint| satpoint.outpoint)
           .collect::<BTreeSet<OutPoint>>();
-        let unspent_outputs =
-          index.get_unspent_outputs_by_mempool_v1(query_address, BTreeMap::new())?;
         let satpoint = unspent_outputs
           .keys()
           .find(|outpoint| {
             (!inscribed_utxos.contains(outpoint)
+              && !self.exclude_inputs.contains(outpoint)
+              && !shallow.contains(outpoint)
               && unspent_outputs[outpoint] > Amount::from_sat(999))
           })
           .map(|outpoint| SatPoint {
             outpoint: *outpoint,
             offset: 0,
           })
-          .ok_or_else(|| {
-            anyhow!("wallet contains no cardinal utxos, not support lower 1000 satoshi")
+          .ok_or(BuildError::NoCardinalUtxos {
+            min_value: Some(1000),
           })?;
-        (vec![satpoint], amount + self.addition_fee, unspent_outputs)
+        (
+          vec![satpoint],
+          vec![amount],
+          self.addition_fee,
+          unspent_outputs,
+        )
       }
     };
 
-    let unsigned_transaction = if let Some(op_return) = self.op_return {
-      TransactionBuilder::build_transaction_with_op_return_v1(
-        address_type,
-        satpoints,
-        inscriptions,
-        unspent_outputs.clone(),
-        vec![(self.destination, amount)],
-        change,
-        self.fee_rate,
-        op_return,
-      )?
+    // fees come from the pinned outputs only, the transferred ones are kept
+    let mut unspent_outputs = match pinned {
+      Some(pinned) => unspent_outputs
+        .into_iter()
+        .filter(|(outpoint, _)| {
+          satpoints
+            .iter()
+            .any(|satpoint| satpoint.outpoint == *outpoint)
+        })
+        .chain(pinned)
+        .collect(),
+      None => unspent_outputs,
+    };
+
+    if let Some(satpoint) = satpoints
+      .iter()
+      .find(|satpoint| self.exclude_inputs.contains(&satpoint.outpoint))
+    {
+      bail!("{} is transferred but also excluded", satpoint.outpoint);
+    }
+    let excluded = exclude_utxos(&mut unspent_outputs, &self.exclude_inputs);
+    // the transferred outputs go whatever their depth
+    let shallow = exclude_utxos(
+      &mut unspent_outputs,
+      &shallow
+        .into_iter()
+        .filter(|outpoint| {
+          satpoints
+            .iter()
+            .all(|satpoint| satpoint.outpoint != *outpoint)
+        })
+        .collect::<Vec<OutPoint>>(),
+    );
+
+    let inscriptions = inscriptions_on(
+      unspent_outputs
+        .keys()
+        .copied()
+        .chain(satpoints.iter().map(|satpoint| satpoint.outpoint))
+        .collect(),
+    )?;
+
+    check_pinned_cardinal(&self.inputs, &inscriptions)?;
+
+    if let Outgoing::SatPoint(_) = self.outgoing {
+      if satpoints
+        .iter()
+        .any(|satpoint| inscriptions.contains_key(satpoint))
+      {
+        bail!("inscriptions must be sent by inscription ID");
+      }
+    }
+
+    // one per satpoint when given, the first being `destination`
+    let destinations = if self.destinations.is_empty() {
+      Vec::new()
     } else {
-      TransactionBuilder::build_transaction_with_value_v1(
-        address_type,
-        satpoints,
-        inscriptions,
-        unspent_outputs.clone(),
-        vec![(self.destination, amount)],
-        change,
-        self.fee_rate,
-      )?
+      iter::once(self.destination.clone())
+        .chain(self.destinations.iter().cloned())
+        .collect::<Vec<Address>>()
     };
 
-    let network_fee = Self::calculate_fee(&unsigned_transaction, &unspent_outputs);
+    let unsigned_transaction = explain_shallow(
+      explain_exclusion(
+        builder::transfer(TransferParams {
+          input_type: address_type,
+          postages: satpoints.iter().copied().zip(postages).collect(),
+          inscriptions,
+          utxos: unspent_outputs.clone(),
+          destination: self.destination.clone(),
+          destinations: destinations.clone(),
+          additional_value,
+          change,
+          fee_rate: self.fee_rate,
+          op_return: self.op_return,
+          locktime: self.locktime,
+          rbf: self.rbf.unwrap_or(true),
+          witness_sizes: script_path::witness_sizes(&script_spends),
+        }),
+        &excluded,
+      ),
+      &shallow,
+      self.min_confirmations,
+    )?;
+
+    let network_fee = builder::fee(&unsigned_transaction, &unspent_outputs);
+    builder::check_total_fee(0, 0, network_fee, self.max_total_fee)?;
+    warnings.check_change(&unsigned_transaction, &change_address);
 
-    let unsigned_transaction_psbt =
+    let postage_padding = match self.outgoing {
+      Outgoing::Amount(_) => Vec::new(),
+      _ => PostagePadding::new(&unsigned_transaction, &satpoints, &unspent_outputs),
+    };
+
+    let proofs = match self.outgoing {
+      Outgoing::InscriptionId(id) => {
+        let index_height = index.index_height()?;
+        iter::once(id)
+          .chain(
+            self
+              .addition_outgoing
+              .iter()
+              .filter_map(|outgoing| match outgoing {
+                Outgoing::InscriptionId(id) => Some(*id),
+                _ => None,
+              }),
+          )
+          .zip(&satpoints)
+          .enumerate()
+          .map(|(i, (id, satpoint))| {
+            TransferProof::new(
+              &unsigned_transaction,
+              &unspent_outputs,
+              id,
+              *satpoint,
+              destinations.get(i).unwrap_or(&self.destination),
+              index_height,
+            )
+          })
+          .collect::<Result<Vec<TransferProof>>>()?
+      }
+      _ => Vec::new(),
+    };
+
+    let mut unsigned_transaction_psbt =
       Self::get_psbt(&unsigned_transaction, &unspent_outputs, &self.source)?;
+    script_path::add_to_psbt(&mut unsigned_transaction_psbt, &script_spends);
     let unsigned_commit_custom = Self::get_custom(&unsigned_transaction_psbt);
 
-    log::info!("Build transfer success");
+    tracing::info!(
+      source = %self.source,
+      network_fee,
+      duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+      "Build transfer success"
+    );
 
     Ok(Output {
       transaction: serialize_hex(&unsigned_transaction_psbt),
       commit_custom: unsigned_commit_custom,
       network_fee,
+      locktime: self.locktime,
+      postage_padding,
+      warnings: warnings.into_vec(),
+      op_return_brand: None,
+      proofs,
+      dry_run: self.dry_run,
     })
   }
 
@@ -260,20 +804,10 @@ impl Transfer {
     utxos: &BTreeMap<OutPoint, Amount>,
     source: &Address,
   ) -> Result<Psbt> {
-    let mut tx_psbt = Psbt::from_unsigned_tx(tx.clone())?;
-    for i in 0..tx_psbt.unsigned_tx.input.len() {
-      tx_psbt.inputs[i].witness_utxo = Some(TxOut {
-        value: utxos
-          .get(&tx_psbt.unsigned_tx.input[i].previous_output)
-          .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
-          .to_sat(),
-        script_pubkey: source.script_pubkey(),
-      });
-    }
-    Ok(tx_psbt)
+    builder::psbt(tx, utxos, |_| source.script_pubkey())
   }
 
-  fn get_custom(tx: &Psbt) -> Vec<String> {
+  pub(crate) fn get_custom(tx: &Psbt) -> Vec<String> {
     let unsigned_commit_custom = ConstructTransaction {
       pre_outputs: TransactionOutputArray {
         outputs: tx
@@ -293,13 +827,304 @@ impl Transfer {
 
     result
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    bitcoin::{consensus::deserialize, hashes::hex::FromHex},
+  };
+
+  #[test]
+  fn parse_postage_policy() {
+    assert_eq!(
+      "preserve".parse::<PostagePolicy>().unwrap(),
+      PostagePolicy::Preserve
+    );
+    assert_eq!(
+      "target".parse::<PostagePolicy>().unwrap(),
+      PostagePolicy::Target
+    );
+    assert_eq!(
+      "10000".parse::<PostagePolicy>().unwrap(),
+      PostagePolicy::Value(Amount::from_sat(10000))
+    );
+    assert!("padded".parse::<PostagePolicy>().is_err());
+  }
 
-  fn calculate_fee(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> u64 {
-    tx.input
-      .iter()
-      .map(|txin| utxos.get(&txin.previous_output).unwrap().to_sat())
-      .sum::<u64>()
-      .checked_sub(tx.output.iter().map(|txout| txout.value).sum::<u64>())
-      .unwrap()
+  #[test]
+  fn postages() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(330)),
+      (outpoint(2), Amount::from_sat(10000)),
+    ]
+    .into();
+    let satpoints = [satpoint(1, 0), satpoint(1, 100), satpoint(2, 0)];
+
+    assert_eq!(
+      PostagePolicy::Preserve
+        .postages(&satpoints, &utxos)
+        .unwrap(),
+      [330, 230, 10000].map(Amount::from_sat)
+    );
+    assert_eq!(
+      PostagePolicy::Target.postages(&satpoints, &utxos).unwrap(),
+      [TransactionBuilder::TARGET_POSTAGE; 3]
+    );
+    assert_eq!(
+      PostagePolicy::Value(Amount::from_sat(600))
+        .postages(&satpoints, &utxos)
+        .unwrap(),
+      [Amount::from_sat(600); 3]
+    );
+    assert!(PostagePolicy::Preserve
+      .postages(&[satpoint(3, 0)], &utxos)
+      .is_err());
+  }
+
+  #[test]
+  fn padding_is_reported_with_its_inputs() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(330)),
+      (outpoint(2), Amount::from_sat(10000)),
+    ]
+    .into();
+    let tx = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1)), tx_in(outpoint(2))],
+      output: vec![tx_out(546, recipient()), tx_out(9000, change(0))],
+    };
+
+    assert_eq!(
+      PostagePadding::new(&tx, &[satpoint(1, 0)], &utxos),
+      [PostagePadding {
+        output: 0,
+        sats: 216,
+        inputs: vec![outpoint(2)],
+      }]
+    );
+
+    let tx = Transaction {
+      input: vec![tx_in(outpoint(2)), tx_in(outpoint(1))],
+      ..tx
+    };
+    assert_eq!(PostagePadding::new(&tx, &[satpoint(2, 0)], &utxos), []);
+  }
+
+  #[test]
+  fn padding_is_reported_per_destination() {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(330)),
+      (outpoint(2), Amount::from_sat(546)),
+      (outpoint(3), Amount::from_sat(10000)),
+    ]
+    .into();
+    let tx = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1)), tx_in(outpoint(3)), tx_in(outpoint(2))],
+      output: vec![
+        tx_out(546, recipient()),
+        tx_out(9784, change(0)),
+        tx_out(546, change(2)),
+      ],
+    };
+
+    // only the first destination is padded, out of the cardinal spent
+    // right after its inscription
+    assert_eq!(
+      PostagePadding::new(&tx, &[satpoint(1, 0), satpoint(2, 0)], &utxos),
+      [PostagePadding {
+        output: 0,
+        sats: 216,
+        inputs: vec![outpoint(3)],
+      }]
+    );
+  }
+
+  fn proven() -> (Psbt, TransferProof) {
+    let utxos = [
+      (outpoint(1), Amount::from_sat(10_000)),
+      (outpoint(2), Amount::from_sat(20_000)),
+    ]
+    .into();
+    let tx = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(2)), tx_in(outpoint(1))],
+      output: vec![tx_out(20_000, change(0)), tx_out(9_000, recipient())],
+    };
+    let proof = TransferProof::new(
+      &tx,
+      &utxos,
+      inscription_id(1),
+      satpoint(1, 100),
+      &recipient(),
+      Some(800_000),
+    )
+    .unwrap();
+    let psbt = builder::psbt(&tx, &utxos, |_| change(0).script_pubkey()).unwrap();
+    (psbt, proof)
+  }
+
+  #[test]
+  fn proof_follows_the_sat_through_earlier_inputs() {
+    let (psbt, proof) = proven();
+    assert_eq!(
+      proof,
+      TransferProof {
+        inscription_id: inscription_id(1),
+        satpoint: satpoint(1, 100),
+        input: 1,
+        output: 1,
+        output_value: 9_000,
+        destination: recipient(),
+        index_height: Some(800_000),
+      }
+    );
+    verify_transfer_proof(&psbt, &proof).unwrap();
+  }
+
+  #[test]
+  fn proof_of_sat_spent_as_fee_is_refused() {
+    let utxos = [(outpoint(1), Amount::from_sat(10_000))].into();
+    let tx = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1))],
+      output: vec![tx_out(9_000, recipient())],
+    };
+    assert!(TransferProof::new(
+      &tx,
+      &utxos,
+      inscription_id(1),
+      satpoint(1, 9_500),
+      &recipient(),
+      None
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn tampered_psbts_fail_verification() {
+    let (psbt, proof) = proven();
+
+    let mut redirected = psbt.clone();
+    redirected.unsigned_tx.output[1].script_pubkey = change(1).script_pubkey();
+    assert!(verify_transfer_proof(&redirected, &proof)
+      .unwrap_err()
+      .to_string()
+      .contains("does not pay"));
+
+    let mut shrunk = psbt.clone();
+    shrunk.unsigned_tx.output[1].value = 546;
+    assert!(verify_transfer_proof(&shrunk, &proof).is_err());
+
+    let mut shifted = psbt.clone();
+    shifted.unsigned_tx.output[0].value = 30_100;
+    assert!(verify_transfer_proof(&shifted, &proof)
+      .unwrap_err()
+      .to_string()
+      .contains("lands in output 0"));
+
+    let mut reordered = psbt.clone();
+    reordered.unsigned_tx.input.swap(0, 1);
+    reordered.inputs.swap(0, 1);
+    assert!(verify_transfer_proof(&reordered, &proof).is_err());
+
+    let mut inflated = psbt.clone();
+    inflated.inputs[0].witness_utxo.as_mut().unwrap().value = 29_500;
+    assert!(verify_transfer_proof(&inflated, &proof).is_err());
+
+    let mut stripped = psbt;
+    stripped.inputs[0].witness_utxo = None;
+    assert!(verify_transfer_proof(&stripped, &proof)
+      .unwrap_err()
+      .to_string()
+      .contains("no witness utxo"));
+  }
+
+  #[test]
+  fn tampered_proofs_fail_verification() {
+    let (psbt, proof) = proven();
+
+    for tampered in [
+      TransferProof {
+        input: 0,
+        ..proof.clone()
+      },
+      TransferProof {
+        input: 2,
+        ..proof.clone()
+      },
+      TransferProof {
+        output: 0,
+        ..proof.clone()
+      },
+      TransferProof {
+        output_value: 10_000,
+        ..proof.clone()
+      },
+      TransferProof {
+        destination: change(1),
+        ..proof.clone()
+      },
+      TransferProof {
+        satpoint: satpoint(1, 10_000),
+        ..proof.clone()
+      },
+    ] {
+      assert!(
+        verify_transfer_proof(&psbt, &tampered).is_err(),
+        "{tampered:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn excluded_inputs_never_pay_fees() {
+    let rpc_server = test_bitcoincore_rpc::builder()
+      .network(Network::Signet)
+      .build();
+    rpc_server.mine_blocks(1);
+    let txid = rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0)],
+      outputs: 4,
+      output_values: &[1_000_000, 20_000, 500_000],
+      recipient: Some(recipient()),
+      ..Default::default()
+    });
+    rpc_server.mine_blocks(1);
+    let tempdir = TempDir::new().unwrap();
+    let options = signet_options(&rpc_server, &tempdir);
+
+    let output = Transfer::try_parse_from([
+      "transfer".into(),
+      format!("--destination={}", change(0)),
+      format!("--source={}", recipient()),
+      "--fee-rate=1".into(),
+      "--addition-fee=0 sat".into(),
+      format!("--input={}", OutPoint::new(txid, 0)),
+      format!("--input={}", OutPoint::new(txid, 1)),
+      format!("--input={}", OutPoint::new(txid, 2)),
+      format!("--exclude={}", OutPoint::new(txid, 0)),
+      "19900 sat".into(),
+    ])
+    .unwrap()
+    .build(options, None)
+    .unwrap();
+
+    let transaction: Psbt = deserialize(&Vec::from_hex(&output.transaction).unwrap()).unwrap();
+    assert_eq!(
+      transaction
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<OutPoint>>(),
+      [OutPoint::new(txid, 1), OutPoint::new(txid, 2)]
+    );
   }
 }
@@ -0,0 +1,168 @@
+use {
+  super::*,
+  bitcoin::hashes::hex::{FromHex, ToHex},
+};
+
+/// A destination flagged as unsafe for inscriptions, such as an exchange
+/// deposit address that only credits plain BTC. Written `LABEL=PATTERN`,
+/// where the pattern is an address, `prefix:` followed by the start of an
+/// address, or `script:` followed by the start of a hex script pubkey.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsafeDestination {
+  pub label: String,
+  pub pattern: DestinationPattern,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DestinationPattern {
+  Address(String),
+  AddressPrefix(String),
+  ScriptPrefix(String),
+}
+
+impl FromStr for DestinationPattern {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Some(prefix) = s.strip_prefix("prefix:") {
+      if prefix.is_empty() {
+        bail!("address prefix pattern `{s}` is empty");
+      }
+      Ok(Self::AddressPrefix(prefix.into()))
+    } else if let Some(prefix) = s.strip_prefix("script:") {
+      if prefix.is_empty() || Vec::<u8>::from_hex(prefix).is_err() {
+        bail!("script pattern `{s}` is not a non-empty hex prefix");
+      }
+      Ok(Self::ScriptPrefix(prefix.to_lowercase()))
+    } else if s.is_empty() {
+      bail!("destination pattern is empty");
+    } else {
+      Ok(Self::Address(s.into()))
+    }
+  }
+}
+
+impl Display for DestinationPattern {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      Self::Address(address) => write!(f, "{address}"),
+      Self::AddressPrefix(prefix) => write!(f, "prefix:{prefix}"),
+      Self::ScriptPrefix(prefix) => write!(f, "script:{prefix}"),
+    }
+  }
+}
+
+impl DestinationPattern {
+  fn matches(&self, address: &Address) -> bool {
+    match self {
+      Self::Address(pattern) => address.to_string() == *pattern,
+      Self::AddressPrefix(prefix) => address.to_string().starts_with(prefix.as_str()),
+      Self::ScriptPrefix(prefix) => address
+        .script_pubkey()
+        .to_hex()
+        .starts_with(prefix.as_str()),
+    }
+  }
+}
+
+impl FromStr for UnsafeDestination {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (label, pattern) = s
+      .split_once('=')
+      .ok_or_else(|| anyhow!("unsafe destination `{s}` is not of the form LABEL=PATTERN"))?;
+    if label.is_empty() {
+      bail!("unsafe destination `{s}` has no label");
+    }
+    Ok(Self {
+      label: label.into(),
+      pattern: pattern.parse()?,
+    })
+  }
+}
+
+impl UnsafeDestination {
+  /// The first of `destinations` that `address` matches.
+  pub fn find<'a>(destinations: &'a [Self], address: &Address) -> Option<&'a Self> {
+    destinations
+      .iter()
+      .find(|destination| destination.pattern.matches(address))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn address(s: &str) -> Address {
+    Address::from_str(s).unwrap()
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      "Exchange A=bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        .parse::<UnsafeDestination>()
+        .unwrap(),
+      UnsafeDestination {
+        label: "Exchange A".into(),
+        pattern: DestinationPattern::Address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".into()),
+      }
+    );
+    assert_eq!(
+      "b=script:0014ABCD"
+        .parse::<UnsafeDestination>()
+        .unwrap()
+        .pattern,
+      DestinationPattern::ScriptPrefix("0014abcd".into())
+    );
+    assert_eq!(
+      "c=prefix:3Exch"
+        .parse::<UnsafeDestination>()
+        .unwrap()
+        .pattern,
+      DestinationPattern::AddressPrefix("3Exch".into())
+    );
+    assert!("no-label".parse::<UnsafeDestination>().is_err());
+    assert!("=bc1q".parse::<UnsafeDestination>().is_err());
+    assert!("d=script:zz".parse::<UnsafeDestination>().is_err());
+    assert!("e=prefix:".parse::<UnsafeDestination>().is_err());
+  }
+
+  #[test]
+  fn find() {
+    let destinations = [
+      "exact=bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        .parse::<UnsafeDestination>()
+        .unwrap(),
+      "p2sh deposits=script:a914".parse().unwrap(),
+    ];
+
+    assert_eq!(
+      UnsafeDestination::find(
+        &destinations,
+        &address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+      )
+      .unwrap()
+      .label,
+      "exact"
+    );
+    assert_eq!(
+      UnsafeDestination::find(
+        &destinations,
+        &address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")
+      )
+      .unwrap()
+      .label,
+      "p2sh deposits"
+    );
+    assert_eq!(
+      UnsafeDestination::find(
+        &destinations,
+        &address("bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297")
+      ),
+      None
+    );
+  }
+}
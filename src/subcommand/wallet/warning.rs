@@ -0,0 +1,285 @@
+use super::*;
+
+/// Fee rates above this multiple of the node's next block estimate are
+/// reported as high.
+pub const HIGH_FEE_RATE_MULTIPLE: f64 = 2.0;
+
+/// Change at or above this value is reported as large.
+pub const LARGE_CHANGE: Amount = Amount::from_sat(10_000_000);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+  IndexLag,
+  HighFeeRate,
+  LargeChange,
+  DestinationIsSource,
+  UnsafeDestination,
+}
+
+impl WarningCode {
+  pub const ALL: [WarningCode; 5] = [
+    WarningCode::IndexLag,
+    WarningCode::HighFeeRate,
+    WarningCode::LargeChange,
+    WarningCode::DestinationIsSource,
+    WarningCode::UnsafeDestination,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      WarningCode::IndexLag => "index_lag",
+      WarningCode::HighFeeRate => "high_fee_rate",
+      WarningCode::LargeChange => "large_change",
+      WarningCode::DestinationIsSource => "destination_is_source",
+      WarningCode::UnsafeDestination => "unsafe_destination",
+    }
+  }
+}
+
+impl FromStr for WarningCode {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL
+      .into_iter()
+      .find(|code| code.name() == s)
+      .ok_or_else(|| {
+        anyhow!(
+          "unknown warning `{s}`, expected one of {}",
+          Self::ALL.map(WarningCode::name).join(", ")
+        )
+      })
+  }
+}
+
+/// Something the caller should know about a build that did not stop it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Warning {
+  pub code: WarningCode,
+  pub message: String,
+  pub data: serde_json::Value,
+}
+
+/// Collects warnings while building. Checks that need the node never fail
+/// the build, when the node cannot answer the check is skipped.
+#[derive(Debug, Default)]
+pub struct Warnings(Vec<Warning>);
+
+impl Warnings {
+  fn push(&mut self, code: WarningCode, message: String, data: serde_json::Value) {
+    self.0.push(Warning {
+      code,
+      message,
+      data,
+    });
+  }
+
+  pub(crate) fn check_index(&mut self, index: &Index) {
+    match index
+      .index_height()
+      .and_then(|index_height| Ok((index_height, index.node_height()?)))
+    {
+      Ok((index_height, node_height)) => self.index_lag(index_height, node_height),
+      Err(err) => tracing::warn!(error = %err, "Skip index lag warning"),
+    }
+  }
+
+  pub(crate) fn check_fee_rate(&mut self, index: &Index, fee_rate: FeeRate) {
+    match index.fast_fee_rate() {
+      Ok(fast_fee_rate) => self.high_fee_rate(fee_rate, fast_fee_rate),
+      Err(err) => tracing::warn!(error = %err, "Skip fee rate warning"),
+    }
+  }
+
+  fn index_lag(&mut self, index_height: Option<u64>, node_height: u64) {
+    let lag = node_height.saturating_sub(index_height.unwrap_or(0));
+    if lag > 0 {
+      self.push(
+        WarningCode::IndexLag,
+        format!(
+          "index is {lag} blocks behind the node, recently confirmed inscriptions may be missing"
+        ),
+        serde_json::json!({
+          "index_height": index_height,
+          "node_height": node_height,
+        }),
+      );
+    }
+  }
+
+  fn high_fee_rate(&mut self, fee_rate: FeeRate, fast_fee_rate: Option<f64>) {
+    let Some(fast_fee_rate) = fast_fee_rate else {
+      return;
+    };
+
+    if fee_rate.0 > fast_fee_rate * HIGH_FEE_RATE_MULTIPLE {
+      self.push(
+        WarningCode::HighFeeRate,
+        format!(
+          "fee rate {} sat/vB is more than {HIGH_FEE_RATE_MULTIPLE} times the next block estimate of {fast_fee_rate} sat/vB",
+          fee_rate.0
+        ),
+        serde_json::json!({
+          "fee_rate": fee_rate.0,
+          "fast_fee_rate": fast_fee_rate,
+        }),
+      );
+    }
+  }
+
+  pub(crate) fn check_change(&mut self, tx: &Transaction, change: &Address) {
+    let script_pubkey = change.script_pubkey();
+    for (vout, output) in tx.output.iter().enumerate() {
+      if output.script_pubkey == script_pubkey && output.value >= LARGE_CHANGE.to_sat() {
+        self.push(
+          WarningCode::LargeChange,
+          format!(
+            "output {vout} returns {} sats of change to {change}",
+            output.value
+          ),
+          serde_json::json!({
+            "vout": vout,
+            "value": output.value,
+          }),
+        );
+      }
+    }
+  }
+
+  pub(crate) fn check_destination(&mut self, destination: &Address, source: &Address) {
+    if destination == source {
+      self.push(
+        WarningCode::DestinationIsSource,
+        format!("destination {destination} is the source address"),
+        serde_json::Value::Null,
+      );
+    }
+  }
+
+  pub(crate) fn unsafe_destination(
+    &mut self,
+    destination: &Address,
+    unsafe_destination: &unsafe_destination::UnsafeDestination,
+  ) {
+    self.push(
+      WarningCode::UnsafeDestination,
+      format!(
+        "destination {destination} matches unsafe destination `{}`, which may not credit inscriptions",
+        unsafe_destination.label
+      ),
+      serde_json::json!({
+        "label": unsafe_destination.label,
+        "pattern": unsafe_destination.pattern.to_string(),
+      }),
+    );
+  }
+
+  pub fn into_vec(self) -> Vec<Warning> {
+    self.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, bitcoin::PackedLockTime};
+
+  fn codes(warnings: Warnings) -> Vec<WarningCode> {
+    warnings
+      .into_vec()
+      .into_iter()
+      .map(|warning| warning.code)
+      .collect()
+  }
+
+  #[test]
+  fn from_str() {
+    for code in WarningCode::ALL {
+      assert_eq!(code.name().parse::<WarningCode>().unwrap(), code);
+      assert_eq!(
+        serde_json::to_value(code).unwrap(),
+        serde_json::Value::String(code.name().into())
+      );
+    }
+    assert!("index-lag".parse::<WarningCode>().is_err());
+  }
+
+  #[test]
+  fn index_lag() {
+    let mut warnings = Warnings::default();
+    warnings.index_lag(Some(100), 100);
+    assert!(codes(warnings).is_empty());
+
+    let mut warnings = Warnings::default();
+    warnings.index_lag(Some(98), 100);
+    let warnings = warnings.into_vec();
+    assert_eq!(warnings[0].code, WarningCode::IndexLag);
+    assert_eq!(
+      warnings[0].data,
+      serde_json::json!({"index_height": 98, "node_height": 100})
+    );
+
+    let mut warnings = Warnings::default();
+    warnings.index_lag(None, 5);
+    assert_eq!(codes(warnings), [WarningCode::IndexLag]);
+  }
+
+  #[test]
+  fn high_fee_rate() {
+    let mut warnings = Warnings::default();
+    warnings.high_fee_rate(FeeRate::try_from(20.0).unwrap(), Some(10.0));
+    warnings.high_fee_rate(FeeRate::try_from(100.0).unwrap(), None);
+    assert!(codes(warnings).is_empty());
+
+    let mut warnings = Warnings::default();
+    warnings.high_fee_rate(FeeRate::try_from(20.1).unwrap(), Some(10.0));
+    assert_eq!(codes(warnings), [WarningCode::HighFeeRate]);
+  }
+
+  #[test]
+  fn large_change() {
+    let tx = Transaction {
+      version: 2,
+      lock_time: PackedLockTime::ZERO,
+      input: vec![tx_in(outpoint(1))],
+      output: vec![
+        tx_out(LARGE_CHANGE.to_sat(), recipient()),
+        tx_out(LARGE_CHANGE.to_sat() - 1, change(0)),
+      ],
+    };
+
+    let mut warnings = Warnings::default();
+    warnings.check_change(&tx, &change(0));
+    assert!(codes(warnings).is_empty());
+
+    let mut warnings = Warnings::default();
+    warnings.check_change(&tx, &recipient());
+    let warnings = warnings.into_vec();
+    assert_eq!(warnings[0].code, WarningCode::LargeChange);
+    assert_eq!(warnings[0].data["vout"], 0);
+  }
+
+  #[test]
+  fn destination_is_source() {
+    let mut warnings = Warnings::default();
+    warnings.check_destination(&recipient(), &change(0));
+    assert!(codes(warnings).is_empty());
+
+    let mut warnings = Warnings::default();
+    warnings.check_destination(&recipient(), &recipient());
+    assert_eq!(codes(warnings), [WarningCode::DestinationIsSource]);
+  }
+
+  #[test]
+  fn unsafe_destination_names_the_label() {
+    let unsafe_destination = format!("Exchange A={}", recipient())
+      .parse::<unsafe_destination::UnsafeDestination>()
+      .unwrap();
+    let mut warnings = Warnings::default();
+    warnings.unsafe_destination(&recipient(), &unsafe_destination);
+    let warnings = warnings.into_vec();
+    assert_eq!(warnings[0].code, WarningCode::UnsafeDestination);
+    assert!(warnings[0].message.contains("`Exchange A`"));
+    assert_eq!(warnings[0].data["label"], "Exchange A");
+  }
+}
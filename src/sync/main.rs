@@ -1,17 +1,20 @@
 use bitcoin::Network;
-use clap::{Arg, Command};
-use log::{error, info};
+use clap::{Arg, ArgAction, Command};
 use ord::chain::Chain;
-use ord::index::{Index, MysqlDatabase};
+use ord::index::{
+  block_timing_stats, BackfillOptions, Index, MysqlDatabase, PruneOptions, ReconcileOptions,
+};
+use ord::logging::{self, LogFormat};
 use ord::options::Options;
+use ord::rpc_failover::{self, rpc_endpoint_status};
 use std::path::PathBuf;
+use std::process;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
 
 fn main() {
-  std::env::set_var("RUST_LOG", "info");
-  env_logger::init();
   let args = Command::new("Brc20 Server")
     .arg(
       Arg::new("chain")
@@ -44,11 +47,28 @@ fn main() {
         .takes_value(true)
         .help("Store index in <DATA_DIR>."),
     )
+    .arg(
+      Arg::new("adopt-data-dir")
+        .long("adopt-data-dir")
+        .takes_value(false)
+        .help(
+          "Mark an index or mysql database that does not record its chain as belonging to <CHAIN>.",
+        ),
+    )
     .arg(
       Arg::new("rpc-url")
         .long("rpc-url")
         .takes_value(true)
-        .help("Connect to Bitcoin Core RPC at <RPC_URL>."),
+        .action(ArgAction::Append)
+        .help("Connect to Bitcoin Core RPC at <RPC_URL>. May be repeated, later endpoints are failed over to in order."),
+    )
+    .arg(
+      Arg::new("rpc-failback-interval")
+        .long("rpc-failback-interval")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64))
+        .default_value("30")
+        .help("Check the first <RPC_URL> every <RPC_FAILBACK_INTERVAL> seconds while failed over, and fail back once it recovers."),
     )
     .arg(
       Arg::new("wait-start")
@@ -73,9 +93,125 @@ fn main() {
         .long("mysql-password")
         .takes_value(true)
         .help("Mysql password."),
+    )
+    .arg(
+      Arg::new("watched-only")
+        .long("watched-only")
+        .takes_value(false)
+        .help("Only index inscriptions revealed to watched addresses and keep mysql rows for them. Inscriptions revealed anywhere else are never indexed, so inscription numbers differ from a full index."),
+    )
+    .arg(
+      Arg::new("watch-service-address")
+        .long("watch-service-address")
+        .takes_value(true)
+        .help("Also watch <WATCH_SERVICE_ADDRESS> in watched-only mode."),
+    )
+    .arg(
+      Arg::new("backfill-depth")
+        .long("backfill-depth")
+        .takes_value(true)
+        .default_value("1000")
+        .help("Scan the last <BACKFILL_DEPTH> blocks for newly watched addresses."),
+    )
+    .arg(
+      Arg::new("backfill-job-blocks")
+        .long("backfill-job-blocks")
+        .takes_value(true)
+        .default_value("100")
+        .help("Scan at most <BACKFILL_JOB_BLOCKS> blocks for queued address backfills per update."),
+    )
+    .arg(
+      Arg::new("backfill-job-sleep-ms")
+        .long("backfill-job-sleep-ms")
+        .takes_value(true)
+        .default_value("100")
+        .help("Sleep <BACKFILL_JOB_SLEEP_MS> between blocks scanned for address backfills."),
+    )
+    .arg(
+      Arg::new("backfill-content-type-stats")
+        .long("backfill-content-type-stats")
+        .takes_value(false)
+        .help("Compute content type stats for inscriptions already indexed, then exit."),
+    )
+    .arg(
+      Arg::new("record-block-stats")
+        .long("record-block-stats")
+        .takes_value(true)
+        .value_name("DAYS")
+        .help("Record each block's indexing time by phase in mysql, keeping <DAYS> of rows."),
+    )
+    .arg(
+      Arg::new("prune-depth")
+        .long("prune-depth")
+        .takes_value(true)
+        .help("Prune mysql rows settled, or left unsettled, more than <PRUNE_DEPTH> blocks ago."),
+    )
+    .arg(
+      Arg::new("prune-batch-size")
+        .long("prune-batch-size")
+        .takes_value(true)
+        .default_value("1000")
+        .help("Delete at most <PRUNE_BATCH_SIZE> rows per statement when pruning."),
+    )
+    .arg(
+      Arg::new("prune-sleep-ms")
+        .long("prune-sleep-ms")
+        .takes_value(true)
+        .default_value("500")
+        .help("Sleep <PRUNE_SLEEP_MS> between prune batches."),
+    )
+    .arg(
+      Arg::new("reconcile-expire-after")
+        .long("reconcile-expire-after")
+        .takes_value(true)
+        .default_value("144")
+        .help("Expire reveal expectations whose commit is unconfirmed <RECONCILE_EXPIRE_AFTER> blocks after they were first seen."),
+    )
+    .arg(
+      Arg::new("reconcile-batch-size")
+        .long("reconcile-batch-size")
+        .takes_value(true)
+        .default_value("100")
+        .help("Check at most <RECONCILE_BATCH_SIZE> reveal expectations per batch when reconciling."),
+    )
+    .arg(
+      Arg::new("reconcile-sleep-ms")
+        .long("reconcile-sleep-ms")
+        .takes_value(true)
+        .default_value("500")
+        .help("Sleep <RECONCILE_SLEEP_MS> between reconcile batches."),
+    )
+    .arg(
+      Arg::new("reconcile-interval")
+        .long("reconcile-interval")
+        .takes_value(true)
+        .default_value("600")
+        .help("Reconcile reveal expectations against the chain at startup and every <RECONCILE_INTERVAL> seconds."),
+    )
+    .arg(
+      Arg::new("log-format")
+        .long("log-format")
+        .takes_value(true)
+        .default_value("text")
+        .help("Write logs as <LOG_FORMAT>, one of json or text."),
+    )
+    .arg(
+      Arg::new("log-level")
+        .long("log-level")
+        .takes_value(true)
+        .help("Log at <LOG_LEVEL>, e.g. `info,ord::index=debug`. Overrides RUST_LOG."),
     );
 
   let matches = args.get_matches();
+  logging::init(
+    matches
+      .get_one::<String>("log-format")
+      .unwrap()
+      .parse::<LogFormat>()
+      .unwrap(),
+    matches.get_one::<String>("log-level").map(|s| s.as_str()),
+  )
+  .unwrap();
   let chain = matches
     .get_one::<String>("chain")
     .map(|s| s.as_str())
@@ -105,6 +241,8 @@ fn main() {
 
   let data_dir: Option<PathBuf> = matches.get_one::<String>("data-dir").map(|s| s.into());
 
+  let adopt_data_dir = matches.contains_id("adopt-data-dir");
+
   let wait_start = matches
     .get_one::<String>("wait-start")
     .map(|s| s.parse().unwrap_or(0));
@@ -114,13 +252,90 @@ fn main() {
   let mysql_password = matches.get_one::<String>("mysql-password").cloned();
 
   if let Some(w) = wait_start {
-    info!("Wait {w}s to start...");
+    info!(seconds = w, "Wait to start...");
     thread::sleep(Duration::from_secs(w));
   }
 
-  let rpc_url = matches.get_one::<String>("rpc-url").cloned();
+  let rpc_url: Vec<String> = matches
+    .get_many::<String>("rpc-url")
+    .map(|urls| urls.cloned().collect())
+    .unwrap_or_default();
+  let rpc_failback_interval =
+    Duration::from_secs(*matches.get_one::<u64>("rpc-failback-interval").unwrap());
+
+  let watched_only = matches.contains_id("watched-only");
+  let watch_service_address: Vec<String> = matches
+    .get_one::<String>("watch-service-address")
+    .cloned()
+    .into_iter()
+    .collect();
+  let backfill_depth: u64 = matches
+    .get_one::<String>("backfill-depth")
+    .map(|s| s.parse().unwrap_or(1000))
+    .unwrap();
+
+  let backfill_options = Arc::new(BackfillOptions {
+    blocks: matches
+      .get_one::<String>("backfill-job-blocks")
+      .map(|s| s.parse().unwrap_or(100))
+      .unwrap(),
+    sleep: Duration::from_millis(
+      matches
+        .get_one::<String>("backfill-job-sleep-ms")
+        .map(|s| s.parse().unwrap_or(100))
+        .unwrap(),
+    ),
+  });
+
+  let backfill_content_type_stats = matches.contains_id("backfill-content-type-stats");
+
+  let record_block_stats: Option<u64> = matches
+    .get_one::<String>("record-block-stats")
+    .map(|s| s.parse().unwrap_or(7));
+
+  let prune_options = matches
+    .get_one::<String>("prune-depth")
+    .map(|s| PruneOptions {
+      depth: s.parse().unwrap_or(1000),
+      batch_size: matches
+        .get_one::<String>("prune-batch-size")
+        .map(|s| s.parse().unwrap_or(1000))
+        .unwrap(),
+      sleep: Duration::from_millis(
+        matches
+          .get_one::<String>("prune-sleep-ms")
+          .map(|s| s.parse().unwrap_or(500))
+          .unwrap(),
+      ),
+    })
+    .map(Arc::new);
+
+  let reconcile_options = Arc::new(ReconcileOptions {
+    expire_after: matches
+      .get_one::<String>("reconcile-expire-after")
+      .map(|s| s.parse().unwrap_or(144))
+      .unwrap(),
+    batch_size: matches
+      .get_one::<String>("reconcile-batch-size")
+      .map(|s| s.parse().unwrap_or(100))
+      .unwrap(),
+    sleep: Duration::from_millis(
+      matches
+        .get_one::<String>("reconcile-sleep-ms")
+        .map(|s| s.parse().unwrap_or(500))
+        .unwrap(),
+    ),
+  });
+  let reconcile_interval = Duration::from_secs(
+    matches
+      .get_one::<String>("reconcile-interval")
+      .map(|s| s.parse().unwrap_or(600))
+      .unwrap(),
+  );
 
   let options = Options {
+    adopt_data_dir,
+    allowed_destination_types: Vec::new(),
     bitcoin_data_dir,
     bitcoin_rpc_pass,
     bitcoin_rpc_user,
@@ -135,11 +350,14 @@ fn main() {
     index_sats: false,
     regtest: false,
     rpc_url,
+    shared_index: None,
     signet: false,
     testnet: false,
     wallet: "ord".to_string(),
   };
 
+  rpc_failover::spawn_failback(options.clone(), rpc_failback_interval);
+
   let my_struct = Arc::new(Mutex::new(options));
 
   let database = if mysql_host.is_none() || mysql_username.is_none() || mysql_password.is_none() {
@@ -147,44 +365,153 @@ fn main() {
     None
   } else {
     info!("Use mysql...");
-    Some(Arc::new(
-      MysqlDatabase::new(mysql_host, mysql_username, mysql_password, network).unwrap(),
-    ))
+    let database = MysqlDatabase::new(mysql_host, mysql_username, mysql_password, network).unwrap();
+    database.check_network(adopt_data_dir).unwrap();
+    Some(Arc::new(database))
   };
 
+  if backfill_content_type_stats {
+    let Some(db) = database else {
+      error!("Backfilling content type stats requires mysql");
+      process::exit(1);
+    };
+    let options = my_struct.lock().unwrap();
+    match Index::open_with_mysql(&options, db).and_then(|index| index.backfill_content_type_stats())
+    {
+      Ok(total) => info!(total, "Backfill content type stats success"),
+      Err(e) => {
+        error!(error = %e, "Backfill content type stats error");
+        process::exit(1);
+      }
+    }
+    return;
+  }
+
   let mut count = 0;
+  let mut last_reconcile: Option<Instant> = None;
   loop {
     if count > 0 {
       thread::sleep(Duration::from_secs(3));
     }
 
+    let reconcile_options =
+      if last_reconcile.map_or(true, |last| last.elapsed() >= reconcile_interval) {
+        last_reconcile = Some(Instant::now());
+        Some(reconcile_options.clone())
+      } else {
+        None
+      };
+
     let thread_struct = Arc::clone(&my_struct);
     let database = database.clone();
+    let watch_service_address = watch_service_address.clone();
+    let prune_options = prune_options.clone();
+    let backfill_options = backfill_options.clone();
     let child_thread = thread::spawn(move || {
-      info!("Index {count}th update...");
+      info!(count, "Index update...");
       let my_struct = thread_struct.lock().unwrap();
-      let open_result = if let Some(db) = database {
+      let open_result = if let Some(db) = database.clone() {
         Index::open_with_mysql(&my_struct, db)
       } else {
         Index::open(&my_struct)
       };
+      let open_result = if watched_only {
+        open_result.map(|index| index.with_watched_only(watch_service_address))
+      } else {
+        open_result
+      };
+      let open_result = match record_block_stats {
+        Some(retain_days) => open_result.map(|index| index.with_block_stats(retain_days)),
+        None => open_result,
+      };
       match open_result {
         Ok(index) => {
           if let Err(e) = index.update() {
-            error!("Index update error:{e}")
+            error!(error = %e, "Index update error")
           } else {
-            info!("Index update success")
+            let rpc = rpc_endpoint_status();
+            info!(
+              rpc_active = ?rpc.active,
+              rpc_failovers = rpc.failovers,
+              rpc_failbacks = rpc.failbacks,
+              "Index update success"
+            );
+            let timings = block_timing_stats();
+            if let (Some(last), Some(average)) = (timings.last, timings.average) {
+              info!(
+                indexed = timings.indexed,
+                ?last,
+                ?average,
+                "Block timings (us)"
+              );
+            }
+            match index.partially_parsed_inscriptions(1) {
+              Ok((total, recent)) => info!(
+                total,
+                last_height = ?recent.first().map(|(height, _)| *height),
+                "Partially parsed inscriptions"
+              ),
+              Err(e) => error!(error = %e, "Partially parsed inscriptions error"),
+            }
+          }
+          if watched_only {
+            if let Err(e) = index.backfill_watched_addresses(backfill_depth) {
+              error!(error = %e, "Backfill watched addresses error")
+            }
+          }
+          if let Some(db) = database {
+            match index.run_backfill_jobs(&backfill_options) {
+              Ok(0) => {}
+              Ok(blocks) => info!(blocks, "Backfill jobs"),
+              Err(e) => error!(error = %e, "Backfill jobs error"),
+            }
+            if let Some(reconcile_options) = reconcile_options {
+              match index.reconcile_reveal_expectations(&reconcile_options) {
+                Ok(reconciliation) => info!(
+                  checked = reconciliation.checked,
+                  fulfilled = reconciliation.fulfilled,
+                  superseded = reconciliation.superseded,
+                  expired = reconciliation.expired,
+                  "Reconciled reveal expectations"
+                ),
+                Err(e) => error!(error = %e, "Reconcile reveal expectations error"),
+              }
+            }
+            match db.get_reveal_expectation_counts() {
+              Ok(counts) => info!(
+                built = counts.built,
+                fulfilled = counts.fulfilled,
+                pending = counts.pending,
+                superseded = counts.superseded,
+                expired = counts.expired,
+                "Reveal expectations"
+              ),
+              Err(e) => error!(error = %e, "Reveal expectations error"),
+            }
+            if let Some(prune_options) = prune_options {
+              match index
+                .node_height()
+                .and_then(|tip| db.prune(tip, &prune_options))
+              {
+                Ok(pruned) => {
+                  for (table, rows) in pruned {
+                    info!(%table, rows, "Pruned");
+                  }
+                }
+                Err(e) => error!(error = %e, "Prune error"),
+              }
+            }
           }
         }
         Err(e) => {
-          error!("Index open error:{e}")
+          error!(error = %e, "Index open error")
         }
       }
     });
 
     if let Err(panic) = child_thread.join() {
       if let Some(payload) = panic.downcast_ref::<&str>() {
-        error!("Index update panic: {payload}");
+        error!(%payload, "Index update panic");
       } else {
         error!("Index update unknown panic");
       }
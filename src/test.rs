@@ -113,3 +113,28 @@ pub(crate) fn inscription_id(n: u32) -> InscriptionId {
 
   format!("{}i{n}", hex.repeat(64)).parse().unwrap()
 }
+
+/// Options for building against `rpc_server` on signet, where the `tb1`
+/// test addresses are valid, with an index in `tempdir` caught up to it.
+pub(crate) fn signet_options(
+  rpc_server: &test_bitcoincore_rpc::Handle,
+  tempdir: &TempDir,
+) -> Options {
+  let cookie_file = tempdir.path().join("cookie");
+  fs::write(&cookie_file, "username:password").unwrap();
+
+  let command: Vec<OsString> = vec![
+    "ord".into(),
+    "--rpc-url".into(),
+    rpc_server.url().into(),
+    "--data-dir".into(),
+    tempdir.path().into(),
+    "--cookie-file".into(),
+    cookie_file.into(),
+    "--signet".into(),
+  ];
+
+  let options = Options::try_parse_from(command).unwrap();
+  Index::open(&options).unwrap().update().unwrap();
+  options
+}
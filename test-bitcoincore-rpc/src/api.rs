@@ -12,6 +12,9 @@ pub trait Api {
   #[rpc(name = "getbalances")]
   fn get_balances(&self) -> Result<GetBalancesResult, jsonrpc_core::Error>;
 
+  #[rpc(name = "getbestblockhash")]
+  fn get_best_block_hash(&self) -> Result<BlockHash, jsonrpc_core::Error>;
+
   #[rpc(name = "getblockhash")]
   fn get_block_hash(&self, height: usize) -> Result<BlockHash, jsonrpc_core::Error>;
 
@@ -89,6 +92,14 @@ pub trait Api {
     blockhash: Option<BlockHash>,
   ) -> Result<Value, jsonrpc_core::Error>;
 
+  #[rpc(name = "gettxout")]
+  fn get_tx_out(
+    &self,
+    txid: Txid,
+    vout: u32,
+    include_mempool: Option<bool>,
+  ) -> Result<Option<GetTxOutResult>, jsonrpc_core::Error>;
+
   #[rpc(name = "listunspent")]
   fn list_unspent(
     &self,
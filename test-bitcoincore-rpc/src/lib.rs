@@ -15,10 +15,11 @@ use {
   bitcoincore_rpc::json::{
     Bip125Replaceable, CreateRawTransactionInput, Descriptor, EstimateMode, GetBalancesResult,
     GetBalancesResultEntry, GetBlockHeaderResult, GetBlockchainInfoResult, GetDescriptorInfoResult,
-    GetNetworkInfoResult, GetRawTransactionResult, GetTransactionResult,
-    GetTransactionResultDetail, GetTransactionResultDetailCategory, GetWalletInfoResult,
-    ImportDescriptors, ImportMultiResult, ListDescriptorsResult, ListTransactionResult,
-    ListUnspentResultEntry, LoadWalletResult, SignRawTransactionResult, Timestamp, WalletTxInfo,
+    GetNetworkInfoResult, GetRawTransactionResult, GetRawTransactionResultVoutScriptPubKey,
+    GetTransactionResult, GetTransactionResultDetail, GetTransactionResultDetailCategory,
+    GetTxOutResult, GetWalletInfoResult, ImportDescriptors, ImportMultiResult,
+    ListDescriptorsResult, ListTransactionResult, ListUnspentResultEntry, LoadWalletResult,
+    SignRawTransactionResult, Timestamp, WalletTxInfo,
   },
   jsonrpc_core::{IoHandler, Value},
   jsonrpc_http_server::{CloseHandle, ServerBuilder},
@@ -118,6 +119,7 @@ pub struct TransactionTemplate<'a> {
   pub inputs: &'a [(usize, usize, usize)],
   pub output_values: &'a [u64],
   pub outputs: usize,
+  pub recipient: Option<Address>,
   pub witness: Witness,
 }
 
@@ -150,6 +152,7 @@ impl<'a> Default for TransactionTemplate<'a> {
       inputs: &[],
       output_values: &[],
       outputs: 1,
+      recipient: None,
       witness: Witness::default(),
     }
   }
@@ -90,6 +90,10 @@ impl Api for Server {
     })
   }
 
+  fn get_best_block_hash(&self) -> Result<BlockHash, jsonrpc_core::Error> {
+    Ok(*self.state().hashes.last().unwrap())
+  }
+
   fn get_block_hash(&self, height: usize) -> Result<BlockHash, jsonrpc_core::Error> {
     match self.state().hashes.get(height) {
       Some(block_hash) => Ok(*block_hash),
@@ -264,7 +268,19 @@ impl Api for Server {
 
   fn send_raw_transaction(&self, tx: String) -> Result<String, jsonrpc_core::Error> {
     let tx: Transaction = deserialize(&hex::decode(tx).unwrap()).unwrap();
-    self.state.lock().unwrap().mempool.push(tx.clone());
+    let mut state = self.state.lock().unwrap();
+
+    // like bitcoind, refuse transactions the next block could not include
+    let next_height = u32::try_from(state.hashes.len()).unwrap();
+    if tx.is_lock_time_enabled() && tx.lock_time.0 >= next_height {
+      return Err(jsonrpc_core::Error {
+        code: jsonrpc_core::types::error::ErrorCode::ServerError(-26),
+        message: "non-final".into(),
+        data: None,
+      });
+    }
+
+    state.mempool.push(tx.clone());
 
     Ok(tx.txid().to_string())
   }
@@ -367,13 +383,75 @@ impl Api for Server {
         None => Err(Self::not_found()),
       }
     } else {
-      match self.state().transactions.get(&txid) {
+      // like bitcoind, serve transactions still in the mempool too
+      let state = self.state();
+      match state
+        .transactions
+        .get(&txid)
+        .or_else(|| state.mempool.iter().find(|tx| tx.txid() == txid))
+      {
         Some(tx) => Ok(Value::String(hex::encode(serialize(tx)))),
         None => Err(Self::not_found()),
       }
     }
   }
 
+  fn get_tx_out(
+    &self,
+    txid: Txid,
+    vout: u32,
+    include_mempool: Option<bool>,
+  ) -> Result<Option<GetTxOutResult>, jsonrpc_core::Error> {
+    let state = self.state();
+    let include_mempool = include_mempool.unwrap_or(true);
+
+    let confirmed = state.hashes.iter().enumerate().flat_map(|(height, hash)| {
+      state.blocks[hash]
+        .txdata
+        .iter()
+        .enumerate()
+        .map(move |(i, tx)| (tx, Some((height, i == 0))))
+    });
+    let unconfirmed = state
+      .mempool
+      .iter()
+      .filter(|_| include_mempool)
+      .map(|tx| (tx, None));
+    let mut transactions = confirmed.chain(unconfirmed);
+
+    let outpoint = OutPoint { txid, vout };
+    let Some((tx, confirmation)) = transactions.clone().find(|(tx, _)| tx.txid() == txid) else {
+      return Ok(None);
+    };
+    let Some(output) = tx.output.get(usize::try_from(vout).unwrap()) else {
+      return Ok(None);
+    };
+    if transactions.any(|(tx, _)| {
+      tx.input
+        .iter()
+        .any(|input| input.previous_output == outpoint)
+    }) {
+      return Ok(None);
+    }
+
+    Ok(Some(GetTxOutResult {
+      bestblock: *state.hashes.last().unwrap(),
+      confirmations: confirmation
+        .map(|(height, _)| u32::try_from(state.hashes.len() - height).unwrap())
+        .unwrap_or_default(),
+      value: Amount::from_sat(output.value),
+      script_pub_key: GetRawTransactionResultVoutScriptPubKey {
+        asm: output.script_pubkey.asm(),
+        hex: output.script_pubkey.to_bytes(),
+        req_sigs: None,
+        type_: None,
+        addresses: Vec::new(),
+        address: None,
+      },
+      coinbase: confirmation.is_some_and(|(_, coinbase)| coinbase),
+    }))
+  }
+
   fn list_unspent(
     &self,
     minconf: Option<usize>,
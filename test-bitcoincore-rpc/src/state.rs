@@ -163,7 +163,11 @@ impl State {
             .get(i)
             .cloned()
             .unwrap_or(value_per_output),
-          script_pubkey: script::Builder::new().into_script(),
+          script_pubkey: template
+            .recipient
+            .as_ref()
+            .map(Address::script_pubkey)
+            .unwrap_or_else(|| script::Builder::new().into_script()),
         })
         .collect(),
     };
@@ -16,6 +16,7 @@ fn json_with_satoshi_index() {
   "metadata_bytes": \d+,
   "outputs_traversed": 1,
   "page_size": \d+,
+  "partially_parsed_inscriptions": 0,
   "sat_ranges": 1,
   "stored_bytes": \d+,
   "transactions": \[
@@ -48,6 +49,7 @@ fn json_without_satoshi_index() {
   "metadata_bytes": \d+,
   "outputs_traversed": 0,
   "page_size": \d+,
+  "partially_parsed_inscriptions": 0,
   "sat_ranges": 0,
   "stored_bytes": \d+,
   "transactions": \[
@@ -1,6 +1,7 @@
 use super::*;
 
 mod balance;
+mod broadcast;
 mod cardinals;
 mod create;
 mod inscribe;
@@ -0,0 +1,66 @@
+use {
+  super::*,
+  bitcoin::{consensus::encode::serialize_hex, PackedLockTime, Script, Transaction, TxIn, TxOut},
+};
+
+fn spend(outpoint: OutPoint, value: u64) -> Transaction {
+  Transaction {
+    version: 1,
+    lock_time: PackedLockTime::ZERO,
+    input: vec![TxIn {
+      previous_output: outpoint,
+      ..Default::default()
+    }],
+    output: vec![TxOut {
+      value,
+      script_pubkey: Script::new(),
+    }],
+  }
+}
+
+#[test]
+fn statuses_are_reported_in_supplied_order() {
+  let rpc_server = test_bitcoincore_rpc::spawn();
+  rpc_server.mine_blocks(1);
+
+  let parent = spend(
+    OutPoint {
+      txid: rpc_server.tx(1, 0).txid(),
+      vout: 0,
+    },
+    49 * COIN_VALUE,
+  );
+  let child = spend(
+    OutPoint {
+      txid: parent.txid(),
+      vout: 0,
+    },
+    48 * COIN_VALUE,
+  );
+
+  let output = CommandBuilder::new(format!(
+    "wallet broadcast --tx {} --tx {}",
+    serialize_hex(&child),
+    serialize_hex(&parent)
+  ))
+  .rpc_server(&rpc_server)
+  .output::<serde_json::Value>();
+
+  assert_eq!(
+    output["order"],
+    serde_json::json!([parent.txid(), child.txid()])
+  );
+  assert_eq!(
+    output["status"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|status| (status["txid"].clone(), status["accepted"].clone()))
+      .collect::<Vec<(serde_json::Value, serde_json::Value)>>(),
+    vec![
+      (serde_json::json!(child.txid()), serde_json::json!(true)),
+      (serde_json::json!(parent.txid()), serde_json::json!(true)),
+    ]
+  );
+  assert_eq!(rpc_server.mempool(), vec![parent, child]);
+}